@@ -1,14 +1,56 @@
 use utoipa::{OpenApi, Modify};
 use crate::models::{
-    user::{User, NewUser, LoginCredentials},
-    travel_plan::{TravelPlan, NewTravelPlan, UpdateTravelPlan},
-    route_option::{RouteOption, NewRouteOption, UpdateRouteOption},
-    point_of_interest::{PointOfInterest, NewPointOfInterest, UpdatePointOfInterest}
+    user::{User, NewUser, LoginCredentials, Role},
+    audit::AuditEntry,
+    travel_plan::{TravelPlan, NewTravelPlan, UpdateTravelPlan, PatchTravelPlan},
+    plan_member::{PlanMember, PlanRole, MemberStatus},
+    share_link::{ShareLink, NewShareLink},
+    route_vote::{RouteVote, VoteDirection},
+    comment::{Comment, CommentTarget, NewComment, UpdateComment},
+    plan_event::{PlanEvent, PlanEventAction},
+    plan_revision::PlanRevision,
+    travel_mode::TravelMode,
+    itinerary::{
+        ItineraryItem, ItineraryItemKind, NewItineraryItem, PatchItineraryItem, ReorderItineraryDay,
+    },
+    budget::{Budget, Expense, ExpenseCategory, NewExpense, PatchExpense, SetBudget},
+    plan_leg::{PlanLeg, NewPlanLeg, PatchPlanLeg, ReorderPlanLegs},
+    route_option::{RouteOption, NewRouteOption, PatchRouteOption},
+    point_of_interest::{PointOfInterest, NewPointOfInterest, PatchPointOfInterest}
 };
 use crate::middleware::auth::{AuthToken, Claims};
 use crate::routes::route_option::ErrorResponse;
 use crate::routes::route_option::GenerateOptionsQuery;
 use crate::routes::auth::{LoginResponse, RegisterResponse};
+use crate::db::backup::BackupInfo;
+use crate::services::backup_service::BackupRun;
+use crate::routes::admin::SetRoleRequest;
+use crate::services::plan_sharing_service::{InviteMember, InvitationDto, PlanMemberDto};
+use crate::services::route_vote_service::{CastVote, RouteTally, SelectRoute, VoteTally};
+use crate::services::travel_plan_service::TravelPlanDto;
+use crate::services::comment_service::{CommentDto, CommentPage};
+use crate::services::activity_service::PlanEventDto;
+use crate::services::plan_revision_service::{RevisionDiff, RevisionHistory};
+use crate::services::itinerary_service::{Itinerary, ItineraryDay};
+use crate::services::route_optimizer::{
+    OptimizeObjective, OptimizeRequest, OptimizedOrder, Solver,
+};
+use crate::services::route_cost::RouteCost;
+use crate::services::route_comparison::{
+    ComparedRoute, ComparisonMetric, RouteComparison, RouteOverlap,
+};
+use crate::services::route_option_service::RouteSort;
+use crate::services::budget_service::{
+    BudgetSummary, CategorySummary, MemberBalance, Settlement,
+};
+use crate::services::plan_leg_service::{LegSummary, PlanSummary};
+use crate::services::emissions_service::{EmissionsReport, ModeEmissions, PlanEmissions};
+use crate::services::itinerary_scheduler::{
+    ScheduleProposal, ScheduleRequest, ScheduledDay, UnscheduledPoi,
+};
+use crate::services::share_link_service::{
+    SharedPointOfInterest, SharedRouteOption, SharedTravelPlanDto,
+};
 
 pub struct SecurityAddon;
 
@@ -38,24 +80,119 @@ impl Modify for SecurityAddon {
         crate::routes::travel_plan::create_travel_plan,
         crate::routes::travel_plan::get_travel_plan_by_id,
         crate::routes::travel_plan::update_travel_plan,
+        crate::routes::travel_plan::patch_travel_plan,
         crate::routes::travel_plan::delete_travel_plan,
+        crate::routes::activity::get_plan_activity,
+        crate::routes::plan_revision::get_revisions,
+        crate::routes::plan_revision::get_revision,
+        crate::routes::plan_revision::diff_revisions,
+        crate::routes::plan_revision::restore_revision,
         
         crate::routes::route_option::get_route_options,
         crate::routes::route_option::generate_route_options,
+        crate::routes::route_option::compare_route_options,
         crate::routes::route_option::get_route_option_by_id,
+        crate::routes::route_option::patch_route_option,
+        crate::routes::route_option::patch_point_of_interest,
+        crate::routes::route_option::optimize_poi_order,
         crate::routes::route_option::delete_route_option,
-        crate::routes::route_option::delete_all_route_options
+        crate::routes::route_option::delete_all_route_options,
+        crate::routes::route_vote::cast_vote,
+        crate::routes::route_vote::retract_vote,
+        crate::routes::route_vote::get_vote_tally,
+        crate::routes::route_vote::select_route,
+        
+        crate::routes::plan_member::get_members,
+        crate::routes::plan_member::invite_member,
+        crate::routes::plan_member::accept_invitation,
+        crate::routes::plan_member::remove_member,
+        crate::routes::plan_member::get_invitations,
+        crate::routes::share_link::create_share_link,
+        crate::routes::share_link::get_share_links,
+        crate::routes::share_link::revoke_share_link,
+        crate::routes::share_link::get_shared_plan,
+        
+        crate::routes::comment::get_plan_comments,
+        crate::routes::comment::create_plan_comment,
+        crate::routes::comment::get_route_comments,
+        crate::routes::comment::create_route_comment,
+        crate::routes::comment::get_poi_comments,
+        crate::routes::comment::create_poi_comment,
+        crate::routes::comment::update_comment,
+        crate::routes::comment::delete_comment,
+
+        crate::routes::itinerary::get_itinerary,
+        crate::routes::itinerary::add_itinerary_item,
+        crate::routes::itinerary::get_itinerary_item,
+        crate::routes::itinerary::patch_itinerary_item,
+        crate::routes::itinerary::delete_itinerary_item,
+        crate::routes::itinerary::reorder_itinerary_day,
+        crate::routes::itinerary::schedule_itinerary,
+
+        crate::routes::plan_leg::get_legs,
+        crate::routes::plan_leg::add_leg,
+        crate::routes::plan_leg::get_leg,
+        crate::routes::plan_leg::patch_leg,
+        crate::routes::plan_leg::delete_leg,
+        crate::routes::plan_leg::reorder_legs,
+        crate::routes::plan_leg::get_plan_summary,
+        crate::routes::route_option::get_leg_route_options,
+        crate::routes::route_option::generate_leg_route_options,
+
+        crate::routes::budget::get_budgets,
+        crate::routes::budget::set_budget,
+        crate::routes::budget::delete_budget,
+        crate::routes::budget::get_expenses,
+        crate::routes::budget::add_expense,
+        crate::routes::budget::get_expense,
+        crate::routes::budget::patch_expense,
+        crate::routes::budget::delete_expense,
+        crate::routes::budget::get_budget_summary,
+
+        crate::routes::emissions::get_emissions_report,
+        
+        crate::routes::admin::create_backup,
+        crate::routes::admin::list_backups,
+        crate::routes::admin::list_users,
+        crate::routes::admin::lock_user,
+        crate::routes::admin::unlock_user,
+        crate::routes::admin::set_user_role,
+        crate::routes::admin::get_audit_log
     ),
     components(
         schemas(
-            User, NewUser, LoginCredentials, AuthToken, Claims,
+            User, NewUser, LoginCredentials, Role, AuthToken, Claims,
             LoginResponse, RegisterResponse,
             
-            TravelPlan, NewTravelPlan, UpdateTravelPlan,
+            TravelPlan, NewTravelPlan, UpdateTravelPlan, PatchTravelPlan, TravelPlanDto,
+            PlanEvent, PlanEventAction, PlanEventDto,
+            PlanRevision, RevisionHistory, RevisionDiff,
+            
+            PlanMember, PlanRole, MemberStatus, PlanMemberDto, InvitationDto, InviteMember,
+            ShareLink, NewShareLink, SharedTravelPlanDto, SharedRouteOption, SharedPointOfInterest,
+            
+            RouteOption, NewRouteOption, PatchRouteOption, GenerateOptionsQuery,
+            RouteCost, RouteSort,
+            RouteComparison, ComparedRoute, ComparisonMetric, RouteOverlap,
+            RouteVote, VoteDirection, CastVote, SelectRoute, RouteTally, VoteTally,
+            
+            PointOfInterest, NewPointOfInterest, PatchPointOfInterest,
+            OptimizeRequest, OptimizeObjective, OptimizedOrder, Solver,
             
-            RouteOption, NewRouteOption, UpdateRouteOption, GenerateOptionsQuery,
+            Comment, CommentTarget, NewComment, UpdateComment, CommentDto, CommentPage,
+
+            Itinerary, ItineraryDay, ItineraryItem, ItineraryItemKind, NewItineraryItem,
+            PatchItineraryItem, ReorderItineraryDay,
+            ScheduleRequest, ScheduleProposal, ScheduledDay, UnscheduledPoi, TravelMode,
+
+            PlanLeg, NewPlanLeg, PatchPlanLeg, ReorderPlanLegs, PlanSummary, LegSummary,
+
+            Budget, SetBudget, Expense, NewExpense, PatchExpense, ExpenseCategory,
+            BudgetSummary, CategorySummary, MemberBalance, Settlement,
+
+            EmissionsReport, ModeEmissions, PlanEmissions,
             
-            PointOfInterest, NewPointOfInterest, UpdatePointOfInterest,
+            BackupInfo, BackupRun, AuditEntry, SetRoleRequest,
             
             ErrorResponse
         )
@@ -66,7 +203,14 @@ impl Modify for SecurityAddon {
     tags(
         (name = "auth", description = "Authentication endpoints"),
         (name = "travel_plans", description = "Travel plan management endpoints"),
-        (name = "route_options", description = "Route options management endpoints")
+        (name = "route_options", description = "Route options management endpoints"),
+        (name = "comments", description = "Comment threads on travel plans, route options and points of interest"),
+        (name = "itinerary", description = "Day-by-day itineraries of travel plans"),
+        (name = "legs", description = "Multi-leg travel plans and their summaries"),
+        (name = "budgets", description = "Budgets, expenses and settling up between members"),
+        (name = "emissions", description = "CO2e estimates of routes and yearly reports"),
+        (name = "sharing", description = "Sharing travel plans with other users and through public links"),
+        (name = "admin", description = "Account management, audit log and backups; admin role only")
     ),
     info(
         title = "Travel API",
@@ -0,0 +1,70 @@
+use std::sync::Arc;
+
+use actix_web::web;
+
+use crate::db::connection::DbError;
+use crate::repositories::{self, Repositories};
+use crate::routes;
+use crate::services::backup_service::BackupConfig;
+use crate::services::currency::ExchangeRates;
+use crate::services::emissions::EmissionFactors;
+use crate::services::route_cost::CostModel;
+
+/// Shared state the travel endpoints need, built once at startup.
+#[derive(Clone)]
+pub struct AppState {
+    pub repositories: Arc<dyn Repositories>,
+    /// Where backups go; `None` when backups are not configured.
+    pub backups: Option<BackupConfig>,
+    /// Reference rates for converting budgets to a plan's home currency.
+    pub exchange_rates: Arc<ExchangeRates>,
+    /// Prices for estimating what route options cost.
+    pub route_costs: Arc<CostModel>,
+    /// Grams of CO2e per kilometre for each travel mode.
+    pub emission_factors: Arc<EmissionFactors>,
+}
+
+impl AppState {
+    pub fn new(repositories: Arc<dyn Repositories>) -> Self {
+        AppState {
+            repositories,
+            backups: None,
+            exchange_rates: Arc::new(ExchangeRates::default()),
+            route_costs: Arc::new(CostModel::default()),
+            emission_factors: Arc::new(EmissionFactors::default()),
+        }
+    }
+
+    /// Opens the storage backend named by `database_url`. Blocks, so call it
+    /// outside the async executor.
+    pub fn connect(database_url: &str) -> Result<Self, DbError> {
+        Ok(AppState::new(repositories::connect(database_url)?))
+    }
+
+    pub fn with_backups(mut self, backups: Option<BackupConfig>) -> Self {
+        self.backups = backups;
+        self
+    }
+
+    pub fn with_exchange_rates(mut self, exchange_rates: ExchangeRates) -> Self {
+        self.exchange_rates = Arc::new(exchange_rates);
+        self
+    }
+
+    pub fn with_route_costs(mut self, route_costs: CostModel) -> Self {
+        self.route_costs = Arc::new(route_costs);
+        self
+    }
+
+    pub fn with_emission_factors(mut self, emission_factors: EmissionFactors) -> Self {
+        self.emission_factors = Arc::new(emission_factors);
+        self
+    }
+}
+
+/// Registers the app data and `/api` routes on an actix `App` or scope.
+pub fn configure_app(cfg: &mut web::ServiceConfig, state: &AppState) {
+    cfg.app_data(web::Data::from(state.repositories.clone()))
+        .app_data(web::Data::new(state.clone()))
+        .configure(routes::configure);
+}
@@ -0,0 +1,338 @@
+//! Simple HTTP load generator for the travel API.
+//!
+//! Start the server, then run:
+//!
+//! ```text
+//! cargo run --release --bin load-test -- --url http://127.0.0.1:8080 --concurrency 32 --duration 10 --scenario mixed
+//! ```
+//!
+//! The `mixed` scenario interleaves bcrypt-heavy logins with cheap reads, which
+//! is where blocking work on the async executor shows up as collapsed read
+//! throughput and long tail latencies. Run it against two builds to compare
+//! them before and after a change.
+
+use serde_json::{Value, json};
+use std::collections::BTreeMap;
+use std::io::{Read, Write};
+use std::net::TcpStream;
+use std::process;
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+use uuid::Uuid;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Scenario {
+    Read,
+    Login,
+    Mixed,
+}
+
+#[derive(Debug, Clone)]
+struct Config {
+    host: String,
+    concurrency: usize,
+    duration: Duration,
+    scenario: Scenario,
+}
+
+struct Session {
+    username: String,
+    password: String,
+    token: String,
+    plan_id: String,
+}
+
+struct Sample {
+    label: &'static str,
+    latency: Duration,
+    ok: bool,
+}
+
+fn usage() -> ! {
+    eprintln!(
+        "Usage: load-test [--url http://127.0.0.1:8080] [--concurrency N] [--duration SECS] [--scenario read|login|mixed]"
+    );
+    process::exit(2);
+}
+
+fn parse_args() -> Config {
+    let mut config = Config {
+        host: "127.0.0.1:8080".to_string(),
+        concurrency: 32,
+        duration: Duration::from_secs(10),
+        scenario: Scenario::Mixed,
+    };
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let value = args.next().unwrap_or_else(|| usage());
+        match arg.as_str() {
+            "--url" => {
+                config.host = value
+                    .trim_start_matches("http://")
+                    .trim_end_matches('/')
+                    .to_string();
+            }
+            "--concurrency" => {
+                config.concurrency = value.parse().unwrap_or_else(|_| usage());
+            }
+            "--duration" => {
+                config.duration = Duration::from_secs(value.parse().unwrap_or_else(|_| usage()));
+            }
+            "--scenario" => {
+                config.scenario = match value.as_str() {
+                    "read" => Scenario::Read,
+                    "login" => Scenario::Login,
+                    "mixed" => Scenario::Mixed,
+                    _ => usage(),
+                };
+            }
+            _ => usage(),
+        }
+    }
+
+    config
+}
+
+/// Sends a single HTTP/1.1 request and returns the status code and body.
+fn send(
+    host: &str,
+    method: &str,
+    path: &str,
+    token: Option<&str>,
+    body: Option<&Value>,
+) -> std::io::Result<(u16, String)> {
+    let mut stream = TcpStream::connect(host)?;
+    stream.set_read_timeout(Some(Duration::from_secs(60)))?;
+
+    let body = body.map(|b| b.to_string()).unwrap_or_default();
+    let mut request = format!(
+        "{} {} HTTP/1.1\r\nHost: {}\r\nConnection: close\r\nContent-Type: application/json\r\nContent-Length: {}\r\n",
+        method,
+        path,
+        host,
+        body.len()
+    );
+    if let Some(token) = token {
+        request.push_str(&format!("Authorization: Bearer {}\r\n", token));
+    }
+    request.push_str("\r\n");
+    request.push_str(&body);
+
+    stream.write_all(request.as_bytes())?;
+
+    let mut response = String::new();
+    stream.read_to_string(&mut response)?;
+
+    let status = response
+        .split_whitespace()
+        .nth(1)
+        .and_then(|code| code.parse().ok())
+        .unwrap_or(0);
+    let body = response
+        .split_once("\r\n\r\n")
+        .map(|(_, body)| body.to_string())
+        .unwrap_or_default();
+
+    Ok((status, body))
+}
+
+fn expect_json(
+    host: &str,
+    method: &str,
+    path: &str,
+    token: Option<&str>,
+    body: Option<&Value>,
+) -> Value {
+    match send(host, method, path, token, body) {
+        Ok((status, body)) if (200..300).contains(&status) => {
+            serde_json::from_str(&body).unwrap_or(Value::Null)
+        }
+        Ok((status, body)) => {
+            eprintln!("Setup request {} {} failed with {}: {}", method, path, status, body);
+            process::exit(1);
+        }
+        Err(e) => {
+            eprintln!("Setup request {} {} failed: {}", method, path, e);
+            process::exit(1);
+        }
+    }
+}
+
+fn set_up(host: &str) -> Session {
+    let username = format!("loadtest-{}", Uuid::new_v4());
+    let password = "load-test-password".to_string();
+
+    expect_json(
+        host,
+        "POST",
+        "/api/register",
+        None,
+        Some(&json!({
+            "username": username,
+            "password": password,
+            "email": format!("{}@example.com", username),
+        })),
+    );
+
+    let login = expect_json(
+        host,
+        "POST",
+        "/api/login",
+        None,
+        Some(&json!({ "username": username, "password": password })),
+    );
+    let token = login["token"].as_str().unwrap_or_default().to_string();
+
+    let plan = expect_json(
+        host,
+        "POST",
+        "/api/travelplan",
+        Some(&token),
+        Some(&json!({
+            "name": "Load test plan",
+            "description": "Created by the load-test binary",
+            "startLocation": "Amsterdam",
+            "endLocation": "Berlin",
+        })),
+    );
+    let plan_id = plan["id"].as_str().unwrap_or_default().to_string();
+
+    expect_json(
+        host,
+        "POST",
+        &format!("/api/travelplan/{}/routes/generate?count=3", plan_id),
+        Some(&token),
+        None,
+    );
+
+    Session {
+        username,
+        password,
+        token,
+        plan_id,
+    }
+}
+
+fn run_worker(config: &Config, session: &Session, worker: usize) -> Vec<Sample> {
+    let deadline = Instant::now() + config.duration;
+    let mut samples = Vec::new();
+    let mut iteration = worker;
+
+    while Instant::now() < deadline {
+        let do_login = match config.scenario {
+            Scenario::Login => true,
+            Scenario::Read => false,
+            Scenario::Mixed => iteration.is_multiple_of(4),
+        };
+
+        let started = Instant::now();
+        let (label, result) = if do_login {
+            let credentials = json!({ "username": session.username, "password": session.password });
+            (
+                "POST /api/login",
+                send(&config.host, "POST", "/api/login", None, Some(&credentials)),
+            )
+        } else if iteration.is_multiple_of(2) {
+            (
+                "GET /api/travelplan",
+                send(&config.host, "GET", "/api/travelplan", Some(&session.token), None),
+            )
+        } else {
+            (
+                "GET /api/travelplan/{id}/routes",
+                send(
+                    &config.host,
+                    "GET",
+                    &format!("/api/travelplan/{}/routes", session.plan_id),
+                    Some(&session.token),
+                    None,
+                ),
+            )
+        };
+
+        samples.push(Sample {
+            label,
+            latency: started.elapsed(),
+            ok: matches!(result, Ok((status, _)) if (200..300).contains(&status)),
+        });
+        iteration += 1;
+    }
+
+    samples
+}
+
+fn percentile(sorted: &[Duration], p: f64) -> Duration {
+    if sorted.is_empty() {
+        return Duration::ZERO;
+    }
+    let idx = ((sorted.len() - 1) as f64 * p).round() as usize;
+    sorted[idx]
+}
+
+fn report(config: &Config, samples: Vec<Sample>, elapsed: Duration) {
+    let mut by_label: BTreeMap<&'static str, (Vec<Duration>, usize)> = BTreeMap::new();
+    for sample in &samples {
+        let entry = by_label.entry(sample.label).or_default();
+        entry.0.push(sample.latency);
+        if !sample.ok {
+            entry.1 += 1;
+        }
+    }
+
+    let secs = elapsed.as_secs_f64();
+    println!(
+        "scenario={:?} concurrency={} duration={:.1}s",
+        config.scenario, config.concurrency, secs
+    );
+    println!(
+        "{:<34} {:>8} {:>7} {:>9} {:>9} {:>9} {:>9}",
+        "endpoint", "requests", "errors", "req/s", "p50 ms", "p95 ms", "p99 ms"
+    );
+
+    for (label, (mut latencies, errors)) in by_label {
+        latencies.sort();
+        println!(
+            "{:<34} {:>8} {:>7} {:>9.1} {:>9.1} {:>9.1} {:>9.1}",
+            label,
+            latencies.len(),
+            errors,
+            latencies.len() as f64 / secs,
+            percentile(&latencies, 0.50).as_secs_f64() * 1000.0,
+            percentile(&latencies, 0.95).as_secs_f64() * 1000.0,
+            percentile(&latencies, 0.99).as_secs_f64() * 1000.0,
+        );
+    }
+
+    let errors = samples.iter().filter(|s| !s.ok).count();
+    println!(
+        "total: {} requests, {} errors, {:.1} req/s",
+        samples.len(),
+        errors,
+        samples.len() as f64 / secs
+    );
+}
+
+fn main() {
+    let config = Arc::new(parse_args());
+
+    println!("Preparing load test user and travel plan on {}", config.host);
+    let session = Arc::new(set_up(&config.host));
+
+    let started = Instant::now();
+    let handles: Vec<_> = (0..config.concurrency)
+        .map(|worker| {
+            let config = Arc::clone(&config);
+            let session = Arc::clone(&session);
+            thread::spawn(move || run_worker(&config, &session, worker))
+        })
+        .collect();
+
+    let samples = handles
+        .into_iter()
+        .flat_map(|handle| handle.join().unwrap_or_default())
+        .collect();
+
+    report(&config, samples, started.elapsed());
+}
@@ -0,0 +1,411 @@
+//! Operator CLI for the travel API database.
+//!
+//! ```text
+//! cargo run --bin travel-admin -- counts
+//! cargo run --bin travel-admin -- --json list-plans alice
+//! echo 's3cret' | cargo run --bin travel-admin -- reset-password alice
+//! cargo run --bin travel-admin -- --dir backups restore --at 2030-06-01T12:00:00Z
+//! ```
+//!
+//! The database comes from `--database`, then `DATABASE_URL`, then
+//! `travel_api.db`, exactly like the server. Opening it applies any pending
+//! migrations.
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use serde_json::json;
+use std::io::BufRead;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::Arc;
+
+use travel_api::db::backup::{self, BackupInfo, RetentionPolicy};
+use travel_api::db::connection;
+use travel_api::models::audit::Actor;
+use travel_api::models::user::{NewUser, Role, User};
+use travel_api::repositories::{self, Repositories};
+use travel_api::services::admin_service::{AdminError, AdminService};
+use travel_api::services::backup_service::{BackupConfig, BackupService, DEFAULT_BACKUP_KEEP};
+
+const USAGE: &str = "Usage: travel-admin [--database URL] [--json] <command>
+
+Commands:
+  migrate                                   Apply pending migrations and print the schema version
+  create-user <username> <email> [--password P]
+  disable-user <username>
+  enable-user <username>
+  set-role <username> <user|support|admin>
+  reset-password <username> [--password P]
+  list-plans <username>
+  purge-orphans                             Delete plans, routes and POIs whose parent is gone
+  vacuum
+  counts                                    Print row counts per table
+  audit-log [--limit N]                     Print the newest audit entries (default 50)
+  backup [--dir D]                          Take an online backup and apply retention
+  list-backups [--dir D]
+  restore <file> | restore --at <RFC 3339 time> [--dir D]
+                                            Verify a backup and swap it in (SQLite only,
+                                            stop the server first)
+
+Passwords not given with --password are read from the first line of stdin.
+The backup directory defaults to BACKUP_DIR.";
+
+struct Config {
+    database_url: String,
+    json: bool,
+    password: Option<String>,
+    backup_dir: Option<PathBuf>,
+    restore_at: Option<String>,
+    limit: usize,
+    command: Vec<String>,
+}
+
+fn usage() -> ! {
+    eprintln!("{}", USAGE);
+    process::exit(2);
+}
+
+fn parse_args() -> Config {
+    let mut config = Config {
+        database_url: connection::database_url(),
+        json: false,
+        password: None,
+        backup_dir: std::env::var("BACKUP_DIR").ok().map(PathBuf::from),
+        restore_at: None,
+        limit: 50,
+        command: Vec::new(),
+    };
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--database" => config.database_url = args.next().unwrap_or_else(|| usage()),
+            "--password" => config.password = Some(args.next().unwrap_or_else(|| usage())),
+            "--dir" => config.backup_dir = Some(args.next().unwrap_or_else(|| usage()).into()),
+            "--at" => config.restore_at = Some(args.next().unwrap_or_else(|| usage())),
+            "--limit" => {
+                config.limit = args
+                    .next()
+                    .and_then(|limit| limit.parse().ok())
+                    .unwrap_or_else(|| usage())
+            }
+            "--json" => config.json = true,
+            "-h" | "--help" => usage(),
+            _ if arg.starts_with("--") => usage(),
+            _ => config.command.push(arg),
+        }
+    }
+
+    if config.command.is_empty() {
+        usage();
+    }
+    config
+}
+
+fn read_password(config: &Config) -> String {
+    if let Some(password) = &config.password {
+        return password.clone();
+    }
+
+    let mut line = String::new();
+    if let Err(e) = std::io::stdin().lock().read_line(&mut line) {
+        fail(config.json, &format!("Could not read password from stdin: {}", e));
+    }
+    line.trim_end_matches(['\r', '\n']).to_string()
+}
+
+fn fail(json: bool, message: &str) -> ! {
+    if json {
+        println!("{}", json!({ "error": message }));
+    } else {
+        eprintln!("error: {}", message);
+    }
+    process::exit(1);
+}
+
+fn print_json<T: Serialize>(value: &T) {
+    match serde_json::to_string_pretty(value) {
+        Ok(output) => println!("{}", output),
+        Err(e) => fail(true, &e.to_string()),
+    }
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct UserSummary<'a> {
+    id: &'a str,
+    username: &'a str,
+    email: &'a str,
+    disabled: bool,
+    role: Role,
+}
+
+impl<'a> From<&'a User> for UserSummary<'a> {
+    fn from(user: &'a User) -> Self {
+        UserSummary {
+            id: &user.id,
+            username: &user.username,
+            email: &user.email,
+            disabled: user.disabled,
+            role: user.role,
+        }
+    }
+}
+
+fn print_user(json: bool, user: &User, action: &str) {
+    if json {
+        print_json(&UserSummary::from(user));
+    } else {
+        println!("{} user {} ({})", action, user.username, user.id);
+    }
+}
+
+fn backup_dir(config: &Config) -> &Path {
+    config
+        .backup_dir
+        .as_deref()
+        .unwrap_or_else(|| fail(config.json, "No backup directory: pass --dir or set BACKUP_DIR"))
+}
+
+fn print_backups(json: bool, backups: &[BackupInfo]) {
+    if json {
+        print_json(&backups);
+    } else if backups.is_empty() {
+        println!("No backups found");
+    } else {
+        for backup in backups {
+            println!("{}  {:>12} bytes", backup.file_name, backup.size_bytes);
+        }
+    }
+}
+
+/// Restores work on the database file directly, without opening a pool on
+/// it, so they run before the normal connection setup.
+fn restore(config: &Config, target: Option<&str>) {
+    let json = config.json;
+    if connection::is_postgres_url(&config.database_url) {
+        fail(json, "restore only supports SQLite databases; use pg_restore for PostgreSQL");
+    }
+
+    let backup_path = match (target, &config.restore_at) {
+        (Some(file), None) => {
+            let path = PathBuf::from(file);
+            match &config.backup_dir {
+                Some(dir) if !path.exists() => dir.join(path),
+                _ => path,
+            }
+        }
+        (None, Some(at)) => {
+            let at = DateTime::parse_from_rfc3339(at)
+                .unwrap_or_else(|e| fail(json, &format!("Invalid --at time: {}", e)))
+                .with_timezone(&Utc);
+            match backup::find_backup_at(backup_dir(config), at) {
+                Ok(Some(found)) => found.path,
+                Ok(None) => fail(json, &format!("No backup taken at or before {}", at)),
+                Err(e) => fail(json, &e.to_string()),
+            }
+        }
+        _ => usage(),
+    };
+
+    let version = backup::verify_backup(&backup_path).unwrap_or_else(|e| fail(json, &e.to_string()));
+    let previous = backup::restore_backup(&backup_path, Path::new(&config.database_url))
+        .unwrap_or_else(|e| fail(json, &e.to_string()));
+
+    if json {
+        print_json(&json!({
+            "restored": backup_path.display().to_string(),
+            "schemaVersion": version,
+            "previous": previous.map(|p| p.display().to_string()),
+        }));
+    } else {
+        println!(
+            "Restored {} from {} (schema version {})",
+            config.database_url,
+            backup_path.display(),
+            version
+        );
+        if let Some(previous) = previous {
+            println!("Previous database saved as {}", previous.display());
+        }
+    }
+}
+
+fn run_backup_command(config: &Config, repos: Arc<dyn Repositories>) {
+    let json = config.json;
+    // Retention follows the server's settings so manual and scheduled
+    // backups share one rotation.
+    let retention = BackupConfig::from_env()
+        .map(|env| env.retention)
+        .unwrap_or(RetentionPolicy {
+            keep_last: DEFAULT_BACKUP_KEEP,
+            max_age: None,
+        });
+    let service = BackupService::new(
+        repos,
+        BackupConfig {
+            dir: backup_dir(config).to_path_buf(),
+            interval: None,
+            retention,
+        },
+    );
+
+    match config.command[0].as_str() {
+        "backup" => {
+            let run = service
+                .run_backup(&Actor::system("travel-admin"))
+                .unwrap_or_else(|e| fail(json, &e.to_string()));
+            if json {
+                print_json(&run);
+            } else {
+                println!("Created backup {}", run.backup.path.display());
+                for removed in &run.removed {
+                    println!("Removed expired backup {}", removed.file_name);
+                }
+            }
+        }
+        "list-backups" => {
+            let backups = service.list_backups().unwrap_or_else(|e| fail(json, &e.to_string()));
+            print_backups(json, &backups);
+        }
+        _ => usage(),
+    }
+}
+
+fn run(config: &Config, service: &AdminService<dyn Repositories>) -> Result<(), AdminError> {
+    let json = config.json;
+    let args: Vec<&str> = config.command.iter().map(String::as_str).collect();
+
+    match args.as_slice() {
+        ["migrate"] => {
+            let version = service.schema_version()?;
+            if json {
+                print_json(&json!({ "schemaVersion": version }));
+            } else {
+                println!("Database is at schema version {}", version);
+            }
+        }
+        ["create-user", username, email] => {
+            let user = service.create_user(&NewUser {
+                username: username.to_string(),
+                email: email.to_string(),
+                password: read_password(config),
+            })?;
+            print_user(json, &user, "Created");
+        }
+        ["disable-user", username] => {
+            let user = service.set_user_disabled(username, true)?;
+            print_user(json, &user, "Disabled");
+        }
+        ["enable-user", username] => {
+            let user = service.set_user_disabled(username, false)?;
+            print_user(json, &user, "Enabled");
+        }
+        ["set-role", username, role] => {
+            let role: Role = role.parse().map_err(AdminError::InvalidInput)?;
+            let user = service.set_user_role(username, role)?;
+            print_user(json, &user, &format!("Made {}", role));
+        }
+        ["reset-password", username] => {
+            let user = service.reset_password(username, &read_password(config))?;
+            print_user(json, &user, "Reset password for");
+        }
+        ["list-plans", username] => {
+            let plans = service.list_user_plans(username)?;
+            if json {
+                print_json(&plans);
+            } else if plans.is_empty() {
+                println!("{} has no travel plans", username);
+            } else {
+                for plan in &plans {
+                    println!(
+                        "{}  {}  {} -> {}  (created {})",
+                        plan.id,
+                        plan.name,
+                        plan.start_location,
+                        plan.end_location,
+                        plan.created_at.format("%Y-%m-%d")
+                    );
+                }
+            }
+        }
+        ["purge-orphans"] => {
+            let report = service.purge_orphans()?;
+            if json {
+                print_json(&report);
+            } else {
+                println!(
+                    "Removed {} travel plans, {} route options, {} points of interest",
+                    report.travel_plans, report.route_options, report.points_of_interest
+                );
+            }
+        }
+        ["vacuum"] => {
+            service.vacuum()?;
+            if json {
+                print_json(&json!({ "vacuumed": true }));
+            } else {
+                println!("Vacuumed database");
+            }
+        }
+        ["counts"] => {
+            let counts = service.count_rows()?;
+            if json {
+                print_json(&counts);
+            } else {
+                for count in &counts {
+                    println!("{:<20} {:>10}", count.table, count.rows);
+                }
+            }
+        }
+        ["audit-log"] => {
+            let entries = service.audit_log(config.limit, 0)?;
+            if json {
+                print_json(&entries);
+            } else {
+                for entry in &entries {
+                    println!(
+                        "{}  {:<16} {:<26} {}/{}  {}",
+                        entry.created_at.format("%Y-%m-%d %H:%M:%S"),
+                        entry.actor_username,
+                        entry.action,
+                        entry.target_type,
+                        entry.target_id.as_deref().unwrap_or("-"),
+                        entry.details.as_deref().unwrap_or("")
+                    );
+                }
+            }
+        }
+        _ => usage(),
+    }
+
+    Ok(())
+}
+
+fn main() {
+    dotenv::dotenv().ok();
+    env_logger::init_from_env(env_logger::Env::default().default_filter_or("warn"));
+
+    let config = parse_args();
+
+    match config.command.iter().map(String::as_str).collect::<Vec<_>>().as_slice() {
+        ["restore"] => return restore(&config, None),
+        ["restore", file] => return restore(&config, Some(file)),
+        _ => {}
+    }
+
+    let repos: Arc<dyn Repositories> = match repositories::connect(&config.database_url) {
+        Ok(repos) => repos,
+        Err(e) => fail(config.json, &e.to_string()),
+    };
+
+    if matches!(config.command.as_slice(), [command] if command == "backup" || command == "list-backups") {
+        return run_backup_command(&config, repos);
+    }
+
+    let service = AdminService::new(repos);
+
+    if let Err(e) = run(&config, &service) {
+        fail(config.json, &e.to_string());
+    }
+}
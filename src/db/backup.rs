@@ -0,0 +1,256 @@
+use chrono::{DateTime, NaiveDateTime, Utc};
+use log::{info, warn};
+use rusqlite::backup::Backup;
+use rusqlite::{Connection, OpenFlags};
+use serde::Serialize;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use utoipa::ToSchema;
+
+use crate::db::schema::{self, SCHEMA_VERSION};
+
+const FILE_PREFIX: &str = "travel_api-";
+const FILE_SUFFIX: &str = ".db";
+const TIMESTAMP_FORMAT: &str = "%Y%m%dT%H%M%S%3fZ";
+
+/// Pages copied per backup step. Writers can take the lock between steps, so
+/// a snapshot never stalls the API for long.
+const PAGES_PER_STEP: std::ffi::c_int = 256;
+const STEP_PAUSE: Duration = Duration::from_millis(10);
+
+#[derive(Debug)]
+pub enum BackupError {
+    Io(std::io::Error),
+    Sqlite(rusqlite::Error),
+    Verification(String),
+}
+
+impl fmt::Display for BackupError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BackupError::Io(e) => write!(f, "Backup I/O error: {}", e),
+            BackupError::Sqlite(e) => write!(f, "Backup database error: {}", e),
+            BackupError::Verification(e) => write!(f, "Backup verification failed: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for BackupError {}
+
+impl From<std::io::Error> for BackupError {
+    fn from(err: std::io::Error) -> Self {
+        BackupError::Io(err)
+    }
+}
+
+impl From<rusqlite::Error> for BackupError {
+    fn from(err: rusqlite::Error) -> Self {
+        BackupError::Sqlite(err)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct BackupInfo {
+    pub file_name: String,
+    #[serde(skip)]
+    pub path: PathBuf,
+    pub size_bytes: u64,
+    pub created_at: DateTime<Utc>,
+}
+
+impl BackupInfo {
+    fn from_path(path: &Path) -> Option<Self> {
+        let file_name = path.file_name()?.to_str()?.to_string();
+        let timestamp = file_name
+            .strip_prefix(FILE_PREFIX)?
+            .strip_suffix(FILE_SUFFIX)?;
+        let created_at = NaiveDateTime::parse_from_str(timestamp, TIMESTAMP_FORMAT)
+            .ok()?
+            .and_utc();
+        let size_bytes = fs::metadata(path).ok()?.len();
+
+        Some(BackupInfo {
+            file_name,
+            path: path.to_path_buf(),
+            size_bytes,
+            created_at,
+        })
+    }
+}
+
+/// Which snapshots to keep. The newest snapshot always survives.
+#[derive(Debug, Clone)]
+pub struct RetentionPolicy {
+    pub keep_last: usize,
+    pub max_age: Option<chrono::Duration>,
+}
+
+/// Copies the live database into a new timestamped file in `dir` using
+/// SQLite's online backup API, so the API keeps serving while it runs.
+pub fn create_backup(conn: &Connection, dir: &Path) -> Result<BackupInfo, BackupError> {
+    fs::create_dir_all(dir)?;
+
+    let created_at = Utc::now();
+    let file_name = format!(
+        "{}{}{}",
+        FILE_PREFIX,
+        created_at.format(TIMESTAMP_FORMAT),
+        FILE_SUFFIX
+    );
+    let path = dir.join(&file_name);
+    let partial = dir.join(format!("{}.partial", file_name));
+
+    {
+        let mut dst = Connection::open(&partial)?;
+        let backup = Backup::new(conn, &mut dst)?;
+        backup.run_to_completion(PAGES_PER_STEP, STEP_PAUSE, None)?;
+    }
+    fs::rename(&partial, &path)?;
+
+    let info = BackupInfo::from_path(&path).ok_or_else(|| {
+        BackupError::Verification(format!("could not read back {}", path.display()))
+    })?;
+
+    info!("Created database backup {} ({} bytes)", info.file_name, info.size_bytes);
+    Ok(info)
+}
+
+/// Backups in `dir`, newest first. A missing directory has no backups.
+pub fn list_backups(dir: &Path) -> Result<Vec<BackupInfo>, BackupError> {
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+
+    let mut backups = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        if let Some(info) = BackupInfo::from_path(&entry?.path()) {
+            backups.push(info);
+        }
+    }
+    backups.sort_by_key(|b| std::cmp::Reverse(b.created_at));
+    Ok(backups)
+}
+
+/// Deletes backups the policy no longer covers and returns them.
+pub fn apply_retention(
+    dir: &Path,
+    policy: &RetentionPolicy,
+) -> Result<Vec<BackupInfo>, BackupError> {
+    let now = Utc::now();
+    let keep_last = policy.keep_last.max(1);
+    let mut removed = Vec::new();
+
+    for (index, backup) in list_backups(dir)?.into_iter().enumerate() {
+        let too_many = index >= keep_last;
+        let too_old = index > 0
+            && policy
+                .max_age
+                .is_some_and(|max_age| now - backup.created_at > max_age);
+
+        if too_many || too_old {
+            fs::remove_file(&backup.path)?;
+            info!("Removed expired backup {}", backup.file_name);
+            removed.push(backup);
+        }
+    }
+
+    Ok(removed)
+}
+
+/// Newest backup taken at or before `at`.
+pub fn find_backup_at(dir: &Path, at: DateTime<Utc>) -> Result<Option<BackupInfo>, BackupError> {
+    Ok(list_backups(dir)?.into_iter().find(|b| b.created_at <= at))
+}
+
+/// Checks that `path` is an intact travel database this build can migrate,
+/// and returns its schema version.
+pub fn verify_backup(path: &Path) -> Result<usize, BackupError> {
+    if !path.is_file() {
+        return Err(BackupError::Verification(format!(
+            "{} does not exist",
+            path.display()
+        )));
+    }
+
+    let conn = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+
+    let integrity: String = conn.query_row("PRAGMA integrity_check", [], |row| row.get(0))?;
+    if integrity != "ok" {
+        return Err(BackupError::Verification(format!(
+            "integrity check reported: {}",
+            integrity
+        )));
+    }
+
+    let version = schema::schema_version(&conn)?;
+    let has_users: bool = conn.query_row(
+        "SELECT EXISTS (SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = 'users')",
+        [],
+        |row| row.get(0),
+    )?;
+    if !has_users {
+        return Err(BackupError::Verification(
+            "not a travel API database".to_string(),
+        ));
+    }
+    if version > SCHEMA_VERSION {
+        return Err(BackupError::Verification(format!(
+            "schema version {} is newer than this build supports ({})",
+            version, SCHEMA_VERSION
+        )));
+    }
+
+    Ok(version)
+}
+
+/// Replaces the database at `db_path` with a verified copy of `backup_path`.
+/// The current file is kept next to it as `<db>.pre-restore-<timestamp>`.
+/// The server must be stopped while this runs.
+pub fn restore_backup(backup_path: &Path, db_path: &Path) -> Result<Option<PathBuf>, BackupError> {
+    verify_backup(backup_path)?;
+
+    let staged = sibling(db_path, ".restoring");
+    fs::copy(backup_path, &staged)?;
+    if let Err(e) = verify_backup(&staged) {
+        fs::remove_file(&staged)?;
+        return Err(e);
+    }
+
+    let previous = if db_path.exists() {
+        let saved = sibling(
+            db_path,
+            &format!(".pre-restore-{}", Utc::now().format(TIMESTAMP_FORMAT)),
+        );
+        fs::copy(db_path, &saved)?;
+        Some(saved)
+    } else {
+        None
+    };
+
+    fs::rename(&staged, db_path)?;
+
+    // Journals left by the old file must not be replayed into the new one.
+    for suffix in ["-journal", "-wal", "-shm"] {
+        let journal = sibling(db_path, suffix);
+        if journal.exists() {
+            warn!("Removing stale {}", journal.display());
+            fs::remove_file(journal)?;
+        }
+    }
+
+    info!(
+        "Restored {} from {}",
+        db_path.display(),
+        backup_path.display()
+    );
+    Ok(previous)
+}
+
+fn sibling(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(suffix);
+    PathBuf::from(name)
+}
@@ -8,6 +8,9 @@ use std::path::Path;
 pub type DbPool = Pool<SqliteConnectionManager>;
 pub type DbConnection = PooledConnection<SqliteConnectionManager>;
 
+const DEFAULT_POOL_SIZE: u32 = 16;
+const DEFAULT_DATABASE_PATH: &str = "travel_api.db";
+
 #[derive(Debug)]
 pub enum DbError {
     PoolError(r2d2::Error),
@@ -34,14 +37,15 @@ impl From<r2d2::Error> for DbError {
 pub fn create_pool(db_path: &str) -> Result<DbPool, DbError> {
     info!("Creating database connection pool for: {}", db_path);
 
-    let db_exists = Path::new(db_path).exists();
+    if !Path::new(db_path).exists() {
+        info!("Database file does not exist. Creating new database.");
+    }
 
     let manager = SqliteConnectionManager::file(db_path);
 
-    let pool = Pool::new(manager)?;
+    let pool = Pool::builder().max_size(pool_size()).build(manager)?;
 
-    if !db_exists {
-        info!("Database file does not exist. Creating new database.");
+    {
         let conn = pool.get()?;
         if let Err(e) = schema::initialize_database(&conn) {
             error!("Failed to initialize database: {}", e);
@@ -53,20 +57,37 @@ pub fn create_pool(db_path: &str) -> Result<DbPool, DbError> {
     Ok(pool)
 }
 
-pub fn get_pool() -> Result<DbPool, DbError> {
-    let db_path = "travel_api.db";
-    create_pool(db_path)
+/// Maximum number of pooled connections, read from `DB_POOL_SIZE`.
+pub fn pool_size() -> u32 {
+    std::env::var("DB_POOL_SIZE")
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .filter(|size| *size > 0)
+        .unwrap_or(DEFAULT_POOL_SIZE)
+}
+
+/// Database location from `DATABASE_URL`. A `postgres://` or `postgresql://`
+/// URL selects the PostgreSQL backend; anything else is a SQLite file path.
+pub fn database_url() -> String {
+    std::env::var("DATABASE_URL").unwrap_or_else(|_| DEFAULT_DATABASE_PATH.to_string())
+}
+
+pub fn is_postgres_url(url: &str) -> bool {
+    url.starts_with("postgres://") || url.starts_with("postgresql://")
 }
 
 #[cfg(test)]
 pub fn get_test_pool() -> Result<DbPool, DbError> {
+    // Every in-memory connection is its own database, so share a single one.
     let manager = SqliteConnectionManager::memory();
-    let pool = Pool::new(manager)?;
+    let pool = Pool::builder().max_size(1).build(manager)?;
 
-    let conn = pool.get()?;
-    if let Err(e) = schema::initialize_database(&conn) {
-        error!("Failed to initialize test database: {}", e);
-        return Err(DbError::InitError(e.to_string()));
+    {
+        let conn = pool.get()?;
+        if let Err(e) = schema::initialize_database(&conn) {
+            error!("Failed to initialize test database: {}", e);
+            return Err(DbError::InitError(e.to_string()));
+        }
     }
 
     Ok(pool)
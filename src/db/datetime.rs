@@ -4,6 +4,7 @@ use std::ops::{Deref, DerefMut};
 
 // Create a newtype wrapper for DateTime<Utc> to satisfy the orphan rule
 // The orphan rule prevents implementing external traits for external types
+#[allow(dead_code)]
 #[derive(Debug, Clone, PartialEq)]
 pub struct SqlDateTime(pub DateTime<Utc>);
 
@@ -64,6 +65,7 @@ impl ToSql for SqlDateTime {
 }
 
 // Create a newtype wrapper for Option<SqlDateTime> to satisfy the orphan rule
+#[allow(dead_code)]
 #[derive(Debug, Clone, PartialEq)]
 pub struct NullableSqlDateTime(pub Option<SqlDateTime>);
 
@@ -0,0 +1,74 @@
+use log::info;
+use rusqlite::{Connection, Result};
+use serde::Serialize;
+
+/// Tables the admin tooling reports on, in dependency order.
+pub const TABLES: &[&str] = &["users", "travel_plans", "route_options", "points_of_interest"];
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TableCount {
+    pub table: String,
+    pub rows: i64,
+}
+
+/// Rows removed because their parent no longer exists.
+#[derive(Debug, Default, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OrphanReport {
+    pub travel_plans: usize,
+    pub route_options: usize,
+    pub points_of_interest: usize,
+}
+
+impl OrphanReport {
+    pub fn total(&self) -> usize {
+        self.travel_plans + self.route_options + self.points_of_interest
+    }
+}
+
+pub fn count_rows(conn: &Connection) -> Result<Vec<TableCount>> {
+    TABLES
+        .iter()
+        .map(|table| {
+            let rows = conn.query_row(&format!("SELECT COUNT(*) FROM {}", table), [], |row| {
+                row.get(0)
+            })?;
+            Ok(TableCount {
+                table: table.to_string(),
+                rows,
+            })
+        })
+        .collect()
+}
+
+/// Deletes plans without a user, routes without a plan and POIs without a
+/// route. Parents go first so one run also clears the rows they orphan.
+pub fn purge_orphans(conn: &Connection) -> Result<OrphanReport> {
+    let tx = conn.unchecked_transaction()?;
+
+    let report = OrphanReport {
+        travel_plans: tx.execute(
+            "DELETE FROM travel_plans WHERE user_id NOT IN (SELECT id FROM users)",
+            [],
+        )?,
+        route_options: tx.execute(
+            "DELETE FROM route_options WHERE travel_plan_id NOT IN (SELECT id FROM travel_plans)",
+            [],
+        )?,
+        points_of_interest: tx.execute(
+            "DELETE FROM points_of_interest WHERE route_option_id NOT IN (SELECT id FROM route_options)",
+            [],
+        )?,
+    };
+
+    tx.commit()?;
+
+    info!("Purged {} orphaned rows", report.total());
+    Ok(report)
+}
+
+pub fn vacuum(conn: &Connection) -> Result<()> {
+    conn.execute_batch("VACUUM")?;
+    info!("Vacuumed database");
+    Ok(())
+}
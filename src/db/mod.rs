@@ -1,3 +1,6 @@
 pub mod schema;
+pub mod backup;
 pub mod connection;
-pub mod datetime;
\ No newline at end of file
+pub mod datetime;
+pub mod maintenance;
+pub mod postgres;
\ No newline at end of file
@@ -0,0 +1,371 @@
+use crate::db::connection::{DbError, pool_size};
+use log::{error, info};
+use postgres::{Client, NoTls};
+use r2d2::{Pool, PooledConnection};
+use r2d2_postgres::PostgresConnectionManager;
+
+pub type PgPool = Pool<PostgresConnectionManager<NoTls>>;
+pub type PgConnection = PooledConnection<PostgresConnectionManager<NoTls>>;
+
+/// Arbitrary key for the advisory lock that serializes migrations when
+/// several API instances start against the same database.
+const MIGRATION_LOCK_KEY: i64 = 0x7472_6176_656c;
+
+/// Ordered schema migrations, recorded in `schema_migrations`. New migrations
+/// must only be appended. `seq` columns preserve insertion order for listings,
+/// matching SQLite's rowid order.
+const MIGRATIONS: &[&str] = &[
+    // 1: initial schema
+    "CREATE TABLE IF NOT EXISTS users (
+        id TEXT PRIMARY KEY,
+        username TEXT UNIQUE NOT NULL,
+        password_hash TEXT NOT NULL,
+        email TEXT UNIQUE NOT NULL,
+        created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+    );
+
+    CREATE TABLE IF NOT EXISTS travel_plans (
+        id TEXT PRIMARY KEY,
+        user_id TEXT NOT NULL REFERENCES users (id),
+        name TEXT NOT NULL,
+        description TEXT,
+        start_location TEXT NOT NULL,
+        end_location TEXT NOT NULL,
+        start_date TIMESTAMPTZ,
+        end_date TIMESTAMPTZ,
+        created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+        updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+    );
+    CREATE INDEX IF NOT EXISTS idx_travel_plans_user_id ON travel_plans (user_id);
+
+    CREATE TABLE IF NOT EXISTS route_options (
+        seq BIGSERIAL,
+        id TEXT PRIMARY KEY,
+        travel_plan_id TEXT NOT NULL REFERENCES travel_plans (id) ON DELETE CASCADE,
+        name TEXT NOT NULL,
+        description TEXT,
+        distance DOUBLE PRECISION,
+        duration BIGINT,
+        start_coordinates TEXT NOT NULL,
+        end_coordinates TEXT NOT NULL,
+        waypoints TEXT,
+        created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+    );
+    CREATE INDEX IF NOT EXISTS idx_route_options_travel_plan_id ON route_options (travel_plan_id);
+
+    CREATE TABLE IF NOT EXISTS points_of_interest (
+        seq BIGSERIAL,
+        id TEXT PRIMARY KEY,
+        route_option_id TEXT NOT NULL REFERENCES route_options (id) ON DELETE CASCADE,
+        name TEXT NOT NULL,
+        description TEXT,
+        category TEXT,
+        coordinates TEXT NOT NULL,
+        created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+    );
+    CREATE INDEX IF NOT EXISTS idx_points_of_interest_route_option_id ON points_of_interest (route_option_id);",
+    // 2: administrators can disable accounts
+    "ALTER TABLE users ADD COLUMN IF NOT EXISTS disabled BOOLEAN NOT NULL DEFAULT FALSE;",
+    // 3: roles, and an audit trail of privileged actions
+    "ALTER TABLE users ADD COLUMN IF NOT EXISTS role TEXT NOT NULL DEFAULT 'user';
+
+    CREATE TABLE IF NOT EXISTS audit_log (
+        seq BIGSERIAL,
+        id TEXT PRIMARY KEY,
+        actor_id TEXT,
+        actor_username TEXT NOT NULL,
+        action TEXT NOT NULL,
+        target_type TEXT NOT NULL,
+        target_id TEXT,
+        details TEXT,
+        created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+    );
+    CREATE INDEX IF NOT EXISTS idx_audit_log_created_at ON audit_log (created_at);",
+    // 4: sharing plans with other users
+    "CREATE TABLE IF NOT EXISTS plan_members (
+        seq BIGSERIAL,
+        id TEXT PRIMARY KEY,
+        travel_plan_id TEXT NOT NULL REFERENCES travel_plans (id) ON DELETE CASCADE,
+        user_id TEXT NOT NULL REFERENCES users (id),
+        role TEXT NOT NULL,
+        status TEXT NOT NULL DEFAULT 'pending',
+        invited_by TEXT NOT NULL,
+        created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+        responded_at TIMESTAMPTZ,
+        UNIQUE (travel_plan_id, user_id)
+    );
+    CREATE INDEX IF NOT EXISTS idx_plan_members_user_id ON plan_members (user_id);",
+    // 5: public read-only share links
+    "CREATE TABLE IF NOT EXISTS share_links (
+        seq BIGSERIAL,
+        id TEXT PRIMARY KEY,
+        travel_plan_id TEXT NOT NULL REFERENCES travel_plans (id) ON DELETE CASCADE,
+        token TEXT UNIQUE NOT NULL,
+        created_by TEXT NOT NULL,
+        include_routes BOOLEAN NOT NULL DEFAULT TRUE,
+        include_pois BOOLEAN NOT NULL DEFAULT FALSE,
+        expires_at TIMESTAMPTZ,
+        revoked_at TIMESTAMPTZ,
+        access_count BIGINT NOT NULL DEFAULT 0,
+        last_accessed_at TIMESTAMPTZ,
+        created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+    );
+    CREATE INDEX IF NOT EXISTS idx_share_links_travel_plan_id ON share_links (travel_plan_id);",
+    // 6: voting on route options and locking one in
+    "ALTER TABLE travel_plans ADD COLUMN IF NOT EXISTS selected_route_id TEXT;
+
+    CREATE TABLE IF NOT EXISTS route_votes (
+        seq BIGSERIAL,
+        id TEXT PRIMARY KEY,
+        route_option_id TEXT NOT NULL REFERENCES route_options (id) ON DELETE CASCADE,
+        travel_plan_id TEXT NOT NULL REFERENCES travel_plans (id) ON DELETE CASCADE,
+        user_id TEXT NOT NULL REFERENCES users (id),
+        direction TEXT NOT NULL,
+        created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+        updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+        UNIQUE (route_option_id, user_id)
+    );
+    CREATE INDEX IF NOT EXISTS idx_route_votes_travel_plan_id ON route_votes (travel_plan_id);",
+    // 7: comment threads on plans, routes and points of interest
+    "CREATE TABLE IF NOT EXISTS comments (
+        seq BIGSERIAL,
+        id TEXT PRIMARY KEY,
+        travel_plan_id TEXT NOT NULL REFERENCES travel_plans (id) ON DELETE CASCADE,
+        route_option_id TEXT REFERENCES route_options (id) ON DELETE CASCADE,
+        target_type TEXT NOT NULL,
+        target_id TEXT NOT NULL,
+        thread_id TEXT NOT NULL,
+        parent_id TEXT,
+        user_id TEXT NOT NULL REFERENCES users (id),
+        body TEXT NOT NULL,
+        mentions TEXT NOT NULL DEFAULT '[]',
+        created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+        updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+    );
+    CREATE INDEX IF NOT EXISTS idx_comments_target ON comments (target_type, target_id);
+    CREATE INDEX IF NOT EXISTS idx_comments_thread_id ON comments (thread_id);
+    CREATE INDEX IF NOT EXISTS idx_comments_route_option_id ON comments (route_option_id);
+    CREATE INDEX IF NOT EXISTS idx_comments_travel_plan_id ON comments (travel_plan_id);",
+    // 8: per-plan activity feed; rows outlive their plan
+    "CREATE TABLE IF NOT EXISTS plan_events (
+        seq BIGSERIAL,
+        id TEXT PRIMARY KEY,
+        travel_plan_id TEXT NOT NULL,
+        actor_id TEXT NOT NULL,
+        action TEXT NOT NULL,
+        changes TEXT NOT NULL DEFAULT '{}',
+        created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+    );
+    CREATE INDEX IF NOT EXISTS idx_plan_events_travel_plan_id ON plan_events (travel_plan_id, created_at);",
+    // 9: earlier states of a plan, one row per update
+    "CREATE TABLE IF NOT EXISTS plan_revisions (
+        seq BIGSERIAL,
+        id TEXT PRIMARY KEY,
+        travel_plan_id TEXT NOT NULL REFERENCES travel_plans (id),
+        revision INTEGER NOT NULL,
+        name TEXT NOT NULL,
+        description TEXT,
+        start_location TEXT NOT NULL,
+        end_location TEXT NOT NULL,
+        start_date TIMESTAMPTZ,
+        end_date TIMESTAMPTZ,
+        replaced_by TEXT NOT NULL,
+        created_at TIMESTAMPTZ NOT NULL,
+        UNIQUE (travel_plan_id, revision)
+    );",
+    // 10: route options can be edited
+    "ALTER TABLE route_options ADD COLUMN IF NOT EXISTS updated_at TIMESTAMPTZ;
+    UPDATE route_options SET updated_at = created_at WHERE updated_at IS NULL;
+    ALTER TABLE route_options ALTER COLUMN updated_at SET NOT NULL;",
+    // 11: day-by-day itineraries
+    "CREATE TABLE IF NOT EXISTS itinerary_items (
+        id TEXT PRIMARY KEY,
+        travel_plan_id TEXT NOT NULL REFERENCES travel_plans (id),
+        day DATE NOT NULL,
+        position INTEGER NOT NULL,
+        kind TEXT NOT NULL,
+        title TEXT NOT NULL,
+        notes TEXT,
+        route_option_id TEXT REFERENCES route_options (id),
+        poi_id TEXT,
+        starts_at TIMESTAMPTZ,
+        ends_at TIMESTAMPTZ,
+        created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+        updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+    );
+    CREATE INDEX IF NOT EXISTS idx_itinerary_items_travel_plan_id
+        ON itinerary_items (travel_plan_id, day, position);
+    CREATE INDEX IF NOT EXISTS idx_itinerary_items_route_option_id
+        ON itinerary_items (route_option_id);",
+    // 12: multi-leg plans
+    "CREATE TABLE IF NOT EXISTS plan_legs (
+        id TEXT PRIMARY KEY,
+        travel_plan_id TEXT NOT NULL REFERENCES travel_plans (id),
+        position INTEGER NOT NULL,
+        start_location TEXT NOT NULL,
+        end_location TEXT NOT NULL,
+        start_date TIMESTAMPTZ,
+        end_date TIMESTAMPTZ,
+        mode TEXT NOT NULL,
+        selected_route_id TEXT,
+        created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+        updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+    );
+    CREATE INDEX IF NOT EXISTS idx_plan_legs_travel_plan_id
+        ON plan_legs (travel_plan_id, position);
+    ALTER TABLE route_options ADD COLUMN IF NOT EXISTS leg_id TEXT REFERENCES plan_legs (id);
+    CREATE INDEX IF NOT EXISTS idx_route_options_leg_id ON route_options (leg_id);",
+    // 13: budgets and expenses, in minor units of their currency
+    "CREATE TABLE IF NOT EXISTS budgets (
+        id TEXT PRIMARY KEY,
+        travel_plan_id TEXT NOT NULL REFERENCES travel_plans (id),
+        category TEXT NOT NULL,
+        amount BIGINT NOT NULL,
+        currency TEXT NOT NULL,
+        created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+        updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+        UNIQUE (travel_plan_id, category)
+    );
+    CREATE TABLE IF NOT EXISTS expenses (
+        id TEXT PRIMARY KEY,
+        travel_plan_id TEXT NOT NULL REFERENCES travel_plans (id),
+        category TEXT NOT NULL,
+        description TEXT NOT NULL,
+        amount BIGINT NOT NULL,
+        currency TEXT NOT NULL,
+        paid_by TEXT NOT NULL REFERENCES users (id),
+        split_between TEXT NOT NULL,
+        spent_on DATE NOT NULL,
+        created_by TEXT NOT NULL,
+        created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+        updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+    );
+    CREATE INDEX IF NOT EXISTS idx_expenses_travel_plan_id
+        ON expenses (travel_plan_id, spent_on);",
+    // 14: the currency a plan's budget summary is converted to
+    "ALTER TABLE travel_plans ADD COLUMN IF NOT EXISTS home_currency TEXT;",
+    // 15: revisions keep the home currency they replaced
+    "ALTER TABLE plan_revisions ADD COLUMN IF NOT EXISTS home_currency TEXT;",
+];
+
+/// Schema version a fully migrated database reports.
+pub const SCHEMA_VERSION: usize = MIGRATIONS.len();
+
+pub fn schema_version(client: &mut Client) -> Result<usize, postgres::Error> {
+    client.batch_execute(
+        "CREATE TABLE IF NOT EXISTS schema_migrations (
+            version BIGINT PRIMARY KEY,
+            applied_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+        )",
+    )?;
+    let row = client.query_one("SELECT COALESCE(MAX(version), 0) FROM schema_migrations", &[])?;
+    let version: i64 = row.get(0);
+    Ok(version as usize)
+}
+
+/// Applies every migration the database has not seen yet and returns how many
+/// were run. Holds an advisory lock so concurrent instances migrate once.
+pub fn run_migrations(client: &mut Client) -> Result<usize, postgres::Error> {
+    client.execute("SELECT pg_advisory_lock($1)", &[&MIGRATION_LOCK_KEY])?;
+    let result = apply_pending(client);
+    client.execute("SELECT pg_advisory_unlock($1)", &[&MIGRATION_LOCK_KEY])?;
+    result
+}
+
+fn apply_pending(client: &mut Client) -> Result<usize, postgres::Error> {
+    let current = schema_version(client)?;
+    let mut applied = 0;
+
+    for (index, migration) in MIGRATIONS.iter().enumerate().skip(current) {
+        let version = (index + 1) as i64;
+        info!("Applying PostgreSQL migration {}", version);
+
+        let mut tx = client.transaction()?;
+        tx.batch_execute(migration)?;
+        tx.execute(
+            "INSERT INTO schema_migrations (version) VALUES ($1)",
+            &[&version],
+        )?;
+        tx.commit()?;
+
+        applied += 1;
+    }
+
+    Ok(applied)
+}
+
+pub fn create_pg_pool(database_url: &str) -> Result<PgPool, DbError> {
+    info!("Creating PostgreSQL connection pool");
+
+    let config = database_url
+        .parse()
+        .map_err(|e: postgres::Error| DbError::InitError(e.to_string()))?;
+    let manager = PostgresConnectionManager::new(config, NoTls);
+
+    let pool = Pool::builder().max_size(pool_size()).build(manager)?;
+
+    {
+        let mut conn = pool.get()?;
+        match run_migrations(&mut conn) {
+            Ok(applied) => info!(
+                "PostgreSQL schema initialized successfully ({} migrations applied, version {})",
+                applied, SCHEMA_VERSION
+            ),
+            Err(e) => {
+                error!("Failed to initialize PostgreSQL database: {}", e);
+                return Err(DbError::InitError(e.to_string()));
+            }
+        }
+    }
+
+    info!("PostgreSQL connection pool created successfully");
+    Ok(pool)
+}
+
+/// Test schemas older than this are left over from earlier runs.
+#[cfg(test)]
+const STALE_TEST_SCHEMA_SECS: i64 = 3600;
+
+#[cfg(test)]
+pub fn get_test_pg_pool(database_url: &str) -> Result<PgPool, DbError> {
+    // A schema of its own keeps each test apart, like SQLite's in-memory
+    // databases. Its name records when it was made so later runs can clear
+    // it away.
+    let init_error = |e: postgres::Error| DbError::InitError(e.to_string());
+    let now = chrono::Utc::now().timestamp();
+    let schema = format!("test_{}_{}", now, uuid::Uuid::new_v4().simple());
+
+    let mut client = Client::connect(database_url, NoTls).map_err(init_error)?;
+    let stale = client
+        .query(
+            "SELECT nspname FROM pg_namespace WHERE nspname LIKE 'test\\_%\\_%'",
+            &[],
+        )
+        .map_err(init_error)?;
+    for row in stale {
+        let name: String = row.get(0);
+        let created = name.split('_').nth(1).and_then(|secs| secs.parse::<i64>().ok());
+        if created.is_some_and(|secs| now - secs > STALE_TEST_SCHEMA_SECS) {
+            let drop = format!("DROP SCHEMA IF EXISTS \"{}\" CASCADE", name);
+            client.batch_execute(&drop).map_err(init_error)?;
+        }
+    }
+    client
+        .batch_execute(&format!("CREATE SCHEMA \"{}\"", schema))
+        .map_err(init_error)?;
+
+    let mut config: postgres::Config = database_url.parse().map_err(init_error)?;
+    config.options(&format!("-c search_path={}", schema));
+    let manager = PostgresConnectionManager::new(config, NoTls);
+    let pool = Pool::builder().max_size(1).build(manager)?;
+
+    {
+        let mut conn = pool.get()?;
+        if let Err(e) = run_migrations(&mut conn) {
+            error!("Failed to initialize test schema {}: {}", schema, e);
+            return Err(DbError::InitError(e.to_string()));
+        }
+    }
+
+    Ok(pool)
+}
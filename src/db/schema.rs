@@ -1,72 +1,283 @@
 use rusqlite::{Connection, Result};
 use log::info;
 
+/// Ordered schema migrations. The database's `user_version` pragma records how
+/// many of them have been applied, so new migrations must only be appended.
+const MIGRATIONS: &[&str] = &[
+    // 1: initial schema
+    "CREATE TABLE IF NOT EXISTS users (
+        id TEXT PRIMARY KEY,
+        username TEXT UNIQUE NOT NULL,
+        password_hash TEXT NOT NULL,
+        email TEXT UNIQUE NOT NULL,
+        created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+    );
+
+    CREATE TABLE IF NOT EXISTS travel_plans (
+        id TEXT PRIMARY KEY,
+        user_id TEXT NOT NULL,
+        name TEXT NOT NULL,
+        description TEXT,
+        start_location TEXT NOT NULL,
+        end_location TEXT NOT NULL,
+        start_date TIMESTAMP,
+        end_date TIMESTAMP,
+        created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+        updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+        FOREIGN KEY (user_id) REFERENCES users (id)
+    );
+
+    CREATE TABLE IF NOT EXISTS route_options (
+        id TEXT PRIMARY KEY,
+        travel_plan_id TEXT NOT NULL,
+        name TEXT NOT NULL,
+        description TEXT,
+        distance REAL,
+        duration INTEGER,
+        start_coordinates TEXT NOT NULL,
+        end_coordinates TEXT NOT NULL,
+        waypoints TEXT,
+        created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+        FOREIGN KEY (travel_plan_id) REFERENCES travel_plans (id)
+    );
+
+    -- Attractions along routes
+    CREATE TABLE IF NOT EXISTS points_of_interest (
+        id TEXT PRIMARY KEY,
+        route_option_id TEXT NOT NULL,
+        name TEXT NOT NULL,
+        description TEXT,
+        category TEXT,
+        coordinates TEXT NOT NULL,
+        created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+        FOREIGN KEY (route_option_id) REFERENCES route_options (id)
+    );",
+    // 2: administrators can disable accounts
+    "ALTER TABLE users ADD COLUMN disabled INTEGER NOT NULL DEFAULT 0;",
+    // 3: roles, and an audit trail of privileged actions
+    "ALTER TABLE users ADD COLUMN role TEXT NOT NULL DEFAULT 'user';
+
+    CREATE TABLE IF NOT EXISTS audit_log (
+        id TEXT PRIMARY KEY,
+        actor_id TEXT,
+        actor_username TEXT NOT NULL,
+        action TEXT NOT NULL,
+        target_type TEXT NOT NULL,
+        target_id TEXT,
+        details TEXT,
+        created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+    );
+    CREATE INDEX IF NOT EXISTS idx_audit_log_created_at ON audit_log (created_at);",
+    // 4: sharing plans with other users
+    "CREATE TABLE IF NOT EXISTS plan_members (
+        id TEXT PRIMARY KEY,
+        travel_plan_id TEXT NOT NULL,
+        user_id TEXT NOT NULL,
+        role TEXT NOT NULL,
+        status TEXT NOT NULL DEFAULT 'pending',
+        invited_by TEXT NOT NULL,
+        created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+        responded_at TIMESTAMP,
+        UNIQUE (travel_plan_id, user_id),
+        FOREIGN KEY (travel_plan_id) REFERENCES travel_plans (id) ON DELETE CASCADE,
+        FOREIGN KEY (user_id) REFERENCES users (id)
+    );
+    CREATE INDEX IF NOT EXISTS idx_plan_members_user_id ON plan_members (user_id);",
+    // 5: public read-only share links
+    "CREATE TABLE IF NOT EXISTS share_links (
+        id TEXT PRIMARY KEY,
+        travel_plan_id TEXT NOT NULL,
+        token TEXT UNIQUE NOT NULL,
+        created_by TEXT NOT NULL,
+        include_routes INTEGER NOT NULL DEFAULT 1,
+        include_pois INTEGER NOT NULL DEFAULT 0,
+        expires_at TIMESTAMP,
+        revoked_at TIMESTAMP,
+        access_count INTEGER NOT NULL DEFAULT 0,
+        last_accessed_at TIMESTAMP,
+        created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+        FOREIGN KEY (travel_plan_id) REFERENCES travel_plans (id) ON DELETE CASCADE
+    );
+    CREATE INDEX IF NOT EXISTS idx_share_links_travel_plan_id ON share_links (travel_plan_id);",
+    // 6: voting on route options and locking one in
+    "ALTER TABLE travel_plans ADD COLUMN selected_route_id TEXT;
+
+    CREATE TABLE IF NOT EXISTS route_votes (
+        id TEXT PRIMARY KEY,
+        route_option_id TEXT NOT NULL,
+        travel_plan_id TEXT NOT NULL,
+        user_id TEXT NOT NULL,
+        direction TEXT NOT NULL,
+        created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+        updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+        UNIQUE (route_option_id, user_id),
+        FOREIGN KEY (route_option_id) REFERENCES route_options (id) ON DELETE CASCADE,
+        FOREIGN KEY (user_id) REFERENCES users (id)
+    );
+    CREATE INDEX IF NOT EXISTS idx_route_votes_travel_plan_id ON route_votes (travel_plan_id);",
+    // 7: comment threads on plans, routes and points of interest
+    "CREATE TABLE IF NOT EXISTS comments (
+        id TEXT PRIMARY KEY,
+        travel_plan_id TEXT NOT NULL,
+        route_option_id TEXT,
+        target_type TEXT NOT NULL,
+        target_id TEXT NOT NULL,
+        thread_id TEXT NOT NULL,
+        parent_id TEXT,
+        user_id TEXT NOT NULL,
+        body TEXT NOT NULL,
+        mentions TEXT NOT NULL DEFAULT '[]',
+        created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+        updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+        FOREIGN KEY (travel_plan_id) REFERENCES travel_plans (id) ON DELETE CASCADE,
+        FOREIGN KEY (user_id) REFERENCES users (id)
+    );
+    CREATE INDEX IF NOT EXISTS idx_comments_target ON comments (target_type, target_id);
+    CREATE INDEX IF NOT EXISTS idx_comments_thread_id ON comments (thread_id);
+    CREATE INDEX IF NOT EXISTS idx_comments_route_option_id ON comments (route_option_id);
+    CREATE INDEX IF NOT EXISTS idx_comments_travel_plan_id ON comments (travel_plan_id);",
+    // 8: per-plan activity feed; rows outlive their plan
+    "CREATE TABLE IF NOT EXISTS plan_events (
+        id TEXT PRIMARY KEY,
+        travel_plan_id TEXT NOT NULL,
+        actor_id TEXT NOT NULL,
+        action TEXT NOT NULL,
+        changes TEXT NOT NULL DEFAULT '{}',
+        created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+    );
+    CREATE INDEX IF NOT EXISTS idx_plan_events_travel_plan_id ON plan_events (travel_plan_id, created_at);",
+    // 9: earlier states of a plan, one row per update
+    "CREATE TABLE IF NOT EXISTS plan_revisions (
+        id TEXT PRIMARY KEY,
+        travel_plan_id TEXT NOT NULL REFERENCES travel_plans (id),
+        revision INTEGER NOT NULL,
+        name TEXT NOT NULL,
+        description TEXT,
+        start_location TEXT NOT NULL,
+        end_location TEXT NOT NULL,
+        start_date TIMESTAMP,
+        end_date TIMESTAMP,
+        replaced_by TEXT NOT NULL,
+        created_at TIMESTAMP NOT NULL,
+        UNIQUE (travel_plan_id, revision)
+    );",
+    // 10: route options can be edited
+    "ALTER TABLE route_options ADD COLUMN updated_at TIMESTAMP;
+    UPDATE route_options SET updated_at = created_at;",
+    // 11: day-by-day itineraries
+    "CREATE TABLE IF NOT EXISTS itinerary_items (
+        id TEXT PRIMARY KEY,
+        travel_plan_id TEXT NOT NULL,
+        day DATE NOT NULL,
+        position INTEGER NOT NULL,
+        kind TEXT NOT NULL,
+        title TEXT NOT NULL,
+        notes TEXT,
+        route_option_id TEXT,
+        poi_id TEXT,
+        starts_at TIMESTAMP,
+        ends_at TIMESTAMP,
+        created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+        updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+        FOREIGN KEY (travel_plan_id) REFERENCES travel_plans (id),
+        FOREIGN KEY (route_option_id) REFERENCES route_options (id)
+    );
+    CREATE INDEX IF NOT EXISTS idx_itinerary_items_travel_plan_id
+        ON itinerary_items (travel_plan_id, day, position);
+    CREATE INDEX IF NOT EXISTS idx_itinerary_items_route_option_id
+        ON itinerary_items (route_option_id);",
+    // 12: multi-leg plans
+    "CREATE TABLE IF NOT EXISTS plan_legs (
+        id TEXT PRIMARY KEY,
+        travel_plan_id TEXT NOT NULL,
+        position INTEGER NOT NULL,
+        start_location TEXT NOT NULL,
+        end_location TEXT NOT NULL,
+        start_date TIMESTAMP,
+        end_date TIMESTAMP,
+        mode TEXT NOT NULL,
+        selected_route_id TEXT,
+        created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+        updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+        FOREIGN KEY (travel_plan_id) REFERENCES travel_plans (id)
+    );
+    CREATE INDEX IF NOT EXISTS idx_plan_legs_travel_plan_id
+        ON plan_legs (travel_plan_id, position);
+    ALTER TABLE route_options ADD COLUMN leg_id TEXT REFERENCES plan_legs (id);
+    CREATE INDEX IF NOT EXISTS idx_route_options_leg_id ON route_options (leg_id);",
+    // 13: budgets and expenses, in minor units of their currency
+    "CREATE TABLE IF NOT EXISTS budgets (
+        id TEXT PRIMARY KEY,
+        travel_plan_id TEXT NOT NULL,
+        category TEXT NOT NULL,
+        amount INTEGER NOT NULL,
+        currency TEXT NOT NULL,
+        created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+        updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+        FOREIGN KEY (travel_plan_id) REFERENCES travel_plans (id),
+        UNIQUE (travel_plan_id, category)
+    );
+    CREATE TABLE IF NOT EXISTS expenses (
+        id TEXT PRIMARY KEY,
+        travel_plan_id TEXT NOT NULL,
+        category TEXT NOT NULL,
+        description TEXT NOT NULL,
+        amount INTEGER NOT NULL,
+        currency TEXT NOT NULL,
+        paid_by TEXT NOT NULL,
+        split_between TEXT NOT NULL,
+        spent_on DATE NOT NULL,
+        created_by TEXT NOT NULL,
+        created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+        updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+        FOREIGN KEY (travel_plan_id) REFERENCES travel_plans (id),
+        FOREIGN KEY (paid_by) REFERENCES users (id)
+    );
+    CREATE INDEX IF NOT EXISTS idx_expenses_travel_plan_id
+        ON expenses (travel_plan_id, spent_on);",
+    // 14: the currency a plan's budget summary is converted to
+    "ALTER TABLE travel_plans ADD COLUMN home_currency TEXT;",
+    // 15: revisions keep the home currency they replaced
+    "ALTER TABLE plan_revisions ADD COLUMN home_currency TEXT;",
+];
+
+/// Schema version a fully migrated database reports.
+pub const SCHEMA_VERSION: usize = MIGRATIONS.len();
+
+pub fn schema_version(conn: &Connection) -> Result<usize> {
+    let version: i64 = conn.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+    Ok(version as usize)
+}
+
+/// Applies every migration the database has not seen yet and returns how many
+/// were run.
+pub fn run_migrations(conn: &Connection) -> Result<usize> {
+    let current = schema_version(conn)?;
+    let mut applied = 0;
+
+    for (index, migration) in MIGRATIONS.iter().enumerate().skip(current) {
+        let version = index + 1;
+        info!("Applying database migration {}", version);
+
+        let tx = conn.unchecked_transaction()?;
+        tx.execute_batch(migration)?;
+        tx.pragma_update(None, "user_version", version as i64)?;
+        tx.commit()?;
+
+        applied += 1;
+    }
+
+    Ok(applied)
+}
+
 pub fn initialize_database(conn: &Connection) -> Result<()> {
     info!("Initializing database schema...");
-    
-    // Create users table
-    conn.execute(
-        "CREATE TABLE IF NOT EXISTS users (
-            id TEXT PRIMARY KEY,
-            username TEXT UNIQUE NOT NULL,
-            password_hash TEXT NOT NULL,
-            email TEXT UNIQUE NOT NULL,
-            created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
-        )",
-        [],
-    )?;
-
-    // Create travel_plans table
-    conn.execute(
-        "CREATE TABLE IF NOT EXISTS travel_plans (
-            id TEXT PRIMARY KEY,
-            user_id TEXT NOT NULL,
-            name TEXT NOT NULL,
-            description TEXT,
-            start_location TEXT NOT NULL,
-            end_location TEXT NOT NULL,
-            start_date TIMESTAMP,
-            end_date TIMESTAMP,
-            created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
-            updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
-            FOREIGN KEY (user_id) REFERENCES users (id)
-        )",
-        [],
-    )?;
-
-    // Create route_options table
-    conn.execute(
-        "CREATE TABLE IF NOT EXISTS route_options (
-            id TEXT PRIMARY KEY,
-            travel_plan_id TEXT NOT NULL,
-            name TEXT NOT NULL,
-            description TEXT,
-            distance REAL,
-            duration INTEGER,
-            start_coordinates TEXT NOT NULL,
-            end_coordinates TEXT NOT NULL,
-            waypoints TEXT,
-            created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
-            FOREIGN KEY (travel_plan_id) REFERENCES travel_plans (id)
-        )",
-        [],
-    )?;
-
-    // Create points_of_interest table for storing attractions along routes
-    conn.execute(
-        "CREATE TABLE IF NOT EXISTS points_of_interest (
-            id TEXT PRIMARY KEY,
-            route_option_id TEXT NOT NULL,
-            name TEXT NOT NULL,
-            description TEXT,
-            category TEXT,
-            coordinates TEXT NOT NULL,
-            created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
-            FOREIGN KEY (route_option_id) REFERENCES route_options (id)
-        )",
-        [],
-    )?;
-
-    info!("Database schema initialized successfully");
+
+    let applied = run_migrations(conn)?;
+
+    info!(
+        "Database schema initialized successfully ({} migrations applied, version {})",
+        applied, SCHEMA_VERSION
+    );
     Ok(())
-}
\ No newline at end of file
+}
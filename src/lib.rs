@@ -0,0 +1,18 @@
+//! Travel planning API: storage backends, services and the actix-web routes.
+//!
+//! The `travel-api` binary is a thin server around [`configure_app`]; other
+//! services can mount the same endpoints in their own `App`.
+
+pub mod api_docs;
+pub mod app;
+pub mod db;
+pub mod middleware;
+pub mod models;
+pub mod repositories;
+pub mod routes;
+pub mod services;
+
+#[cfg(test)]
+mod tests;
+
+pub use app::{configure_app, AppState};
@@ -2,19 +2,18 @@ use actix_web::{web, App, HttpServer, middleware::Logger};
 use actix_cors::Cors;
 use dotenv::dotenv;
 use log::info;
+use std::sync::Arc;
 use utoipa::OpenApi;
 use utoipa_swagger_ui::SwaggerUi;
 
-mod api_docs;
-mod db;
-mod middleware;
-mod models;
-mod routes;
-mod services;
-
-use crate::api_docs::ApiDoc;
-use crate::db::connection;
-use crate::routes::{auth, travel_plan, route_option};
+use travel_api::api_docs::ApiDoc;
+use travel_api::db::connection;
+use travel_api::services::backup_service::{BackupConfig, BackupService};
+use travel_api::services::blocking;
+use travel_api::services::currency::ExchangeRates;
+use travel_api::services::emissions::EmissionFactors;
+use travel_api::services::route_cost::CostModel;
+use travel_api::{configure_app, AppState};
 
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
@@ -22,19 +21,59 @@ async fn main() -> std::io::Result<()> {
     
     env_logger::init_from_env(env_logger::Env::default().default_filter_or("info"));
     
-    let db_pool = match connection::get_pool() {
-        Ok(pool) => {
+    // The PostgreSQL client drives its own runtime, so connect off the
+    // executor thread like all other database work.
+    let database_url = connection::database_url();
+    let connected = web::block(move || AppState::connect(&database_url))
+        .await
+        .expect("Database connection task was canceled");
+    
+    let state = match connected {
+        Ok(state) => {
             info!("Database connection pool created successfully");
-            pool
+            state.with_backups(BackupConfig::from_env())
         },
         Err(e) => {
             panic!("Failed to create database connection pool: {}", e);
         }
     };
     
-    let db_data = web::Data::new(db_pool);
+    let state = match ExchangeRates::from_env() {
+        Ok(rates) => {
+            if rates.is_empty() {
+                info!("No exchange rates loaded; set EXCHANGE_RATES_FILE to convert currencies");
+            }
+            state.with_exchange_rates(rates)
+        },
+        Err(e) => {
+            panic!("Failed to load exchange rates: {}", e);
+        }
+    };
     
-    info!("Starting HTTP server at http://127.0.0.1:8080");
+    let state = match CostModel::from_env() {
+        Ok(costs) => state.with_route_costs(costs),
+        Err(e) => {
+            panic!("Failed to load route costs: {}", e);
+        }
+    };
+    
+    let state = match EmissionFactors::from_env() {
+        Ok(factors) => state.with_emission_factors(factors),
+        Err(e) => {
+            panic!("Failed to load emission factors: {}", e);
+        }
+    };
+    
+    if let Some(backups) = &state.backups {
+        Arc::new(BackupService::new(state.repositories.clone(), backups.clone())).spawn_scheduler();
+    }
+    
+    let blocking_threads = blocking::blocking_threads();
+    
+    info!(
+        "Starting HTTP server at http://127.0.0.1:8080 ({} blocking threads per worker)",
+        blocking_threads
+    );
     
     HttpServer::new(move || {
         let openapi = ApiDoc::openapi();
@@ -45,36 +84,25 @@ async fn main() -> std::io::Result<()> {
                 Cors::default()
                     .allowed_origin("http://localhost:4200")
                     .allowed_origin("http://127.0.0.1:8080")
-                    .allowed_methods(vec!["GET", "POST", "PUT", "DELETE", "OPTIONS"])
-                    .allowed_headers(vec!["Content-Type", "Authorization"])
+                    .allowed_methods(vec!["GET", "POST", "PUT", "PATCH", "DELETE", "OPTIONS"])
+                    .allowed_headers(vec![
+                        "Content-Type",
+                        "Authorization",
+                        "If-Match",
+                        "If-None-Match",
+                    ])
+                    .expose_headers(vec!["ETag"])
                     .supports_credentials()
                     .max_age(3600)
             )
-            .app_data(db_data.clone())
-            
             .service(
                 SwaggerUi::new("/swagger-ui/{_:.*}")
                     .url("/api-docs/openapi.json", openapi.clone())
             )
             
-            .service(
-                web::scope("/api")
-                    .route("/register", web::post().to(auth::register))
-                    .route("/login", web::post().to(auth::login))
-                    
-                    .route("/travelplan", web::get().to(travel_plan::get_travel_plans))
-                    .route("/travelplan", web::post().to(travel_plan::create_travel_plan))
-                    .route("/travelplan/{id}", web::get().to(travel_plan::get_travel_plan_by_id))
-                    .route("/travelplan/{id}", web::put().to(travel_plan::update_travel_plan))
-                    .route("/travelplan/{id}", web::delete().to(travel_plan::delete_travel_plan))
-                    
-                    .route("/travelplan/{id}/routes", web::get().to(route_option::get_route_options))
-                    .route("/travelplan/{id}/routes", web::delete().to(route_option::delete_all_route_options))
-                    .route("/travelplan/{id}/routes/generate", web::post().to(route_option::generate_route_options))
-                    .route("/travelplan/{plan_id}/routes/{route_id}", web::get().to(route_option::get_route_option_by_id))
-                    .route("/travelplan/{plan_id}/routes/{route_id}", web::delete().to(route_option::delete_route_option))
-            )
+            .configure(|cfg| configure_app(cfg, &state))
     })
+    .worker_max_blocking_threads(blocking_threads)
     .bind("127.0.0.1:8080")?
     .run()
     .await
@@ -1,15 +1,21 @@
 use actix_web::{
-    dev::Payload, error::ErrorUnauthorized, http::header, web, Error, FromRequest, HttpRequest,
+    dev::Payload,
+    error::{ErrorForbidden, ErrorInternalServerError, ErrorUnauthorized},
+    http::header,
+    web, Error, FromRequest, HttpRequest,
 };
 use chrono::{Duration, Utc};
 use jsonwebtoken::{decode, encode, DecodingKey, EncodingKey, Header, Validation};
 use serde::{Deserialize, Serialize};
-use std::future::{ready, Ready};
-use log::{error, info};
+use std::future::{ready, Future};
+use std::pin::Pin;
+use log::{error, info, warn};
 use utoipa::ToSchema;
 
-use crate::db::connection::{DbPool, DbConnection};
-use crate::models::user::User;
+use crate::models::audit::Actor;
+use crate::models::user::{Role, User};
+use crate::repositories::Repositories;
+use crate::services::blocking;
 
 const JWT_SECRET: &[u8] = b"secret_key_for_jwt_token_generation";
 const TOKEN_EXPIRATION_HOURS: i64 = 24;
@@ -20,6 +26,9 @@ pub struct Claims {
     pub username: String,
     pub exp: i64,
     pub iat: i64,
+    /// Tokens issued before roles existed carry none and act as `user`.
+    #[serde(default)]
+    pub role: Role,
 }
 
 #[derive(Debug, Serialize, Deserialize, ToSchema)]
@@ -30,7 +39,7 @@ pub struct AuthToken {
 }
 
 impl Claims {
-    pub fn new(user_id: &str, username: &str) -> Self {
+    pub fn new(user_id: &str, username: &str, role: Role) -> Self {
         let now = Utc::now();
         let expiration = now + Duration::hours(TOKEN_EXPIRATION_HOURS);
         
@@ -39,12 +48,13 @@ impl Claims {
             username: username.to_string(),
             exp: expiration.timestamp(),
             iat: now.timestamp(),
+            role,
         }
     }
 }
 
 pub fn generate_token(user: &User) -> Result<AuthToken, jsonwebtoken::errors::Error> {
-    let claims = Claims::new(&user.id, &user.username);
+    let claims = Claims::new(&user.id, &user.username, user.role);
     let token = encode(
         &Header::default(),
         &claims,
@@ -72,73 +82,124 @@ pub fn validate_token(token: &str) -> Result<Claims, jsonwebtoken::errors::Error
 pub struct AuthenticatedUser {
     pub user_id: String,
     pub username: String,
+    pub role: Role,
 }
 
-impl FromRequest for AuthenticatedUser {
-    type Error = Error;
-    type Future = Ready<Result<Self, Self::Error>>;
+impl AuthenticatedUser {
+    pub fn actor(&self) -> Actor {
+        Actor {
+            user_id: Some(self.user_id.clone()),
+            username: self.username.clone(),
+            role: self.role,
+        }
+    }
+}
 
-    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
-        let auth_header = req.headers().get(header::AUTHORIZATION);
-        let auth_header = match auth_header {
-            Some(header) => header,
-            None => {
-                return ready(Err(ErrorUnauthorized("No authorization header found")));
-            }
-        };
+/// The claims of the request's bearer token.
+fn bearer_claims(req: &HttpRequest) -> Result<Claims, Error> {
+    let auth_header = match req.headers().get(header::AUTHORIZATION) {
+        Some(header) => header,
+        None => return Err(ErrorUnauthorized("No authorization header found")),
+    };
 
-        let auth_str = match auth_header.to_str() {
-            Ok(s) => s,
-            Err(_) => {
-                return ready(Err(ErrorUnauthorized("Invalid authorization header")));
-            }
-        };
+    let auth_str = match auth_header.to_str() {
+        Ok(s) => s,
+        Err(_) => return Err(ErrorUnauthorized("Invalid authorization header")),
+    };
 
-        if !auth_str.starts_with("Bearer ") {
-            return ready(Err(ErrorUnauthorized("Invalid authorization scheme")));
-        }
+    if !auth_str.starts_with("Bearer ") {
+        return Err(ErrorUnauthorized("Invalid authorization scheme"));
+    }
 
-        let token = &auth_str[7..];
+    let token = &auth_str[7..];
 
-        match validate_token(token) {
-            Ok(claims) => {
-                ready(Ok(AuthenticatedUser {
-                    user_id: claims.sub,
-                    username: claims.username,
-                }))
-            }
-            Err(e) => {
-                error!("Token validation error: {}", e);
-                ready(Err(ErrorUnauthorized("Invalid token")))
-            }
+    validate_token(token).map_err(|e| {
+        error!("Token validation error: {}", e);
+        ErrorUnauthorized("Invalid token")
+    })
+}
+
+/// Checks the token against the account as it is now: a token outlives
+/// neither its user being disabled or removed nor a change of role.
+async fn current_user(
+    repos: Option<web::Data<dyn Repositories>>,
+    claims: Claims,
+) -> Result<AuthenticatedUser, Error> {
+    let Some(repos) = repos else {
+        error!("No repositories registered to check tokens against");
+        return Err(ErrorInternalServerError("Authentication is unavailable"));
+    };
+
+    let user_id = claims.sub.clone();
+    let user = match blocking::run(move || repos.find_user_by_id(&user_id)).await {
+        Ok(user) => user,
+        Err(e) => {
+            error!("Error loading user {} for a token: {}", claims.sub, e);
+            return Err(ErrorInternalServerError("Authentication is unavailable"));
         }
+    };
+
+    match user {
+        None => {
+            warn!("Token for unknown user {} rejected", claims.sub);
+            Err(ErrorUnauthorized("Invalid token"))
+        }
+        Some(user) if user.disabled => {
+            warn!("Token for disabled user {} rejected", user.username);
+            Err(ErrorForbidden("Account is disabled"))
+        }
+        Some(user) if user.role != claims.role => {
+            warn!(
+                "Token for {} claims role {} but the account is now {}",
+                user.username, claims.role, user.role
+            );
+            Err(ErrorUnauthorized("Token is out of date; log in again"))
+        }
+        Some(user) => Ok(AuthenticatedUser {
+            user_id: user.id,
+            username: user.username,
+            role: user.role,
+        }),
     }
 }
 
-pub struct AuthDbConn(#[allow(dead_code)] pub DbConnection);
-
-impl FromRequest for AuthDbConn {
+impl FromRequest for AuthenticatedUser {
     type Error = Error;
-    type Future = Ready<Result<Self, Self::Error>>;
+    type Future = Pin<Box<dyn Future<Output = Result<Self, Self::Error>>>>;
 
     fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
-        let pool = match req.app_data::<web::Data<DbPool>>() {
-            Some(pool) => pool.get_ref(),
-            None => {
-                error!("Database pool not found in application data");
-                return ready(Err(ErrorUnauthorized("Database error")));
-            }
+        let claims = match bearer_claims(req) {
+            Ok(claims) => claims,
+            Err(e) => return Box::pin(ready(Err(e))),
         };
+        let repos = req.app_data::<web::Data<dyn Repositories>>().cloned();
+
+        Box::pin(current_user(repos, claims))
+    }
+}
 
-        match pool.get() {
-            Ok(conn) => {
-                ready(Ok(AuthDbConn(conn)))
-            },
-            Err(e) => {
-                error!("Failed to get database connection from pool: {}", e);
-                ready(Err(ErrorUnauthorized("Database error")))
+/// Guard for admin-only endpoints: rejects anyone without the admin role
+/// with 403 before the handler runs.
+#[derive(Debug, Clone)]
+pub struct AdminUser(pub AuthenticatedUser);
+
+impl FromRequest for AdminUser {
+    type Error = Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self, Self::Error>>>>;
+
+    fn from_request(req: &HttpRequest, payload: &mut Payload) -> Self::Future {
+        let user = AuthenticatedUser::from_request(req, payload);
+        let path = req.path().to_string();
+
+        Box::pin(async move {
+            let user = user.await?;
+            if user.role != Role::Admin {
+                warn!("User {} ({}) denied admin access to {}", user.username, user.role, path);
+                return Err(ErrorForbidden("Admin access required"));
             }
-        }
+
+            Ok(AdminUser(user))
+        })
     }
 }
 
@@ -0,0 +1,60 @@
+use actix_web::dev::Payload;
+use actix_web::http::header;
+use actix_web::{Error, FromRequest, HttpRequest, HttpResponse};
+use std::future::{Ready, ready};
+
+use crate::services::precondition;
+
+/// The conditional request headers. Absent or unreadable headers impose no
+/// condition.
+#[derive(Debug, Clone, Default)]
+pub struct Preconditions {
+    pub if_match: Option<String>,
+    pub if_none_match: Option<String>,
+}
+
+impl Preconditions {
+    /// Whether a read can answer 304 because the caller has `etag` already.
+    pub fn not_modified(&self, etag: &str) -> bool {
+        self.if_none_match
+            .as_deref()
+            .is_some_and(|header| precondition::if_none_match(header, etag))
+    }
+}
+
+impl FromRequest for Preconditions {
+    type Error = Error;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        let header_value = |name: header::HeaderName| {
+            req.headers()
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .map(str::to_string)
+        };
+
+        ready(Ok(Preconditions {
+            if_match: header_value(header::IF_MATCH),
+            if_none_match: header_value(header::IF_NONE_MATCH),
+        }))
+    }
+}
+
+/// 200 with the body and its `ETag`, or an empty 304 when the caller's
+/// `If-None-Match` already names it.
+pub fn tagged_response<T: serde::Serialize>(
+    preconditions: &Preconditions,
+    etag: String,
+    body: &T,
+) -> HttpResponse {
+    if preconditions.not_modified(&etag) {
+        return HttpResponse::NotModified()
+            .insert_header((header::ETAG, etag))
+            .finish();
+    }
+
+    HttpResponse::Ok()
+        .insert_header((header::ETAG, etag))
+        .json(body)
+}
@@ -0,0 +1,113 @@
+use chrono::{DateTime, Utc};
+use rusqlite::{Connection, Result, Row, params};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::models::user::Role;
+
+/// Whoever performs a privileged action: a signed-in user, or a process such
+/// as the operator CLI or the backup scheduler.
+#[derive(Debug, Clone)]
+pub struct Actor {
+    pub user_id: Option<String>,
+    pub username: String,
+    pub role: Role,
+}
+
+impl Actor {
+    /// A trusted process acting with admin rights.
+    pub fn system(name: &str) -> Self {
+        Actor {
+            user_id: None,
+            username: name.to_string(),
+            role: Role::Admin,
+        }
+    }
+}
+
+/// One privileged action, e.g. `user.lock` on target `user/<id>`.
+#[derive(Debug, Serialize, Deserialize, Clone, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct AuditEntry {
+    pub id: String,
+    pub actor_id: Option<String>,
+    pub actor_username: String,
+    pub action: String,
+    pub target_type: String,
+    pub target_id: Option<String>,
+    pub details: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl AuditEntry {
+    pub fn from_row(row: &Row) -> Result<Self> {
+        Ok(AuditEntry {
+            id: row.get(0)?,
+            actor_id: row.get(1)?,
+            actor_username: row.get(2)?,
+            action: row.get(3)?,
+            target_type: row.get(4)?,
+            target_id: row.get(5)?,
+            details: row.get(6)?,
+            created_at: row.get(7)?,
+        })
+    }
+
+    pub fn new(
+        actor: &Actor,
+        action: &str,
+        target_type: &str,
+        target_id: Option<&str>,
+        details: Option<String>,
+    ) -> Self {
+        AuditEntry {
+            id: Uuid::new_v4().to_string(),
+            actor_id: actor.user_id.clone(),
+            actor_username: actor.username.clone(),
+            action: action.to_string(),
+            target_type: target_type.to_string(),
+            target_id: target_id.map(str::to_string),
+            details,
+            created_at: Utc::now(),
+        }
+    }
+
+    pub fn insert(&self, conn: &Connection) -> Result<()> {
+        conn.execute(
+            "INSERT INTO audit_log (
+                id, actor_id, actor_username, action, target_type, target_id, details, created_at
+            ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+            params![
+                self.id,
+                self.actor_id,
+                self.actor_username,
+                self.action,
+                self.target_type,
+                self.target_id,
+                self.details,
+                self.created_at
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// Newest entries first.
+    pub fn find_recent(conn: &Connection, limit: usize, offset: usize) -> Result<Vec<Self>> {
+        let mut stmt = conn.prepare(
+            "SELECT id, actor_id, actor_username, action, target_type, target_id, details, created_at
+             FROM audit_log
+             ORDER BY created_at DESC, rowid DESC
+             LIMIT ?1 OFFSET ?2",
+        )?;
+
+        let entry_iter = stmt.query_map(params![limit as i64, offset as i64], Self::from_row)?;
+
+        let mut entries = Vec::new();
+        for entry_result in entry_iter {
+            entries.push(entry_result?);
+        }
+
+        Ok(entries)
+    }
+}
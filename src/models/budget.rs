@@ -0,0 +1,424 @@
+use chrono::{DateTime, NaiveDate, Utc};
+use rusqlite::types::{FromSql, FromSqlError, FromSqlResult, ToSql, ToSqlOutput, ValueRef};
+use rusqlite::{Connection, Result, Row, params};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::models::merge_patch;
+
+/// What money is spent on.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ExpenseCategory {
+    Transport,
+    Lodging,
+    Food,
+    Activities,
+    Other,
+}
+
+impl ExpenseCategory {
+    pub const ALL: [ExpenseCategory; 5] = [
+        ExpenseCategory::Transport,
+        ExpenseCategory::Lodging,
+        ExpenseCategory::Food,
+        ExpenseCategory::Activities,
+        ExpenseCategory::Other,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ExpenseCategory::Transport => "transport",
+            ExpenseCategory::Lodging => "lodging",
+            ExpenseCategory::Food => "food",
+            ExpenseCategory::Activities => "activities",
+            ExpenseCategory::Other => "other",
+        }
+    }
+}
+
+impl fmt::Display for ExpenseCategory {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for ExpenseCategory {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        ExpenseCategory::ALL
+            .into_iter()
+            .find(|category| category.as_str() == s)
+            .ok_or_else(|| format!("unknown expense category: {}", s))
+    }
+}
+
+impl ToSql for ExpenseCategory {
+    fn to_sql(&self) -> Result<ToSqlOutput<'_>> {
+        Ok(ToSqlOutput::from(self.as_str()))
+    }
+}
+
+impl FromSql for ExpenseCategory {
+    fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+        value
+            .as_str()?
+            .parse()
+            .map_err(|e: String| FromSqlError::Other(e.into()))
+    }
+}
+
+/// The amount a plan sets aside for one category. Amounts are in minor
+/// units of the currency, e.g. cents.
+#[derive(Debug, Serialize, Deserialize, Clone, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct Budget {
+    pub id: String,
+    pub travel_plan_id: String,
+    pub category: ExpenseCategory,
+    #[schema(example = 50000)]
+    pub amount: i64,
+    /// ISO 4217 code.
+    #[schema(example = "EUR")]
+    pub currency: String,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct SetBudget {
+    #[schema(example = 50000)]
+    pub amount: i64,
+    #[schema(example = "EUR")]
+    pub currency: String,
+}
+
+/// Money one member spent for the plan, shared between `split_between`.
+#[derive(Debug, Serialize, Deserialize, Clone, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct Expense {
+    pub id: String,
+    pub travel_plan_id: String,
+    pub category: ExpenseCategory,
+    pub description: String,
+    /// In minor units of the currency, e.g. cents.
+    #[schema(example = 4250)]
+    pub amount: i64,
+    #[schema(example = "EUR")]
+    pub currency: String,
+    /// The member who paid.
+    pub paid_by: String,
+    /// The members who share the cost equally, the payer included if they
+    /// are listed.
+    pub split_between: Vec<String>,
+    pub spent_on: NaiveDate,
+    pub created_by: String,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct NewExpense {
+    pub category: ExpenseCategory,
+    pub description: String,
+    pub amount: i64,
+    pub currency: String,
+    /// Defaults to the member recording the expense.
+    pub paid_by: Option<String>,
+    /// Defaults to every member of the plan.
+    pub split_between: Option<Vec<String>>,
+    pub spent_on: NaiveDate,
+}
+
+/// A JSON Merge Patch for an expense. No field can be cleared.
+#[derive(Debug, Default, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct PatchExpense {
+    #[serde(default, deserialize_with = "merge_patch::non_null")]
+    #[schema(value_type = Option<ExpenseCategory>)]
+    pub category: Option<ExpenseCategory>,
+    #[serde(default, deserialize_with = "merge_patch::non_null")]
+    pub description: Option<String>,
+    #[serde(default, deserialize_with = "merge_patch::non_null")]
+    pub amount: Option<i64>,
+    #[serde(default, deserialize_with = "merge_patch::non_null")]
+    pub currency: Option<String>,
+    #[serde(default, deserialize_with = "merge_patch::non_null")]
+    pub paid_by: Option<String>,
+    #[serde(default, deserialize_with = "merge_patch::non_null")]
+    pub split_between: Option<Vec<String>>,
+    #[serde(default, deserialize_with = "merge_patch::non_null")]
+    #[schema(value_type = Option<NaiveDate>)]
+    pub spent_on: Option<NaiveDate>,
+}
+
+fn split_from_json(index: usize, text: &str) -> Result<Vec<String>> {
+    serde_json::from_str(text).map_err(|e| {
+        rusqlite::Error::FromSqlConversionFailure(index, rusqlite::types::Type::Text, Box::new(e))
+    })
+}
+
+impl Budget {
+    pub fn from_row(row: &Row) -> Result<Self> {
+        Ok(Budget {
+            id: row.get(0)?,
+            travel_plan_id: row.get(1)?,
+            category: row.get(2)?,
+            amount: row.get(3)?,
+            currency: row.get(4)?,
+            created_at: row.get(5)?,
+            updated_at: row.get(6)?,
+        })
+    }
+
+    pub fn new(plan_id: &str, category: ExpenseCategory, budget: &SetBudget) -> Self {
+        let now = Utc::now();
+        Budget {
+            id: Uuid::new_v4().to_string(),
+            travel_plan_id: plan_id.to_string(),
+            category,
+            amount: budget.amount,
+            currency: budget.currency.clone(),
+            created_at: now,
+            updated_at: now,
+        }
+    }
+
+    pub fn insert(&self, conn: &Connection) -> Result<()> {
+        conn.execute(
+            "INSERT INTO budgets (
+                id, travel_plan_id, category, amount, currency, created_at, updated_at
+            ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            params![
+                self.id,
+                self.travel_plan_id,
+                self.category,
+                self.amount,
+                self.currency,
+                self.created_at,
+                self.updated_at
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// Ordered by category.
+    pub fn find_by_travel_plan_id(conn: &Connection, plan_id: &str) -> Result<Vec<Self>> {
+        let mut stmt = conn.prepare(
+            "SELECT id, travel_plan_id, category, amount, currency, created_at, updated_at
+             FROM budgets
+             WHERE travel_plan_id = ?1
+             ORDER BY category",
+        )?;
+
+        let budget_iter = stmt.query_map(params![plan_id], Self::from_row)?;
+
+        let mut budgets = Vec::new();
+        for budget_result in budget_iter {
+            budgets.push(budget_result?);
+        }
+
+        Ok(budgets)
+    }
+
+    pub fn update(&self, conn: &Connection) -> Result<()> {
+        conn.execute(
+            "UPDATE budgets SET amount = ?1, currency = ?2, updated_at = ?3 WHERE id = ?4",
+            params![self.amount, self.currency, self.updated_at, self.id],
+        )?;
+        Ok(())
+    }
+
+    pub fn delete(conn: &Connection, id: &str) -> Result<bool> {
+        let rows_affected = conn.execute("DELETE FROM budgets WHERE id = ?1", params![id])?;
+        Ok(rows_affected > 0)
+    }
+
+    pub fn delete_by_travel_plan_id(conn: &Connection, plan_id: &str) -> Result<usize> {
+        conn.execute("DELETE FROM budgets WHERE travel_plan_id = ?1", params![plan_id])
+    }
+}
+
+impl Expense {
+    pub fn from_row(row: &Row) -> Result<Self> {
+        let split_between: String = row.get(7)?;
+        Ok(Expense {
+            id: row.get(0)?,
+            travel_plan_id: row.get(1)?,
+            category: row.get(2)?,
+            description: row.get(3)?,
+            amount: row.get(4)?,
+            currency: row.get(5)?,
+            paid_by: row.get(6)?,
+            split_between: split_from_json(7, &split_between)?,
+            spent_on: row.get(8)?,
+            created_by: row.get(9)?,
+            created_at: row.get(10)?,
+            updated_at: row.get(11)?,
+        })
+    }
+
+    /// An expense recorded by `created_by`, with the payer and split already
+    /// resolved.
+    pub fn new(
+        plan_id: &str,
+        new_expense: &NewExpense,
+        paid_by: &str,
+        split_between: Vec<String>,
+        created_by: &str,
+    ) -> Self {
+        let now = Utc::now();
+        Expense {
+            id: Uuid::new_v4().to_string(),
+            travel_plan_id: plan_id.to_string(),
+            category: new_expense.category,
+            description: new_expense.description.clone(),
+            amount: new_expense.amount,
+            currency: new_expense.currency.clone(),
+            paid_by: paid_by.to_string(),
+            split_between,
+            spent_on: new_expense.spent_on,
+            created_by: created_by.to_string(),
+            created_at: now,
+            updated_at: now,
+        }
+    }
+
+    /// Returns a copy of this expense with a merge patch applied.
+    pub fn with_patch(&self, patch: &PatchExpense) -> Self {
+        let mut patched_expense = self.clone();
+
+        if let Some(category) = patch.category {
+            patched_expense.category = category;
+        }
+        if let Some(description) = &patch.description {
+            patched_expense.description = description.clone();
+        }
+        if let Some(amount) = patch.amount {
+            patched_expense.amount = amount;
+        }
+        if let Some(currency) = &patch.currency {
+            patched_expense.currency = currency.clone();
+        }
+        if let Some(paid_by) = &patch.paid_by {
+            patched_expense.paid_by = paid_by.clone();
+        }
+        if let Some(split_between) = &patch.split_between {
+            patched_expense.split_between = split_between.clone();
+        }
+        if let Some(spent_on) = patch.spent_on {
+            patched_expense.spent_on = spent_on;
+        }
+
+        patched_expense.updated_at = Utc::now();
+
+        patched_expense
+    }
+
+    pub fn split_to_json(&self) -> String {
+        serde_json::to_string(&self.split_between).unwrap_or_else(|_| "[]".to_string())
+    }
+
+    pub fn insert(&self, conn: &Connection) -> Result<()> {
+        conn.execute(
+            "INSERT INTO expenses (
+                id, travel_plan_id, category, description, amount, currency, paid_by,
+                split_between, spent_on, created_by, created_at, updated_at
+            ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)",
+            params![
+                self.id,
+                self.travel_plan_id,
+                self.category,
+                self.description,
+                self.amount,
+                self.currency,
+                self.paid_by,
+                self.split_to_json(),
+                self.spent_on,
+                self.created_by,
+                self.created_at,
+                self.updated_at
+            ],
+        )?;
+        Ok(())
+    }
+
+    pub fn find_by_id(conn: &Connection, id: &str) -> Result<Option<Self>> {
+        let mut stmt = conn.prepare(
+            "SELECT id, travel_plan_id, category, description, amount, currency, paid_by,
+                    split_between, spent_on, created_by, created_at, updated_at
+             FROM expenses
+             WHERE id = ?1",
+        )?;
+
+        let mut rows = stmt.query(params![id])?;
+
+        if let Some(row) = rows.next()? {
+            Ok(Some(Self::from_row(row)?))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Ordered by the day spent, then when recorded.
+    pub fn find_by_travel_plan_id(conn: &Connection, plan_id: &str) -> Result<Vec<Self>> {
+        let mut stmt = conn.prepare(
+            "SELECT id, travel_plan_id, category, description, amount, currency, paid_by,
+                    split_between, spent_on, created_by, created_at, updated_at
+             FROM expenses
+             WHERE travel_plan_id = ?1
+             ORDER BY spent_on, created_at",
+        )?;
+
+        let expense_iter = stmt.query_map(params![plan_id], Self::from_row)?;
+
+        let mut expenses = Vec::new();
+        for expense_result in expense_iter {
+            expenses.push(expense_result?);
+        }
+
+        Ok(expenses)
+    }
+
+    pub fn update(&self, conn: &Connection) -> Result<()> {
+        conn.execute(
+            "UPDATE expenses SET
+                category = ?1,
+                description = ?2,
+                amount = ?3,
+                currency = ?4,
+                paid_by = ?5,
+                split_between = ?6,
+                spent_on = ?7,
+                updated_at = ?8
+             WHERE id = ?9",
+            params![
+                self.category,
+                self.description,
+                self.amount,
+                self.currency,
+                self.paid_by,
+                self.split_to_json(),
+                self.spent_on,
+                self.updated_at,
+                self.id
+            ],
+        )?;
+        Ok(())
+    }
+
+    pub fn delete(conn: &Connection, id: &str) -> Result<bool> {
+        let rows_affected = conn.execute("DELETE FROM expenses WHERE id = ?1", params![id])?;
+        Ok(rows_affected > 0)
+    }
+
+    pub fn delete_by_travel_plan_id(conn: &Connection, plan_id: &str) -> Result<usize> {
+        conn.execute("DELETE FROM expenses WHERE travel_plan_id = ?1", params![plan_id])
+    }
+}
@@ -0,0 +1,344 @@
+use chrono::{DateTime, Utc};
+use rusqlite::types::{FromSql, FromSqlError, FromSqlResult, ToSql, ToSqlOutput, ValueRef};
+use rusqlite::{Connection, Result, Row, params};
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+/// What a comment thread is attached to.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum CommentTarget {
+    Plan,
+    Route,
+    Poi,
+}
+
+impl CommentTarget {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CommentTarget::Plan => "plan",
+            CommentTarget::Route => "route",
+            CommentTarget::Poi => "poi",
+        }
+    }
+}
+
+impl FromStr for CommentTarget {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "plan" => Ok(CommentTarget::Plan),
+            "route" => Ok(CommentTarget::Route),
+            "poi" => Ok(CommentTarget::Poi),
+            other => Err(format!("unknown comment target: {}", other)),
+        }
+    }
+}
+
+impl ToSql for CommentTarget {
+    fn to_sql(&self) -> Result<ToSqlOutput<'_>> {
+        Ok(ToSqlOutput::from(self.as_str()))
+    }
+}
+
+impl FromSql for CommentTarget {
+    fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+        value
+            .as_str()?
+            .parse()
+            .map_err(|e: String| FromSqlError::Other(e.into()))
+    }
+}
+
+/// What a deleted comment with replies shows instead of its body.
+pub const DELETED_BODY: &str = "[deleted]";
+
+/// A comment on a plan, route option or point of interest. Replies point at
+/// their parent and share the `thread_id` of the top-level comment.
+#[derive(Debug, Serialize, Deserialize, Clone, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct Comment {
+    pub id: String,
+    pub travel_plan_id: String,
+    /// The route the target belongs to, for route and POI comments.
+    pub route_option_id: Option<String>,
+    pub target_type: CommentTarget,
+    pub target_id: String,
+    pub thread_id: String,
+    pub parent_id: Option<String>,
+    pub user_id: String,
+    pub body: String,
+    /// Usernames @mentioned in the body that belong to real accounts.
+    pub mentions: Vec<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct NewComment {
+    pub body: String,
+    /// Reply to this comment instead of starting a new thread.
+    pub parent_id: Option<String>,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct UpdateComment {
+    pub body: String,
+}
+
+/// Candidate usernames from `@name` tokens in `body`, in order of first
+/// appearance. An `@` inside a word, as in an email address, is not a mention.
+pub fn parse_mentions(body: &str) -> Vec<String> {
+    let is_name_char = |c: char| c.is_alphanumeric() || matches!(c, '_' | '.' | '-');
+    let mut mentions: Vec<String> = Vec::new();
+    let mut previous: Option<char> = None;
+
+    for (index, c) in body.char_indices() {
+        if c == '@' && !previous.is_some_and(is_name_char) {
+            let rest = &body[index + 1..];
+            let end = rest.find(|c: char| !is_name_char(c)).unwrap_or(rest.len());
+            // Trailing punctuation ends the sentence, not the name
+            let name = rest[..end].trim_end_matches(['.', '-']);
+            if !name.is_empty() && !mentions.iter().any(|m| m == name) {
+                mentions.push(name.to_string());
+            }
+        }
+        previous = Some(c);
+    }
+
+    mentions
+}
+
+fn mentions_from_json(json: &str) -> Result<Vec<String>> {
+    serde_json::from_str(json).map_err(|e| {
+        rusqlite::Error::FromSqlConversionFailure(0, rusqlite::types::Type::Text, Box::new(e))
+    })
+}
+
+impl Comment {
+    pub fn from_row(row: &Row) -> Result<Self> {
+        Ok(Comment {
+            id: row.get(0)?,
+            travel_plan_id: row.get(1)?,
+            route_option_id: row.get(2)?,
+            target_type: row.get(3)?,
+            target_id: row.get(4)?,
+            thread_id: row.get(5)?,
+            parent_id: row.get(6)?,
+            user_id: row.get(7)?,
+            body: row.get(8)?,
+            mentions: mentions_from_json(&row.get::<_, String>(9)?)?,
+            created_at: row.get(10)?,
+            updated_at: row.get(11)?,
+        })
+    }
+
+    /// A new top-level comment; use [`Comment::reply_to`] for replies.
+    pub fn new(
+        plan_id: &str,
+        route_id: Option<&str>,
+        target_type: CommentTarget,
+        target_id: &str,
+        user_id: &str,
+        body: &str,
+    ) -> Self {
+        let now = Utc::now();
+        let id = Uuid::new_v4().to_string();
+
+        Comment {
+            thread_id: id.clone(),
+            id,
+            travel_plan_id: plan_id.to_string(),
+            route_option_id: route_id.map(str::to_string),
+            target_type,
+            target_id: target_id.to_string(),
+            parent_id: None,
+            user_id: user_id.to_string(),
+            body: body.to_string(),
+            mentions: Vec::new(),
+            created_at: now,
+            updated_at: now,
+        }
+    }
+
+    pub fn reply_to(parent: &Comment, user_id: &str, body: &str) -> Self {
+        let now = Utc::now();
+
+        Comment {
+            id: Uuid::new_v4().to_string(),
+            travel_plan_id: parent.travel_plan_id.clone(),
+            route_option_id: parent.route_option_id.clone(),
+            target_type: parent.target_type,
+            target_id: parent.target_id.clone(),
+            thread_id: parent.thread_id.clone(),
+            parent_id: Some(parent.id.clone()),
+            user_id: user_id.to_string(),
+            body: body.to_string(),
+            mentions: Vec::new(),
+            created_at: now,
+            updated_at: now,
+        }
+    }
+
+    /// Blanks a deleted comment that still has replies, keeping its place in
+    /// the thread.
+    pub fn tombstone(&mut self) {
+        self.body = DELETED_BODY.to_string();
+        self.mentions.clear();
+        self.updated_at = Utc::now();
+    }
+
+    pub fn mentions_json(&self) -> String {
+        serde_json::to_string(&self.mentions).unwrap_or_else(|_| "[]".to_string())
+    }
+
+    pub fn insert(&self, conn: &Connection) -> Result<()> {
+        conn.execute(
+            "INSERT INTO comments (
+                id, travel_plan_id, route_option_id, target_type, target_id, thread_id,
+                parent_id, user_id, body, mentions, created_at, updated_at
+            ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)",
+            params![
+                self.id,
+                self.travel_plan_id,
+                self.route_option_id,
+                self.target_type,
+                self.target_id,
+                self.thread_id,
+                self.parent_id,
+                self.user_id,
+                self.body,
+                self.mentions_json(),
+                self.created_at,
+                self.updated_at
+            ],
+        )?;
+        Ok(())
+    }
+
+    pub fn find_by_id(conn: &Connection, id: &str) -> Result<Option<Self>> {
+        let mut stmt = conn.prepare(
+            "SELECT id, travel_plan_id, route_option_id, target_type, target_id, thread_id,
+                    parent_id, user_id, body, mentions, created_at, updated_at
+             FROM comments
+             WHERE id = ?1",
+        )?;
+
+        let mut rows = stmt.query(params![id])?;
+
+        if let Some(row) = rows.next()? {
+            Ok(Some(Self::from_row(row)?))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Top-level comments on a target, oldest first.
+    pub fn find_threads(
+        conn: &Connection,
+        target_type: CommentTarget,
+        target_id: &str,
+        limit: usize,
+        offset: usize,
+    ) -> Result<Vec<Self>> {
+        let mut stmt = conn.prepare(
+            "SELECT id, travel_plan_id, route_option_id, target_type, target_id, thread_id,
+                    parent_id, user_id, body, mentions, created_at, updated_at
+             FROM comments
+             WHERE target_type = ?1 AND target_id = ?2 AND parent_id IS NULL
+             ORDER BY created_at, rowid
+             LIMIT ?3 OFFSET ?4",
+        )?;
+
+        let comment_iter = stmt.query_map(
+            params![target_type, target_id, limit as i64, offset as i64],
+            Self::from_row,
+        )?;
+
+        let mut comments = Vec::new();
+        for comment_result in comment_iter {
+            comments.push(comment_result?);
+        }
+
+        Ok(comments)
+    }
+
+    pub fn count_threads(conn: &Connection, target_type: CommentTarget, target_id: &str) -> Result<usize> {
+        let count: i64 = conn.query_row(
+            "SELECT COUNT(*) FROM comments
+             WHERE target_type = ?1 AND target_id = ?2 AND parent_id IS NULL",
+            params![target_type, target_id],
+            |row| row.get(0),
+        )?;
+        Ok(count as usize)
+    }
+
+    /// Every comment in a thread, the top-level one included, oldest first.
+    pub fn find_by_thread_id(conn: &Connection, thread_id: &str) -> Result<Vec<Self>> {
+        let mut stmt = conn.prepare(
+            "SELECT id, travel_plan_id, route_option_id, target_type, target_id, thread_id,
+                    parent_id, user_id, body, mentions, created_at, updated_at
+             FROM comments
+             WHERE thread_id = ?1
+             ORDER BY created_at, rowid",
+        )?;
+
+        let comment_iter = stmt.query_map(params![thread_id], Self::from_row)?;
+
+        let mut comments = Vec::new();
+        for comment_result in comment_iter {
+            comments.push(comment_result?);
+        }
+
+        Ok(comments)
+    }
+
+    pub fn update(&self, conn: &Connection) -> Result<()> {
+        conn.execute(
+            "UPDATE comments SET body = ?1, mentions = ?2, updated_at = ?3 WHERE id = ?4",
+            params![self.body, self.mentions_json(), self.updated_at, self.id],
+        )?;
+        Ok(())
+    }
+
+    pub fn delete(conn: &Connection, id: &str) -> Result<bool> {
+        let rows_affected = conn.execute("DELETE FROM comments WHERE id = ?1", params![id])?;
+        Ok(rows_affected > 0)
+    }
+
+    /// Removes comments on the route and on its points of interest.
+    pub fn delete_by_route_option_id(conn: &Connection, route_id: &str) -> Result<usize> {
+        let rows_affected = conn.execute(
+            "DELETE FROM comments WHERE route_option_id = ?1",
+            params![route_id],
+        )?;
+        Ok(rows_affected)
+    }
+
+    pub fn delete_by_travel_plan_id(conn: &Connection, plan_id: &str) -> Result<usize> {
+        let rows_affected = conn.execute(
+            "DELETE FROM comments WHERE travel_plan_id = ?1",
+            params![plan_id],
+        )?;
+        Ok(rows_affected)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_mentions() {
+        assert_eq!(
+            parse_mentions("@alice, can you and @bob.smith check? Thanks @alice."),
+            ["alice", "bob.smith"]
+        );
+        assert!(parse_mentions("mail me at carol@example.com or @ nobody").is_empty());
+        assert_eq!(parse_mentions("(@dave)"), ["dave"]);
+    }
+}
@@ -0,0 +1,101 @@
+use serde::{Deserialize, Deserializer, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+/// An ISO 4217 currency code, upper-cased. Deserializing rejects anything
+/// that isn't three letters.
+#[derive(Debug, Serialize, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[serde(transparent)]
+pub struct CurrencyCode(String);
+
+/// Currencies whose minor unit isn't a hundredth, by decimal places.
+const MINOR_UNITS: &[(&str, u32)] = &[
+    ("BIF", 0),
+    ("CLP", 0),
+    ("DJF", 0),
+    ("GNF", 0),
+    ("ISK", 0),
+    ("JPY", 0),
+    ("KMF", 0),
+    ("KRW", 0),
+    ("PYG", 0),
+    ("RWF", 0),
+    ("UGX", 0),
+    ("VND", 0),
+    ("VUV", 0),
+    ("XAF", 0),
+    ("XOF", 0),
+    ("XPF", 0),
+    ("BHD", 3),
+    ("IQD", 3),
+    ("JOD", 3),
+    ("KWD", 3),
+    ("LYD", 3),
+    ("OMR", 3),
+    ("TND", 3),
+    ("CLF", 4),
+];
+
+impl CurrencyCode {
+    pub fn parse(code: &str) -> Result<Self, String> {
+        let upper = code.trim().to_ascii_uppercase();
+        if upper.len() != 3 || !upper.bytes().all(|b| b.is_ascii_uppercase()) {
+            return Err(format!("{} is not a three-letter currency code", code));
+        }
+        Ok(CurrencyCode(upper))
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// Decimal places between the major and the minor unit: 2 for euro
+    /// cents, 0 for yen.
+    pub fn minor_units(&self) -> u32 {
+        MINOR_UNITS
+            .iter()
+            .find(|(code, _)| *code == self.0)
+            .map_or(2, |(_, places)| *places)
+    }
+}
+
+impl fmt::Display for CurrencyCode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl FromStr for CurrencyCode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        CurrencyCode::parse(s)
+    }
+}
+
+impl<'de> Deserialize<'de> for CurrencyCode {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let code = String::deserialize(deserializer)?;
+        CurrencyCode::parse(&code).map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalises_codes_and_knows_their_minor_units() {
+        let yen: CurrencyCode = serde_json::from_str("\" jpy\"").unwrap();
+        assert_eq!(yen.as_str(), "JPY");
+        assert_eq!(yen.minor_units(), 0);
+        assert_eq!(CurrencyCode::parse("kwd").unwrap().minor_units(), 3);
+        assert_eq!(CurrencyCode::parse("EUR").unwrap().minor_units(), 2);
+
+        assert!(serde_json::from_str::<CurrencyCode>("\"Euro\"").is_err());
+        assert!(CurrencyCode::parse("E1R").is_err());
+    }
+}
@@ -0,0 +1,289 @@
+use chrono::{DateTime, NaiveDate, Utc};
+use rusqlite::types::{FromSql, FromSqlError, FromSqlResult, ToSql, ToSqlOutput, ValueRef};
+use rusqlite::{Connection, Result, Row, params};
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::models::merge_patch;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ItineraryItemKind {
+    /// Travelling one of the plan's route options.
+    RouteLeg,
+    /// Time spent at a point of interest; needs a time window.
+    PoiVisit,
+    /// Anything else, described by its title.
+    Activity,
+}
+
+impl ItineraryItemKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ItineraryItemKind::RouteLeg => "route_leg",
+            ItineraryItemKind::PoiVisit => "poi_visit",
+            ItineraryItemKind::Activity => "activity",
+        }
+    }
+}
+
+impl FromStr for ItineraryItemKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "route_leg" => Ok(ItineraryItemKind::RouteLeg),
+            "poi_visit" => Ok(ItineraryItemKind::PoiVisit),
+            "activity" => Ok(ItineraryItemKind::Activity),
+            other => Err(format!("unknown itinerary item kind: {}", other)),
+        }
+    }
+}
+
+impl ToSql for ItineraryItemKind {
+    fn to_sql(&self) -> Result<ToSqlOutput<'_>> {
+        Ok(ToSqlOutput::from(self.as_str()))
+    }
+}
+
+impl FromSql for ItineraryItemKind {
+    fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+        value
+            .as_str()?
+            .parse()
+            .map_err(|e: String| FromSqlError::Other(e.into()))
+    }
+}
+
+/// One entry on a day of a plan's itinerary.
+#[derive(Debug, Serialize, Deserialize, Clone, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ItineraryItem {
+    pub id: String,
+    pub travel_plan_id: String,
+    pub day: NaiveDate,
+    /// Order within the day, lowest first.
+    pub position: i64,
+    pub kind: ItineraryItemKind,
+    pub title: String,
+    pub notes: Option<String>,
+    /// The route travelled, or the route the visited point of interest is on.
+    pub route_option_id: Option<String>,
+    pub poi_id: Option<String>,
+    pub starts_at: Option<DateTime<Utc>>,
+    pub ends_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct NewItineraryItem {
+    pub day: NaiveDate,
+    pub kind: ItineraryItemKind,
+    /// Defaults to the name of the route or point of interest.
+    pub title: Option<String>,
+    pub notes: Option<String>,
+    /// Required for route legs.
+    pub route_option_id: Option<String>,
+    /// Required for point of interest visits.
+    pub poi_id: Option<String>,
+    pub starts_at: Option<DateTime<Utc>>,
+    pub ends_at: Option<DateTime<Utc>>,
+}
+
+/// A JSON Merge Patch for an itinerary item. What the item refers to can't
+/// change; moving it to another day puts it last on that day.
+#[derive(Debug, Default, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct PatchItineraryItem {
+    #[serde(default, deserialize_with = "merge_patch::non_null")]
+    #[schema(value_type = Option<NaiveDate>)]
+    pub day: Option<NaiveDate>,
+    #[serde(default, deserialize_with = "merge_patch::non_null")]
+    pub title: Option<String>,
+    #[serde(default, deserialize_with = "merge_patch::nullable")]
+    #[schema(value_type = Option<String>)]
+    pub notes: Option<Option<String>>,
+    #[serde(default, deserialize_with = "merge_patch::nullable")]
+    #[schema(value_type = Option<DateTime<Utc>>)]
+    pub starts_at: Option<Option<DateTime<Utc>>>,
+    #[serde(default, deserialize_with = "merge_patch::nullable")]
+    #[schema(value_type = Option<DateTime<Utc>>)]
+    pub ends_at: Option<Option<DateTime<Utc>>>,
+}
+
+/// The new order of a day's items; must name each of them exactly once.
+#[derive(Debug, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ReorderItineraryDay {
+    pub item_ids: Vec<String>,
+}
+
+impl ItineraryItem {
+    pub fn from_row(row: &Row) -> Result<Self> {
+        Ok(ItineraryItem {
+            id: row.get(0)?,
+            travel_plan_id: row.get(1)?,
+            day: row.get(2)?,
+            position: row.get(3)?,
+            kind: row.get(4)?,
+            title: row.get(5)?,
+            notes: row.get(6)?,
+            route_option_id: row.get(7)?,
+            poi_id: row.get(8)?,
+            starts_at: row.get(9)?,
+            ends_at: row.get(10)?,
+            created_at: row.get(11)?,
+            updated_at: row.get(12)?,
+        })
+    }
+
+    pub fn new(plan_id: &str, new_item: &NewItineraryItem, title: String, position: i64) -> Self {
+        let now = Utc::now();
+        ItineraryItem {
+            id: Uuid::new_v4().to_string(),
+            travel_plan_id: plan_id.to_string(),
+            day: new_item.day,
+            position,
+            kind: new_item.kind,
+            title,
+            notes: new_item.notes.clone(),
+            route_option_id: new_item.route_option_id.clone(),
+            poi_id: new_item.poi_id.clone(),
+            starts_at: new_item.starts_at,
+            ends_at: new_item.ends_at,
+            created_at: now,
+            updated_at: now,
+        }
+    }
+
+    /// Returns a copy of this item with a merge patch applied. The position
+    /// is left for the caller to settle when the day changes.
+    pub fn with_patch(&self, patch: &PatchItineraryItem) -> Self {
+        let mut patched_item = self.clone();
+
+        if let Some(day) = patch.day {
+            patched_item.day = day;
+        }
+        if let Some(title) = &patch.title {
+            patched_item.title = title.clone();
+        }
+        merge_patch::apply(&mut patched_item.notes, &patch.notes);
+        merge_patch::apply(&mut patched_item.starts_at, &patch.starts_at);
+        merge_patch::apply(&mut patched_item.ends_at, &patch.ends_at);
+
+        patched_item.updated_at = Utc::now();
+
+        patched_item
+    }
+
+    pub fn insert(&self, conn: &Connection) -> Result<()> {
+        conn.execute(
+            "INSERT INTO itinerary_items (
+                id, travel_plan_id, day, position, kind, title, notes, route_option_id, poi_id,
+                starts_at, ends_at, created_at, updated_at
+            ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13)",
+            params![
+                self.id,
+                self.travel_plan_id,
+                self.day,
+                self.position,
+                self.kind,
+                self.title,
+                self.notes,
+                self.route_option_id,
+                self.poi_id,
+                self.starts_at,
+                self.ends_at,
+                self.created_at,
+                self.updated_at
+            ],
+        )?;
+        Ok(())
+    }
+
+    pub fn find_by_id(conn: &Connection, id: &str) -> Result<Option<Self>> {
+        let mut stmt = conn.prepare(
+            "SELECT id, travel_plan_id, day, position, kind, title, notes, route_option_id, poi_id,
+                    starts_at, ends_at, created_at, updated_at
+             FROM itinerary_items
+             WHERE id = ?1",
+        )?;
+
+        let mut rows = stmt.query(params![id])?;
+
+        if let Some(row) = rows.next()? {
+            Ok(Some(Self::from_row(row)?))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Ordered by day, then position.
+    pub fn find_by_travel_plan_id(conn: &Connection, plan_id: &str) -> Result<Vec<Self>> {
+        let mut stmt = conn.prepare(
+            "SELECT id, travel_plan_id, day, position, kind, title, notes, route_option_id, poi_id,
+                    starts_at, ends_at, created_at, updated_at
+             FROM itinerary_items
+             WHERE travel_plan_id = ?1
+             ORDER BY day, position",
+        )?;
+
+        let item_iter = stmt.query_map(params![plan_id], Self::from_row)?;
+
+        let mut items = Vec::new();
+        for item_result in item_iter {
+            items.push(item_result?);
+        }
+
+        Ok(items)
+    }
+
+    pub fn update(&self, conn: &Connection) -> Result<()> {
+        conn.execute(
+            "UPDATE itinerary_items SET
+                day = ?1,
+                position = ?2,
+                title = ?3,
+                notes = ?4,
+                starts_at = ?5,
+                ends_at = ?6,
+                updated_at = ?7
+             WHERE id = ?8",
+            params![
+                self.day,
+                self.position,
+                self.title,
+                self.notes,
+                self.starts_at,
+                self.ends_at,
+                self.updated_at,
+                self.id
+            ],
+        )?;
+        Ok(())
+    }
+
+    pub fn delete(conn: &Connection, id: &str) -> Result<bool> {
+        let rows_affected =
+            conn.execute("DELETE FROM itinerary_items WHERE id = ?1", params![id])?;
+        Ok(rows_affected > 0)
+    }
+
+    pub fn delete_by_route_option_id(conn: &Connection, route_option_id: &str) -> Result<usize> {
+        conn.execute(
+            "DELETE FROM itinerary_items WHERE route_option_id = ?1",
+            params![route_option_id],
+        )
+    }
+
+    pub fn delete_by_travel_plan_id(conn: &Connection, plan_id: &str) -> Result<usize> {
+        conn.execute(
+            "DELETE FROM itinerary_items WHERE travel_plan_id = ?1",
+            params![plan_id],
+        )
+    }
+}
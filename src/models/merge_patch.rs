@@ -0,0 +1,63 @@
+//! Field deserializers for RFC 7396 JSON Merge Patch bodies. A patch names
+//! only the fields it changes; `null` clears a field, so an absent field and
+//! an explicit `null` have to stay distinguishable.
+
+use serde::{Deserialize, Deserializer};
+
+/// For fields that can be cleared. Use with `#[serde(default)]`: an absent
+/// field stays `None`, `null` becomes `Some(None)` and a value `Some(Some(v))`.
+pub fn nullable<'de, D, T>(deserializer: D) -> Result<Option<Option<T>>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    Option::<T>::deserialize(deserializer).map(Some)
+}
+
+/// For fields that can be changed but not cleared. Use with
+/// `#[serde(default)]`: an absent field stays `None` and `null` is rejected.
+pub fn non_null<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    T::deserialize(deserializer).map(Some)
+}
+
+/// Applies a [`nullable`] field to `target`.
+pub fn apply<T: Clone>(target: &mut Option<T>, patch: &Option<Option<T>>) {
+    if let Some(value) = patch {
+        *target = value.clone();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Deserialize)]
+    struct Patch {
+        #[serde(default, deserialize_with = "non_null")]
+        name: Option<String>,
+        #[serde(default, deserialize_with = "nullable")]
+        note: Option<Option<String>>,
+    }
+
+    #[test]
+    fn tells_absent_from_null() {
+        let patch: Patch = serde_json::from_str(r#"{"note": null}"#).unwrap();
+        assert_eq!(patch.name, None);
+        assert_eq!(patch.note, Some(None));
+
+        let patch: Patch = serde_json::from_str(r#"{"name": "Trip", "note": "x"}"#).unwrap();
+        assert_eq!(patch.name.as_deref(), Some("Trip"));
+        assert_eq!(patch.note, Some(Some("x".to_string())));
+
+        let patch: Patch = serde_json::from_str("{}").unwrap();
+        let mut note = Some("kept".to_string());
+        apply(&mut note, &patch.note);
+        assert_eq!(note.as_deref(), Some("kept"));
+
+        assert!(serde_json::from_str::<Patch>(r#"{"name": null}"#).is_err());
+    }
+}
@@ -1,4 +1,17 @@
 pub mod user;
+pub mod audit;
 pub mod travel_plan;
+pub mod plan_member;
+pub mod share_link;
 pub mod route_option;
-pub mod point_of_interest;
\ No newline at end of file
+pub mod route_vote;
+pub mod point_of_interest;
+pub mod comment;
+pub mod plan_event;
+pub mod plan_revision;
+pub mod merge_patch;
+pub mod itinerary;
+pub mod travel_mode;
+pub mod plan_leg;
+pub mod budget;
+pub mod currency;
@@ -0,0 +1,231 @@
+use chrono::{DateTime, Utc};
+use rusqlite::types::{FromSql, FromSqlError, FromSqlResult, ToSql, ToSqlOutput, ValueRef};
+use rusqlite::{Connection, Result, Row, params};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value, json};
+use std::str::FromStr;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::models::travel_plan::TravelPlan;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum PlanEventAction {
+    PlanCreated,
+    PlanUpdated,
+    PlanDeleted,
+    PlanRestored,
+    RoutesGenerated,
+    RouteUpdated,
+    PoiUpdated,
+    RouteDeleted,
+    RoutesCleared,
+    RouteSelected,
+    MemberInvited,
+    MemberJoined,
+    MemberRemoved,
+    ShareLinkCreated,
+    ShareLinkRevoked,
+}
+
+impl PlanEventAction {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PlanEventAction::PlanCreated => "plan_created",
+            PlanEventAction::PlanUpdated => "plan_updated",
+            PlanEventAction::PlanDeleted => "plan_deleted",
+            PlanEventAction::PlanRestored => "plan_restored",
+            PlanEventAction::RoutesGenerated => "routes_generated",
+            PlanEventAction::RouteUpdated => "route_updated",
+            PlanEventAction::PoiUpdated => "poi_updated",
+            PlanEventAction::RouteDeleted => "route_deleted",
+            PlanEventAction::RoutesCleared => "routes_cleared",
+            PlanEventAction::RouteSelected => "route_selected",
+            PlanEventAction::MemberInvited => "member_invited",
+            PlanEventAction::MemberJoined => "member_joined",
+            PlanEventAction::MemberRemoved => "member_removed",
+            PlanEventAction::ShareLinkCreated => "share_link_created",
+            PlanEventAction::ShareLinkRevoked => "share_link_revoked",
+        }
+    }
+}
+
+impl FromStr for PlanEventAction {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "plan_created" => Ok(PlanEventAction::PlanCreated),
+            "plan_updated" => Ok(PlanEventAction::PlanUpdated),
+            "plan_deleted" => Ok(PlanEventAction::PlanDeleted),
+            "plan_restored" => Ok(PlanEventAction::PlanRestored),
+            "routes_generated" => Ok(PlanEventAction::RoutesGenerated),
+            "route_updated" => Ok(PlanEventAction::RouteUpdated),
+            "poi_updated" => Ok(PlanEventAction::PoiUpdated),
+            "route_deleted" => Ok(PlanEventAction::RouteDeleted),
+            "routes_cleared" => Ok(PlanEventAction::RoutesCleared),
+            "route_selected" => Ok(PlanEventAction::RouteSelected),
+            "member_invited" => Ok(PlanEventAction::MemberInvited),
+            "member_joined" => Ok(PlanEventAction::MemberJoined),
+            "member_removed" => Ok(PlanEventAction::MemberRemoved),
+            "share_link_created" => Ok(PlanEventAction::ShareLinkCreated),
+            "share_link_revoked" => Ok(PlanEventAction::ShareLinkRevoked),
+            other => Err(format!("unknown plan event action: {}", other)),
+        }
+    }
+}
+
+impl ToSql for PlanEventAction {
+    fn to_sql(&self) -> Result<ToSqlOutput<'_>> {
+        Ok(ToSqlOutput::from(self.as_str()))
+    }
+}
+
+impl FromSql for PlanEventAction {
+    fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+        value
+            .as_str()?
+            .parse()
+            .map_err(|e: String| FromSqlError::Other(e.into()))
+    }
+}
+
+/// One entry in a plan's activity feed. Events are only ever appended, and
+/// outlive the plan so its deletion stays on record.
+#[derive(Debug, Serialize, Deserialize, Clone, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct PlanEvent {
+    pub id: String,
+    pub travel_plan_id: String,
+    pub actor_id: String,
+    pub action: PlanEventAction,
+    /// Changed fields as `{"field": {"from": old, "to": new}}`.
+    #[schema(value_type = Object)]
+    pub changes: Value,
+    pub created_at: DateTime<Utc>,
+}
+
+/// The `{"field": {"from", "to"}}` entries for every top-level field that
+/// differs between two JSON objects. Missing fields count as `null`.
+pub fn diff(before: &Value, after: &Value) -> Value {
+    let empty = Map::new();
+    let before = before.as_object().unwrap_or(&empty);
+    let after = after.as_object().unwrap_or(&empty);
+
+    let mut changes = Map::new();
+    for key in before.keys().chain(after.keys()) {
+        let from = before.get(key).unwrap_or(&Value::Null);
+        let to = after.get(key).unwrap_or(&Value::Null);
+        if from != to && !changes.contains_key(key) {
+            changes.insert(key.clone(), json!({ "from": from, "to": to }));
+        }
+    }
+
+    Value::Object(changes)
+}
+
+/// The plan fields the activity feed tracks.
+pub fn plan_snapshot(plan: &TravelPlan) -> Value {
+    json!({
+        "name": plan.name,
+        "description": plan.description,
+        "startLocation": plan.start_location,
+        "endLocation": plan.end_location,
+        "startDate": plan.start_date,
+        "endDate": plan.end_date,
+        "homeCurrency": plan.home_currency,
+    })
+}
+
+impl PlanEvent {
+    pub fn from_row(row: &Row) -> Result<Self> {
+        let changes: String = row.get(4)?;
+        Ok(PlanEvent {
+            id: row.get(0)?,
+            travel_plan_id: row.get(1)?,
+            actor_id: row.get(2)?,
+            action: row.get(3)?,
+            changes: serde_json::from_str(&changes).map_err(|e| {
+                rusqlite::Error::FromSqlConversionFailure(4, rusqlite::types::Type::Text, Box::new(e))
+            })?,
+            created_at: row.get(5)?,
+        })
+    }
+
+    pub fn new(plan_id: &str, actor_id: &str, action: PlanEventAction, changes: Value) -> Self {
+        PlanEvent {
+            id: Uuid::new_v4().to_string(),
+            travel_plan_id: plan_id.to_string(),
+            actor_id: actor_id.to_string(),
+            action,
+            changes,
+            created_at: Utc::now(),
+        }
+    }
+
+    pub fn insert(&self, conn: &Connection) -> Result<()> {
+        conn.execute(
+            "INSERT INTO plan_events (id, travel_plan_id, actor_id, action, changes, created_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![
+                self.id,
+                self.travel_plan_id,
+                self.actor_id,
+                self.action,
+                self.changes.to_string(),
+                self.created_at
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// Newest events first.
+    pub fn find_by_travel_plan_id(
+        conn: &Connection,
+        plan_id: &str,
+        limit: usize,
+        offset: usize,
+    ) -> Result<Vec<Self>> {
+        let mut stmt = conn.prepare(
+            "SELECT id, travel_plan_id, actor_id, action, changes, created_at
+             FROM plan_events
+             WHERE travel_plan_id = ?1
+             ORDER BY created_at DESC, rowid DESC
+             LIMIT ?2 OFFSET ?3",
+        )?;
+
+        let event_iter = stmt.query_map(
+            params![plan_id, limit as i64, offset as i64],
+            Self::from_row,
+        )?;
+
+        let mut events = Vec::new();
+        for event_result in event_iter {
+            events.push(event_result?);
+        }
+
+        Ok(events)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn diffs_changed_fields_only() {
+        let before = json!({ "name": "Trip", "endDate": null, "stops": 2 });
+        let after = json!({ "name": "Road trip", "endDate": null, "extra": true });
+
+        assert_eq!(
+            diff(&before, &after),
+            json!({
+                "name": { "from": "Trip", "to": "Road trip" },
+                "stops": { "from": 2, "to": null },
+                "extra": { "from": null, "to": true },
+            })
+        );
+        assert_eq!(diff(&after, &after), json!({}));
+    }
+}
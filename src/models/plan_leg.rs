@@ -0,0 +1,222 @@
+use chrono::{DateTime, Utc};
+use rusqlite::{Connection, Result, Row, params};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::models::merge_patch;
+use crate::models::travel_mode::TravelMode;
+
+/// One stretch of a multi-city trip. A plan's legs are travelled in order of
+/// their position.
+#[derive(Debug, Serialize, Deserialize, Clone, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct PlanLeg {
+    pub id: String,
+    pub travel_plan_id: String,
+    /// Order within the plan, lowest first.
+    pub position: i64,
+    pub start_location: String,
+    pub end_location: String,
+    pub start_date: Option<DateTime<Utc>>,
+    pub end_date: Option<DateTime<Utc>>,
+    pub mode: TravelMode,
+    /// The route option chosen for this leg, one of the leg's own.
+    pub selected_route_id: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct NewPlanLeg {
+    pub start_location: String,
+    pub end_location: String,
+    pub start_date: Option<DateTime<Utc>>,
+    pub end_date: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub mode: TravelMode,
+}
+
+/// A JSON Merge Patch for a leg. The locations and mode can change but not
+/// be cleared.
+#[derive(Debug, Default, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct PatchPlanLeg {
+    #[serde(default, deserialize_with = "merge_patch::non_null")]
+    pub start_location: Option<String>,
+    #[serde(default, deserialize_with = "merge_patch::non_null")]
+    pub end_location: Option<String>,
+    #[serde(default, deserialize_with = "merge_patch::nullable")]
+    #[schema(value_type = Option<DateTime<Utc>>)]
+    pub start_date: Option<Option<DateTime<Utc>>>,
+    #[serde(default, deserialize_with = "merge_patch::nullable")]
+    #[schema(value_type = Option<DateTime<Utc>>)]
+    pub end_date: Option<Option<DateTime<Utc>>>,
+    #[serde(default, deserialize_with = "merge_patch::non_null")]
+    #[schema(value_type = Option<TravelMode>)]
+    pub mode: Option<TravelMode>,
+    #[serde(default, deserialize_with = "merge_patch::nullable")]
+    #[schema(value_type = Option<String>)]
+    pub selected_route_id: Option<Option<String>>,
+}
+
+/// The new order of a plan's legs; must name each of them exactly once.
+#[derive(Debug, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ReorderPlanLegs {
+    pub leg_ids: Vec<String>,
+}
+
+impl PlanLeg {
+    pub fn from_row(row: &Row) -> Result<Self> {
+        Ok(PlanLeg {
+            id: row.get(0)?,
+            travel_plan_id: row.get(1)?,
+            position: row.get(2)?,
+            start_location: row.get(3)?,
+            end_location: row.get(4)?,
+            start_date: row.get(5)?,
+            end_date: row.get(6)?,
+            mode: row.get(7)?,
+            selected_route_id: row.get(8)?,
+            created_at: row.get(9)?,
+            updated_at: row.get(10)?,
+        })
+    }
+
+    pub fn new(plan_id: &str, new_leg: &NewPlanLeg, position: i64) -> Self {
+        let now = Utc::now();
+        PlanLeg {
+            id: Uuid::new_v4().to_string(),
+            travel_plan_id: plan_id.to_string(),
+            position,
+            start_location: new_leg.start_location.clone(),
+            end_location: new_leg.end_location.clone(),
+            start_date: new_leg.start_date,
+            end_date: new_leg.end_date,
+            mode: new_leg.mode,
+            selected_route_id: None,
+            created_at: now,
+            updated_at: now,
+        }
+    }
+
+    /// Returns a copy of this leg with a merge patch applied.
+    pub fn with_patch(&self, patch: &PatchPlanLeg) -> Self {
+        let mut patched_leg = self.clone();
+
+        if let Some(start_location) = &patch.start_location {
+            patched_leg.start_location = start_location.clone();
+        }
+        if let Some(end_location) = &patch.end_location {
+            patched_leg.end_location = end_location.clone();
+        }
+        if let Some(mode) = patch.mode {
+            patched_leg.mode = mode;
+        }
+        merge_patch::apply(&mut patched_leg.start_date, &patch.start_date);
+        merge_patch::apply(&mut patched_leg.end_date, &patch.end_date);
+        merge_patch::apply(&mut patched_leg.selected_route_id, &patch.selected_route_id);
+
+        patched_leg.updated_at = Utc::now();
+
+        patched_leg
+    }
+
+    pub fn insert(&self, conn: &Connection) -> Result<()> {
+        conn.execute(
+            "INSERT INTO plan_legs (
+                id, travel_plan_id, position, start_location, end_location, start_date,
+                end_date, mode, selected_route_id, created_at, updated_at
+            ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
+            params![
+                self.id,
+                self.travel_plan_id,
+                self.position,
+                self.start_location,
+                self.end_location,
+                self.start_date,
+                self.end_date,
+                self.mode,
+                self.selected_route_id,
+                self.created_at,
+                self.updated_at
+            ],
+        )?;
+        Ok(())
+    }
+
+    pub fn find_by_id(conn: &Connection, id: &str) -> Result<Option<Self>> {
+        let mut stmt = conn.prepare(
+            "SELECT id, travel_plan_id, position, start_location, end_location, start_date,
+                    end_date, mode, selected_route_id, created_at, updated_at
+             FROM plan_legs
+             WHERE id = ?1",
+        )?;
+
+        let mut rows = stmt.query(params![id])?;
+
+        if let Some(row) = rows.next()? {
+            Ok(Some(Self::from_row(row)?))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Ordered by position.
+    pub fn find_by_travel_plan_id(conn: &Connection, plan_id: &str) -> Result<Vec<Self>> {
+        let mut stmt = conn.prepare(
+            "SELECT id, travel_plan_id, position, start_location, end_location, start_date,
+                    end_date, mode, selected_route_id, created_at, updated_at
+             FROM plan_legs
+             WHERE travel_plan_id = ?1
+             ORDER BY position",
+        )?;
+
+        let leg_iter = stmt.query_map(params![plan_id], Self::from_row)?;
+
+        let mut legs = Vec::new();
+        for leg_result in leg_iter {
+            legs.push(leg_result?);
+        }
+
+        Ok(legs)
+    }
+
+    pub fn update(&self, conn: &Connection) -> Result<()> {
+        conn.execute(
+            "UPDATE plan_legs SET
+                position = ?1,
+                start_location = ?2,
+                end_location = ?3,
+                start_date = ?4,
+                end_date = ?5,
+                mode = ?6,
+                selected_route_id = ?7,
+                updated_at = ?8
+             WHERE id = ?9",
+            params![
+                self.position,
+                self.start_location,
+                self.end_location,
+                self.start_date,
+                self.end_date,
+                self.mode,
+                self.selected_route_id,
+                self.updated_at,
+                self.id
+            ],
+        )?;
+        Ok(())
+    }
+
+    pub fn delete(conn: &Connection, id: &str) -> Result<bool> {
+        let rows_affected = conn.execute("DELETE FROM plan_legs WHERE id = ?1", params![id])?;
+        Ok(rows_affected > 0)
+    }
+
+    pub fn delete_by_travel_plan_id(conn: &Connection, plan_id: &str) -> Result<usize> {
+        conn.execute("DELETE FROM plan_legs WHERE travel_plan_id = ?1", params![plan_id])
+    }
+}
@@ -0,0 +1,282 @@
+use chrono::{DateTime, Utc};
+use log::info;
+use rusqlite::types::{FromSql, FromSqlError, FromSqlResult, ToSql, ToSqlOutput, ValueRef};
+use rusqlite::{Connection, Result, Row, params};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::models::travel_plan::TravelPlan;
+
+/// Access a user has to a travel plan. Ordered, so `role >= PlanRole::Editor`
+/// reads as "may edit".
+#[derive(
+    Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, ToSchema,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum PlanRole {
+    /// Can see the plan and its routes.
+    Viewer,
+    /// Can also change the plan and generate or delete routes.
+    Editor,
+    /// Can also delete the plan and manage who it is shared with.
+    Owner,
+}
+
+impl PlanRole {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PlanRole::Viewer => "viewer",
+            PlanRole::Editor => "editor",
+            PlanRole::Owner => "owner",
+        }
+    }
+}
+
+impl fmt::Display for PlanRole {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for PlanRole {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "viewer" => Ok(PlanRole::Viewer),
+            "editor" => Ok(PlanRole::Editor),
+            "owner" => Ok(PlanRole::Owner),
+            other => Err(format!("unknown plan role: {}", other)),
+        }
+    }
+}
+
+impl ToSql for PlanRole {
+    fn to_sql(&self) -> Result<ToSqlOutput<'_>> {
+        Ok(ToSqlOutput::from(self.as_str()))
+    }
+}
+
+impl FromSql for PlanRole {
+    fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+        value
+            .as_str()?
+            .parse()
+            .map_err(|e: String| FromSqlError::Other(e.into()))
+    }
+}
+
+/// A membership grants access only once the invited user accepts it.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum MemberStatus {
+    Pending,
+    Accepted,
+}
+
+impl MemberStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MemberStatus::Pending => "pending",
+            MemberStatus::Accepted => "accepted",
+        }
+    }
+}
+
+impl FromStr for MemberStatus {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "pending" => Ok(MemberStatus::Pending),
+            "accepted" => Ok(MemberStatus::Accepted),
+            other => Err(format!("unknown membership status: {}", other)),
+        }
+    }
+}
+
+impl ToSql for MemberStatus {
+    fn to_sql(&self) -> Result<ToSqlOutput<'_>> {
+        Ok(ToSqlOutput::from(self.as_str()))
+    }
+}
+
+impl FromSql for MemberStatus {
+    fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+        value
+            .as_str()?
+            .parse()
+            .map_err(|e: String| FromSqlError::Other(e.into()))
+    }
+}
+
+/// Someone other than the creator with access to a plan. The creator
+/// (`travel_plans.user_id`) is always an owner and has no row here.
+#[derive(Debug, Serialize, Deserialize, Clone, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct PlanMember {
+    pub id: String,
+    pub travel_plan_id: String,
+    pub user_id: String,
+    pub role: PlanRole,
+    pub status: MemberStatus,
+    pub invited_by: String,
+    pub created_at: DateTime<Utc>,
+    pub responded_at: Option<DateTime<Utc>>,
+}
+
+impl PlanMember {
+    pub fn from_row(row: &Row) -> Result<Self> {
+        Ok(PlanMember {
+            id: row.get(0)?,
+            travel_plan_id: row.get(1)?,
+            user_id: row.get(2)?,
+            role: row.get(3)?,
+            status: row.get(4)?,
+            invited_by: row.get(5)?,
+            created_at: row.get(6)?,
+            responded_at: row.get(7)?,
+        })
+    }
+
+    /// A pending invitation for `user_id`.
+    pub fn invite(plan_id: &str, user_id: &str, role: PlanRole, invited_by: &str) -> Self {
+        PlanMember {
+            id: Uuid::new_v4().to_string(),
+            travel_plan_id: plan_id.to_string(),
+            user_id: user_id.to_string(),
+            role,
+            status: MemberStatus::Pending,
+            invited_by: invited_by.to_string(),
+            created_at: Utc::now(),
+            responded_at: None,
+        }
+    }
+
+    pub fn insert(&self, conn: &Connection) -> Result<()> {
+        conn.execute(
+            "INSERT INTO plan_members (
+                id, travel_plan_id, user_id, role, status, invited_by, created_at, responded_at
+            ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+            params![
+                self.id,
+                self.travel_plan_id,
+                self.user_id,
+                self.role,
+                self.status,
+                self.invited_by,
+                self.created_at,
+                self.responded_at
+            ],
+        )?;
+
+        info!(
+            "Invited user {} to travel plan {} as {}",
+            self.user_id, self.travel_plan_id, self.role
+        );
+        Ok(())
+    }
+
+    pub fn find(conn: &Connection, plan_id: &str, user_id: &str) -> Result<Option<Self>> {
+        let mut stmt = conn.prepare(
+            "SELECT id, travel_plan_id, user_id, role, status, invited_by, created_at, responded_at
+             FROM plan_members
+             WHERE travel_plan_id = ?1 AND user_id = ?2",
+        )?;
+
+        let mut rows = stmt.query(params![plan_id, user_id])?;
+
+        if let Some(row) = rows.next()? {
+            Ok(Some(Self::from_row(row)?))
+        } else {
+            Ok(None)
+        }
+    }
+
+    pub fn find_by_travel_plan_id(conn: &Connection, plan_id: &str) -> Result<Vec<Self>> {
+        let mut stmt = conn.prepare(
+            "SELECT id, travel_plan_id, user_id, role, status, invited_by, created_at, responded_at
+             FROM plan_members
+             WHERE travel_plan_id = ?1
+             ORDER BY created_at, rowid",
+        )?;
+
+        let member_iter = stmt.query_map(params![plan_id], Self::from_row)?;
+
+        let mut members = Vec::new();
+        for member_result in member_iter {
+            members.push(member_result?);
+        }
+
+        Ok(members)
+    }
+
+    pub fn find_by_user_id(conn: &Connection, user_id: &str) -> Result<Vec<Self>> {
+        let mut stmt = conn.prepare(
+            "SELECT id, travel_plan_id, user_id, role, status, invited_by, created_at, responded_at
+             FROM plan_members
+             WHERE user_id = ?1
+             ORDER BY created_at DESC, rowid DESC",
+        )?;
+
+        let member_iter = stmt.query_map(params![user_id], Self::from_row)?;
+
+        let mut members = Vec::new();
+        for member_result in member_iter {
+            members.push(member_result?);
+        }
+
+        Ok(members)
+    }
+
+    /// Plans whose invitations `user_id` has accepted, newest first.
+    pub fn find_shared_plans(conn: &Connection, user_id: &str) -> Result<Vec<TravelPlan>> {
+        let mut stmt = conn.prepare(
+            "SELECT p.id, p.user_id, p.name, p.description, p.start_location, p.end_location,
+                    p.start_date, p.end_date, p.created_at, p.updated_at,
+                    p.selected_route_id, p.home_currency
+             FROM travel_plans p
+             JOIN plan_members m ON m.travel_plan_id = p.id
+             WHERE m.user_id = ?1 AND m.status = 'accepted'
+             ORDER BY p.created_at DESC",
+        )?;
+
+        let plan_iter = stmt.query_map(params![user_id], TravelPlan::from_row)?;
+
+        let mut plans = Vec::new();
+        for plan_result in plan_iter {
+            plans.push(plan_result?);
+        }
+
+        Ok(plans)
+    }
+
+    pub fn update(&self, conn: &Connection) -> Result<()> {
+        conn.execute(
+            "UPDATE plan_members SET role = ?1, status = ?2, responded_at = ?3 WHERE id = ?4",
+            params![self.role, self.status, self.responded_at, self.id],
+        )?;
+        Ok(())
+    }
+
+    pub fn delete(conn: &Connection, plan_id: &str, user_id: &str) -> Result<bool> {
+        let rows_affected = conn.execute(
+            "DELETE FROM plan_members WHERE travel_plan_id = ?1 AND user_id = ?2",
+            params![plan_id, user_id],
+        )?;
+
+        info!("Removed user {} from travel plan {}", user_id, plan_id);
+        Ok(rows_affected > 0)
+    }
+
+    pub fn delete_by_travel_plan_id(conn: &Connection, plan_id: &str) -> Result<usize> {
+        let rows_affected = conn.execute(
+            "DELETE FROM plan_members WHERE travel_plan_id = ?1",
+            params![plan_id],
+        )?;
+        Ok(rows_affected)
+    }
+}
@@ -0,0 +1,155 @@
+use chrono::{DateTime, Utc};
+use rusqlite::{Connection, Result, Row, params};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::models::travel_plan::TravelPlan;
+
+/// A plan's editable fields as they were before an update replaced them.
+#[derive(Debug, Serialize, Deserialize, Clone, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct PlanRevision {
+    pub id: String,
+    pub travel_plan_id: String,
+    /// Numbered from 1 per plan, oldest first. Assigned when the revision is
+    /// stored.
+    pub revision: i64,
+    pub name: String,
+    pub description: Option<String>,
+    pub start_location: String,
+    pub end_location: String,
+    pub start_date: Option<DateTime<Utc>>,
+    pub end_date: Option<DateTime<Utc>>,
+    pub home_currency: Option<String>,
+    /// The user whose update replaced this state.
+    pub replaced_by: String,
+    /// When this state was replaced.
+    pub created_at: DateTime<Utc>,
+}
+
+impl PlanRevision {
+    pub fn from_row(row: &Row) -> Result<Self> {
+        Ok(PlanRevision {
+            id: row.get(0)?,
+            travel_plan_id: row.get(1)?,
+            revision: row.get(2)?,
+            name: row.get(3)?,
+            description: row.get(4)?,
+            start_location: row.get(5)?,
+            end_location: row.get(6)?,
+            start_date: row.get(7)?,
+            end_date: row.get(8)?,
+            replaced_by: row.get(9)?,
+            created_at: row.get(10)?,
+            home_currency: row.get(11)?,
+        })
+    }
+
+    pub fn new(plan: &TravelPlan, replaced_by: &str) -> Self {
+        PlanRevision {
+            id: Uuid::new_v4().to_string(),
+            travel_plan_id: plan.id.clone(),
+            revision: 0,
+            name: plan.name.clone(),
+            description: plan.description.clone(),
+            start_location: plan.start_location.clone(),
+            end_location: plan.end_location.clone(),
+            start_date: plan.start_date,
+            end_date: plan.end_date,
+            home_currency: plan.home_currency.clone(),
+            replaced_by: replaced_by.to_string(),
+            created_at: Utc::now(),
+        }
+    }
+
+    /// The plan with this revision's fields put back. Everything that isn't
+    /// revisioned, like the selected route, is kept.
+    pub fn apply_to(&self, plan: &TravelPlan) -> TravelPlan {
+        TravelPlan {
+            name: self.name.clone(),
+            description: self.description.clone(),
+            start_location: self.start_location.clone(),
+            end_location: self.end_location.clone(),
+            start_date: self.start_date,
+            end_date: self.end_date,
+            home_currency: self.home_currency.clone(),
+            updated_at: Utc::now(),
+            ..plan.clone()
+        }
+    }
+
+    /// Stores the revision under the plan's next number, which is worked out
+    /// by the insert itself so concurrent saves can't pick the same one.
+    /// Returns the number assigned.
+    pub fn insert(&self, conn: &Connection) -> Result<i64> {
+        conn.query_row(
+            "INSERT INTO plan_revisions (
+                id, travel_plan_id, revision, name, description, start_location, end_location,
+                start_date, end_date, replaced_by, created_at, home_currency
+            )
+            SELECT ?1, ?2, COALESCE(MAX(revision), 0) + 1, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11
+            FROM plan_revisions
+            WHERE travel_plan_id = ?2
+            RETURNING revision",
+            params![
+                self.id,
+                self.travel_plan_id,
+                self.name,
+                self.description,
+                self.start_location,
+                self.end_location,
+                self.start_date,
+                self.end_date,
+                self.replaced_by,
+                self.created_at,
+                self.home_currency
+            ],
+            |row| row.get(0),
+        )
+    }
+
+    /// Newest revision first.
+    pub fn find_by_travel_plan_id(conn: &Connection, plan_id: &str) -> Result<Vec<Self>> {
+        let mut stmt = conn.prepare(
+            "SELECT id, travel_plan_id, revision, name, description, start_location, end_location,
+                    start_date, end_date, replaced_by, created_at, home_currency
+             FROM plan_revisions
+             WHERE travel_plan_id = ?1
+             ORDER BY revision DESC",
+        )?;
+
+        let revision_iter = stmt.query_map(params![plan_id], Self::from_row)?;
+
+        let mut revisions = Vec::new();
+        for revision_result in revision_iter {
+            revisions.push(revision_result?);
+        }
+
+        Ok(revisions)
+    }
+
+    pub fn find_by_revision(conn: &Connection, plan_id: &str, revision: i64) -> Result<Option<Self>> {
+        let mut stmt = conn.prepare(
+            "SELECT id, travel_plan_id, revision, name, description, start_location, end_location,
+                    start_date, end_date, replaced_by, created_at, home_currency
+             FROM plan_revisions
+             WHERE travel_plan_id = ?1 AND revision = ?2",
+        )?;
+
+        let mut rows = stmt.query(params![plan_id, revision])?;
+
+        if let Some(row) = rows.next()? {
+            Ok(Some(Self::from_row(row)?))
+        } else {
+            Ok(None)
+        }
+    }
+
+    pub fn delete_by_travel_plan_id(conn: &Connection, plan_id: &str) -> Result<usize> {
+        conn.execute(
+            "DELETE FROM plan_revisions WHERE travel_plan_id = ?1",
+            params![plan_id],
+        )
+    }
+}
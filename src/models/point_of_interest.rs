@@ -6,6 +6,9 @@ use serde::{Deserialize, Serialize};
 use utoipa::ToSchema;
 use uuid::Uuid;
 
+use crate::models::merge_patch;
+use crate::models::route_option::RouteOption;
+
 #[derive(Debug, Serialize, Deserialize, Clone, ToSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct PointOfInterest {
@@ -28,15 +31,19 @@ pub struct NewPointOfInterest {
     pub coordinates: String,
 }
 
-#[derive(Debug, Deserialize, ToSchema)]
+/// A JSON Merge Patch for a point of interest. Absent fields are left
+/// alone; `null` clears the description or category.
+#[derive(Debug, Default, Deserialize, ToSchema)]
 #[serde(rename_all = "camelCase")]
-pub struct UpdatePointOfInterest {
-    #[allow(dead_code)]
+pub struct PatchPointOfInterest {
+    #[serde(default, deserialize_with = "merge_patch::non_null")]
     pub name: Option<String>,
-    #[allow(dead_code)]
-    pub description: Option<String>,
-    #[allow(dead_code)]
-    pub category: Option<String>,
+    #[serde(default, deserialize_with = "merge_patch::nullable")]
+    #[schema(value_type = Option<String>)]
+    pub description: Option<Option<String>>,
+    #[serde(default, deserialize_with = "merge_patch::nullable")]
+    #[schema(value_type = Option<String>)]
+    pub category: Option<Option<String>>,
 }
 
 impl PointOfInterest {
@@ -52,36 +59,36 @@ impl PointOfInterest {
         })
     }
 
-    pub fn create(conn: &Connection, new_poi: &NewPointOfInterest) -> Result<Self> {
-        let id = Uuid::new_v4().to_string();
-        let now = Utc::now();
+    pub fn new(new_poi: &NewPointOfInterest) -> Self {
+        PointOfInterest {
+            id: Uuid::new_v4().to_string(),
+            route_option_id: new_poi.route_option_id.clone(),
+            name: new_poi.name.clone(),
+            description: new_poi.description.clone(),
+            category: new_poi.category.clone(),
+            coordinates: new_poi.coordinates.clone(),
+            created_at: Utc::now(),
+        }
+    }
 
+    pub fn insert(&self, conn: &Connection) -> Result<()> {
         conn.execute(
             "INSERT INTO points_of_interest (
                 id, route_option_id, name, description, category, coordinates, created_at
             ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
             params![
-                id,
-                new_poi.route_option_id,
-                new_poi.name,
-                new_poi.description,
-                new_poi.category,
-                new_poi.coordinates,
-                now
+                self.id,
+                self.route_option_id,
+                self.name,
+                self.description,
+                self.category,
+                self.coordinates,
+                self.created_at
             ],
         )?;
 
-        info!("Created new point of interest: {}", new_poi.name);
-
-        Ok(PointOfInterest {
-            id,
-            route_option_id: new_poi.route_option_id.clone(),
-            name: new_poi.name.clone(),
-            description: new_poi.description.clone(),
-            category: new_poi.category.clone(),
-            coordinates: new_poi.coordinates.clone(),
-            created_at: now,
-        })
+        info!("Created new point of interest: {}", self.name);
+        Ok(())
     }
 
     #[allow(dead_code)]
@@ -95,7 +102,7 @@ impl PointOfInterest {
         let mut rows = stmt.query(params![id])?;
 
         if let Some(row) = rows.next()? {
-            Ok(Some(Self::from_row(&row)?))
+            Ok(Some(Self::from_row(row)?))
         } else {
             Ok(None)
         }
@@ -108,7 +115,7 @@ impl PointOfInterest {
              WHERE route_option_id = ?1",
         )?;
 
-        let poi_iter = stmt.query_map(params![route_option_id], |row| Self::from_row(row))?;
+        let poi_iter = stmt.query_map(params![route_option_id], Self::from_row)?;
 
         let mut pois = Vec::new();
         for poi_result in poi_iter {
@@ -118,39 +125,32 @@ impl PointOfInterest {
         Ok(pois)
     }
 
-    #[allow(dead_code)]
-    pub fn update(&self, conn: &Connection, update: &UpdatePointOfInterest) -> Result<Self> {
-        let mut updated_poi = self.clone();
+    /// Returns a copy of this point of interest with a merge patch applied.
+    pub fn with_patch(&self, patch: &PatchPointOfInterest) -> Self {
+        let mut patched_poi = self.clone();
 
-        if let Some(name) = &update.name {
-            updated_poi.name = name.clone();
+        if let Some(name) = &patch.name {
+            patched_poi.name = name.clone();
         }
+        merge_patch::apply(&mut patched_poi.description, &patch.description);
+        merge_patch::apply(&mut patched_poi.category, &patch.category);
 
-        if let Some(description) = &update.description {
-            updated_poi.description = Some(description.clone());
-        }
-
-        if let Some(category) = &update.category {
-            updated_poi.category = Some(category.clone());
-        }
+        patched_poi
+    }
 
+    pub fn update(&self, conn: &Connection) -> Result<()> {
         conn.execute(
             "UPDATE points_of_interest SET
                 name = ?1,
                 description = ?2,
                 category = ?3
              WHERE id = ?4",
-            params![
-                updated_poi.name,
-                updated_poi.description,
-                updated_poi.category,
-                self.id
-            ],
+            params![self.name, self.description, self.category, self.id],
         )?;
 
-        info!("Updated point of interest: {}", updated_poi.name);
+        info!("Updated point of interest: {}", self.name);
 
-        Ok(updated_poi)
+        Ok(())
     }
 
     #[allow(dead_code)]
@@ -183,62 +183,42 @@ impl PointOfInterest {
     }
 
     // Generate random points of interest for a route option
-    pub fn generate_random_pois(
-        conn: &Connection,
-        route_option_id: &str,
-        count: usize,
-    ) -> Result<Vec<Self>> {
+    pub fn generate_random_pois(route: &RouteOption, count: usize) -> Vec<Self> {
         let mut rng = rand::thread_rng();
         let mut pois = Vec::new();
 
-        // Get the route option to use its waypoints
-        let mut stmt = conn.prepare(
-            "SELECT start_coordinates, end_coordinates, waypoints FROM route_options WHERE id = ?1",
-        )?;
-
-        let mut rows = stmt.query(params![route_option_id])?;
-
-        if let Some(row) = rows.next()? {
-            let start_coords: String = row.get(0)?;
-            let end_coords: String = row.get(1)?;
-            let waypoints: Option<String> = row.get(2)?;
-
-            // Categories for points of interest
-            let categories = vec![
-                "Restaurant",
-                "Museum",
-                "Park",
-                "Hotel",
-                "Landmark",
-                "Beach",
-                "Mountain",
-                "Lake",
-                "Forest",
-                "Historical Site",
-            ];
-
-            for i in 0..count {
-                // Generate a random coordinate near the route
-                let coords = if i == 0 {
-                    // Near start
-                    start_coords.clone()
-                } else if i == count - 1 {
-                    // Near end
-                    end_coords.clone()
-                } else if let Some(waypoints_str) = &waypoints {
-                    // Near a waypoint if available
-                    let waypoint_list: Vec<&str> = waypoints_str.split(';').collect();
-                    if !waypoint_list.is_empty() {
-                        let idx = rng.gen_range(0..waypoint_list.len());
-                        waypoint_list[idx].to_string()
-                    } else {
-                        // Random coordinates
-                        format!(
-                            "{},{}",
-                            rng.gen_range(-90.0..90.0),
-                            rng.gen_range(-180.0..180.0)
-                        )
-                    }
+        let start_coords = &route.start_coordinates;
+        let end_coords = &route.end_coordinates;
+        let waypoints = &route.waypoints;
+
+        // Categories for points of interest
+        let categories = vec![
+            "Restaurant",
+            "Museum",
+            "Park",
+            "Hotel",
+            "Landmark",
+            "Beach",
+            "Mountain",
+            "Lake",
+            "Forest",
+            "Historical Site",
+        ];
+
+        for i in 0..count {
+            // Generate a random coordinate near the route
+            let coords = if i == 0 {
+                // Near start
+                start_coords.clone()
+            } else if i == count - 1 {
+                // Near end
+                end_coords.clone()
+            } else if let Some(waypoints_str) = &waypoints {
+                // Near a waypoint if available
+                let waypoint_list: Vec<&str> = waypoints_str.split(';').collect();
+                if !waypoint_list.is_empty() {
+                    let idx = rng.gen_range(0..waypoint_list.len());
+                    waypoint_list[idx].to_string()
                 } else {
                     // Random coordinates
                     format!(
@@ -246,155 +226,158 @@ impl PointOfInterest {
                         rng.gen_range(-90.0..90.0),
                         rng.gen_range(-180.0..180.0)
                     )
-                };
-
-                // Generate a random name and category
-                let category_idx = rng.gen_range(0..categories.len());
-                let category = categories[category_idx];
-
-                let poi_name = match category {
-                    "Restaurant" => format!(
-                        "The {} Restaurant",
-                        ["Delicious", "Tasty", "Gourmet", "Cozy", "Fancy"][rng.gen_range(0..5)]
-                    ),
-                    "Museum" => format!(
-                        "{} Museum",
-                        ["Art", "History", "Science", "Natural", "Modern"][rng.gen_range(0..5)]
-                    ),
-                    "Park" => format!(
-                        "{} Park",
-                        ["Central", "City", "National", "Memorial", "State"][rng.gen_range(0..5)]
-                    ),
-                    "Hotel" => format!(
-                        "Hotel {}",
-                        ["Grand", "Royal", "Luxury", "Comfort", "Plaza"][rng.gen_range(0..5)]
-                    ),
-                    "Landmark" => format!(
-                        "The {} Monument",
-                        ["Historic", "Ancient", "Famous", "Iconic", "Majestic"]
-                            [rng.gen_range(0..5)]
-                    ),
-                    "Beach" => format!(
-                        "{} Beach",
-                        ["Sandy", "Golden", "Paradise", "Sunset", "Crystal"][rng.gen_range(0..5)]
-                    ),
-                    "Mountain" => format!(
-                        "Mount {}",
-                        ["Everest", "Fuji", "Kilimanjaro", "McKinley", "Blanc"]
-                            [rng.gen_range(0..5)]
-                    ),
-                    "Lake" => format!(
-                        "Lake {}",
-                        ["Superior", "Victoria", "Michigan", "Geneva", "Como"][rng.gen_range(0..5)]
-                    ),
-                    "Forest" => format!(
-                        "{} Forest",
-                        ["Enchanted", "Dark", "Ancient", "Mystic", "Green"][rng.gen_range(0..5)]
-                    ),
-                    _ => format!(
-                        "{} Site",
-                        [
-                            "Historical",
-                            "Cultural",
-                            "Heritage",
-                            "Ancient",
-                            "Traditional"
-                        ][rng.gen_range(0..5)]
-                    ),
-                };
-
-                let description = match category {
-                    "Restaurant" => Some(format!(
-                        "A {} restaurant with excellent food and service.",
-                        [
-                            "cozy",
-                            "fancy",
-                            "family-friendly",
-                            "romantic",
-                            "traditional"
-                        ][rng.gen_range(0..5)]
-                    )),
-                    "Museum" => Some(format!(
-                        "A museum showcasing {} exhibits.",
-                        [
-                            "historical",
-                            "artistic",
-                            "scientific",
-                            "cultural",
-                            "interactive"
-                        ][rng.gen_range(0..5)]
-                    )),
-                    "Park" => Some(format!(
-                        "A beautiful park with {} views.",
-                        [
-                            "scenic",
-                            "panoramic",
-                            "breathtaking",
-                            "relaxing",
-                            "peaceful"
-                        ][rng.gen_range(0..5)]
-                    )),
-                    "Hotel" => Some(format!(
-                        "A {} hotel with excellent amenities.",
-                        ["luxury", "boutique", "historic", "modern", "charming"]
-                            [rng.gen_range(0..5)]
-                    )),
-                    "Landmark" => Some(format!(
-                        "A famous landmark known for its {} architecture.",
-                        ["impressive", "unique", "historic", "stunning", "iconic"]
-                            [rng.gen_range(0..5)]
-                    )),
-                    "Beach" => Some(format!(
-                        "A {} beach with crystal clear waters.",
-                        ["sandy", "secluded", "popular", "pristine", "tropical"]
-                            [rng.gen_range(0..5)]
-                    )),
-                    "Mountain" => Some(format!(
-                        "A majestic mountain offering {} hiking trails.",
-                        ["challenging", "scenic", "popular", "diverse", "beautiful"]
-                            [rng.gen_range(0..5)]
-                    )),
-                    "Lake" => Some(format!(
-                        "A {} lake perfect for outdoor activities.",
-                        ["serene", "vast", "picturesque", "clear", "beautiful"]
-                            [rng.gen_range(0..5)]
-                    )),
-                    "Forest" => Some(format!(
-                        "A {} forest with diverse flora and fauna.",
-                        ["dense", "ancient", "magical", "lush", "protected"][rng.gen_range(0..5)]
-                    )),
-                    _ => Some(format!(
-                        "A {} historical site with rich heritage.",
-                        [
-                            "fascinating",
-                            "well-preserved",
-                            "ancient",
-                            "significant",
-                            "mysterious"
-                        ][rng.gen_range(0..5)]
-                    )),
-                };
-
-                let new_poi = NewPointOfInterest {
-                    route_option_id: route_option_id.to_string(),
-                    name: poi_name,
-                    description,
-                    category: Some(category.to_string()),
-                    coordinates: coords,
-                };
-
-                let poi = Self::create(conn, &new_poi)?;
-                pois.push(poi);
-            }
-
-            info!(
-                "Generated {} random points of interest for route option ID: {}",
-                count, route_option_id
-            );
-            Ok(pois)
-        } else {
-            info!("No route option found with ID: {}", route_option_id);
-            Ok(Vec::new())
+                }
+            } else {
+                // Random coordinates
+                format!(
+                    "{},{}",
+                    rng.gen_range(-90.0..90.0),
+                    rng.gen_range(-180.0..180.0)
+                )
+            };
+
+            // Generate a random name and category
+            let category_idx = rng.gen_range(0..categories.len());
+            let category = categories[category_idx];
+
+            let poi_name = match category {
+                "Restaurant" => format!(
+                    "The {} Restaurant",
+                    ["Delicious", "Tasty", "Gourmet", "Cozy", "Fancy"][rng.gen_range(0..5)]
+                ),
+                "Museum" => format!(
+                    "{} Museum",
+                    ["Art", "History", "Science", "Natural", "Modern"][rng.gen_range(0..5)]
+                ),
+                "Park" => format!(
+                    "{} Park",
+                    ["Central", "City", "National", "Memorial", "State"][rng.gen_range(0..5)]
+                ),
+                "Hotel" => format!(
+                    "Hotel {}",
+                    ["Grand", "Royal", "Luxury", "Comfort", "Plaza"][rng.gen_range(0..5)]
+                ),
+                "Landmark" => format!(
+                    "The {} Monument",
+                    ["Historic", "Ancient", "Famous", "Iconic", "Majestic"]
+                        [rng.gen_range(0..5)]
+                ),
+                "Beach" => format!(
+                    "{} Beach",
+                    ["Sandy", "Golden", "Paradise", "Sunset", "Crystal"][rng.gen_range(0..5)]
+                ),
+                "Mountain" => format!(
+                    "Mount {}",
+                    ["Everest", "Fuji", "Kilimanjaro", "McKinley", "Blanc"]
+                        [rng.gen_range(0..5)]
+                ),
+                "Lake" => format!(
+                    "Lake {}",
+                    ["Superior", "Victoria", "Michigan", "Geneva", "Como"][rng.gen_range(0..5)]
+                ),
+                "Forest" => format!(
+                    "{} Forest",
+                    ["Enchanted", "Dark", "Ancient", "Mystic", "Green"][rng.gen_range(0..5)]
+                ),
+                _ => format!(
+                    "{} Site",
+                    [
+                        "Historical",
+                        "Cultural",
+                        "Heritage",
+                        "Ancient",
+                        "Traditional"
+                    ][rng.gen_range(0..5)]
+                ),
+            };
+
+            let description = match category {
+                "Restaurant" => Some(format!(
+                    "A {} restaurant with excellent food and service.",
+                    [
+                        "cozy",
+                        "fancy",
+                        "family-friendly",
+                        "romantic",
+                        "traditional"
+                    ][rng.gen_range(0..5)]
+                )),
+                "Museum" => Some(format!(
+                    "A museum showcasing {} exhibits.",
+                    [
+                        "historical",
+                        "artistic",
+                        "scientific",
+                        "cultural",
+                        "interactive"
+                    ][rng.gen_range(0..5)]
+                )),
+                "Park" => Some(format!(
+                    "A beautiful park with {} views.",
+                    [
+                        "scenic",
+                        "panoramic",
+                        "breathtaking",
+                        "relaxing",
+                        "peaceful"
+                    ][rng.gen_range(0..5)]
+                )),
+                "Hotel" => Some(format!(
+                    "A {} hotel with excellent amenities.",
+                    ["luxury", "boutique", "historic", "modern", "charming"]
+                        [rng.gen_range(0..5)]
+                )),
+                "Landmark" => Some(format!(
+                    "A famous landmark known for its {} architecture.",
+                    ["impressive", "unique", "historic", "stunning", "iconic"]
+                        [rng.gen_range(0..5)]
+                )),
+                "Beach" => Some(format!(
+                    "A {} beach with crystal clear waters.",
+                    ["sandy", "secluded", "popular", "pristine", "tropical"]
+                        [rng.gen_range(0..5)]
+                )),
+                "Mountain" => Some(format!(
+                    "A majestic mountain offering {} hiking trails.",
+                    ["challenging", "scenic", "popular", "diverse", "beautiful"]
+                        [rng.gen_range(0..5)]
+                )),
+                "Lake" => Some(format!(
+                    "A {} lake perfect for outdoor activities.",
+                    ["serene", "vast", "picturesque", "clear", "beautiful"]
+                        [rng.gen_range(0..5)]
+                )),
+                "Forest" => Some(format!(
+                    "A {} forest with diverse flora and fauna.",
+                    ["dense", "ancient", "magical", "lush", "protected"][rng.gen_range(0..5)]
+                )),
+                _ => Some(format!(
+                    "A {} historical site with rich heritage.",
+                    [
+                        "fascinating",
+                        "well-preserved",
+                        "ancient",
+                        "significant",
+                        "mysterious"
+                    ][rng.gen_range(0..5)]
+                )),
+            };
+
+            let new_poi = NewPointOfInterest {
+                route_option_id: route.id.clone(),
+                name: poi_name,
+                description,
+                category: Some(category.to_string()),
+                coordinates: coords,
+            };
+
+            pois.push(Self::new(&new_poi));
         }
+
+        info!(
+            "Generated {} random points of interest for route option ID: {}",
+            count, route.id
+        );
+        pois
     }
-}
+}
\ No newline at end of file
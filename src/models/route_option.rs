@@ -1,16 +1,43 @@
 use chrono::{DateTime, Utc};
 use log::info;
 use rand::Rng;
-use rusqlite::{Connection, Result, Row, params};
+use rusqlite::{Connection, OptionalExtension, Result, Row, params};
 use serde::{Deserialize, Serialize};
 use utoipa::ToSchema;
 use uuid::Uuid;
 
+use crate::models::merge_patch;
+use crate::models::plan_leg::PlanLeg;
+use crate::models::travel_mode::TravelMode;
+use crate::models::travel_plan::TravelPlan;
+
+/// What a route owns, in the order it is deleted before the route.
+const DELETE_ROUTE_CONTENTS: &[&str] = &[
+    "DELETE FROM itinerary_items WHERE route_option_id = ?1",
+    "DELETE FROM comments WHERE route_option_id = ?1",
+    "DELETE FROM route_votes WHERE route_option_id = ?1",
+    "DELETE FROM points_of_interest WHERE route_option_id = ?1",
+];
+
+/// The plan's routes that neither the plan nor one of its legs has selected.
+const UNSELECTED_ROUTE_IDS: &str = "SELECT id FROM route_options
+     WHERE travel_plan_id = ?1
+       AND id NOT IN (
+           SELECT selected_route_id FROM travel_plans
+           WHERE id = ?1 AND selected_route_id IS NOT NULL
+           UNION
+           SELECT selected_route_id FROM plan_legs
+           WHERE travel_plan_id = ?1 AND selected_route_id IS NOT NULL
+       )";
+
 #[derive(Debug, Serialize, Deserialize, Clone, ToSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct RouteOption {
     pub id: String,
     pub travel_plan_id: String,
+    /// The leg of a multi-leg plan the route covers; `None` for the whole
+    /// trip.
+    pub leg_id: Option<String>,
     pub name: String,
     pub description: Option<String>,
     pub distance: Option<f64>,
@@ -19,12 +46,16 @@ pub struct RouteOption {
     pub end_coordinates: String,
     pub waypoints: Option<String>,
     pub created_at: DateTime<Utc>,
+    /// Last edit to the route or one of its points of interest.
+    pub updated_at: DateTime<Utc>,
 }
 
 #[derive(Debug, Deserialize, ToSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct NewRouteOption {
     pub travel_plan_id: String,
+    #[serde(default)]
+    pub leg_id: Option<String>,
     pub name: String,
     pub description: Option<String>,
     pub distance: Option<f64>,
@@ -34,19 +65,25 @@ pub struct NewRouteOption {
     pub waypoints: Option<String>,
 }
 
-#[derive(Debug, Deserialize, ToSchema)]
+/// A JSON Merge Patch for a route option. Absent fields are left alone;
+/// `null` clears any field but the name.
+#[derive(Debug, Default, Deserialize, ToSchema)]
 #[serde(rename_all = "camelCase")]
-pub struct UpdateRouteOption {
-    #[allow(dead_code)]
+pub struct PatchRouteOption {
+    #[serde(default, deserialize_with = "merge_patch::non_null")]
     pub name: Option<String>,
-    #[allow(dead_code)]
-    pub description: Option<String>,
-    #[allow(dead_code)]
-    pub distance: Option<f64>,
-    #[allow(dead_code)]
-    pub duration: Option<i64>,
-    #[allow(dead_code)]
-    pub waypoints: Option<String>,
+    #[serde(default, deserialize_with = "merge_patch::nullable")]
+    #[schema(value_type = Option<String>)]
+    pub description: Option<Option<String>>,
+    #[serde(default, deserialize_with = "merge_patch::nullable")]
+    #[schema(value_type = Option<f64>)]
+    pub distance: Option<Option<f64>>,
+    #[serde(default, deserialize_with = "merge_patch::nullable")]
+    #[schema(value_type = Option<i64>)]
+    pub duration: Option<Option<i64>>,
+    #[serde(default, deserialize_with = "merge_patch::nullable")]
+    #[schema(value_type = Option<String>)]
+    pub waypoints: Option<Option<String>>,
 }
 
 impl RouteOption {
@@ -54,6 +91,7 @@ impl RouteOption {
         Ok(RouteOption {
             id: row.get(0)?,
             travel_plan_id: row.get(1)?,
+            leg_id: row.get(11)?,
             name: row.get(2)?,
             description: row.get(3)?,
             distance: row.get(4)?,
@@ -62,37 +100,16 @@ impl RouteOption {
             end_coordinates: row.get(7)?,
             waypoints: row.get(8)?,
             created_at: row.get(9)?,
+            updated_at: row.get(10)?,
         })
     }
 
-    pub fn create(conn: &Connection, new_route: &NewRouteOption) -> Result<Self> {
-        let id = Uuid::new_v4().to_string();
+    pub fn new(new_route: &NewRouteOption) -> Self {
         let now = Utc::now();
-
-        conn.execute(
-            "INSERT INTO route_options (
-                id, travel_plan_id, name, description, distance, duration,
-                start_coordinates, end_coordinates, waypoints, created_at
-            ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
-            params![
-                id,
-                new_route.travel_plan_id,
-                new_route.name,
-                new_route.description,
-                new_route.distance,
-                new_route.duration,
-                new_route.start_coordinates,
-                new_route.end_coordinates,
-                new_route.waypoints,
-                now
-            ],
-        )?;
-
-        info!("Created new route option: {}", new_route.name);
-
-        Ok(RouteOption {
-            id,
+        RouteOption {
+            id: Uuid::new_v4().to_string(),
             travel_plan_id: new_route.travel_plan_id.clone(),
+            leg_id: new_route.leg_id.clone(),
             name: new_route.name.clone(),
             description: new_route.description.clone(),
             distance: new_route.distance,
@@ -101,13 +118,41 @@ impl RouteOption {
             end_coordinates: new_route.end_coordinates.clone(),
             waypoints: new_route.waypoints.clone(),
             created_at: now,
-        })
+            updated_at: now,
+        }
+    }
+
+    pub fn insert(&self, conn: &Connection) -> Result<()> {
+        conn.execute(
+            "INSERT INTO route_options (
+                id, travel_plan_id, name, description, distance, duration,
+                start_coordinates, end_coordinates, waypoints, created_at, updated_at, leg_id
+            ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)",
+            params![
+                self.id,
+                self.travel_plan_id,
+                self.name,
+                self.description,
+                self.distance,
+                self.duration,
+                self.start_coordinates,
+                self.end_coordinates,
+                self.waypoints,
+                self.created_at,
+                self.updated_at,
+                self.leg_id
+            ],
+        )?;
+
+        info!("Created new route option: {}", self.name);
+        Ok(())
     }
 
     pub fn find_by_id(conn: &Connection, id: &str) -> Result<Option<Self>> {
         let mut stmt = conn.prepare(
             "SELECT id, travel_plan_id, name, description, distance, duration,
-                    start_coordinates, end_coordinates, waypoints, created_at
+                    start_coordinates, end_coordinates, waypoints, created_at, updated_at,
+                    leg_id
              FROM route_options
              WHERE id = ?1",
         )?;
@@ -115,7 +160,7 @@ impl RouteOption {
         let mut rows = stmt.query(params![id])?;
 
         if let Some(row) = rows.next()? {
-            Ok(Some(Self::from_row(&row)?))
+            Ok(Some(Self::from_row(row)?))
         } else {
             Ok(None)
         }
@@ -124,12 +169,13 @@ impl RouteOption {
     pub fn find_by_travel_plan_id(conn: &Connection, travel_plan_id: &str) -> Result<Vec<Self>> {
         let mut stmt = conn.prepare(
             "SELECT id, travel_plan_id, name, description, distance, duration,
-                    start_coordinates, end_coordinates, waypoints, created_at
+                    start_coordinates, end_coordinates, waypoints, created_at, updated_at,
+                    leg_id
              FROM route_options
              WHERE travel_plan_id = ?1",
         )?;
 
-        let route_iter = stmt.query_map(params![travel_plan_id], |row| Self::from_row(row))?;
+        let route_iter = stmt.query_map(params![travel_plan_id], Self::from_row)?;
 
         let mut routes = Vec::new();
         for route_result in route_iter {
@@ -139,163 +185,255 @@ impl RouteOption {
         Ok(routes)
     }
 
-    #[allow(dead_code)]
-    pub fn update(&self, conn: &Connection, update: &UpdateRouteOption) -> Result<Self> {
-        let mut updated_route = self.clone();
-
-        if let Some(name) = &update.name {
-            updated_route.name = name.clone();
-        }
-
-        if let Some(description) = &update.description {
-            updated_route.description = Some(description.clone());
-        }
+    /// Returns a copy of this route option with a merge patch applied.
+    pub fn with_patch(&self, patch: &PatchRouteOption) -> Self {
+        let mut patched_route = self.clone();
 
-        if let Some(distance) = update.distance {
-            updated_route.distance = Some(distance);
+        if let Some(name) = &patch.name {
+            patched_route.name = name.clone();
         }
+        merge_patch::apply(&mut patched_route.description, &patch.description);
+        merge_patch::apply(&mut patched_route.distance, &patch.distance);
+        merge_patch::apply(&mut patched_route.duration, &patch.duration);
+        merge_patch::apply(&mut patched_route.waypoints, &patch.waypoints);
 
-        if let Some(duration) = update.duration {
-            updated_route.duration = Some(duration);
-        }
+        patched_route.updated_at = Utc::now();
 
-        if let Some(waypoints) = &update.waypoints {
-            updated_route.waypoints = Some(waypoints.clone());
-        }
+        patched_route
+    }
 
-        conn.execute(
+    /// Writes the route over the stored one if that was last updated at
+    /// `expected_updated_at`; returns the rows affected.
+    pub fn update(&self, conn: &Connection, expected_updated_at: DateTime<Utc>) -> Result<usize> {
+        let rows_affected = conn.execute(
             "UPDATE route_options SET
                 name = ?1,
                 description = ?2,
                 distance = ?3,
                 duration = ?4,
-                waypoints = ?5
-             WHERE id = ?6",
+                waypoints = ?5,
+                updated_at = ?6
+             WHERE id = ?7 AND updated_at = ?8",
             params![
-                updated_route.name,
-                updated_route.description,
-                updated_route.distance,
-                updated_route.duration,
-                updated_route.waypoints,
-                self.id
+                self.name,
+                self.description,
+                self.distance,
+                self.duration,
+                self.waypoints,
+                self.updated_at,
+                self.id,
+                expected_updated_at
             ],
         )?;
 
-        info!("Updated route option: {}", updated_route.name);
+        if rows_affected > 0 {
+            info!("Updated route option: {}", self.name);
+        } else {
+            info!("Route option {} changed since it was read, not updated", self.id);
+        }
 
-        Ok(updated_route)
+        Ok(rows_affected)
     }
 
-    #[allow(dead_code)]
-    pub fn delete(conn: &Connection, id: &str) -> Result<bool> {
-        let rows_affected = conn.execute("DELETE FROM route_options WHERE id = ?1", params![id])?;
+    /// Deletes the route with what hangs off it in one transaction. With
+    /// `expected_updated_at`, the route must still have been last updated
+    /// then, or nothing is deleted.
+    pub fn delete(
+        conn: &Connection,
+        id: &str,
+        expected_updated_at: Option<DateTime<Utc>>,
+    ) -> Result<bool> {
+        let tx = conn.unchecked_transaction()?;
+        for statement in DELETE_ROUTE_CONTENTS {
+            tx.execute(statement, params![id])?;
+        }
+        let rows_affected = tx.execute(
+            "DELETE FROM route_options WHERE id = ?1 AND (?2 IS NULL OR updated_at = ?2)",
+            params![id, expected_updated_at],
+        )?;
 
         if rows_affected > 0 {
+            tx.commit()?;
             info!("Deleted route option with ID: {}", id);
             Ok(true)
         } else {
-            info!("No route option found with ID: {}", id);
+            info!("No route option found with ID: {} at the expected version", id);
             Ok(false)
         }
     }
 
-    // Generate random route options for a travel plan
-    pub fn generate_random_options(
+    pub fn delete_by_travel_plan_id(conn: &Connection, travel_plan_id: &str) -> Result<usize> {
+        let rows_affected = conn.execute(
+            "DELETE FROM route_options WHERE travel_plan_id = ?1",
+            params![travel_plan_id],
+        )?;
+
+        info!(
+            "Deleted {} route options for travel plan ID: {}",
+            rows_affected, travel_plan_id
+        );
+
+        Ok(rows_affected)
+    }
+
+    /// Deletes the plan's unselected routes with everything on them in one
+    /// transaction. `None` when the plan is missing or was not last updated
+    /// at `expected_plan_updated_at`.
+    pub fn delete_unselected(
         conn: &Connection,
+        plan_id: &str,
+        expected_plan_updated_at: Option<DateTime<Utc>>,
+    ) -> Result<Option<Vec<String>>> {
+        let tx = conn.unchecked_transaction()?;
+        let plan = tx
+            .query_row(
+                "SELECT id FROM travel_plans WHERE id = ?1 AND (?2 IS NULL OR updated_at = ?2)",
+                params![plan_id, expected_plan_updated_at],
+                |row| row.get::<_, String>(0),
+            )
+            .optional()?;
+        if plan.is_none() {
+            info!("No travel plan {} at the expected version", plan_id);
+            return Ok(None);
+        }
+
+        let route_ids = tx
+            .prepare(UNSELECTED_ROUTE_IDS)?
+            .query_map(params![plan_id], |row| row.get(0))?
+            .collect::<Result<Vec<String>>>()?;
+        for id in &route_ids {
+            for statement in DELETE_ROUTE_CONTENTS {
+                tx.execute(statement, params![id])?;
+            }
+            tx.execute("DELETE FROM route_options WHERE id = ?1", params![id])?;
+        }
+        tx.commit()?;
+
+        info!(
+            "Deleted {} unselected route options for travel plan ID: {}",
+            route_ids.len(),
+            plan_id
+        );
+        Ok(Some(route_ids))
+    }
+
+    // Generate random route options for a travel plan
+    pub fn generate_random_options(plan: &TravelPlan, count: usize) -> Vec<Self> {
+        Self::generate_between(
+            &plan.id,
+            None,
+            &plan.start_location,
+            &plan.end_location,
+            None,
+            count,
+        )
+    }
+
+    /// Random route options for one leg of a plan, with durations to suit
+    /// the leg's mode.
+    pub fn generate_random_options_for_leg(leg: &PlanLeg, count: usize) -> Vec<Self> {
+        Self::generate_between(
+            &leg.travel_plan_id,
+            Some(&leg.id),
+            &leg.start_location,
+            &leg.end_location,
+            Some(leg.mode),
+            count,
+        )
+    }
+
+    fn generate_between(
         travel_plan_id: &str,
+        leg_id: Option<&str>,
+        start_location: &str,
+        end_location: &str,
+        mode: Option<TravelMode>,
         count: usize,
-    ) -> Result<Vec<Self>> {
+    ) -> Vec<Self> {
         let mut rng = rand::thread_rng();
         let mut routes = Vec::new();
 
-        // Get the travel plan to use its start and end locations
-        let mut stmt =
-            conn.prepare("SELECT start_location, end_location FROM travel_plans WHERE id = ?1")?;
-
-        let mut rows = stmt.query(params![travel_plan_id])?;
-
-        if let Some(row) = rows.next()? {
-            let start_location: String = row.get(0)?;
-            let end_location: String = row.get(1)?;
-
-            // Generate random start and end coordinates based on the locations
-            let start_coords = format!(
-                "{},{}",
-                rng.gen_range(-90.0..90.0),
-                rng.gen_range(-180.0..180.0)
-            );
-
-            let end_coords = format!(
-                "{},{}",
-                rng.gen_range(-90.0..90.0),
-                rng.gen_range(-180.0..180.0)
-            );
-
-            for i in 0..count {
-                // Generate random route options
-                let route_name = format!("Route Option {}", i + 1);
-                let description = match i % 3 {
-                    0 => Some(format!(
-                        "Scenic route from {} to {}",
-                        start_location, end_location
-                    )),
-                    1 => Some(format!(
-                        "Fastest route from {} to {}",
-                        start_location, end_location
-                    )),
-                    _ => Some(format!(
-                        "Alternative route from {} to {}",
-                        start_location, end_location
-                    )),
-                };
-
-                // Random distance between 10 and 1000 km
-                let distance = Some(rng.gen_range(10.0..1000.0));
-
-                // Random duration between 30 minutes and 12 hours (in minutes)
-                let duration = Some(rng.gen_range(30..720));
-
-                // Generate random waypoints
-                let waypoint_count = rng.gen_range(1..5);
-                let mut waypoints = Vec::new();
-
-                for _ in 0..waypoint_count {
-                    waypoints.push(format!(
-                        "{},{}",
-                        rng.gen_range(-90.0..90.0),
-                        rng.gen_range(-180.0..180.0)
-                    ));
+        // Generate random start and end coordinates based on the locations
+        let start_coords = format!(
+            "{},{}",
+            rng.gen_range(-90.0..90.0),
+            rng.gen_range(-180.0..180.0)
+        );
+
+        let end_coords = format!(
+            "{},{}",
+            rng.gen_range(-90.0..90.0),
+            rng.gen_range(-180.0..180.0)
+        );
+
+        for i in 0..count {
+            // Generate random route options
+            let route_name = format!("Route Option {}", i + 1);
+            let description = match i % 3 {
+                0 => Some(format!(
+                    "Scenic route from {} to {}",
+                    start_location, end_location
+                )),
+                1 => Some(format!(
+                    "Fastest route from {} to {}",
+                    start_location, end_location
+                )),
+                _ => Some(format!(
+                    "Alternative route from {} to {}",
+                    start_location, end_location
+                )),
+            };
+
+            // Random distance between 10 and 1000 km
+            let distance = Some(rng.gen_range(10.0..1000.0));
+
+            // Random duration between 30 minutes and 12 hours (in minutes), or
+            // a little slower than the mode's usual pace
+            let duration = match (mode, distance) {
+                (Some(mode), Some(distance)) => {
+                    let hours = distance / mode.average_speed_kmh() * rng.gen_range(1.0..1.25);
+                    Some((hours * 60.0).round() as i64)
                 }
-
-                let waypoints_str = if waypoints.is_empty() {
-                    None
-                } else {
-                    Some(waypoints.join(";"))
-                };
-
-                let new_route = NewRouteOption {
-                    travel_plan_id: travel_plan_id.to_string(),
-                    name: route_name,
-                    description,
-                    distance,
-                    duration,
-                    start_coordinates: start_coords.clone(),
-                    end_coordinates: end_coords.clone(),
-                    waypoints: waypoints_str,
-                };
-
-                let route = Self::create(conn, &new_route)?;
-                routes.push(route);
+                _ => Some(rng.gen_range(30..720)),
+            };
+
+            // Generate random waypoints
+            let waypoint_count = rng.gen_range(1..5);
+            let mut waypoints = Vec::new();
+
+            for _ in 0..waypoint_count {
+                waypoints.push(format!(
+                    "{},{}",
+                    rng.gen_range(-90.0..90.0),
+                    rng.gen_range(-180.0..180.0)
+                ));
             }
 
-            info!(
-                "Generated {} random route options for travel plan ID: {}",
-                count, travel_plan_id
-            );
-            Ok(routes)
-        } else {
-            info!("No travel plan found with ID: {}", travel_plan_id);
-            Ok(Vec::new())
+            let waypoints_str = if waypoints.is_empty() {
+                None
+            } else {
+                Some(waypoints.join(";"))
+            };
+
+            let new_route = NewRouteOption {
+                travel_plan_id: travel_plan_id.to_string(),
+                leg_id: leg_id.map(str::to_string),
+                name: route_name,
+                description,
+                distance,
+                duration,
+                start_coordinates: start_coords.clone(),
+                end_coordinates: end_coords.clone(),
+                waypoints: waypoints_str,
+            };
+
+            routes.push(Self::new(&new_route));
         }
+
+        info!(
+            "Generated {} random route options for travel plan ID: {}",
+            count, travel_plan_id
+        );
+        routes
     }
 }
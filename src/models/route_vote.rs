@@ -0,0 +1,156 @@
+use chrono::{DateTime, Utc};
+use rusqlite::types::{FromSql, FromSqlError, FromSqlResult, ToSql, ToSqlOutput, ValueRef};
+use rusqlite::{Connection, Result, Row, params};
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum VoteDirection {
+    Up,
+    Down,
+}
+
+impl VoteDirection {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            VoteDirection::Up => "up",
+            VoteDirection::Down => "down",
+        }
+    }
+}
+
+impl FromStr for VoteDirection {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "up" => Ok(VoteDirection::Up),
+            "down" => Ok(VoteDirection::Down),
+            other => Err(format!("unknown vote direction: {}", other)),
+        }
+    }
+}
+
+impl ToSql for VoteDirection {
+    fn to_sql(&self) -> Result<ToSqlOutput<'_>> {
+        Ok(ToSqlOutput::from(self.as_str()))
+    }
+}
+
+impl FromSql for VoteDirection {
+    fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+        value
+            .as_str()?
+            .parse()
+            .map_err(|e: String| FromSqlError::Other(e.into()))
+    }
+}
+
+/// One member's vote on a route option. Each user has at most one vote per
+/// route; voting again changes it.
+#[derive(Debug, Serialize, Deserialize, Clone, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct RouteVote {
+    pub id: String,
+    pub route_option_id: String,
+    pub travel_plan_id: String,
+    pub user_id: String,
+    pub direction: VoteDirection,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl RouteVote {
+    pub fn from_row(row: &Row) -> Result<Self> {
+        Ok(RouteVote {
+            id: row.get(0)?,
+            route_option_id: row.get(1)?,
+            travel_plan_id: row.get(2)?,
+            user_id: row.get(3)?,
+            direction: row.get(4)?,
+            created_at: row.get(5)?,
+            updated_at: row.get(6)?,
+        })
+    }
+
+    pub fn new(plan_id: &str, route_id: &str, user_id: &str, direction: VoteDirection) -> Self {
+        let now = Utc::now();
+
+        RouteVote {
+            id: Uuid::new_v4().to_string(),
+            route_option_id: route_id.to_string(),
+            travel_plan_id: plan_id.to_string(),
+            user_id: user_id.to_string(),
+            direction,
+            created_at: now,
+            updated_at: now,
+        }
+    }
+
+    /// Inserts the vote, or changes the direction of the user's existing vote
+    /// on the same route.
+    pub fn upsert(&self, conn: &Connection) -> Result<()> {
+        conn.execute(
+            "INSERT INTO route_votes (
+                id, route_option_id, travel_plan_id, user_id, direction, created_at, updated_at
+            ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)
+            ON CONFLICT (route_option_id, user_id)
+            DO UPDATE SET direction = excluded.direction, updated_at = excluded.updated_at",
+            params![
+                self.id,
+                self.route_option_id,
+                self.travel_plan_id,
+                self.user_id,
+                self.direction,
+                self.created_at,
+                self.updated_at
+            ],
+        )?;
+        Ok(())
+    }
+
+    pub fn find_by_travel_plan_id(conn: &Connection, plan_id: &str) -> Result<Vec<Self>> {
+        let mut stmt = conn.prepare(
+            "SELECT id, route_option_id, travel_plan_id, user_id, direction, created_at, updated_at
+             FROM route_votes
+             WHERE travel_plan_id = ?1
+             ORDER BY created_at, rowid",
+        )?;
+
+        let vote_iter = stmt.query_map(params![plan_id], Self::from_row)?;
+
+        let mut votes = Vec::new();
+        for vote_result in vote_iter {
+            votes.push(vote_result?);
+        }
+
+        Ok(votes)
+    }
+
+    pub fn delete(conn: &Connection, route_id: &str, user_id: &str) -> Result<bool> {
+        let rows_affected = conn.execute(
+            "DELETE FROM route_votes WHERE route_option_id = ?1 AND user_id = ?2",
+            params![route_id, user_id],
+        )?;
+        Ok(rows_affected > 0)
+    }
+
+    pub fn delete_by_route_option_id(conn: &Connection, route_id: &str) -> Result<usize> {
+        let rows_affected = conn.execute(
+            "DELETE FROM route_votes WHERE route_option_id = ?1",
+            params![route_id],
+        )?;
+        Ok(rows_affected)
+    }
+
+    pub fn delete_by_travel_plan_id(conn: &Connection, plan_id: &str) -> Result<usize> {
+        let rows_affected = conn.execute(
+            "DELETE FROM route_votes WHERE travel_plan_id = ?1",
+            params![plan_id],
+        )?;
+        Ok(rows_affected)
+    }
+}
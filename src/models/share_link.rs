@@ -0,0 +1,197 @@
+use chrono::{DateTime, Utc};
+use log::info;
+use rand::Rng;
+use rand::distributions::Alphanumeric;
+use rusqlite::{Connection, Result, Row, params};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+/// Length of a share token. 43 alphanumeric characters carry about 256 bits.
+const TOKEN_LENGTH: usize = 43;
+
+/// A public, read-only link to a travel plan for people without an account.
+#[derive(Debug, Serialize, Deserialize, Clone, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ShareLink {
+    pub id: String,
+    pub travel_plan_id: String,
+    pub token: String,
+    pub created_by: String,
+    pub include_routes: bool,
+    pub include_pois: bool,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub revoked_at: Option<DateTime<Utc>>,
+    pub access_count: i64,
+    pub last_accessed_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct NewShareLink {
+    /// The link stops working after this time. Never expires when omitted.
+    pub expires_at: Option<DateTime<Utc>>,
+    #[serde(default = "default_include_routes")]
+    pub include_routes: bool,
+    /// Only takes effect together with `include_routes`.
+    #[serde(default)]
+    pub include_pois: bool,
+}
+
+fn default_include_routes() -> bool {
+    true
+}
+
+impl ShareLink {
+    pub fn from_row(row: &Row) -> Result<Self> {
+        Ok(ShareLink {
+            id: row.get(0)?,
+            travel_plan_id: row.get(1)?,
+            token: row.get(2)?,
+            created_by: row.get(3)?,
+            include_routes: row.get(4)?,
+            include_pois: row.get(5)?,
+            expires_at: row.get(6)?,
+            revoked_at: row.get(7)?,
+            access_count: row.get(8)?,
+            last_accessed_at: row.get(9)?,
+            created_at: row.get(10)?,
+        })
+    }
+
+    pub fn new(new_link: &NewShareLink, plan_id: &str, created_by: &str) -> Self {
+        let token = rand::thread_rng()
+            .sample_iter(&Alphanumeric)
+            .take(TOKEN_LENGTH)
+            .map(char::from)
+            .collect();
+
+        ShareLink {
+            id: Uuid::new_v4().to_string(),
+            travel_plan_id: plan_id.to_string(),
+            token,
+            created_by: created_by.to_string(),
+            include_routes: new_link.include_routes,
+            include_pois: new_link.include_routes && new_link.include_pois,
+            expires_at: new_link.expires_at,
+            revoked_at: None,
+            access_count: 0,
+            last_accessed_at: None,
+            created_at: Utc::now(),
+        }
+    }
+
+    /// Whether the link still grants access at `now`.
+    pub fn is_active(&self, now: DateTime<Utc>) -> bool {
+        self.revoked_at.is_none() && self.expires_at.is_none_or(|expires_at| expires_at > now)
+    }
+
+    pub fn insert(&self, conn: &Connection) -> Result<()> {
+        conn.execute(
+            "INSERT INTO share_links (
+                id, travel_plan_id, token, created_by, include_routes, include_pois,
+                expires_at, revoked_at, access_count, last_accessed_at, created_at
+            ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
+            params![
+                self.id,
+                self.travel_plan_id,
+                self.token,
+                self.created_by,
+                self.include_routes,
+                self.include_pois,
+                self.expires_at,
+                self.revoked_at,
+                self.access_count,
+                self.last_accessed_at,
+                self.created_at
+            ],
+        )?;
+
+        info!("Created share link {} for travel plan {}", self.id, self.travel_plan_id);
+        Ok(())
+    }
+
+    pub fn find_by_id(conn: &Connection, id: &str) -> Result<Option<Self>> {
+        let mut stmt = conn.prepare(
+            "SELECT id, travel_plan_id, token, created_by, include_routes, include_pois,
+                    expires_at, revoked_at, access_count, last_accessed_at, created_at
+             FROM share_links
+             WHERE id = ?1",
+        )?;
+
+        let mut rows = stmt.query(params![id])?;
+
+        if let Some(row) = rows.next()? {
+            Ok(Some(Self::from_row(row)?))
+        } else {
+            Ok(None)
+        }
+    }
+
+    pub fn find_by_token(conn: &Connection, token: &str) -> Result<Option<Self>> {
+        let mut stmt = conn.prepare(
+            "SELECT id, travel_plan_id, token, created_by, include_routes, include_pois,
+                    expires_at, revoked_at, access_count, last_accessed_at, created_at
+             FROM share_links
+             WHERE token = ?1",
+        )?;
+
+        let mut rows = stmt.query(params![token])?;
+
+        if let Some(row) = rows.next()? {
+            Ok(Some(Self::from_row(row)?))
+        } else {
+            Ok(None)
+        }
+    }
+
+    pub fn find_by_travel_plan_id(conn: &Connection, plan_id: &str) -> Result<Vec<Self>> {
+        let mut stmt = conn.prepare(
+            "SELECT id, travel_plan_id, token, created_by, include_routes, include_pois,
+                    expires_at, revoked_at, access_count, last_accessed_at, created_at
+             FROM share_links
+             WHERE travel_plan_id = ?1
+             ORDER BY created_at DESC, rowid DESC",
+        )?;
+
+        let link_iter = stmt.query_map(params![plan_id], Self::from_row)?;
+
+        let mut links = Vec::new();
+        for link_result in link_iter {
+            links.push(link_result?);
+        }
+
+        Ok(links)
+    }
+
+    /// Counts one visit. Done in SQL so concurrent visits are not lost.
+    pub fn record_access(conn: &Connection, id: &str, at: DateTime<Utc>) -> Result<()> {
+        conn.execute(
+            "UPDATE share_links
+             SET access_count = access_count + 1, last_accessed_at = ?1
+             WHERE id = ?2",
+            params![at, id],
+        )?;
+        Ok(())
+    }
+
+    /// Revokes the link unless it already was; returns whether it changed.
+    pub fn revoke(conn: &Connection, id: &str, at: DateTime<Utc>) -> Result<bool> {
+        let rows_affected = conn.execute(
+            "UPDATE share_links SET revoked_at = ?1 WHERE id = ?2 AND revoked_at IS NULL",
+            params![at, id],
+        )?;
+
+        info!("Revoked share link {}", id);
+        Ok(rows_affected > 0)
+    }
+
+    pub fn delete_by_travel_plan_id(conn: &Connection, plan_id: &str) -> Result<usize> {
+        let rows_affected = conn.execute(
+            "DELETE FROM share_links WHERE travel_plan_id = ?1",
+            params![plan_id],
+        )?;
+        Ok(rows_affected)
+    }
+}
@@ -0,0 +1,72 @@
+use rusqlite::Result;
+use rusqlite::types::{FromSql, FromSqlError, FromSqlResult, ToSql, ToSqlOutput, ValueRef};
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+use utoipa::ToSchema;
+
+/// How a route is travelled.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum TravelMode {
+    #[default]
+    Driving,
+    Cycling,
+    Walking,
+    /// Trains, buses and ferries.
+    Transit,
+    Flight,
+}
+
+impl TravelMode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TravelMode::Driving => "driving",
+            TravelMode::Cycling => "cycling",
+            TravelMode::Walking => "walking",
+            TravelMode::Transit => "transit",
+            TravelMode::Flight => "flight",
+        }
+    }
+
+    /// A typical door-to-door speed in km/h, for when only a route's distance
+    /// is known.
+    pub fn average_speed_kmh(&self) -> f64 {
+        match self {
+            TravelMode::Driving => 80.0,
+            TravelMode::Cycling => 15.0,
+            TravelMode::Walking => 5.0,
+            TravelMode::Transit => 60.0,
+            TravelMode::Flight => 600.0,
+        }
+    }
+}
+
+impl FromStr for TravelMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "driving" => Ok(TravelMode::Driving),
+            "cycling" => Ok(TravelMode::Cycling),
+            "walking" => Ok(TravelMode::Walking),
+            "transit" => Ok(TravelMode::Transit),
+            "flight" => Ok(TravelMode::Flight),
+            other => Err(format!("unknown travel mode: {}", other)),
+        }
+    }
+}
+
+impl ToSql for TravelMode {
+    fn to_sql(&self) -> Result<ToSqlOutput<'_>> {
+        Ok(ToSqlOutput::from(self.as_str()))
+    }
+}
+
+impl FromSql for TravelMode {
+    fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+        value
+            .as_str()?
+            .parse()
+            .map_err(|e: String| FromSqlError::Other(e.into()))
+    }
+}
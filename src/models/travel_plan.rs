@@ -5,6 +5,26 @@ use serde::{Deserialize, Serialize};
 use utoipa::ToSchema;
 use uuid::Uuid;
 
+use crate::models::currency::CurrencyCode;
+use crate::models::merge_patch;
+
+/// Removes what a plan owns; routes go after what points at them and before
+/// the legs they belong to.
+const DELETE_PLAN_CONTENTS: &[&str] = &[
+    "DELETE FROM points_of_interest
+     WHERE route_option_id IN (SELECT id FROM route_options WHERE travel_plan_id = ?1)",
+    "DELETE FROM route_votes WHERE travel_plan_id = ?1",
+    "DELETE FROM comments WHERE travel_plan_id = ?1",
+    "DELETE FROM itinerary_items WHERE travel_plan_id = ?1",
+    "DELETE FROM route_options WHERE travel_plan_id = ?1",
+    "DELETE FROM plan_legs WHERE travel_plan_id = ?1",
+    "DELETE FROM plan_revisions WHERE travel_plan_id = ?1",
+    "DELETE FROM expenses WHERE travel_plan_id = ?1",
+    "DELETE FROM budgets WHERE travel_plan_id = ?1",
+    "DELETE FROM plan_members WHERE travel_plan_id = ?1",
+    "DELETE FROM share_links WHERE travel_plan_id = ?1",
+];
+
 #[derive(Debug, Serialize, Deserialize, Clone, ToSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct TravelPlan {
@@ -18,6 +38,10 @@ pub struct TravelPlan {
     pub end_date: Option<DateTime<Utc>>,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
+    /// The route option the owner locked in, if any.
+    pub selected_route_id: Option<String>,
+    /// What the budget summary converts every amount to, if set.
+    pub home_currency: Option<String>,
 }
 
 #[derive(Debug, Deserialize, ToSchema)]
@@ -29,6 +53,8 @@ pub struct NewTravelPlan {
     pub end_location: String,
     pub start_date: Option<DateTime<Utc>>,
     pub end_date: Option<DateTime<Utc>>,
+    #[schema(value_type = Option<String>, example = "EUR")]
+    pub home_currency: Option<CurrencyCode>,
 }
 
 #[derive(Debug, Deserialize, ToSchema)]
@@ -40,6 +66,33 @@ pub struct UpdateTravelPlan {
     pub end_location: Option<String>,
     pub start_date: Option<DateTime<Utc>>,
     pub end_date: Option<DateTime<Utc>>,
+    #[schema(value_type = Option<String>, example = "EUR")]
+    pub home_currency: Option<CurrencyCode>,
+}
+
+/// A JSON Merge Patch for a plan. Absent fields are left alone; `null`
+/// clears the description, a date or the home currency.
+#[derive(Debug, Default, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct PatchTravelPlan {
+    #[serde(default, deserialize_with = "merge_patch::non_null")]
+    pub name: Option<String>,
+    #[serde(default, deserialize_with = "merge_patch::nullable")]
+    #[schema(value_type = Option<String>)]
+    pub description: Option<Option<String>>,
+    #[serde(default, deserialize_with = "merge_patch::non_null")]
+    pub start_location: Option<String>,
+    #[serde(default, deserialize_with = "merge_patch::non_null")]
+    pub end_location: Option<String>,
+    #[serde(default, deserialize_with = "merge_patch::nullable")]
+    #[schema(value_type = Option<DateTime<Utc>>)]
+    pub start_date: Option<Option<DateTime<Utc>>>,
+    #[serde(default, deserialize_with = "merge_patch::nullable")]
+    #[schema(value_type = Option<DateTime<Utc>>)]
+    pub end_date: Option<Option<DateTime<Utc>>>,
+    #[serde(default, deserialize_with = "merge_patch::nullable")]
+    #[schema(value_type = Option<String>)]
+    pub home_currency: Option<Option<CurrencyCode>>,
 }
 
 impl TravelPlan {
@@ -55,36 +108,16 @@ impl TravelPlan {
             end_date: row.get(7)?,
             created_at: row.get(8)?,
             updated_at: row.get(9)?,
+            selected_route_id: row.get(10)?,
+            home_currency: row.get(11)?,
         })
     }
 
-    pub fn create(conn: &Connection, new_plan: &NewTravelPlan, user_id: &str) -> Result<Self> {
-        let id = Uuid::new_v4().to_string();
+    pub fn new(new_plan: &NewTravelPlan, user_id: &str) -> Self {
         let now = Utc::now();
 
-        conn.execute(
-            "INSERT INTO travel_plans (
-                id, user_id, name, description, start_location, end_location,
-                start_date, end_date, created_at, updated_at
-            ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
-            params![
-                id,
-                user_id,
-                new_plan.name,
-                new_plan.description,
-                new_plan.start_location,
-                new_plan.end_location,
-                new_plan.start_date,
-                new_plan.end_date,
-                now,
-                now
-            ],
-        )?;
-
-        info!("Created new travel plan: {}", new_plan.name);
-
-        Ok(TravelPlan {
-            id,
+        TravelPlan {
+            id: Uuid::new_v4().to_string(),
             user_id: user_id.to_string(),
             name: new_plan.name.clone(),
             description: new_plan.description.clone(),
@@ -94,13 +127,42 @@ impl TravelPlan {
             end_date: new_plan.end_date,
             created_at: now,
             updated_at: now,
-        })
+            selected_route_id: None,
+            home_currency: new_plan.home_currency.as_ref().map(CurrencyCode::to_string),
+        }
+    }
+
+    pub fn insert(&self, conn: &Connection) -> Result<()> {
+        conn.execute(
+            "INSERT INTO travel_plans (
+                id, user_id, name, description, start_location, end_location,
+                start_date, end_date, created_at, updated_at, selected_route_id, home_currency
+            ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)",
+            params![
+                self.id,
+                self.user_id,
+                self.name,
+                self.description,
+                self.start_location,
+                self.end_location,
+                self.start_date,
+                self.end_date,
+                self.created_at,
+                self.updated_at,
+                self.selected_route_id,
+                self.home_currency
+            ],
+        )?;
+
+        info!("Created new travel plan: {}", self.name);
+        Ok(())
     }
 
     pub fn find_by_id(conn: &Connection, id: &str) -> Result<Option<Self>> {
         let mut stmt = conn.prepare(
             "SELECT id, user_id, name, description, start_location, end_location, 
-                    start_date, end_date, created_at, updated_at 
+                    start_date, end_date, created_at, updated_at, selected_route_id,
+                    home_currency
              FROM travel_plans 
              WHERE id = ?1",
         )?;
@@ -108,7 +170,7 @@ impl TravelPlan {
         let mut rows = stmt.query(params![id])?;
 
         if let Some(row) = rows.next()? {
-            Ok(Some(Self::from_row(&row)?))
+            Ok(Some(Self::from_row(row)?))
         } else {
             Ok(None)
         }
@@ -117,13 +179,14 @@ impl TravelPlan {
     pub fn find_by_user_id(conn: &Connection, user_id: &str) -> Result<Vec<Self>> {
         let mut stmt = conn.prepare(
             "SELECT id, user_id, name, description, start_location, end_location, 
-                    start_date, end_date, created_at, updated_at 
+                    start_date, end_date, created_at, updated_at, selected_route_id,
+                    home_currency
              FROM travel_plans 
              WHERE user_id = ?1
              ORDER BY created_at DESC",
         )?;
 
-        let plan_iter = stmt.query_map(params![user_id], |row| Self::from_row(row))?;
+        let plan_iter = stmt.query_map(params![user_id], Self::from_row)?;
 
         let mut plans = Vec::new();
         for plan_result in plan_iter {
@@ -137,12 +200,13 @@ impl TravelPlan {
     pub fn get_all(conn: &Connection) -> Result<Vec<Self>> {
         let mut stmt = conn.prepare(
             "SELECT id, user_id, name, description, start_location, end_location, 
-                    start_date, end_date, created_at, updated_at 
+                    start_date, end_date, created_at, updated_at, selected_route_id,
+                    home_currency
              FROM travel_plans
              ORDER BY created_at DESC",
         )?;
 
-        let plan_iter = stmt.query_map([], |row| Self::from_row(row))?;
+        let plan_iter = stmt.query_map([], Self::from_row)?;
 
         let mut plans = Vec::new();
         for plan_result in plan_iter {
@@ -152,9 +216,8 @@ impl TravelPlan {
         Ok(plans)
     }
 
-    pub fn update(&self, conn: &Connection, update: &UpdateTravelPlan) -> Result<Self> {
-        let now = Utc::now();
-
+    /// Returns a copy of this plan with the provided fields applied.
+    pub fn with_update(&self, update: &UpdateTravelPlan) -> Self {
         let mut updated_plan = self.clone();
 
         if let Some(name) = &update.name {
@@ -181,9 +244,44 @@ impl TravelPlan {
             updated_plan.end_date = Some(end_date);
         }
 
-        updated_plan.updated_at = now;
+        if let Some(home_currency) = &update.home_currency {
+            updated_plan.home_currency = Some(home_currency.to_string());
+        }
 
-        conn.execute(
+        updated_plan.updated_at = Utc::now();
+
+        updated_plan
+    }
+
+    /// Returns a copy of this plan with a merge patch applied.
+    pub fn with_patch(&self, patch: &PatchTravelPlan) -> Self {
+        let mut patched_plan = self.clone();
+
+        if let Some(name) = &patch.name {
+            patched_plan.name = name.clone();
+        }
+        merge_patch::apply(&mut patched_plan.description, &patch.description);
+        if let Some(start_location) = &patch.start_location {
+            patched_plan.start_location = start_location.clone();
+        }
+        if let Some(end_location) = &patch.end_location {
+            patched_plan.end_location = end_location.clone();
+        }
+        merge_patch::apply(&mut patched_plan.start_date, &patch.start_date);
+        merge_patch::apply(&mut patched_plan.end_date, &patch.end_date);
+        if let Some(home_currency) = &patch.home_currency {
+            patched_plan.home_currency = home_currency.as_ref().map(CurrencyCode::to_string);
+        }
+
+        patched_plan.updated_at = Utc::now();
+
+        patched_plan
+    }
+
+    /// Writes the plan over the stored one if that was last updated at
+    /// `expected_updated_at`; returns the rows affected.
+    pub fn update(&self, conn: &Connection, expected_updated_at: DateTime<Utc>) -> Result<usize> {
+        let rows_affected = conn.execute(
             "UPDATE travel_plans SET 
                 name = ?1, 
                 description = ?2, 
@@ -191,33 +289,71 @@ impl TravelPlan {
                 end_location = ?4, 
                 start_date = ?5, 
                 end_date = ?6, 
-                updated_at = ?7 
-             WHERE id = ?8",
+                home_currency = ?7, 
+                updated_at = ?8 
+             WHERE id = ?9 AND updated_at = ?10",
             params![
-                updated_plan.name,
-                updated_plan.description,
-                updated_plan.start_location,
-                updated_plan.end_location,
-                updated_plan.start_date,
-                updated_plan.end_date,
-                now,
-                self.id
+                self.name,
+                self.description,
+                self.start_location,
+                self.end_location,
+                self.start_date,
+                self.end_date,
+                self.home_currency,
+                self.updated_at,
+                self.id,
+                expected_updated_at
             ],
         )?;
 
-        info!("Updated travel plan: {}", updated_plan.name);
+        if rows_affected > 0 {
+            info!("Updated travel plan: {}", self.name);
+        } else {
+            info!("Travel plan {} changed since it was read, not updated", self.id);
+        }
+
+        Ok(rows_affected)
+    }
 
-        Ok(updated_plan)
+    /// Locks in `route_id` as the plan's chosen route, or clears the choice.
+    pub fn set_selected_route(
+        conn: &Connection,
+        id: &str,
+        route_id: Option<&str>,
+        updated_at: DateTime<Utc>,
+    ) -> Result<bool> {
+        let rows_affected = conn.execute(
+            "UPDATE travel_plans SET selected_route_id = ?1, updated_at = ?2 WHERE id = ?3",
+            params![route_id, updated_at, id],
+        )?;
+        Ok(rows_affected > 0)
     }
 
-    pub fn delete(conn: &Connection, id: &str) -> Result<bool> {
-        let rows_affected = conn.execute("DELETE FROM travel_plans WHERE id = ?1", params![id])?;
+    /// Deletes the plan and everything that belongs to it in one
+    /// transaction, children before the rows they reference. The activity
+    /// log outlives the plan. With `expected_updated_at`, the plan must
+    /// still have been last updated then, or nothing is deleted.
+    pub fn delete(
+        conn: &Connection,
+        id: &str,
+        expected_updated_at: Option<DateTime<Utc>>,
+    ) -> Result<bool> {
+        let tx = conn.unchecked_transaction()?;
+        for statement in DELETE_PLAN_CONTENTS {
+            tx.execute(statement, params![id])?;
+        }
+        let rows_affected = tx.execute(
+            "DELETE FROM travel_plans WHERE id = ?1 AND (?2 IS NULL OR updated_at = ?2)",
+            params![id, expected_updated_at],
+        )?;
 
         if rows_affected > 0 {
+            tx.commit()?;
             info!("Deleted travel plan with ID: {}", id);
             Ok(true)
         } else {
-            info!("No travel plan found with ID: {}", id);
+            // Dropping the transaction puts the plan's contents back
+            info!("No travel plan found with ID: {} at the expected version", id);
             Ok(false)
         }
     }
@@ -1,11 +1,79 @@
-use bcrypt::{DEFAULT_COST, hash, verify};
+use bcrypt::{BcryptError, hash, verify};
 use chrono::{DateTime, Utc};
 use log::{error, info};
+use rusqlite::types::{FromSql, FromSqlError, FromSqlResult, ToSql, ToSqlOutput, ValueRef};
 use rusqlite::{Connection, Result, Row, params};
 use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
 use utoipa::ToSchema;
 use uuid::Uuid;
 
+// bcrypt at the default cost dominates test run time, so tests hash cheaply.
+#[cfg(not(test))]
+const PASSWORD_HASH_COST: u32 = bcrypt::DEFAULT_COST;
+#[cfg(test)]
+const PASSWORD_HASH_COST: u32 = 4;
+
+/// What an account may do beyond managing its own plans. Support staff can
+/// read any plan; admins can also manage accounts and run maintenance.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    #[default]
+    User,
+    Support,
+    Admin,
+}
+
+impl Role {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Role::User => "user",
+            Role::Support => "support",
+            Role::Admin => "admin",
+        }
+    }
+
+    pub fn can_view_any_plan(&self) -> bool {
+        matches!(self, Role::Support | Role::Admin)
+    }
+}
+
+impl fmt::Display for Role {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Role {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "user" => Ok(Role::User),
+            "support" => Ok(Role::Support),
+            "admin" => Ok(Role::Admin),
+            other => Err(format!("unknown role: {}", other)),
+        }
+    }
+}
+
+impl ToSql for Role {
+    fn to_sql(&self) -> Result<ToSqlOutput<'_>> {
+        Ok(ToSqlOutput::from(self.as_str()))
+    }
+}
+
+impl FromSql for Role {
+    fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+        value
+            .as_str()?
+            .parse()
+            .map_err(|e: String| FromSqlError::Other(e.into()))
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone, ToSchema)]
 pub struct User {
     pub id: String,
@@ -14,6 +82,10 @@ pub struct User {
     pub password_hash: String,
     pub email: String,
     pub created_at: DateTime<Utc>,
+    #[serde(default)]
+    pub disabled: bool,
+    #[serde(default)]
+    pub role: Role,
 }
 
 #[derive(Debug, Deserialize, ToSchema)]
@@ -37,43 +109,77 @@ impl User {
             password_hash: row.get(2)?,
             email: row.get(3)?,
             created_at: row.get(4)?,
+            disabled: row.get(5)?,
+            role: row.get(6)?,
         })
     }
 
-    pub fn create(conn: &Connection, new_user: &NewUser) -> Result<Self> {
-        let id = Uuid::new_v4().to_string();
-        let password_hash = hash(&new_user.password, DEFAULT_COST)
-            .map_err(|e| rusqlite::Error::InvalidParameterName(e.to_string()))?;
-
-        let now = Utc::now();
-
-        conn.execute(
-            "INSERT INTO users (id, username, password_hash, email, created_at) 
-             VALUES (?1, ?2, ?3, ?4, ?5)",
-            params![id, new_user.username, password_hash, new_user.email, now],
-        )?;
-
-        info!("Created new user: {}", new_user.username);
+    /// Builds a new user with a freshly hashed password.
+    pub fn new(new_user: &NewUser) -> std::result::Result<Self, BcryptError> {
+        let password_hash = Self::hash_password(&new_user.password)?;
 
         Ok(User {
-            id,
+            id: Uuid::new_v4().to_string(),
             username: new_user.username.clone(),
             password_hash,
             email: new_user.email.clone(),
-            created_at: now,
+            created_at: Utc::now(),
+            disabled: false,
+            role: Role::User,
         })
     }
 
+    pub fn hash_password(password: &str) -> std::result::Result<String, BcryptError> {
+        hash(password, PASSWORD_HASH_COST)
+    }
+
+    pub fn verify_password(&self, password: &str) -> bool {
+        match verify(password, &self.password_hash) {
+            Ok(matches) => matches,
+            Err(e) => {
+                error!("Error verifying password for user {}: {}", self.username, e);
+                false
+            }
+        }
+    }
+
+    pub fn insert(&self, conn: &Connection) -> Result<()> {
+        conn.execute(
+            "INSERT INTO users (id, username, password_hash, email, created_at, disabled, role) 
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            params![
+                self.id,
+                self.username,
+                self.password_hash,
+                self.email,
+                self.created_at,
+                self.disabled,
+                self.role
+            ],
+        )?;
+
+        info!("Created new user: {}", self.username);
+        Ok(())
+    }
+
+    #[allow(dead_code)]
+    pub fn create(conn: &Connection, new_user: &NewUser) -> Result<Self> {
+        let user = Self::new(new_user)
+            .map_err(|e| rusqlite::Error::InvalidParameterName(e.to_string()))?;
+        user.insert(conn)?;
+        Ok(user)
+    }
+
     #[allow(dead_code)]
     pub fn find_by_id(conn: &Connection, id: &str) -> Result<Option<Self>> {
         let mut stmt = conn.prepare(
-            "SELECT id, username, password_hash, email, created_at FROM users WHERE id = ?1",
+            "SELECT id, username, password_hash, email, created_at, disabled, role FROM users WHERE id = ?1",
         )?;
 
         let mut rows = stmt.query(params![id])?;
 
         if let Some(row) = rows.next()? {
-            Ok(Some(Self::from_row(&row)?))
+            Ok(Some(Self::from_row(row)?))
         } else {
             Ok(None)
         }
@@ -81,42 +187,39 @@ impl User {
 
     pub fn find_by_username(conn: &Connection, username: &str) -> Result<Option<Self>> {
         let mut stmt = conn.prepare(
-            "SELECT id, username, password_hash, email, created_at FROM users WHERE username = ?1",
+            "SELECT id, username, password_hash, email, created_at, disabled, role FROM users WHERE username = ?1",
         )?;
 
         let mut rows = stmt.query(params![username])?;
 
         if let Some(row) = rows.next()? {
-            Ok(Some(Self::from_row(&row)?))
+            Ok(Some(Self::from_row(row)?))
         } else {
             Ok(None)
         }
     }
 
-    pub fn authenticate(conn: &Connection, credentials: &LoginCredentials) -> Result<Option<Self>> {
-        if let Some(user) = Self::find_by_username(conn, &credentials.username)? {
-            let password_matches = verify(&credentials.password, &user.password_hash)
-                .map_err(|e| rusqlite::Error::InvalidParameterName(e.to_string()))?;
+    pub fn find_by_email(conn: &Connection, email: &str) -> Result<Option<Self>> {
+        let mut stmt = conn.prepare(
+            "SELECT id, username, password_hash, email, created_at, disabled, role FROM users WHERE email = ?1",
+        )?;
 
-            if password_matches {
-                info!("User authenticated successfully: {}", credentials.username);
-                return Ok(Some(user));
-            }
-        }
+        let mut rows = stmt.query(params![email])?;
 
-        error!(
-            "Authentication failed for username: {}",
-            credentials.username
-        );
-        Ok(None)
+        if let Some(row) = rows.next()? {
+            Ok(Some(Self::from_row(row)?))
+        } else {
+            Ok(None)
+        }
     }
 
-    #[allow(dead_code)]
     pub fn get_all(conn: &Connection) -> Result<Vec<Self>> {
-        let mut stmt =
-            conn.prepare("SELECT id, username, password_hash, email, created_at FROM users")?;
+        let mut stmt = conn.prepare(
+            "SELECT id, username, password_hash, email, created_at, disabled, role FROM users
+             ORDER BY created_at, username",
+        )?;
 
-        let user_iter = stmt.query_map([], |row| Self::from_row(row))?;
+        let user_iter = stmt.query_map([], Self::from_row)?;
 
         let mut users = Vec::new();
         for user_result in user_iter {
@@ -137,6 +240,36 @@ impl User {
         Ok(())
     }
 
+    pub fn set_disabled(conn: &Connection, id: &str, disabled: bool) -> Result<bool> {
+        let rows_affected = conn.execute(
+            "UPDATE users SET disabled = ?1 WHERE id = ?2",
+            params![disabled, id],
+        )?;
+
+        info!("Set disabled = {} for user with ID: {}", disabled, id);
+        Ok(rows_affected > 0)
+    }
+
+    pub fn set_role(conn: &Connection, id: &str, role: Role) -> Result<bool> {
+        let rows_affected = conn.execute(
+            "UPDATE users SET role = ?1 WHERE id = ?2",
+            params![role, id],
+        )?;
+
+        info!("Set role = {} for user with ID: {}", role, id);
+        Ok(rows_affected > 0)
+    }
+
+    pub fn update_password_hash(conn: &Connection, id: &str, password_hash: &str) -> Result<bool> {
+        let rows_affected = conn.execute(
+            "UPDATE users SET password_hash = ?1 WHERE id = ?2",
+            params![password_hash, id],
+        )?;
+
+        info!("Updated password for user with ID: {}", id);
+        Ok(rows_affected > 0)
+    }
+
     #[allow(dead_code)]
     pub fn delete(conn: &Connection, id: &str) -> Result<bool> {
         let rows_affected = conn.execute("DELETE FROM users WHERE id = ?1", params![id])?;
@@ -0,0 +1,1072 @@
+use std::path::Path;
+use std::sync::{Mutex, MutexGuard};
+
+use chrono::{DateTime, Utc};
+
+use crate::db::backup::BackupInfo;
+use crate::db::maintenance::{OrphanReport, TableCount, TABLES};
+use crate::db::schema::SCHEMA_VERSION;
+use crate::models::audit::AuditEntry;
+use crate::models::comment::{Comment, CommentTarget};
+use crate::models::plan_event::PlanEvent;
+use crate::models::itinerary::ItineraryItem;
+use crate::models::plan_leg::PlanLeg;
+use crate::models::budget::{Budget, Expense};
+use crate::models::plan_revision::PlanRevision;
+use crate::models::plan_member::{MemberStatus, PlanMember};
+use crate::models::point_of_interest::PointOfInterest;
+use crate::models::route_option::RouteOption;
+use crate::models::route_vote::RouteVote;
+use crate::models::share_link::ShareLink;
+use crate::models::travel_plan::TravelPlan;
+use crate::models::user::{Role, User};
+use crate::repositories::{
+    AuditRepository, BudgetRepository, CommentRepository, ItineraryRepository,
+    MaintenanceRepository, PlanEventRepository, PlanLegRepository, PlanMemberRepository,
+    PlanRevisionRepository, PoiRepository, RepositoryError, RepositoryResult, RouteOptionRepository,
+    RouteVoteRepository, ShareLinkRepository, TravelPlanRepository, UserRepository,
+};
+
+#[derive(Default)]
+struct MemoryState {
+    users: Vec<User>,
+    travel_plans: Vec<TravelPlan>,
+    plan_members: Vec<PlanMember>,
+    share_links: Vec<ShareLink>,
+    route_options: Vec<RouteOption>,
+    route_votes: Vec<RouteVote>,
+    points_of_interest: Vec<PointOfInterest>,
+    comments: Vec<Comment>,
+    plan_events: Vec<PlanEvent>,
+    plan_revisions: Vec<PlanRevision>,
+    itinerary_items: Vec<ItineraryItem>,
+    plan_legs: Vec<PlanLeg>,
+    budgets: Vec<Budget>,
+    expenses: Vec<Expense>,
+    audit_log: Vec<AuditEntry>,
+}
+
+/// Process-local storage for fast unit tests and embedders that need no
+/// database. Rows are kept in insertion order to match what the SQLite
+/// backend returns.
+#[derive(Default)]
+pub struct InMemoryRepository {
+    state: Mutex<MemoryState>,
+}
+
+impl InMemoryRepository {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn state(&self) -> RepositoryResult<MutexGuard<'_, MemoryState>> {
+        self.state
+            .lock()
+            .map_err(|e| RepositoryError::ConnectionError(e.to_string()))
+    }
+}
+
+impl UserRepository for InMemoryRepository {
+    fn insert_user(&self, user: &User) -> RepositoryResult<()> {
+        let mut state = self.state()?;
+
+        if state.users.iter().any(|u| u.username == user.username) {
+            return Err(RepositoryError::DatabaseError(
+                "UNIQUE constraint failed: users.username".to_string(),
+            ));
+        }
+        if state.users.iter().any(|u| u.email == user.email) {
+            return Err(RepositoryError::DatabaseError(
+                "UNIQUE constraint failed: users.email".to_string(),
+            ));
+        }
+
+        state.users.push(user.clone());
+        Ok(())
+    }
+
+    fn find_user_by_id(&self, id: &str) -> RepositoryResult<Option<User>> {
+        Ok(self.state()?.users.iter().find(|u| u.id == id).cloned())
+    }
+
+    fn find_user_by_username(&self, username: &str) -> RepositoryResult<Option<User>> {
+        Ok(self
+            .state()?
+            .users
+            .iter()
+            .find(|u| u.username == username)
+            .cloned())
+    }
+
+    fn find_user_by_email(&self, email: &str) -> RepositoryResult<Option<User>> {
+        Ok(self.state()?.users.iter().find(|u| u.email == email).cloned())
+    }
+
+    fn set_user_disabled(&self, id: &str, disabled: bool) -> RepositoryResult<bool> {
+        let mut state = self.state()?;
+        match state.users.iter_mut().find(|u| u.id == id) {
+            Some(user) => {
+                user.disabled = disabled;
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    fn update_user_password(&self, id: &str, password_hash: &str) -> RepositoryResult<bool> {
+        let mut state = self.state()?;
+        match state.users.iter_mut().find(|u| u.id == id) {
+            Some(user) => {
+                user.password_hash = password_hash.to_string();
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    fn set_user_role(&self, id: &str, role: Role) -> RepositoryResult<bool> {
+        let mut state = self.state()?;
+        match state.users.iter_mut().find(|u| u.id == id) {
+            Some(user) => {
+                user.role = role;
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    fn list_users(&self) -> RepositoryResult<Vec<User>> {
+        let mut users = self.state()?.users.clone();
+        users.sort_by(|a, b| (a.created_at, &a.username).cmp(&(b.created_at, &b.username)));
+        Ok(users)
+    }
+}
+
+impl TravelPlanRepository for InMemoryRepository {
+    fn insert_travel_plan(&self, plan: &TravelPlan) -> RepositoryResult<()> {
+        self.state()?.travel_plans.push(plan.clone());
+        Ok(())
+    }
+
+    fn find_travel_plan(&self, id: &str) -> RepositoryResult<Option<TravelPlan>> {
+        Ok(self
+            .state()?
+            .travel_plans
+            .iter()
+            .find(|p| p.id == id)
+            .cloned())
+    }
+
+    fn find_travel_plans_by_user(&self, user_id: &str) -> RepositoryResult<Vec<TravelPlan>> {
+        let mut plans: Vec<TravelPlan> = self
+            .state()?
+            .travel_plans
+            .iter()
+            .filter(|p| p.user_id == user_id)
+            .cloned()
+            .collect();
+        plans.sort_by_key(|p| std::cmp::Reverse(p.created_at));
+        Ok(plans)
+    }
+
+    fn update_travel_plan(
+        &self,
+        plan: &TravelPlan,
+        expected_updated_at: DateTime<Utc>,
+    ) -> RepositoryResult<usize> {
+        let mut state = self.state()?;
+        match state
+            .travel_plans
+            .iter_mut()
+            .find(|p| p.id == plan.id && p.updated_at == expected_updated_at)
+        {
+            Some(existing) => {
+                let selected_route_id = existing.selected_route_id.take();
+                *existing = plan.clone();
+                existing.selected_route_id = selected_route_id;
+                Ok(1)
+            }
+            None => Ok(0),
+        }
+    }
+
+    fn set_selected_route(
+        &self,
+        plan_id: &str,
+        route_id: Option<&str>,
+        updated_at: DateTime<Utc>,
+    ) -> RepositoryResult<bool> {
+        let mut state = self.state()?;
+        match state.travel_plans.iter_mut().find(|p| p.id == plan_id) {
+            Some(plan) => {
+                plan.selected_route_id = route_id.map(str::to_string);
+                plan.updated_at = updated_at;
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    fn delete_travel_plan(
+        &self,
+        id: &str,
+        expected_updated_at: Option<DateTime<Utc>>,
+    ) -> RepositoryResult<bool> {
+        let mut state = self.state()?;
+        let before = state.travel_plans.len();
+        state.travel_plans.retain(|p| {
+            p.id != id || expected_updated_at.is_some_and(|expected| p.updated_at != expected)
+        });
+        if state.travel_plans.len() == before {
+            return Ok(false);
+        }
+
+        let route_ids: Vec<String> = state
+            .route_options
+            .iter()
+            .filter(|r| r.travel_plan_id == id)
+            .map(|r| r.id.clone())
+            .collect();
+        state.points_of_interest.retain(|poi| !route_ids.contains(&poi.route_option_id));
+        state.route_votes.retain(|v| v.travel_plan_id != id);
+        state.comments.retain(|c| c.travel_plan_id != id);
+        state.itinerary_items.retain(|i| i.travel_plan_id != id);
+        state.route_options.retain(|r| r.travel_plan_id != id);
+        state.plan_legs.retain(|l| l.travel_plan_id != id);
+        state.plan_revisions.retain(|r| r.travel_plan_id != id);
+        state.expenses.retain(|e| e.travel_plan_id != id);
+        state.budgets.retain(|b| b.travel_plan_id != id);
+        state.plan_members.retain(|m| m.travel_plan_id != id);
+        state.share_links.retain(|l| l.travel_plan_id != id);
+        Ok(true)
+    }
+}
+
+impl PlanMemberRepository for InMemoryRepository {
+    fn insert_plan_member(&self, member: &PlanMember) -> RepositoryResult<()> {
+        let mut state = self.state()?;
+
+        if state.plan_members.iter().any(|m| {
+            m.travel_plan_id == member.travel_plan_id && m.user_id == member.user_id
+        }) {
+            return Err(RepositoryError::DatabaseError(
+                "UNIQUE constraint failed: plan_members.travel_plan_id, plan_members.user_id"
+                    .to_string(),
+            ));
+        }
+
+        state.plan_members.push(member.clone());
+        Ok(())
+    }
+
+    fn find_plan_member(&self, plan_id: &str, user_id: &str) -> RepositoryResult<Option<PlanMember>> {
+        Ok(self
+            .state()?
+            .plan_members
+            .iter()
+            .find(|m| m.travel_plan_id == plan_id && m.user_id == user_id)
+            .cloned())
+    }
+
+    fn find_plan_members(&self, plan_id: &str) -> RepositoryResult<Vec<PlanMember>> {
+        let mut members: Vec<PlanMember> = self
+            .state()?
+            .plan_members
+            .iter()
+            .filter(|m| m.travel_plan_id == plan_id)
+            .cloned()
+            .collect();
+        members.sort_by_key(|m| m.created_at);
+        Ok(members)
+    }
+
+    fn find_memberships_by_user(&self, user_id: &str) -> RepositoryResult<Vec<PlanMember>> {
+        let mut members: Vec<PlanMember> = self
+            .state()?
+            .plan_members
+            .iter()
+            .rev()
+            .filter(|m| m.user_id == user_id)
+            .cloned()
+            .collect();
+        members.sort_by_key(|m| std::cmp::Reverse(m.created_at));
+        Ok(members)
+    }
+
+    fn find_shared_travel_plans(&self, user_id: &str) -> RepositoryResult<Vec<TravelPlan>> {
+        let state = self.state()?;
+        let mut plans: Vec<TravelPlan> = state
+            .travel_plans
+            .iter()
+            .filter(|p| {
+                state.plan_members.iter().any(|m| {
+                    m.travel_plan_id == p.id
+                        && m.user_id == user_id
+                        && m.status == MemberStatus::Accepted
+                })
+            })
+            .cloned()
+            .collect();
+        plans.sort_by_key(|p| std::cmp::Reverse(p.created_at));
+        Ok(plans)
+    }
+
+    fn update_plan_member(&self, member: &PlanMember) -> RepositoryResult<()> {
+        let mut state = self.state()?;
+        if let Some(existing) = state.plan_members.iter_mut().find(|m| m.id == member.id) {
+            existing.role = member.role;
+            existing.status = member.status;
+            existing.responded_at = member.responded_at;
+        }
+        Ok(())
+    }
+
+    fn delete_plan_member(&self, plan_id: &str, user_id: &str) -> RepositoryResult<bool> {
+        let mut state = self.state()?;
+        let before = state.plan_members.len();
+        state
+            .plan_members
+            .retain(|m| !(m.travel_plan_id == plan_id && m.user_id == user_id));
+        Ok(state.plan_members.len() < before)
+    }
+
+    fn delete_plan_members_by_plan(&self, plan_id: &str) -> RepositoryResult<usize> {
+        let mut state = self.state()?;
+        let before = state.plan_members.len();
+        state.plan_members.retain(|m| m.travel_plan_id != plan_id);
+        Ok(before - state.plan_members.len())
+    }
+}
+
+impl ShareLinkRepository for InMemoryRepository {
+    fn insert_share_link(&self, link: &ShareLink) -> RepositoryResult<()> {
+        let mut state = self.state()?;
+
+        if state.share_links.iter().any(|l| l.token == link.token) {
+            return Err(RepositoryError::DatabaseError(
+                "UNIQUE constraint failed: share_links.token".to_string(),
+            ));
+        }
+
+        state.share_links.push(link.clone());
+        Ok(())
+    }
+
+    fn find_share_link(&self, id: &str) -> RepositoryResult<Option<ShareLink>> {
+        Ok(self.state()?.share_links.iter().find(|l| l.id == id).cloned())
+    }
+
+    fn find_share_link_by_token(&self, token: &str) -> RepositoryResult<Option<ShareLink>> {
+        Ok(self
+            .state()?
+            .share_links
+            .iter()
+            .find(|l| l.token == token)
+            .cloned())
+    }
+
+    fn find_share_links_by_plan(&self, plan_id: &str) -> RepositoryResult<Vec<ShareLink>> {
+        let mut links: Vec<ShareLink> = self
+            .state()?
+            .share_links
+            .iter()
+            .rev()
+            .filter(|l| l.travel_plan_id == plan_id)
+            .cloned()
+            .collect();
+        links.sort_by_key(|l| std::cmp::Reverse(l.created_at));
+        Ok(links)
+    }
+
+    fn record_share_link_access(&self, id: &str, at: DateTime<Utc>) -> RepositoryResult<()> {
+        let mut state = self.state()?;
+        if let Some(link) = state.share_links.iter_mut().find(|l| l.id == id) {
+            link.access_count += 1;
+            link.last_accessed_at = Some(at);
+        }
+        Ok(())
+    }
+
+    fn revoke_share_link(&self, id: &str, at: DateTime<Utc>) -> RepositoryResult<bool> {
+        let mut state = self.state()?;
+        match state
+            .share_links
+            .iter_mut()
+            .find(|l| l.id == id && l.revoked_at.is_none())
+        {
+            Some(link) => {
+                link.revoked_at = Some(at);
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    fn delete_share_links_by_plan(&self, plan_id: &str) -> RepositoryResult<usize> {
+        let mut state = self.state()?;
+        let before = state.share_links.len();
+        state.share_links.retain(|l| l.travel_plan_id != plan_id);
+        Ok(before - state.share_links.len())
+    }
+}
+
+impl RouteOptionRepository for InMemoryRepository {
+    fn insert_route_option(&self, route: &RouteOption) -> RepositoryResult<()> {
+        self.state()?.route_options.push(route.clone());
+        Ok(())
+    }
+
+    fn find_route_option(&self, id: &str) -> RepositoryResult<Option<RouteOption>> {
+        Ok(self
+            .state()?
+            .route_options
+            .iter()
+            .find(|r| r.id == id)
+            .cloned())
+    }
+
+    fn find_route_options_by_plan(&self, plan_id: &str) -> RepositoryResult<Vec<RouteOption>> {
+        Ok(self
+            .state()?
+            .route_options
+            .iter()
+            .filter(|r| r.travel_plan_id == plan_id)
+            .cloned()
+            .collect())
+    }
+
+    fn update_route_option(
+        &self,
+        route: &RouteOption,
+        expected_updated_at: DateTime<Utc>,
+    ) -> RepositoryResult<usize> {
+        let mut state = self.state()?;
+        match state
+            .route_options
+            .iter_mut()
+            .find(|r| r.id == route.id && r.updated_at == expected_updated_at)
+        {
+            Some(existing) => {
+                *existing = route.clone();
+                Ok(1)
+            }
+            None => Ok(0),
+        }
+    }
+
+    fn delete_route_option(
+        &self,
+        id: &str,
+        expected_updated_at: Option<DateTime<Utc>>,
+    ) -> RepositoryResult<bool> {
+        let mut state = self.state()?;
+        let before = state.route_options.len();
+        state.route_options.retain(|r| {
+            r.id != id || expected_updated_at.is_some_and(|expected| r.updated_at != expected)
+        });
+        if state.route_options.len() == before {
+            return Ok(false);
+        }
+
+        let owned_by_route = |route_id: Option<&str>| route_id == Some(id);
+        state.itinerary_items.retain(|i| !owned_by_route(i.route_option_id.as_deref()));
+        state.comments.retain(|c| !owned_by_route(c.route_option_id.as_deref()));
+        state.route_votes.retain(|v| v.route_option_id != id);
+        state.points_of_interest.retain(|p| p.route_option_id != id);
+        Ok(true)
+    }
+
+    fn delete_route_options_by_plan(&self, plan_id: &str) -> RepositoryResult<usize> {
+        let mut state = self.state()?;
+        let before = state.route_options.len();
+        state.route_options.retain(|r| r.travel_plan_id != plan_id);
+        Ok(before - state.route_options.len())
+    }
+
+    fn delete_unselected_route_options(
+        &self,
+        plan_id: &str,
+        expected_plan_updated_at: Option<DateTime<Utc>>,
+    ) -> RepositoryResult<Option<Vec<String>>> {
+        let mut state = self.state()?;
+        let Some(plan) = state.travel_plans.iter().find(|p| {
+            p.id == plan_id && expected_plan_updated_at.is_none_or(|e| p.updated_at == e)
+        }) else {
+            return Ok(None);
+        };
+
+        let selected: Vec<String> = plan
+            .selected_route_id
+            .iter()
+            .cloned()
+            .chain(
+                state
+                    .plan_legs
+                    .iter()
+                    .filter(|l| l.travel_plan_id == plan_id)
+                    .filter_map(|l| l.selected_route_id.clone()),
+            )
+            .collect();
+        let route_ids: Vec<String> = state
+            .route_options
+            .iter()
+            .filter(|r| r.travel_plan_id == plan_id && !selected.contains(&r.id))
+            .map(|r| r.id.clone())
+            .collect();
+
+        let doomed =
+            |route_id: Option<&str>| route_id.is_some_and(|id| route_ids.iter().any(|r| r == id));
+        state.route_options.retain(|r| !doomed(Some(&r.id)));
+        state.itinerary_items.retain(|i| !doomed(i.route_option_id.as_deref()));
+        state.comments.retain(|c| !doomed(c.route_option_id.as_deref()));
+        state.route_votes.retain(|v| !doomed(Some(&v.route_option_id)));
+        state.points_of_interest.retain(|p| !doomed(Some(&p.route_option_id)));
+        Ok(Some(route_ids))
+    }
+}
+
+impl RouteVoteRepository for InMemoryRepository {
+    fn upsert_route_vote(&self, vote: &RouteVote) -> RepositoryResult<()> {
+        let mut state = self.state()?;
+        match state.route_votes.iter_mut().find(|v| {
+            v.route_option_id == vote.route_option_id && v.user_id == vote.user_id
+        }) {
+            Some(existing) => {
+                existing.direction = vote.direction;
+                existing.updated_at = vote.updated_at;
+            }
+            None => state.route_votes.push(vote.clone()),
+        }
+        Ok(())
+    }
+
+    fn find_route_votes_by_plan(&self, plan_id: &str) -> RepositoryResult<Vec<RouteVote>> {
+        let mut votes: Vec<RouteVote> = self
+            .state()?
+            .route_votes
+            .iter()
+            .filter(|v| v.travel_plan_id == plan_id)
+            .cloned()
+            .collect();
+        votes.sort_by_key(|v| v.created_at);
+        Ok(votes)
+    }
+
+    fn delete_route_vote(&self, route_id: &str, user_id: &str) -> RepositoryResult<bool> {
+        let mut state = self.state()?;
+        let before = state.route_votes.len();
+        state
+            .route_votes
+            .retain(|v| !(v.route_option_id == route_id && v.user_id == user_id));
+        Ok(state.route_votes.len() < before)
+    }
+
+    fn delete_route_votes_by_route(&self, route_id: &str) -> RepositoryResult<usize> {
+        let mut state = self.state()?;
+        let before = state.route_votes.len();
+        state.route_votes.retain(|v| v.route_option_id != route_id);
+        Ok(before - state.route_votes.len())
+    }
+
+    fn delete_route_votes_by_plan(&self, plan_id: &str) -> RepositoryResult<usize> {
+        let mut state = self.state()?;
+        let before = state.route_votes.len();
+        state.route_votes.retain(|v| v.travel_plan_id != plan_id);
+        Ok(before - state.route_votes.len())
+    }
+}
+
+impl PoiRepository for InMemoryRepository {
+    fn insert_poi(&self, poi: &PointOfInterest) -> RepositoryResult<()> {
+        self.state()?.points_of_interest.push(poi.clone());
+        Ok(())
+    }
+
+    fn find_poi(&self, id: &str) -> RepositoryResult<Option<PointOfInterest>> {
+        Ok(self
+            .state()?
+            .points_of_interest
+            .iter()
+            .find(|p| p.id == id)
+            .cloned())
+    }
+
+    fn find_pois_by_route(&self, route_id: &str) -> RepositoryResult<Vec<PointOfInterest>> {
+        Ok(self
+            .state()?
+            .points_of_interest
+            .iter()
+            .filter(|p| p.route_option_id == route_id)
+            .cloned()
+            .collect())
+    }
+
+    fn update_poi(&self, poi: &PointOfInterest) -> RepositoryResult<()> {
+        let mut state = self.state()?;
+        if let Some(existing) = state.points_of_interest.iter_mut().find(|p| p.id == poi.id) {
+            *existing = poi.clone();
+        }
+        Ok(())
+    }
+
+    fn delete_pois_by_route(&self, route_id: &str) -> RepositoryResult<usize> {
+        let mut state = self.state()?;
+        let before = state.points_of_interest.len();
+        state.points_of_interest.retain(|p| p.route_option_id != route_id);
+        Ok(before - state.points_of_interest.len())
+    }
+}
+
+impl CommentRepository for InMemoryRepository {
+    fn insert_comment(&self, comment: &Comment) -> RepositoryResult<()> {
+        self.state()?.comments.push(comment.clone());
+        Ok(())
+    }
+
+    fn find_comment(&self, id: &str) -> RepositoryResult<Option<Comment>> {
+        Ok(self.state()?.comments.iter().find(|c| c.id == id).cloned())
+    }
+
+    fn find_comment_threads(
+        &self,
+        target_type: CommentTarget,
+        target_id: &str,
+        limit: usize,
+        offset: usize,
+    ) -> RepositoryResult<Vec<Comment>> {
+        let mut threads: Vec<Comment> = self
+            .state()?
+            .comments
+            .iter()
+            .filter(|c| c.target_type == target_type && c.target_id == target_id)
+            .filter(|c| c.parent_id.is_none())
+            .cloned()
+            .collect();
+        threads.sort_by_key(|c| c.created_at);
+        Ok(threads.into_iter().skip(offset).take(limit).collect())
+    }
+
+    fn count_comment_threads(&self, target_type: CommentTarget, target_id: &str) -> RepositoryResult<usize> {
+        Ok(self
+            .state()?
+            .comments
+            .iter()
+            .filter(|c| c.target_type == target_type && c.target_id == target_id)
+            .filter(|c| c.parent_id.is_none())
+            .count())
+    }
+
+    fn find_comments_in_thread(&self, thread_id: &str) -> RepositoryResult<Vec<Comment>> {
+        let mut comments: Vec<Comment> = self
+            .state()?
+            .comments
+            .iter()
+            .filter(|c| c.thread_id == thread_id)
+            .cloned()
+            .collect();
+        comments.sort_by_key(|c| c.created_at);
+        Ok(comments)
+    }
+
+    fn update_comment(&self, comment: &Comment) -> RepositoryResult<()> {
+        let mut state = self.state()?;
+        if let Some(existing) = state.comments.iter_mut().find(|c| c.id == comment.id) {
+            existing.body = comment.body.clone();
+            existing.mentions = comment.mentions.clone();
+            existing.updated_at = comment.updated_at;
+        }
+        Ok(())
+    }
+
+    fn delete_comment(&self, id: &str) -> RepositoryResult<bool> {
+        let mut state = self.state()?;
+        let before = state.comments.len();
+        state.comments.retain(|c| c.id != id);
+        Ok(state.comments.len() < before)
+    }
+
+    fn delete_comments_by_route(&self, route_id: &str) -> RepositoryResult<usize> {
+        let mut state = self.state()?;
+        let before = state.comments.len();
+        state
+            .comments
+            .retain(|c| c.route_option_id.as_deref() != Some(route_id));
+        Ok(before - state.comments.len())
+    }
+
+    fn delete_comments_by_plan(&self, plan_id: &str) -> RepositoryResult<usize> {
+        let mut state = self.state()?;
+        let before = state.comments.len();
+        state.comments.retain(|c| c.travel_plan_id != plan_id);
+        Ok(before - state.comments.len())
+    }
+}
+
+impl PlanEventRepository for InMemoryRepository {
+    fn insert_plan_event(&self, event: &PlanEvent) -> RepositoryResult<()> {
+        self.state()?.plan_events.push(event.clone());
+        Ok(())
+    }
+
+    fn find_plan_events(
+        &self,
+        plan_id: &str,
+        limit: usize,
+        offset: usize,
+    ) -> RepositoryResult<Vec<PlanEvent>> {
+        let mut events: Vec<PlanEvent> = self
+            .state()?
+            .plan_events
+            .iter()
+            .rev()
+            .filter(|e| e.travel_plan_id == plan_id)
+            .cloned()
+            .collect();
+        // Stable, so events with equal timestamps stay newest first
+        events.sort_by_key(|e| std::cmp::Reverse(e.created_at));
+        Ok(events.into_iter().skip(offset).take(limit).collect())
+    }
+}
+
+impl PlanRevisionRepository for InMemoryRepository {
+    fn insert_plan_revision(&self, revision: &PlanRevision) -> RepositoryResult<i64> {
+        let mut state = self.state()?;
+        let number = state
+            .plan_revisions
+            .iter()
+            .filter(|r| r.travel_plan_id == revision.travel_plan_id)
+            .map(|r| r.revision)
+            .max()
+            .unwrap_or(0)
+            + 1;
+        state.plan_revisions.push(PlanRevision {
+            revision: number,
+            ..revision.clone()
+        });
+        Ok(number)
+    }
+
+    fn find_plan_revisions(&self, plan_id: &str) -> RepositoryResult<Vec<PlanRevision>> {
+        let mut revisions: Vec<PlanRevision> = self
+            .state()?
+            .plan_revisions
+            .iter()
+            .filter(|r| r.travel_plan_id == plan_id)
+            .cloned()
+            .collect();
+        revisions.sort_by_key(|r| std::cmp::Reverse(r.revision));
+        Ok(revisions)
+    }
+
+    fn find_plan_revision(
+        &self,
+        plan_id: &str,
+        revision: i64,
+    ) -> RepositoryResult<Option<PlanRevision>> {
+        Ok(self
+            .state()?
+            .plan_revisions
+            .iter()
+            .find(|r| r.travel_plan_id == plan_id && r.revision == revision)
+            .cloned())
+    }
+
+    fn delete_plan_revisions_by_plan(&self, plan_id: &str) -> RepositoryResult<usize> {
+        let mut state = self.state()?;
+        let before = state.plan_revisions.len();
+        state.plan_revisions.retain(|r| r.travel_plan_id != plan_id);
+        Ok(before - state.plan_revisions.len())
+    }
+}
+
+impl ItineraryRepository for InMemoryRepository {
+    fn insert_itinerary_item(&self, item: &ItineraryItem) -> RepositoryResult<()> {
+        self.state()?.itinerary_items.push(item.clone());
+        Ok(())
+    }
+
+    fn find_itinerary_item(&self, id: &str) -> RepositoryResult<Option<ItineraryItem>> {
+        Ok(self.state()?.itinerary_items.iter().find(|i| i.id == id).cloned())
+    }
+
+    fn find_itinerary_items(&self, plan_id: &str) -> RepositoryResult<Vec<ItineraryItem>> {
+        let mut items: Vec<ItineraryItem> = self
+            .state()?
+            .itinerary_items
+            .iter()
+            .filter(|i| i.travel_plan_id == plan_id)
+            .cloned()
+            .collect();
+        items.sort_by_key(|i| (i.day, i.position));
+        Ok(items)
+    }
+
+    fn update_itinerary_item(&self, item: &ItineraryItem) -> RepositoryResult<()> {
+        let mut state = self.state()?;
+        if let Some(existing) = state.itinerary_items.iter_mut().find(|i| i.id == item.id) {
+            existing.day = item.day;
+            existing.position = item.position;
+            existing.title = item.title.clone();
+            existing.notes = item.notes.clone();
+            existing.starts_at = item.starts_at;
+            existing.ends_at = item.ends_at;
+            existing.updated_at = item.updated_at;
+        }
+        Ok(())
+    }
+
+    fn delete_itinerary_item(&self, id: &str) -> RepositoryResult<bool> {
+        let mut state = self.state()?;
+        let before = state.itinerary_items.len();
+        state.itinerary_items.retain(|i| i.id != id);
+        Ok(state.itinerary_items.len() < before)
+    }
+
+    fn delete_itinerary_items_by_route(&self, route_id: &str) -> RepositoryResult<usize> {
+        let mut state = self.state()?;
+        let before = state.itinerary_items.len();
+        state
+            .itinerary_items
+            .retain(|i| i.route_option_id.as_deref() != Some(route_id));
+        Ok(before - state.itinerary_items.len())
+    }
+
+    fn delete_itinerary_items_by_plan(&self, plan_id: &str) -> RepositoryResult<usize> {
+        let mut state = self.state()?;
+        let before = state.itinerary_items.len();
+        state.itinerary_items.retain(|i| i.travel_plan_id != plan_id);
+        Ok(before - state.itinerary_items.len())
+    }
+}
+
+impl PlanLegRepository for InMemoryRepository {
+    fn insert_plan_leg(&self, leg: &PlanLeg) -> RepositoryResult<()> {
+        self.state()?.plan_legs.push(leg.clone());
+        Ok(())
+    }
+
+    fn find_plan_leg(&self, id: &str) -> RepositoryResult<Option<PlanLeg>> {
+        Ok(self.state()?.plan_legs.iter().find(|l| l.id == id).cloned())
+    }
+
+    fn find_plan_legs(&self, plan_id: &str) -> RepositoryResult<Vec<PlanLeg>> {
+        let mut legs: Vec<PlanLeg> = self
+            .state()?
+            .plan_legs
+            .iter()
+            .filter(|l| l.travel_plan_id == plan_id)
+            .cloned()
+            .collect();
+        legs.sort_by_key(|l| l.position);
+        Ok(legs)
+    }
+
+    fn update_plan_leg(&self, leg: &PlanLeg) -> RepositoryResult<()> {
+        let mut state = self.state()?;
+        if let Some(existing) = state.plan_legs.iter_mut().find(|l| l.id == leg.id) {
+            existing.position = leg.position;
+            existing.start_location = leg.start_location.clone();
+            existing.end_location = leg.end_location.clone();
+            existing.start_date = leg.start_date;
+            existing.end_date = leg.end_date;
+            existing.mode = leg.mode;
+            existing.selected_route_id = leg.selected_route_id.clone();
+            existing.updated_at = leg.updated_at;
+        }
+        Ok(())
+    }
+
+    fn delete_plan_leg(&self, id: &str) -> RepositoryResult<bool> {
+        let mut state = self.state()?;
+        let before = state.plan_legs.len();
+        state.plan_legs.retain(|l| l.id != id);
+        Ok(state.plan_legs.len() < before)
+    }
+
+    fn delete_plan_legs_by_plan(&self, plan_id: &str) -> RepositoryResult<usize> {
+        let mut state = self.state()?;
+        let before = state.plan_legs.len();
+        state.plan_legs.retain(|l| l.travel_plan_id != plan_id);
+        Ok(before - state.plan_legs.len())
+    }
+}
+
+impl BudgetRepository for InMemoryRepository {
+    fn insert_budget(&self, budget: &Budget) -> RepositoryResult<()> {
+        let mut state = self.state()?;
+        if state.budgets.iter().any(|b| {
+            b.travel_plan_id == budget.travel_plan_id && b.category == budget.category
+        }) {
+            return Err(RepositoryError::DatabaseError(
+                "UNIQUE constraint failed: budgets.travel_plan_id, budgets.category".to_string(),
+            ));
+        }
+        state.budgets.push(budget.clone());
+        Ok(())
+    }
+
+    fn find_budgets(&self, plan_id: &str) -> RepositoryResult<Vec<Budget>> {
+        let mut budgets: Vec<Budget> = self
+            .state()?
+            .budgets
+            .iter()
+            .filter(|b| b.travel_plan_id == plan_id)
+            .cloned()
+            .collect();
+        budgets.sort_by_key(|b| b.category.as_str());
+        Ok(budgets)
+    }
+
+    fn update_budget(&self, budget: &Budget) -> RepositoryResult<()> {
+        let mut state = self.state()?;
+        if let Some(existing) = state.budgets.iter_mut().find(|b| b.id == budget.id) {
+            existing.amount = budget.amount;
+            existing.currency = budget.currency.clone();
+            existing.updated_at = budget.updated_at;
+        }
+        Ok(())
+    }
+
+    fn delete_budget(&self, id: &str) -> RepositoryResult<bool> {
+        let mut state = self.state()?;
+        let before = state.budgets.len();
+        state.budgets.retain(|b| b.id != id);
+        Ok(state.budgets.len() < before)
+    }
+
+    fn delete_budgets_by_plan(&self, plan_id: &str) -> RepositoryResult<usize> {
+        let mut state = self.state()?;
+        let before = state.budgets.len();
+        state.budgets.retain(|b| b.travel_plan_id != plan_id);
+        Ok(before - state.budgets.len())
+    }
+
+    fn insert_expense(&self, expense: &Expense) -> RepositoryResult<()> {
+        self.state()?.expenses.push(expense.clone());
+        Ok(())
+    }
+
+    fn find_expense(&self, id: &str) -> RepositoryResult<Option<Expense>> {
+        Ok(self.state()?.expenses.iter().find(|e| e.id == id).cloned())
+    }
+
+    fn find_expenses(&self, plan_id: &str) -> RepositoryResult<Vec<Expense>> {
+        let mut expenses: Vec<Expense> = self
+            .state()?
+            .expenses
+            .iter()
+            .filter(|e| e.travel_plan_id == plan_id)
+            .cloned()
+            .collect();
+        expenses.sort_by_key(|e| (e.spent_on, e.created_at));
+        Ok(expenses)
+    }
+
+    fn update_expense(&self, expense: &Expense) -> RepositoryResult<()> {
+        let mut state = self.state()?;
+        if let Some(existing) = state.expenses.iter_mut().find(|e| e.id == expense.id) {
+            existing.category = expense.category;
+            existing.description = expense.description.clone();
+            existing.amount = expense.amount;
+            existing.currency = expense.currency.clone();
+            existing.paid_by = expense.paid_by.clone();
+            existing.split_between = expense.split_between.clone();
+            existing.spent_on = expense.spent_on;
+            existing.updated_at = expense.updated_at;
+        }
+        Ok(())
+    }
+
+    fn delete_expense(&self, id: &str) -> RepositoryResult<bool> {
+        let mut state = self.state()?;
+        let before = state.expenses.len();
+        state.expenses.retain(|e| e.id != id);
+        Ok(state.expenses.len() < before)
+    }
+
+    fn delete_expenses_by_plan(&self, plan_id: &str) -> RepositoryResult<usize> {
+        let mut state = self.state()?;
+        let before = state.expenses.len();
+        state.expenses.retain(|e| e.travel_plan_id != plan_id);
+        Ok(before - state.expenses.len())
+    }
+}
+
+impl MaintenanceRepository for InMemoryRepository {
+    fn schema_version(&self) -> RepositoryResult<usize> {
+        Ok(SCHEMA_VERSION)
+    }
+
+    fn count_rows(&self) -> RepositoryResult<Vec<TableCount>> {
+        let state = self.state()?;
+        let counts = [
+            state.users.len(),
+            state.travel_plans.len(),
+            state.route_options.len(),
+            state.points_of_interest.len(),
+        ];
+
+        Ok(TABLES
+            .iter()
+            .zip(counts)
+            .map(|(table, rows)| TableCount {
+                table: table.to_string(),
+                rows: rows as i64,
+            })
+            .collect())
+    }
+
+    fn purge_orphans(&self) -> RepositoryResult<OrphanReport> {
+        let mut guard = self.state()?;
+        let state = &mut *guard;
+        let mut report = OrphanReport::default();
+
+        let before = state.travel_plans.len();
+        let users = &state.users;
+        state
+            .travel_plans
+            .retain(|p| users.iter().any(|u| u.id == p.user_id));
+        report.travel_plans = before - state.travel_plans.len();
+
+        let before = state.route_options.len();
+        let plans = &state.travel_plans;
+        state
+            .route_options
+            .retain(|r| plans.iter().any(|p| p.id == r.travel_plan_id));
+        report.route_options = before - state.route_options.len();
+
+        let before = state.points_of_interest.len();
+        let routes = &state.route_options;
+        state
+            .points_of_interest
+            .retain(|poi| routes.iter().any(|r| r.id == poi.route_option_id));
+        report.points_of_interest = before - state.points_of_interest.len();
+
+        Ok(report)
+    }
+
+    fn vacuum(&self) -> RepositoryResult<()> {
+        Ok(())
+    }
+
+    fn backup_to(&self, _dir: &Path) -> RepositoryResult<BackupInfo> {
+        Err(RepositoryError::Unsupported(
+            "in-memory storage cannot be backed up".to_string(),
+        ))
+    }
+}
+
+impl AuditRepository for InMemoryRepository {
+    fn insert_audit_entry(&self, entry: &AuditEntry) -> RepositoryResult<()> {
+        self.state()?.audit_log.push(entry.clone());
+        Ok(())
+    }
+
+    fn find_audit_entries(&self, limit: usize, offset: usize) -> RepositoryResult<Vec<AuditEntry>> {
+        let mut entries = self.state()?.audit_log.clone();
+        // Stable, so entries with equal timestamps stay newest-inserted first
+        entries.reverse();
+        entries.sort_by_key(|e| std::cmp::Reverse(e.created_at));
+        Ok(entries.into_iter().skip(offset).take(limit).collect())
+    }
+}
@@ -0,0 +1,368 @@
+pub mod memory;
+pub mod postgres;
+pub mod sqlite;
+#[cfg(test)]
+mod conformance;
+
+use std::fmt;
+use std::path::Path;
+use std::sync::Arc;
+
+use chrono::{DateTime, Utc};
+use log::info;
+
+use crate::db::backup::{BackupError, BackupInfo};
+use crate::db::connection::{self, DbError};
+use crate::db::maintenance::{OrphanReport, TableCount};
+use crate::db::postgres::create_pg_pool;
+
+use crate::models::audit::AuditEntry;
+use crate::models::plan_member::PlanMember;
+use crate::models::point_of_interest::PointOfInterest;
+use crate::models::route_option::RouteOption;
+use crate::models::route_vote::RouteVote;
+use crate::models::comment::{Comment, CommentTarget};
+use crate::models::itinerary::ItineraryItem;
+use crate::models::plan_leg::PlanLeg;
+use crate::models::budget::{Budget, Expense};
+use crate::models::plan_event::PlanEvent;
+use crate::models::plan_revision::PlanRevision;
+use crate::models::share_link::ShareLink;
+use crate::models::travel_plan::TravelPlan;
+use crate::models::user::{Role, User};
+use crate::services::blocking::BlockingError;
+
+#[derive(Debug)]
+pub enum RepositoryError {
+    ConnectionError(String),
+    DatabaseError(String),
+    Unsupported(String),
+}
+
+impl fmt::Display for RepositoryError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RepositoryError::ConnectionError(e) => write!(f, "Database connection error: {}", e),
+            RepositoryError::DatabaseError(e) => write!(f, "{}", e),
+            RepositoryError::Unsupported(e) => write!(f, "Not supported by this backend: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for RepositoryError {}
+
+impl From<rusqlite::Error> for RepositoryError {
+    fn from(err: rusqlite::Error) -> Self {
+        RepositoryError::DatabaseError(err.to_string())
+    }
+}
+
+impl From<::postgres::Error> for RepositoryError {
+    fn from(err: ::postgres::Error) -> Self {
+        RepositoryError::DatabaseError(err.to_string())
+    }
+}
+
+impl From<BackupError> for RepositoryError {
+    fn from(err: BackupError) -> Self {
+        RepositoryError::DatabaseError(err.to_string())
+    }
+}
+
+impl From<BlockingError> for RepositoryError {
+    fn from(err: BlockingError) -> Self {
+        RepositoryError::ConnectionError(err.to_string())
+    }
+}
+
+impl From<r2d2::Error> for RepositoryError {
+    fn from(err: r2d2::Error) -> Self {
+        RepositoryError::ConnectionError(err.to_string())
+    }
+}
+
+pub type RepositoryResult<T> = Result<T, RepositoryError>;
+
+pub trait UserRepository {
+    fn insert_user(&self, user: &User) -> RepositoryResult<()>;
+    fn find_user_by_id(&self, id: &str) -> RepositoryResult<Option<User>>;
+    fn find_user_by_username(&self, username: &str) -> RepositoryResult<Option<User>>;
+    fn find_user_by_email(&self, email: &str) -> RepositoryResult<Option<User>>;
+    fn set_user_disabled(&self, id: &str, disabled: bool) -> RepositoryResult<bool>;
+    fn update_user_password(&self, id: &str, password_hash: &str) -> RepositoryResult<bool>;
+    fn set_user_role(&self, id: &str, role: Role) -> RepositoryResult<bool>;
+    /// Every account, oldest first.
+    fn list_users(&self) -> RepositoryResult<Vec<User>>;
+}
+
+pub trait TravelPlanRepository {
+    fn insert_travel_plan(&self, plan: &TravelPlan) -> RepositoryResult<()>;
+    fn find_travel_plan(&self, id: &str) -> RepositoryResult<Option<TravelPlan>>;
+    fn find_travel_plans_by_user(&self, user_id: &str) -> RepositoryResult<Vec<TravelPlan>>;
+    /// Updates the editable fields; leaves the selected route alone. Only
+    /// writes while the stored plan was last updated at `expected_updated_at`,
+    /// and returns the rows affected: 0 when it changed in between.
+    fn update_travel_plan(
+        &self,
+        plan: &TravelPlan,
+        expected_updated_at: DateTime<Utc>,
+    ) -> RepositoryResult<usize>;
+    fn set_selected_route(
+        &self,
+        plan_id: &str,
+        route_id: Option<&str>,
+        updated_at: DateTime<Utc>,
+    ) -> RepositoryResult<bool>;
+    /// Deletes the plan with its routes, legs, members and everything else
+    /// it owns, all or nothing. Its activity log is kept. With
+    /// `expected_updated_at`, nothing is deleted and false returned unless the
+    /// stored plan was last updated then.
+    fn delete_travel_plan(
+        &self,
+        id: &str,
+        expected_updated_at: Option<DateTime<Utc>>,
+    ) -> RepositoryResult<bool>;
+}
+
+pub trait PlanMemberRepository {
+    fn insert_plan_member(&self, member: &PlanMember) -> RepositoryResult<()>;
+    fn find_plan_member(&self, plan_id: &str, user_id: &str) -> RepositoryResult<Option<PlanMember>>;
+    /// Members of a plan in invitation order.
+    fn find_plan_members(&self, plan_id: &str) -> RepositoryResult<Vec<PlanMember>>;
+    /// Memberships of a user, newest first.
+    fn find_memberships_by_user(&self, user_id: &str) -> RepositoryResult<Vec<PlanMember>>;
+    /// Plans whose invitations the user has accepted, newest first.
+    fn find_shared_travel_plans(&self, user_id: &str) -> RepositoryResult<Vec<TravelPlan>>;
+    fn update_plan_member(&self, member: &PlanMember) -> RepositoryResult<()>;
+    fn delete_plan_member(&self, plan_id: &str, user_id: &str) -> RepositoryResult<bool>;
+    fn delete_plan_members_by_plan(&self, plan_id: &str) -> RepositoryResult<usize>;
+}
+
+pub trait ShareLinkRepository {
+    fn insert_share_link(&self, link: &ShareLink) -> RepositoryResult<()>;
+    fn find_share_link(&self, id: &str) -> RepositoryResult<Option<ShareLink>>;
+    fn find_share_link_by_token(&self, token: &str) -> RepositoryResult<Option<ShareLink>>;
+    /// Links of a plan, newest first.
+    fn find_share_links_by_plan(&self, plan_id: &str) -> RepositoryResult<Vec<ShareLink>>;
+    /// Atomically bumps the access count and last access time.
+    fn record_share_link_access(&self, id: &str, at: DateTime<Utc>) -> RepositoryResult<()>;
+    /// Returns false when the link does not exist or is already revoked.
+    fn revoke_share_link(&self, id: &str, at: DateTime<Utc>) -> RepositoryResult<bool>;
+    fn delete_share_links_by_plan(&self, plan_id: &str) -> RepositoryResult<usize>;
+}
+
+pub trait RouteOptionRepository {
+    fn insert_route_option(&self, route: &RouteOption) -> RepositoryResult<()>;
+    fn find_route_option(&self, id: &str) -> RepositoryResult<Option<RouteOption>>;
+    fn find_route_options_by_plan(&self, plan_id: &str) -> RepositoryResult<Vec<RouteOption>>;
+    /// Only writes while the stored route was last updated at
+    /// `expected_updated_at`; returns the rows affected.
+    fn update_route_option(
+        &self,
+        route: &RouteOption,
+        expected_updated_at: DateTime<Utc>,
+    ) -> RepositoryResult<usize>;
+    /// Deletes the route with its points of interest, votes, comments and
+    /// itinerary items, all or nothing. With `expected_updated_at`, nothing
+    /// is deleted and false returned unless the stored route was last updated
+    /// then.
+    fn delete_route_option(
+        &self,
+        id: &str,
+        expected_updated_at: Option<DateTime<Utc>>,
+    ) -> RepositoryResult<bool>;
+    fn delete_route_options_by_plan(&self, plan_id: &str) -> RepositoryResult<usize>;
+    /// Deletes the plan's routes that neither the plan nor one of its legs
+    /// has selected, each with everything on it, all or nothing. The
+    /// selection is read in the same transaction. Returns the ids deleted, or
+    /// `None` when the plan is missing or, with `expected_plan_updated_at`,
+    /// was not last updated then.
+    fn delete_unselected_route_options(
+        &self,
+        plan_id: &str,
+        expected_plan_updated_at: Option<DateTime<Utc>>,
+    ) -> RepositoryResult<Option<Vec<String>>>;
+}
+
+pub trait RouteVoteRepository {
+    /// Records the vote, replacing the user's earlier vote on the same route.
+    fn upsert_route_vote(&self, vote: &RouteVote) -> RepositoryResult<()>;
+    fn find_route_votes_by_plan(&self, plan_id: &str) -> RepositoryResult<Vec<RouteVote>>;
+    fn delete_route_vote(&self, route_id: &str, user_id: &str) -> RepositoryResult<bool>;
+    fn delete_route_votes_by_route(&self, route_id: &str) -> RepositoryResult<usize>;
+    fn delete_route_votes_by_plan(&self, plan_id: &str) -> RepositoryResult<usize>;
+}
+
+pub trait PoiRepository {
+    fn insert_poi(&self, poi: &PointOfInterest) -> RepositoryResult<()>;
+    fn find_poi(&self, id: &str) -> RepositoryResult<Option<PointOfInterest>>;
+    fn find_pois_by_route(&self, route_id: &str) -> RepositoryResult<Vec<PointOfInterest>>;
+    fn update_poi(&self, poi: &PointOfInterest) -> RepositoryResult<()>;
+    fn delete_pois_by_route(&self, route_id: &str) -> RepositoryResult<usize>;
+}
+
+pub trait CommentRepository {
+    fn insert_comment(&self, comment: &Comment) -> RepositoryResult<()>;
+    fn find_comment(&self, id: &str) -> RepositoryResult<Option<Comment>>;
+    /// Top-level comments on a target, oldest first.
+    fn find_comment_threads(
+        &self,
+        target_type: CommentTarget,
+        target_id: &str,
+        limit: usize,
+        offset: usize,
+    ) -> RepositoryResult<Vec<Comment>>;
+    fn count_comment_threads(&self, target_type: CommentTarget, target_id: &str) -> RepositoryResult<usize>;
+    /// The top-level comment and all its replies, oldest first.
+    fn find_comments_in_thread(&self, thread_id: &str) -> RepositoryResult<Vec<Comment>>;
+    /// Stores a new body and mentions; nothing else changes.
+    fn update_comment(&self, comment: &Comment) -> RepositoryResult<()>;
+    fn delete_comment(&self, id: &str) -> RepositoryResult<bool>;
+    /// Removes comments on the route and on its points of interest.
+    fn delete_comments_by_route(&self, route_id: &str) -> RepositoryResult<usize>;
+    fn delete_comments_by_plan(&self, plan_id: &str) -> RepositoryResult<usize>;
+}
+
+/// The append-only activity log of each plan.
+pub trait PlanEventRepository {
+    fn insert_plan_event(&self, event: &PlanEvent) -> RepositoryResult<()>;
+    /// Newest events first.
+    fn find_plan_events(
+        &self,
+        plan_id: &str,
+        limit: usize,
+        offset: usize,
+    ) -> RepositoryResult<Vec<PlanEvent>>;
+}
+
+/// Earlier states of each plan, numbered per plan.
+pub trait PlanRevisionRepository {
+    /// Stores the revision under the plan's next number, ignoring
+    /// `revision.revision`, and returns the number assigned.
+    fn insert_plan_revision(&self, revision: &PlanRevision) -> RepositoryResult<i64>;
+    /// Newest revision first.
+    fn find_plan_revisions(&self, plan_id: &str) -> RepositoryResult<Vec<PlanRevision>>;
+    fn find_plan_revision(
+        &self,
+        plan_id: &str,
+        revision: i64,
+    ) -> RepositoryResult<Option<PlanRevision>>;
+    fn delete_plan_revisions_by_plan(&self, plan_id: &str) -> RepositoryResult<usize>;
+}
+
+/// Items on the days of each plan's itinerary.
+pub trait ItineraryRepository {
+    fn insert_itinerary_item(&self, item: &ItineraryItem) -> RepositoryResult<()>;
+    fn find_itinerary_item(&self, id: &str) -> RepositoryResult<Option<ItineraryItem>>;
+    /// Ordered by day, then position.
+    fn find_itinerary_items(&self, plan_id: &str) -> RepositoryResult<Vec<ItineraryItem>>;
+    fn update_itinerary_item(&self, item: &ItineraryItem) -> RepositoryResult<()>;
+    fn delete_itinerary_item(&self, id: &str) -> RepositoryResult<bool>;
+    /// Removes legs on the route and visits to its points of interest.
+    fn delete_itinerary_items_by_route(&self, route_id: &str) -> RepositoryResult<usize>;
+    fn delete_itinerary_items_by_plan(&self, plan_id: &str) -> RepositoryResult<usize>;
+}
+
+/// The legs of multi-city plans.
+pub trait PlanLegRepository {
+    fn insert_plan_leg(&self, leg: &PlanLeg) -> RepositoryResult<()>;
+    fn find_plan_leg(&self, id: &str) -> RepositoryResult<Option<PlanLeg>>;
+    /// Ordered by position.
+    fn find_plan_legs(&self, plan_id: &str) -> RepositoryResult<Vec<PlanLeg>>;
+    fn update_plan_leg(&self, leg: &PlanLeg) -> RepositoryResult<()>;
+    fn delete_plan_leg(&self, id: &str) -> RepositoryResult<bool>;
+    fn delete_plan_legs_by_plan(&self, plan_id: &str) -> RepositoryResult<usize>;
+}
+
+/// Budgets and expenses of each plan.
+pub trait BudgetRepository {
+    fn insert_budget(&self, budget: &Budget) -> RepositoryResult<()>;
+    /// Ordered by category.
+    fn find_budgets(&self, plan_id: &str) -> RepositoryResult<Vec<Budget>>;
+    fn update_budget(&self, budget: &Budget) -> RepositoryResult<()>;
+    fn delete_budget(&self, id: &str) -> RepositoryResult<bool>;
+    fn delete_budgets_by_plan(&self, plan_id: &str) -> RepositoryResult<usize>;
+    fn insert_expense(&self, expense: &Expense) -> RepositoryResult<()>;
+    fn find_expense(&self, id: &str) -> RepositoryResult<Option<Expense>>;
+    /// Ordered by the day spent, then when recorded.
+    fn find_expenses(&self, plan_id: &str) -> RepositoryResult<Vec<Expense>>;
+    fn update_expense(&self, expense: &Expense) -> RepositoryResult<()>;
+    fn delete_expense(&self, id: &str) -> RepositoryResult<bool>;
+    fn delete_expenses_by_plan(&self, plan_id: &str) -> RepositoryResult<usize>;
+}
+
+/// Whole-database housekeeping used by the admin tooling.
+pub trait MaintenanceRepository {
+    fn schema_version(&self) -> RepositoryResult<usize>;
+    fn count_rows(&self) -> RepositoryResult<Vec<TableCount>>;
+    fn purge_orphans(&self) -> RepositoryResult<OrphanReport>;
+    fn vacuum(&self) -> RepositoryResult<()>;
+    /// Writes an online snapshot of the whole database into `dir`.
+    fn backup_to(&self, dir: &Path) -> RepositoryResult<BackupInfo>;
+}
+
+pub trait AuditRepository {
+    fn insert_audit_entry(&self, entry: &AuditEntry) -> RepositoryResult<()>;
+    /// Newest entries first.
+    fn find_audit_entries(&self, limit: usize, offset: usize) -> RepositoryResult<Vec<AuditEntry>>;
+}
+
+/// Every repository the services need, implemented by each storage backend.
+///
+/// Handlers share one `dyn Repositories` so the backend can be chosen at
+/// startup, while services stay generic over the individual traits.
+pub trait Repositories:
+    UserRepository
+    + TravelPlanRepository
+    + PlanMemberRepository
+    + ShareLinkRepository
+    + RouteOptionRepository
+    + RouteVoteRepository
+    + PoiRepository
+    + CommentRepository
+    + PlanEventRepository
+    + PlanRevisionRepository
+    + ItineraryRepository
+    + PlanLegRepository
+    + BudgetRepository
+    + MaintenanceRepository
+    + AuditRepository
+    + Send
+    + Sync
+{
+}
+
+impl<T> Repositories for T where
+    T: UserRepository
+        + TravelPlanRepository
+        + PlanMemberRepository
+        + ShareLinkRepository
+        + RouteOptionRepository
+        + RouteVoteRepository
+        + PoiRepository
+        + CommentRepository
+        + PlanEventRepository
+        + PlanRevisionRepository
+        + ItineraryRepository
+        + PlanLegRepository
+        + BudgetRepository
+        + MaintenanceRepository
+        + AuditRepository
+        + Send
+        + Sync
+{
+}
+
+/// Opens the storage backend named by `database_url`: PostgreSQL for
+/// `postgres://` URLs, otherwise a SQLite database file at that path.
+pub fn connect(database_url: &str) -> Result<Arc<dyn Repositories>, DbError> {
+    if connection::is_postgres_url(database_url) {
+        info!("Using PostgreSQL storage backend");
+        let pool = create_pg_pool(database_url)?;
+        Ok(Arc::new(postgres::PostgresRepository::new(pool)))
+    } else {
+        info!("Using SQLite storage backend");
+        let pool = connection::create_pool(database_url)?;
+        Ok(Arc::new(sqlite::SqliteRepository::new(pool)))
+    }
+}
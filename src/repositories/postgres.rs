@@ -0,0 +1,1619 @@
+use chrono::{DateTime, Utc};
+use postgres::Row;
+use std::path::Path;
+
+use crate::db::backup::BackupInfo;
+use crate::db::maintenance::{OrphanReport, TableCount, TABLES};
+use crate::db::postgres::{self as pg, PgConnection, PgPool};
+use crate::models::audit::AuditEntry;
+use crate::models::comment::{Comment, CommentTarget};
+use crate::models::plan_event::{PlanEvent, PlanEventAction};
+use crate::models::itinerary::{ItineraryItem, ItineraryItemKind};
+use crate::models::plan_leg::PlanLeg;
+use crate::models::budget::{Budget, Expense, ExpenseCategory};
+use crate::models::plan_revision::PlanRevision;
+use crate::models::plan_member::{MemberStatus, PlanMember, PlanRole};
+use crate::models::point_of_interest::PointOfInterest;
+use crate::models::route_option::RouteOption;
+use crate::models::route_vote::{RouteVote, VoteDirection};
+use crate::models::share_link::ShareLink;
+use crate::models::travel_plan::TravelPlan;
+use crate::models::user::{Role, User};
+use crate::repositories::{
+    AuditRepository, BudgetRepository, CommentRepository, ItineraryRepository,
+    MaintenanceRepository, PlanEventRepository, PlanLegRepository, PlanMemberRepository,
+    PlanRevisionRepository, PoiRepository, RepositoryError, RepositoryResult, RouteOptionRepository,
+    RouteVoteRepository, ShareLinkRepository, TravelPlanRepository, UserRepository,
+};
+
+const USER_COLUMNS: &str = "id, username, password_hash, email, created_at, disabled, role";
+const TRAVEL_PLAN_COLUMNS: &str = "id, user_id, name, description, start_location, end_location, \
+     start_date, end_date, created_at, updated_at, selected_route_id, home_currency";
+const PLAN_MEMBER_COLUMNS: &str = "id, travel_plan_id, user_id, role, status, invited_by, \
+     created_at, responded_at";
+const SHARE_LINK_COLUMNS: &str = "id, travel_plan_id, token, created_by, include_routes, \
+     include_pois, expires_at, revoked_at, access_count, last_accessed_at, created_at";
+const ROUTE_OPTION_COLUMNS: &str = "id, travel_plan_id, name, description, distance, duration, \
+     start_coordinates, end_coordinates, waypoints, created_at, updated_at, leg_id";
+const ROUTE_VOTE_COLUMNS: &str = "id, route_option_id, travel_plan_id, user_id, direction, \
+     created_at, updated_at";
+const COMMENT_COLUMNS: &str = "id, travel_plan_id, route_option_id, target_type, target_id, \
+     thread_id, parent_id, user_id, body, mentions, created_at, updated_at";
+const PLAN_EVENT_COLUMNS: &str = "id, travel_plan_id, actor_id, action, changes, created_at";
+const PLAN_REVISION_COLUMNS: &str = "id, travel_plan_id, revision, name, description, \
+    start_location, end_location, start_date, end_date, replaced_by, created_at, home_currency";
+const ITINERARY_ITEM_COLUMNS: &str = "id, travel_plan_id, day, position, kind, title, notes, \
+    route_option_id, poi_id, starts_at, ends_at, created_at, updated_at";
+const PLAN_LEG_COLUMNS: &str = "id, travel_plan_id, position, start_location, end_location, \
+    start_date, end_date, mode, selected_route_id, created_at, updated_at";
+const BUDGET_COLUMNS: &str =
+    "id, travel_plan_id, category, amount, currency, created_at, updated_at";
+const EXPENSE_COLUMNS: &str = "id, travel_plan_id, category, description, amount, currency, \
+    paid_by, split_between, spent_on, created_by, created_at, updated_at";
+const POI_COLUMNS: &str = "id, route_option_id, name, description, category, coordinates, created_at";
+const AUDIT_COLUMNS: &str = "id, actor_id, actor_username, action, target_type, target_id, details, created_at";
+
+/// Removes what a plan owns; routes go after what points at them and before
+/// the legs they belong to.
+const DELETE_PLAN_CONTENTS: &[&str] = &[
+    "DELETE FROM points_of_interest
+     WHERE route_option_id IN (SELECT id FROM route_options WHERE travel_plan_id = $1)",
+    "DELETE FROM route_votes WHERE travel_plan_id = $1",
+    "DELETE FROM comments WHERE travel_plan_id = $1",
+    "DELETE FROM itinerary_items WHERE travel_plan_id = $1",
+    "DELETE FROM route_options WHERE travel_plan_id = $1",
+    "DELETE FROM plan_legs WHERE travel_plan_id = $1",
+    "DELETE FROM plan_revisions WHERE travel_plan_id = $1",
+    "DELETE FROM expenses WHERE travel_plan_id = $1",
+    "DELETE FROM budgets WHERE travel_plan_id = $1",
+    "DELETE FROM plan_members WHERE travel_plan_id = $1",
+    "DELETE FROM share_links WHERE travel_plan_id = $1",
+];
+
+/// What a route owns, in the order it is deleted before the route.
+const DELETE_ROUTE_CONTENTS: &[&str] = &[
+    "DELETE FROM itinerary_items WHERE route_option_id = $1",
+    "DELETE FROM comments WHERE route_option_id = $1",
+    "DELETE FROM route_votes WHERE route_option_id = $1",
+    "DELETE FROM points_of_interest WHERE route_option_id = $1",
+];
+
+/// PostgreSQL storage for deployments that run several API instances against
+/// one shared database.
+#[derive(Clone)]
+pub struct PostgresRepository {
+    pool: PgPool,
+}
+
+impl PostgresRepository {
+    pub fn new(pool: PgPool) -> Self {
+        PostgresRepository { pool }
+    }
+
+    fn conn(&self) -> RepositoryResult<PgConnection> {
+        Ok(self.pool.get()?)
+    }
+}
+
+fn user_from_row(row: &Row) -> User {
+    User {
+        id: row.get(0),
+        username: row.get(1),
+        password_hash: row.get(2),
+        email: row.get(3),
+        created_at: row.get(4),
+        disabled: row.get(5),
+        // Unknown roles grant nothing beyond a regular account
+        role: row.get::<_, String>(6).parse().unwrap_or_default(),
+    }
+}
+
+fn travel_plan_from_row(row: &Row) -> TravelPlan {
+    TravelPlan {
+        id: row.get(0),
+        user_id: row.get(1),
+        name: row.get(2),
+        description: row.get(3),
+        start_location: row.get(4),
+        end_location: row.get(5),
+        start_date: row.get(6),
+        end_date: row.get(7),
+        created_at: row.get(8),
+        updated_at: row.get(9),
+        selected_route_id: row.get(10),
+        home_currency: row.get(11),
+    }
+}
+
+fn plan_member_from_row(row: &Row) -> PlanMember {
+    PlanMember {
+        id: row.get(0),
+        travel_plan_id: row.get(1),
+        user_id: row.get(2),
+        // Unknown values fall back to the least access
+        role: row.get::<_, String>(3).parse().unwrap_or(PlanRole::Viewer),
+        status: row.get::<_, String>(4).parse().unwrap_or(MemberStatus::Pending),
+        invited_by: row.get(5),
+        created_at: row.get(6),
+        responded_at: row.get(7),
+    }
+}
+
+fn share_link_from_row(row: &Row) -> ShareLink {
+    ShareLink {
+        id: row.get(0),
+        travel_plan_id: row.get(1),
+        token: row.get(2),
+        created_by: row.get(3),
+        include_routes: row.get(4),
+        include_pois: row.get(5),
+        expires_at: row.get(6),
+        revoked_at: row.get(7),
+        access_count: row.get(8),
+        last_accessed_at: row.get(9),
+        created_at: row.get(10),
+    }
+}
+
+fn route_vote_from_row(row: &Row) -> RouteVote {
+    RouteVote {
+        id: row.get(0),
+        route_option_id: row.get(1),
+        travel_plan_id: row.get(2),
+        user_id: row.get(3),
+        // Unreadable votes count against the route rather than for it
+        direction: row.get::<_, String>(4).parse().unwrap_or(VoteDirection::Down),
+        created_at: row.get(5),
+        updated_at: row.get(6),
+    }
+}
+
+fn comment_from_row(row: &Row) -> Comment {
+    Comment {
+        id: row.get(0),
+        travel_plan_id: row.get(1),
+        route_option_id: row.get(2),
+        // Unknown targets read as plan comments, which every member can see
+        target_type: row.get::<_, String>(3).parse().unwrap_or(CommentTarget::Plan),
+        target_id: row.get(4),
+        thread_id: row.get(5),
+        parent_id: row.get(6),
+        user_id: row.get(7),
+        body: row.get(8),
+        mentions: serde_json::from_str(&row.get::<_, String>(9)).unwrap_or_default(),
+        created_at: row.get(10),
+        updated_at: row.get(11),
+    }
+}
+
+fn plan_event_from_row(row: &Row) -> PlanEvent {
+    PlanEvent {
+        id: row.get(0),
+        travel_plan_id: row.get(1),
+        actor_id: row.get(2),
+        // Unknown actions were written by a newer version; show them as edits
+        action: row.get::<_, String>(3).parse().unwrap_or(PlanEventAction::PlanUpdated),
+        changes: serde_json::from_str(&row.get::<_, String>(4)).unwrap_or_default(),
+        created_at: row.get(5),
+    }
+}
+
+fn itinerary_item_from_row(row: &Row) -> ItineraryItem {
+    let kind: String = row.get(4);
+    ItineraryItem {
+        id: row.get(0),
+        travel_plan_id: row.get(1),
+        day: row.get(2),
+        position: row.get::<_, i32>(3) as i64,
+        kind: kind.parse().unwrap_or(ItineraryItemKind::Activity),
+        title: row.get(5),
+        notes: row.get(6),
+        route_option_id: row.get(7),
+        poi_id: row.get(8),
+        starts_at: row.get(9),
+        ends_at: row.get(10),
+        created_at: row.get(11),
+        updated_at: row.get(12),
+    }
+}
+
+fn plan_leg_from_row(row: &Row) -> PlanLeg {
+    let mode: String = row.get(7);
+    PlanLeg {
+        id: row.get(0),
+        travel_plan_id: row.get(1),
+        position: row.get::<_, i32>(2) as i64,
+        start_location: row.get(3),
+        end_location: row.get(4),
+        start_date: row.get(5),
+        end_date: row.get(6),
+        mode: mode.parse().unwrap_or_default(),
+        selected_route_id: row.get(8),
+        created_at: row.get(9),
+        updated_at: row.get(10),
+    }
+}
+
+fn budget_from_row(row: &Row) -> Budget {
+    let category: String = row.get(2);
+    Budget {
+        id: row.get(0),
+        travel_plan_id: row.get(1),
+        category: category.parse().unwrap_or(ExpenseCategory::Other),
+        amount: row.get(3),
+        currency: row.get(4),
+        created_at: row.get(5),
+        updated_at: row.get(6),
+    }
+}
+
+fn expense_from_row(row: &Row) -> Expense {
+    let category: String = row.get(2);
+    Expense {
+        id: row.get(0),
+        travel_plan_id: row.get(1),
+        category: category.parse().unwrap_or(ExpenseCategory::Other),
+        description: row.get(3),
+        amount: row.get(4),
+        currency: row.get(5),
+        paid_by: row.get(6),
+        split_between: serde_json::from_str(&row.get::<_, String>(7)).unwrap_or_default(),
+        spent_on: row.get(8),
+        created_by: row.get(9),
+        created_at: row.get(10),
+        updated_at: row.get(11),
+    }
+}
+
+fn plan_revision_from_row(row: &Row) -> PlanRevision {
+    PlanRevision {
+        id: row.get(0),
+        travel_plan_id: row.get(1),
+        revision: row.get::<_, i32>(2) as i64,
+        name: row.get(3),
+        description: row.get(4),
+        start_location: row.get(5),
+        end_location: row.get(6),
+        start_date: row.get(7),
+        end_date: row.get(8),
+        replaced_by: row.get(9),
+        created_at: row.get(10),
+        home_currency: row.get(11),
+    }
+}
+
+fn route_option_from_row(row: &Row) -> RouteOption {
+    RouteOption {
+        id: row.get(0),
+        travel_plan_id: row.get(1),
+        leg_id: row.get(11),
+        name: row.get(2),
+        description: row.get(3),
+        distance: row.get(4),
+        duration: row.get(5),
+        start_coordinates: row.get(6),
+        end_coordinates: row.get(7),
+        waypoints: row.get(8),
+        created_at: row.get(9),
+        updated_at: row.get(10),
+    }
+}
+
+fn audit_entry_from_row(row: &Row) -> AuditEntry {
+    AuditEntry {
+        id: row.get(0),
+        actor_id: row.get(1),
+        actor_username: row.get(2),
+        action: row.get(3),
+        target_type: row.get(4),
+        target_id: row.get(5),
+        details: row.get(6),
+        created_at: row.get(7),
+    }
+}
+
+fn poi_from_row(row: &Row) -> PointOfInterest {
+    PointOfInterest {
+        id: row.get(0),
+        route_option_id: row.get(1),
+        name: row.get(2),
+        description: row.get(3),
+        category: row.get(4),
+        coordinates: row.get(5),
+        created_at: row.get(6),
+    }
+}
+
+impl UserRepository for PostgresRepository {
+    fn insert_user(&self, user: &User) -> RepositoryResult<()> {
+        let mut conn = self.conn()?;
+        conn.execute(
+            "INSERT INTO users (id, username, password_hash, email, created_at, disabled, role)
+             VALUES ($1, $2, $3, $4, $5, $6, $7)",
+            &[
+                &user.id,
+                &user.username,
+                &user.password_hash,
+                &user.email,
+                &user.created_at,
+                &user.disabled,
+                &user.role.as_str(),
+            ],
+        )?;
+        Ok(())
+    }
+
+    fn find_user_by_id(&self, id: &str) -> RepositoryResult<Option<User>> {
+        let mut conn = self.conn()?;
+        let row = conn.query_opt(
+            &format!("SELECT {} FROM users WHERE id = $1", USER_COLUMNS),
+            &[&id],
+        )?;
+        Ok(row.as_ref().map(user_from_row))
+    }
+
+    fn find_user_by_username(&self, username: &str) -> RepositoryResult<Option<User>> {
+        let mut conn = self.conn()?;
+        let row = conn.query_opt(
+            &format!("SELECT {} FROM users WHERE username = $1", USER_COLUMNS),
+            &[&username],
+        )?;
+        Ok(row.as_ref().map(user_from_row))
+    }
+
+    fn find_user_by_email(&self, email: &str) -> RepositoryResult<Option<User>> {
+        let mut conn = self.conn()?;
+        let row = conn.query_opt(
+            &format!("SELECT {} FROM users WHERE email = $1", USER_COLUMNS),
+            &[&email],
+        )?;
+        Ok(row.as_ref().map(user_from_row))
+    }
+
+    fn set_user_disabled(&self, id: &str, disabled: bool) -> RepositoryResult<bool> {
+        let mut conn = self.conn()?;
+        let updated = conn.execute(
+            "UPDATE users SET disabled = $1 WHERE id = $2",
+            &[&disabled, &id],
+        )?;
+        Ok(updated > 0)
+    }
+
+    fn update_user_password(&self, id: &str, password_hash: &str) -> RepositoryResult<bool> {
+        let mut conn = self.conn()?;
+        let updated = conn.execute(
+            "UPDATE users SET password_hash = $1 WHERE id = $2",
+            &[&password_hash, &id],
+        )?;
+        Ok(updated > 0)
+    }
+
+    fn set_user_role(&self, id: &str, role: Role) -> RepositoryResult<bool> {
+        let mut conn = self.conn()?;
+        let updated = conn.execute(
+            "UPDATE users SET role = $1 WHERE id = $2",
+            &[&role.as_str(), &id],
+        )?;
+        Ok(updated > 0)
+    }
+
+    fn list_users(&self) -> RepositoryResult<Vec<User>> {
+        let mut conn = self.conn()?;
+        let rows = conn.query(
+            &format!("SELECT {} FROM users ORDER BY created_at, username", USER_COLUMNS),
+            &[],
+        )?;
+        Ok(rows.iter().map(user_from_row).collect())
+    }
+}
+
+impl TravelPlanRepository for PostgresRepository {
+    fn insert_travel_plan(&self, plan: &TravelPlan) -> RepositoryResult<()> {
+        let mut conn = self.conn()?;
+        conn.execute(
+            "INSERT INTO travel_plans (
+                id, user_id, name, description, start_location, end_location,
+                start_date, end_date, created_at, updated_at, selected_route_id, home_currency
+             ) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12)",
+            &[
+                &plan.id,
+                &plan.user_id,
+                &plan.name,
+                &plan.description,
+                &plan.start_location,
+                &plan.end_location,
+                &plan.start_date,
+                &plan.end_date,
+                &plan.created_at,
+                &plan.updated_at,
+                &plan.selected_route_id,
+                &plan.home_currency,
+            ],
+        )?;
+        Ok(())
+    }
+
+    fn find_travel_plan(&self, id: &str) -> RepositoryResult<Option<TravelPlan>> {
+        let mut conn = self.conn()?;
+        let row = conn.query_opt(
+            &format!("SELECT {} FROM travel_plans WHERE id = $1", TRAVEL_PLAN_COLUMNS),
+            &[&id],
+        )?;
+        Ok(row.as_ref().map(travel_plan_from_row))
+    }
+
+    fn find_travel_plans_by_user(&self, user_id: &str) -> RepositoryResult<Vec<TravelPlan>> {
+        let mut conn = self.conn()?;
+        let rows = conn.query(
+            &format!(
+                "SELECT {} FROM travel_plans WHERE user_id = $1 ORDER BY created_at DESC",
+                TRAVEL_PLAN_COLUMNS
+            ),
+            &[&user_id],
+        )?;
+        Ok(rows.iter().map(travel_plan_from_row).collect())
+    }
+
+    fn update_travel_plan(
+        &self,
+        plan: &TravelPlan,
+        expected_updated_at: DateTime<Utc>,
+    ) -> RepositoryResult<usize> {
+        let mut conn = self.conn()?;
+        let updated = conn.execute(
+            "UPDATE travel_plans SET
+                name = $1, description = $2, start_location = $3, end_location = $4,
+                start_date = $5, end_date = $6, home_currency = $7, updated_at = $8
+             WHERE id = $9 AND updated_at = $10",
+            &[
+                &plan.name,
+                &plan.description,
+                &plan.start_location,
+                &plan.end_location,
+                &plan.start_date,
+                &plan.end_date,
+                &plan.home_currency,
+                &plan.updated_at,
+                &plan.id,
+                &expected_updated_at,
+            ],
+        )?;
+        Ok(updated as usize)
+    }
+
+    fn set_selected_route(
+        &self,
+        plan_id: &str,
+        route_id: Option<&str>,
+        updated_at: DateTime<Utc>,
+    ) -> RepositoryResult<bool> {
+        let mut conn = self.conn()?;
+        let updated = conn.execute(
+            "UPDATE travel_plans SET selected_route_id = $1, updated_at = $2 WHERE id = $3",
+            &[&route_id, &updated_at, &plan_id],
+        )?;
+        Ok(updated > 0)
+    }
+
+    fn delete_travel_plan(
+        &self,
+        id: &str,
+        expected_updated_at: Option<DateTime<Utc>>,
+    ) -> RepositoryResult<bool> {
+        let mut conn = self.conn()?;
+        let mut tx = conn.transaction()?;
+        for statement in DELETE_PLAN_CONTENTS {
+            tx.execute(*statement, &[&id])?;
+        }
+        let deleted = tx.execute(
+            "DELETE FROM travel_plans
+             WHERE id = $1 AND ($2::timestamptz IS NULL OR updated_at = $2)",
+            &[&id, &expected_updated_at],
+        )?;
+        // Without the plan row, dropping the transaction rolls the rest back
+        if deleted > 0 {
+            tx.commit()?;
+        }
+        Ok(deleted > 0)
+    }
+}
+
+impl PlanMemberRepository for PostgresRepository {
+    fn insert_plan_member(&self, member: &PlanMember) -> RepositoryResult<()> {
+        let mut conn = self.conn()?;
+        conn.execute(
+            "INSERT INTO plan_members (
+                id, travel_plan_id, user_id, role, status, invited_by, created_at, responded_at
+            ) VALUES ($1, $2, $3, $4, $5, $6, $7, $8)",
+            &[
+                &member.id,
+                &member.travel_plan_id,
+                &member.user_id,
+                &member.role.as_str(),
+                &member.status.as_str(),
+                &member.invited_by,
+                &member.created_at,
+                &member.responded_at,
+            ],
+        )?;
+        Ok(())
+    }
+
+    fn find_plan_member(&self, plan_id: &str, user_id: &str) -> RepositoryResult<Option<PlanMember>> {
+        let mut conn = self.conn()?;
+        let row = conn.query_opt(
+            &format!(
+                "SELECT {} FROM plan_members WHERE travel_plan_id = $1 AND user_id = $2",
+                PLAN_MEMBER_COLUMNS
+            ),
+            &[&plan_id, &user_id],
+        )?;
+        Ok(row.as_ref().map(plan_member_from_row))
+    }
+
+    fn find_plan_members(&self, plan_id: &str) -> RepositoryResult<Vec<PlanMember>> {
+        let mut conn = self.conn()?;
+        let rows = conn.query(
+            &format!(
+                "SELECT {} FROM plan_members WHERE travel_plan_id = $1 ORDER BY created_at, seq",
+                PLAN_MEMBER_COLUMNS
+            ),
+            &[&plan_id],
+        )?;
+        Ok(rows.iter().map(plan_member_from_row).collect())
+    }
+
+    fn find_memberships_by_user(&self, user_id: &str) -> RepositoryResult<Vec<PlanMember>> {
+        let mut conn = self.conn()?;
+        let rows = conn.query(
+            &format!(
+                "SELECT {} FROM plan_members WHERE user_id = $1 ORDER BY created_at DESC, seq DESC",
+                PLAN_MEMBER_COLUMNS
+            ),
+            &[&user_id],
+        )?;
+        Ok(rows.iter().map(plan_member_from_row).collect())
+    }
+
+    fn find_shared_travel_plans(&self, user_id: &str) -> RepositoryResult<Vec<TravelPlan>> {
+        let mut conn = self.conn()?;
+        let rows = conn.query(
+            &format!(
+                "SELECT {} FROM travel_plans
+                 WHERE id IN (
+                     SELECT travel_plan_id FROM plan_members
+                     WHERE user_id = $1 AND status = 'accepted'
+                 )
+                 ORDER BY created_at DESC",
+                TRAVEL_PLAN_COLUMNS
+            ),
+            &[&user_id],
+        )?;
+        Ok(rows.iter().map(travel_plan_from_row).collect())
+    }
+
+    fn update_plan_member(&self, member: &PlanMember) -> RepositoryResult<()> {
+        let mut conn = self.conn()?;
+        conn.execute(
+            "UPDATE plan_members SET role = $1, status = $2, responded_at = $3 WHERE id = $4",
+            &[
+                &member.role.as_str(),
+                &member.status.as_str(),
+                &member.responded_at,
+                &member.id,
+            ],
+        )?;
+        Ok(())
+    }
+
+    fn delete_plan_member(&self, plan_id: &str, user_id: &str) -> RepositoryResult<bool> {
+        let mut conn = self.conn()?;
+        let deleted = conn.execute(
+            "DELETE FROM plan_members WHERE travel_plan_id = $1 AND user_id = $2",
+            &[&plan_id, &user_id],
+        )?;
+        Ok(deleted > 0)
+    }
+
+    fn delete_plan_members_by_plan(&self, plan_id: &str) -> RepositoryResult<usize> {
+        let mut conn = self.conn()?;
+        let deleted = conn.execute(
+            "DELETE FROM plan_members WHERE travel_plan_id = $1",
+            &[&plan_id],
+        )?;
+        Ok(deleted as usize)
+    }
+}
+
+impl ShareLinkRepository for PostgresRepository {
+    fn insert_share_link(&self, link: &ShareLink) -> RepositoryResult<()> {
+        let mut conn = self.conn()?;
+        conn.execute(
+            "INSERT INTO share_links (
+                id, travel_plan_id, token, created_by, include_routes, include_pois,
+                expires_at, revoked_at, access_count, last_accessed_at, created_at
+            ) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)",
+            &[
+                &link.id,
+                &link.travel_plan_id,
+                &link.token,
+                &link.created_by,
+                &link.include_routes,
+                &link.include_pois,
+                &link.expires_at,
+                &link.revoked_at,
+                &link.access_count,
+                &link.last_accessed_at,
+                &link.created_at,
+            ],
+        )?;
+        Ok(())
+    }
+
+    fn find_share_link(&self, id: &str) -> RepositoryResult<Option<ShareLink>> {
+        let mut conn = self.conn()?;
+        let row = conn.query_opt(
+            &format!("SELECT {} FROM share_links WHERE id = $1", SHARE_LINK_COLUMNS),
+            &[&id],
+        )?;
+        Ok(row.as_ref().map(share_link_from_row))
+    }
+
+    fn find_share_link_by_token(&self, token: &str) -> RepositoryResult<Option<ShareLink>> {
+        let mut conn = self.conn()?;
+        let row = conn.query_opt(
+            &format!("SELECT {} FROM share_links WHERE token = $1", SHARE_LINK_COLUMNS),
+            &[&token],
+        )?;
+        Ok(row.as_ref().map(share_link_from_row))
+    }
+
+    fn find_share_links_by_plan(&self, plan_id: &str) -> RepositoryResult<Vec<ShareLink>> {
+        let mut conn = self.conn()?;
+        let rows = conn.query(
+            &format!(
+                "SELECT {} FROM share_links WHERE travel_plan_id = $1 ORDER BY created_at DESC, seq DESC",
+                SHARE_LINK_COLUMNS
+            ),
+            &[&plan_id],
+        )?;
+        Ok(rows.iter().map(share_link_from_row).collect())
+    }
+
+    fn record_share_link_access(&self, id: &str, at: DateTime<Utc>) -> RepositoryResult<()> {
+        let mut conn = self.conn()?;
+        conn.execute(
+            "UPDATE share_links
+             SET access_count = access_count + 1, last_accessed_at = $1
+             WHERE id = $2",
+            &[&at, &id],
+        )?;
+        Ok(())
+    }
+
+    fn revoke_share_link(&self, id: &str, at: DateTime<Utc>) -> RepositoryResult<bool> {
+        let mut conn = self.conn()?;
+        let revoked = conn.execute(
+            "UPDATE share_links SET revoked_at = $1 WHERE id = $2 AND revoked_at IS NULL",
+            &[&at, &id],
+        )?;
+        Ok(revoked > 0)
+    }
+
+    fn delete_share_links_by_plan(&self, plan_id: &str) -> RepositoryResult<usize> {
+        let mut conn = self.conn()?;
+        let deleted = conn.execute(
+            "DELETE FROM share_links WHERE travel_plan_id = $1",
+            &[&plan_id],
+        )?;
+        Ok(deleted as usize)
+    }
+}
+
+impl RouteOptionRepository for PostgresRepository {
+    fn insert_route_option(&self, route: &RouteOption) -> RepositoryResult<()> {
+        let mut conn = self.conn()?;
+        conn.execute(
+            "INSERT INTO route_options (
+                id, travel_plan_id, name, description, distance, duration,
+                start_coordinates, end_coordinates, waypoints, created_at, updated_at, leg_id
+             ) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12)",
+            &[
+                &route.id,
+                &route.travel_plan_id,
+                &route.name,
+                &route.description,
+                &route.distance,
+                &route.duration,
+                &route.start_coordinates,
+                &route.end_coordinates,
+                &route.waypoints,
+                &route.created_at,
+                &route.updated_at,
+                &route.leg_id,
+            ],
+        )?;
+        Ok(())
+    }
+
+    fn find_route_option(&self, id: &str) -> RepositoryResult<Option<RouteOption>> {
+        let mut conn = self.conn()?;
+        let row = conn.query_opt(
+            &format!("SELECT {} FROM route_options WHERE id = $1", ROUTE_OPTION_COLUMNS),
+            &[&id],
+        )?;
+        Ok(row.as_ref().map(route_option_from_row))
+    }
+
+    fn find_route_options_by_plan(&self, plan_id: &str) -> RepositoryResult<Vec<RouteOption>> {
+        let mut conn = self.conn()?;
+        let rows = conn.query(
+            &format!(
+                "SELECT {} FROM route_options WHERE travel_plan_id = $1 ORDER BY seq",
+                ROUTE_OPTION_COLUMNS
+            ),
+            &[&plan_id],
+        )?;
+        Ok(rows.iter().map(route_option_from_row).collect())
+    }
+
+    fn update_route_option(
+        &self,
+        route: &RouteOption,
+        expected_updated_at: DateTime<Utc>,
+    ) -> RepositoryResult<usize> {
+        let mut conn = self.conn()?;
+        let updated = conn.execute(
+            "UPDATE route_options SET
+                name = $1, description = $2, distance = $3, duration = $4, waypoints = $5,
+                updated_at = $6
+             WHERE id = $7 AND updated_at = $8",
+            &[
+                &route.name,
+                &route.description,
+                &route.distance,
+                &route.duration,
+                &route.waypoints,
+                &route.updated_at,
+                &route.id,
+                &expected_updated_at,
+            ],
+        )?;
+        Ok(updated as usize)
+    }
+
+    fn delete_route_option(
+        &self,
+        id: &str,
+        expected_updated_at: Option<DateTime<Utc>>,
+    ) -> RepositoryResult<bool> {
+        let mut conn = self.conn()?;
+        let mut tx = conn.transaction()?;
+        for statement in DELETE_ROUTE_CONTENTS {
+            tx.execute(*statement, &[&id])?;
+        }
+        let deleted = tx.execute(
+            "DELETE FROM route_options
+             WHERE id = $1 AND ($2::timestamptz IS NULL OR updated_at = $2)",
+            &[&id, &expected_updated_at],
+        )?;
+        if deleted > 0 {
+            tx.commit()?;
+        }
+        Ok(deleted > 0)
+    }
+
+    fn delete_route_options_by_plan(&self, plan_id: &str) -> RepositoryResult<usize> {
+        let mut conn = self.conn()?;
+        let deleted = conn.execute(
+            "DELETE FROM route_options WHERE travel_plan_id = $1",
+            &[&plan_id],
+        )?;
+        Ok(deleted as usize)
+    }
+
+    fn delete_unselected_route_options(
+        &self,
+        plan_id: &str,
+        expected_plan_updated_at: Option<DateTime<Utc>>,
+    ) -> RepositoryResult<Option<Vec<String>>> {
+        let mut conn = self.conn()?;
+        let mut tx = conn.transaction()?;
+        // Locking the plan and its legs keeps the version and the selection
+        // from changing until the routes are gone
+        let plan = tx.query_opt(
+            "SELECT id FROM travel_plans
+             WHERE id = $1 AND ($2::timestamptz IS NULL OR updated_at = $2)
+             FOR UPDATE",
+            &[&plan_id, &expected_plan_updated_at],
+        )?;
+        if plan.is_none() {
+            return Ok(None);
+        }
+        tx.execute(
+            "SELECT id FROM plan_legs WHERE travel_plan_id = $1 FOR UPDATE",
+            &[&plan_id],
+        )?;
+
+        let route_ids: Vec<String> = tx
+            .query(
+                "SELECT id FROM route_options
+                 WHERE travel_plan_id = $1
+                   AND id NOT IN (
+                       SELECT selected_route_id FROM travel_plans
+                       WHERE id = $1 AND selected_route_id IS NOT NULL
+                       UNION
+                       SELECT selected_route_id FROM plan_legs
+                       WHERE travel_plan_id = $1 AND selected_route_id IS NOT NULL
+                   )",
+                &[&plan_id],
+            )?
+            .iter()
+            .map(|row| row.get(0))
+            .collect();
+        for id in &route_ids {
+            for statement in DELETE_ROUTE_CONTENTS {
+                tx.execute(*statement, &[id])?;
+            }
+            tx.execute("DELETE FROM route_options WHERE id = $1", &[id])?;
+        }
+        tx.commit()?;
+        Ok(Some(route_ids))
+    }
+}
+
+impl RouteVoteRepository for PostgresRepository {
+    fn upsert_route_vote(&self, vote: &RouteVote) -> RepositoryResult<()> {
+        let mut conn = self.conn()?;
+        conn.execute(
+            "INSERT INTO route_votes (
+                id, route_option_id, travel_plan_id, user_id, direction, created_at, updated_at
+             ) VALUES ($1, $2, $3, $4, $5, $6, $7)
+             ON CONFLICT (route_option_id, user_id)
+             DO UPDATE SET direction = EXCLUDED.direction, updated_at = EXCLUDED.updated_at",
+            &[
+                &vote.id,
+                &vote.route_option_id,
+                &vote.travel_plan_id,
+                &vote.user_id,
+                &vote.direction.as_str(),
+                &vote.created_at,
+                &vote.updated_at,
+            ],
+        )?;
+        Ok(())
+    }
+
+    fn find_route_votes_by_plan(&self, plan_id: &str) -> RepositoryResult<Vec<RouteVote>> {
+        let mut conn = self.conn()?;
+        let rows = conn.query(
+            &format!(
+                "SELECT {} FROM route_votes WHERE travel_plan_id = $1 ORDER BY created_at, seq",
+                ROUTE_VOTE_COLUMNS
+            ),
+            &[&plan_id],
+        )?;
+        Ok(rows.iter().map(route_vote_from_row).collect())
+    }
+
+    fn delete_route_vote(&self, route_id: &str, user_id: &str) -> RepositoryResult<bool> {
+        let mut conn = self.conn()?;
+        let deleted = conn.execute(
+            "DELETE FROM route_votes WHERE route_option_id = $1 AND user_id = $2",
+            &[&route_id, &user_id],
+        )?;
+        Ok(deleted > 0)
+    }
+
+    fn delete_route_votes_by_route(&self, route_id: &str) -> RepositoryResult<usize> {
+        let mut conn = self.conn()?;
+        let deleted = conn.execute(
+            "DELETE FROM route_votes WHERE route_option_id = $1",
+            &[&route_id],
+        )?;
+        Ok(deleted as usize)
+    }
+
+    fn delete_route_votes_by_plan(&self, plan_id: &str) -> RepositoryResult<usize> {
+        let mut conn = self.conn()?;
+        let deleted = conn.execute(
+            "DELETE FROM route_votes WHERE travel_plan_id = $1",
+            &[&plan_id],
+        )?;
+        Ok(deleted as usize)
+    }
+}
+
+impl PoiRepository for PostgresRepository {
+    fn insert_poi(&self, poi: &PointOfInterest) -> RepositoryResult<()> {
+        let mut conn = self.conn()?;
+        conn.execute(
+            "INSERT INTO points_of_interest (
+                id, route_option_id, name, description, category, coordinates, created_at
+             ) VALUES ($1, $2, $3, $4, $5, $6, $7)",
+            &[
+                &poi.id,
+                &poi.route_option_id,
+                &poi.name,
+                &poi.description,
+                &poi.category,
+                &poi.coordinates,
+                &poi.created_at,
+            ],
+        )?;
+        Ok(())
+    }
+
+    fn find_poi(&self, id: &str) -> RepositoryResult<Option<PointOfInterest>> {
+        let mut conn = self.conn()?;
+        let row = conn.query_opt(
+            &format!("SELECT {} FROM points_of_interest WHERE id = $1", POI_COLUMNS),
+            &[&id],
+        )?;
+        Ok(row.as_ref().map(poi_from_row))
+    }
+
+    fn find_pois_by_route(&self, route_id: &str) -> RepositoryResult<Vec<PointOfInterest>> {
+        let mut conn = self.conn()?;
+        let rows = conn.query(
+            &format!(
+                "SELECT {} FROM points_of_interest WHERE route_option_id = $1 ORDER BY seq",
+                POI_COLUMNS
+            ),
+            &[&route_id],
+        )?;
+        Ok(rows.iter().map(poi_from_row).collect())
+    }
+
+    fn update_poi(&self, poi: &PointOfInterest) -> RepositoryResult<()> {
+        let mut conn = self.conn()?;
+        conn.execute(
+            "UPDATE points_of_interest SET
+                name = $1, description = $2, category = $3
+             WHERE id = $4",
+            &[&poi.name, &poi.description, &poi.category, &poi.id],
+        )?;
+        Ok(())
+    }
+
+    fn delete_pois_by_route(&self, route_id: &str) -> RepositoryResult<usize> {
+        let mut conn = self.conn()?;
+        let deleted = conn.execute(
+            "DELETE FROM points_of_interest WHERE route_option_id = $1",
+            &[&route_id],
+        )?;
+        Ok(deleted as usize)
+    }
+}
+
+impl CommentRepository for PostgresRepository {
+    fn insert_comment(&self, comment: &Comment) -> RepositoryResult<()> {
+        let mut conn = self.conn()?;
+        conn.execute(
+            "INSERT INTO comments (
+                id, travel_plan_id, route_option_id, target_type, target_id, thread_id,
+                parent_id, user_id, body, mentions, created_at, updated_at
+             ) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12)",
+            &[
+                &comment.id,
+                &comment.travel_plan_id,
+                &comment.route_option_id,
+                &comment.target_type.as_str(),
+                &comment.target_id,
+                &comment.thread_id,
+                &comment.parent_id,
+                &comment.user_id,
+                &comment.body,
+                &comment.mentions_json(),
+                &comment.created_at,
+                &comment.updated_at,
+            ],
+        )?;
+        Ok(())
+    }
+
+    fn find_comment(&self, id: &str) -> RepositoryResult<Option<Comment>> {
+        let mut conn = self.conn()?;
+        let row = conn.query_opt(
+            &format!("SELECT {} FROM comments WHERE id = $1", COMMENT_COLUMNS),
+            &[&id],
+        )?;
+        Ok(row.as_ref().map(comment_from_row))
+    }
+
+    fn find_comment_threads(
+        &self,
+        target_type: CommentTarget,
+        target_id: &str,
+        limit: usize,
+        offset: usize,
+    ) -> RepositoryResult<Vec<Comment>> {
+        let mut conn = self.conn()?;
+        let rows = conn.query(
+            &format!(
+                "SELECT {} FROM comments
+                 WHERE target_type = $1 AND target_id = $2 AND parent_id IS NULL
+                 ORDER BY created_at, seq
+                 LIMIT $3 OFFSET $4",
+                COMMENT_COLUMNS
+            ),
+            &[&target_type.as_str(), &target_id, &(limit as i64), &(offset as i64)],
+        )?;
+        Ok(rows.iter().map(comment_from_row).collect())
+    }
+
+    fn count_comment_threads(&self, target_type: CommentTarget, target_id: &str) -> RepositoryResult<usize> {
+        let mut conn = self.conn()?;
+        let row = conn.query_one(
+            "SELECT COUNT(*) FROM comments
+             WHERE target_type = $1 AND target_id = $2 AND parent_id IS NULL",
+            &[&target_type.as_str(), &target_id],
+        )?;
+        Ok(row.get::<_, i64>(0) as usize)
+    }
+
+    fn find_comments_in_thread(&self, thread_id: &str) -> RepositoryResult<Vec<Comment>> {
+        let mut conn = self.conn()?;
+        let rows = conn.query(
+            &format!(
+                "SELECT {} FROM comments WHERE thread_id = $1 ORDER BY created_at, seq",
+                COMMENT_COLUMNS
+            ),
+            &[&thread_id],
+        )?;
+        Ok(rows.iter().map(comment_from_row).collect())
+    }
+
+    fn update_comment(&self, comment: &Comment) -> RepositoryResult<()> {
+        let mut conn = self.conn()?;
+        conn.execute(
+            "UPDATE comments SET body = $1, mentions = $2, updated_at = $3 WHERE id = $4",
+            &[&comment.body, &comment.mentions_json(), &comment.updated_at, &comment.id],
+        )?;
+        Ok(())
+    }
+
+    fn delete_comment(&self, id: &str) -> RepositoryResult<bool> {
+        let mut conn = self.conn()?;
+        let deleted = conn.execute("DELETE FROM comments WHERE id = $1", &[&id])?;
+        Ok(deleted > 0)
+    }
+
+    fn delete_comments_by_route(&self, route_id: &str) -> RepositoryResult<usize> {
+        let mut conn = self.conn()?;
+        let deleted = conn.execute(
+            "DELETE FROM comments WHERE route_option_id = $1",
+            &[&route_id],
+        )?;
+        Ok(deleted as usize)
+    }
+
+    fn delete_comments_by_plan(&self, plan_id: &str) -> RepositoryResult<usize> {
+        let mut conn = self.conn()?;
+        let deleted = conn.execute(
+            "DELETE FROM comments WHERE travel_plan_id = $1",
+            &[&plan_id],
+        )?;
+        Ok(deleted as usize)
+    }
+}
+
+impl PlanEventRepository for PostgresRepository {
+    fn insert_plan_event(&self, event: &PlanEvent) -> RepositoryResult<()> {
+        let mut conn = self.conn()?;
+        conn.execute(
+            "INSERT INTO plan_events (id, travel_plan_id, actor_id, action, changes, created_at)
+             VALUES ($1, $2, $3, $4, $5, $6)",
+            &[
+                &event.id,
+                &event.travel_plan_id,
+                &event.actor_id,
+                &event.action.as_str(),
+                &event.changes.to_string(),
+                &event.created_at,
+            ],
+        )?;
+        Ok(())
+    }
+
+    fn find_plan_events(
+        &self,
+        plan_id: &str,
+        limit: usize,
+        offset: usize,
+    ) -> RepositoryResult<Vec<PlanEvent>> {
+        let mut conn = self.conn()?;
+        let rows = conn.query(
+            &format!(
+                "SELECT {} FROM plan_events WHERE travel_plan_id = $1
+                 ORDER BY created_at DESC, seq DESC LIMIT $2 OFFSET $3",
+                PLAN_EVENT_COLUMNS
+            ),
+            &[&plan_id, &(limit as i64), &(offset as i64)],
+        )?;
+        Ok(rows.iter().map(plan_event_from_row).collect())
+    }
+}
+
+impl PlanRevisionRepository for PostgresRepository {
+    fn insert_plan_revision(&self, revision: &PlanRevision) -> RepositoryResult<i64> {
+        let mut conn = self.conn()?;
+        let mut tx = conn.transaction()?;
+        // Holding the plan's row serialises saves of the same plan, so two of
+        // them can't both read the same highest number.
+        tx.execute(
+            "SELECT id FROM travel_plans WHERE id = $1 FOR UPDATE",
+            &[&revision.travel_plan_id],
+        )?;
+        let row = tx.query_one(
+            &format!(
+                "INSERT INTO plan_revisions ({})
+                 SELECT $1, $2, COALESCE(MAX(revision), 0) + 1,
+                        $3, $4, $5, $6, $7, $8, $9, $10, $11
+                 FROM plan_revisions
+                 WHERE travel_plan_id = $2
+                 RETURNING revision",
+                PLAN_REVISION_COLUMNS
+            ),
+            &[
+                &revision.id,
+                &revision.travel_plan_id,
+                &revision.name,
+                &revision.description,
+                &revision.start_location,
+                &revision.end_location,
+                &revision.start_date,
+                &revision.end_date,
+                &revision.replaced_by,
+                &revision.created_at,
+                &revision.home_currency,
+            ],
+        )?;
+        tx.commit()?;
+        Ok(row.get::<_, i32>(0) as i64)
+    }
+
+    fn find_plan_revisions(&self, plan_id: &str) -> RepositoryResult<Vec<PlanRevision>> {
+        let mut conn = self.conn()?;
+        let rows = conn.query(
+            &format!(
+                "SELECT {} FROM plan_revisions WHERE travel_plan_id = $1 ORDER BY revision DESC",
+                PLAN_REVISION_COLUMNS
+            ),
+            &[&plan_id],
+        )?;
+        Ok(rows.iter().map(plan_revision_from_row).collect())
+    }
+
+    fn find_plan_revision(
+        &self,
+        plan_id: &str,
+        revision: i64,
+    ) -> RepositoryResult<Option<PlanRevision>> {
+        let mut conn = self.conn()?;
+        let row = conn.query_opt(
+            &format!(
+                "SELECT {} FROM plan_revisions WHERE travel_plan_id = $1 AND revision = $2",
+                PLAN_REVISION_COLUMNS
+            ),
+            &[&plan_id, &(revision as i32)],
+        )?;
+        Ok(row.as_ref().map(plan_revision_from_row))
+    }
+
+    fn delete_plan_revisions_by_plan(&self, plan_id: &str) -> RepositoryResult<usize> {
+        let mut conn = self.conn()?;
+        let deleted = conn.execute(
+            "DELETE FROM plan_revisions WHERE travel_plan_id = $1",
+            &[&plan_id],
+        )?;
+        Ok(deleted as usize)
+    }
+}
+
+impl ItineraryRepository for PostgresRepository {
+    fn insert_itinerary_item(&self, item: &ItineraryItem) -> RepositoryResult<()> {
+        let mut conn = self.conn()?;
+        conn.execute(
+            &format!(
+                "INSERT INTO itinerary_items ({})
+                 VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13)",
+                ITINERARY_ITEM_COLUMNS
+            ),
+            &[
+                &item.id,
+                &item.travel_plan_id,
+                &item.day,
+                &(item.position as i32),
+                &item.kind.as_str(),
+                &item.title,
+                &item.notes,
+                &item.route_option_id,
+                &item.poi_id,
+                &item.starts_at,
+                &item.ends_at,
+                &item.created_at,
+                &item.updated_at,
+            ],
+        )?;
+        Ok(())
+    }
+
+    fn find_itinerary_item(&self, id: &str) -> RepositoryResult<Option<ItineraryItem>> {
+        let mut conn = self.conn()?;
+        let row = conn.query_opt(
+            &format!("SELECT {} FROM itinerary_items WHERE id = $1", ITINERARY_ITEM_COLUMNS),
+            &[&id],
+        )?;
+        Ok(row.as_ref().map(itinerary_item_from_row))
+    }
+
+    fn find_itinerary_items(&self, plan_id: &str) -> RepositoryResult<Vec<ItineraryItem>> {
+        let mut conn = self.conn()?;
+        let rows = conn.query(
+            &format!(
+                "SELECT {} FROM itinerary_items WHERE travel_plan_id = $1 ORDER BY day, position",
+                ITINERARY_ITEM_COLUMNS
+            ),
+            &[&plan_id],
+        )?;
+        Ok(rows.iter().map(itinerary_item_from_row).collect())
+    }
+
+    fn update_itinerary_item(&self, item: &ItineraryItem) -> RepositoryResult<()> {
+        let mut conn = self.conn()?;
+        conn.execute(
+            "UPDATE itinerary_items SET
+                day = $1, position = $2, title = $3, notes = $4, starts_at = $5, ends_at = $6,
+                updated_at = $7
+             WHERE id = $8",
+            &[
+                &item.day,
+                &(item.position as i32),
+                &item.title,
+                &item.notes,
+                &item.starts_at,
+                &item.ends_at,
+                &item.updated_at,
+                &item.id,
+            ],
+        )?;
+        Ok(())
+    }
+
+    fn delete_itinerary_item(&self, id: &str) -> RepositoryResult<bool> {
+        let mut conn = self.conn()?;
+        let deleted = conn.execute("DELETE FROM itinerary_items WHERE id = $1", &[&id])?;
+        Ok(deleted > 0)
+    }
+
+    fn delete_itinerary_items_by_route(&self, route_id: &str) -> RepositoryResult<usize> {
+        let mut conn = self.conn()?;
+        let deleted = conn.execute(
+            "DELETE FROM itinerary_items WHERE route_option_id = $1",
+            &[&route_id],
+        )?;
+        Ok(deleted as usize)
+    }
+
+    fn delete_itinerary_items_by_plan(&self, plan_id: &str) -> RepositoryResult<usize> {
+        let mut conn = self.conn()?;
+        let deleted = conn.execute(
+            "DELETE FROM itinerary_items WHERE travel_plan_id = $1",
+            &[&plan_id],
+        )?;
+        Ok(deleted as usize)
+    }
+}
+
+impl PlanLegRepository for PostgresRepository {
+    fn insert_plan_leg(&self, leg: &PlanLeg) -> RepositoryResult<()> {
+        let mut conn = self.conn()?;
+        conn.execute(
+            &format!(
+                "INSERT INTO plan_legs ({})
+                 VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)",
+                PLAN_LEG_COLUMNS
+            ),
+            &[
+                &leg.id,
+                &leg.travel_plan_id,
+                &(leg.position as i32),
+                &leg.start_location,
+                &leg.end_location,
+                &leg.start_date,
+                &leg.end_date,
+                &leg.mode.as_str(),
+                &leg.selected_route_id,
+                &leg.created_at,
+                &leg.updated_at,
+            ],
+        )?;
+        Ok(())
+    }
+
+    fn find_plan_leg(&self, id: &str) -> RepositoryResult<Option<PlanLeg>> {
+        let mut conn = self.conn()?;
+        let row = conn.query_opt(
+            &format!("SELECT {} FROM plan_legs WHERE id = $1", PLAN_LEG_COLUMNS),
+            &[&id],
+        )?;
+        Ok(row.as_ref().map(plan_leg_from_row))
+    }
+
+    fn find_plan_legs(&self, plan_id: &str) -> RepositoryResult<Vec<PlanLeg>> {
+        let mut conn = self.conn()?;
+        let rows = conn.query(
+            &format!(
+                "SELECT {} FROM plan_legs WHERE travel_plan_id = $1 ORDER BY position",
+                PLAN_LEG_COLUMNS
+            ),
+            &[&plan_id],
+        )?;
+        Ok(rows.iter().map(plan_leg_from_row).collect())
+    }
+
+    fn update_plan_leg(&self, leg: &PlanLeg) -> RepositoryResult<()> {
+        let mut conn = self.conn()?;
+        conn.execute(
+            "UPDATE plan_legs SET
+                position = $1, start_location = $2, end_location = $3, start_date = $4,
+                end_date = $5, mode = $6, selected_route_id = $7, updated_at = $8
+             WHERE id = $9",
+            &[
+                &(leg.position as i32),
+                &leg.start_location,
+                &leg.end_location,
+                &leg.start_date,
+                &leg.end_date,
+                &leg.mode.as_str(),
+                &leg.selected_route_id,
+                &leg.updated_at,
+                &leg.id,
+            ],
+        )?;
+        Ok(())
+    }
+
+    fn delete_plan_leg(&self, id: &str) -> RepositoryResult<bool> {
+        let mut conn = self.conn()?;
+        let deleted = conn.execute("DELETE FROM plan_legs WHERE id = $1", &[&id])?;
+        Ok(deleted > 0)
+    }
+
+    fn delete_plan_legs_by_plan(&self, plan_id: &str) -> RepositoryResult<usize> {
+        let mut conn = self.conn()?;
+        let deleted =
+            conn.execute("DELETE FROM plan_legs WHERE travel_plan_id = $1", &[&plan_id])?;
+        Ok(deleted as usize)
+    }
+}
+
+impl BudgetRepository for PostgresRepository {
+    fn insert_budget(&self, budget: &Budget) -> RepositoryResult<()> {
+        let mut conn = self.conn()?;
+        conn.execute(
+            &format!(
+                "INSERT INTO budgets ({}) VALUES ($1, $2, $3, $4, $5, $6, $7)",
+                BUDGET_COLUMNS
+            ),
+            &[
+                &budget.id,
+                &budget.travel_plan_id,
+                &budget.category.as_str(),
+                &budget.amount,
+                &budget.currency,
+                &budget.created_at,
+                &budget.updated_at,
+            ],
+        )?;
+        Ok(())
+    }
+
+    fn find_budgets(&self, plan_id: &str) -> RepositoryResult<Vec<Budget>> {
+        let mut conn = self.conn()?;
+        let rows = conn.query(
+            &format!(
+                "SELECT {} FROM budgets WHERE travel_plan_id = $1 ORDER BY category",
+                BUDGET_COLUMNS
+            ),
+            &[&plan_id],
+        )?;
+        Ok(rows.iter().map(budget_from_row).collect())
+    }
+
+    fn update_budget(&self, budget: &Budget) -> RepositoryResult<()> {
+        let mut conn = self.conn()?;
+        conn.execute(
+            "UPDATE budgets SET amount = $1, currency = $2, updated_at = $3 WHERE id = $4",
+            &[&budget.amount, &budget.currency, &budget.updated_at, &budget.id],
+        )?;
+        Ok(())
+    }
+
+    fn delete_budget(&self, id: &str) -> RepositoryResult<bool> {
+        let mut conn = self.conn()?;
+        let deleted = conn.execute("DELETE FROM budgets WHERE id = $1", &[&id])?;
+        Ok(deleted > 0)
+    }
+
+    fn delete_budgets_by_plan(&self, plan_id: &str) -> RepositoryResult<usize> {
+        let mut conn = self.conn()?;
+        let deleted = conn.execute("DELETE FROM budgets WHERE travel_plan_id = $1", &[&plan_id])?;
+        Ok(deleted as usize)
+    }
+
+    fn insert_expense(&self, expense: &Expense) -> RepositoryResult<()> {
+        let mut conn = self.conn()?;
+        conn.execute(
+            &format!(
+                "INSERT INTO expenses ({})
+                 VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12)",
+                EXPENSE_COLUMNS
+            ),
+            &[
+                &expense.id,
+                &expense.travel_plan_id,
+                &expense.category.as_str(),
+                &expense.description,
+                &expense.amount,
+                &expense.currency,
+                &expense.paid_by,
+                &expense.split_to_json(),
+                &expense.spent_on,
+                &expense.created_by,
+                &expense.created_at,
+                &expense.updated_at,
+            ],
+        )?;
+        Ok(())
+    }
+
+    fn find_expense(&self, id: &str) -> RepositoryResult<Option<Expense>> {
+        let mut conn = self.conn()?;
+        let row = conn.query_opt(
+            &format!("SELECT {} FROM expenses WHERE id = $1", EXPENSE_COLUMNS),
+            &[&id],
+        )?;
+        Ok(row.as_ref().map(expense_from_row))
+    }
+
+    fn find_expenses(&self, plan_id: &str) -> RepositoryResult<Vec<Expense>> {
+        let mut conn = self.conn()?;
+        let rows = conn.query(
+            &format!(
+                "SELECT {} FROM expenses WHERE travel_plan_id = $1
+                 ORDER BY spent_on, created_at",
+                EXPENSE_COLUMNS
+            ),
+            &[&plan_id],
+        )?;
+        Ok(rows.iter().map(expense_from_row).collect())
+    }
+
+    fn update_expense(&self, expense: &Expense) -> RepositoryResult<()> {
+        let mut conn = self.conn()?;
+        conn.execute(
+            "UPDATE expenses SET
+                category = $1, description = $2, amount = $3, currency = $4, paid_by = $5,
+                split_between = $6, spent_on = $7, updated_at = $8
+             WHERE id = $9",
+            &[
+                &expense.category.as_str(),
+                &expense.description,
+                &expense.amount,
+                &expense.currency,
+                &expense.paid_by,
+                &expense.split_to_json(),
+                &expense.spent_on,
+                &expense.updated_at,
+                &expense.id,
+            ],
+        )?;
+        Ok(())
+    }
+
+    fn delete_expense(&self, id: &str) -> RepositoryResult<bool> {
+        let mut conn = self.conn()?;
+        let deleted = conn.execute("DELETE FROM expenses WHERE id = $1", &[&id])?;
+        Ok(deleted > 0)
+    }
+
+    fn delete_expenses_by_plan(&self, plan_id: &str) -> RepositoryResult<usize> {
+        let mut conn = self.conn()?;
+        let deleted =
+            conn.execute("DELETE FROM expenses WHERE travel_plan_id = $1", &[&plan_id])?;
+        Ok(deleted as usize)
+    }
+}
+
+impl MaintenanceRepository for PostgresRepository {
+    fn schema_version(&self) -> RepositoryResult<usize> {
+        let mut conn = self.conn()?;
+        Ok(pg::schema_version(&mut conn)?)
+    }
+
+    fn count_rows(&self) -> RepositoryResult<Vec<TableCount>> {
+        let mut conn = self.conn()?;
+        let mut counts = Vec::with_capacity(TABLES.len());
+        for table in TABLES {
+            let row = conn.query_one(&format!("SELECT COUNT(*) FROM {}", table), &[])?;
+            counts.push(TableCount {
+                table: table.to_string(),
+                rows: row.get(0),
+            });
+        }
+        Ok(counts)
+    }
+
+    // Foreign keys keep PostgreSQL free of orphans, but rows copied in from
+    // elsewhere may still predate them.
+    fn purge_orphans(&self) -> RepositoryResult<OrphanReport> {
+        let mut conn = self.conn()?;
+        let mut tx = conn.transaction()?;
+
+        let report = OrphanReport {
+            travel_plans: tx.execute(
+                "DELETE FROM travel_plans p WHERE NOT EXISTS (SELECT 1 FROM users u WHERE u.id = p.user_id)",
+                &[],
+            )? as usize,
+            route_options: tx.execute(
+                "DELETE FROM route_options r WHERE NOT EXISTS (SELECT 1 FROM travel_plans p WHERE p.id = r.travel_plan_id)",
+                &[],
+            )? as usize,
+            points_of_interest: tx.execute(
+                "DELETE FROM points_of_interest poi WHERE NOT EXISTS (SELECT 1 FROM route_options r WHERE r.id = poi.route_option_id)",
+                &[],
+            )? as usize,
+        };
+
+        tx.commit()?;
+        Ok(report)
+    }
+
+    fn vacuum(&self) -> RepositoryResult<()> {
+        let mut conn = self.conn()?;
+        conn.batch_execute("VACUUM ANALYZE")?;
+        Ok(())
+    }
+
+    fn backup_to(&self, _dir: &Path) -> RepositoryResult<BackupInfo> {
+        Err(RepositoryError::Unsupported(
+            "use pg_dump or continuous archiving to back up PostgreSQL".to_string(),
+        ))
+    }
+}
+
+impl AuditRepository for PostgresRepository {
+    fn insert_audit_entry(&self, entry: &AuditEntry) -> RepositoryResult<()> {
+        let mut conn = self.conn()?;
+        conn.execute(
+            "INSERT INTO audit_log (
+                id, actor_id, actor_username, action, target_type, target_id, details, created_at
+            ) VALUES ($1, $2, $3, $4, $5, $6, $7, $8)",
+            &[
+                &entry.id,
+                &entry.actor_id,
+                &entry.actor_username,
+                &entry.action,
+                &entry.target_type,
+                &entry.target_id,
+                &entry.details,
+                &entry.created_at,
+            ],
+        )?;
+        Ok(())
+    }
+
+    fn find_audit_entries(&self, limit: usize, offset: usize) -> RepositoryResult<Vec<AuditEntry>> {
+        let mut conn = self.conn()?;
+        let rows = conn.query(
+            &format!(
+                "SELECT {} FROM audit_log ORDER BY created_at DESC, seq DESC LIMIT $1 OFFSET $2",
+                AUDIT_COLUMNS
+            ),
+            &[&(limit as i64), &(offset as i64)],
+        )?;
+        Ok(rows.iter().map(audit_entry_from_row).collect())
+    }
+}
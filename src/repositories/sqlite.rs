@@ -0,0 +1,574 @@
+use std::path::Path;
+
+use chrono::{DateTime, Utc};
+
+use crate::db::backup::{self, BackupInfo};
+use crate::db::connection::{DbConnection, DbPool};
+use crate::db::maintenance::{self, OrphanReport, TableCount};
+use crate::db::schema;
+use crate::models::audit::AuditEntry;
+use crate::models::comment::{Comment, CommentTarget};
+use crate::models::plan_event::PlanEvent;
+use crate::models::itinerary::ItineraryItem;
+use crate::models::plan_leg::PlanLeg;
+use crate::models::budget::{Budget, Expense};
+use crate::models::plan_revision::PlanRevision;
+use crate::models::plan_member::PlanMember;
+use crate::models::point_of_interest::PointOfInterest;
+use crate::models::route_option::RouteOption;
+use crate::models::route_vote::RouteVote;
+use crate::models::share_link::ShareLink;
+use crate::models::travel_plan::TravelPlan;
+use crate::models::user::{Role, User};
+use crate::repositories::{
+    AuditRepository, BudgetRepository, CommentRepository, ItineraryRepository,
+    MaintenanceRepository, PlanEventRepository, PlanLegRepository, PlanMemberRepository,
+    PlanRevisionRepository, PoiRepository, RepositoryResult, RouteOptionRepository,
+    RouteVoteRepository, ShareLinkRepository, TravelPlanRepository, UserRepository,
+};
+
+/// SQLite storage backed by the r2d2 pool, delegating to the model helpers.
+#[derive(Clone)]
+pub struct SqliteRepository {
+    pool: DbPool,
+}
+
+impl SqliteRepository {
+    pub fn new(pool: DbPool) -> Self {
+        SqliteRepository { pool }
+    }
+
+    fn conn(&self) -> RepositoryResult<DbConnection> {
+        Ok(self.pool.get()?)
+    }
+}
+
+impl UserRepository for SqliteRepository {
+    fn insert_user(&self, user: &User) -> RepositoryResult<()> {
+        let conn = self.conn()?;
+        Ok(user.insert(&conn)?)
+    }
+
+    fn find_user_by_id(&self, id: &str) -> RepositoryResult<Option<User>> {
+        let conn = self.conn()?;
+        Ok(User::find_by_id(&conn, id)?)
+    }
+
+    fn find_user_by_username(&self, username: &str) -> RepositoryResult<Option<User>> {
+        let conn = self.conn()?;
+        Ok(User::find_by_username(&conn, username)?)
+    }
+
+    fn find_user_by_email(&self, email: &str) -> RepositoryResult<Option<User>> {
+        let conn = self.conn()?;
+        Ok(User::find_by_email(&conn, email)?)
+    }
+
+    fn set_user_disabled(&self, id: &str, disabled: bool) -> RepositoryResult<bool> {
+        let conn = self.conn()?;
+        Ok(User::set_disabled(&conn, id, disabled)?)
+    }
+
+    fn update_user_password(&self, id: &str, password_hash: &str) -> RepositoryResult<bool> {
+        let conn = self.conn()?;
+        Ok(User::update_password_hash(&conn, id, password_hash)?)
+    }
+
+    fn set_user_role(&self, id: &str, role: Role) -> RepositoryResult<bool> {
+        let conn = self.conn()?;
+        Ok(User::set_role(&conn, id, role)?)
+    }
+
+    fn list_users(&self) -> RepositoryResult<Vec<User>> {
+        let conn = self.conn()?;
+        Ok(User::get_all(&conn)?)
+    }
+}
+
+impl TravelPlanRepository for SqliteRepository {
+    fn insert_travel_plan(&self, plan: &TravelPlan) -> RepositoryResult<()> {
+        let conn = self.conn()?;
+        Ok(plan.insert(&conn)?)
+    }
+
+    fn find_travel_plan(&self, id: &str) -> RepositoryResult<Option<TravelPlan>> {
+        let conn = self.conn()?;
+        Ok(TravelPlan::find_by_id(&conn, id)?)
+    }
+
+    fn find_travel_plans_by_user(&self, user_id: &str) -> RepositoryResult<Vec<TravelPlan>> {
+        let conn = self.conn()?;
+        Ok(TravelPlan::find_by_user_id(&conn, user_id)?)
+    }
+
+    fn update_travel_plan(
+        &self,
+        plan: &TravelPlan,
+        expected_updated_at: DateTime<Utc>,
+    ) -> RepositoryResult<usize> {
+        let conn = self.conn()?;
+        Ok(plan.update(&conn, expected_updated_at)?)
+    }
+
+    fn set_selected_route(
+        &self,
+        plan_id: &str,
+        route_id: Option<&str>,
+        updated_at: DateTime<Utc>,
+    ) -> RepositoryResult<bool> {
+        let conn = self.conn()?;
+        Ok(TravelPlan::set_selected_route(&conn, plan_id, route_id, updated_at)?)
+    }
+
+    fn delete_travel_plan(
+        &self,
+        id: &str,
+        expected_updated_at: Option<DateTime<Utc>>,
+    ) -> RepositoryResult<bool> {
+        let conn = self.conn()?;
+        Ok(TravelPlan::delete(&conn, id, expected_updated_at)?)
+    }
+}
+
+impl PlanMemberRepository for SqliteRepository {
+    fn insert_plan_member(&self, member: &PlanMember) -> RepositoryResult<()> {
+        let conn = self.conn()?;
+        Ok(member.insert(&conn)?)
+    }
+
+    fn find_plan_member(&self, plan_id: &str, user_id: &str) -> RepositoryResult<Option<PlanMember>> {
+        let conn = self.conn()?;
+        Ok(PlanMember::find(&conn, plan_id, user_id)?)
+    }
+
+    fn find_plan_members(&self, plan_id: &str) -> RepositoryResult<Vec<PlanMember>> {
+        let conn = self.conn()?;
+        Ok(PlanMember::find_by_travel_plan_id(&conn, plan_id)?)
+    }
+
+    fn find_memberships_by_user(&self, user_id: &str) -> RepositoryResult<Vec<PlanMember>> {
+        let conn = self.conn()?;
+        Ok(PlanMember::find_by_user_id(&conn, user_id)?)
+    }
+
+    fn find_shared_travel_plans(&self, user_id: &str) -> RepositoryResult<Vec<TravelPlan>> {
+        let conn = self.conn()?;
+        Ok(PlanMember::find_shared_plans(&conn, user_id)?)
+    }
+
+    fn update_plan_member(&self, member: &PlanMember) -> RepositoryResult<()> {
+        let conn = self.conn()?;
+        Ok(member.update(&conn)?)
+    }
+
+    fn delete_plan_member(&self, plan_id: &str, user_id: &str) -> RepositoryResult<bool> {
+        let conn = self.conn()?;
+        Ok(PlanMember::delete(&conn, plan_id, user_id)?)
+    }
+
+    fn delete_plan_members_by_plan(&self, plan_id: &str) -> RepositoryResult<usize> {
+        let conn = self.conn()?;
+        Ok(PlanMember::delete_by_travel_plan_id(&conn, plan_id)?)
+    }
+}
+
+impl ShareLinkRepository for SqliteRepository {
+    fn insert_share_link(&self, link: &ShareLink) -> RepositoryResult<()> {
+        let conn = self.conn()?;
+        Ok(link.insert(&conn)?)
+    }
+
+    fn find_share_link(&self, id: &str) -> RepositoryResult<Option<ShareLink>> {
+        let conn = self.conn()?;
+        Ok(ShareLink::find_by_id(&conn, id)?)
+    }
+
+    fn find_share_link_by_token(&self, token: &str) -> RepositoryResult<Option<ShareLink>> {
+        let conn = self.conn()?;
+        Ok(ShareLink::find_by_token(&conn, token)?)
+    }
+
+    fn find_share_links_by_plan(&self, plan_id: &str) -> RepositoryResult<Vec<ShareLink>> {
+        let conn = self.conn()?;
+        Ok(ShareLink::find_by_travel_plan_id(&conn, plan_id)?)
+    }
+
+    fn record_share_link_access(&self, id: &str, at: DateTime<Utc>) -> RepositoryResult<()> {
+        let conn = self.conn()?;
+        Ok(ShareLink::record_access(&conn, id, at)?)
+    }
+
+    fn revoke_share_link(&self, id: &str, at: DateTime<Utc>) -> RepositoryResult<bool> {
+        let conn = self.conn()?;
+        Ok(ShareLink::revoke(&conn, id, at)?)
+    }
+
+    fn delete_share_links_by_plan(&self, plan_id: &str) -> RepositoryResult<usize> {
+        let conn = self.conn()?;
+        Ok(ShareLink::delete_by_travel_plan_id(&conn, plan_id)?)
+    }
+}
+
+impl RouteOptionRepository for SqliteRepository {
+    fn insert_route_option(&self, route: &RouteOption) -> RepositoryResult<()> {
+        let conn = self.conn()?;
+        Ok(route.insert(&conn)?)
+    }
+
+    fn find_route_option(&self, id: &str) -> RepositoryResult<Option<RouteOption>> {
+        let conn = self.conn()?;
+        Ok(RouteOption::find_by_id(&conn, id)?)
+    }
+
+    fn find_route_options_by_plan(&self, plan_id: &str) -> RepositoryResult<Vec<RouteOption>> {
+        let conn = self.conn()?;
+        Ok(RouteOption::find_by_travel_plan_id(&conn, plan_id)?)
+    }
+
+    fn update_route_option(
+        &self,
+        route: &RouteOption,
+        expected_updated_at: DateTime<Utc>,
+    ) -> RepositoryResult<usize> {
+        let conn = self.conn()?;
+        Ok(route.update(&conn, expected_updated_at)?)
+    }
+
+    fn delete_route_option(
+        &self,
+        id: &str,
+        expected_updated_at: Option<DateTime<Utc>>,
+    ) -> RepositoryResult<bool> {
+        let conn = self.conn()?;
+        Ok(RouteOption::delete(&conn, id, expected_updated_at)?)
+    }
+
+    fn delete_route_options_by_plan(&self, plan_id: &str) -> RepositoryResult<usize> {
+        let conn = self.conn()?;
+        Ok(RouteOption::delete_by_travel_plan_id(&conn, plan_id)?)
+    }
+
+    fn delete_unselected_route_options(
+        &self,
+        plan_id: &str,
+        expected_plan_updated_at: Option<DateTime<Utc>>,
+    ) -> RepositoryResult<Option<Vec<String>>> {
+        let conn = self.conn()?;
+        Ok(RouteOption::delete_unselected(&conn, plan_id, expected_plan_updated_at)?)
+    }
+}
+
+impl RouteVoteRepository for SqliteRepository {
+    fn upsert_route_vote(&self, vote: &RouteVote) -> RepositoryResult<()> {
+        let conn = self.conn()?;
+        Ok(vote.upsert(&conn)?)
+    }
+
+    fn find_route_votes_by_plan(&self, plan_id: &str) -> RepositoryResult<Vec<RouteVote>> {
+        let conn = self.conn()?;
+        Ok(RouteVote::find_by_travel_plan_id(&conn, plan_id)?)
+    }
+
+    fn delete_route_vote(&self, route_id: &str, user_id: &str) -> RepositoryResult<bool> {
+        let conn = self.conn()?;
+        Ok(RouteVote::delete(&conn, route_id, user_id)?)
+    }
+
+    fn delete_route_votes_by_route(&self, route_id: &str) -> RepositoryResult<usize> {
+        let conn = self.conn()?;
+        Ok(RouteVote::delete_by_route_option_id(&conn, route_id)?)
+    }
+
+    fn delete_route_votes_by_plan(&self, plan_id: &str) -> RepositoryResult<usize> {
+        let conn = self.conn()?;
+        Ok(RouteVote::delete_by_travel_plan_id(&conn, plan_id)?)
+    }
+}
+
+impl PoiRepository for SqliteRepository {
+    fn insert_poi(&self, poi: &PointOfInterest) -> RepositoryResult<()> {
+        let conn = self.conn()?;
+        Ok(poi.insert(&conn)?)
+    }
+
+    fn find_poi(&self, id: &str) -> RepositoryResult<Option<PointOfInterest>> {
+        let conn = self.conn()?;
+        Ok(PointOfInterest::find_by_id(&conn, id)?)
+    }
+
+    fn find_pois_by_route(&self, route_id: &str) -> RepositoryResult<Vec<PointOfInterest>> {
+        let conn = self.conn()?;
+        Ok(PointOfInterest::find_by_route_option_id(&conn, route_id)?)
+    }
+
+    fn update_poi(&self, poi: &PointOfInterest) -> RepositoryResult<()> {
+        let conn = self.conn()?;
+        Ok(poi.update(&conn)?)
+    }
+
+    fn delete_pois_by_route(&self, route_id: &str) -> RepositoryResult<usize> {
+        let conn = self.conn()?;
+        Ok(PointOfInterest::delete_by_route_option_id(&conn, route_id)?)
+    }
+}
+
+impl CommentRepository for SqliteRepository {
+    fn insert_comment(&self, comment: &Comment) -> RepositoryResult<()> {
+        let conn = self.conn()?;
+        Ok(comment.insert(&conn)?)
+    }
+
+    fn find_comment(&self, id: &str) -> RepositoryResult<Option<Comment>> {
+        let conn = self.conn()?;
+        Ok(Comment::find_by_id(&conn, id)?)
+    }
+
+    fn find_comment_threads(
+        &self,
+        target_type: CommentTarget,
+        target_id: &str,
+        limit: usize,
+        offset: usize,
+    ) -> RepositoryResult<Vec<Comment>> {
+        let conn = self.conn()?;
+        Ok(Comment::find_threads(&conn, target_type, target_id, limit, offset)?)
+    }
+
+    fn count_comment_threads(&self, target_type: CommentTarget, target_id: &str) -> RepositoryResult<usize> {
+        let conn = self.conn()?;
+        Ok(Comment::count_threads(&conn, target_type, target_id)?)
+    }
+
+    fn find_comments_in_thread(&self, thread_id: &str) -> RepositoryResult<Vec<Comment>> {
+        let conn = self.conn()?;
+        Ok(Comment::find_by_thread_id(&conn, thread_id)?)
+    }
+
+    fn update_comment(&self, comment: &Comment) -> RepositoryResult<()> {
+        let conn = self.conn()?;
+        Ok(comment.update(&conn)?)
+    }
+
+    fn delete_comment(&self, id: &str) -> RepositoryResult<bool> {
+        let conn = self.conn()?;
+        Ok(Comment::delete(&conn, id)?)
+    }
+
+    fn delete_comments_by_route(&self, route_id: &str) -> RepositoryResult<usize> {
+        let conn = self.conn()?;
+        Ok(Comment::delete_by_route_option_id(&conn, route_id)?)
+    }
+
+    fn delete_comments_by_plan(&self, plan_id: &str) -> RepositoryResult<usize> {
+        let conn = self.conn()?;
+        Ok(Comment::delete_by_travel_plan_id(&conn, plan_id)?)
+    }
+}
+
+impl PlanEventRepository for SqliteRepository {
+    fn insert_plan_event(&self, event: &PlanEvent) -> RepositoryResult<()> {
+        let conn = self.conn()?;
+        Ok(event.insert(&conn)?)
+    }
+
+    fn find_plan_events(
+        &self,
+        plan_id: &str,
+        limit: usize,
+        offset: usize,
+    ) -> RepositoryResult<Vec<PlanEvent>> {
+        let conn = self.conn()?;
+        Ok(PlanEvent::find_by_travel_plan_id(&conn, plan_id, limit, offset)?)
+    }
+}
+
+impl PlanRevisionRepository for SqliteRepository {
+    fn insert_plan_revision(&self, revision: &PlanRevision) -> RepositoryResult<i64> {
+        let conn = self.conn()?;
+        Ok(revision.insert(&conn)?)
+    }
+
+    fn find_plan_revisions(&self, plan_id: &str) -> RepositoryResult<Vec<PlanRevision>> {
+        let conn = self.conn()?;
+        Ok(PlanRevision::find_by_travel_plan_id(&conn, plan_id)?)
+    }
+
+    fn find_plan_revision(
+        &self,
+        plan_id: &str,
+        revision: i64,
+    ) -> RepositoryResult<Option<PlanRevision>> {
+        let conn = self.conn()?;
+        Ok(PlanRevision::find_by_revision(&conn, plan_id, revision)?)
+    }
+
+    fn delete_plan_revisions_by_plan(&self, plan_id: &str) -> RepositoryResult<usize> {
+        let conn = self.conn()?;
+        Ok(PlanRevision::delete_by_travel_plan_id(&conn, plan_id)?)
+    }
+}
+
+impl ItineraryRepository for SqliteRepository {
+    fn insert_itinerary_item(&self, item: &ItineraryItem) -> RepositoryResult<()> {
+        let conn = self.conn()?;
+        Ok(item.insert(&conn)?)
+    }
+
+    fn find_itinerary_item(&self, id: &str) -> RepositoryResult<Option<ItineraryItem>> {
+        let conn = self.conn()?;
+        Ok(ItineraryItem::find_by_id(&conn, id)?)
+    }
+
+    fn find_itinerary_items(&self, plan_id: &str) -> RepositoryResult<Vec<ItineraryItem>> {
+        let conn = self.conn()?;
+        Ok(ItineraryItem::find_by_travel_plan_id(&conn, plan_id)?)
+    }
+
+    fn update_itinerary_item(&self, item: &ItineraryItem) -> RepositoryResult<()> {
+        let conn = self.conn()?;
+        Ok(item.update(&conn)?)
+    }
+
+    fn delete_itinerary_item(&self, id: &str) -> RepositoryResult<bool> {
+        let conn = self.conn()?;
+        Ok(ItineraryItem::delete(&conn, id)?)
+    }
+
+    fn delete_itinerary_items_by_route(&self, route_id: &str) -> RepositoryResult<usize> {
+        let conn = self.conn()?;
+        Ok(ItineraryItem::delete_by_route_option_id(&conn, route_id)?)
+    }
+
+    fn delete_itinerary_items_by_plan(&self, plan_id: &str) -> RepositoryResult<usize> {
+        let conn = self.conn()?;
+        Ok(ItineraryItem::delete_by_travel_plan_id(&conn, plan_id)?)
+    }
+}
+
+impl PlanLegRepository for SqliteRepository {
+    fn insert_plan_leg(&self, leg: &PlanLeg) -> RepositoryResult<()> {
+        let conn = self.conn()?;
+        Ok(leg.insert(&conn)?)
+    }
+
+    fn find_plan_leg(&self, id: &str) -> RepositoryResult<Option<PlanLeg>> {
+        let conn = self.conn()?;
+        Ok(PlanLeg::find_by_id(&conn, id)?)
+    }
+
+    fn find_plan_legs(&self, plan_id: &str) -> RepositoryResult<Vec<PlanLeg>> {
+        let conn = self.conn()?;
+        Ok(PlanLeg::find_by_travel_plan_id(&conn, plan_id)?)
+    }
+
+    fn update_plan_leg(&self, leg: &PlanLeg) -> RepositoryResult<()> {
+        let conn = self.conn()?;
+        Ok(leg.update(&conn)?)
+    }
+
+    fn delete_plan_leg(&self, id: &str) -> RepositoryResult<bool> {
+        let conn = self.conn()?;
+        Ok(PlanLeg::delete(&conn, id)?)
+    }
+
+    fn delete_plan_legs_by_plan(&self, plan_id: &str) -> RepositoryResult<usize> {
+        let conn = self.conn()?;
+        Ok(PlanLeg::delete_by_travel_plan_id(&conn, plan_id)?)
+    }
+}
+
+impl BudgetRepository for SqliteRepository {
+    fn insert_budget(&self, budget: &Budget) -> RepositoryResult<()> {
+        let conn = self.conn()?;
+        Ok(budget.insert(&conn)?)
+    }
+
+    fn find_budgets(&self, plan_id: &str) -> RepositoryResult<Vec<Budget>> {
+        let conn = self.conn()?;
+        Ok(Budget::find_by_travel_plan_id(&conn, plan_id)?)
+    }
+
+    fn update_budget(&self, budget: &Budget) -> RepositoryResult<()> {
+        let conn = self.conn()?;
+        Ok(budget.update(&conn)?)
+    }
+
+    fn delete_budget(&self, id: &str) -> RepositoryResult<bool> {
+        let conn = self.conn()?;
+        Ok(Budget::delete(&conn, id)?)
+    }
+
+    fn delete_budgets_by_plan(&self, plan_id: &str) -> RepositoryResult<usize> {
+        let conn = self.conn()?;
+        Ok(Budget::delete_by_travel_plan_id(&conn, plan_id)?)
+    }
+
+    fn insert_expense(&self, expense: &Expense) -> RepositoryResult<()> {
+        let conn = self.conn()?;
+        Ok(expense.insert(&conn)?)
+    }
+
+    fn find_expense(&self, id: &str) -> RepositoryResult<Option<Expense>> {
+        let conn = self.conn()?;
+        Ok(Expense::find_by_id(&conn, id)?)
+    }
+
+    fn find_expenses(&self, plan_id: &str) -> RepositoryResult<Vec<Expense>> {
+        let conn = self.conn()?;
+        Ok(Expense::find_by_travel_plan_id(&conn, plan_id)?)
+    }
+
+    fn update_expense(&self, expense: &Expense) -> RepositoryResult<()> {
+        let conn = self.conn()?;
+        Ok(expense.update(&conn)?)
+    }
+
+    fn delete_expense(&self, id: &str) -> RepositoryResult<bool> {
+        let conn = self.conn()?;
+        Ok(Expense::delete(&conn, id)?)
+    }
+
+    fn delete_expenses_by_plan(&self, plan_id: &str) -> RepositoryResult<usize> {
+        let conn = self.conn()?;
+        Ok(Expense::delete_by_travel_plan_id(&conn, plan_id)?)
+    }
+}
+
+impl MaintenanceRepository for SqliteRepository {
+    fn schema_version(&self) -> RepositoryResult<usize> {
+        let conn = self.conn()?;
+        Ok(schema::schema_version(&conn)?)
+    }
+
+    fn count_rows(&self) -> RepositoryResult<Vec<TableCount>> {
+        let conn = self.conn()?;
+        Ok(maintenance::count_rows(&conn)?)
+    }
+
+    fn purge_orphans(&self) -> RepositoryResult<OrphanReport> {
+        let conn = self.conn()?;
+        Ok(maintenance::purge_orphans(&conn)?)
+    }
+
+    fn vacuum(&self) -> RepositoryResult<()> {
+        let conn = self.conn()?;
+        Ok(maintenance::vacuum(&conn)?)
+    }
+
+    fn backup_to(&self, dir: &Path) -> RepositoryResult<BackupInfo> {
+        let conn = self.conn()?;
+        Ok(backup::create_backup(&conn, dir)?)
+    }
+}
+
+impl AuditRepository for SqliteRepository {
+    fn insert_audit_entry(&self, entry: &AuditEntry) -> RepositoryResult<()> {
+        let conn = self.conn()?;
+        Ok(entry.insert(&conn)?)
+    }
+
+    fn find_audit_entries(&self, limit: usize, offset: usize) -> RepositoryResult<Vec<AuditEntry>> {
+        let conn = self.conn()?;
+        Ok(AuditEntry::find_recent(&conn, limit, offset)?)
+    }
+}
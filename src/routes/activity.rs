@@ -0,0 +1,92 @@
+use actix_web::{HttpResponse, Responder, web};
+use serde::Deserialize;
+use utoipa::IntoParams;
+
+use crate::middleware::auth::AuthenticatedUser;
+use crate::repositories::Repositories;
+use crate::routes::route_option::ErrorResponse;
+use crate::services::activity_service::{ActivityError, ActivityService};
+use crate::services::blocking;
+use crate::services::travel_plan_service::TravelPlanError;
+
+const DEFAULT_ACTIVITY_PAGE_SIZE: usize = 50;
+const MAX_ACTIVITY_PAGE_SIZE: usize = 200;
+
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct ActivityQuery {
+    /// Events per page (default 50, at most 200)
+    pub limit: Option<usize>,
+    pub offset: Option<usize>,
+}
+
+fn activity_error_response(error: ActivityError) -> HttpResponse {
+    match error {
+        ActivityError::TravelPlanError(TravelPlanError::NotFound) => {
+            HttpResponse::NotFound().json(ErrorResponse {
+                error: "Travel plan not found".to_string(),
+            })
+        }
+        ActivityError::TravelPlanError(TravelPlanError::Unauthorized) => {
+            HttpResponse::Forbidden().json(ErrorResponse {
+                error: "You don't have permission to access this travel plan".to_string(),
+            })
+        }
+        ActivityError::TravelPlanError(TravelPlanError::PreconditionFailed) => {
+            HttpResponse::PreconditionFailed().json(ErrorResponse {
+                error: "The travel plan has changed since it was read".to_string(),
+            })
+        }
+        ActivityError::TravelPlanError(TravelPlanError::DatabaseError(e))
+        | ActivityError::DatabaseError(e) => {
+            HttpResponse::InternalServerError().json(ErrorResponse {
+                error: format!("Database error: {}", e),
+            })
+        }
+    }
+}
+
+/// Activity feed of a travel plan
+///
+/// Who created, edited or deleted the plan, generated or removed its routes,
+/// and shared it. Each event lists the changed fields as `from`/`to` pairs.
+#[utoipa::path(
+    get,
+    path = "/api/travelplan/{id}/activity",
+    params(
+        ("id" = String, Path, description = "Travel plan ID"),
+        ActivityQuery
+    ),
+    responses(
+        (status = 200, description = "Events, newest first", body = [PlanEventDto]),
+        (status = 403, description = "Unauthorized access", body = ErrorResponse),
+        (status = 404, description = "Travel plan not found", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
+    ),
+    security(
+        ("Bearer" = [])
+    ),
+    tag = "travel_plans"
+)]
+pub async fn get_plan_activity(
+    repos: web::Data<dyn Repositories>,
+    auth_user: AuthenticatedUser,
+    path: web::Path<String>,
+    query: web::Query<ActivityQuery>,
+) -> impl Responder {
+    let plan_id = path.into_inner();
+    let limit = query
+        .limit
+        .unwrap_or(DEFAULT_ACTIVITY_PAGE_SIZE)
+        .clamp(1, MAX_ACTIVITY_PAGE_SIZE);
+    // The repositories page with an i64 offset
+    let offset = query.offset.unwrap_or(0).min(i64::MAX as usize);
+
+    let user_id = auth_user.user_id.clone();
+    let service = ActivityService::new(repos.into_inner());
+    let result = blocking::run(move || service.activity(&plan_id, &user_id, limit, offset)).await;
+
+    match result {
+        Ok(events) => HttpResponse::Ok().json(events),
+        Err(e) => activity_error_response(e),
+    }
+}
@@ -0,0 +1,297 @@
+use actix_web::{HttpResponse, Responder, web};
+use log::info;
+use serde::Deserialize;
+use utoipa::{IntoParams, ToSchema};
+
+use crate::app::AppState;
+use crate::middleware::auth::{AdminUser, AuthenticatedUser};
+use crate::models::user::Role;
+use crate::repositories::Repositories;
+use crate::routes::route_option::ErrorResponse;
+use crate::services::admin_service::{AdminError, AdminService};
+use crate::services::backup_service::{BackupService, BackupServiceError};
+use crate::services::blocking;
+
+const DEFAULT_AUDIT_PAGE_SIZE: usize = 50;
+const MAX_AUDIT_PAGE_SIZE: usize = 500;
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct SetRoleRequest {
+    pub role: Role,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct AuditQuery {
+    /// Entries per page (default 50, at most 500)
+    pub limit: Option<usize>,
+    pub offset: Option<usize>,
+}
+
+fn admin_error_response(error: AdminError) -> HttpResponse {
+    match error {
+        AdminError::UserNotFound(_) => HttpResponse::NotFound().json(ErrorResponse {
+            error: "User not found".to_string(),
+        }),
+        AdminError::UsernameTaken(_) => HttpResponse::Conflict().json(ErrorResponse {
+            error: error.to_string(),
+        }),
+        AdminError::InvalidInput(e) => HttpResponse::BadRequest().json(ErrorResponse { error: e }),
+        AdminError::DatabaseError(e) => HttpResponse::InternalServerError().json(ErrorResponse {
+            error: format!("Database error: {}", e),
+        }),
+    }
+}
+
+fn backup_error_response(error: BackupServiceError) -> HttpResponse {
+    match error {
+        BackupServiceError::NotConfigured => {
+            HttpResponse::ServiceUnavailable().json(ErrorResponse {
+                error: "Backups are not configured".to_string(),
+            })
+        }
+        BackupServiceError::Unsupported(e) => HttpResponse::NotImplemented().json(ErrorResponse {
+            error: e,
+        }),
+        BackupServiceError::BackupFailed(e) => {
+            HttpResponse::InternalServerError().json(ErrorResponse {
+                error: format!("Backup failed: {}", e),
+            })
+        }
+    }
+}
+
+fn backup_service(state: &AppState) -> Result<BackupService<dyn Repositories>, BackupServiceError> {
+    let config = state.backups.clone().ok_or(BackupServiceError::NotConfigured)?;
+    Ok(BackupService::new(state.repositories.clone(), config))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/admin/backups",
+    responses(
+        (status = 201, description = "Backup created", body = BackupRun),
+        (status = 403, description = "Caller is not an admin"),
+        (status = 500, description = "Backup failed", body = ErrorResponse),
+        (status = 501, description = "Storage backend cannot be backed up online", body = ErrorResponse),
+        (status = 503, description = "Backups are not configured", body = ErrorResponse)
+    ),
+    security(
+        ("Bearer" = [])
+    ),
+    tag = "admin"
+)]
+pub async fn create_backup(state: web::Data<AppState>, admin: AdminUser) -> impl Responder {
+    let AdminUser(auth_user) = admin;
+    info!("Backup requested by admin: {}", auth_user.username);
+
+    let service = match backup_service(&state) {
+        Ok(service) => service,
+        Err(e) => return backup_error_response(e),
+    };
+    let actor = auth_user.actor();
+    let result = blocking::run(move || service.run_backup(&actor)).await;
+
+    match result {
+        Ok(run) => HttpResponse::Created().json(run),
+        Err(e) => backup_error_response(e),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/admin/backups",
+    responses(
+        (status = 200, description = "Backups, newest first", body = [BackupInfo]),
+        (status = 403, description = "Caller is not an admin"),
+        (status = 500, description = "Backup directory could not be read", body = ErrorResponse),
+        (status = 503, description = "Backups are not configured", body = ErrorResponse)
+    ),
+    security(
+        ("Bearer" = [])
+    ),
+    tag = "admin"
+)]
+pub async fn list_backups(state: web::Data<AppState>, _admin: AdminUser) -> impl Responder {
+    let service = match backup_service(&state) {
+        Ok(service) => service,
+        Err(e) => return backup_error_response(e),
+    };
+    let result = blocking::run(move || service.list_backups()).await;
+
+    match result {
+        Ok(backups) => HttpResponse::Ok().json(backups),
+        Err(e) => backup_error_response(e),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/admin/users",
+    responses(
+        (status = 200, description = "All accounts, oldest first", body = [User]),
+        (status = 403, description = "Caller is not an admin"),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
+    ),
+    security(
+        ("Bearer" = [])
+    ),
+    tag = "admin"
+)]
+pub async fn list_users(repos: web::Data<dyn Repositories>, admin: AdminUser) -> impl Responder {
+    let AdminUser(auth_user) = admin;
+    info!("Listing users for admin: {}", auth_user.username);
+
+    let service = AdminService::new(repos.into_inner()).acting_as(auth_user.actor());
+    let result = blocking::run(move || service.list_users()).await;
+
+    match result {
+        Ok(users) => HttpResponse::Ok().json(users),
+        Err(e) => admin_error_response(e),
+    }
+}
+
+async fn set_user_locked(
+    repos: web::Data<dyn Repositories>,
+    auth_user: AuthenticatedUser,
+    username: String,
+    locked: bool,
+) -> HttpResponse {
+    info!(
+        "Admin {} {} user: {}",
+        auth_user.username,
+        if locked { "locking" } else { "unlocking" },
+        username
+    );
+
+    let service = AdminService::new(repos.into_inner()).acting_as(auth_user.actor());
+    let result = blocking::run(move || service.set_user_disabled(&username, locked)).await;
+
+    match result {
+        Ok(user) => HttpResponse::Ok().json(user),
+        Err(e) => admin_error_response(e),
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/admin/users/{username}/lock",
+    params(
+        ("username" = String, Path, description = "Account to lock")
+    ),
+    responses(
+        (status = 200, description = "Account locked; it can no longer log in", body = User),
+        (status = 400, description = "Admins cannot lock themselves", body = ErrorResponse),
+        (status = 403, description = "Caller is not an admin"),
+        (status = 404, description = "User not found", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
+    ),
+    security(
+        ("Bearer" = [])
+    ),
+    tag = "admin"
+)]
+pub async fn lock_user(
+    repos: web::Data<dyn Repositories>,
+    admin: AdminUser,
+    path: web::Path<String>,
+) -> impl Responder {
+    set_user_locked(repos, admin.0, path.into_inner(), true).await
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/admin/users/{username}/unlock",
+    params(
+        ("username" = String, Path, description = "Account to unlock")
+    ),
+    responses(
+        (status = 200, description = "Account unlocked", body = User),
+        (status = 403, description = "Caller is not an admin"),
+        (status = 404, description = "User not found", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
+    ),
+    security(
+        ("Bearer" = [])
+    ),
+    tag = "admin"
+)]
+pub async fn unlock_user(
+    repos: web::Data<dyn Repositories>,
+    admin: AdminUser,
+    path: web::Path<String>,
+) -> impl Responder {
+    set_user_locked(repos, admin.0, path.into_inner(), false).await
+}
+
+#[utoipa::path(
+    put,
+    path = "/api/admin/users/{username}/role",
+    params(
+        ("username" = String, Path, description = "Account to change")
+    ),
+    request_body = SetRoleRequest,
+    responses(
+        (status = 200, description = "Role changed; applies from the user's next login", body = User),
+        (status = 400, description = "Admins cannot demote themselves", body = ErrorResponse),
+        (status = 403, description = "Caller is not an admin"),
+        (status = 404, description = "User not found", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
+    ),
+    security(
+        ("Bearer" = [])
+    ),
+    tag = "admin"
+)]
+pub async fn set_user_role(
+    repos: web::Data<dyn Repositories>,
+    admin: AdminUser,
+    path: web::Path<String>,
+    body: web::Json<SetRoleRequest>,
+) -> impl Responder {
+    let AdminUser(auth_user) = admin;
+    let username = path.into_inner();
+    let role = body.into_inner().role;
+    info!("Admin {} setting role of {} to {}", auth_user.username, username, role);
+
+    let service = AdminService::new(repos.into_inner()).acting_as(auth_user.actor());
+    let result = blocking::run(move || service.set_user_role(&username, role)).await;
+
+    match result {
+        Ok(user) => HttpResponse::Ok().json(user),
+        Err(e) => admin_error_response(e),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/admin/audit",
+    params(AuditQuery),
+    responses(
+        (status = 200, description = "Audit log, newest first", body = [AuditEntry]),
+        (status = 403, description = "Caller is not an admin"),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
+    ),
+    security(
+        ("Bearer" = [])
+    ),
+    tag = "admin"
+)]
+pub async fn get_audit_log(
+    repos: web::Data<dyn Repositories>,
+    admin: AdminUser,
+    query: web::Query<AuditQuery>,
+) -> impl Responder {
+    let limit = query
+        .limit
+        .unwrap_or(DEFAULT_AUDIT_PAGE_SIZE)
+        .min(MAX_AUDIT_PAGE_SIZE);
+    let offset = query.offset.unwrap_or(0);
+
+    let service = AdminService::new(repos.into_inner()).acting_as(admin.0.actor());
+    let result = blocking::run(move || service.audit_log(limit, offset)).await;
+
+    match result {
+        Ok(entries) => HttpResponse::Ok().json(entries),
+        Err(e) => admin_error_response(e),
+    }
+}
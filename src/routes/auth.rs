@@ -3,9 +3,10 @@ use log::info;
 use serde::Serialize;
 use utoipa::ToSchema;
 
-use crate::db::connection::DbPool;
-use crate::models::user::{LoginCredentials, NewUser};
+use crate::models::user::{LoginCredentials, NewUser, Role};
 use crate::services::auth_service::{AuthService, AuthError};
+use crate::repositories::Repositories;
+use crate::services::blocking;
 
 #[derive(Debug, Serialize, ToSchema)]
 pub struct RegisterResponse {
@@ -20,6 +21,7 @@ pub struct LoginResponse {
     expires_in: i64,
     user_id: String,
     username: String,
+    role: Role,
 }
 
 #[derive(Debug, Serialize, ToSchema)]
@@ -39,21 +41,16 @@ struct ErrorResponse {
     tag = "auth"
 )]
 pub async fn register(
-    pool: web::Data<DbPool>,
+    repos: web::Data<dyn Repositories>,
     user_data: web::Json<NewUser>,
 ) -> impl Responder {
     info!("Received registration request for user: {}", user_data.username);
     
-    let conn = match pool.get() {
-        Ok(conn) => conn,
-        Err(e) => {
-            return HttpResponse::InternalServerError().json(ErrorResponse {
-                error: format!("Database connection error: {}", e),
-            });
-        }
-    };
-    
-    match AuthService::register(&conn, &user_data) {
+    let user_data = user_data.into_inner();
+    let service = AuthService::new(repos.into_inner());
+    let result = blocking::run(move || service.register(&user_data)).await;
+
+    match result {
         Ok(user) => {
             HttpResponse::Created().json(RegisterResponse {
                 message: "User registered successfully".to_string(),
@@ -85,26 +82,22 @@ pub async fn register(
     responses(
         (status = 200, description = "Login successful", body = LoginResponse),
         (status = 401, description = "Invalid credentials", body = ErrorResponse),
+        (status = 403, description = "Account is disabled", body = ErrorResponse),
         (status = 500, description = "Internal server error", body = ErrorResponse)
     ),
     tag = "auth"
 )]
 pub async fn login(
-    pool: web::Data<DbPool>,
+    repos: web::Data<dyn Repositories>,
     credentials: web::Json<LoginCredentials>,
 ) -> impl Responder {
     info!("Received login request for user: {}", credentials.username);
     
-    let conn = match pool.get() {
-        Ok(conn) => conn,
-        Err(e) => {
-            return HttpResponse::InternalServerError().json(ErrorResponse {
-                error: format!("Database connection error: {}", e),
-            });
-        }
-    };
-    
-    match AuthService::login(&conn, &credentials) {
+    let credentials = credentials.into_inner();
+    let service = AuthService::new(repos.into_inner());
+    let result = blocking::run(move || service.login(&credentials)).await;
+
+    match result {
         Ok((user, token, expires_in)) => {
             HttpResponse::Ok().json(LoginResponse {
                 token,
@@ -112,6 +105,7 @@ pub async fn login(
                 expires_in,
                 user_id: user.id,
                 username: user.username,
+                role: user.role,
             })
         }
         Err(AuthError::InvalidCredentials) => {
@@ -119,6 +113,11 @@ pub async fn login(
                 error: "Invalid username or password".to_string(),
             })
         }
+        Err(AuthError::AccountDisabled) => {
+            HttpResponse::Forbidden().json(ErrorResponse {
+                error: "Account is disabled".to_string(),
+            })
+        }
         Err(AuthError::DatabaseError(e)) => {
             HttpResponse::InternalServerError().json(ErrorResponse {
                 error: format!("Database error: {}", e),
@@ -0,0 +1,416 @@
+use actix_web::{HttpResponse, Responder, web};
+use log::info;
+
+use crate::app::AppState;
+use crate::middleware::auth::AuthenticatedUser;
+use crate::models::budget::{ExpenseCategory, NewExpense, PatchExpense, SetBudget};
+use crate::repositories::Repositories;
+use crate::routes::route_option::ErrorResponse;
+use crate::services::blocking;
+use crate::services::budget_service::{BudgetError, BudgetService};
+use crate::services::travel_plan_service::TravelPlanError;
+
+fn budget_error_response(error: BudgetError) -> HttpResponse {
+    match error {
+        BudgetError::TravelPlanError(TravelPlanError::NotFound) => {
+            HttpResponse::NotFound().json(ErrorResponse {
+                error: "Travel plan not found".to_string(),
+            })
+        }
+        BudgetError::TravelPlanError(TravelPlanError::Unauthorized) => {
+            HttpResponse::Forbidden().json(ErrorResponse {
+                error: "You don't have permission to access this travel plan".to_string(),
+            })
+        }
+        BudgetError::TravelPlanError(TravelPlanError::PreconditionFailed) => {
+            HttpResponse::PreconditionFailed().json(ErrorResponse {
+                error: "The travel plan has changed since it was read".to_string(),
+            })
+        }
+        BudgetError::BudgetNotFound => HttpResponse::NotFound().json(ErrorResponse {
+            error: "No budget set for this category".to_string(),
+        }),
+        BudgetError::ExpenseNotFound => HttpResponse::NotFound().json(ErrorResponse {
+            error: "Expense not found".to_string(),
+        }),
+        BudgetError::InvalidInput(message) => {
+            HttpResponse::BadRequest().json(ErrorResponse { error: message })
+        }
+        BudgetError::CurrencyError(e) => HttpResponse::UnprocessableEntity().json(ErrorResponse {
+            error: e.to_string(),
+        }),
+        BudgetError::TravelPlanError(TravelPlanError::DatabaseError(e))
+        | BudgetError::DatabaseError(e) => {
+            HttpResponse::InternalServerError().json(ErrorResponse {
+                error: format!("Database error: {}", e),
+            })
+        }
+    }
+}
+
+/// List a travel plan's budgets
+#[utoipa::path(
+    get,
+    path = "/api/travelplan/{id}/budgets",
+    params(
+        ("id" = String, Path, description = "Travel plan ID")
+    ),
+    responses(
+        (status = 200, description = "The plan's budgets by category", body = [Budget]),
+        (status = 403, description = "Unauthorized access", body = ErrorResponse),
+        (status = 404, description = "Travel plan not found", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
+    ),
+    security(
+        ("Bearer" = [])
+    ),
+    tag = "budgets"
+)]
+pub async fn get_budgets(
+    repos: web::Data<dyn Repositories>,
+    auth_user: AuthenticatedUser,
+    path: web::Path<String>,
+) -> impl Responder {
+    let plan_id = path.into_inner();
+
+    let user_id = auth_user.user_id.clone();
+    let service = BudgetService::new(repos.into_inner());
+    let result = blocking::run(move || service.get_budgets(&plan_id, &user_id)).await;
+
+    match result {
+        Ok(budgets) => HttpResponse::Ok().json(budgets),
+        Err(e) => budget_error_response(e),
+    }
+}
+
+/// Set the budget for one category of a travel plan
+///
+/// Replaces any budget the category already has. Amounts are in minor units
+/// of the currency, e.g. cents.
+#[utoipa::path(
+    put,
+    path = "/api/travelplan/{plan_id}/budgets/{category}",
+    params(
+        ("plan_id" = String, Path, description = "Travel plan ID"),
+        ("category" = ExpenseCategory, Path, description = "Expense category")
+    ),
+    request_body = SetBudget,
+    responses(
+        (status = 200, description = "Budget replaced", body = Budget),
+        (status = 201, description = "Budget set", body = Budget),
+        (status = 400, description = "Invalid amount or currency", body = ErrorResponse),
+        (status = 403, description = "Unauthorized access", body = ErrorResponse),
+        (status = 404, description = "Travel plan not found", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
+    ),
+    security(
+        ("Bearer" = [])
+    ),
+    tag = "budgets"
+)]
+pub async fn set_budget(
+    repos: web::Data<dyn Repositories>,
+    auth_user: AuthenticatedUser,
+    path: web::Path<(String, ExpenseCategory)>,
+    budget: web::Json<SetBudget>,
+) -> impl Responder {
+    let (plan_id, category) = path.into_inner();
+    info!(
+        "User {} is setting the {} budget of travel plan {}",
+        auth_user.username, category, plan_id
+    );
+
+    let budget = budget.into_inner();
+    let user_id = auth_user.user_id.clone();
+    let service = BudgetService::new(repos.into_inner());
+    let result =
+        blocking::run(move || service.set_budget(&plan_id, category, &budget, &user_id)).await;
+
+    match result {
+        Ok((budget, true)) => HttpResponse::Created().json(budget),
+        Ok((budget, false)) => HttpResponse::Ok().json(budget),
+        Err(e) => budget_error_response(e),
+    }
+}
+
+/// Remove the budget for one category of a travel plan
+#[utoipa::path(
+    delete,
+    path = "/api/travelplan/{plan_id}/budgets/{category}",
+    params(
+        ("plan_id" = String, Path, description = "Travel plan ID"),
+        ("category" = ExpenseCategory, Path, description = "Expense category")
+    ),
+    responses(
+        (status = 204, description = "Budget removed"),
+        (status = 403, description = "Unauthorized access", body = ErrorResponse),
+        (status = 404, description = "Travel plan or budget not found", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
+    ),
+    security(
+        ("Bearer" = [])
+    ),
+    tag = "budgets"
+)]
+pub async fn delete_budget(
+    repos: web::Data<dyn Repositories>,
+    auth_user: AuthenticatedUser,
+    path: web::Path<(String, ExpenseCategory)>,
+) -> impl Responder {
+    let (plan_id, category) = path.into_inner();
+
+    let user_id = auth_user.user_id.clone();
+    let service = BudgetService::new(repos.into_inner());
+    let result = blocking::run(move || service.delete_budget(&plan_id, category, &user_id)).await;
+
+    match result {
+        Ok(()) => HttpResponse::NoContent().finish(),
+        Err(e) => budget_error_response(e),
+    }
+}
+
+/// List a travel plan's expenses
+#[utoipa::path(
+    get,
+    path = "/api/travelplan/{id}/expenses",
+    params(
+        ("id" = String, Path, description = "Travel plan ID")
+    ),
+    responses(
+        (status = 200, description = "The plan's expenses by date", body = [Expense]),
+        (status = 403, description = "Unauthorized access", body = ErrorResponse),
+        (status = 404, description = "Travel plan not found", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
+    ),
+    security(
+        ("Bearer" = [])
+    ),
+    tag = "budgets"
+)]
+pub async fn get_expenses(
+    repos: web::Data<dyn Repositories>,
+    auth_user: AuthenticatedUser,
+    path: web::Path<String>,
+) -> impl Responder {
+    let plan_id = path.into_inner();
+
+    let user_id = auth_user.user_id.clone();
+    let service = BudgetService::new(repos.into_inner());
+    let result = blocking::run(move || service.get_expenses(&plan_id, &user_id)).await;
+
+    match result {
+        Ok(expenses) => HttpResponse::Ok().json(expenses),
+        Err(e) => budget_error_response(e),
+    }
+}
+
+/// Record an expense on a travel plan
+///
+/// The expense is paid by the caller and split equally between every member
+/// of the plan unless `paidBy` and `splitBetween` say otherwise. Amounts are
+/// in minor units of the currency.
+#[utoipa::path(
+    post,
+    path = "/api/travelplan/{id}/expenses",
+    params(
+        ("id" = String, Path, description = "Travel plan ID")
+    ),
+    request_body = NewExpense,
+    responses(
+        (status = 201, description = "Expense recorded", body = Expense),
+        (status = 400, description = "Invalid expense", body = ErrorResponse),
+        (status = 403, description = "Unauthorized access", body = ErrorResponse),
+        (status = 404, description = "Travel plan not found", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
+    ),
+    security(
+        ("Bearer" = [])
+    ),
+    tag = "budgets"
+)]
+pub async fn add_expense(
+    repos: web::Data<dyn Repositories>,
+    auth_user: AuthenticatedUser,
+    path: web::Path<String>,
+    expense_data: web::Json<NewExpense>,
+) -> impl Responder {
+    let plan_id = path.into_inner();
+    info!(
+        "User {} is recording an expense on travel plan {}",
+        auth_user.username, plan_id
+    );
+
+    let new_expense = expense_data.into_inner();
+    let user_id = auth_user.user_id.clone();
+    let service = BudgetService::new(repos.into_inner());
+    let result =
+        blocking::run(move || service.add_expense(&plan_id, &new_expense, &user_id)).await;
+
+    match result {
+        Ok(expense) => HttpResponse::Created().json(expense),
+        Err(e) => budget_error_response(e),
+    }
+}
+
+/// Get one expense of a travel plan
+#[utoipa::path(
+    get,
+    path = "/api/travelplan/{plan_id}/expenses/{expense_id}",
+    params(
+        ("plan_id" = String, Path, description = "Travel plan ID"),
+        ("expense_id" = String, Path, description = "Expense ID")
+    ),
+    responses(
+        (status = 200, description = "The expense", body = Expense),
+        (status = 403, description = "Unauthorized access", body = ErrorResponse),
+        (status = 404, description = "Travel plan or expense not found", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
+    ),
+    security(
+        ("Bearer" = [])
+    ),
+    tag = "budgets"
+)]
+pub async fn get_expense(
+    repos: web::Data<dyn Repositories>,
+    auth_user: AuthenticatedUser,
+    path: web::Path<(String, String)>,
+) -> impl Responder {
+    let (plan_id, expense_id) = path.into_inner();
+
+    let user_id = auth_user.user_id.clone();
+    let service = BudgetService::new(repos.into_inner());
+    let result =
+        blocking::run(move || service.get_expense(&plan_id, &expense_id, &user_id)).await;
+
+    match result {
+        Ok(expense) => HttpResponse::Ok().json(expense),
+        Err(e) => budget_error_response(e),
+    }
+}
+
+/// Patch an expense of a travel plan
+///
+/// Applies a JSON Merge Patch (RFC 7396); no field can be cleared.
+#[utoipa::path(
+    patch,
+    path = "/api/travelplan/{plan_id}/expenses/{expense_id}",
+    params(
+        ("plan_id" = String, Path, description = "Travel plan ID"),
+        ("expense_id" = String, Path, description = "Expense ID")
+    ),
+    request_body(content = PatchExpense, content_type = "application/merge-patch+json"),
+    responses(
+        (status = 200, description = "Expense updated", body = Expense),
+        (status = 400, description = "Invalid patch", body = ErrorResponse),
+        (status = 403, description = "Unauthorized access", body = ErrorResponse),
+        (status = 404, description = "Travel plan or expense not found", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
+    ),
+    security(
+        ("Bearer" = [])
+    ),
+    tag = "budgets"
+)]
+pub async fn patch_expense(
+    repos: web::Data<dyn Repositories>,
+    auth_user: AuthenticatedUser,
+    path: web::Path<(String, String)>,
+    patch: web::Json<PatchExpense>,
+) -> impl Responder {
+    let (plan_id, expense_id) = path.into_inner();
+
+    let patch = patch.into_inner();
+    let user_id = auth_user.user_id.clone();
+    let service = BudgetService::new(repos.into_inner());
+    let result = blocking::run(move || {
+        service.patch_expense(&plan_id, &expense_id, &patch, &user_id)
+    })
+    .await;
+
+    match result {
+        Ok(expense) => HttpResponse::Ok().json(expense),
+        Err(e) => budget_error_response(e),
+    }
+}
+
+/// Remove an expense from a travel plan
+#[utoipa::path(
+    delete,
+    path = "/api/travelplan/{plan_id}/expenses/{expense_id}",
+    params(
+        ("plan_id" = String, Path, description = "Travel plan ID"),
+        ("expense_id" = String, Path, description = "Expense ID")
+    ),
+    responses(
+        (status = 204, description = "Expense removed"),
+        (status = 403, description = "Unauthorized access", body = ErrorResponse),
+        (status = 404, description = "Travel plan or expense not found", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
+    ),
+    security(
+        ("Bearer" = [])
+    ),
+    tag = "budgets"
+)]
+pub async fn delete_expense(
+    repos: web::Data<dyn Repositories>,
+    auth_user: AuthenticatedUser,
+    path: web::Path<(String, String)>,
+) -> impl Responder {
+    let (plan_id, expense_id) = path.into_inner();
+
+    let user_id = auth_user.user_id.clone();
+    let service = BudgetService::new(repos.into_inner());
+    let result =
+        blocking::run(move || service.delete_expense(&plan_id, &expense_id, &user_id)).await;
+
+    match result {
+        Ok(()) => HttpResponse::NoContent().finish(),
+        Err(e) => budget_error_response(e),
+    }
+}
+
+/// Summarise a travel plan's budget and expenses
+///
+/// Compares each category's budget with what was spent, shows what every
+/// member paid and owes, and lists the payments that settle up. A plan with
+/// a home currency is summarised in it, at the exchange rate for each
+/// expense's date; otherwise each currency is summarised on its own.
+#[utoipa::path(
+    get,
+    path = "/api/travelplan/{id}/budget-summary",
+    params(
+        ("id" = String, Path, description = "Travel plan ID")
+    ),
+    responses(
+        (status = 200, description = "Budget against actual and who owes whom",
+            body = BudgetSummary),
+        (status = 403, description = "Unauthorized access", body = ErrorResponse),
+        (status = 404, description = "Travel plan not found", body = ErrorResponse),
+        (status = 422, description = "An exchange rate is missing", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
+    ),
+    security(
+        ("Bearer" = [])
+    ),
+    tag = "budgets"
+)]
+pub async fn get_budget_summary(
+    repos: web::Data<dyn Repositories>,
+    state: web::Data<AppState>,
+    auth_user: AuthenticatedUser,
+    path: web::Path<String>,
+) -> impl Responder {
+    let plan_id = path.into_inner();
+
+    let user_id = auth_user.user_id.clone();
+    let rates = state.exchange_rates.clone();
+    let service = BudgetService::new(repos.into_inner());
+    let result = blocking::run(move || service.summary(&plan_id, &user_id, &rates)).await;
+
+    match result {
+        Ok(summary) => HttpResponse::Ok().json(summary),
+        Err(e) => budget_error_response(e),
+    }
+}
@@ -0,0 +1,390 @@
+use actix_web::{HttpResponse, Responder, web};
+use log::info;
+use serde::Deserialize;
+use utoipa::IntoParams;
+
+use crate::middleware::auth::AuthenticatedUser;
+use crate::models::comment::{NewComment, UpdateComment};
+use crate::repositories::Repositories;
+use crate::routes::route_option::ErrorResponse;
+use crate::services::blocking;
+use crate::services::comment_service::{CommentError, CommentService, CommentSubject};
+use crate::services::travel_plan_service::TravelPlanError;
+
+const DEFAULT_COMMENT_PAGE_SIZE: usize = 20;
+const MAX_COMMENT_PAGE_SIZE: usize = 100;
+
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct CommentQuery {
+    /// Threads per page (default 20, at most 100)
+    pub limit: Option<usize>,
+    pub offset: Option<usize>,
+}
+
+fn comment_error_response(error: CommentError) -> HttpResponse {
+    match error {
+        CommentError::TravelPlanError(TravelPlanError::NotFound) => {
+            HttpResponse::NotFound().json(ErrorResponse {
+                error: "Travel plan not found".to_string(),
+            })
+        }
+        CommentError::TravelPlanError(TravelPlanError::Unauthorized) => {
+            HttpResponse::Forbidden().json(ErrorResponse {
+                error: "You don't have permission to access this travel plan".to_string(),
+            })
+        }
+        CommentError::TargetNotFound => HttpResponse::NotFound().json(ErrorResponse {
+            error: "Route option or point of interest not found in this travel plan".to_string(),
+        }),
+        CommentError::CommentNotFound => HttpResponse::NotFound().json(ErrorResponse {
+            error: "Comment not found".to_string(),
+        }),
+        CommentError::NotAuthor => HttpResponse::Forbidden().json(ErrorResponse {
+            error: "Only the author can change or delete a comment".to_string(),
+        }),
+        CommentError::InvalidInput(e) => {
+            HttpResponse::BadRequest().json(ErrorResponse { error: e })
+        }
+        CommentError::TravelPlanError(TravelPlanError::PreconditionFailed) => {
+            HttpResponse::PreconditionFailed().json(ErrorResponse {
+                error: "The travel plan has changed since it was read".to_string(),
+            })
+        }
+        CommentError::TravelPlanError(TravelPlanError::DatabaseError(e))
+        | CommentError::DatabaseError(e) => {
+            HttpResponse::InternalServerError().json(ErrorResponse {
+                error: format!("Database error: {}", e),
+            })
+        }
+    }
+}
+
+async fn list_comments(
+    repos: web::Data<dyn Repositories>,
+    auth_user: AuthenticatedUser,
+    plan_id: String,
+    subject: CommentSubject,
+    query: CommentQuery,
+) -> HttpResponse {
+    let limit = query
+        .limit
+        .unwrap_or(DEFAULT_COMMENT_PAGE_SIZE)
+        .clamp(1, MAX_COMMENT_PAGE_SIZE);
+    // The repositories page with an i64 offset
+    let offset = query.offset.unwrap_or(0).min(i64::MAX as usize);
+
+    let user_id = auth_user.user_id.clone();
+    let service = CommentService::new(repos.into_inner());
+    let result = blocking::run(move || {
+        service.list_comments(&plan_id, &subject, &user_id, limit, offset)
+    })
+    .await;
+
+    match result {
+        Ok(page) => HttpResponse::Ok().json(page),
+        Err(e) => comment_error_response(e),
+    }
+}
+
+async fn add_comment(
+    repos: web::Data<dyn Repositories>,
+    auth_user: AuthenticatedUser,
+    plan_id: String,
+    subject: CommentSubject,
+    new_comment: NewComment,
+) -> HttpResponse {
+    info!(
+        "User {} is commenting on {:?} of travel plan {}",
+        auth_user.username, subject, plan_id
+    );
+
+    let user_id = auth_user.user_id.clone();
+    let service = CommentService::new(repos.into_inner());
+    let result = blocking::run(move || {
+        service.add_comment(&plan_id, &subject, &user_id, &new_comment)
+    })
+    .await;
+
+    match result {
+        Ok(comment) => HttpResponse::Created().json(comment),
+        Err(e) => comment_error_response(e),
+    }
+}
+
+/// List comment threads on a travel plan
+#[utoipa::path(
+    get,
+    path = "/api/travelplan/{id}/comments",
+    params(
+        ("id" = String, Path, description = "Travel plan ID"),
+        CommentQuery
+    ),
+    responses(
+        (status = 200, description = "Threads, oldest first, with nested replies", body = CommentPage),
+        (status = 403, description = "Unauthorized access", body = ErrorResponse),
+        (status = 404, description = "Travel plan not found", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
+    ),
+    security(
+        ("Bearer" = [])
+    ),
+    tag = "comments"
+)]
+pub async fn get_plan_comments(
+    repos: web::Data<dyn Repositories>,
+    auth_user: AuthenticatedUser,
+    path: web::Path<String>,
+    query: web::Query<CommentQuery>,
+) -> impl Responder {
+    let plan_id = path.into_inner();
+    list_comments(repos, auth_user, plan_id, CommentSubject::Plan, query.into_inner()).await
+}
+
+/// Comment on a travel plan
+///
+/// Set `parentId` to reply within an existing thread. `@username` mentions
+/// of existing accounts are recorded on the comment.
+#[utoipa::path(
+    post,
+    path = "/api/travelplan/{id}/comments",
+    params(
+        ("id" = String, Path, description = "Travel plan ID")
+    ),
+    request_body = NewComment,
+    responses(
+        (status = 201, description = "Comment created", body = CommentDto),
+        (status = 400, description = "Empty or too long body, or an unknown parent", body = ErrorResponse),
+        (status = 403, description = "Unauthorized access", body = ErrorResponse),
+        (status = 404, description = "Travel plan not found", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
+    ),
+    security(
+        ("Bearer" = [])
+    ),
+    tag = "comments"
+)]
+pub async fn create_plan_comment(
+    repos: web::Data<dyn Repositories>,
+    auth_user: AuthenticatedUser,
+    path: web::Path<String>,
+    comment_data: web::Json<NewComment>,
+) -> impl Responder {
+    let plan_id = path.into_inner();
+    add_comment(repos, auth_user, plan_id, CommentSubject::Plan, comment_data.into_inner()).await
+}
+
+/// List comment threads on a route option
+#[utoipa::path(
+    get,
+    path = "/api/travelplan/{plan_id}/routes/{route_id}/comments",
+    params(
+        ("plan_id" = String, Path, description = "Travel plan ID"),
+        ("route_id" = String, Path, description = "Route option ID"),
+        CommentQuery
+    ),
+    responses(
+        (status = 200, description = "Threads, oldest first, with nested replies", body = CommentPage),
+        (status = 403, description = "Unauthorized access", body = ErrorResponse),
+        (status = 404, description = "Travel plan or route option not found", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
+    ),
+    security(
+        ("Bearer" = [])
+    ),
+    tag = "comments"
+)]
+pub async fn get_route_comments(
+    repos: web::Data<dyn Repositories>,
+    auth_user: AuthenticatedUser,
+    path: web::Path<(String, String)>,
+    query: web::Query<CommentQuery>,
+) -> impl Responder {
+    let (plan_id, route_id) = path.into_inner();
+    let subject = CommentSubject::Route(route_id);
+    list_comments(repos, auth_user, plan_id, subject, query.into_inner()).await
+}
+
+/// Comment on a route option
+#[utoipa::path(
+    post,
+    path = "/api/travelplan/{plan_id}/routes/{route_id}/comments",
+    params(
+        ("plan_id" = String, Path, description = "Travel plan ID"),
+        ("route_id" = String, Path, description = "Route option ID")
+    ),
+    request_body = NewComment,
+    responses(
+        (status = 201, description = "Comment created", body = CommentDto),
+        (status = 400, description = "Empty or too long body, or an unknown parent", body = ErrorResponse),
+        (status = 403, description = "Unauthorized access", body = ErrorResponse),
+        (status = 404, description = "Travel plan or route option not found", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
+    ),
+    security(
+        ("Bearer" = [])
+    ),
+    tag = "comments"
+)]
+pub async fn create_route_comment(
+    repos: web::Data<dyn Repositories>,
+    auth_user: AuthenticatedUser,
+    path: web::Path<(String, String)>,
+    comment_data: web::Json<NewComment>,
+) -> impl Responder {
+    let (plan_id, route_id) = path.into_inner();
+    let subject = CommentSubject::Route(route_id);
+    add_comment(repos, auth_user, plan_id, subject, comment_data.into_inner()).await
+}
+
+/// List comment threads on a point of interest
+#[utoipa::path(
+    get,
+    path = "/api/travelplan/{plan_id}/routes/{route_id}/pois/{poi_id}/comments",
+    params(
+        ("plan_id" = String, Path, description = "Travel plan ID"),
+        ("route_id" = String, Path, description = "Route option ID"),
+        ("poi_id" = String, Path, description = "Point of interest ID"),
+        CommentQuery
+    ),
+    responses(
+        (status = 200, description = "Threads, oldest first, with nested replies", body = CommentPage),
+        (status = 403, description = "Unauthorized access", body = ErrorResponse),
+        (status = 404, description = "Travel plan, route option or point of interest not found", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
+    ),
+    security(
+        ("Bearer" = [])
+    ),
+    tag = "comments"
+)]
+pub async fn get_poi_comments(
+    repos: web::Data<dyn Repositories>,
+    auth_user: AuthenticatedUser,
+    path: web::Path<(String, String, String)>,
+    query: web::Query<CommentQuery>,
+) -> impl Responder {
+    let (plan_id, route_id, poi_id) = path.into_inner();
+    let subject = CommentSubject::Poi { route_id, poi_id };
+    list_comments(repos, auth_user, plan_id, subject, query.into_inner()).await
+}
+
+/// Comment on a point of interest
+#[utoipa::path(
+    post,
+    path = "/api/travelplan/{plan_id}/routes/{route_id}/pois/{poi_id}/comments",
+    params(
+        ("plan_id" = String, Path, description = "Travel plan ID"),
+        ("route_id" = String, Path, description = "Route option ID"),
+        ("poi_id" = String, Path, description = "Point of interest ID")
+    ),
+    request_body = NewComment,
+    responses(
+        (status = 201, description = "Comment created", body = CommentDto),
+        (status = 400, description = "Empty or too long body, or an unknown parent", body = ErrorResponse),
+        (status = 403, description = "Unauthorized access", body = ErrorResponse),
+        (status = 404, description = "Travel plan, route option or point of interest not found", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
+    ),
+    security(
+        ("Bearer" = [])
+    ),
+    tag = "comments"
+)]
+pub async fn create_poi_comment(
+    repos: web::Data<dyn Repositories>,
+    auth_user: AuthenticatedUser,
+    path: web::Path<(String, String, String)>,
+    comment_data: web::Json<NewComment>,
+) -> impl Responder {
+    let (plan_id, route_id, poi_id) = path.into_inner();
+    let subject = CommentSubject::Poi { route_id, poi_id };
+    add_comment(repos, auth_user, plan_id, subject, comment_data.into_inner()).await
+}
+
+/// Edit your comment
+///
+/// A comment that was deleted but kept for its replies can't be edited.
+#[utoipa::path(
+    put,
+    path = "/api/travelplan/{plan_id}/comments/{comment_id}",
+    params(
+        ("plan_id" = String, Path, description = "Travel plan ID"),
+        ("comment_id" = String, Path, description = "Comment ID")
+    ),
+    request_body = UpdateComment,
+    responses(
+        (status = 200, description = "Comment updated", body = CommentDto),
+        (status = 400, description = "Empty or too long body", body = ErrorResponse),
+        (status = 403, description = "Not the comment's author", body = ErrorResponse),
+        (status = 404, description = "Travel plan or comment not found", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
+    ),
+    security(
+        ("Bearer" = [])
+    ),
+    tag = "comments"
+)]
+pub async fn update_comment(
+    repos: web::Data<dyn Repositories>,
+    auth_user: AuthenticatedUser,
+    path: web::Path<(String, String)>,
+    comment_data: web::Json<UpdateComment>,
+) -> impl Responder {
+    let (plan_id, comment_id) = path.into_inner();
+    let update = comment_data.into_inner();
+    let user_id = auth_user.user_id.clone();
+    let service = CommentService::new(repos.into_inner());
+    let result = blocking::run(move || {
+        service.edit_comment(&plan_id, &comment_id, &user_id, &update)
+    })
+    .await;
+
+    match result {
+        Ok(comment) => HttpResponse::Ok().json(comment),
+        Err(e) => comment_error_response(e),
+    }
+}
+
+/// Delete your comment
+///
+/// A comment that has replies is replaced by "[deleted]" so the replies keep
+/// their place in the thread.
+#[utoipa::path(
+    delete,
+    path = "/api/travelplan/{plan_id}/comments/{comment_id}",
+    params(
+        ("plan_id" = String, Path, description = "Travel plan ID"),
+        ("comment_id" = String, Path, description = "Comment ID")
+    ),
+    responses(
+        (status = 204, description = "Comment deleted"),
+        (status = 403, description = "Not the comment's author", body = ErrorResponse),
+        (status = 404, description = "Travel plan or comment not found", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
+    ),
+    security(
+        ("Bearer" = [])
+    ),
+    tag = "comments"
+)]
+pub async fn delete_comment(
+    repos: web::Data<dyn Repositories>,
+    auth_user: AuthenticatedUser,
+    path: web::Path<(String, String)>,
+) -> impl Responder {
+    let (plan_id, comment_id) = path.into_inner();
+    info!(
+        "User {} is deleting comment {} of travel plan {}",
+        auth_user.username, comment_id, plan_id
+    );
+
+    let user_id = auth_user.user_id.clone();
+    let service = CommentService::new(repos.into_inner());
+    let result =
+        blocking::run(move || service.delete_comment(&plan_id, &comment_id, &user_id)).await;
+
+    match result {
+        Ok(_) => HttpResponse::NoContent().finish(),
+        Err(e) => comment_error_response(e),
+    }
+}
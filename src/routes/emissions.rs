@@ -0,0 +1,78 @@
+use actix_web::{HttpResponse, Responder, web};
+use chrono::{Datelike, Utc};
+use log::info;
+use serde::Deserialize;
+use utoipa::IntoParams;
+
+use crate::app::AppState;
+use crate::middleware::auth::AuthenticatedUser;
+use crate::repositories::Repositories;
+use crate::routes::route_option::ErrorResponse;
+use crate::services::blocking;
+use crate::services::emissions_service::{EmissionsError, EmissionsService};
+use crate::services::travel_plan_service::TravelPlanError;
+
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct EmissionsQuery {
+    /// Calendar year of the plans to report on (default this year)
+    pub year: Option<i32>,
+}
+
+fn emissions_error_response(error: EmissionsError) -> HttpResponse {
+    match error {
+        EmissionsError::TravelPlanError(TravelPlanError::DatabaseError(e))
+        | EmissionsError::DatabaseError(e) => {
+            HttpResponse::InternalServerError().json(ErrorResponse {
+                error: format!("Database error: {}", e),
+            })
+        }
+        EmissionsError::TravelPlanError(_) => {
+            HttpResponse::InternalServerError().json(ErrorResponse {
+                error: "Failed to build the emissions report".to_string(),
+            })
+        }
+    }
+}
+
+/// Yearly emissions report of the current user
+///
+/// Kilograms of CO2e for one traveller over the plans they own or have
+/// joined that start in the year, by travel mode and by plan. Each leg counts
+/// its selected route, or its fastest option while nothing is selected.
+#[utoipa::path(
+    get,
+    path = "/api/emissions",
+    params(EmissionsQuery),
+    responses(
+        (status = 200, description = "The year's emissions", body = EmissionsReport),
+        (status = 400, description = "Invalid year", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
+    ),
+    security(
+        ("Bearer" = [])
+    ),
+    tag = "emissions"
+)]
+pub async fn get_emissions_report(
+    repos: web::Data<dyn Repositories>,
+    state: web::Data<AppState>,
+    auth_user: AuthenticatedUser,
+    query: web::Query<EmissionsQuery>,
+) -> impl Responder {
+    let year = query.year.unwrap_or_else(|| Utc::now().year());
+    if !(1..=9999).contains(&year) {
+        return HttpResponse::BadRequest().json(ErrorResponse {
+            error: format!("{} is not a year", year),
+        });
+    }
+    info!("Reporting {} emissions for user {}", year, auth_user.username);
+
+    let user_id = auth_user.user_id.clone();
+    let service = EmissionsService::new(repos.into_inner(), state.emission_factors.clone());
+    let result = blocking::run(move || service.yearly_report(&user_id, year)).await;
+
+    match result {
+        Ok(report) => HttpResponse::Ok().json(report),
+        Err(e) => emissions_error_response(e),
+    }
+}
@@ -0,0 +1,339 @@
+use actix_web::{HttpResponse, Responder, web};
+use chrono::NaiveDate;
+use log::info;
+
+use crate::middleware::auth::AuthenticatedUser;
+use crate::models::itinerary::{NewItineraryItem, PatchItineraryItem, ReorderItineraryDay};
+use crate::repositories::Repositories;
+use crate::routes::route_option::ErrorResponse;
+use crate::services::blocking;
+use crate::services::itinerary_scheduler::ScheduleRequest;
+use crate::services::itinerary_service::{ItineraryError, ItineraryService};
+use crate::services::travel_plan_service::TravelPlanError;
+
+fn itinerary_error_response(error: ItineraryError) -> HttpResponse {
+    match error {
+        ItineraryError::TravelPlanError(TravelPlanError::NotFound) => {
+            HttpResponse::NotFound().json(ErrorResponse {
+                error: "Travel plan not found".to_string(),
+            })
+        }
+        ItineraryError::TravelPlanError(TravelPlanError::Unauthorized) => {
+            HttpResponse::Forbidden().json(ErrorResponse {
+                error: "You don't have permission to access this travel plan".to_string(),
+            })
+        }
+        ItineraryError::TravelPlanError(TravelPlanError::PreconditionFailed) => {
+            HttpResponse::PreconditionFailed().json(ErrorResponse {
+                error: "The travel plan has changed since it was read".to_string(),
+            })
+        }
+        ItineraryError::ItemNotFound => HttpResponse::NotFound().json(ErrorResponse {
+            error: "Itinerary item not found".to_string(),
+        }),
+        ItineraryError::NoDateRange => HttpResponse::Conflict().json(ErrorResponse {
+            error: "The travel plan needs a start and end date first".to_string(),
+        }),
+        ItineraryError::InvalidItem(message) => {
+            HttpResponse::BadRequest().json(ErrorResponse { error: message })
+        }
+        ItineraryError::TravelPlanError(TravelPlanError::DatabaseError(e))
+        | ItineraryError::DatabaseError(e) => {
+            HttpResponse::InternalServerError().json(ErrorResponse {
+                error: format!("Database error: {}", e),
+            })
+        }
+    }
+}
+
+/// Get a travel plan's itinerary
+///
+/// Lists every day from the plan's start date to its end date with the
+/// items on it, in order.
+#[utoipa::path(
+    get,
+    path = "/api/travelplan/{id}/itinerary",
+    params(
+        ("id" = String, Path, description = "Travel plan ID")
+    ),
+    responses(
+        (status = 200, description = "The plan's days and their items", body = Itinerary),
+        (status = 403, description = "Unauthorized access", body = ErrorResponse),
+        (status = 404, description = "Travel plan not found", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
+    ),
+    security(
+        ("Bearer" = [])
+    ),
+    tag = "itinerary"
+)]
+pub async fn get_itinerary(
+    repos: web::Data<dyn Repositories>,
+    auth_user: AuthenticatedUser,
+    path: web::Path<String>,
+) -> impl Responder {
+    let plan_id = path.into_inner();
+
+    let user_id = auth_user.user_id.clone();
+    let service = ItineraryService::new(repos.into_inner());
+    let result = blocking::run(move || service.itinerary(&plan_id, &user_id)).await;
+
+    match result {
+        Ok(itinerary) => HttpResponse::Ok().json(itinerary),
+        Err(e) => itinerary_error_response(e),
+    }
+}
+
+/// Add an item to a travel plan's itinerary
+///
+/// The item goes last on its day. Route legs name a route option and point
+/// of interest visits name a point of interest and a time window; both take
+/// their title from it unless one is given.
+#[utoipa::path(
+    post,
+    path = "/api/travelplan/{id}/itinerary/items",
+    params(
+        ("id" = String, Path, description = "Travel plan ID")
+    ),
+    request_body = NewItineraryItem,
+    responses(
+        (status = 201, description = "Item added", body = ItineraryItem),
+        (status = 400, description = "Invalid item", body = ErrorResponse),
+        (status = 403, description = "Unauthorized access", body = ErrorResponse),
+        (status = 404, description = "Travel plan not found", body = ErrorResponse),
+        (status = 409, description = "The plan has no start or end date", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
+    ),
+    security(
+        ("Bearer" = [])
+    ),
+    tag = "itinerary"
+)]
+pub async fn add_itinerary_item(
+    repos: web::Data<dyn Repositories>,
+    auth_user: AuthenticatedUser,
+    path: web::Path<String>,
+    item_data: web::Json<NewItineraryItem>,
+) -> impl Responder {
+    let plan_id = path.into_inner();
+    info!(
+        "User {} is adding an itinerary item to travel plan {}",
+        auth_user.username, plan_id
+    );
+
+    let new_item = item_data.into_inner();
+    let user_id = auth_user.user_id.clone();
+    let service = ItineraryService::new(repos.into_inner());
+    let result = blocking::run(move || service.add_item(&plan_id, new_item, &user_id)).await;
+
+    match result {
+        Ok(item) => HttpResponse::Created().json(item),
+        Err(e) => itinerary_error_response(e),
+    }
+}
+
+/// Get one itinerary item
+#[utoipa::path(
+    get,
+    path = "/api/travelplan/{plan_id}/itinerary/items/{item_id}",
+    params(
+        ("plan_id" = String, Path, description = "Travel plan ID"),
+        ("item_id" = String, Path, description = "Itinerary item ID")
+    ),
+    responses(
+        (status = 200, description = "The itinerary item", body = ItineraryItem),
+        (status = 403, description = "Unauthorized access", body = ErrorResponse),
+        (status = 404, description = "Travel plan or item not found", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
+    ),
+    security(
+        ("Bearer" = [])
+    ),
+    tag = "itinerary"
+)]
+pub async fn get_itinerary_item(
+    repos: web::Data<dyn Repositories>,
+    auth_user: AuthenticatedUser,
+    path: web::Path<(String, String)>,
+) -> impl Responder {
+    let (plan_id, item_id) = path.into_inner();
+
+    let user_id = auth_user.user_id.clone();
+    let service = ItineraryService::new(repos.into_inner());
+    let result = blocking::run(move || service.get_item(&plan_id, &item_id, &user_id)).await;
+
+    match result {
+        Ok(item) => HttpResponse::Ok().json(item),
+        Err(e) => itinerary_error_response(e),
+    }
+}
+
+/// Patch an itinerary item
+///
+/// Applies a JSON Merge Patch (RFC 7396). Moving the item to another day
+/// puts it last on that day.
+#[utoipa::path(
+    patch,
+    path = "/api/travelplan/{plan_id}/itinerary/items/{item_id}",
+    params(
+        ("plan_id" = String, Path, description = "Travel plan ID"),
+        ("item_id" = String, Path, description = "Itinerary item ID")
+    ),
+    request_body(content = PatchItineraryItem, content_type = "application/merge-patch+json"),
+    responses(
+        (status = 200, description = "Item updated", body = ItineraryItem),
+        (status = 400, description = "Invalid patch", body = ErrorResponse),
+        (status = 403, description = "Unauthorized access", body = ErrorResponse),
+        (status = 404, description = "Travel plan or item not found", body = ErrorResponse),
+        (status = 409, description = "The plan has no start or end date", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
+    ),
+    security(
+        ("Bearer" = [])
+    ),
+    tag = "itinerary"
+)]
+pub async fn patch_itinerary_item(
+    repos: web::Data<dyn Repositories>,
+    auth_user: AuthenticatedUser,
+    path: web::Path<(String, String)>,
+    patch: web::Json<PatchItineraryItem>,
+) -> impl Responder {
+    let (plan_id, item_id) = path.into_inner();
+
+    let patch = patch.into_inner();
+    let user_id = auth_user.user_id.clone();
+    let service = ItineraryService::new(repos.into_inner());
+    let result =
+        blocking::run(move || service.patch_item(&plan_id, &item_id, &patch, &user_id)).await;
+
+    match result {
+        Ok(item) => HttpResponse::Ok().json(item),
+        Err(e) => itinerary_error_response(e),
+    }
+}
+
+/// Remove an itinerary item
+#[utoipa::path(
+    delete,
+    path = "/api/travelplan/{plan_id}/itinerary/items/{item_id}",
+    params(
+        ("plan_id" = String, Path, description = "Travel plan ID"),
+        ("item_id" = String, Path, description = "Itinerary item ID")
+    ),
+    responses(
+        (status = 204, description = "Item removed"),
+        (status = 403, description = "Unauthorized access", body = ErrorResponse),
+        (status = 404, description = "Travel plan or item not found", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
+    ),
+    security(
+        ("Bearer" = [])
+    ),
+    tag = "itinerary"
+)]
+pub async fn delete_itinerary_item(
+    repos: web::Data<dyn Repositories>,
+    auth_user: AuthenticatedUser,
+    path: web::Path<(String, String)>,
+) -> impl Responder {
+    let (plan_id, item_id) = path.into_inner();
+
+    let user_id = auth_user.user_id.clone();
+    let service = ItineraryService::new(repos.into_inner());
+    let result = blocking::run(move || service.delete_item(&plan_id, &item_id, &user_id)).await;
+
+    match result {
+        Ok(()) => HttpResponse::NoContent().finish(),
+        Err(e) => itinerary_error_response(e),
+    }
+}
+
+/// Reorder the items on one day of the itinerary
+#[utoipa::path(
+    put,
+    path = "/api/travelplan/{plan_id}/itinerary/days/{date}/order",
+    params(
+        ("plan_id" = String, Path, description = "Travel plan ID"),
+        ("date" = NaiveDate, Path, description = "The day, as YYYY-MM-DD")
+    ),
+    request_body = ReorderItineraryDay,
+    responses(
+        (status = 200, description = "The day in its new order", body = ItineraryDay),
+        (status = 400, description = "The ids don't match the day's items", body = ErrorResponse),
+        (status = 403, description = "Unauthorized access", body = ErrorResponse),
+        (status = 404, description = "Travel plan not found", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
+    ),
+    security(
+        ("Bearer" = [])
+    ),
+    tag = "itinerary"
+)]
+pub async fn reorder_itinerary_day(
+    repos: web::Data<dyn Repositories>,
+    auth_user: AuthenticatedUser,
+    path: web::Path<(String, NaiveDate)>,
+    order: web::Json<ReorderItineraryDay>,
+) -> impl Responder {
+    let (plan_id, date) = path.into_inner();
+
+    let item_ids = order.into_inner().item_ids;
+    let user_id = auth_user.user_id.clone();
+    let service = ItineraryService::new(repos.into_inner());
+    let result =
+        blocking::run(move || service.reorder_day(&plan_id, date, &item_ids, &user_id)).await;
+
+    match result {
+        Ok(day) => HttpResponse::Ok().json(day),
+        Err(e) => itinerary_error_response(e),
+    }
+}
+
+/// Propose a schedule for travelling a route
+///
+/// Spreads the route over the plan's days, capping each day's travel and
+/// keeping it to daylight. Nights are spent at hotels on the route where
+/// possible and the other points of interest are visited as they are passed.
+/// With `apply` set the proposed items are added to the itinerary.
+#[utoipa::path(
+    post,
+    path = "/api/travelplan/{id}/itinerary/schedule",
+    params(
+        ("id" = String, Path, description = "Travel plan ID")
+    ),
+    request_body = ScheduleRequest,
+    responses(
+        (status = 200, description = "The proposed schedule", body = ScheduleProposal),
+        (status = 201, description = "Schedule added to the itinerary", body = ScheduleProposal),
+        (status = 400, description = "Invalid request or route", body = ErrorResponse),
+        (status = 403, description = "Unauthorized access", body = ErrorResponse),
+        (status = 404, description = "Travel plan not found", body = ErrorResponse),
+        (status = 409, description = "The plan has no start or end date", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
+    ),
+    security(
+        ("Bearer" = [])
+    ),
+    tag = "itinerary"
+)]
+pub async fn schedule_itinerary(
+    repos: web::Data<dyn Repositories>,
+    auth_user: AuthenticatedUser,
+    path: web::Path<String>,
+    request: web::Json<ScheduleRequest>,
+) -> impl Responder {
+    let plan_id = path.into_inner();
+
+    let request = request.into_inner();
+    let user_id = auth_user.user_id.clone();
+    let service = ItineraryService::new(repos.into_inner());
+    let result =
+        blocking::run(move || service.propose_schedule(&plan_id, &request, &user_id)).await;
+
+    match result {
+        Ok(proposal) if proposal.applied => HttpResponse::Created().json(proposal),
+        Ok(proposal) => HttpResponse::Ok().json(proposal),
+        Err(e) => itinerary_error_response(e),
+    }
+}
@@ -1,3 +1,110 @@
+pub mod admin;
 pub mod auth;
 pub mod travel_plan;
-pub mod route_option;
\ No newline at end of file
+pub mod route_option;
+pub mod route_vote;
+pub mod plan_member;
+pub mod share_link;
+pub mod comment;
+pub mod activity;
+pub mod plan_revision;
+pub mod itinerary;
+pub mod plan_leg;
+pub mod budget;
+pub mod emissions;
+
+use actix_web::web;
+
+/// Registers the `/api` routes. Shared by the server and the integration
+/// tests so both exercise the same route table.
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.service(
+        web::scope("/api")
+            .route("/register", web::post().to(auth::register))
+            .route("/login", web::post().to(auth::login))
+
+            .route("/travelplan", web::get().to(travel_plan::get_travel_plans))
+            .route("/travelplan", web::post().to(travel_plan::create_travel_plan))
+            .route("/travelplan/{id}", web::get().to(travel_plan::get_travel_plan_by_id))
+            .route("/travelplan/{id}", web::put().to(travel_plan::update_travel_plan))
+            .route("/travelplan/{id}", web::patch().to(travel_plan::patch_travel_plan))
+            .route("/travelplan/{id}", web::delete().to(travel_plan::delete_travel_plan))
+            .route("/travelplan/{id}/activity", web::get().to(activity::get_plan_activity))
+            .route("/travelplan/{id}/revisions", web::get().to(plan_revision::get_revisions))
+            .route("/travelplan/{id}/revisions/diff", web::get().to(plan_revision::diff_revisions))
+            .route("/travelplan/{plan_id}/revisions/{revision}", web::get().to(plan_revision::get_revision))
+            .route("/travelplan/{plan_id}/revisions/{revision}/restore", web::post().to(plan_revision::restore_revision))
+
+            .route("/travelplan/{id}/routes", web::get().to(route_option::get_route_options))
+            .route("/travelplan/{id}/routes", web::delete().to(route_option::delete_all_route_options))
+            .route("/travelplan/{id}/routes/generate", web::post().to(route_option::generate_route_options))
+            .route("/travelplan/{id}/routes/compare", web::get().to(route_option::compare_route_options))
+            .route("/travelplan/{plan_id}/routes/{route_id}", web::get().to(route_option::get_route_option_by_id))
+            .route("/travelplan/{plan_id}/routes/{route_id}", web::patch().to(route_option::patch_route_option))
+            .route("/travelplan/{plan_id}/routes/{route_id}", web::delete().to(route_option::delete_route_option))
+            .route("/travelplan/{plan_id}/routes/{route_id}/optimize", web::post().to(route_option::optimize_poi_order))
+            .route("/travelplan/{plan_id}/routes/{route_id}/pois/{poi_id}", web::patch().to(route_option::patch_point_of_interest))
+            .route("/travelplan/{plan_id}/routes/{route_id}/vote", web::put().to(route_vote::cast_vote))
+            .route("/travelplan/{plan_id}/routes/{route_id}/vote", web::delete().to(route_vote::retract_vote))
+            .route("/travelplan/{id}/votes", web::get().to(route_vote::get_vote_tally))
+            .route("/travelplan/{id}/selected-route", web::put().to(route_vote::select_route))
+
+            .route("/travelplan/{id}/members", web::get().to(plan_member::get_members))
+            .route("/travelplan/{id}/members", web::post().to(plan_member::invite_member))
+            .route("/travelplan/{id}/members/accept", web::post().to(plan_member::accept_invitation))
+            .route("/travelplan/{plan_id}/members/{user_id}", web::delete().to(plan_member::remove_member))
+            .route("/invitations", web::get().to(plan_member::get_invitations))
+
+            .route("/travelplan/{id}/share-links", web::get().to(share_link::get_share_links))
+            .route("/travelplan/{id}/share-links", web::post().to(share_link::create_share_link))
+            .route("/travelplan/{plan_id}/share-links/{link_id}", web::delete().to(share_link::revoke_share_link))
+            .route("/shared/{token}", web::get().to(share_link::get_shared_plan))
+
+            .route("/travelplan/{id}/comments", web::get().to(comment::get_plan_comments))
+            .route("/travelplan/{id}/comments", web::post().to(comment::create_plan_comment))
+            .route("/travelplan/{plan_id}/comments/{comment_id}", web::put().to(comment::update_comment))
+            .route("/travelplan/{plan_id}/comments/{comment_id}", web::delete().to(comment::delete_comment))
+            .route("/travelplan/{plan_id}/routes/{route_id}/comments", web::get().to(comment::get_route_comments))
+            .route("/travelplan/{plan_id}/routes/{route_id}/comments", web::post().to(comment::create_route_comment))
+            .route("/travelplan/{plan_id}/routes/{route_id}/pois/{poi_id}/comments", web::get().to(comment::get_poi_comments))
+            .route("/travelplan/{plan_id}/routes/{route_id}/pois/{poi_id}/comments", web::post().to(comment::create_poi_comment))
+
+            .route("/travelplan/{id}/itinerary", web::get().to(itinerary::get_itinerary))
+            .route("/travelplan/{id}/itinerary/items", web::post().to(itinerary::add_itinerary_item))
+            .route("/travelplan/{plan_id}/itinerary/items/{item_id}", web::get().to(itinerary::get_itinerary_item))
+            .route("/travelplan/{plan_id}/itinerary/items/{item_id}", web::patch().to(itinerary::patch_itinerary_item))
+            .route("/travelplan/{plan_id}/itinerary/items/{item_id}", web::delete().to(itinerary::delete_itinerary_item))
+            .route("/travelplan/{plan_id}/itinerary/days/{date}/order", web::put().to(itinerary::reorder_itinerary_day))
+            .route("/travelplan/{id}/itinerary/schedule", web::post().to(itinerary::schedule_itinerary))
+
+            .route("/travelplan/{id}/legs", web::get().to(plan_leg::get_legs))
+            .route("/travelplan/{id}/legs", web::post().to(plan_leg::add_leg))
+            .route("/travelplan/{id}/legs/order", web::put().to(plan_leg::reorder_legs))
+            .route("/travelplan/{plan_id}/legs/{leg_id}", web::get().to(plan_leg::get_leg))
+            .route("/travelplan/{plan_id}/legs/{leg_id}", web::patch().to(plan_leg::patch_leg))
+            .route("/travelplan/{plan_id}/legs/{leg_id}", web::delete().to(plan_leg::delete_leg))
+            .route("/travelplan/{plan_id}/legs/{leg_id}/routes", web::get().to(route_option::get_leg_route_options))
+            .route("/travelplan/{plan_id}/legs/{leg_id}/routes/generate", web::post().to(route_option::generate_leg_route_options))
+            .route("/travelplan/{id}/summary", web::get().to(plan_leg::get_plan_summary))
+
+            .route("/travelplan/{id}/budgets", web::get().to(budget::get_budgets))
+            .route("/travelplan/{plan_id}/budgets/{category}", web::put().to(budget::set_budget))
+            .route("/travelplan/{plan_id}/budgets/{category}", web::delete().to(budget::delete_budget))
+            .route("/travelplan/{id}/expenses", web::get().to(budget::get_expenses))
+            .route("/travelplan/{id}/expenses", web::post().to(budget::add_expense))
+            .route("/travelplan/{plan_id}/expenses/{expense_id}", web::get().to(budget::get_expense))
+            .route("/travelplan/{plan_id}/expenses/{expense_id}", web::patch().to(budget::patch_expense))
+            .route("/travelplan/{plan_id}/expenses/{expense_id}", web::delete().to(budget::delete_expense))
+            .route("/travelplan/{id}/budget-summary", web::get().to(budget::get_budget_summary))
+
+            .route("/emissions", web::get().to(emissions::get_emissions_report))
+
+            .route("/admin/backups", web::get().to(admin::list_backups))
+            .route("/admin/backups", web::post().to(admin::create_backup))
+            .route("/admin/users", web::get().to(admin::list_users))
+            .route("/admin/users/{username}/lock", web::post().to(admin::lock_user))
+            .route("/admin/users/{username}/unlock", web::post().to(admin::unlock_user))
+            .route("/admin/users/{username}/role", web::put().to(admin::set_user_role))
+            .route("/admin/audit", web::get().to(admin::get_audit_log))
+    );
+}
@@ -0,0 +1,325 @@
+use actix_web::{HttpResponse, Responder, web};
+use log::info;
+
+use crate::middleware::auth::AuthenticatedUser;
+use crate::models::plan_leg::{NewPlanLeg, PatchPlanLeg, ReorderPlanLegs};
+use crate::repositories::Repositories;
+use crate::routes::route_option::ErrorResponse;
+use crate::services::blocking;
+use crate::services::plan_leg_service::{PlanLegError, PlanLegService};
+use crate::services::travel_plan_service::TravelPlanError;
+
+fn plan_leg_error_response(error: PlanLegError) -> HttpResponse {
+    match error {
+        PlanLegError::TravelPlanError(TravelPlanError::NotFound) => {
+            HttpResponse::NotFound().json(ErrorResponse {
+                error: "Travel plan not found".to_string(),
+            })
+        }
+        PlanLegError::TravelPlanError(TravelPlanError::Unauthorized) => {
+            HttpResponse::Forbidden().json(ErrorResponse {
+                error: "You don't have permission to access this travel plan".to_string(),
+            })
+        }
+        PlanLegError::TravelPlanError(TravelPlanError::PreconditionFailed) => {
+            HttpResponse::PreconditionFailed().json(ErrorResponse {
+                error: "The travel plan has changed since it was read".to_string(),
+            })
+        }
+        PlanLegError::LegNotFound => HttpResponse::NotFound().json(ErrorResponse {
+            error: "Leg not found".to_string(),
+        }),
+        PlanLegError::InvalidLeg(message) => {
+            HttpResponse::BadRequest().json(ErrorResponse { error: message })
+        }
+        PlanLegError::RouteLocked => HttpResponse::Conflict().json(ErrorResponse {
+            error: "One of the leg's routes is the plan's selected route".to_string(),
+        }),
+        PlanLegError::TravelPlanError(TravelPlanError::DatabaseError(e))
+        | PlanLegError::DatabaseError(e) => {
+            HttpResponse::InternalServerError().json(ErrorResponse {
+                error: format!("Database error: {}", e),
+            })
+        }
+    }
+}
+
+/// List a travel plan's legs
+#[utoipa::path(
+    get,
+    path = "/api/travelplan/{id}/legs",
+    params(
+        ("id" = String, Path, description = "Travel plan ID")
+    ),
+    responses(
+        (status = 200, description = "The plan's legs in order", body = [PlanLeg]),
+        (status = 403, description = "Unauthorized access", body = ErrorResponse),
+        (status = 404, description = "Travel plan not found", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
+    ),
+    security(
+        ("Bearer" = [])
+    ),
+    tag = "legs"
+)]
+pub async fn get_legs(
+    repos: web::Data<dyn Repositories>,
+    auth_user: AuthenticatedUser,
+    path: web::Path<String>,
+) -> impl Responder {
+    let plan_id = path.into_inner();
+
+    let user_id = auth_user.user_id.clone();
+    let service = PlanLegService::new(repos.into_inner());
+    let result = blocking::run(move || service.get_legs(&plan_id, &user_id)).await;
+
+    match result {
+        Ok(legs) => HttpResponse::Ok().json(legs),
+        Err(e) => plan_leg_error_response(e),
+    }
+}
+
+/// Add a leg to a travel plan
+///
+/// The leg goes after the plan's last one. Its dates must fall within the
+/// plan's.
+#[utoipa::path(
+    post,
+    path = "/api/travelplan/{id}/legs",
+    params(
+        ("id" = String, Path, description = "Travel plan ID")
+    ),
+    request_body = NewPlanLeg,
+    responses(
+        (status = 201, description = "Leg added", body = PlanLeg),
+        (status = 400, description = "Invalid leg", body = ErrorResponse),
+        (status = 403, description = "Unauthorized access", body = ErrorResponse),
+        (status = 404, description = "Travel plan not found", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
+    ),
+    security(
+        ("Bearer" = [])
+    ),
+    tag = "legs"
+)]
+pub async fn add_leg(
+    repos: web::Data<dyn Repositories>,
+    auth_user: AuthenticatedUser,
+    path: web::Path<String>,
+    leg_data: web::Json<NewPlanLeg>,
+) -> impl Responder {
+    let plan_id = path.into_inner();
+    info!("User {} is adding a leg to travel plan {}", auth_user.username, plan_id);
+
+    let new_leg = leg_data.into_inner();
+    let user_id = auth_user.user_id.clone();
+    let service = PlanLegService::new(repos.into_inner());
+    let result = blocking::run(move || service.add_leg(&plan_id, &new_leg, &user_id)).await;
+
+    match result {
+        Ok(leg) => HttpResponse::Created().json(leg),
+        Err(e) => plan_leg_error_response(e),
+    }
+}
+
+/// Get one leg of a travel plan
+#[utoipa::path(
+    get,
+    path = "/api/travelplan/{plan_id}/legs/{leg_id}",
+    params(
+        ("plan_id" = String, Path, description = "Travel plan ID"),
+        ("leg_id" = String, Path, description = "Leg ID")
+    ),
+    responses(
+        (status = 200, description = "The leg", body = PlanLeg),
+        (status = 403, description = "Unauthorized access", body = ErrorResponse),
+        (status = 404, description = "Travel plan or leg not found", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
+    ),
+    security(
+        ("Bearer" = [])
+    ),
+    tag = "legs"
+)]
+pub async fn get_leg(
+    repos: web::Data<dyn Repositories>,
+    auth_user: AuthenticatedUser,
+    path: web::Path<(String, String)>,
+) -> impl Responder {
+    let (plan_id, leg_id) = path.into_inner();
+
+    let user_id = auth_user.user_id.clone();
+    let service = PlanLegService::new(repos.into_inner());
+    let result = blocking::run(move || service.get_leg(&plan_id, &leg_id, &user_id)).await;
+
+    match result {
+        Ok(leg) => HttpResponse::Ok().json(leg),
+        Err(e) => plan_leg_error_response(e),
+    }
+}
+
+/// Patch a leg of a travel plan
+///
+/// Applies a JSON Merge Patch (RFC 7396). `selectedRouteId` picks one of the
+/// leg's own route options, or clears the choice with `null`.
+#[utoipa::path(
+    patch,
+    path = "/api/travelplan/{plan_id}/legs/{leg_id}",
+    params(
+        ("plan_id" = String, Path, description = "Travel plan ID"),
+        ("leg_id" = String, Path, description = "Leg ID")
+    ),
+    request_body(content = PatchPlanLeg, content_type = "application/merge-patch+json"),
+    responses(
+        (status = 200, description = "Leg updated", body = PlanLeg),
+        (status = 400, description = "Invalid patch", body = ErrorResponse),
+        (status = 403, description = "Unauthorized access", body = ErrorResponse),
+        (status = 404, description = "Travel plan or leg not found", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
+    ),
+    security(
+        ("Bearer" = [])
+    ),
+    tag = "legs"
+)]
+pub async fn patch_leg(
+    repos: web::Data<dyn Repositories>,
+    auth_user: AuthenticatedUser,
+    path: web::Path<(String, String)>,
+    patch: web::Json<PatchPlanLeg>,
+) -> impl Responder {
+    let (plan_id, leg_id) = path.into_inner();
+
+    let patch = patch.into_inner();
+    let user_id = auth_user.user_id.clone();
+    let service = PlanLegService::new(repos.into_inner());
+    let result =
+        blocking::run(move || service.patch_leg(&plan_id, &leg_id, &patch, &user_id)).await;
+
+    match result {
+        Ok(leg) => HttpResponse::Ok().json(leg),
+        Err(e) => plan_leg_error_response(e),
+    }
+}
+
+/// Remove a leg from a travel plan
+///
+/// The leg's route options go with it, unless one of them is the plan's
+/// selected route.
+#[utoipa::path(
+    delete,
+    path = "/api/travelplan/{plan_id}/legs/{leg_id}",
+    params(
+        ("plan_id" = String, Path, description = "Travel plan ID"),
+        ("leg_id" = String, Path, description = "Leg ID")
+    ),
+    responses(
+        (status = 204, description = "Leg removed"),
+        (status = 403, description = "Unauthorized access", body = ErrorResponse),
+        (status = 404, description = "Travel plan or leg not found", body = ErrorResponse),
+        (status = 409, description = "A route of the leg is selected", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
+    ),
+    security(
+        ("Bearer" = [])
+    ),
+    tag = "legs"
+)]
+pub async fn delete_leg(
+    repos: web::Data<dyn Repositories>,
+    auth_user: AuthenticatedUser,
+    path: web::Path<(String, String)>,
+) -> impl Responder {
+    let (plan_id, leg_id) = path.into_inner();
+    info!(
+        "User {} is removing leg {} from travel plan {}",
+        auth_user.username, leg_id, plan_id
+    );
+
+    let user_id = auth_user.user_id.clone();
+    let service = PlanLegService::new(repos.into_inner());
+    let result = blocking::run(move || service.delete_leg(&plan_id, &leg_id, &user_id)).await;
+
+    match result {
+        Ok(()) => HttpResponse::NoContent().finish(),
+        Err(e) => plan_leg_error_response(e),
+    }
+}
+
+/// Reorder a travel plan's legs
+#[utoipa::path(
+    put,
+    path = "/api/travelplan/{id}/legs/order",
+    params(
+        ("id" = String, Path, description = "Travel plan ID")
+    ),
+    request_body = ReorderPlanLegs,
+    responses(
+        (status = 200, description = "The legs in their new order", body = [PlanLeg]),
+        (status = 400, description = "The ids don't match the plan's legs", body = ErrorResponse),
+        (status = 403, description = "Unauthorized access", body = ErrorResponse),
+        (status = 404, description = "Travel plan not found", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
+    ),
+    security(
+        ("Bearer" = [])
+    ),
+    tag = "legs"
+)]
+pub async fn reorder_legs(
+    repos: web::Data<dyn Repositories>,
+    auth_user: AuthenticatedUser,
+    path: web::Path<String>,
+    order: web::Json<ReorderPlanLegs>,
+) -> impl Responder {
+    let plan_id = path.into_inner();
+
+    let leg_ids = order.into_inner().leg_ids;
+    let user_id = auth_user.user_id.clone();
+    let service = PlanLegService::new(repos.into_inner());
+    let result = blocking::run(move || service.reorder_legs(&plan_id, &leg_ids, &user_id)).await;
+
+    match result {
+        Ok(legs) => HttpResponse::Ok().json(legs),
+        Err(e) => plan_leg_error_response(e),
+    }
+}
+
+/// Summarise a travel plan's legs
+///
+/// Totals the distance and duration of each leg's selected route, or of its
+/// fastest route while none is selected. A plan without legs is summarised
+/// as a single leg over its own routes.
+#[utoipa::path(
+    get,
+    path = "/api/travelplan/{id}/summary",
+    params(
+        ("id" = String, Path, description = "Travel plan ID")
+    ),
+    responses(
+        (status = 200, description = "The plan's legs and totals", body = PlanSummary),
+        (status = 403, description = "Unauthorized access", body = ErrorResponse),
+        (status = 404, description = "Travel plan not found", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
+    ),
+    security(
+        ("Bearer" = [])
+    ),
+    tag = "legs"
+)]
+pub async fn get_plan_summary(
+    repos: web::Data<dyn Repositories>,
+    auth_user: AuthenticatedUser,
+    path: web::Path<String>,
+) -> impl Responder {
+    let plan_id = path.into_inner();
+
+    let user_id = auth_user.user_id.clone();
+    let service = PlanLegService::new(repos.into_inner());
+    let result = blocking::run(move || service.summary(&plan_id, &user_id)).await;
+
+    match result {
+        Ok(summary) => HttpResponse::Ok().json(summary),
+        Err(e) => plan_leg_error_response(e),
+    }
+}
@@ -0,0 +1,236 @@
+use actix_web::{HttpResponse, Responder, web};
+use log::info;
+
+use crate::middleware::auth::AuthenticatedUser;
+use crate::repositories::Repositories;
+use crate::routes::route_option::ErrorResponse;
+use crate::services::blocking;
+use crate::services::plan_sharing_service::{InviteMember, PlanSharingService, SharingError};
+use crate::services::travel_plan_service::TravelPlanError;
+
+fn sharing_error_response(error: SharingError) -> HttpResponse {
+    match error {
+        SharingError::TravelPlanError(TravelPlanError::NotFound) => {
+            HttpResponse::NotFound().json(ErrorResponse {
+                error: "Travel plan not found".to_string(),
+            })
+        }
+        SharingError::TravelPlanError(TravelPlanError::Unauthorized) => {
+            HttpResponse::Forbidden().json(ErrorResponse {
+                error: "You don't have permission to manage who this travel plan is shared with"
+                    .to_string(),
+            })
+        }
+        SharingError::UserNotFound => HttpResponse::NotFound().json(ErrorResponse {
+            error: "User not found".to_string(),
+        }),
+        SharingError::InvitationNotFound => HttpResponse::NotFound().json(ErrorResponse {
+            error: "Invitation not found".to_string(),
+        }),
+        SharingError::MemberNotFound => HttpResponse::NotFound().json(ErrorResponse {
+            error: "Member not found".to_string(),
+        }),
+        SharingError::AlreadyMember => HttpResponse::Conflict().json(ErrorResponse {
+            error: "User already has access to this travel plan".to_string(),
+        }),
+        SharingError::InvalidInput(e) => HttpResponse::BadRequest().json(ErrorResponse { error: e }),
+        SharingError::TravelPlanError(TravelPlanError::PreconditionFailed) => {
+            HttpResponse::PreconditionFailed().json(ErrorResponse {
+                error: "The travel plan has changed since it was read".to_string(),
+            })
+        }
+        SharingError::TravelPlanError(TravelPlanError::DatabaseError(e))
+        | SharingError::DatabaseError(e) => {
+            HttpResponse::InternalServerError().json(ErrorResponse {
+                error: format!("Database error: {}", e),
+            })
+        }
+    }
+}
+
+/// List who a travel plan is shared with
+///
+/// Includes pending invitations. Any member can see the list.
+#[utoipa::path(
+    get,
+    path = "/api/travelplan/{id}/members",
+    params(
+        ("id" = String, Path, description = "Travel plan ID")
+    ),
+    responses(
+        (status = 200, description = "Members retrieved successfully", body = [PlanMemberDto]),
+        (status = 403, description = "Unauthorized access", body = ErrorResponse),
+        (status = 404, description = "Travel plan not found", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
+    ),
+    security(
+        ("Bearer" = [])
+    ),
+    tag = "sharing"
+)]
+pub async fn get_members(
+    repos: web::Data<dyn Repositories>,
+    auth_user: AuthenticatedUser,
+    path: web::Path<String>,
+) -> impl Responder {
+    let plan_id = path.into_inner();
+    let user_id = auth_user.user_id.clone();
+    let service = PlanSharingService::new(repos.into_inner());
+    let result = blocking::run(move || service.list_members(&plan_id, &user_id)).await;
+
+    match result {
+        Ok(members) => HttpResponse::Ok().json(members),
+        Err(e) => sharing_error_response(e),
+    }
+}
+
+/// Invite a user to a travel plan
+///
+/// Owners invite by username or email. The invitation grants access once the
+/// invited user accepts it.
+#[utoipa::path(
+    post,
+    path = "/api/travelplan/{id}/members",
+    params(
+        ("id" = String, Path, description = "Travel plan ID")
+    ),
+    request_body = InviteMember,
+    responses(
+        (status = 201, description = "Invitation created", body = PlanMemberDto),
+        (status = 400, description = "Invalid invitation", body = ErrorResponse),
+        (status = 403, description = "Only owners can share a plan", body = ErrorResponse),
+        (status = 404, description = "Travel plan or user not found", body = ErrorResponse),
+        (status = 409, description = "User already invited", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
+    ),
+    security(
+        ("Bearer" = [])
+    ),
+    tag = "sharing"
+)]
+pub async fn invite_member(
+    repos: web::Data<dyn Repositories>,
+    auth_user: AuthenticatedUser,
+    path: web::Path<String>,
+    invite: web::Json<InviteMember>,
+) -> impl Responder {
+    let plan_id = path.into_inner();
+    info!(
+        "User {} is sharing travel plan {}",
+        auth_user.username, plan_id
+    );
+
+    let invite = invite.into_inner();
+    let user_id = auth_user.user_id.clone();
+    let service = PlanSharingService::new(repos.into_inner());
+    let result = blocking::run(move || service.invite(&plan_id, &user_id, &invite)).await;
+
+    match result {
+        Ok(member) => HttpResponse::Created().json(member),
+        Err(e) => sharing_error_response(e),
+    }
+}
+
+/// Accept an invitation to a travel plan
+#[utoipa::path(
+    post,
+    path = "/api/travelplan/{id}/members/accept",
+    params(
+        ("id" = String, Path, description = "Travel plan ID")
+    ),
+    responses(
+        (status = 200, description = "Invitation accepted", body = PlanMemberDto),
+        (status = 404, description = "Invitation not found", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
+    ),
+    security(
+        ("Bearer" = [])
+    ),
+    tag = "sharing"
+)]
+pub async fn accept_invitation(
+    repos: web::Data<dyn Repositories>,
+    auth_user: AuthenticatedUser,
+    path: web::Path<String>,
+) -> impl Responder {
+    let plan_id = path.into_inner();
+    let user_id = auth_user.user_id.clone();
+    let service = PlanSharingService::new(repos.into_inner());
+    let result = blocking::run(move || service.accept(&plan_id, &user_id)).await;
+
+    match result {
+        Ok(member) => HttpResponse::Ok().json(member),
+        Err(e) => sharing_error_response(e),
+    }
+}
+
+/// Revoke a member's access
+///
+/// Owners can remove anyone; members can remove themselves to leave a plan
+/// or decline an invitation.
+#[utoipa::path(
+    delete,
+    path = "/api/travelplan/{id}/members/{user_id}",
+    params(
+        ("id" = String, Path, description = "Travel plan ID"),
+        ("user_id" = String, Path, description = "ID of the member to remove")
+    ),
+    responses(
+        (status = 204, description = "Access revoked"),
+        (status = 403, description = "Only owners can remove other members", body = ErrorResponse),
+        (status = 404, description = "Travel plan or member not found", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
+    ),
+    security(
+        ("Bearer" = [])
+    ),
+    tag = "sharing"
+)]
+pub async fn remove_member(
+    repos: web::Data<dyn Repositories>,
+    auth_user: AuthenticatedUser,
+    path: web::Path<(String, String)>,
+) -> impl Responder {
+    let (plan_id, member_id) = path.into_inner();
+    info!(
+        "User {} is removing {} from travel plan {}",
+        auth_user.username, member_id, plan_id
+    );
+
+    let user_id = auth_user.user_id.clone();
+    let service = PlanSharingService::new(repos.into_inner());
+    let result =
+        blocking::run(move || service.remove_member(&plan_id, &user_id, &member_id)).await;
+
+    match result {
+        Ok(()) => HttpResponse::NoContent().finish(),
+        Err(e) => sharing_error_response(e),
+    }
+}
+
+/// List the caller's pending invitations
+#[utoipa::path(
+    get,
+    path = "/api/invitations",
+    responses(
+        (status = 200, description = "Pending invitations", body = [InvitationDto]),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
+    ),
+    security(
+        ("Bearer" = [])
+    ),
+    tag = "sharing"
+)]
+pub async fn get_invitations(
+    repos: web::Data<dyn Repositories>,
+    auth_user: AuthenticatedUser,
+) -> impl Responder {
+    let user_id = auth_user.user_id.clone();
+    let service = PlanSharingService::new(repos.into_inner());
+    let result = blocking::run(move || service.pending_invitations(&user_id)).await;
+
+    match result {
+        Ok(invitations) => HttpResponse::Ok().json(invitations),
+        Err(e) => sharing_error_response(e),
+    }
+}
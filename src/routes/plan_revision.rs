@@ -0,0 +1,205 @@
+use actix_web::{HttpResponse, Responder, web};
+use log::info;
+use serde::Deserialize;
+use utoipa::IntoParams;
+
+use crate::middleware::auth::AuthenticatedUser;
+use crate::repositories::Repositories;
+use crate::routes::route_option::ErrorResponse;
+use crate::services::blocking;
+use crate::services::plan_revision_service::{PlanRevisionError, PlanRevisionService};
+use crate::services::travel_plan_service::TravelPlanError;
+
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct RevisionDiffQuery {
+    /// Revision to compare from
+    pub from: i64,
+    /// Revision to compare to; the plan's current state when omitted
+    pub to: Option<i64>,
+}
+
+fn revision_error_response(error: PlanRevisionError) -> HttpResponse {
+    match error {
+        PlanRevisionError::TravelPlanError(TravelPlanError::NotFound) => {
+            HttpResponse::NotFound().json(ErrorResponse {
+                error: "Travel plan not found".to_string(),
+            })
+        }
+        PlanRevisionError::TravelPlanError(TravelPlanError::Unauthorized) => {
+            HttpResponse::Forbidden().json(ErrorResponse {
+                error: "You don't have permission to access this travel plan".to_string(),
+            })
+        }
+        PlanRevisionError::RevisionNotFound => HttpResponse::NotFound().json(ErrorResponse {
+            error: "Revision not found".to_string(),
+        }),
+        PlanRevisionError::TravelPlanError(TravelPlanError::PreconditionFailed) => {
+            HttpResponse::PreconditionFailed().json(ErrorResponse {
+                error: "The travel plan has changed since it was read".to_string(),
+            })
+        }
+        PlanRevisionError::TravelPlanError(TravelPlanError::DatabaseError(e))
+        | PlanRevisionError::DatabaseError(e) => {
+            HttpResponse::InternalServerError().json(ErrorResponse {
+                error: format!("Database error: {}", e),
+            })
+        }
+    }
+}
+
+/// List the revisions of a travel plan
+///
+/// Every update that changes the plan keeps the state it replaced as a
+/// numbered revision.
+#[utoipa::path(
+    get,
+    path = "/api/travelplan/{id}/revisions",
+    params(
+        ("id" = String, Path, description = "Travel plan ID")
+    ),
+    responses(
+        (status = 200, description = "Stored revisions, newest first", body = RevisionHistory),
+        (status = 403, description = "Unauthorized access", body = ErrorResponse),
+        (status = 404, description = "Travel plan not found", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
+    ),
+    security(
+        ("Bearer" = [])
+    ),
+    tag = "travel_plans"
+)]
+pub async fn get_revisions(
+    repos: web::Data<dyn Repositories>,
+    auth_user: AuthenticatedUser,
+    path: web::Path<String>,
+) -> impl Responder {
+    let plan_id = path.into_inner();
+
+    let user_id = auth_user.user_id.clone();
+    let service = PlanRevisionService::new(repos.into_inner());
+    let result = blocking::run(move || service.list_revisions(&plan_id, &user_id)).await;
+
+    match result {
+        Ok(history) => HttpResponse::Ok().json(history),
+        Err(e) => revision_error_response(e),
+    }
+}
+
+/// Get one revision of a travel plan
+#[utoipa::path(
+    get,
+    path = "/api/travelplan/{plan_id}/revisions/{revision}",
+    params(
+        ("plan_id" = String, Path, description = "Travel plan ID"),
+        ("revision" = i64, Path, description = "Revision number")
+    ),
+    responses(
+        (status = 200, description = "The plan's fields as of the revision", body = PlanRevision),
+        (status = 403, description = "Unauthorized access", body = ErrorResponse),
+        (status = 404, description = "Travel plan or revision not found", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
+    ),
+    security(
+        ("Bearer" = [])
+    ),
+    tag = "travel_plans"
+)]
+pub async fn get_revision(
+    repos: web::Data<dyn Repositories>,
+    auth_user: AuthenticatedUser,
+    path: web::Path<(String, i64)>,
+) -> impl Responder {
+    let (plan_id, revision) = path.into_inner();
+
+    let user_id = auth_user.user_id.clone();
+    let service = PlanRevisionService::new(repos.into_inner());
+    let result = blocking::run(move || service.get_revision(&plan_id, revision, &user_id)).await;
+
+    match result {
+        Ok(revision) => HttpResponse::Ok().json(revision),
+        Err(e) => revision_error_response(e),
+    }
+}
+
+/// Compare two revisions of a travel plan
+#[utoipa::path(
+    get,
+    path = "/api/travelplan/{id}/revisions/diff",
+    params(
+        ("id" = String, Path, description = "Travel plan ID"),
+        RevisionDiffQuery
+    ),
+    responses(
+        (status = 200, description = "Fields that differ between the revisions", body = RevisionDiff),
+        (status = 403, description = "Unauthorized access", body = ErrorResponse),
+        (status = 404, description = "Travel plan or revision not found", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
+    ),
+    security(
+        ("Bearer" = [])
+    ),
+    tag = "travel_plans"
+)]
+pub async fn diff_revisions(
+    repos: web::Data<dyn Repositories>,
+    auth_user: AuthenticatedUser,
+    path: web::Path<String>,
+    query: web::Query<RevisionDiffQuery>,
+) -> impl Responder {
+    let plan_id = path.into_inner();
+    let RevisionDiffQuery { from, to } = query.into_inner();
+
+    let user_id = auth_user.user_id.clone();
+    let service = PlanRevisionService::new(repos.into_inner());
+    let result =
+        blocking::run(move || service.diff_revisions(&plan_id, &user_id, from, to)).await;
+
+    match result {
+        Ok(diff) => HttpResponse::Ok().json(diff),
+        Err(e) => revision_error_response(e),
+    }
+}
+
+/// Restore an earlier revision of a travel plan
+///
+/// The revision's fields replace the current ones, and the replaced state is
+/// kept as a new revision.
+#[utoipa::path(
+    post,
+    path = "/api/travelplan/{plan_id}/revisions/{revision}/restore",
+    params(
+        ("plan_id" = String, Path, description = "Travel plan ID"),
+        ("revision" = i64, Path, description = "Revision number")
+    ),
+    responses(
+        (status = 200, description = "The restored travel plan", body = TravelPlanDto),
+        (status = 403, description = "Unauthorized access", body = ErrorResponse),
+        (status = 404, description = "Travel plan or revision not found", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
+    ),
+    security(
+        ("Bearer" = [])
+    ),
+    tag = "travel_plans"
+)]
+pub async fn restore_revision(
+    repos: web::Data<dyn Repositories>,
+    auth_user: AuthenticatedUser,
+    path: web::Path<(String, i64)>,
+) -> impl Responder {
+    let (plan_id, revision) = path.into_inner();
+    info!(
+        "User {} is restoring revision {} of travel plan {}",
+        auth_user.username, revision, plan_id
+    );
+
+    let user_id = auth_user.user_id.clone();
+    let service = PlanRevisionService::new(repos.into_inner());
+    let result =
+        blocking::run(move || service.restore_revision(&plan_id, revision, &user_id)).await;
+
+    match result {
+        Ok(plan) => HttpResponse::Ok().json(plan),
+        Err(e) => revision_error_response(e),
+    }
+}
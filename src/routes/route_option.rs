@@ -1,11 +1,20 @@
+use actix_web::http::header;
 use actix_web::{HttpResponse, Responder, web};
 use log::info;
 use serde::{Deserialize, Serialize};
 use utoipa::ToSchema;
 
-use crate::db::connection::DbPool;
+use crate::app::AppState;
 use crate::middleware::auth::AuthenticatedUser;
-use crate::services::route_option_service::{RouteOptionError, RouteOptionService};
+use crate::middleware::conditional::{Preconditions, tagged_response};
+use crate::models::point_of_interest::PatchPointOfInterest;
+use crate::models::route_option::PatchRouteOption;
+use crate::repositories::Repositories;
+use crate::services::blocking;
+use crate::services::route_optimizer::OptimizeRequest;
+use crate::services::route_option_service::{
+    RouteOptionError, RouteOptionService, RouteSort, sort_routes,
+};
 use crate::services::travel_plan_service::TravelPlanError;
 
 #[derive(Debug, Serialize, ToSchema)]
@@ -19,11 +28,34 @@ pub struct GenerateOptionsQuery {
     pub count: Option<usize>,
 }
 
+#[derive(Debug, Deserialize)]
+pub struct RouteListQuery {
+    pub sort: Option<RouteSort>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CompareQuery {
+    pub ids: String,
+}
+
+/// A route option service that prices routes and estimates their emissions
+/// with the configured tables.
+fn route_service(
+    repos: web::Data<dyn Repositories>,
+    state: &AppState,
+) -> RouteOptionService<dyn Repositories> {
+    RouteOptionService::new(repos.into_inner())
+        .with_costs(state.route_costs.clone())
+        .with_emission_factors(state.emission_factors.clone())
+}
+
 #[utoipa::path(
     get,
     path = "/api/travelplan/{id}/routes",
     params(
-        ("id" = String, Path, description = "Travel plan ID")
+        ("id" = String, Path, description = "Travel plan ID"),
+        ("sort" = Option<RouteSort>, Query,
+            description = "Order by estimated cost, distance or duration, least first")
     ),
     responses(
         (status = 200, description = "List of route options retrieved successfully"),
@@ -37,9 +69,11 @@ pub struct GenerateOptionsQuery {
     tag = "route_options"
 )]
 pub async fn get_route_options(
-    pool: web::Data<DbPool>,
+    repos: web::Data<dyn Repositories>,
+    state: web::Data<AppState>,
     auth_user: AuthenticatedUser,
     path: web::Path<String>,
+    query: web::Query<RouteListQuery>,
 ) -> impl Responder {
     let plan_id = path.into_inner();
     info!(
@@ -47,16 +81,17 @@ pub async fn get_route_options(
         plan_id, auth_user.username
     );
 
-    let conn = match pool.get() {
-        Ok(conn) => conn,
-        Err(e) => {
-            return HttpResponse::InternalServerError().json(ErrorResponse {
-                error: format!("Database connection error: {}", e),
-            });
-        }
-    };
+    let user_id = auth_user.user_id.clone();
+    let service = route_service(repos, &state);
+    let sort = query.sort;
+    let result = blocking::run(move || {
+        service
+            .get_route_options(&plan_id, &user_id)
+            .map(|routes| sort_routes(routes, sort))
+    })
+    .await;
 
-    match RouteOptionService::get_route_options(&conn, &plan_id, &auth_user.user_id) {
+    match result {
         Ok(routes_with_pois) => HttpResponse::Ok().json(routes_with_pois),
         Err(RouteOptionError::TravelPlanError(TravelPlanError::NotFound)) => {
             HttpResponse::NotFound().json(ErrorResponse {
@@ -79,6 +114,88 @@ pub async fn get_route_options(
     }
 }
 
+/// Compare route options side by side
+///
+/// Distance, duration, estimated cost, CO2e and points of interest per
+/// category for each route, the metrics each route is best at, and how much
+/// of every pair of routes runs along the same roads.
+#[utoipa::path(
+    get,
+    path = "/api/travelplan/{id}/routes/compare",
+    params(
+        ("id" = String, Path, description = "Travel plan ID"),
+        ("ids" = String, Query, description = "Comma-separated IDs of 2 to 10 route options")
+    ),
+    responses(
+        (status = 200, description = "The routes side by side", body = RouteComparison),
+        (status = 400, description = "Too few or too many routes", body = ErrorResponse),
+        (status = 403, description = "Unauthorized access", body = ErrorResponse),
+        (status = 404, description = "Travel plan or route option not found", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
+    ),
+    security(
+        ("Bearer" = [])
+    ),
+    tag = "route_options"
+)]
+pub async fn compare_route_options(
+    repos: web::Data<dyn Repositories>,
+    state: web::Data<AppState>,
+    auth_user: AuthenticatedUser,
+    path: web::Path<String>,
+    query: web::Query<CompareQuery>,
+) -> impl Responder {
+    let plan_id = path.into_inner();
+    let route_ids: Vec<String> = query
+        .ids
+        .split(',')
+        .map(str::trim)
+        .filter(|id| !id.is_empty())
+        .map(str::to_string)
+        .collect();
+    info!(
+        "Comparing {} route options of travel plan ID: {} for user: {}",
+        route_ids.len(),
+        plan_id,
+        auth_user.username
+    );
+
+    let user_id = auth_user.user_id.clone();
+    let service = route_service(repos, &state);
+    let result = blocking::run(move || {
+        service.compare_route_options(&plan_id, &user_id, &route_ids)
+    })
+    .await;
+
+    match result {
+        Ok(comparison) => HttpResponse::Ok().json(comparison),
+        Err(RouteOptionError::TravelPlanError(TravelPlanError::NotFound)) => {
+            HttpResponse::NotFound().json(ErrorResponse {
+                error: "Travel plan not found".to_string(),
+            })
+        }
+        Err(RouteOptionError::TravelPlanError(TravelPlanError::Unauthorized)) => {
+            HttpResponse::Forbidden().json(ErrorResponse {
+                error: "You don't have permission to access this travel plan".to_string(),
+            })
+        }
+        Err(RouteOptionError::RouteNotFound) => HttpResponse::NotFound().json(ErrorResponse {
+            error: "Route option not found".to_string(),
+        }),
+        Err(RouteOptionError::InvalidComparison(message)) => {
+            HttpResponse::BadRequest().json(ErrorResponse { error: message })
+        }
+        Err(RouteOptionError::DatabaseError(e)) => {
+            HttpResponse::InternalServerError().json(ErrorResponse {
+                error: format!("Database error: {}", e),
+            })
+        }
+        Err(_) => HttpResponse::InternalServerError().json(ErrorResponse {
+            error: "Failed to compare route options".to_string(),
+        }),
+    }
+}
+
 #[utoipa::path(
     post,
     path = "/api/travelplan/{id}/routes/generate",
@@ -98,7 +215,8 @@ pub async fn get_route_options(
     tag = "route_options"
 )]
 pub async fn generate_route_options(
-    pool: web::Data<DbPool>,
+    repos: web::Data<dyn Repositories>,
+    state: web::Data<AppState>,
     auth_user: AuthenticatedUser,
     path: web::Path<String>,
     query: web::Query<GenerateOptionsQuery>,
@@ -111,16 +229,14 @@ pub async fn generate_route_options(
         count, plan_id, auth_user.username
     );
 
-    let conn = match pool.get() {
-        Ok(conn) => conn,
-        Err(e) => {
-            return HttpResponse::InternalServerError().json(ErrorResponse {
-                error: format!("Database connection error: {}", e),
-            });
-        }
-    };
+    let user_id = auth_user.user_id.clone();
+    let service = route_service(repos, &state);
+    let result = blocking::run(move || {
+        service.generate_route_options(&plan_id, &user_id, count)
+    })
+    .await;
 
-    match RouteOptionService::generate_route_options(&conn, &plan_id, &auth_user.user_id, count) {
+    match result {
         Ok(routes_with_pois) => HttpResponse::Ok().json(routes_with_pois),
         Err(RouteOptionError::TravelPlanError(TravelPlanError::NotFound)) => {
             HttpResponse::NotFound().json(ErrorResponse {
@@ -148,10 +264,13 @@ pub async fn generate_route_options(
     path = "/api/travelplan/{plan_id}/routes/{route_id}",
     params(
         ("plan_id" = String, Path, description = "Travel plan ID"),
-        ("route_id" = String, Path, description = "Route option ID")
+        ("route_id" = String, Path, description = "Route option ID"),
+        ("If-None-Match" = Option<String>, Header, description = "ETag of the cached copy")
     ),
     responses(
-        (status = 200, description = "Route option retrieved successfully"),
+        (status = 200, description = "Route option retrieved successfully",
+            headers(("ETag" = String, description = "Version of the route option"))),
+        (status = 304, description = "The caller's copy is current"),
         (status = 400, description = "Invalid route option", body = ErrorResponse),
         (status = 403, description = "Unauthorized access", body = ErrorResponse),
         (status = 404, description = "Travel plan or route option not found", body = ErrorResponse),
@@ -163,8 +282,10 @@ pub async fn generate_route_options(
     tag = "route_options"
 )]
 pub async fn get_route_option_by_id(
-    pool: web::Data<DbPool>,
+    repos: web::Data<dyn Repositories>,
+    state: web::Data<AppState>,
     auth_user: AuthenticatedUser,
+    preconditions: Preconditions,
     path: web::Path<(String, String)>,
 ) -> impl Responder {
     let (plan_id, route_id) = path.into_inner();
@@ -173,18 +294,17 @@ pub async fn get_route_option_by_id(
         route_id, plan_id, auth_user.username
     );
 
-    let conn = match pool.get() {
-        Ok(conn) => conn,
-        Err(e) => {
-            return HttpResponse::InternalServerError().json(ErrorResponse {
-                error: format!("Database connection error: {}", e),
-            });
-        }
-    };
+    let user_id = auth_user.user_id.clone();
+    let service = route_service(repos, &state);
+    let result = blocking::run(move || {
+        service.get_route_option_by_id(&plan_id, &route_id, &user_id)
+    })
+    .await;
 
-    match RouteOptionService::get_route_option_by_id(&conn, &plan_id, &route_id, &auth_user.user_id)
-    {
-        Ok(route_with_pois) => HttpResponse::Ok().json(route_with_pois),
+    match result {
+        Ok(route_with_pois) => {
+            tagged_response(&preconditions, route_with_pois.etag(), &route_with_pois)
+        }
         Err(RouteOptionError::TravelPlanError(TravelPlanError::NotFound)) => {
             HttpResponse::NotFound().json(ErrorResponse {
                 error: "Travel plan not found".to_string(),
@@ -214,18 +334,251 @@ pub async fn get_route_option_by_id(
     }
 }
 
+fn patch_error_response(error: RouteOptionError) -> HttpResponse {
+    match error {
+        RouteOptionError::TravelPlanError(TravelPlanError::NotFound) => {
+            HttpResponse::NotFound().json(ErrorResponse {
+                error: "Travel plan not found".to_string(),
+            })
+        }
+        RouteOptionError::TravelPlanError(TravelPlanError::Unauthorized) => {
+            HttpResponse::Forbidden().json(ErrorResponse {
+                error: "You don't have permission to edit this travel plan".to_string(),
+            })
+        }
+        RouteOptionError::TravelPlanError(TravelPlanError::PreconditionFailed) => {
+            HttpResponse::PreconditionFailed().json(ErrorResponse {
+                error: "The route option has changed since it was read".to_string(),
+            })
+        }
+        RouteOptionError::RouteNotFound => HttpResponse::NotFound().json(ErrorResponse {
+            error: "Route option not found".to_string(),
+        }),
+        RouteOptionError::PoiNotFound => HttpResponse::NotFound().json(ErrorResponse {
+            error: "Point of interest not found".to_string(),
+        }),
+        RouteOptionError::InvalidRouteOption => HttpResponse::BadRequest().json(ErrorResponse {
+            error: "Route option does not belong to the specified travel plan".to_string(),
+        }),
+        RouteOptionError::InvalidOptimization(message) => {
+            HttpResponse::BadRequest().json(ErrorResponse { error: message })
+        }
+        RouteOptionError::DatabaseError(e) => {
+            HttpResponse::InternalServerError().json(ErrorResponse {
+                error: format!("Database error: {}", e),
+            })
+        }
+        _ => HttpResponse::InternalServerError().json(ErrorResponse {
+            error: "Failed to update route option".to_string(),
+        }),
+    }
+}
+
+/// Applies a JSON Merge Patch (RFC 7396) to a route option: fields left out
+/// are unchanged and `null` clears any field but the name.
+#[utoipa::path(
+    patch,
+    path = "/api/travelplan/{plan_id}/routes/{route_id}",
+    params(
+        ("plan_id" = String, Path, description = "Travel plan ID"),
+        ("route_id" = String, Path, description = "Route option ID"),
+        ("If-Match" = Option<String>, Header, description = "ETag the caller last read")
+    ),
+    request_body(content = PatchRouteOption, content_type = "application/merge-patch+json"),
+    responses(
+        (status = 200, description = "Route option patched successfully",
+            headers(("ETag" = String, description = "Version of the patched route option"))),
+        (status = 400, description = "Invalid patch or route option", body = ErrorResponse),
+        (status = 403, description = "Unauthorized access", body = ErrorResponse),
+        (status = 404, description = "Travel plan or route option not found", body = ErrorResponse),
+        (status = 412, description = "The route option has changed", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
+    ),
+    security(
+        ("Bearer" = [])
+    ),
+    tag = "route_options"
+)]
+pub async fn patch_route_option(
+    repos: web::Data<dyn Repositories>,
+    state: web::Data<AppState>,
+    auth_user: AuthenticatedUser,
+    preconditions: Preconditions,
+    path: web::Path<(String, String)>,
+    patch: web::Json<PatchRouteOption>,
+) -> impl Responder {
+    let (plan_id, route_id) = path.into_inner();
+    info!(
+        "Patching route option with ID: {} for travel plan ID: {} for user: {}",
+        route_id, plan_id, auth_user.username
+    );
+
+    let patch = patch.into_inner();
+    let user_id = auth_user.user_id.clone();
+    let service = route_service(repos, &state);
+    let result = blocking::run(move || {
+        service.patch_route_option(
+            &plan_id,
+            &route_id,
+            &patch,
+            &user_id,
+            preconditions.if_match.as_deref(),
+        )
+    })
+    .await;
+
+    match result {
+        Ok(route_with_pois) => HttpResponse::Ok()
+            .insert_header((header::ETAG, route_with_pois.etag()))
+            .json(route_with_pois),
+        Err(e) => patch_error_response(e),
+    }
+}
+
+/// Applies a JSON Merge Patch (RFC 7396) to a point of interest: fields left
+/// out are unchanged and `null` clears the description or category. Points
+/// of interest are versioned with their route, so `If-Match` takes the
+/// route's ETag and the response is the whole route.
+#[utoipa::path(
+    patch,
+    path = "/api/travelplan/{plan_id}/routes/{route_id}/pois/{poi_id}",
+    params(
+        ("plan_id" = String, Path, description = "Travel plan ID"),
+        ("route_id" = String, Path, description = "Route option ID"),
+        ("poi_id" = String, Path, description = "Point of interest ID"),
+        ("If-Match" = Option<String>, Header, description = "ETag of the route last read")
+    ),
+    request_body(content = PatchPointOfInterest, content_type = "application/merge-patch+json"),
+    responses(
+        (status = 200, description = "Point of interest patched successfully",
+            headers(("ETag" = String, description = "Version of the route option"))),
+        (status = 400, description = "Invalid patch or route option", body = ErrorResponse),
+        (status = 403, description = "Unauthorized access", body = ErrorResponse),
+        (status = 404, description = "Travel plan, route option or point of interest not found",
+            body = ErrorResponse),
+        (status = 412, description = "The route option has changed", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
+    ),
+    security(
+        ("Bearer" = [])
+    ),
+    tag = "route_options"
+)]
+pub async fn patch_point_of_interest(
+    repos: web::Data<dyn Repositories>,
+    state: web::Data<AppState>,
+    auth_user: AuthenticatedUser,
+    preconditions: Preconditions,
+    path: web::Path<(String, String, String)>,
+    patch: web::Json<PatchPointOfInterest>,
+) -> impl Responder {
+    let (plan_id, route_id, poi_id) = path.into_inner();
+    info!(
+        "Patching point of interest with ID: {} on route option {} for user: {}",
+        poi_id, route_id, auth_user.username
+    );
+
+    let patch = patch.into_inner();
+    let user_id = auth_user.user_id.clone();
+    let service = route_service(repos, &state);
+    let result = blocking::run(move || {
+        service.patch_point_of_interest(
+            &plan_id,
+            &route_id,
+            &poi_id,
+            &patch,
+            &user_id,
+            preconditions.if_match.as_deref(),
+        )
+    })
+    .await;
+
+    match result {
+        Ok(route_with_pois) => HttpResponse::Ok()
+            .insert_header((header::ETAG, route_with_pois.etag()))
+            .json(route_with_pois),
+        Err(e) => patch_error_response(e),
+    }
+}
+
+/// Puts the chosen points of interest in the visiting order that makes the
+/// trip from the route's start to its end shortest, or quickest for the
+/// given mode, and saves it as the route's waypoints. Up to ten stops are
+/// ordered exactly; more are ordered by a 2-opt and or-opt heuristic.
+#[utoipa::path(
+    post,
+    path = "/api/travelplan/{plan_id}/routes/{route_id}/optimize",
+    params(
+        ("plan_id" = String, Path, description = "Travel plan ID"),
+        ("route_id" = String, Path, description = "Route option ID"),
+        ("If-Match" = Option<String>, Header, description = "ETag the caller last read")
+    ),
+    request_body = OptimizeRequest,
+    responses(
+        (status = 200, description = "Points of interest put in order", body = OptimizedOrder,
+            headers(("ETag" = String, description = "Version of the updated route option"))),
+        (status = 400, description = "Invalid selection or route option", body = ErrorResponse),
+        (status = 403, description = "Unauthorized access", body = ErrorResponse),
+        (status = 404, description = "Travel plan, route option or point of interest not found",
+            body = ErrorResponse),
+        (status = 412, description = "The route option has changed", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
+    ),
+    security(
+        ("Bearer" = [])
+    ),
+    tag = "route_options"
+)]
+pub async fn optimize_poi_order(
+    repos: web::Data<dyn Repositories>,
+    auth_user: AuthenticatedUser,
+    preconditions: Preconditions,
+    path: web::Path<(String, String)>,
+    request: web::Json<OptimizeRequest>,
+) -> impl Responder {
+    let (plan_id, route_id) = path.into_inner();
+    info!(
+        "Optimising the point of interest order of route option {} for user: {}",
+        route_id, auth_user.username
+    );
+
+    let request = request.into_inner();
+    let user_id = auth_user.user_id.clone();
+    let service = RouteOptionService::new(repos.into_inner());
+    let result = blocking::run(move || {
+        service.optimize_poi_order(
+            &plan_id,
+            &route_id,
+            &request,
+            &user_id,
+            preconditions.if_match.as_deref(),
+        )
+    })
+    .await;
+
+    match result {
+        Ok(order) => HttpResponse::Ok()
+            .insert_header((header::ETAG, order.etag()))
+            .json(order),
+        Err(e) => patch_error_response(e),
+    }
+}
+
 #[utoipa::path(
     delete,
     path = "/api/travelplan/{plan_id}/routes/{route_id}",
     params(
         ("plan_id" = String, Path, description = "Travel plan ID"),
-        ("route_id" = String, Path, description = "Route option ID")
+        ("route_id" = String, Path, description = "Route option ID"),
+        ("If-Match" = Option<String>, Header, description = "ETag the caller last read")
     ),
     responses(
         (status = 200, description = "Route option deleted successfully"),
         (status = 400, description = "Invalid route option", body = ErrorResponse),
         (status = 403, description = "Unauthorized access", body = ErrorResponse),
         (status = 404, description = "Travel plan or route option not found", body = ErrorResponse),
+        (status = 409, description = "Route option is the plan's selected route", body = ErrorResponse),
+        (status = 412, description = "The route option has changed", body = ErrorResponse),
         (status = 500, description = "Internal server error", body = ErrorResponse)
     ),
     security(
@@ -234,8 +587,9 @@ pub async fn get_route_option_by_id(
     tag = "route_options"
 )]
 pub async fn delete_route_option(
-    pool: web::Data<DbPool>,
+    repos: web::Data<dyn Repositories>,
     auth_user: AuthenticatedUser,
+    preconditions: Preconditions,
     path: web::Path<(String, String)>,
 ) -> impl Responder {
     let (plan_id, route_id) = path.into_inner();
@@ -244,16 +598,20 @@ pub async fn delete_route_option(
         route_id, plan_id, auth_user.username
     );
 
-    let conn = match pool.get() {
-        Ok(conn) => conn,
-        Err(e) => {
-            return HttpResponse::InternalServerError().json(ErrorResponse {
-                error: format!("Database connection error: {}", e),
-            });
-        }
-    };
+    let user_id = auth_user.user_id.clone();
+    let deleted_route_id = route_id.clone();
+    let service = RouteOptionService::new(repos.into_inner());
+    let result = blocking::run(move || {
+        service.delete_route_option(
+            &plan_id,
+            &deleted_route_id,
+            &user_id,
+            preconditions.if_match.as_deref(),
+        )
+    })
+    .await;
 
-    match RouteOptionService::delete_route_option(&conn, &plan_id, &route_id, &auth_user.user_id) {
+    match result {
         Ok(deleted) => {
             if deleted {
                 HttpResponse::Ok().json(serde_json::json!({
@@ -283,6 +641,14 @@ pub async fn delete_route_option(
                 error: "Route option does not belong to the specified travel plan".to_string(),
             })
         }
+        Err(RouteOptionError::RouteLocked) => HttpResponse::Conflict().json(ErrorResponse {
+            error: "Route option is the plan's selected route; unselect it first".to_string(),
+        }),
+        Err(RouteOptionError::TravelPlanError(TravelPlanError::PreconditionFailed)) => {
+            HttpResponse::PreconditionFailed().json(ErrorResponse {
+                error: "The route option has changed since it was read".to_string(),
+            })
+        }
         Err(RouteOptionError::DatabaseError(e)) => {
             HttpResponse::InternalServerError().json(ErrorResponse {
                 error: format!("Database error: {}", e),
@@ -298,12 +664,14 @@ pub async fn delete_route_option(
     delete,
     path = "/api/travelplan/{id}/routes",
     params(
-        ("id" = String, Path, description = "Travel plan ID")
+        ("id" = String, Path, description = "Travel plan ID"),
+        ("If-Match" = Option<String>, Header, description = "ETag of the plan the caller last read")
     ),
     responses(
         (status = 200, description = "All route options deleted successfully"),
         (status = 403, description = "Unauthorized access", body = ErrorResponse),
         (status = 404, description = "Travel plan not found", body = ErrorResponse),
+        (status = 412, description = "The travel plan has changed", body = ErrorResponse),
         (status = 500, description = "Internal server error", body = ErrorResponse)
     ),
     security(
@@ -312,8 +680,9 @@ pub async fn delete_route_option(
     tag = "route_options"
 )]
 pub async fn delete_all_route_options(
-    pool: web::Data<DbPool>,
+    repos: web::Data<dyn Repositories>,
     auth_user: AuthenticatedUser,
+    preconditions: Preconditions,
     path: web::Path<String>,
 ) -> impl Responder {
     let plan_id = path.into_inner();
@@ -322,16 +691,19 @@ pub async fn delete_all_route_options(
         plan_id, auth_user.username
     );
 
-    let conn = match pool.get() {
-        Ok(conn) => conn,
-        Err(e) => {
-            return HttpResponse::InternalServerError().json(ErrorResponse {
-                error: format!("Database connection error: {}", e),
-            });
-        }
-    };
+    let user_id = auth_user.user_id.clone();
+    let target_plan_id = plan_id.clone();
+    let service = RouteOptionService::new(repos.into_inner());
+    let result = blocking::run(move || {
+        service.delete_all_route_options(
+            &target_plan_id,
+            &user_id,
+            preconditions.if_match.as_deref(),
+        )
+    })
+    .await;
 
-    match RouteOptionService::delete_all_route_options(&conn, &plan_id, &auth_user.user_id) {
+    match result {
         Ok(count) => {
             HttpResponse::Ok().json(serde_json::json!({
                 "message": format!("Deleted {} route options for travel plan ID: {}", count, plan_id)
@@ -347,6 +719,11 @@ pub async fn delete_all_route_options(
                 error: "You don't have permission to access this travel plan".to_string(),
             })
         }
+        Err(RouteOptionError::TravelPlanError(TravelPlanError::PreconditionFailed)) => {
+            HttpResponse::PreconditionFailed().json(ErrorResponse {
+                error: "The travel plan has changed since it was read".to_string(),
+            })
+        }
         Err(RouteOptionError::DatabaseError(e)) => {
             HttpResponse::InternalServerError().json(ErrorResponse {
                 error: format!("Database error: {}", e),
@@ -357,3 +734,126 @@ pub async fn delete_all_route_options(
         }),
     }
 }
+
+fn leg_route_error_response(error: RouteOptionError) -> HttpResponse {
+    match error {
+        RouteOptionError::TravelPlanError(TravelPlanError::NotFound) => {
+            HttpResponse::NotFound().json(ErrorResponse {
+                error: "Travel plan not found".to_string(),
+            })
+        }
+        RouteOptionError::TravelPlanError(TravelPlanError::Unauthorized) => {
+            HttpResponse::Forbidden().json(ErrorResponse {
+                error: "You don't have permission to access this travel plan".to_string(),
+            })
+        }
+        RouteOptionError::LegNotFound => HttpResponse::NotFound().json(ErrorResponse {
+            error: "Leg not found".to_string(),
+        }),
+        RouteOptionError::DatabaseError(e) => {
+            HttpResponse::InternalServerError().json(ErrorResponse {
+                error: format!("Database error: {}", e),
+            })
+        }
+        _ => HttpResponse::InternalServerError().json(ErrorResponse {
+            error: "Failed to fetch route options".to_string(),
+        }),
+    }
+}
+
+/// List the route options of one leg of a travel plan
+#[utoipa::path(
+    get,
+    path = "/api/travelplan/{plan_id}/legs/{leg_id}/routes",
+    params(
+        ("plan_id" = String, Path, description = "Travel plan ID"),
+        ("leg_id" = String, Path, description = "Leg ID"),
+        ("sort" = Option<RouteSort>, Query,
+            description = "Order by estimated cost, distance or duration, least first")
+    ),
+    responses(
+        (status = 200, description = "The leg's route options"),
+        (status = 403, description = "Unauthorized access", body = ErrorResponse),
+        (status = 404, description = "Travel plan or leg not found", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
+    ),
+    security(
+        ("Bearer" = [])
+    ),
+    tag = "route_options"
+)]
+pub async fn get_leg_route_options(
+    repos: web::Data<dyn Repositories>,
+    state: web::Data<AppState>,
+    auth_user: AuthenticatedUser,
+    path: web::Path<(String, String)>,
+    query: web::Query<RouteListQuery>,
+) -> impl Responder {
+    let (plan_id, leg_id) = path.into_inner();
+
+    let user_id = auth_user.user_id.clone();
+    let service = route_service(repos, &state);
+    let sort = query.sort;
+    let result = blocking::run(move || {
+        service
+            .get_leg_route_options(&plan_id, &leg_id, &user_id)
+            .map(|routes| sort_routes(routes, sort))
+    })
+    .await;
+
+    match result {
+        Ok(routes_with_pois) => HttpResponse::Ok().json(routes_with_pois),
+        Err(e) => leg_route_error_response(e),
+    }
+}
+
+/// Generate route options for one leg of a travel plan
+///
+/// The options run between the leg's own start and end locations and their
+/// durations follow the leg's travel mode.
+#[utoipa::path(
+    post,
+    path = "/api/travelplan/{plan_id}/legs/{leg_id}/routes/generate",
+    params(
+        ("plan_id" = String, Path, description = "Travel plan ID"),
+        ("leg_id" = String, Path, description = "Leg ID")
+    ),
+    request_body(content = GenerateOptionsQuery, description = "Number of route options to generate"),
+    responses(
+        (status = 200, description = "Route options generated successfully"),
+        (status = 403, description = "Unauthorized access", body = ErrorResponse),
+        (status = 404, description = "Travel plan or leg not found", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
+    ),
+    security(
+        ("Bearer" = [])
+    ),
+    tag = "route_options"
+)]
+pub async fn generate_leg_route_options(
+    repos: web::Data<dyn Repositories>,
+    state: web::Data<AppState>,
+    auth_user: AuthenticatedUser,
+    path: web::Path<(String, String)>,
+    query: web::Query<GenerateOptionsQuery>,
+) -> impl Responder {
+    let (plan_id, leg_id) = path.into_inner();
+    let count = query.count.unwrap_or(3);
+
+    info!(
+        "Generating {} random route options for leg {} of travel plan ID: {} for user: {}",
+        count, leg_id, plan_id, auth_user.username
+    );
+
+    let user_id = auth_user.user_id.clone();
+    let service = route_service(repos, &state);
+    let result = blocking::run(move || {
+        service.generate_leg_route_options(&plan_id, &leg_id, &user_id, count)
+    })
+    .await;
+
+    match result {
+        Ok(routes_with_pois) => HttpResponse::Ok().json(routes_with_pois),
+        Err(e) => leg_route_error_response(e),
+    }
+}
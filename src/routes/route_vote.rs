@@ -0,0 +1,211 @@
+use actix_web::{HttpResponse, Responder, web};
+use log::info;
+
+use crate::middleware::auth::AuthenticatedUser;
+use crate::repositories::Repositories;
+use crate::routes::route_option::ErrorResponse;
+use crate::services::blocking;
+use crate::services::route_vote_service::{CastVote, RouteVoteError, RouteVoteService, SelectRoute};
+use crate::services::travel_plan_service::TravelPlanError;
+
+fn route_vote_error_response(error: RouteVoteError) -> HttpResponse {
+    match error {
+        RouteVoteError::TravelPlanError(TravelPlanError::NotFound) => {
+            HttpResponse::NotFound().json(ErrorResponse {
+                error: "Travel plan not found".to_string(),
+            })
+        }
+        RouteVoteError::TravelPlanError(TravelPlanError::Unauthorized) => {
+            HttpResponse::Forbidden().json(ErrorResponse {
+                error: "You don't have permission to do this on this travel plan".to_string(),
+            })
+        }
+        RouteVoteError::RouteNotFound => HttpResponse::NotFound().json(ErrorResponse {
+            error: "Route option not found in this travel plan".to_string(),
+        }),
+        RouteVoteError::VoteNotFound => HttpResponse::NotFound().json(ErrorResponse {
+            error: "You have not voted on this route option".to_string(),
+        }),
+        RouteVoteError::TravelPlanError(TravelPlanError::PreconditionFailed) => {
+            HttpResponse::PreconditionFailed().json(ErrorResponse {
+                error: "The travel plan has changed since it was read".to_string(),
+            })
+        }
+        RouteVoteError::TravelPlanError(TravelPlanError::DatabaseError(e))
+        | RouteVoteError::DatabaseError(e) => {
+            HttpResponse::InternalServerError().json(ErrorResponse {
+                error: format!("Database error: {}", e),
+            })
+        }
+    }
+}
+
+/// Vote on a route option
+///
+/// Any member of the plan can vote once per route; voting again changes the
+/// vote.
+#[utoipa::path(
+    put,
+    path = "/api/travelplan/{plan_id}/routes/{route_id}/vote",
+    params(
+        ("plan_id" = String, Path, description = "Travel plan ID"),
+        ("route_id" = String, Path, description = "Route option ID")
+    ),
+    request_body = CastVote,
+    responses(
+        (status = 200, description = "Vote recorded", body = RouteVote),
+        (status = 403, description = "Unauthorized access", body = ErrorResponse),
+        (status = 404, description = "Travel plan or route option not found", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
+    ),
+    security(
+        ("Bearer" = [])
+    ),
+    tag = "route_options"
+)]
+pub async fn cast_vote(
+    repos: web::Data<dyn Repositories>,
+    auth_user: AuthenticatedUser,
+    path: web::Path<(String, String)>,
+    vote_data: web::Json<CastVote>,
+) -> impl Responder {
+    let (plan_id, route_id) = path.into_inner();
+    info!(
+        "User {} is voting on route option {} of travel plan {}",
+        auth_user.username, route_id, plan_id
+    );
+
+    let direction = vote_data.into_inner().direction;
+    let user_id = auth_user.user_id.clone();
+    let service = RouteVoteService::new(repos.into_inner());
+    let result = blocking::run(move || {
+        service.cast_vote(&plan_id, &route_id, &user_id, direction)
+    })
+    .await;
+
+    match result {
+        Ok(vote) => HttpResponse::Ok().json(vote),
+        Err(e) => route_vote_error_response(e),
+    }
+}
+
+/// Withdraw your vote on a route option
+#[utoipa::path(
+    delete,
+    path = "/api/travelplan/{plan_id}/routes/{route_id}/vote",
+    params(
+        ("plan_id" = String, Path, description = "Travel plan ID"),
+        ("route_id" = String, Path, description = "Route option ID")
+    ),
+    responses(
+        (status = 204, description = "Vote withdrawn"),
+        (status = 403, description = "Unauthorized access", body = ErrorResponse),
+        (status = 404, description = "Travel plan, route option or vote not found", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
+    ),
+    security(
+        ("Bearer" = [])
+    ),
+    tag = "route_options"
+)]
+pub async fn retract_vote(
+    repos: web::Data<dyn Repositories>,
+    auth_user: AuthenticatedUser,
+    path: web::Path<(String, String)>,
+) -> impl Responder {
+    let (plan_id, route_id) = path.into_inner();
+    let user_id = auth_user.user_id.clone();
+    let service = RouteVoteService::new(repos.into_inner());
+    let result =
+        blocking::run(move || service.retract_vote(&plan_id, &route_id, &user_id)).await;
+
+    match result {
+        Ok(()) => HttpResponse::NoContent().finish(),
+        Err(e) => route_vote_error_response(e),
+    }
+}
+
+/// Tally the votes on a plan's route options
+///
+/// Routes come back highest score first, with the caller's own vote and
+/// which route the owner selected.
+#[utoipa::path(
+    get,
+    path = "/api/travelplan/{id}/votes",
+    params(
+        ("id" = String, Path, description = "Travel plan ID")
+    ),
+    responses(
+        (status = 200, description = "Vote tally", body = VoteTally),
+        (status = 403, description = "Unauthorized access", body = ErrorResponse),
+        (status = 404, description = "Travel plan not found", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
+    ),
+    security(
+        ("Bearer" = [])
+    ),
+    tag = "route_options"
+)]
+pub async fn get_vote_tally(
+    repos: web::Data<dyn Repositories>,
+    auth_user: AuthenticatedUser,
+    path: web::Path<String>,
+) -> impl Responder {
+    let plan_id = path.into_inner();
+    let user_id = auth_user.user_id.clone();
+    let service = RouteVoteService::new(repos.into_inner());
+    let result = blocking::run(move || service.tally(&plan_id, &user_id)).await;
+
+    match result {
+        Ok(tally) => HttpResponse::Ok().json(tally),
+        Err(e) => route_vote_error_response(e),
+    }
+}
+
+/// Lock in the plan's route
+///
+/// Owner only. The selected route can't be deleted until it is unselected
+/// by sending `null`.
+#[utoipa::path(
+    put,
+    path = "/api/travelplan/{id}/selected-route",
+    params(
+        ("id" = String, Path, description = "Travel plan ID")
+    ),
+    request_body = SelectRoute,
+    responses(
+        (status = 200, description = "Selection updated", body = TravelPlanDto),
+        (status = 403, description = "Only owners can select a route", body = ErrorResponse),
+        (status = 404, description = "Travel plan or route option not found", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
+    ),
+    security(
+        ("Bearer" = [])
+    ),
+    tag = "route_options"
+)]
+pub async fn select_route(
+    repos: web::Data<dyn Repositories>,
+    auth_user: AuthenticatedUser,
+    path: web::Path<String>,
+    selection: web::Json<SelectRoute>,
+) -> impl Responder {
+    let plan_id = path.into_inner();
+    let route_id = selection.into_inner().route_id;
+    info!(
+        "User {} is selecting route {:?} for travel plan {}",
+        auth_user.username, route_id, plan_id
+    );
+
+    let user_id = auth_user.user_id.clone();
+    let service = RouteVoteService::new(repos.into_inner());
+    let result = blocking::run(move || {
+        service.select_route(&plan_id, &user_id, route_id.as_deref())
+    })
+    .await;
+
+    match result {
+        Ok(plan) => HttpResponse::Ok().json(plan),
+        Err(e) => route_vote_error_response(e),
+    }
+}
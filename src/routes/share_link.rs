@@ -0,0 +1,202 @@
+use actix_web::{HttpResponse, Responder, web};
+use log::info;
+
+use crate::middleware::auth::AuthenticatedUser;
+use crate::models::share_link::NewShareLink;
+use crate::repositories::Repositories;
+use crate::routes::route_option::ErrorResponse;
+use crate::services::blocking;
+use crate::services::share_link_service::{ShareLinkError, ShareLinkService};
+use crate::services::travel_plan_service::TravelPlanError;
+
+fn share_link_error_response(error: ShareLinkError) -> HttpResponse {
+    match error {
+        ShareLinkError::TravelPlanError(TravelPlanError::NotFound) => {
+            HttpResponse::NotFound().json(ErrorResponse {
+                error: "Travel plan not found".to_string(),
+            })
+        }
+        ShareLinkError::TravelPlanError(TravelPlanError::Unauthorized) => {
+            HttpResponse::Forbidden().json(ErrorResponse {
+                error: "Only the plan's owners can manage share links".to_string(),
+            })
+        }
+        ShareLinkError::LinkNotFound => HttpResponse::NotFound().json(ErrorResponse {
+            error: "Share link not found".to_string(),
+        }),
+        ShareLinkError::LinkExpired => HttpResponse::Gone().json(ErrorResponse {
+            error: "This share link has expired or been revoked".to_string(),
+        }),
+        ShareLinkError::InvalidInput(e) => {
+            HttpResponse::BadRequest().json(ErrorResponse { error: e })
+        }
+        ShareLinkError::TravelPlanError(TravelPlanError::PreconditionFailed) => {
+            HttpResponse::PreconditionFailed().json(ErrorResponse {
+                error: "The travel plan has changed since it was read".to_string(),
+            })
+        }
+        ShareLinkError::TravelPlanError(TravelPlanError::DatabaseError(e))
+        | ShareLinkError::DatabaseError(e) => {
+            HttpResponse::InternalServerError().json(ErrorResponse {
+                error: format!("Database error: {}", e),
+            })
+        }
+    }
+}
+
+/// Create a public share link
+///
+/// Mints an unguessable token that gives read-only access to the plan
+/// without an account.
+#[utoipa::path(
+    post,
+    path = "/api/travelplan/{id}/share-links",
+    params(
+        ("id" = String, Path, description = "Travel plan ID")
+    ),
+    request_body = NewShareLink,
+    responses(
+        (status = 201, description = "Share link created", body = ShareLink),
+        (status = 400, description = "Invalid expiry", body = ErrorResponse),
+        (status = 403, description = "Only owners can share a plan", body = ErrorResponse),
+        (status = 404, description = "Travel plan not found", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
+    ),
+    security(
+        ("Bearer" = [])
+    ),
+    tag = "sharing"
+)]
+pub async fn create_share_link(
+    repos: web::Data<dyn Repositories>,
+    auth_user: AuthenticatedUser,
+    path: web::Path<String>,
+    link_data: web::Json<NewShareLink>,
+) -> impl Responder {
+    let plan_id = path.into_inner();
+    info!(
+        "User {} is creating a share link for travel plan {}",
+        auth_user.username, plan_id
+    );
+
+    let new_link = link_data.into_inner();
+    let user_id = auth_user.user_id.clone();
+    let service = ShareLinkService::new(repos.into_inner());
+    let result =
+        blocking::run(move || service.create_share_link(&plan_id, &user_id, &new_link)).await;
+
+    match result {
+        Ok(link) => HttpResponse::Created().json(link),
+        Err(e) => share_link_error_response(e),
+    }
+}
+
+/// List a plan's share links
+///
+/// Includes revoked and expired links, with their access counts.
+#[utoipa::path(
+    get,
+    path = "/api/travelplan/{id}/share-links",
+    params(
+        ("id" = String, Path, description = "Travel plan ID")
+    ),
+    responses(
+        (status = 200, description = "Share links retrieved successfully", body = [ShareLink]),
+        (status = 403, description = "Only owners can see share links", body = ErrorResponse),
+        (status = 404, description = "Travel plan not found", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
+    ),
+    security(
+        ("Bearer" = [])
+    ),
+    tag = "sharing"
+)]
+pub async fn get_share_links(
+    repos: web::Data<dyn Repositories>,
+    auth_user: AuthenticatedUser,
+    path: web::Path<String>,
+) -> impl Responder {
+    let plan_id = path.into_inner();
+    let user_id = auth_user.user_id.clone();
+    let service = ShareLinkService::new(repos.into_inner());
+    let result = blocking::run(move || service.list_share_links(&plan_id, &user_id)).await;
+
+    match result {
+        Ok(links) => HttpResponse::Ok().json(links),
+        Err(e) => share_link_error_response(e),
+    }
+}
+
+/// Revoke a share link
+#[utoipa::path(
+    delete,
+    path = "/api/travelplan/{id}/share-links/{link_id}",
+    params(
+        ("id" = String, Path, description = "Travel plan ID"),
+        ("link_id" = String, Path, description = "Share link ID")
+    ),
+    responses(
+        (status = 204, description = "Share link revoked"),
+        (status = 403, description = "Only owners can revoke share links", body = ErrorResponse),
+        (status = 404, description = "Travel plan or share link not found", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
+    ),
+    security(
+        ("Bearer" = [])
+    ),
+    tag = "sharing"
+)]
+pub async fn revoke_share_link(
+    repos: web::Data<dyn Repositories>,
+    auth_user: AuthenticatedUser,
+    path: web::Path<(String, String)>,
+) -> impl Responder {
+    let (plan_id, link_id) = path.into_inner();
+    info!(
+        "User {} is revoking share link {} of travel plan {}",
+        auth_user.username, link_id, plan_id
+    );
+
+    let user_id = auth_user.user_id.clone();
+    let service = ShareLinkService::new(repos.into_inner());
+    let result =
+        blocking::run(move || service.revoke_share_link(&plan_id, &user_id, &link_id)).await;
+
+    match result {
+        Ok(()) => HttpResponse::NoContent().finish(),
+        Err(e) => share_link_error_response(e),
+    }
+}
+
+/// View a plan through a public share link
+///
+/// Needs no account. Returns the plan without owner details, plus routes and
+/// points of interest when the link includes them.
+#[utoipa::path(
+    get,
+    path = "/api/shared/{token}",
+    params(
+        ("token" = String, Path, description = "Share token")
+    ),
+    responses(
+        (status = 200, description = "Shared travel plan", body = SharedTravelPlanDto),
+        (status = 404, description = "Unknown share link", body = ErrorResponse),
+        (status = 410, description = "Share link expired or revoked", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
+    ),
+    security(()),
+    tag = "sharing"
+)]
+pub async fn get_shared_plan(
+    repos: web::Data<dyn Repositories>,
+    path: web::Path<String>,
+) -> impl Responder {
+    let token = path.into_inner();
+    let service = ShareLinkService::new(repos.into_inner());
+    let result = blocking::run(move || service.view_shared_plan(&token)).await;
+
+    match result {
+        Ok(plan) => HttpResponse::Ok().json(plan),
+        Err(e) => share_link_error_response(e),
+    }
+}
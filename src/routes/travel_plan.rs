@@ -1,11 +1,13 @@
-use actix_web::{HttpResponse, Responder, web};
+use actix_web::{HttpResponse, Responder, http::header, web};
 use log::info;
 use serde::Serialize;
 use utoipa::ToSchema;
 
-use crate::db::connection::DbPool;
 use crate::middleware::auth::AuthenticatedUser;
-use crate::models::travel_plan::{NewTravelPlan, UpdateTravelPlan};
+use crate::middleware::conditional::{Preconditions, tagged_response};
+use crate::models::travel_plan::{NewTravelPlan, PatchTravelPlan, UpdateTravelPlan};
+use crate::repositories::Repositories;
+use crate::services::blocking;
 use crate::services::travel_plan_service::{TravelPlanError, TravelPlanService};
 
 #[derive(Debug, Serialize, ToSchema)]
@@ -13,6 +15,12 @@ struct ErrorResponse {
     error: String,
 }
 
+fn precondition_failed() -> HttpResponse {
+    HttpResponse::PreconditionFailed().json(ErrorResponse {
+        error: "The travel plan has changed since it was read".to_string(),
+    })
+}
+
 #[utoipa::path(
     get,
     path = "/api/travelplan",
@@ -26,21 +34,16 @@ struct ErrorResponse {
     tag = "travel_plans"
 )]
 pub async fn get_travel_plans(
-    pool: web::Data<DbPool>,
+    repos: web::Data<dyn Repositories>,
     auth_user: AuthenticatedUser,
 ) -> impl Responder {
     info!("Fetching travel plans for user: {}", auth_user.username);
 
-    let conn = match pool.get() {
-        Ok(conn) => conn,
-        Err(e) => {
-            return HttpResponse::InternalServerError().json(ErrorResponse {
-                error: format!("Database connection error: {}", e),
-            });
-        }
-    };
+    let user_id = auth_user.user_id.clone();
+    let service = TravelPlanService::new(repos.into_inner());
+    let result = blocking::run(move || service.get_travel_plans(&user_id)).await;
 
-    match TravelPlanService::get_travel_plans(&conn, &auth_user.user_id) {
+    match result {
         Ok(plan_dtos) => HttpResponse::Ok().json(plan_dtos),
         Err(TravelPlanError::DatabaseError(e)) => {
             HttpResponse::InternalServerError().json(ErrorResponse {
@@ -55,15 +58,20 @@ pub async fn get_travel_plans(
 
 /// Get a specific travel plan by ID
 ///
-/// Retrieves a specific travel plan by its ID.
+/// Retrieves a specific travel plan by its ID. Support staff and admins can
+/// read any plan; those reads are recorded in the audit log. The `ETag` can
+/// be sent back as `If-Match` on writes or `If-None-Match` on later reads.
 #[utoipa::path(
     get,
     path = "/api/travelplan/{id}",
     params(
-        ("id" = String, Path, description = "Travel plan ID")
+        ("id" = String, Path, description = "Travel plan ID"),
+        ("If-None-Match" = Option<String>, Header, description = "ETag of the cached copy")
     ),
     responses(
-        (status = 200, description = "Travel plan retrieved successfully"),
+        (status = 200, description = "Travel plan retrieved successfully", body = TravelPlanDto,
+            headers(("ETag" = String, description = "Current version of the plan"))),
+        (status = 304, description = "The caller's copy is current"),
         (status = 403, description = "Unauthorized access", body = ErrorResponse),
         (status = 404, description = "Travel plan not found", body = ErrorResponse),
         (status = 500, description = "Internal server error", body = ErrorResponse)
@@ -74,8 +82,9 @@ pub async fn get_travel_plans(
     tag = "travel_plans"
 )]
 pub async fn get_travel_plan_by_id(
-    pool: web::Data<DbPool>,
+    repos: web::Data<dyn Repositories>,
     auth_user: AuthenticatedUser,
+    preconditions: Preconditions,
     path: web::Path<String>,
 ) -> impl Responder {
     let plan_id = path.into_inner();
@@ -84,23 +93,19 @@ pub async fn get_travel_plan_by_id(
         plan_id, auth_user.username
     );
 
-    let conn = match pool.get() {
-        Ok(conn) => conn,
-        Err(e) => {
-            return HttpResponse::InternalServerError().json(ErrorResponse {
-                error: format!("Database connection error: {}", e),
-            });
-        }
-    };
+    let viewer = auth_user.actor();
+    let service = TravelPlanService::new(repos.into_inner());
+    let result = blocking::run(move || service.view_travel_plan(&plan_id, &viewer)).await;
 
-    match TravelPlanService::get_travel_plan_by_id(&conn, &plan_id, &auth_user.user_id) {
-        Ok(plan_dto) => HttpResponse::Ok().json(plan_dto),
+    match result {
+        Ok(plan_dto) => tagged_response(&preconditions, plan_dto.etag(), &plan_dto),
         Err(TravelPlanError::NotFound) => HttpResponse::NotFound().json(ErrorResponse {
             error: "Travel plan not found".to_string(),
         }),
         Err(TravelPlanError::Unauthorized) => HttpResponse::Forbidden().json(ErrorResponse {
             error: "You don't have permission to access this travel plan".to_string(),
         }),
+        Err(TravelPlanError::PreconditionFailed) => precondition_failed(),
         Err(TravelPlanError::DatabaseError(e)) => {
             HttpResponse::InternalServerError().json(ErrorResponse {
                 error: format!("Database error: {}", e),
@@ -127,26 +132,20 @@ pub async fn get_travel_plan_by_id(
     tag = "travel_plans"
 )]
 pub async fn create_travel_plan(
-    pool: web::Data<DbPool>,
+    repos: web::Data<dyn Repositories>,
     auth_user: AuthenticatedUser,
     plan_data: web::Json<NewTravelPlan>,
 ) -> impl Responder {
     info!("Creating new travel plan for user: {}", auth_user.username);
 
-    let conn = match pool.get() {
-        Ok(conn) => conn,
-        Err(e) => {
-            return HttpResponse::InternalServerError().json(ErrorResponse {
-                error: format!("Database connection error: {}", e),
-            });
-        }
-    };
-
     let new_plan = plan_data.into_inner();
 
     let user_id = auth_user.user_id.clone();
 
-    match TravelPlanService::create_travel_plan(&conn, &new_plan, &user_id) {
+    let service = TravelPlanService::new(repos.into_inner());
+    let result = blocking::run(move || service.create_travel_plan(&new_plan, &user_id)).await;
+
+    match result {
         Ok(plan_dto) => HttpResponse::Created().json(plan_dto),
         Err(TravelPlanError::NotFound) => HttpResponse::NotFound().json(ErrorResponse {
             error: "Resource not found".to_string(),
@@ -154,6 +153,7 @@ pub async fn create_travel_plan(
         Err(TravelPlanError::Unauthorized) => HttpResponse::Forbidden().json(ErrorResponse {
             error: "You don't have permission to create this travel plan".to_string(),
         }),
+        Err(TravelPlanError::PreconditionFailed) => precondition_failed(),
         Err(TravelPlanError::DatabaseError(e)) => {
             HttpResponse::InternalServerError().json(ErrorResponse {
                 error: format!("Database error: {}", e),
@@ -164,18 +164,22 @@ pub async fn create_travel_plan(
 
 /// Update a travel plan
 ///
-/// Updates an existing travel plan.
+/// Updates an existing travel plan. With `If-Match`, the update only goes
+/// through if nobody changed the plan since the caller read it.
 #[utoipa::path(
     put,
     path = "/api/travelplan/{id}",
     params(
-        ("id" = String, Path, description = "Travel plan ID")
+        ("id" = String, Path, description = "Travel plan ID"),
+        ("If-Match" = Option<String>, Header, description = "ETag the caller last read")
     ),
     request_body = UpdateTravelPlan,
     responses(
-        (status = 200, description = "Travel plan updated successfully"),
+        (status = 200, description = "Travel plan updated successfully", body = TravelPlanDto,
+            headers(("ETag" = String, description = "Version of the updated plan"))),
         (status = 403, description = "Unauthorized access", body = ErrorResponse),
         (status = 404, description = "Travel plan not found", body = ErrorResponse),
+        (status = 412, description = "The plan has changed", body = ErrorResponse),
         (status = 500, description = "Internal server error", body = ErrorResponse)
     ),
     security(
@@ -184,8 +188,9 @@ pub async fn create_travel_plan(
     tag = "travel_plans"
 )]
 pub async fn update_travel_plan(
-    pool: web::Data<DbPool>,
+    repos: web::Data<dyn Repositories>,
     auth_user: AuthenticatedUser,
+    preconditions: Preconditions,
     path: web::Path<String>,
     update_data: web::Json<UpdateTravelPlan>,
 ) -> impl Responder {
@@ -195,23 +200,97 @@ pub async fn update_travel_plan(
         plan_id, auth_user.username
     );
 
-    let conn = match pool.get() {
-        Ok(conn) => conn,
-        Err(e) => {
-            return HttpResponse::InternalServerError().json(ErrorResponse {
-                error: format!("Database connection error: {}", e),
-            });
+    let update_data = update_data.into_inner();
+    let user_id = auth_user.user_id.clone();
+    let service = TravelPlanService::new(repos.into_inner());
+    let result = blocking::run(move || {
+        service.update_travel_plan(
+            &plan_id,
+            &update_data,
+            &user_id,
+            preconditions.if_match.as_deref(),
+        )
+    })
+    .await;
+
+    match result {
+        Ok(updated_plan_dto) => HttpResponse::Ok()
+            .insert_header((header::ETAG, updated_plan_dto.etag()))
+            .json(updated_plan_dto),
+        Err(TravelPlanError::NotFound) => HttpResponse::NotFound().json(ErrorResponse {
+            error: "Travel plan not found".to_string(),
+        }),
+        Err(TravelPlanError::Unauthorized) => HttpResponse::Forbidden().json(ErrorResponse {
+            error: "You don't have permission to update this travel plan".to_string(),
+        }),
+        Err(TravelPlanError::PreconditionFailed) => precondition_failed(),
+        Err(TravelPlanError::DatabaseError(e)) => {
+            HttpResponse::InternalServerError().json(ErrorResponse {
+                error: format!("Database error: {}", e),
+            })
         }
-    };
+    }
+}
 
-    match TravelPlanService::update_travel_plan(&conn, &plan_id, &update_data, &auth_user.user_id) {
-        Ok(updated_plan_dto) => HttpResponse::Ok().json(updated_plan_dto),
+/// Patch a travel plan
+///
+/// Applies a JSON Merge Patch (RFC 7396): fields left out are unchanged and
+/// `null` clears the description or a date. With `If-Match`, the patch only
+/// goes through if nobody changed the plan since the caller read it.
+#[utoipa::path(
+    patch,
+    path = "/api/travelplan/{id}",
+    params(
+        ("id" = String, Path, description = "Travel plan ID"),
+        ("If-Match" = Option<String>, Header, description = "ETag the caller last read")
+    ),
+    request_body(content = PatchTravelPlan, content_type = "application/merge-patch+json"),
+    responses(
+        (status = 200, description = "Travel plan patched successfully", body = TravelPlanDto,
+            headers(("ETag" = String, description = "Version of the patched plan"))),
+        (status = 400, description = "Malformed patch, or null for a required field"),
+        (status = 403, description = "Unauthorized access", body = ErrorResponse),
+        (status = 404, description = "Travel plan not found", body = ErrorResponse),
+        (status = 412, description = "The plan has changed", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
+    ),
+    security(
+        ("Bearer" = [])
+    ),
+    tag = "travel_plans"
+)]
+pub async fn patch_travel_plan(
+    repos: web::Data<dyn Repositories>,
+    auth_user: AuthenticatedUser,
+    preconditions: Preconditions,
+    path: web::Path<String>,
+    patch: web::Json<PatchTravelPlan>,
+) -> impl Responder {
+    let plan_id = path.into_inner();
+    info!(
+        "Patching travel plan with ID: {} for user: {}",
+        plan_id, auth_user.username
+    );
+
+    let patch = patch.into_inner();
+    let user_id = auth_user.user_id.clone();
+    let service = TravelPlanService::new(repos.into_inner());
+    let result = blocking::run(move || {
+        service.patch_travel_plan(&plan_id, &patch, &user_id, preconditions.if_match.as_deref())
+    })
+    .await;
+
+    match result {
+        Ok(patched_plan_dto) => HttpResponse::Ok()
+            .insert_header((header::ETAG, patched_plan_dto.etag()))
+            .json(patched_plan_dto),
         Err(TravelPlanError::NotFound) => HttpResponse::NotFound().json(ErrorResponse {
             error: "Travel plan not found".to_string(),
         }),
         Err(TravelPlanError::Unauthorized) => HttpResponse::Forbidden().json(ErrorResponse {
             error: "You don't have permission to update this travel plan".to_string(),
         }),
+        Err(TravelPlanError::PreconditionFailed) => precondition_failed(),
         Err(TravelPlanError::DatabaseError(e)) => {
             HttpResponse::InternalServerError().json(ErrorResponse {
                 error: format!("Database error: {}", e),
@@ -225,12 +304,14 @@ pub async fn update_travel_plan(
     delete,
     path = "/api/travelplan/{id}",
     params(
-        ("id" = String, Path, description = "Travel plan ID")
+        ("id" = String, Path, description = "Travel plan ID"),
+        ("If-Match" = Option<String>, Header, description = "ETag the caller last read")
     ),
     responses(
         (status = 204, description = "Travel plan deleted successfully"),
         (status = 403, description = "Unauthorized access", body = ErrorResponse),
         (status = 404, description = "Travel plan not found", body = ErrorResponse),
+        (status = 412, description = "The plan has changed", body = ErrorResponse),
         (status = 500, description = "Internal server error", body = ErrorResponse)
     ),
     security(
@@ -239,8 +320,9 @@ pub async fn update_travel_plan(
     tag = "travel_plans"
 )]
 pub async fn delete_travel_plan(
-    pool: web::Data<DbPool>,
+    repos: web::Data<dyn Repositories>,
     auth_user: AuthenticatedUser,
+    preconditions: Preconditions,
     path: web::Path<String>,
 ) -> impl Responder {
     let plan_id = path.into_inner();
@@ -249,16 +331,15 @@ pub async fn delete_travel_plan(
         plan_id, auth_user.username
     );
 
-    let conn = match pool.get() {
-        Ok(conn) => conn,
-        Err(e) => {
-            return HttpResponse::InternalServerError().json(ErrorResponse {
-                error: format!("Database connection error: {}", e),
-            });
-        }
-    };
+    let user_id = auth_user.user_id.clone();
+    let service = TravelPlanService::new(repos.into_inner());
+    let if_match = preconditions.if_match;
+    let result = blocking::run(move || {
+        service.delete_travel_plan(&plan_id, &user_id, if_match.as_deref())
+    })
+    .await;
 
-    match TravelPlanService::delete_travel_plan(&conn, &plan_id, &auth_user.user_id) {
+    match result {
         Ok(()) => HttpResponse::NoContent().finish(),
         Err(TravelPlanError::NotFound) => HttpResponse::NotFound().json(ErrorResponse {
             error: "Travel plan not found".to_string(),
@@ -266,6 +347,7 @@ pub async fn delete_travel_plan(
         Err(TravelPlanError::Unauthorized) => HttpResponse::Forbidden().json(ErrorResponse {
             error: "You don't have permission to delete this travel plan".to_string(),
         }),
+        Err(TravelPlanError::PreconditionFailed) => precondition_failed(),
         Err(TravelPlanError::DatabaseError(e)) => {
             HttpResponse::InternalServerError().json(ErrorResponse {
                 error: format!("Database error: {}", e),
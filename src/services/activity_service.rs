@@ -0,0 +1,199 @@
+use log::error;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Arc;
+use utoipa::ToSchema;
+
+use crate::models::plan_event::PlanEvent;
+use crate::models::plan_member::PlanRole;
+use crate::repositories::{
+    BudgetRepository, CommentRepository, ItineraryRepository, PlanEventRepository,
+    PlanLegRepository, PlanMemberRepository, PlanRevisionRepository, RepositoryError,
+    RouteOptionRepository, RouteVoteRepository, ShareLinkRepository, TravelPlanRepository,
+    UserRepository,
+};
+use crate::services::blocking::BlockingError;
+use crate::services::travel_plan_service::{TravelPlanError, TravelPlanService};
+
+/// Reads a plan's activity feed. The events themselves are written by the
+/// services that make the changes.
+pub struct ActivityService<R: ?Sized> {
+    repos: Arc<R>,
+    travel_plans: TravelPlanService<R>,
+}
+
+#[derive(Debug)]
+pub enum ActivityError {
+    TravelPlanError(TravelPlanError),
+    DatabaseError(String),
+}
+
+impl From<TravelPlanError> for ActivityError {
+    fn from(error: TravelPlanError) -> Self {
+        ActivityError::TravelPlanError(error)
+    }
+}
+
+impl From<BlockingError> for ActivityError {
+    fn from(error: BlockingError) -> Self {
+        ActivityError::DatabaseError(error.to_string())
+    }
+}
+
+impl From<RepositoryError> for ActivityError {
+    fn from(error: RepositoryError) -> Self {
+        error!("Activity repository error: {}", error);
+        ActivityError::DatabaseError(error.to_string())
+    }
+}
+
+/// An event with the name of the user who caused it. The name is empty when
+/// that account no longer exists.
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct PlanEventDto {
+    #[serde(flatten)]
+    pub event: PlanEvent,
+    pub actor_username: String,
+}
+
+impl<R> ActivityService<R>
+where
+    R: UserRepository
+        + TravelPlanRepository
+        + PlanMemberRepository
+        + ShareLinkRepository
+        + RouteOptionRepository
+        + RouteVoteRepository
+        + CommentRepository
+        + PlanEventRepository
+        + PlanRevisionRepository
+        + ItineraryRepository
+        + PlanLegRepository
+        + BudgetRepository
+        + ?Sized,
+{
+    pub fn new(repos: Arc<R>) -> Self {
+        ActivityService {
+            travel_plans: TravelPlanService::new(repos.clone()),
+            repos,
+        }
+    }
+
+    /// A page of the plan's events, newest first. Open to every member.
+    pub fn activity(
+        &self,
+        plan_id: &str,
+        user_id: &str,
+        limit: usize,
+        offset: usize,
+    ) -> Result<Vec<PlanEventDto>, ActivityError> {
+        self.travel_plans.authorize(plan_id, user_id, PlanRole::Viewer)?;
+
+        let mut usernames: HashMap<String, String> = HashMap::new();
+        let mut events = Vec::new();
+        for event in self.repos.find_plan_events(plan_id, limit, offset)? {
+            if !usernames.contains_key(&event.actor_id) {
+                let username = self
+                    .repos
+                    .find_user_by_id(&event.actor_id)?
+                    .map(|user| user.username)
+                    .unwrap_or_default();
+                usernames.insert(event.actor_id.clone(), username);
+            }
+            events.push(PlanEventDto {
+                actor_username: usernames[&event.actor_id].clone(),
+                event,
+            });
+        }
+
+        Ok(events)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::plan_event::PlanEventAction;
+    use crate::models::travel_plan::{NewTravelPlan, UpdateTravelPlan};
+    use crate::models::user::{NewUser, User};
+    use crate::repositories::UserRepository;
+    use crate::repositories::memory::InMemoryRepository;
+    use crate::services::route_option_service::RouteOptionService;
+    use serde_json::json;
+
+    #[test]
+    fn records_plan_changes_newest_first() {
+        let repos = Arc::new(InMemoryRepository::new());
+        let user = User::new(&NewUser {
+            username: "alice".to_string(),
+            password: "password123".to_string(),
+            email: "alice@example.com".to_string(),
+        })
+        .unwrap();
+        repos.insert_user(&user).unwrap();
+
+        let plans = TravelPlanService::new(repos.clone());
+        let plan_id = plans
+            .create_travel_plan(
+                &NewTravelPlan {
+                    name: "Road trip".to_string(),
+                    description: None,
+                    start_location: "Amsterdam".to_string(),
+                    end_location: "Berlin".to_string(),
+                    start_date: None,
+                    end_date: None,
+                    home_currency: None,
+                },
+                &user.id,
+            )
+            .unwrap()
+            .travel_plan
+            .id;
+        plans
+            .update_travel_plan(
+                &plan_id,
+                &UpdateTravelPlan {
+                    name: Some("Rhine trip".to_string()),
+                    description: None,
+                    start_location: Some("Amsterdam".to_string()),
+                    end_location: None,
+                    start_date: None,
+                    end_date: None,
+                    home_currency: None,
+                },
+                &user.id,
+                None,
+            )
+            .unwrap();
+        RouteOptionService::new(repos.clone())
+            .generate_route_options(&plan_id, &user.id, 2)
+            .unwrap();
+
+        let activity = ActivityService::new(repos);
+        let events = activity.activity(&plan_id, &user.id, 10, 0).unwrap();
+        let actions: Vec<_> = events.iter().map(|e| e.event.action).collect();
+        assert_eq!(
+            actions,
+            vec![
+                PlanEventAction::RoutesGenerated,
+                PlanEventAction::PlanUpdated,
+                PlanEventAction::PlanCreated,
+            ]
+        );
+        assert_eq!(events[0].actor_username, "alice");
+        assert_eq!(events[0].event.changes["routes"]["to"].as_array().unwrap().len(), 2);
+        // Unchanged fields are left out of the diff
+        assert_eq!(
+            events[1].event.changes,
+            json!({ "name": { "from": "Road trip", "to": "Rhine trip" } })
+        );
+
+        let page = activity.activity(&plan_id, &user.id, 1, 2).unwrap();
+        assert_eq!(page[0].event.action, PlanEventAction::PlanCreated);
+        assert!(matches!(
+            activity.activity(&plan_id, "mallory", 10, 0),
+            Err(ActivityError::TravelPlanError(TravelPlanError::Unauthorized))
+        ));
+    }
+}
@@ -0,0 +1,380 @@
+use log::{error, info};
+use std::fmt;
+use std::sync::Arc;
+
+use crate::db::maintenance::{OrphanReport, TableCount};
+use crate::models::audit::{Actor, AuditEntry};
+use crate::models::travel_plan::TravelPlan;
+use crate::models::user::{NewUser, Role, User};
+use crate::repositories::{
+    AuditRepository, MaintenanceRepository, RepositoryError, TravelPlanRepository,
+    UserRepository,
+};
+use crate::services::blocking::BlockingError;
+
+/// Operator tasks behind the `travel-admin` binary and the `/api/admin`
+/// endpoints. Every change, and every look at another user's data, is
+/// written to the audit log under the acting admin's name.
+pub struct AdminService<R: ?Sized> {
+    repos: Arc<R>,
+    actor: Actor,
+}
+
+#[derive(Debug)]
+pub enum AdminError {
+    UserNotFound(String),
+    UsernameTaken(String),
+    InvalidInput(String),
+    DatabaseError(String),
+}
+
+impl fmt::Display for AdminError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AdminError::UserNotFound(username) => write!(f, "User not found: {}", username),
+            AdminError::UsernameTaken(username) => write!(f, "Username already exists: {}", username),
+            AdminError::InvalidInput(e) => write!(f, "Invalid input: {}", e),
+            AdminError::DatabaseError(e) => write!(f, "Database error: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for AdminError {}
+
+impl From<BlockingError> for AdminError {
+    fn from(error: BlockingError) -> Self {
+        AdminError::DatabaseError(error.to_string())
+    }
+}
+
+impl From<RepositoryError> for AdminError {
+    fn from(error: RepositoryError) -> Self {
+        error!("Admin repository error: {}", error);
+        AdminError::DatabaseError(error.to_string())
+    }
+}
+
+impl<R> AdminService<R>
+where
+    R: UserRepository + TravelPlanRepository + MaintenanceRepository + AuditRepository + ?Sized,
+{
+    /// A service acting as the operator CLI.
+    pub fn new(repos: Arc<R>) -> Self {
+        AdminService {
+            repos,
+            actor: Actor::system("travel-admin"),
+        }
+    }
+
+    pub fn acting_as(mut self, actor: Actor) -> Self {
+        self.actor = actor;
+        self
+    }
+
+    fn audit(
+        &self,
+        action: &str,
+        target_type: &str,
+        target_id: Option<&str>,
+        details: Option<String>,
+    ) -> Result<(), AdminError> {
+        let entry = AuditEntry::new(&self.actor, action, target_type, target_id, details);
+        self.repos.insert_audit_entry(&entry)?;
+        Ok(())
+    }
+
+    /// Admins may not lock or demote themselves, so there is always someone
+    /// left who can undo a mistake.
+    fn check_not_self(&self, user: &User, what: &str) -> Result<(), AdminError> {
+        if self.actor.user_id.as_deref() == Some(user.id.as_str()) {
+            return Err(AdminError::InvalidInput(format!(
+                "you cannot {} your own account",
+                what
+            )));
+        }
+        Ok(())
+    }
+
+    fn find_user(&self, username: &str) -> Result<User, AdminError> {
+        self.repos
+            .find_user_by_username(username)?
+            .ok_or_else(|| AdminError::UserNotFound(username.to_string()))
+    }
+
+    fn check_password(password: &str) -> Result<(), AdminError> {
+        if password.is_empty() {
+            return Err(AdminError::InvalidInput("password must not be empty".to_string()));
+        }
+        Ok(())
+    }
+
+    pub fn schema_version(&self) -> Result<usize, AdminError> {
+        Ok(self.repos.schema_version()?)
+    }
+
+    pub fn create_user(&self, new_user: &NewUser) -> Result<User, AdminError> {
+        if new_user.username.trim().is_empty() || new_user.email.trim().is_empty() {
+            return Err(AdminError::InvalidInput(
+                "username and email must not be empty".to_string(),
+            ));
+        }
+        Self::check_password(&new_user.password)?;
+
+        if self.repos.find_user_by_username(&new_user.username)?.is_some() {
+            return Err(AdminError::UsernameTaken(new_user.username.clone()));
+        }
+
+        let user = User::new(new_user).map_err(|e| AdminError::InvalidInput(e.to_string()))?;
+        self.repos.insert_user(&user)?;
+        self.audit("user.create", "user", Some(&user.id), Some(user.username.clone()))?;
+
+        info!("Admin created user: {}", user.username);
+        Ok(user)
+    }
+
+    pub fn list_users(&self) -> Result<Vec<User>, AdminError> {
+        let users = self.repos.list_users()?;
+        self.audit("user.list", "user", None, None)?;
+        Ok(users)
+    }
+
+    pub fn set_user_disabled(&self, username: &str, disabled: bool) -> Result<User, AdminError> {
+        let mut user = self.find_user(username)?;
+        if disabled {
+            self.check_not_self(&user, "lock")?;
+        }
+        self.repos.set_user_disabled(&user.id, disabled)?;
+        user.disabled = disabled;
+        self.audit(
+            if disabled { "user.lock" } else { "user.unlock" },
+            "user",
+            Some(&user.id),
+            Some(user.username.clone()),
+        )?;
+
+        info!(
+            "Admin {} user: {}",
+            if disabled { "disabled" } else { "enabled" },
+            username
+        );
+        Ok(user)
+    }
+
+    pub fn reset_password(&self, username: &str, password: &str) -> Result<User, AdminError> {
+        Self::check_password(password)?;
+        let user = self.find_user(username)?;
+
+        let password_hash =
+            User::hash_password(password).map_err(|e| AdminError::InvalidInput(e.to_string()))?;
+        self.repos.update_user_password(&user.id, &password_hash)?;
+        self.audit("user.reset_password", "user", Some(&user.id), Some(user.username.clone()))?;
+
+        info!("Admin reset password for user: {}", username);
+        Ok(user)
+    }
+
+    pub fn set_user_role(&self, username: &str, role: Role) -> Result<User, AdminError> {
+        let mut user = self.find_user(username)?;
+        if role != Role::Admin {
+            self.check_not_self(&user, "demote")?;
+        }
+        self.repos.set_user_role(&user.id, role)?;
+        self.audit(
+            "user.set_role",
+            "user",
+            Some(&user.id),
+            Some(format!("{}: {} -> {}", user.username, user.role, role)),
+        )?;
+        user.role = role;
+
+        info!("Admin set role of user {} to {}", username, role);
+        Ok(user)
+    }
+
+    pub fn list_user_plans(&self, username: &str) -> Result<Vec<TravelPlan>, AdminError> {
+        let user = self.find_user(username)?;
+        let plans = self.repos.find_travel_plans_by_user(&user.id)?;
+        self.audit("user.list_plans", "user", Some(&user.id), Some(user.username.clone()))?;
+        Ok(plans)
+    }
+
+    pub fn audit_log(&self, limit: usize, offset: usize) -> Result<Vec<AuditEntry>, AdminError> {
+        Ok(self.repos.find_audit_entries(limit, offset)?)
+    }
+
+    pub fn count_rows(&self) -> Result<Vec<TableCount>, AdminError> {
+        Ok(self.repos.count_rows()?)
+    }
+
+    pub fn purge_orphans(&self) -> Result<OrphanReport, AdminError> {
+        let report = self.repos.purge_orphans()?;
+        self.audit(
+            "maintenance.purge_orphans",
+            "database",
+            None,
+            Some(format!("{} rows removed", report.total())),
+        )?;
+        Ok(report)
+    }
+
+    pub fn vacuum(&self) -> Result<(), AdminError> {
+        self.repos.vacuum()?;
+        self.audit("maintenance.vacuum", "database", None, None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::route_option::RouteOption;
+    use crate::models::travel_plan::NewTravelPlan;
+    use crate::repositories::RouteOptionRepository;
+    use crate::repositories::memory::InMemoryRepository;
+
+    fn new_user(username: &str) -> NewUser {
+        NewUser {
+            username: username.to_string(),
+            password: "password123".to_string(),
+            email: format!("{}@example.com", username),
+        }
+    }
+
+    fn plan_for(user_id: &str) -> TravelPlan {
+        TravelPlan::new(
+            &NewTravelPlan {
+                name: "Road trip".to_string(),
+                description: None,
+                start_location: "Amsterdam".to_string(),
+                end_location: "Berlin".to_string(),
+                start_date: None,
+                end_date: None,
+                home_currency: None,
+            },
+            user_id,
+        )
+    }
+
+    fn setup() -> (Arc<InMemoryRepository>, AdminService<InMemoryRepository>) {
+        let repos = Arc::new(InMemoryRepository::new());
+        (repos.clone(), AdminService::new(repos))
+    }
+
+    #[test]
+    fn creates_users_once() {
+        let (_, service) = setup();
+
+        service.create_user(&new_user("alice")).unwrap();
+
+        assert!(matches!(
+            service.create_user(&new_user("alice")),
+            Err(AdminError::UsernameTaken(_))
+        ));
+        assert!(matches!(
+            service.create_user(&NewUser {
+                password: String::new(),
+                ..new_user("bob")
+            }),
+            Err(AdminError::InvalidInput(_))
+        ));
+    }
+
+    #[test]
+    fn disables_and_resets_password() {
+        let (repos, service) = setup();
+        service.create_user(&new_user("alice")).unwrap();
+
+        assert!(service.set_user_disabled("alice", true).unwrap().disabled);
+        service.reset_password("alice", "new-secret").unwrap();
+
+        let stored = repos.find_user_by_username("alice").unwrap().unwrap();
+        assert!(stored.disabled);
+        assert!(stored.verify_password("new-secret"));
+        assert!(!stored.verify_password("password123"));
+
+        assert!(matches!(
+            service.set_user_disabled("nobody", true),
+            Err(AdminError::UserNotFound(_))
+        ));
+    }
+
+    #[test]
+    fn audits_changes_under_the_acting_admin() {
+        let (repos, service) = setup();
+        let admin = service.create_user(&new_user("root")).unwrap();
+        service.create_user(&new_user("alice")).unwrap();
+
+        let service = service.acting_as(Actor {
+            user_id: Some(admin.id.clone()),
+            username: admin.username.clone(),
+            role: Role::Admin,
+        });
+        assert_eq!(service.set_user_role("alice", Role::Support).unwrap().role, Role::Support);
+        assert_eq!(repos.find_user_by_username("alice").unwrap().unwrap().role, Role::Support);
+
+        // Admins cannot lock or demote themselves
+        assert!(matches!(
+            service.set_user_disabled("root", true),
+            Err(AdminError::InvalidInput(_))
+        ));
+        assert!(matches!(
+            service.set_user_role("root", Role::User),
+            Err(AdminError::InvalidInput(_))
+        ));
+
+        let entries = service.audit_log(10, 0).unwrap();
+        let trail: Vec<(&str, &str)> = entries
+            .iter()
+            .map(|e| (e.actor_username.as_str(), e.action.as_str()))
+            .collect();
+        assert_eq!(
+            trail,
+            [
+                ("root", "user.set_role"),
+                ("travel-admin", "user.create"),
+                ("travel-admin", "user.create"),
+            ]
+        );
+        assert_eq!(entries[0].details.as_deref(), Some("alice: user -> support"));
+    }
+
+    #[test]
+    fn lists_plans_of_one_user() {
+        let (repos, service) = setup();
+        let alice = service.create_user(&new_user("alice")).unwrap();
+        let bob = service.create_user(&new_user("bob")).unwrap();
+        repos.insert_travel_plan(&plan_for(&alice.id)).unwrap();
+        repos.insert_travel_plan(&plan_for(&bob.id)).unwrap();
+
+        let plans = service.list_user_plans("alice").unwrap();
+
+        assert_eq!(plans.len(), 1);
+        assert_eq!(plans[0].user_id, alice.id);
+    }
+
+    #[test]
+    fn purges_orphans_transitively() {
+        let (repos, service) = setup();
+        let alice = service.create_user(&new_user("alice")).unwrap();
+        let kept = plan_for(&alice.id);
+        let orphan = plan_for("deleted-user");
+        repos.insert_travel_plan(&kept).unwrap();
+        repos.insert_travel_plan(&orphan).unwrap();
+        for route in RouteOption::generate_random_options(&orphan, 2) {
+            repos.insert_route_option(&route).unwrap();
+        }
+        for route in RouteOption::generate_random_options(&kept, 1) {
+            repos.insert_route_option(&route).unwrap();
+        }
+
+        let report = service.purge_orphans().unwrap();
+
+        assert_eq!(report.travel_plans, 1);
+        assert_eq!(report.route_options, 2);
+        let counts = service.count_rows().unwrap();
+        let rows: Vec<(&str, i64)> = counts.iter().map(|c| (c.table.as_str(), c.rows)).collect();
+        assert_eq!(
+            rows,
+            [("users", 1), ("travel_plans", 1), ("route_options", 1), ("points_of_interest", 0)]
+        );
+    }
+}
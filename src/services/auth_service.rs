@@ -1,25 +1,43 @@
-use rusqlite::Connection;
 use log::{error, info};
+use std::sync::Arc;
 
 use crate::middleware::auth::generate_token;
 use crate::models::user::{LoginCredentials, NewUser, User};
+use crate::repositories::UserRepository;
+use crate::services::blocking::BlockingError;
 
-pub struct AuthService;
+pub struct AuthService<R: ?Sized> {
+    repos: Arc<R>,
+}
 
 #[derive(Debug)]
 pub enum AuthError {
     UsernameTaken,
     InvalidCredentials,
+    AccountDisabled,
     DatabaseError(String),
     TokenGenerationError(String),
 }
 
-impl AuthService {
-    pub fn register(conn: &Connection, user_data: &NewUser) -> Result<User, AuthError> {
+impl From<BlockingError> for AuthError {
+    fn from(error: BlockingError) -> Self {
+        AuthError::DatabaseError(error.to_string())
+    }
+}
+
+impl<R> AuthService<R>
+where
+    R: UserRepository + ?Sized,
+{
+    pub fn new(repos: Arc<R>) -> Self {
+        AuthService { repos }
+    }
+
+    pub fn register(&self, user_data: &NewUser) -> Result<User, AuthError> {
         info!("Registering new user: {}", user_data.username);
-        
+
         // Check if username already exists
-        match User::find_by_username(conn, &user_data.username) {
+        match self.repos.find_user_by_username(&user_data.username) {
             Ok(Some(_)) => {
                 info!("Username already exists: {}", user_data.username);
                 return Err(AuthError::UsernameTaken);
@@ -30,10 +48,18 @@ impl AuthService {
                 return Err(AuthError::DatabaseError(e.to_string()));
             }
         }
-        
+
         // Create new user
-        match User::create(conn, user_data) {
-            Ok(user) => {
+        let user = match User::new(user_data) {
+            Ok(user) => user,
+            Err(e) => {
+                error!("Error hashing password: {}", e);
+                return Err(AuthError::DatabaseError(e.to_string()));
+            }
+        };
+
+        match self.repos.insert_user(&user) {
+            Ok(()) => {
                 info!("User registered successfully: {}", user.username);
                 Ok(user)
             }
@@ -43,33 +69,105 @@ impl AuthService {
             }
         }
     }
-    
-    pub fn login(conn: &Connection, credentials: &LoginCredentials) -> Result<(User, String, i64), AuthError> {
+
+    pub fn login(&self, credentials: &LoginCredentials) -> Result<(User, String, i64), AuthError> {
         info!("Authenticating user: {}", credentials.username);
-        
+
         // Authenticate user
-        match User::authenticate(conn, credentials) {
-            Ok(Some(user)) => {
-                // Generate JWT token
-                match generate_token(&user) {
-                    Ok(token) => {
-                        info!("User logged in successfully: {}", user.username);
-                        Ok((user, token.token, token.expires_in))
-                    }
-                    Err(e) => {
-                        error!("Error generating token: {}", e);
-                        Err(AuthError::TokenGenerationError(e.to_string()))
-                    }
-                }
-            }
-            Ok(None) => {
+        let user = match self.repos.find_user_by_username(&credentials.username) {
+            Ok(Some(user)) if user.verify_password(&credentials.password) => user,
+            Ok(_) => {
                 info!("Login failed for user: {}", credentials.username);
-                Err(AuthError::InvalidCredentials)
+                return Err(AuthError::InvalidCredentials);
             }
             Err(e) => {
                 error!("Database error during authentication: {}", e);
-                Err(AuthError::DatabaseError(e.to_string()))
+                return Err(AuthError::DatabaseError(e.to_string()));
             }
+        };
+
+        // Only tell the right password holder that the account is disabled
+        if user.disabled {
+            info!("Login rejected for disabled user: {}", credentials.username);
+            return Err(AuthError::AccountDisabled);
+        }
+
+        // Generate JWT token
+        match generate_token(&user) {
+            Ok(token) => {
+                info!("User logged in successfully: {}", user.username);
+                Ok((user, token.token, token.expires_in))
+            }
+            Err(e) => {
+                error!("Error generating token: {}", e);
+                Err(AuthError::TokenGenerationError(e.to_string()))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::repositories::memory::InMemoryRepository;
+
+    fn new_user() -> NewUser {
+        NewUser {
+            username: "testuser".to_string(),
+            password: "password123".to_string(),
+            email: "test@example.com".to_string(),
         }
     }
-}
\ No newline at end of file
+
+    fn credentials(password: &str) -> LoginCredentials {
+        LoginCredentials {
+            username: "testuser".to_string(),
+            password: password.to_string(),
+        }
+    }
+
+    #[test]
+    fn registers_user_once() {
+        let service = AuthService::new(Arc::new(InMemoryRepository::new()));
+
+        let user = service.register(&new_user()).unwrap();
+
+        assert_ne!(user.password_hash, "password123");
+        assert!(matches!(
+            service.register(&new_user()),
+            Err(AuthError::UsernameTaken)
+        ));
+    }
+
+    #[test]
+    fn logs_in_with_correct_password_only() {
+        let service = AuthService::new(Arc::new(InMemoryRepository::new()));
+        let user = service.register(&new_user()).unwrap();
+
+        let (logged_in, token, _) = service.login(&credentials("password123")).unwrap();
+
+        assert_eq!(logged_in.id, user.id);
+        assert!(!token.is_empty());
+        assert!(matches!(
+            service.login(&credentials("wrongpassword")),
+            Err(AuthError::InvalidCredentials)
+        ));
+    }
+
+    #[test]
+    fn rejects_disabled_accounts() {
+        let repos = Arc::new(InMemoryRepository::new());
+        let service = AuthService::new(repos.clone());
+        let user = service.register(&new_user()).unwrap();
+        repos.set_user_disabled(&user.id, true).unwrap();
+
+        assert!(matches!(
+            service.login(&credentials("password123")),
+            Err(AuthError::AccountDisabled)
+        ));
+        assert!(matches!(
+            service.login(&credentials("wrongpassword")),
+            Err(AuthError::InvalidCredentials)
+        ));
+    }
+}
@@ -0,0 +1,289 @@
+use log::{error, info, warn};
+use serde::Serialize;
+use std::fmt;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+use utoipa::ToSchema;
+
+use crate::db::backup::{self, BackupInfo, RetentionPolicy};
+use crate::models::audit::{Actor, AuditEntry};
+use crate::repositories::{AuditRepository, MaintenanceRepository, RepositoryError};
+use crate::services::blocking::BlockingError;
+
+pub const DEFAULT_BACKUP_INTERVAL_MINUTES: u64 = 60;
+pub const DEFAULT_BACKUP_KEEP: usize = 24;
+
+#[derive(Debug, Clone)]
+pub struct BackupConfig {
+    pub dir: PathBuf,
+    /// Time between scheduled snapshots; `None` only allows manual backups.
+    pub interval: Option<Duration>,
+    pub retention: RetentionPolicy,
+}
+
+impl BackupConfig {
+    /// Reads `BACKUP_DIR`, `BACKUP_INTERVAL_MINUTES` (0 disables the
+    /// schedule), `BACKUP_KEEP` and `BACKUP_MAX_AGE_DAYS`. Backups are off
+    /// unless `BACKUP_DIR` is set.
+    pub fn from_env() -> Option<Self> {
+        let dir = std::env::var("BACKUP_DIR").ok().filter(|dir| !dir.is_empty())?;
+
+        let interval_minutes = env_number("BACKUP_INTERVAL_MINUTES")
+            .unwrap_or(DEFAULT_BACKUP_INTERVAL_MINUTES);
+        let keep_last = env_number("BACKUP_KEEP")
+            .map(|keep| keep as usize)
+            .unwrap_or(DEFAULT_BACKUP_KEEP);
+        let max_age = env_number("BACKUP_MAX_AGE_DAYS").map(|days| chrono::Duration::days(days as i64));
+
+        Some(BackupConfig {
+            dir: PathBuf::from(dir),
+            interval: (interval_minutes > 0).then(|| Duration::from_secs(interval_minutes * 60)),
+            retention: RetentionPolicy { keep_last, max_age },
+        })
+    }
+}
+
+fn env_number(name: &str) -> Option<u64> {
+    std::env::var(name).ok().and_then(|value| value.parse().ok())
+}
+
+#[derive(Debug)]
+pub enum BackupServiceError {
+    NotConfigured,
+    Unsupported(String),
+    BackupFailed(String),
+}
+
+impl fmt::Display for BackupServiceError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BackupServiceError::NotConfigured => write!(f, "Backups are not configured"),
+            BackupServiceError::Unsupported(e) => write!(f, "{}", e),
+            BackupServiceError::BackupFailed(e) => write!(f, "Backup failed: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for BackupServiceError {}
+
+impl From<BlockingError> for BackupServiceError {
+    fn from(error: BlockingError) -> Self {
+        BackupServiceError::BackupFailed(error.to_string())
+    }
+}
+
+impl From<RepositoryError> for BackupServiceError {
+    fn from(error: RepositoryError) -> Self {
+        match error {
+            RepositoryError::Unsupported(e) => BackupServiceError::Unsupported(e),
+            e => BackupServiceError::BackupFailed(e.to_string()),
+        }
+    }
+}
+
+impl From<backup::BackupError> for BackupServiceError {
+    fn from(error: backup::BackupError) -> Self {
+        BackupServiceError::BackupFailed(error.to_string())
+    }
+}
+
+/// A snapshot plus the old ones retention removed after it.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct BackupRun {
+    pub backup: BackupInfo,
+    pub removed: Vec<BackupInfo>,
+}
+
+pub struct BackupService<R: ?Sized> {
+    repos: Arc<R>,
+    config: BackupConfig,
+}
+
+impl<R> BackupService<R>
+where
+    R: MaintenanceRepository + AuditRepository + ?Sized,
+{
+    pub fn new(repos: Arc<R>, config: BackupConfig) -> Self {
+        BackupService { repos, config }
+    }
+
+    pub fn run_backup(&self, actor: &Actor) -> Result<BackupRun, BackupServiceError> {
+        let backup = self.repos.backup_to(&self.config.dir)?;
+        let removed = backup::apply_retention(&self.config.dir, &self.config.retention)?;
+
+        self.repos.insert_audit_entry(&AuditEntry::new(
+            actor,
+            "backup.create",
+            "backup",
+            Some(&backup.file_name),
+            Some(format!("{} expired backups removed", removed.len())),
+        ))?;
+
+        info!(
+            "Backup {} complete, {} expired backups removed",
+            backup.file_name,
+            removed.len()
+        );
+        Ok(BackupRun { backup, removed })
+    }
+
+    pub fn list_backups(&self) -> Result<Vec<BackupInfo>, BackupServiceError> {
+        Ok(backup::list_backups(&self.config.dir)?)
+    }
+}
+
+impl<R> BackupService<R>
+where
+    R: MaintenanceRepository + AuditRepository + Send + Sync + ?Sized + 'static,
+{
+    /// Takes a snapshot every configured interval on a dedicated thread.
+    /// Does nothing when the schedule is disabled.
+    pub fn spawn_scheduler(self: Arc<Self>) {
+        let Some(interval) = self.config.interval else {
+            info!("Scheduled backups disabled");
+            return;
+        };
+
+        info!(
+            "Scheduling backups to {} every {} minutes",
+            self.config.dir.display(),
+            interval.as_secs() / 60
+        );
+
+        let spawned = thread::Builder::new()
+            .name("backup-scheduler".to_string())
+            .spawn(move || {
+                let scheduler = Actor::system("backup-scheduler");
+                loop {
+                    thread::sleep(interval);
+                    match self.run_backup(&scheduler) {
+                        Ok(_) => {}
+                        Err(BackupServiceError::Unsupported(e)) => {
+                            warn!("Stopping scheduled backups: {}", e);
+                            return;
+                        }
+                        Err(e) => error!("Scheduled backup failed: {}", e),
+                    }
+                }
+            });
+
+        if let Err(e) = spawned {
+            error!("Could not start backup scheduler: {}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::connection::create_pool;
+    use crate::models::user::{NewUser, User};
+    use crate::repositories::{AuditRepository, UserRepository};
+    use crate::repositories::memory::InMemoryRepository;
+    use crate::repositories::sqlite::SqliteRepository;
+    use uuid::Uuid;
+
+    fn operator() -> Actor {
+        Actor::system("test")
+    }
+
+    fn scratch_dir() -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("travel-api-backup-{}", Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn config(dir: PathBuf, keep_last: usize) -> BackupConfig {
+        BackupConfig {
+            dir,
+            interval: None,
+            retention: RetentionPolicy {
+                keep_last,
+                max_age: None,
+            },
+        }
+    }
+
+    fn user(username: &str) -> User {
+        User::new(&NewUser {
+            username: username.to_string(),
+            password: "password123".to_string(),
+            email: format!("{}@example.com", username),
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn backs_up_restores_and_applies_retention() {
+        let scratch = scratch_dir();
+        let db_path = scratch.join("live.db");
+        let repos = Arc::new(SqliteRepository::new(
+            create_pool(db_path.to_str().unwrap()).unwrap(),
+        ));
+        let service = BackupService::new(repos.clone(), config(scratch.join("backups"), 2));
+
+        repos.insert_user(&user("before")).unwrap();
+        let first = service.run_backup(&operator()).unwrap().backup;
+        assert_eq!(repos.find_audit_entries(10, 0).unwrap()[0].action, "backup.create");
+        repos.insert_user(&user("after")).unwrap();
+        service.run_backup(&operator()).unwrap();
+        let third = service.run_backup(&operator()).unwrap();
+
+        // Only the two newest snapshots survive
+        assert_eq!(third.removed.len(), 1);
+        assert_eq!(third.removed[0].file_name, first.file_name);
+        assert_eq!(service.list_backups().unwrap().len(), 2);
+        assert_eq!(
+            backup::verify_backup(&third.backup.path).unwrap(),
+            crate::db::schema::SCHEMA_VERSION
+        );
+
+        // Restoring the surviving older snapshot brings back its contents
+        drop(service);
+        drop(repos);
+        let older = &backup::list_backups(&scratch.join("backups")).unwrap()[1];
+        let saved = backup::restore_backup(&older.path, &db_path).unwrap();
+        assert!(saved.unwrap().exists());
+
+        let restored = SqliteRepository::new(create_pool(db_path.to_str().unwrap()).unwrap());
+        assert!(restored.find_user_by_username("before").unwrap().is_some());
+        assert!(restored.find_user_by_username("after").unwrap().is_some());
+
+        std::fs::remove_dir_all(scratch).unwrap();
+    }
+
+    #[test]
+    fn refuses_to_restore_damaged_or_foreign_files() {
+        let scratch = scratch_dir();
+        let garbage = scratch.join("travel_api-20300101T000000000Z.db");
+        std::fs::write(&garbage, b"definitely not sqlite").unwrap();
+        let foreign = scratch.join("foreign.db");
+        rusqlite::Connection::open(&foreign)
+            .unwrap()
+            .execute_batch("CREATE TABLE other (id INTEGER)")
+            .unwrap();
+        let target = scratch.join("live.db");
+
+        assert!(backup::restore_backup(&garbage, &target).is_err());
+        assert!(backup::restore_backup(&foreign, &target).is_err());
+        assert!(backup::restore_backup(&scratch.join("missing.db"), &target).is_err());
+        assert!(!target.exists());
+
+        std::fs::remove_dir_all(scratch).unwrap();
+    }
+
+    #[test]
+    fn reports_unsupported_backends() {
+        let service = BackupService::new(
+            Arc::new(InMemoryRepository::new()),
+            config(std::env::temp_dir(), 1),
+        );
+
+        assert!(matches!(
+            service.run_backup(&operator()),
+            Err(BackupServiceError::Unsupported(_))
+        ));
+    }
+}
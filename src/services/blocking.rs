@@ -0,0 +1,49 @@
+use actix_web::web;
+use log::error;
+use std::fmt;
+
+/// Default number of blocking threads each actix worker may spawn for
+/// database and password hashing work.
+pub const DEFAULT_BLOCKING_THREADS: usize = 16;
+
+#[derive(Debug)]
+pub enum BlockingError {
+    Canceled,
+}
+
+impl fmt::Display for BlockingError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BlockingError::Canceled => write!(f, "Blocking task was canceled"),
+        }
+    }
+}
+
+impl std::error::Error for BlockingError {}
+
+/// Number of blocking threads per worker, read from `BLOCKING_THREADS`.
+pub fn blocking_threads() -> usize {
+    std::env::var("BLOCKING_THREADS")
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .filter(|threads| *threads > 0)
+        .unwrap_or(DEFAULT_BLOCKING_THREADS)
+}
+
+/// Runs a service call on the worker's bounded blocking thread pool, so the
+/// synchronous repository and bcrypt work it does never stalls the async
+/// executor.
+pub async fn run<F, T, E>(f: F) -> Result<T, E>
+where
+    F: FnOnce() -> Result<T, E> + Send + 'static,
+    T: Send + 'static,
+    E: From<BlockingError> + Send + 'static,
+{
+    match web::block(f).await {
+        Ok(result) => result,
+        Err(e) => {
+            error!("Blocking task failed: {}", e);
+            Err(E::from(BlockingError::Canceled))
+        }
+    }
+}
@@ -0,0 +1,629 @@
+use log::{error, info};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use std::sync::Arc;
+use utoipa::ToSchema;
+
+use crate::models::budget::{Budget, Expense, ExpenseCategory, NewExpense, PatchExpense, SetBudget};
+use crate::models::currency::CurrencyCode;
+use crate::models::plan_member::{MemberStatus, PlanRole};
+use crate::models::travel_plan::TravelPlan;
+use crate::repositories::{
+    BudgetRepository, CommentRepository, ItineraryRepository, PlanEventRepository,
+    PlanLegRepository, PlanMemberRepository, PlanRevisionRepository, PoiRepository, RepositoryError,
+    RouteOptionRepository, RouteVoteRepository, ShareLinkRepository, TravelPlanRepository,
+    UserRepository,
+};
+use crate::services::blocking::BlockingError;
+use crate::services::currency::{CurrencyError, ExchangeRates};
+use crate::services::travel_plan_service::{TravelPlanError, TravelPlanService};
+
+/// Budgets per category and the expenses members record against them.
+/// Members can read them; editors change them.
+pub struct BudgetService<R: ?Sized> {
+    repos: Arc<R>,
+    travel_plans: TravelPlanService<R>,
+}
+
+#[derive(Debug)]
+pub enum BudgetError {
+    TravelPlanError(TravelPlanError),
+    BudgetNotFound,
+    ExpenseNotFound,
+    InvalidInput(String),
+    /// An amount couldn't be converted to the plan's home currency.
+    CurrencyError(CurrencyError),
+    DatabaseError(String),
+}
+
+impl From<TravelPlanError> for BudgetError {
+    fn from(error: TravelPlanError) -> Self {
+        BudgetError::TravelPlanError(error)
+    }
+}
+
+impl From<CurrencyError> for BudgetError {
+    fn from(error: CurrencyError) -> Self {
+        BudgetError::CurrencyError(error)
+    }
+}
+
+impl From<BlockingError> for BudgetError {
+    fn from(error: BlockingError) -> Self {
+        BudgetError::DatabaseError(error.to_string())
+    }
+}
+
+impl From<RepositoryError> for BudgetError {
+    fn from(error: RepositoryError) -> Self {
+        error!("Budget repository error: {}", error);
+        BudgetError::DatabaseError(error.to_string())
+    }
+}
+
+/// Budget against spending for one category in one currency. Amounts are
+/// in minor units.
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct CategorySummary {
+    pub category: ExpenseCategory,
+    pub currency: String,
+    /// `None` when the category has no budget in this currency.
+    pub budgeted: Option<i64>,
+    pub spent: i64,
+    pub remaining: Option<i64>,
+}
+
+/// What one member paid and owes in one currency. A positive balance is
+/// owed to them.
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct MemberBalance {
+    pub user_id: String,
+    pub username: String,
+    pub currency: String,
+    pub paid: i64,
+    pub share: i64,
+    pub balance: i64,
+}
+
+/// One payment that, with the others, squares every balance.
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct Settlement {
+    pub from_user_id: String,
+    pub from_username: String,
+    pub to_user_id: String,
+    pub to_username: String,
+    pub amount: i64,
+    pub currency: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct BudgetSummary {
+    pub travel_plan_id: String,
+    /// The plan's home currency. When set, every amount is in it.
+    pub home_currency: Option<String>,
+    pub categories: Vec<CategorySummary>,
+    pub balances: Vec<MemberBalance>,
+    /// Who owes whom, per currency.
+    pub settlements: Vec<Settlement>,
+}
+
+/// Upper-cases an ISO 4217 code, or says why it isn't one.
+fn currency_code(currency: &str) -> Result<String, BudgetError> {
+    CurrencyCode::parse(currency)
+        .map(|code| code.to_string())
+        .map_err(BudgetError::InvalidInput)
+}
+
+/// Restates budgets and expenses in `home`: an expense at the rate for the
+/// day it was spent, a budget at the rate for the day it was last set.
+fn convert_to(
+    home: &CurrencyCode,
+    rates: &ExchangeRates,
+    budgets: &mut [Budget],
+    expenses: &mut [Expense],
+) -> Result<(), BudgetError> {
+    let code = |currency: &str| CurrencyCode::parse(currency).map_err(BudgetError::InvalidInput);
+    for budget in budgets.iter_mut() {
+        let date = budget.updated_at.date_naive();
+        budget.amount = rates.convert(budget.amount, &code(&budget.currency)?, home, date)?;
+        budget.currency = home.to_string();
+    }
+    for expense in expenses.iter_mut() {
+        let from = code(&expense.currency)?;
+        expense.amount = rates.convert(expense.amount, &from, home, expense.spent_on)?;
+        expense.currency = home.to_string();
+    }
+    Ok(())
+}
+
+/// Splits `amount` as evenly as minor units allow; the first participants
+/// take the odd units.
+fn split_amount(amount: i64, participants: &[String]) -> Vec<(&str, i64)> {
+    let count = participants.len() as i64;
+    if count == 0 {
+        return Vec::new();
+    }
+    let (base, remainder) = (amount / count, amount % count);
+    participants
+        .iter()
+        .enumerate()
+        .map(|(index, user_id)| {
+            let extra = if (index as i64) < remainder { 1 } else { 0 };
+            (user_id.as_str(), base + extra)
+        })
+        .collect()
+}
+
+/// `(user_id, currency) -> (paid, share)` over every expense.
+fn tally(expenses: &[Expense]) -> BTreeMap<(String, String), (i64, i64)> {
+    let mut totals: BTreeMap<(String, String), (i64, i64)> = BTreeMap::new();
+    for expense in expenses {
+        let key = (expense.paid_by.clone(), expense.currency.clone());
+        totals.entry(key).or_default().0 += expense.amount;
+        for (user_id, share) in split_amount(expense.amount, &expense.split_between) {
+            let key = (user_id.to_string(), expense.currency.clone());
+            totals.entry(key).or_default().1 += share;
+        }
+    }
+    totals
+}
+
+/// Pays the largest debt to the largest credit until every balance in a
+/// currency is square, which takes at most one payment fewer than there are
+/// members. Returns `(from, to, amount)`.
+fn settle(balances: &[(String, i64)]) -> Vec<(String, String, i64)> {
+    let by_size = |people: &mut Vec<(String, i64)>| {
+        people.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    };
+    let mut creditors: Vec<(String, i64)> =
+        balances.iter().filter(|(_, b)| *b > 0).cloned().collect();
+    let mut debtors: Vec<(String, i64)> = balances
+        .iter()
+        .filter(|(_, b)| *b < 0)
+        .map(|(user_id, b)| (user_id.clone(), -b))
+        .collect();
+
+    let mut payments = Vec::new();
+    loop {
+        by_size(&mut creditors);
+        by_size(&mut debtors);
+        let (Some(creditor), Some(debtor)) = (creditors.first_mut(), debtors.first_mut()) else {
+            break;
+        };
+        let amount = creditor.1.min(debtor.1);
+        if amount == 0 {
+            break;
+        }
+        payments.push((debtor.0.clone(), creditor.0.clone(), amount));
+        creditor.1 -= amount;
+        debtor.1 -= amount;
+        creditors.retain(|(_, b)| *b > 0);
+        debtors.retain(|(_, b)| *b > 0);
+    }
+    payments
+}
+
+impl<R> BudgetService<R>
+where
+    R: UserRepository
+        + TravelPlanRepository
+        + PlanMemberRepository
+        + ShareLinkRepository
+        + RouteOptionRepository
+        + RouteVoteRepository
+        + PoiRepository
+        + CommentRepository
+        + PlanEventRepository
+        + PlanRevisionRepository
+        + ItineraryRepository
+        + PlanLegRepository
+        + BudgetRepository
+        + ?Sized,
+{
+    pub fn new(repos: Arc<R>) -> Self {
+        BudgetService {
+            travel_plans: TravelPlanService::new(repos.clone()),
+            repos,
+        }
+    }
+
+    /// The owner and every member who has accepted, owner first.
+    fn member_ids(&self, plan: &TravelPlan) -> Result<Vec<String>, BudgetError> {
+        let mut member_ids = vec![plan.user_id.clone()];
+        member_ids.extend(
+            self.repos
+                .find_plan_members(&plan.id)?
+                .into_iter()
+                .filter(|member| member.status == MemberStatus::Accepted)
+                .map(|member| member.user_id),
+        );
+        Ok(member_ids)
+    }
+
+    fn username(&self, user_id: &str) -> Result<String, BudgetError> {
+        Ok(self
+            .repos
+            .find_user_by_id(user_id)?
+            .map(|user| user.username)
+            .unwrap_or_default())
+    }
+
+    fn expense_in_plan(&self, plan_id: &str, expense_id: &str) -> Result<Expense, BudgetError> {
+        match self.repos.find_expense(expense_id)? {
+            Some(expense) if expense.travel_plan_id == plan_id => Ok(expense),
+            _ => Err(BudgetError::ExpenseNotFound),
+        }
+    }
+
+    /// Checks an expense's amount and description, and that the payer and
+    /// everyone sharing it are members of the plan.
+    fn validate_expense(&self, plan: &TravelPlan, expense: &Expense) -> Result<(), BudgetError> {
+        let invalid = |message: &str| Err(BudgetError::InvalidInput(message.to_string()));
+        if expense.amount <= 0 {
+            return invalid("amount must be positive");
+        }
+        if expense.description.trim().is_empty() {
+            return invalid("description must not be empty");
+        }
+        if expense.split_between.is_empty() {
+            return invalid("splitBetween must name at least one member");
+        }
+        let unique: HashSet<&String> = expense.split_between.iter().collect();
+        if unique.len() != expense.split_between.len() {
+            return invalid("splitBetween must not name a member twice");
+        }
+
+        let member_ids = self.member_ids(plan)?;
+        let people = std::iter::once(&expense.paid_by).chain(&expense.split_between);
+        for user_id in people {
+            if !member_ids.contains(user_id) {
+                return Err(BudgetError::InvalidInput(format!(
+                    "{} is not a member of the travel plan",
+                    user_id
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn get_budgets(&self, plan_id: &str, user_id: &str) -> Result<Vec<Budget>, BudgetError> {
+        self.travel_plans.authorize(plan_id, user_id, PlanRole::Viewer)?;
+        Ok(self.repos.find_budgets(plan_id)?)
+    }
+
+    /// Sets the budget for a category, replacing any earlier one. Returns
+    /// whether it is new.
+    pub fn set_budget(
+        &self,
+        plan_id: &str,
+        category: ExpenseCategory,
+        budget: &SetBudget,
+        user_id: &str,
+    ) -> Result<(Budget, bool), BudgetError> {
+        self.travel_plans.authorize(plan_id, user_id, PlanRole::Editor)?;
+        if budget.amount < 0 {
+            return Err(BudgetError::InvalidInput("amount must not be negative".to_string()));
+        }
+        let currency = currency_code(&budget.currency)?;
+
+        let existing = self
+            .repos
+            .find_budgets(plan_id)?
+            .into_iter()
+            .find(|b| b.category == category);
+        let (saved, created) = match existing {
+            Some(mut existing) => {
+                existing.amount = budget.amount;
+                existing.currency = currency;
+                existing.updated_at = chrono::Utc::now();
+                self.repos.update_budget(&existing)?;
+                (existing, false)
+            }
+            None => {
+                let mut new_budget = Budget::new(plan_id, category, budget);
+                new_budget.currency = currency;
+                self.repos.insert_budget(&new_budget)?;
+                (new_budget, true)
+            }
+        };
+
+        info!(
+            "User {} set the {} budget of travel plan {} to {} {}",
+            user_id, category, plan_id, saved.amount, saved.currency
+        );
+        Ok((saved, created))
+    }
+
+    pub fn delete_budget(
+        &self,
+        plan_id: &str,
+        category: ExpenseCategory,
+        user_id: &str,
+    ) -> Result<(), BudgetError> {
+        self.travel_plans.authorize(plan_id, user_id, PlanRole::Editor)?;
+        let budget = self
+            .repos
+            .find_budgets(plan_id)?
+            .into_iter()
+            .find(|b| b.category == category)
+            .ok_or(BudgetError::BudgetNotFound)?;
+        self.repos.delete_budget(&budget.id)?;
+        Ok(())
+    }
+
+    pub fn get_expenses(&self, plan_id: &str, user_id: &str) -> Result<Vec<Expense>, BudgetError> {
+        self.travel_plans.authorize(plan_id, user_id, PlanRole::Viewer)?;
+        Ok(self.repos.find_expenses(plan_id)?)
+    }
+
+    pub fn get_expense(
+        &self,
+        plan_id: &str,
+        expense_id: &str,
+        user_id: &str,
+    ) -> Result<Expense, BudgetError> {
+        self.travel_plans.authorize(plan_id, user_id, PlanRole::Viewer)?;
+        self.expense_in_plan(plan_id, expense_id)
+    }
+
+    /// Records an expense. It is paid by the recording member and split
+    /// between everyone on the plan unless the request says otherwise.
+    pub fn add_expense(
+        &self,
+        plan_id: &str,
+        new_expense: &NewExpense,
+        user_id: &str,
+    ) -> Result<Expense, BudgetError> {
+        let plan = self
+            .travel_plans
+            .authorize(plan_id, user_id, PlanRole::Editor)?
+            .travel_plan;
+
+        let paid_by = new_expense.paid_by.as_deref().unwrap_or(user_id);
+        let split_between = match &new_expense.split_between {
+            Some(split_between) => split_between.clone(),
+            None => self.member_ids(&plan)?,
+        };
+        let mut expense = Expense::new(plan_id, new_expense, paid_by, split_between, user_id);
+        expense.currency = currency_code(&expense.currency)?;
+        self.validate_expense(&plan, &expense)?;
+
+        self.repos.insert_expense(&expense)?;
+        info!(
+            "User {} recorded expense {} of {} {} on travel plan {}",
+            user_id, expense.id, expense.amount, expense.currency, plan_id
+        );
+        Ok(expense)
+    }
+
+    pub fn patch_expense(
+        &self,
+        plan_id: &str,
+        expense_id: &str,
+        patch: &PatchExpense,
+        user_id: &str,
+    ) -> Result<Expense, BudgetError> {
+        let plan = self
+            .travel_plans
+            .authorize(plan_id, user_id, PlanRole::Editor)?
+            .travel_plan;
+        let expense = self.expense_in_plan(plan_id, expense_id)?;
+
+        let mut patched_expense = expense.with_patch(patch);
+        patched_expense.currency = currency_code(&patched_expense.currency)?;
+        self.validate_expense(&plan, &patched_expense)?;
+
+        self.repos.update_expense(&patched_expense)?;
+        Ok(patched_expense)
+    }
+
+    pub fn delete_expense(
+        &self,
+        plan_id: &str,
+        expense_id: &str,
+        user_id: &str,
+    ) -> Result<(), BudgetError> {
+        self.travel_plans.authorize(plan_id, user_id, PlanRole::Editor)?;
+        self.expense_in_plan(plan_id, expense_id)?;
+        if !self.repos.delete_expense(expense_id)? {
+            return Err(BudgetError::ExpenseNotFound);
+        }
+        info!("User {} removed expense {} from travel plan {}", user_id, expense_id, plan_id);
+        Ok(())
+    }
+
+    /// Budget against spending per category, what each member paid and
+    /// owes, and the payments that square the plan. Amounts are converted to
+    /// the plan's home currency when it has one; otherwise different
+    /// currencies are kept apart.
+    pub fn summary(
+        &self,
+        plan_id: &str,
+        user_id: &str,
+        rates: &ExchangeRates,
+    ) -> Result<BudgetSummary, BudgetError> {
+        let plan = self
+            .travel_plans
+            .authorize(plan_id, user_id, PlanRole::Viewer)?
+            .travel_plan;
+        let mut budgets = self.repos.find_budgets(plan_id)?;
+        let mut expenses = self.repos.find_expenses(plan_id)?;
+        if let Some(home) = &plan.home_currency {
+            let home = CurrencyCode::parse(home).map_err(BudgetError::InvalidInput)?;
+            convert_to(&home, rates, &mut budgets, &mut expenses)?;
+        }
+
+        let mut spent: BTreeMap<(usize, String), i64> = BTreeMap::new();
+        let category_index =
+            |category| ExpenseCategory::ALL.iter().position(|c| *c == category).unwrap_or(0);
+        for budget in &budgets {
+            spent.entry((category_index(budget.category), budget.currency.clone())).or_default();
+        }
+        for expense in &expenses {
+            *spent
+                .entry((category_index(expense.category), expense.currency.clone()))
+                .or_default() += expense.amount;
+        }
+        let categories = spent
+            .into_iter()
+            .map(|((index, currency), spent)| {
+                let category = ExpenseCategory::ALL[index];
+                let budgeted = budgets
+                    .iter()
+                    .find(|b| b.category == category && b.currency == currency)
+                    .map(|b| b.amount);
+                CategorySummary {
+                    category,
+                    currency,
+                    budgeted,
+                    spent,
+                    remaining: budgeted.map(|budgeted| budgeted - spent),
+                }
+            })
+            .collect();
+
+        let mut balances = Vec::new();
+        let mut by_currency: BTreeMap<String, Vec<(String, i64)>> = BTreeMap::new();
+        for ((member_id, currency), (paid, share)) in tally(&expenses) {
+            by_currency
+                .entry(currency.clone())
+                .or_default()
+                .push((member_id.clone(), paid - share));
+            balances.push(MemberBalance {
+                username: self.username(&member_id)?,
+                user_id: member_id,
+                currency,
+                paid,
+                share,
+                balance: paid - share,
+            });
+        }
+
+        let mut settlements = Vec::new();
+        for (currency, currency_balances) in by_currency {
+            for (from, to, amount) in settle(&currency_balances) {
+                settlements.push(Settlement {
+                    from_username: self.username(&from)?,
+                    to_username: self.username(&to)?,
+                    from_user_id: from,
+                    to_user_id: to,
+                    amount,
+                    currency: currency.clone(),
+                });
+            }
+        }
+
+        Ok(BudgetSummary {
+            travel_plan_id: plan_id.to_string(),
+            home_currency: plan.home_currency,
+            categories,
+            balances,
+            settlements,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ids(names: &[&str]) -> Vec<String> {
+        names.iter().map(|name| name.to_string()).collect()
+    }
+
+    #[test]
+    fn splits_leave_no_minor_unit_behind() {
+        let people = ids(&["ana", "ben", "cy"]);
+        let shares = split_amount(1000, &people);
+        assert_eq!(shares, [("ana", 334), ("ben", 333), ("cy", 333)]);
+        assert!(split_amount(1000, &[]).is_empty());
+    }
+
+    #[test]
+    fn settles_with_the_fewest_payments() {
+        let expense = |paid_by: &str, amount, split_between: &[&str]| Expense {
+            id: String::new(),
+            travel_plan_id: "plan".to_string(),
+            category: ExpenseCategory::Food,
+            description: "Dinner".to_string(),
+            amount,
+            currency: "EUR".to_string(),
+            paid_by: paid_by.to_string(),
+            split_between: ids(split_between),
+            spent_on: chrono::NaiveDate::from_ymd_opt(2030, 6, 1).unwrap(),
+            created_by: paid_by.to_string(),
+            created_at: chrono::Utc::now(),
+            updated_at: chrono::Utc::now(),
+        };
+        // Ana pays 90 for three, Ben pays 30 for three
+        let totals = tally(&[
+            expense("ana", 9000, &["ana", "ben", "cy"]),
+            expense("ben", 3000, &["ana", "ben", "cy"]),
+        ]);
+        let balances: Vec<(String, i64)> = totals
+            .into_iter()
+            .map(|((user_id, _), (paid, share))| (user_id, paid - share))
+            .collect();
+        assert_eq!(
+            balances,
+            [("ana".to_string(), 5000), ("ben".to_string(), -1000), ("cy".to_string(), -4000)]
+        );
+
+        let payments = settle(&balances);
+        assert_eq!(
+            payments,
+            [
+                ("cy".to_string(), "ana".to_string(), 4000),
+                ("ben".to_string(), "ana".to_string(), 1000)
+            ]
+        );
+    }
+
+    #[test]
+    fn converts_at_the_rate_of_the_day() {
+        let day = |d| chrono::NaiveDate::from_ymd_opt(2030, 6, d).unwrap();
+        let rates = ExchangeRates::parse_csv(
+            "Date,NOK,USD\n2030-06-03,11.50,1.10\n2030-06-04,11.60,1.20\n",
+        )
+        .unwrap();
+        let set_on = day(3).and_hms_opt(12, 0, 0).unwrap().and_utc();
+        let mut budgets = [Budget {
+            id: String::new(),
+            travel_plan_id: "plan".to_string(),
+            category: ExpenseCategory::Lodging,
+            amount: 115000,
+            currency: "NOK".to_string(),
+            created_at: set_on,
+            updated_at: set_on,
+        }];
+        let mut expenses = [Expense {
+            id: String::new(),
+            travel_plan_id: "plan".to_string(),
+            category: ExpenseCategory::Lodging,
+            description: "Cabin".to_string(),
+            amount: 6000,
+            currency: "USD".to_string(),
+            paid_by: "ana".to_string(),
+            split_between: ids(&["ana"]),
+            spent_on: day(4),
+            created_by: "ana".to_string(),
+            created_at: set_on,
+            updated_at: set_on,
+        }];
+
+        let euro = CurrencyCode::parse("EUR").unwrap();
+        convert_to(&euro, &rates, &mut budgets, &mut expenses).unwrap();
+        assert_eq!((budgets[0].amount, budgets[0].currency.as_str()), (10000, "EUR"));
+        assert_eq!((expenses[0].amount, expenses[0].currency.as_str()), (5000, "EUR"));
+
+        let yen = CurrencyCode::parse("JPY").unwrap();
+        assert!(matches!(
+            convert_to(&yen, &rates, &mut budgets, &mut expenses),
+            Err(BudgetError::CurrencyError(CurrencyError::MissingRate { .. }))
+        ));
+    }
+}
@@ -0,0 +1,520 @@
+use chrono::Utc;
+use log::{error, info};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Arc;
+use utoipa::ToSchema;
+
+use crate::models::comment::{
+    Comment, CommentTarget, DELETED_BODY, NewComment, UpdateComment, parse_mentions,
+};
+use crate::models::plan_member::PlanRole;
+use crate::repositories::{
+    BudgetRepository, CommentRepository, ItineraryRepository, PlanEventRepository,
+    PlanLegRepository, PlanMemberRepository, PlanRevisionRepository, PoiRepository, RepositoryError,
+    RouteOptionRepository, RouteVoteRepository, ShareLinkRepository, TravelPlanRepository,
+    UserRepository,
+};
+use crate::services::blocking::BlockingError;
+use crate::services::travel_plan_service::{TravelPlanError, TravelPlanService};
+
+/// Longest comment body accepted, in characters.
+pub const MAX_COMMENT_LENGTH: usize = 4000;
+
+/// Comment threads on a plan and its routes and points of interest. Anyone
+/// who can see the plan can read and write comments; only the author can
+/// change or remove their own.
+pub struct CommentService<R: ?Sized> {
+    repos: Arc<R>,
+    travel_plans: TravelPlanService<R>,
+}
+
+#[derive(Debug)]
+pub enum CommentError {
+    TravelPlanError(TravelPlanError),
+    /// The route or point of interest is not part of the plan.
+    TargetNotFound,
+    CommentNotFound,
+    NotAuthor,
+    InvalidInput(String),
+    DatabaseError(String),
+}
+
+impl From<TravelPlanError> for CommentError {
+    fn from(error: TravelPlanError) -> Self {
+        CommentError::TravelPlanError(error)
+    }
+}
+
+impl From<BlockingError> for CommentError {
+    fn from(error: BlockingError) -> Self {
+        CommentError::DatabaseError(error.to_string())
+    }
+}
+
+impl From<RepositoryError> for CommentError {
+    fn from(error: RepositoryError) -> Self {
+        error!("Comment repository error: {}", error);
+        CommentError::DatabaseError(error.to_string())
+    }
+}
+
+/// The item a request comments on, as addressed in the URL.
+#[derive(Debug, Clone)]
+pub enum CommentSubject {
+    Plan,
+    Route(String),
+    Poi { route_id: String, poi_id: String },
+}
+
+/// A comment with its author's name and its replies, oldest first.
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct CommentDto {
+    #[serde(flatten)]
+    pub comment: Comment,
+    pub username: String,
+    pub replies: Vec<CommentDto>,
+}
+
+/// One page of threads on an item.
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct CommentPage {
+    pub threads: Vec<CommentDto>,
+    /// Threads on the item across all pages.
+    pub total: usize,
+    pub limit: usize,
+    pub offset: usize,
+}
+
+struct ResolvedTarget {
+    target_type: CommentTarget,
+    target_id: String,
+    route_id: Option<String>,
+}
+
+impl<R> CommentService<R>
+where
+    R: UserRepository
+        + TravelPlanRepository
+        + PlanMemberRepository
+        + ShareLinkRepository
+        + RouteOptionRepository
+        + RouteVoteRepository
+        + CommentRepository
+        + PlanEventRepository
+        + PlanRevisionRepository
+        + ItineraryRepository
+        + PlanLegRepository
+        + BudgetRepository
+        + PoiRepository
+        + ?Sized,
+{
+    pub fn new(repos: Arc<R>) -> Self {
+        CommentService {
+            travel_plans: TravelPlanService::new(repos.clone()),
+            repos,
+        }
+    }
+
+    fn resolve(&self, plan_id: &str, subject: &CommentSubject) -> Result<ResolvedTarget, CommentError> {
+        let route_in_plan = |route_id: &str| -> Result<(), CommentError> {
+            match self.repos.find_route_option(route_id)? {
+                Some(route) if route.travel_plan_id == plan_id => Ok(()),
+                _ => Err(CommentError::TargetNotFound),
+            }
+        };
+
+        match subject {
+            CommentSubject::Plan => Ok(ResolvedTarget {
+                target_type: CommentTarget::Plan,
+                target_id: plan_id.to_string(),
+                route_id: None,
+            }),
+            CommentSubject::Route(route_id) => {
+                route_in_plan(route_id)?;
+                Ok(ResolvedTarget {
+                    target_type: CommentTarget::Route,
+                    target_id: route_id.clone(),
+                    route_id: Some(route_id.clone()),
+                })
+            }
+            CommentSubject::Poi { route_id, poi_id } => {
+                route_in_plan(route_id)?;
+                match self.repos.find_poi(poi_id)? {
+                    Some(poi) if poi.route_option_id == *route_id => Ok(ResolvedTarget {
+                        target_type: CommentTarget::Poi,
+                        target_id: poi_id.clone(),
+                        route_id: Some(route_id.clone()),
+                    }),
+                    _ => Err(CommentError::TargetNotFound),
+                }
+            }
+        }
+    }
+
+    fn validate_body(body: &str) -> Result<String, CommentError> {
+        let body = body.trim();
+        if body.is_empty() {
+            return Err(CommentError::InvalidInput("body must not be empty".to_string()));
+        }
+        if body.chars().count() > MAX_COMMENT_LENGTH {
+            return Err(CommentError::InvalidInput(format!(
+                "body must be at most {} characters",
+                MAX_COMMENT_LENGTH
+            )));
+        }
+        Ok(body.to_string())
+    }
+
+    /// Keeps the @mentions that name an existing account.
+    fn resolve_mentions(&self, body: &str) -> Result<Vec<String>, CommentError> {
+        let mut mentions = Vec::new();
+        for candidate in parse_mentions(body) {
+            if let Some(user) = self.repos.find_user_by_username(&candidate)? {
+                mentions.push(user.username);
+            }
+        }
+        Ok(mentions)
+    }
+
+    fn find_in_plan(&self, plan_id: &str, comment_id: &str) -> Result<Comment, CommentError> {
+        match self.repos.find_comment(comment_id)? {
+            Some(comment) if comment.travel_plan_id == plan_id => Ok(comment),
+            _ => Err(CommentError::CommentNotFound),
+        }
+    }
+
+    fn username(
+        &self,
+        user_id: &str,
+        cache: &mut HashMap<String, String>,
+    ) -> Result<String, CommentError> {
+        if let Some(name) = cache.get(user_id) {
+            return Ok(name.clone());
+        }
+        let name = self
+            .repos
+            .find_user_by_id(user_id)?
+            .map(|user| user.username)
+            .unwrap_or_default();
+        cache.insert(user_id.to_string(), name.clone());
+        Ok(name)
+    }
+
+    /// Nests a thread's comments under their parents.
+    fn build_thread(
+        &self,
+        root: Comment,
+        thread: &[Comment],
+        cache: &mut HashMap<String, String>,
+    ) -> Result<CommentDto, CommentError> {
+        let mut replies = Vec::new();
+        for reply in thread.iter().filter(|c| c.parent_id.as_ref() == Some(&root.id)) {
+            replies.push(self.build_thread(reply.clone(), thread, cache)?);
+        }
+
+        Ok(CommentDto {
+            username: self.username(&root.user_id, cache)?,
+            comment: root,
+            replies,
+        })
+    }
+
+    pub fn list_comments(
+        &self,
+        plan_id: &str,
+        subject: &CommentSubject,
+        user_id: &str,
+        limit: usize,
+        offset: usize,
+    ) -> Result<CommentPage, CommentError> {
+        self.travel_plans.authorize(plan_id, user_id, PlanRole::Viewer)?;
+        let target = self.resolve(plan_id, subject)?;
+
+        let total = self
+            .repos
+            .count_comment_threads(target.target_type, &target.target_id)?;
+        let roots =
+            self.repos
+                .find_comment_threads(target.target_type, &target.target_id, limit, offset)?;
+
+        let mut cache = HashMap::new();
+        let mut threads = Vec::with_capacity(roots.len());
+        for root in roots {
+            let thread = self.repos.find_comments_in_thread(&root.thread_id)?;
+            threads.push(self.build_thread(root, &thread, &mut cache)?);
+        }
+
+        Ok(CommentPage {
+            threads,
+            total,
+            limit,
+            offset,
+        })
+    }
+
+    /// Starts a thread, or replies within one when `parent_id` is set.
+    pub fn add_comment(
+        &self,
+        plan_id: &str,
+        subject: &CommentSubject,
+        user_id: &str,
+        new_comment: &NewComment,
+    ) -> Result<CommentDto, CommentError> {
+        self.travel_plans.authorize(plan_id, user_id, PlanRole::Viewer)?;
+        let target = self.resolve(plan_id, subject)?;
+        let body = Self::validate_body(&new_comment.body)?;
+
+        let mut comment = match &new_comment.parent_id {
+            Some(parent_id) => {
+                let parent = self
+                    .repos
+                    .find_comment(parent_id)?
+                    .filter(|p| p.target_type == target.target_type && p.target_id == target.target_id)
+                    .ok_or_else(|| {
+                        CommentError::InvalidInput(
+                            "parentId must be a comment on the same item".to_string(),
+                        )
+                    })?;
+                Comment::reply_to(&parent, user_id, &body)
+            }
+            None => Comment::new(
+                plan_id,
+                target.route_id.as_deref(),
+                target.target_type,
+                &target.target_id,
+                user_id,
+                &body,
+            ),
+        };
+        comment.mentions = self.resolve_mentions(&body)?;
+        self.repos.insert_comment(&comment)?;
+
+        info!(
+            "User {} commented on {} {} of travel plan {}",
+            user_id,
+            comment.target_type.as_str(),
+            comment.target_id,
+            plan_id
+        );
+        self.build_thread(comment, &[], &mut HashMap::new())
+    }
+
+    pub fn edit_comment(
+        &self,
+        plan_id: &str,
+        comment_id: &str,
+        user_id: &str,
+        update: &UpdateComment,
+    ) -> Result<CommentDto, CommentError> {
+        self.travel_plans.authorize(plan_id, user_id, PlanRole::Viewer)?;
+        let mut comment = self.find_in_plan(plan_id, comment_id)?;
+        if comment.user_id != user_id {
+            return Err(CommentError::NotAuthor);
+        }
+        // A deleted comment only stays for its replies' sake
+        if comment.body == DELETED_BODY {
+            return Err(CommentError::CommentNotFound);
+        }
+
+        comment.body = Self::validate_body(&update.body)?;
+        comment.mentions = self.resolve_mentions(&comment.body)?;
+        comment.updated_at = Utc::now();
+        self.repos.update_comment(&comment)?;
+
+        info!("User {} edited comment {}", user_id, comment_id);
+        let thread = self.repos.find_comments_in_thread(&comment.thread_id)?;
+        self.build_thread(comment, &thread, &mut HashMap::new())
+    }
+
+    /// Deletes the comment. One that others have replied to is blanked to
+    /// "[deleted]" instead, so the replies stay in their thread.
+    pub fn delete_comment(
+        &self,
+        plan_id: &str,
+        comment_id: &str,
+        user_id: &str,
+    ) -> Result<(), CommentError> {
+        self.travel_plans.authorize(plan_id, user_id, PlanRole::Viewer)?;
+        let mut comment = self.find_in_plan(plan_id, comment_id)?;
+        if comment.user_id != user_id {
+            return Err(CommentError::NotAuthor);
+        }
+
+        let thread = self.repos.find_comments_in_thread(&comment.thread_id)?;
+        if thread.iter().any(|c| c.parent_id.as_ref() == Some(&comment.id)) {
+            comment.tombstone();
+            self.repos.update_comment(&comment)?;
+            info!("User {} deleted comment {}, keeping its replies", user_id, comment_id);
+        } else {
+            self.repos.delete_comment(&comment.id)?;
+            info!("User {} deleted comment {}", user_id, comment_id);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::repositories::memory::InMemoryRepository;
+    use crate::services::route_option_service::RouteOptionService;
+    use crate::services::test_fixtures::{add_member, create_plan, road_trip, user};
+
+    struct Fixture {
+        repos: Arc<InMemoryRepository>,
+        comments: CommentService<InMemoryRepository>,
+        plan_id: String,
+        alice: String,
+        bob: String,
+    }
+
+    fn setup() -> Fixture {
+        let repos = Arc::new(InMemoryRepository::new());
+        let alice = user(&repos, "alice").id;
+        let bob = user(&repos, "bob").id;
+        let plan_id = create_plan(&repos, &road_trip(), &alice);
+        add_member(&repos, &plan_id, &bob, PlanRole::Viewer, &alice);
+
+        Fixture {
+            comments: CommentService::new(repos.clone()),
+            repos,
+            plan_id,
+            alice,
+            bob,
+        }
+    }
+
+    fn comment(body: &str, parent_id: Option<&str>) -> NewComment {
+        NewComment {
+            body: body.to_string(),
+            parent_id: parent_id.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn threads_nest_replies_and_paginate() {
+        let f = setup();
+        let on_plan = CommentSubject::Plan;
+
+        let first = f
+            .comments
+            .add_comment(&f.plan_id, &on_plan, &f.alice, &comment("Thoughts @bob?", None))
+            .unwrap();
+        assert_eq!(first.comment.mentions, ["bob"]);
+        assert_eq!(first.username, "alice");
+
+        let reply = f
+            .comments
+            .add_comment(&f.plan_id, &on_plan, &f.bob, &comment("Looks good", Some(&first.comment.id)))
+            .unwrap();
+        f.comments
+            .add_comment(&f.plan_id, &on_plan, &f.alice, &comment("Great", Some(&reply.comment.id)))
+            .unwrap();
+        f.comments
+            .add_comment(&f.plan_id, &on_plan, &f.bob, &comment("Second thread @nobody", None))
+            .unwrap();
+
+        let page = f.comments.list_comments(&f.plan_id, &on_plan, &f.bob, 1, 0).unwrap();
+        assert_eq!(page.total, 2);
+        assert_eq!(page.threads.len(), 1);
+        let thread = &page.threads[0];
+        assert_eq!(thread.replies[0].username, "bob");
+        assert_eq!(thread.replies[0].replies[0].comment.body, "Great");
+
+        let page = f.comments.list_comments(&f.plan_id, &on_plan, &f.bob, 1, 1).unwrap();
+        assert!(page.threads[0].comment.mentions.is_empty());
+        assert!(page.threads[0].replies.is_empty());
+    }
+
+    #[test]
+    fn only_authors_edit_and_delete() {
+        let f = setup();
+        let on_plan = CommentSubject::Plan;
+        let root = f
+            .comments
+            .add_comment(&f.plan_id, &on_plan, &f.alice, &comment("Original", None))
+            .unwrap();
+        let reply = f
+            .comments
+            .add_comment(&f.plan_id, &on_plan, &f.bob, &comment("Reply", Some(&root.comment.id)))
+            .unwrap();
+
+        let edit = UpdateComment { body: "Edited for @alice".to_string() };
+        assert!(matches!(
+            f.comments.edit_comment(&f.plan_id, &root.comment.id, &f.bob, &edit),
+            Err(CommentError::NotAuthor)
+        ));
+        let edited = f
+            .comments
+            .edit_comment(&f.plan_id, &reply.comment.id, &f.bob, &edit)
+            .unwrap();
+        assert_eq!(edited.comment.body, "Edited for @alice");
+        assert_eq!(edited.comment.mentions, ["alice"]);
+
+        assert!(matches!(
+            f.comments.delete_comment(&f.plan_id, &root.comment.id, &f.bob),
+            Err(CommentError::NotAuthor)
+        ));
+        // A comment with replies is blanked and the replies stay
+        f.comments.delete_comment(&f.plan_id, &root.comment.id, &f.alice).unwrap();
+        let page = f.comments.list_comments(&f.plan_id, &on_plan, &f.bob, 10, 0).unwrap();
+        assert_eq!(page.threads[0].comment.body, DELETED_BODY);
+        assert!(page.threads[0].comment.mentions.is_empty());
+        assert_eq!(page.threads[0].replies[0].comment.id, reply.comment.id);
+        let revive = UpdateComment { body: "Back again".to_string() };
+        assert!(matches!(
+            f.comments.edit_comment(&f.plan_id, &root.comment.id, &f.alice, &revive),
+            Err(CommentError::CommentNotFound)
+        ));
+        // A comment without replies is removed
+        f.comments.delete_comment(&f.plan_id, &reply.comment.id, &f.bob).unwrap();
+        assert!(f.repos.find_comment(&reply.comment.id).unwrap().is_none());
+
+        assert!(matches!(
+            f.comments.add_comment(&f.plan_id, &on_plan, &f.alice, &comment("  ", None)),
+            Err(CommentError::InvalidInput(_))
+        ));
+    }
+
+    #[test]
+    fn route_comments_go_with_the_route() {
+        let f = setup();
+        let routes = RouteOptionService::new(f.repos.clone());
+        let route = routes
+            .generate_route_options(&f.plan_id, &f.alice, 1)
+            .unwrap()
+            .remove(0);
+        let poi_id = route.points_of_interest[0].id.clone();
+        let on_route = CommentSubject::Route(route.route.id.clone());
+        let on_poi = CommentSubject::Poi {
+            route_id: route.route.id.clone(),
+            poi_id,
+        };
+
+        f.comments
+            .add_comment(&f.plan_id, &on_route, &f.bob, &comment("Scenic", None))
+            .unwrap();
+        let poi_comment = f
+            .comments
+            .add_comment(&f.plan_id, &on_poi, &f.bob, &comment("Worth a stop", None))
+            .unwrap();
+
+        // A POI must be addressed through its own route
+        let wrong_route = CommentSubject::Poi {
+            route_id: "elsewhere".to_string(),
+            poi_id: poi_comment.comment.target_id.clone(),
+        };
+        assert!(matches!(
+            f.comments.list_comments(&f.plan_id, &wrong_route, &f.bob, 10, 0),
+            Err(CommentError::TargetNotFound)
+        ));
+
+        routes
+            .delete_route_option(&f.plan_id, &route.route.id, &f.alice, None)
+            .unwrap();
+        assert!(f.repos.find_comment(&poi_comment.comment.id).unwrap().is_none());
+    }
+}
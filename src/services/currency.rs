@@ -0,0 +1,216 @@
+//! Offline currency conversion against a table of reference rates, loaded
+//! from a file in the layout of the ECB's historical CSV: a `Date` column,
+//! then one column per currency giving units of it per euro.
+
+use chrono::NaiveDate;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::path::Path;
+
+use crate::models::currency::CurrencyCode;
+
+/// How far back a rate may be used. Reference rates skip weekends and
+/// holidays, but not whole weeks.
+pub const MAX_RATE_AGE_DAYS: i64 = 7;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum CurrencyError {
+    /// No rate for the currency on the date or in the week before it.
+    MissingRate { currency: CurrencyCode, date: NaiveDate },
+    InvalidTable(String),
+}
+
+impl fmt::Display for CurrencyError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CurrencyError::MissingRate { currency, date } => {
+                write!(f, "No exchange rate for {} on or shortly before {}", currency, date)
+            }
+            CurrencyError::InvalidTable(e) => write!(f, "Invalid exchange rate table: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for CurrencyError {}
+
+/// Units of each currency per unit of the base currency, by the date each
+/// rate took effect.
+#[derive(Debug, Clone)]
+pub struct ExchangeRates {
+    base: CurrencyCode,
+    rates: HashMap<CurrencyCode, BTreeMap<NaiveDate, f64>>,
+}
+
+impl Default for ExchangeRates {
+    /// An empty table in euros; only converts between equal currencies.
+    fn default() -> Self {
+        ExchangeRates::new(CurrencyCode::parse("EUR").unwrap())
+    }
+}
+
+impl ExchangeRates {
+    pub fn new(base: CurrencyCode) -> Self {
+        ExchangeRates {
+            base,
+            rates: HashMap::new(),
+        }
+    }
+
+    /// Reads the file named by `EXCHANGE_RATES_FILE`, or an empty table when
+    /// it isn't set.
+    pub fn from_env() -> Result<Self, CurrencyError> {
+        match std::env::var("EXCHANGE_RATES_FILE") {
+            Ok(path) if !path.is_empty() => ExchangeRates::load(Path::new(&path)),
+            _ => Ok(ExchangeRates::default()),
+        }
+    }
+
+    pub fn load(path: &Path) -> Result<Self, CurrencyError> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| CurrencyError::InvalidTable(format!("{}: {}", path.display(), e)))?;
+        ExchangeRates::parse_csv(&text)
+    }
+
+    /// Parses euro reference rates. Blank and `N/A` cells, and the trailing
+    /// comma the ECB leaves on each line, are skipped.
+    pub fn parse_csv(text: &str) -> Result<Self, CurrencyError> {
+        let invalid = |line: usize, message: String| {
+            CurrencyError::InvalidTable(format!("line {}: {}", line, message))
+        };
+        let mut lines = text.lines().enumerate().filter(|(_, line)| !line.trim().is_empty());
+
+        let Some((_, header)) = lines.next() else {
+            return Err(CurrencyError::InvalidTable("the file is empty".to_string()));
+        };
+        let mut columns = header.split(',').map(str::trim);
+        if !columns.next().is_some_and(|first| first.eq_ignore_ascii_case("date")) {
+            return Err(invalid(1, "the first column must be Date".to_string()));
+        }
+        let currencies = columns
+            .map(|code| match code {
+                "" => Ok(None),
+                code => CurrencyCode::parse(code).map(Some).map_err(|e| invalid(1, e)),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut table = ExchangeRates::default();
+        for (index, line) in lines {
+            let mut cells = line.split(',').map(str::trim);
+            let date = cells.next().unwrap_or_default();
+            let date = NaiveDate::parse_from_str(date, "%Y-%m-%d")
+                .map_err(|_| invalid(index + 1, format!("{} is not a date", date)))?;
+            for (currency, cell) in currencies.iter().zip(cells) {
+                let Some(currency) = currency else { continue };
+                if cell.is_empty() || cell.eq_ignore_ascii_case("N/A") {
+                    continue;
+                }
+                match cell.parse::<f64>() {
+                    Ok(rate) if rate > 0.0 && rate.is_finite() => {
+                        table.insert(currency.clone(), date, rate)
+                    }
+                    _ => {
+                        return Err(invalid(
+                            index + 1,
+                            format!("{} is not a rate for {}", cell, currency),
+                        ));
+                    }
+                }
+            }
+        }
+        Ok(table)
+    }
+
+    /// Sets how many units of `currency` one unit of the base currency buys
+    /// from `date` on.
+    pub fn insert(&mut self, currency: CurrencyCode, date: NaiveDate, rate: f64) {
+        self.rates.entry(currency).or_default().insert(date, rate);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rates.is_empty()
+    }
+
+    /// Units of `currency` per unit of the base currency on `date`: the
+    /// latest rate that had taken effect, if it is recent enough.
+    pub fn rate_on(&self, currency: &CurrencyCode, date: NaiveDate) -> Result<f64, CurrencyError> {
+        if *currency == self.base {
+            return Ok(1.0);
+        }
+        self.rates
+            .get(currency)
+            .and_then(|rates| rates.range(..=date).next_back())
+            .filter(|(effective, _)| (date - **effective).num_days() <= MAX_RATE_AGE_DAYS)
+            .map(|(_, rate)| *rate)
+            .ok_or_else(|| CurrencyError::MissingRate {
+                currency: currency.clone(),
+                date,
+            })
+    }
+
+    /// Converts `amount` minor units of `from` into minor units of `to` at
+    /// the rates for `date`, rounding half away from zero.
+    pub fn convert(
+        &self,
+        amount: i64,
+        from: &CurrencyCode,
+        to: &CurrencyCode,
+        date: NaiveDate,
+    ) -> Result<i64, CurrencyError> {
+        if from == to {
+            return Ok(amount);
+        }
+        let from_rate = self.rate_on(from, date)?;
+        let to_rate = self.rate_on(to, date)?;
+        let major = amount as f64 / 10f64.powi(from.minor_units() as i32);
+        let converted = major / from_rate * to_rate;
+        Ok((converted * 10f64.powi(to.minor_units() as i32)).round() as i64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ECB_CSV: &str = "Date,USD,JPY,GBP,CYP,\n\
+        2024-01-05,1.0921,158.95,0.8613,N/A,\n\
+        2024-01-04,1.0953,158.07,0.8634,N/A,\n";
+
+    fn code(code: &str) -> CurrencyCode {
+        CurrencyCode::parse(code).unwrap()
+    }
+
+    fn day(d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2024, 1, d).unwrap()
+    }
+
+    #[test]
+    fn converts_between_minor_units_through_the_euro() {
+        let rates = ExchangeRates::parse_csv(ECB_CSV).unwrap();
+
+        // 100.00 EUR at 158.95 is 15895 yen, which has no minor unit
+        assert_eq!(rates.convert(10000, &code("EUR"), &code("JPY"), day(5)), Ok(15895));
+        // 10.00 USD -> EUR -> GBP
+        assert_eq!(rates.convert(1000, &code("USD"), &code("GBP"), day(4)), Ok(788));
+        // The weekend uses Friday's rate
+        assert_eq!(rates.rate_on(&code("USD"), day(7)), Ok(1.0921));
+    }
+
+    #[test]
+    fn missing_and_stale_rates_are_errors() {
+        let rates = ExchangeRates::parse_csv(ECB_CSV).unwrap();
+
+        let missing = |currency: &str, date| {
+            Err(CurrencyError::MissingRate {
+                currency: code(currency),
+                date,
+            })
+        };
+        assert_eq!(rates.rate_on(&code("CYP"), day(5)), missing("CYP", day(5)));
+        assert_eq!(rates.rate_on(&code("USD"), day(3)), missing("USD", day(3)));
+        assert_eq!(rates.rate_on(&code("USD"), day(20)), missing("USD", day(20)));
+        assert_eq!(rates.convert(500, &code("CHF"), &code("CHF"), day(20)), Ok(500));
+
+        assert!(ExchangeRates::parse_csv("Date,USD\n2024-01-05,cheap\n").is_err());
+        assert!(ExchangeRates::parse_csv("USD,Date\n").is_err());
+    }
+}
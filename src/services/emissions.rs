@@ -0,0 +1,128 @@
+//! CO2-equivalent emissions of travel, from the distance and a table of
+//! emission factors by travel mode.
+
+use serde::Deserialize;
+use std::fmt;
+use std::path::Path;
+
+use crate::models::route_option::RouteOption;
+use crate::models::travel_mode::TravelMode;
+
+/// Grams of CO2e per kilometre and traveller, keyed by travel mode. The
+/// defaults are typical figures for a petrol car with one occupant, a mix of
+/// rail and coach, and a short-haul economy flight.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "snake_case", default)]
+pub struct EmissionFactors {
+    pub driving: f64,
+    pub cycling: f64,
+    pub walking: f64,
+    pub transit: f64,
+    pub flight: f64,
+}
+
+impl Default for EmissionFactors {
+    fn default() -> Self {
+        EmissionFactors {
+            driving: 170.0,
+            cycling: 0.0,
+            walking: 0.0,
+            transit: 40.0,
+            flight: 250.0,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct InvalidEmissionFactors(pub String);
+
+impl fmt::Display for InvalidEmissionFactors {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Invalid emission factors: {}", self.0)
+    }
+}
+
+impl std::error::Error for InvalidEmissionFactors {}
+
+/// Rounds to one decimal place, as kilograms and kilometres are reported.
+pub fn round1(value: f64) -> f64 {
+    (value * 10.0).round() / 10.0
+}
+
+impl EmissionFactors {
+    /// Reads the JSON file named by `EMISSION_FACTORS_FILE`, or the defaults
+    /// when it isn't set. Modes the file leaves out keep their defaults.
+    pub fn from_env() -> Result<Self, InvalidEmissionFactors> {
+        match std::env::var("EMISSION_FACTORS_FILE") {
+            Ok(path) if !path.is_empty() => EmissionFactors::load(Path::new(&path)),
+            _ => Ok(EmissionFactors::default()),
+        }
+    }
+
+    pub fn load(path: &Path) -> Result<Self, InvalidEmissionFactors> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| InvalidEmissionFactors(format!("{}: {}", path.display(), e)))?;
+        EmissionFactors::parse(&text)
+    }
+
+    pub fn parse(text: &str) -> Result<Self, InvalidEmissionFactors> {
+        let factors: EmissionFactors =
+            serde_json::from_str(text).map_err(|e| InvalidEmissionFactors(e.to_string()))?;
+        let modes = [
+            TravelMode::Driving,
+            TravelMode::Cycling,
+            TravelMode::Walking,
+            TravelMode::Transit,
+            TravelMode::Flight,
+        ];
+        for mode in modes {
+            let factor = factors.grams_per_km(mode);
+            if !factor.is_finite() || factor < 0.0 {
+                return Err(InvalidEmissionFactors(format!(
+                    "{} must be a non-negative number of grams",
+                    mode.as_str()
+                )));
+            }
+        }
+        Ok(factors)
+    }
+
+    pub fn grams_per_km(&self, mode: TravelMode) -> f64 {
+        match mode {
+            TravelMode::Driving => self.driving,
+            TravelMode::Cycling => self.cycling,
+            TravelMode::Walking => self.walking,
+            TravelMode::Transit => self.transit,
+            TravelMode::Flight => self.flight,
+        }
+    }
+
+    /// Unrounded kilograms of CO2e for one traveller covering `distance_km`.
+    pub fn kg_for(&self, distance_km: f64, mode: TravelMode) -> f64 {
+        distance_km * self.grams_per_km(mode) / 1000.0
+    }
+
+    /// Kilograms of CO2e for one traveller on `route`; `None` when the
+    /// route's distance isn't known.
+    pub fn estimate(&self, route: &RouteOption, mode: TravelMode) -> Option<f64> {
+        route.distance.map(|distance| round1(self.kg_for(distance, mode)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn factors_default_per_mode_and_reject_nonsense() {
+        let factors = EmissionFactors::parse(r#"{ "flight": 150, "transit": 6 }"#).unwrap();
+        assert_eq!(factors.grams_per_km(TravelMode::Flight), 150.0);
+        assert_eq!(factors.grams_per_km(TravelMode::Driving), 170.0);
+        // 1234 km by train at 6 g/km is 7.404 kg
+        assert_eq!(round1(factors.kg_for(1234.0, TravelMode::Transit)), 7.4);
+        assert_eq!(factors.kg_for(50.0, TravelMode::Cycling), 0.0);
+
+        assert!(EmissionFactors::parse(r#"{ "driving": -1 }"#).is_err());
+        assert!(EmissionFactors::parse(r#"{ "driving": "lots" }"#).is_err());
+    }
+}
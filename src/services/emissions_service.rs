@@ -0,0 +1,195 @@
+use chrono::{DateTime, Datelike, Utc};
+use log::info;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use utoipa::ToSchema;
+
+use crate::models::travel_mode::TravelMode;
+use crate::models::travel_plan::TravelPlan;
+use crate::repositories::{
+    BudgetRepository, CommentRepository, ItineraryRepository, PlanEventRepository,
+    PlanLegRepository, PlanMemberRepository, PlanRevisionRepository, PoiRepository,
+    RouteOptionRepository, RouteVoteRepository, ShareLinkRepository, TravelPlanRepository,
+};
+use crate::services::blocking::BlockingError;
+use crate::services::emissions::{EmissionFactors, round1};
+use crate::services::plan_leg_service::{PlanLegError, PlanLegService};
+use crate::services::travel_plan_service::{TravelPlanError, TravelPlanService};
+
+/// Reports the CO2e of a user's travel, from the route that counts for each
+/// leg of their plans.
+pub struct EmissionsService<R: ?Sized> {
+    travel_plans: TravelPlanService<R>,
+    plan_legs: PlanLegService<R>,
+    factors: Arc<EmissionFactors>,
+}
+
+#[derive(Debug)]
+pub enum EmissionsError {
+    TravelPlanError(TravelPlanError),
+    DatabaseError(String),
+}
+
+impl From<TravelPlanError> for EmissionsError {
+    fn from(error: TravelPlanError) -> Self {
+        EmissionsError::TravelPlanError(error)
+    }
+}
+
+impl From<BlockingError> for EmissionsError {
+    fn from(error: BlockingError) -> Self {
+        EmissionsError::DatabaseError(error.to_string())
+    }
+}
+
+impl From<PlanLegError> for EmissionsError {
+    fn from(error: PlanLegError) -> Self {
+        match error {
+            PlanLegError::TravelPlanError(e) => EmissionsError::TravelPlanError(e),
+            PlanLegError::DatabaseError(e) => EmissionsError::DatabaseError(e),
+            PlanLegError::LegNotFound | PlanLegError::InvalidLeg(_) | PlanLegError::RouteLocked => {
+                EmissionsError::DatabaseError("Unexpected error summarising a plan".to_string())
+            }
+        }
+    }
+}
+
+/// Travel by one mode over the year.
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ModeEmissions {
+    pub mode: TravelMode,
+    pub distance_km: f64,
+    pub co2e_kg: f64,
+}
+
+/// One plan's share of the year.
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct PlanEmissions {
+    pub travel_plan_id: String,
+    pub name: String,
+    pub start_date: Option<DateTime<Utc>>,
+    pub distance_km: f64,
+    pub co2e_kg: f64,
+    /// False when a leg has no selected route and its fastest option stands
+    /// in.
+    pub complete: bool,
+}
+
+/// Kilograms of CO2e for one traveller over the plans of a calendar year.
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct EmissionsReport {
+    pub user_id: String,
+    pub year: i32,
+    pub total_co2e_kg: f64,
+    /// Largest share first.
+    pub by_mode: Vec<ModeEmissions>,
+    /// In the order they start.
+    pub plans: Vec<PlanEmissions>,
+}
+
+/// When a plan happens: its start, or its creation if it has no dates.
+fn plan_date(plan: &TravelPlan) -> DateTime<Utc> {
+    plan.start_date.unwrap_or(plan.created_at)
+}
+
+impl<R> EmissionsService<R>
+where
+    R: TravelPlanRepository
+        + PlanMemberRepository
+        + ShareLinkRepository
+        + RouteOptionRepository
+        + RouteVoteRepository
+        + PoiRepository
+        + CommentRepository
+        + PlanEventRepository
+        + PlanRevisionRepository
+        + ItineraryRepository
+        + PlanLegRepository
+        + BudgetRepository
+        + ?Sized,
+{
+    pub fn new(repos: Arc<R>, factors: Arc<EmissionFactors>) -> Self {
+        EmissionsService {
+            travel_plans: TravelPlanService::new(repos.clone()),
+            plan_legs: PlanLegService::new(repos),
+            factors,
+        }
+    }
+
+    /// Emissions over the plans the user owns or has joined that start in
+    /// `year`, per mode and per plan.
+    pub fn yearly_report(
+        &self,
+        user_id: &str,
+        year: i32,
+    ) -> Result<EmissionsReport, EmissionsError> {
+        let mut plans: Vec<TravelPlan> = self
+            .travel_plans
+            .get_travel_plans(user_id)?
+            .into_iter()
+            .map(|dto| dto.travel_plan)
+            .filter(|plan| plan_date(plan).year() == year)
+            .collect();
+        plans.sort_by_key(plan_date);
+
+        let mut by_mode: Vec<ModeEmissions> = Vec::new();
+        let mut plan_emissions = Vec::new();
+        for plan in plans {
+            let summary = self.plan_legs.summary(&plan.id, user_id)?;
+            let (mut distance_km, mut co2e_kg) = (0.0, 0.0);
+            for leg in &summary.legs {
+                let Some(distance) = leg.distance else {
+                    continue;
+                };
+                let kg = self.factors.kg_for(distance, leg.mode);
+                distance_km += distance;
+                co2e_kg += kg;
+                match by_mode.iter_mut().find(|entry| entry.mode == leg.mode) {
+                    Some(entry) => {
+                        entry.distance_km += distance;
+                        entry.co2e_kg += kg;
+                    }
+                    None => by_mode.push(ModeEmissions {
+                        mode: leg.mode,
+                        distance_km: distance,
+                        co2e_kg: kg,
+                    }),
+                }
+            }
+
+            plan_emissions.push(PlanEmissions {
+                travel_plan_id: plan.id,
+                name: plan.name,
+                start_date: plan.start_date,
+                distance_km: round1(distance_km),
+                co2e_kg: round1(co2e_kg),
+                complete: summary.complete,
+            });
+        }
+
+        let total_co2e_kg = round1(by_mode.iter().map(|entry| entry.co2e_kg).sum());
+        by_mode.sort_by(|a, b| b.co2e_kg.total_cmp(&a.co2e_kg));
+        for entry in &mut by_mode {
+            entry.distance_km = round1(entry.distance_km);
+            entry.co2e_kg = round1(entry.co2e_kg);
+        }
+
+        info!(
+            "Reported {} kg CO2e over {} plans in {} for user {}",
+            total_co2e_kg,
+            plan_emissions.len(),
+            year,
+            user_id
+        );
+        Ok(EmissionsReport {
+            user_id: user_id.to_string(),
+            year,
+            total_co2e_kg,
+            by_mode,
+            plans: plan_emissions,
+        })
+    }
+}
@@ -0,0 +1,173 @@
+//! Geometry on the `"lat,lng"` coordinate strings stored on routes and
+//! points of interest.
+
+use crate::models::route_option::RouteOption;
+
+const EARTH_RADIUS_KM: f64 = 6371.0;
+
+/// How many pieces a path is cut into when measuring its overlap with another.
+const OVERLAP_SAMPLES: f64 = 200.0;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Coordinates {
+    pub lat: f64,
+    pub lng: f64,
+}
+
+impl Coordinates {
+    /// Reads `"lat,lng"`; `None` if either half is missing, not a number or
+    /// out of range.
+    pub fn parse(text: &str) -> Option<Self> {
+        let (lat, lng) = text.split_once(',')?;
+        let lat: f64 = lat.trim().parse().ok()?;
+        let lng: f64 = lng.trim().parse().ok()?;
+        if !(-90.0..=90.0).contains(&lat) || !(-180.0..=180.0).contains(&lng) {
+            return None;
+        }
+        Some(Coordinates { lat, lng })
+    }
+
+    /// Great-circle distance in kilometres.
+    pub fn distance_km(&self, other: &Coordinates) -> f64 {
+        let (lat1, lat2) = (self.lat.to_radians(), other.lat.to_radians());
+        let d_lat = lat2 - lat1;
+        let d_lng = (other.lng - self.lng).to_radians();
+        let a = (d_lat / 2.0).sin().powi(2)
+            + lat1.cos() * lat2.cos() * (d_lng / 2.0).sin().powi(2);
+        2.0 * EARTH_RADIUS_KM * a.sqrt().asin()
+    }
+
+    /// The point `t` of the way from here to `other`, in a straight line on
+    /// the map.
+    fn lerp(&self, other: &Coordinates, t: f64) -> Coordinates {
+        Coordinates {
+            lat: self.lat + (other.lat - self.lat) * t,
+            lng: self.lng + (other.lng - self.lng) * t,
+        }
+    }
+}
+
+/// A route's start, waypoints and end as a polyline. Points that can't be
+/// read are left out.
+pub fn route_path(route: &RouteOption) -> Vec<Coordinates> {
+    let waypoints = route.waypoints.as_deref().unwrap_or("");
+    std::iter::once(route.start_coordinates.as_str())
+        .chain(waypoints.split(';').filter(|point| !point.trim().is_empty()))
+        .chain(std::iter::once(route.end_coordinates.as_str()))
+        .filter_map(Coordinates::parse)
+        .collect()
+}
+
+/// Length of a polyline in kilometres.
+pub fn path_length_km(path: &[Coordinates]) -> f64 {
+    path.windows(2).map(|pair| pair[0].distance_km(&pair[1])).sum()
+}
+
+/// How far along `path` the point closest to `point` lies, as a fraction of
+/// the path's length from 0 to 1.
+pub fn fraction_along(path: &[Coordinates], point: &Coordinates) -> f64 {
+    let total = path_length_km(path);
+    if total == 0.0 {
+        return 0.0;
+    }
+
+    closest_on_path(path, point).1 / total
+}
+
+/// How far `point` is from the nearest point of `path`, in kilometres.
+pub fn distance_from_path_km(path: &[Coordinates], point: &Coordinates) -> f64 {
+    match path {
+        [] => f64::INFINITY,
+        [only] => point.distance_km(only),
+        _ => closest_on_path(path, point).0,
+    }
+}
+
+/// How many kilometres of `path` run within `tolerance_km` of `other`,
+/// judged at the middle of each of a couple of hundred equal pieces.
+pub fn overlap_km(path: &[Coordinates], other: &[Coordinates], tolerance_km: f64) -> f64 {
+    let total = path_length_km(path);
+    if total == 0.0 || other.is_empty() {
+        return 0.0;
+    }
+
+    let step = total / OVERLAP_SAMPLES;
+    let mut shared = 0.0;
+    for pair in path.windows(2) {
+        let (a, b) = (pair[0], pair[1]);
+        let length = a.distance_km(&b);
+        let pieces = (length / step).ceil().max(1.0);
+        for piece in 0..pieces as usize {
+            let middle = a.lerp(&b, (piece as f64 + 0.5) / pieces);
+            if distance_from_path_km(other, &middle) <= tolerance_km {
+                shared += length / pieces;
+            }
+        }
+    }
+
+    shared
+}
+
+/// `(distance off the path, distance along it)` of the path's point closest
+/// to `point`, both in kilometres.
+fn closest_on_path(path: &[Coordinates], point: &Coordinates) -> (f64, f64) {
+    let mut travelled = 0.0;
+    let mut best = (f64::INFINITY, 0.0);
+    for pair in path.windows(2) {
+        let (a, b) = (pair[0], pair[1]);
+        let length = a.distance_km(&b);
+        // Project onto the segment in a local flat approximation around `a`
+        let scale = a.lat.to_radians().cos();
+        let (dx, dy) = ((b.lng - a.lng) * scale, b.lat - a.lat);
+        let (px, py) = ((point.lng - a.lng) * scale, point.lat - a.lat);
+        let squared = dx * dx + dy * dy;
+        let t = if squared == 0.0 {
+            0.0
+        } else {
+            ((px * dx + py * dy) / squared).clamp(0.0, 1.0)
+        };
+
+        let off_path = point.distance_km(&a.lerp(&b, t));
+        if off_path < best.0 {
+            best = (off_path, travelled + t * length);
+        }
+        travelled += length;
+    }
+
+    best
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn places_points_along_a_path() {
+        assert!(Coordinates::parse("91,0").is_none());
+        assert!(Coordinates::parse("52.37").is_none());
+
+        let path: Vec<Coordinates> = ["0,0", "0,1", "0,2"]
+            .iter()
+            .map(|text| Coordinates::parse(text).unwrap())
+            .collect();
+        // One degree of longitude on the equator
+        assert!((path[0].distance_km(&path[1]) - 111.19).abs() < 0.01);
+
+        let near_middle = Coordinates::parse("0.01,1.0").unwrap();
+        assert!((fraction_along(&path, &near_middle) - 0.5).abs() < 1e-6);
+        let past_the_end = Coordinates::parse("0,3").unwrap();
+        assert_eq!(fraction_along(&path, &past_the_end), 1.0);
+        assert!((distance_from_path_km(&path, &near_middle) - 1.11).abs() < 0.01);
+        assert!((distance_from_path_km(&path, &past_the_end) - 111.19).abs() < 0.01);
+
+        // The fork leaves the path halfway along
+        let fork: Vec<Coordinates> = ["0,0", "0,1", "1,2"]
+            .iter()
+            .map(|text| Coordinates::parse(text).unwrap())
+            .collect();
+        let shared = overlap_km(&path, &fork, 1.0);
+        assert!(shared > 111.0 && shared < 115.0, "{}", shared);
+        assert!((overlap_km(&path, &path, 0.1) - path_length_km(&path)).abs() < 1e-6);
+        assert_eq!(overlap_km(&path, &[], 1.0), 0.0);
+    }
+}
@@ -0,0 +1,574 @@
+//! Proposes a day-by-day schedule for travelling one route option: travel is
+//! capped per day and kept to daylight hours, nights are spent at hotels on
+//! the route where possible and the other points of interest are visited as
+//! they are passed.
+
+use chrono::{DateTime, Duration, NaiveDate, NaiveTime, Utc};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::models::itinerary::{ItineraryItem, ItineraryItemKind, NewItineraryItem};
+use crate::models::point_of_interest::PointOfInterest;
+use crate::models::route_option::RouteOption;
+use crate::models::travel_mode::TravelMode;
+use crate::services::geo::{self, Coordinates};
+
+/// More days than this and the pace is taken to be a mistake.
+pub const MAX_DAYS: usize = 366;
+const MIN_DAILY_TRAVEL_MINUTES: i64 = 30;
+const HOTEL_CATEGORY: &str = "Hotel";
+
+#[derive(Debug, Default, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ScheduleRequest {
+    /// Defaults to the plan's selected route.
+    pub route_option_id: Option<String>,
+    #[serde(default)]
+    pub mode: TravelMode,
+    /// Defaults to a limit that suits the mode, e.g. 8 hours of driving.
+    pub max_daily_travel_minutes: Option<i64>,
+    /// When days start, in UTC; defaults to 08:00.
+    #[schema(value_type = Option<String>, example = "08:00:00")]
+    pub daylight_start: Option<NaiveTime>,
+    /// When days end, in UTC; defaults to 20:00.
+    #[schema(value_type = Option<String>, example = "20:00:00")]
+    pub daylight_end: Option<NaiveTime>,
+    /// Add the proposed items to the itinerary instead of only returning them.
+    #[serde(default)]
+    pub apply: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ScheduledDay {
+    pub date: NaiveDate,
+    pub travel_minutes: i64,
+    /// Kilometres covered.
+    pub distance: f64,
+    /// In the order they happen.
+    pub items: Vec<ItineraryItem>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct UnscheduledPoi {
+    pub poi_id: String,
+    pub name: String,
+    pub reason: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ScheduleProposal {
+    pub route_option_id: String,
+    pub mode: TravelMode,
+    pub max_daily_travel_minutes: i64,
+    pub days: Vec<ScheduledDay>,
+    /// Points of interest the schedule leaves out, and why.
+    pub unscheduled: Vec<UnscheduledPoi>,
+    /// Whether the trip is over by the plan's end date.
+    pub fits_plan_dates: bool,
+    /// Whether the items were added to the itinerary.
+    pub applied: bool,
+}
+
+/// A checked [`ScheduleRequest`].
+#[derive(Debug, Clone, Copy)]
+pub struct ScheduleSettings {
+    pub mode: TravelMode,
+    pub max_daily_travel_minutes: i64,
+    pub daylight_start: NaiveTime,
+    pub daylight_end: NaiveTime,
+}
+
+impl ScheduleSettings {
+    pub fn from_request(request: &ScheduleRequest) -> Result<Self, String> {
+        let settings = ScheduleSettings {
+            mode: request.mode,
+            max_daily_travel_minutes: request
+                .max_daily_travel_minutes
+                .unwrap_or_else(|| default_daily_minutes(request.mode)),
+            daylight_start: request
+                .daylight_start
+                .unwrap_or(NaiveTime::from_hms_opt(8, 0, 0).unwrap()),
+            daylight_end: request
+                .daylight_end
+                .unwrap_or(NaiveTime::from_hms_opt(20, 0, 0).unwrap()),
+        };
+
+        if settings.max_daily_travel_minutes < MIN_DAILY_TRAVEL_MINUTES {
+            return Err(format!(
+                "maxDailyTravelMinutes must be at least {}",
+                MIN_DAILY_TRAVEL_MINUTES
+            ));
+        }
+        if settings.daylight_minutes() < MIN_DAILY_TRAVEL_MINUTES as f64 {
+            return Err(format!(
+                "daylightEnd must be at least {} minutes after daylightStart",
+                MIN_DAILY_TRAVEL_MINUTES
+            ));
+        }
+        Ok(settings)
+    }
+
+    fn daylight_minutes(&self) -> f64 {
+        (self.daylight_end - self.daylight_start).num_seconds() as f64 / 60.0
+    }
+
+    /// The most travelling that fits in one fresh day.
+    fn day_capacity(&self) -> f64 {
+        (self.max_daily_travel_minutes as f64).min(self.daylight_minutes())
+    }
+}
+
+fn default_daily_minutes(mode: TravelMode) -> i64 {
+    match mode {
+        TravelMode::Driving => 8 * 60,
+        TravelMode::Cycling => 5 * 60,
+        TravelMode::Walking => 6 * 60,
+        TravelMode::Transit => 10 * 60,
+        TravelMode::Flight => 12 * 60,
+    }
+}
+
+/// How long a stop at a point of interest takes, by its category.
+fn visit_minutes(category: Option<&str>) -> f64 {
+    match category {
+        Some("Museum") | Some("Beach") => 120.0,
+        Some("Mountain") => 180.0,
+        Some("Forest") => 90.0,
+        Some("Landmark") => 45.0,
+        _ => 60.0,
+    }
+}
+
+fn is_hotel(poi: &PointOfInterest) -> bool {
+    poi.category.as_deref() == Some(HOTEL_CATEGORY)
+}
+
+fn minutes(duration: f64) -> Duration {
+    Duration::seconds((duration * 60.0).round() as i64)
+}
+
+fn at_time(date: NaiveDate, time: NaiveTime) -> DateTime<Utc> {
+    date.and_time(time).and_utc()
+}
+
+/// Total travel time over the route in minutes, and its length in km. The
+/// stored duration is a driving time, so other modes go by distance.
+fn route_totals(route: &RouteOption, mode: TravelMode) -> Result<(f64, f64), String> {
+    let driving_speed = TravelMode::Driving.average_speed_kmh();
+    let distance = match (route.distance, route.duration) {
+        (Some(distance), _) => distance,
+        (None, Some(duration)) => duration as f64 / 60.0 * driving_speed,
+        (None, None) => return Err("The route has no distance or duration".to_string()),
+    };
+    let travel_minutes = match (mode, route.duration) {
+        (TravelMode::Driving, Some(duration)) => duration as f64,
+        _ => distance / mode.average_speed_kmh() * 60.0,
+    };
+    Ok((travel_minutes, distance))
+}
+
+/// A point of interest placed on the route.
+struct Stop<'a> {
+    poi: &'a PointOfInterest,
+    /// How far along the route, from 0 to 1.
+    fraction: f64,
+}
+
+#[derive(Default)]
+struct DayDraft {
+    travel_minutes: f64,
+    distance: f64,
+    travel_start: Option<DateTime<Utc>>,
+    travel_end: Option<DateTime<Utc>>,
+    items: Vec<ItineraryItem>,
+}
+
+struct Scheduler<'a> {
+    plan_id: &'a str,
+    route: &'a RouteOption,
+    settings: ScheduleSettings,
+    total_minutes: f64,
+    total_distance: f64,
+    first_day: NaiveDate,
+    days: Vec<DayDraft>,
+    at: DateTime<Utc>,
+    travelled_today: f64,
+}
+
+impl Scheduler<'_> {
+    fn today(&self) -> NaiveDate {
+        self.first_day + Duration::days(self.days.len() as i64 - 1)
+    }
+
+    fn daylight_left(&self) -> f64 {
+        let end = at_time(self.today(), self.settings.daylight_end);
+        (end - self.at).num_seconds() as f64 / 60.0
+    }
+
+    fn start_next_day(&mut self) -> Result<(), String> {
+        if self.days.len() >= MAX_DAYS {
+            return Err(format!(
+                "The route takes more than {} days at this pace",
+                MAX_DAYS
+            ));
+        }
+        self.days.push(DayDraft::default());
+        self.at = at_time(self.today(), self.settings.daylight_start);
+        self.travelled_today = 0.0;
+        Ok(())
+    }
+
+    fn item(
+        &self,
+        kind: ItineraryItemKind,
+        title: &str,
+        notes: Option<String>,
+        poi_id: Option<&str>,
+        ends_at: DateTime<Utc>,
+    ) -> ItineraryItem {
+        let new_item = NewItineraryItem {
+            day: self.today(),
+            kind,
+            title: None,
+            notes,
+            route_option_id: (kind != ItineraryItemKind::Activity).then(|| self.route.id.clone()),
+            poi_id: poi_id.map(str::to_string),
+            starts_at: Some(self.at),
+            ends_at: Some(ends_at),
+        };
+        ItineraryItem::new(self.plan_id, &new_item, title.to_string(), 0)
+    }
+
+    /// Travels `duration` minutes further, carrying on over as many days as
+    /// it takes.
+    fn travel(&mut self, mut duration: f64) -> Result<(), String> {
+        while duration > 0.5 {
+            let available = (self.settings.max_daily_travel_minutes as f64 - self.travelled_today)
+                .min(self.daylight_left());
+            if available < 1.0 {
+                self.stay_overnight(None)?;
+                continue;
+            }
+
+            let leg = duration.min(available);
+            let start = self.at;
+            self.at += minutes(leg);
+            self.travelled_today += leg;
+            duration -= leg;
+
+            let distance = leg / self.total_minutes * self.total_distance;
+            let today = self.days.last_mut().unwrap();
+            today.travel_minutes += leg;
+            today.distance += distance;
+            today.travel_start.get_or_insert(start);
+            today.travel_end = Some(self.at);
+        }
+        Ok(())
+    }
+
+    /// Ends the day, at `hotel` or wherever travel stopped.
+    fn stay_overnight(&mut self, hotel: Option<&PointOfInterest>) -> Result<(), String> {
+        let next_morning = at_time(
+            self.today() + Duration::days(1),
+            self.settings.daylight_start,
+        );
+        let item = match hotel {
+            Some(hotel) => self.item(
+                ItineraryItemKind::PoiVisit,
+                &hotel.name,
+                Some("Overnight stop".to_string()),
+                Some(&hotel.id),
+                next_morning,
+            ),
+            None => self.item(
+                ItineraryItemKind::Activity,
+                "Overnight stop",
+                Some("No hotel on the route within the day's travel".to_string()),
+                None,
+                next_morning,
+            ),
+        };
+        self.days.last_mut().unwrap().items.push(item);
+        self.start_next_day()
+    }
+
+    /// Visits `poi` now if it fits in what is left of the daylight.
+    fn visit(&mut self, poi: &PointOfInterest) -> bool {
+        let duration = visit_minutes(poi.category.as_deref());
+        if duration > self.daylight_left() {
+            return false;
+        }
+
+        let item = self.item(
+            ItineraryItemKind::PoiVisit,
+            &poi.name,
+            poi.category.clone(),
+            Some(&poi.id),
+            self.at + minutes(duration),
+        );
+        self.days.last_mut().unwrap().items.push(item);
+        self.at += minutes(duration);
+        true
+    }
+
+    fn into_days(self) -> Vec<ScheduledDay> {
+        let route = self.route;
+        let plan_id = self.plan_id;
+        let first_day = self.first_day;
+        self.days
+            .into_iter()
+            .enumerate()
+            .map(|(index, draft)| {
+                let date = first_day + Duration::days(index as i64);
+                let mut items = Vec::with_capacity(draft.items.len() + 1);
+                if let (Some(start), Some(end)) = (draft.travel_start, draft.travel_end) {
+                    let leg = NewItineraryItem {
+                        day: date,
+                        kind: ItineraryItemKind::RouteLeg,
+                        title: None,
+                        notes: Some(format!("{:.0} km on the road", draft.distance)),
+                        route_option_id: Some(route.id.clone()),
+                        poi_id: None,
+                        starts_at: Some(start),
+                        ends_at: Some(end),
+                    };
+                    items.push(ItineraryItem::new(plan_id, &leg, route.name.clone(), 0));
+                }
+                items.extend(draft.items);
+                // Stable, so a day's travel stays ahead of a stop made as it starts
+                items.sort_by_key(|item| item.starts_at);
+                for (position, item) in items.iter_mut().enumerate() {
+                    item.position = position as i64;
+                }
+
+                ScheduledDay {
+                    date,
+                    travel_minutes: draft.travel_minutes.round() as i64,
+                    distance: draft.distance,
+                    items,
+                }
+            })
+            .collect()
+    }
+}
+
+/// Schedules travelling `route` from `plan_start`, visiting `pois` on the way.
+pub fn propose(
+    plan_id: &str,
+    plan_start: DateTime<Utc>,
+    plan_end: DateTime<Utc>,
+    route: &RouteOption,
+    pois: &[PointOfInterest],
+    settings: ScheduleSettings,
+) -> Result<ScheduleProposal, String> {
+    let (total_minutes, total_distance) = route_totals(route, settings.mode)?;
+
+    let path = geo::route_path(route);
+    let mut unscheduled = Vec::new();
+    let mut stops = Vec::new();
+    for poi in pois {
+        match Coordinates::parse(&poi.coordinates) {
+            Some(point) => stops.push(Stop {
+                poi,
+                fraction: geo::fraction_along(&path, &point),
+            }),
+            None => unscheduled.push(UnscheduledPoi {
+                poi_id: poi.id.clone(),
+                name: poi.name.clone(),
+                reason: "Its coordinates can't be read".to_string(),
+            }),
+        }
+    }
+    stops.sort_by(|a, b| a.fraction.total_cmp(&b.fraction));
+
+    let first_day = plan_start.date_naive();
+    let mut scheduler = Scheduler {
+        plan_id,
+        route,
+        settings,
+        total_minutes,
+        total_distance,
+        first_day,
+        days: vec![DayDraft::default()],
+        at: plan_start.max(at_time(first_day, settings.daylight_start)),
+        travelled_today: 0.0,
+    };
+    if scheduler.daylight_left() < 1.0 {
+        scheduler.start_next_day()?;
+    }
+
+    let mut position = 0.0;
+    for (index, stop) in stops.iter().enumerate() {
+        scheduler.travel((stop.fraction - position) * total_minutes)?;
+        position = stop.fraction;
+
+        if is_hotel(stop.poi) {
+            // Stay if the next hotel, or the end of the route, is out of reach
+            // today but not from here tomorrow
+            let ahead = &stops[index + 1..];
+            let next_night = ahead.iter().position(|stop| is_hotel(stop.poi));
+            let (fraction, on_the_way) = match next_night {
+                Some(next) => (ahead[next].fraction, &ahead[..next]),
+                None => (1.0, ahead),
+            };
+            let travel = (fraction - position) * total_minutes;
+            let needed = travel
+                + on_the_way
+                    .iter()
+                    .map(|stop| visit_minutes(stop.poi.category.as_deref()))
+                    .sum::<f64>();
+            let reachable_today = scheduler.travelled_today + travel
+                <= settings.max_daily_travel_minutes as f64
+                && needed <= scheduler.daylight_left();
+            let reachable_tomorrow = travel <= settings.day_capacity()
+                && needed <= settings.daylight_minutes();
+
+            if !reachable_today && reachable_tomorrow {
+                scheduler.stay_overnight(Some(stop.poi))?;
+            } else {
+                unscheduled.push(UnscheduledPoi {
+                    poi_id: stop.poi.id.clone(),
+                    name: stop.poi.name.clone(),
+                    reason: "Not needed as an overnight stop".to_string(),
+                });
+            }
+        } else if !scheduler.visit(stop.poi) {
+            unscheduled.push(UnscheduledPoi {
+                poi_id: stop.poi.id.clone(),
+                name: stop.poi.name.clone(),
+                reason: "Doesn't fit in the day's daylight hours".to_string(),
+            });
+        }
+    }
+    scheduler.travel((1.0 - position) * total_minutes)?;
+
+    let fits_plan_dates = scheduler.at <= plan_end;
+    Ok(ScheduleProposal {
+        route_option_id: route.id.clone(),
+        mode: settings.mode,
+        max_daily_travel_minutes: settings.max_daily_travel_minutes,
+        days: scheduler.into_days(),
+        unscheduled,
+        fits_plan_dates,
+        applied: false,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::point_of_interest::NewPointOfInterest;
+    use crate::models::route_option::NewRouteOption;
+    use chrono::TimeZone;
+
+    fn route(distance: f64, duration: i64) -> RouteOption {
+        RouteOption::new(&NewRouteOption {
+            travel_plan_id: "plan".to_string(),
+            leg_id: None,
+            name: "Along the equator".to_string(),
+            description: None,
+            distance: Some(distance),
+            duration: Some(duration),
+            start_coordinates: "0,0".to_string(),
+            end_coordinates: "0,10".to_string(),
+            waypoints: None,
+        })
+    }
+
+    fn poi(route: &RouteOption, name: &str, category: &str, lng: f64) -> PointOfInterest {
+        PointOfInterest::new(&NewPointOfInterest {
+            route_option_id: route.id.clone(),
+            name: name.to_string(),
+            description: None,
+            category: Some(category.to_string()),
+            coordinates: format!("0,{}", lng),
+        })
+    }
+
+    fn settings(request: &ScheduleRequest) -> ScheduleSettings {
+        ScheduleSettings::from_request(request).unwrap()
+    }
+
+    #[test]
+    fn spends_nights_at_hotels_and_caps_daily_travel() {
+        // 20 hours of driving over 1000 km; hotels at 35% and 70% of the way
+        let route = route(1000.0, 1200);
+        let pois = vec![
+            poi(&route, "Hotel Early", "Hotel", 3.5),
+            poi(&route, "Hotel Late", "Hotel", 7.0),
+            poi(&route, "Art Museum", "Museum", 1.0),
+            poi(&route, "Broken", "Park", 0.0),
+        ];
+        let mut pois = pois;
+        pois[3].coordinates = "nowhere".to_string();
+
+        let start = Utc.with_ymd_and_hms(2030, 6, 1, 9, 0, 0).unwrap();
+        let end = Utc.with_ymd_and_hms(2030, 6, 10, 18, 0, 0).unwrap();
+        let proposal = propose(
+            "plan",
+            start,
+            end,
+            &route,
+            &pois,
+            settings(&ScheduleRequest::default()),
+        )
+        .unwrap();
+
+        assert!(proposal.fits_plan_dates);
+        assert_eq!(proposal.days.len(), 3);
+        for day in &proposal.days {
+            assert!(day.travel_minutes <= 480);
+        }
+        let total: i64 = proposal.days.iter().map(|day| day.travel_minutes).sum();
+        assert!((total - 1200).abs() <= 1);
+
+        // The first night is at the early hotel, the second at the late one
+        let nights: Vec<&str> = proposal
+            .days
+            .iter()
+            .filter_map(|day| day.items.last())
+            .filter(|item| item.notes.as_deref() == Some("Overnight stop"))
+            .map(|item| item.title.as_str())
+            .collect();
+        assert_eq!(nights, ["Hotel Early", "Hotel Late"]);
+
+        let first_day = &proposal.days[0].items;
+        assert_eq!(first_day[0].kind, ItineraryItemKind::RouteLeg);
+        assert_eq!(first_day[1].title, "Art Museum");
+        assert_eq!(first_day[1].starts_at, Some(start + Duration::minutes(120)));
+        assert_eq!(first_day[1].ends_at, Some(start + Duration::minutes(240)));
+
+        assert_eq!(proposal.unscheduled.len(), 1);
+        assert_eq!(proposal.unscheduled[0].name, "Broken");
+    }
+
+    #[test]
+    fn stops_en_route_without_a_hotel_and_reports_overruns() {
+        // Walking 100 km takes 20 hours, more than two days at 6 hours a day
+        let route = route(100.0, 60);
+        let request = ScheduleRequest {
+            mode: TravelMode::Walking,
+            ..Default::default()
+        };
+
+        let start = Utc.with_ymd_and_hms(2030, 6, 1, 0, 0, 0).unwrap();
+        let end = Utc.with_ymd_and_hms(2030, 6, 2, 23, 0, 0).unwrap();
+        let proposal = propose("plan", start, end, &route, &[], settings(&request)).unwrap();
+
+        assert_eq!(proposal.max_daily_travel_minutes, 360);
+        assert_eq!(proposal.days.len(), 4);
+        assert!(!proposal.fits_plan_dates);
+        let first_night = proposal.days[0].items.last().unwrap();
+        assert_eq!(first_night.kind, ItineraryItemKind::Activity);
+        assert_eq!(first_night.starts_at, Some(start + Duration::hours(14)));
+
+        let too_short = ScheduleRequest {
+            max_daily_travel_minutes: Some(10),
+            ..Default::default()
+        };
+        assert!(ScheduleSettings::from_request(&too_short).is_err());
+    }
+}
@@ -0,0 +1,607 @@
+use chrono::{DateTime, NaiveDate, Utc};
+use log::{error, info};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::sync::Arc;
+use utoipa::ToSchema;
+
+use crate::models::itinerary::{
+    ItineraryItem, ItineraryItemKind, NewItineraryItem, PatchItineraryItem,
+};
+use crate::models::plan_member::PlanRole;
+use crate::models::travel_plan::TravelPlan;
+use crate::repositories::{
+    BudgetRepository, CommentRepository, ItineraryRepository, PlanEventRepository,
+    PlanLegRepository, PlanMemberRepository, PlanRevisionRepository, PoiRepository, RepositoryError,
+    RouteOptionRepository, RouteVoteRepository, ShareLinkRepository, TravelPlanRepository,
+};
+use crate::services::blocking::BlockingError;
+use crate::services::itinerary_scheduler::{
+    self, ScheduleProposal, ScheduleRequest, ScheduleSettings,
+};
+use crate::services::travel_plan_service::{TravelPlanError, TravelPlanService};
+
+/// Splits a plan's dates into days and keeps an ordered list of items on
+/// each. Members can read the itinerary; editors change it.
+pub struct ItineraryService<R: ?Sized> {
+    repos: Arc<R>,
+    travel_plans: TravelPlanService<R>,
+}
+
+#[derive(Debug)]
+pub enum ItineraryError {
+    TravelPlanError(TravelPlanError),
+    ItemNotFound,
+    /// The plan needs both a start and an end date before items can go on it.
+    NoDateRange,
+    InvalidItem(String),
+    DatabaseError(String),
+}
+
+impl From<TravelPlanError> for ItineraryError {
+    fn from(error: TravelPlanError) -> Self {
+        ItineraryError::TravelPlanError(error)
+    }
+}
+
+impl From<BlockingError> for ItineraryError {
+    fn from(error: BlockingError) -> Self {
+        ItineraryError::DatabaseError(error.to_string())
+    }
+}
+
+impl From<RepositoryError> for ItineraryError {
+    fn from(error: RepositoryError) -> Self {
+        error!("Itinerary repository error: {}", error);
+        ItineraryError::DatabaseError(error.to_string())
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ItineraryDay {
+    pub date: NaiveDate,
+    /// In the order they happen.
+    pub items: Vec<ItineraryItem>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct Itinerary {
+    /// Every day from the plan's start date to its end date, empty or not,
+    /// but no more than [`itinerary_scheduler::MAX_DAYS`] of them.
+    pub days: Vec<ItineraryDay>,
+    /// Items left outside the plan's dates after they were changed, or past
+    /// the last day listed.
+    pub out_of_range: Vec<ItineraryItem>,
+}
+
+/// The plan's first and last day, if it has both dates.
+fn date_range(plan: &TravelPlan) -> Option<(NaiveDate, NaiveDate)> {
+    match (plan.start_date, plan.end_date) {
+        (Some(start), Some(end)) => Some((start.date_naive(), end.date_naive())),
+        _ => None,
+    }
+}
+
+/// Checks that an item sits inside the plan's dates and that its time
+/// window, if any, is on its day and in order.
+fn validate_schedule(plan: &TravelPlan, item: &ItineraryItem) -> Result<(), ItineraryError> {
+    let (Some(plan_start), Some(plan_end)) = (plan.start_date, plan.end_date) else {
+        return Err(ItineraryError::NoDateRange);
+    };
+
+    if item.day < plan_start.date_naive() || item.day > plan_end.date_naive() {
+        return Err(ItineraryError::InvalidItem(format!(
+            "{} is outside the plan's dates",
+            item.day
+        )));
+    }
+
+    let within_plan = |at: DateTime<Utc>| at >= plan_start && at <= plan_end;
+    if let Some(starts_at) = item.starts_at {
+        if starts_at.date_naive() != item.day {
+            return Err(ItineraryError::InvalidItem(
+                "startsAt must fall on the item's day".to_string(),
+            ));
+        }
+        if !within_plan(starts_at) {
+            return Err(ItineraryError::InvalidItem(
+                "startsAt is outside the plan's dates".to_string(),
+            ));
+        }
+    }
+    if let Some(ends_at) = item.ends_at {
+        if !within_plan(ends_at) {
+            return Err(ItineraryError::InvalidItem(
+                "endsAt is outside the plan's dates".to_string(),
+            ));
+        }
+        if item.starts_at.is_some_and(|starts_at| ends_at < starts_at) {
+            return Err(ItineraryError::InvalidItem(
+                "endsAt is before startsAt".to_string(),
+            ));
+        }
+    }
+
+    let has_window = item.starts_at.is_some() && item.ends_at.is_some();
+    if item.kind == ItineraryItemKind::PoiVisit && !has_window {
+        return Err(ItineraryError::InvalidItem(
+            "A point of interest visit needs startsAt and endsAt".to_string(),
+        ));
+    }
+    if item.title.trim().is_empty() {
+        return Err(ItineraryError::InvalidItem("title must not be empty".to_string()));
+    }
+
+    Ok(())
+}
+
+impl<R> ItineraryService<R>
+where
+    R: TravelPlanRepository
+        + PlanMemberRepository
+        + ShareLinkRepository
+        + RouteOptionRepository
+        + RouteVoteRepository
+        + PoiRepository
+        + CommentRepository
+        + PlanEventRepository
+        + PlanRevisionRepository
+        + ItineraryRepository
+        + PlanLegRepository
+        + BudgetRepository
+        + ?Sized,
+{
+    pub fn new(repos: Arc<R>) -> Self {
+        ItineraryService {
+            travel_plans: TravelPlanService::new(repos.clone()),
+            repos,
+        }
+    }
+
+    fn item_in_plan(&self, plan_id: &str, item_id: &str) -> Result<ItineraryItem, ItineraryError> {
+        match self.repos.find_itinerary_item(item_id)? {
+            Some(item) if item.travel_plan_id == plan_id => Ok(item),
+            _ => Err(ItineraryError::ItemNotFound),
+        }
+    }
+
+    /// One past the last position used on `day`.
+    fn next_position(&self, plan_id: &str, day: NaiveDate) -> Result<i64, ItineraryError> {
+        Ok(self
+            .repos
+            .find_itinerary_items(plan_id)?
+            .iter()
+            .filter(|item| item.day == day)
+            .map(|item| item.position + 1)
+            .max()
+            .unwrap_or(0))
+    }
+
+    /// Fills in what a new item refers to, and its default title, from the
+    /// route or point of interest it names.
+    fn resolve_reference(
+        &self,
+        plan_id: &str,
+        new_item: &mut NewItineraryItem,
+    ) -> Result<String, ItineraryError> {
+        let invalid = |message: &str| Err(ItineraryError::InvalidItem(message.to_string()));
+
+        let default_title = match new_item.kind {
+            ItineraryItemKind::RouteLeg => {
+                if new_item.poi_id.is_some() {
+                    return invalid("A route leg can't name a point of interest");
+                }
+                let Some(route_id) = new_item.route_option_id.as_deref() else {
+                    return invalid("A route leg needs a routeOptionId");
+                };
+                match self.repos.find_route_option(route_id)? {
+                    Some(route) if route.travel_plan_id == plan_id => route.name,
+                    _ => return invalid("The route option is not part of this plan"),
+                }
+            }
+            ItineraryItemKind::PoiVisit => {
+                let Some(poi_id) = new_item.poi_id.as_deref() else {
+                    return invalid("A point of interest visit needs a poiId");
+                };
+                let poi = self.repos.find_poi(poi_id)?;
+                let route = match &poi {
+                    Some(poi) => self.repos.find_route_option(&poi.route_option_id)?,
+                    None => None,
+                };
+                match (poi, route) {
+                    (Some(poi), Some(route)) if route.travel_plan_id == plan_id => {
+                        if new_item
+                            .route_option_id
+                            .as_ref()
+                            .is_some_and(|route_id| *route_id != route.id)
+                        {
+                            return invalid("The point of interest is not on that route");
+                        }
+                        new_item.route_option_id = Some(route.id);
+                        poi.name
+                    }
+                    _ => return invalid("The point of interest is not part of this plan"),
+                }
+            }
+            ItineraryItemKind::Activity => {
+                if new_item.route_option_id.is_some() || new_item.poi_id.is_some() {
+                    return invalid("An activity can't name a route or point of interest");
+                }
+                String::new()
+            }
+        };
+
+        Ok(new_item.title.clone().unwrap_or(default_title))
+    }
+
+    /// The plan's days with their items, open to every member.
+    pub fn itinerary(&self, plan_id: &str, user_id: &str) -> Result<Itinerary, ItineraryError> {
+        let plan = self
+            .travel_plans
+            .authorize(plan_id, user_id, PlanRole::Viewer)?
+            .travel_plan;
+        let mut items = self.repos.find_itinerary_items(plan_id)?;
+
+        let mut days = Vec::new();
+        if let Some((first, last)) = date_range(&plan) {
+            let span = first.iter_days().take_while(|date| *date <= last);
+            for date in span.take(itinerary_scheduler::MAX_DAYS) {
+                let (on_day, rest) = items.into_iter().partition(|item| item.day == date);
+                items = rest;
+                days.push(ItineraryDay {
+                    date,
+                    items: on_day,
+                });
+            }
+        }
+
+        Ok(Itinerary {
+            days,
+            out_of_range: items,
+        })
+    }
+
+    pub fn get_item(
+        &self,
+        plan_id: &str,
+        item_id: &str,
+        user_id: &str,
+    ) -> Result<ItineraryItem, ItineraryError> {
+        self.travel_plans.authorize(plan_id, user_id, PlanRole::Viewer)?;
+        self.item_in_plan(plan_id, item_id)
+    }
+
+    /// Adds an item at the end of its day.
+    pub fn add_item(
+        &self,
+        plan_id: &str,
+        mut new_item: NewItineraryItem,
+        user_id: &str,
+    ) -> Result<ItineraryItem, ItineraryError> {
+        let plan = self
+            .travel_plans
+            .authorize(plan_id, user_id, PlanRole::Editor)?
+            .travel_plan;
+
+        let title = self.resolve_reference(plan_id, &mut new_item)?;
+        let position = self.next_position(plan_id, new_item.day)?;
+        let item = ItineraryItem::new(plan_id, &new_item, title, position);
+        validate_schedule(&plan, &item)?;
+
+        self.repos.insert_itinerary_item(&item)?;
+        info!(
+            "User {} added {} item {} to travel plan {} on {}",
+            user_id,
+            item.kind.as_str(),
+            item.id,
+            plan_id,
+            item.day
+        );
+        Ok(item)
+    }
+
+    /// Applies a merge patch to an item. Moving it to another day puts it
+    /// last there.
+    pub fn patch_item(
+        &self,
+        plan_id: &str,
+        item_id: &str,
+        patch: &PatchItineraryItem,
+        user_id: &str,
+    ) -> Result<ItineraryItem, ItineraryError> {
+        let plan = self
+            .travel_plans
+            .authorize(plan_id, user_id, PlanRole::Editor)?
+            .travel_plan;
+        let item = self.item_in_plan(plan_id, item_id)?;
+
+        let mut patched_item = item.with_patch(patch);
+        if patched_item.day != item.day {
+            patched_item.position = self.next_position(plan_id, patched_item.day)?;
+        }
+        validate_schedule(&plan, &patched_item)?;
+
+        self.repos.update_itinerary_item(&patched_item)?;
+        Ok(patched_item)
+    }
+
+    pub fn delete_item(
+        &self,
+        plan_id: &str,
+        item_id: &str,
+        user_id: &str,
+    ) -> Result<(), ItineraryError> {
+        self.travel_plans.authorize(plan_id, user_id, PlanRole::Editor)?;
+        self.item_in_plan(plan_id, item_id)?;
+
+        if !self.repos.delete_itinerary_item(item_id)? {
+            return Err(ItineraryError::ItemNotFound);
+        }
+        info!("User {} removed itinerary item {}", user_id, item_id);
+        Ok(())
+    }
+
+    /// Puts a day's items in the given order. `item_ids` must list every item
+    /// on the day exactly once.
+    pub fn reorder_day(
+        &self,
+        plan_id: &str,
+        day: NaiveDate,
+        item_ids: &[String],
+        user_id: &str,
+    ) -> Result<ItineraryDay, ItineraryError> {
+        self.travel_plans.authorize(plan_id, user_id, PlanRole::Editor)?;
+
+        let on_day: Vec<ItineraryItem> = self
+            .repos
+            .find_itinerary_items(plan_id)?
+            .into_iter()
+            .filter(|item| item.day == day)
+            .collect();
+
+        let current: HashSet<&str> = on_day.iter().map(|item| item.id.as_str()).collect();
+        let requested: HashSet<&str> = item_ids.iter().map(String::as_str).collect();
+        if requested.len() != item_ids.len() || requested != current {
+            return Err(ItineraryError::InvalidItem(format!(
+                "itemIds must list each of the {} items on {} exactly once",
+                on_day.len(),
+                day
+            )));
+        }
+
+        let mut items = Vec::with_capacity(on_day.len());
+        for (position, item_id) in item_ids.iter().enumerate() {
+            let mut item = on_day
+                .iter()
+                .find(|item| item.id == *item_id)
+                .cloned()
+                .ok_or(ItineraryError::ItemNotFound)?;
+            if item.position != position as i64 {
+                item.position = position as i64;
+                item.updated_at = Utc::now();
+                self.repos.update_itinerary_item(&item)?;
+            }
+            items.push(item);
+        }
+
+        info!(
+            "User {} reordered {} itinerary items of travel plan {} on {}",
+            user_id,
+            items.len(),
+            plan_id,
+            day
+        );
+        Ok(ItineraryDay { date: day, items })
+    }
+
+    /// Proposes a schedule for travelling a route over the plan's days, and
+    /// adds it to the itinerary if the request says to. Any member can ask
+    /// for a proposal; applying one takes an editor.
+    pub fn propose_schedule(
+        &self,
+        plan_id: &str,
+        request: &ScheduleRequest,
+        user_id: &str,
+    ) -> Result<ScheduleProposal, ItineraryError> {
+        let role = if request.apply { PlanRole::Editor } else { PlanRole::Viewer };
+        let plan = self.travel_plans.authorize(plan_id, user_id, role)?.travel_plan;
+        let (Some(plan_start), Some(plan_end)) = (plan.start_date, plan.end_date) else {
+            return Err(ItineraryError::NoDateRange);
+        };
+
+        let Some(route_id) = request
+            .route_option_id
+            .as_deref()
+            .or(plan.selected_route_id.as_deref())
+        else {
+            return Err(ItineraryError::InvalidItem(
+                "Name a routeOptionId or select a route for the plan first".to_string(),
+            ));
+        };
+        let route = match self.repos.find_route_option(route_id)? {
+            Some(route) if route.travel_plan_id == plan_id => route,
+            _ => {
+                return Err(ItineraryError::InvalidItem(
+                    "The route option is not part of this plan".to_string(),
+                ));
+            }
+        };
+        let pois = self.repos.find_pois_by_route(&route.id)?;
+
+        let settings =
+            ScheduleSettings::from_request(request).map_err(ItineraryError::InvalidItem)?;
+        let mut proposal =
+            itinerary_scheduler::propose(plan_id, plan_start, plan_end, &route, &pois, settings)
+                .map_err(ItineraryError::InvalidItem)?;
+        if !request.apply {
+            return Ok(proposal);
+        }
+
+        if !proposal.fits_plan_dates {
+            return Err(ItineraryError::InvalidItem(
+                "The schedule runs past the plan's end date".to_string(),
+            ));
+        }
+        for day in &mut proposal.days {
+            let offset = self.next_position(plan_id, day.date)?;
+            for item in &mut day.items {
+                item.position += offset;
+                validate_schedule(&plan, item)?;
+            }
+        }
+        for item in proposal.days.iter().flat_map(|day| &day.items) {
+            self.repos.insert_itinerary_item(item)?;
+        }
+        proposal.applied = true;
+
+        info!(
+            "User {} scheduled route {} over {} days of travel plan {}",
+            user_id,
+            route.id,
+            proposal.days.len(),
+            plan_id
+        );
+        Ok(proposal)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::travel_plan::NewTravelPlan;
+    use crate::repositories::memory::InMemoryRepository;
+    use crate::services::route_option_service::RouteOptionService;
+    use chrono::TimeZone;
+
+    fn day(d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2030, 6, d).unwrap()
+    }
+
+    fn activity(d: u32, title: &str) -> NewItineraryItem {
+        NewItineraryItem {
+            day: day(d),
+            kind: ItineraryItemKind::Activity,
+            title: Some(title.to_string()),
+            notes: None,
+            route_option_id: None,
+            poi_id: None,
+            starts_at: None,
+            ends_at: None,
+        }
+    }
+
+    #[test]
+    fn keeps_items_in_order_within_the_plan_dates() {
+        let repos = Arc::new(InMemoryRepository::new());
+        let plan_id = TravelPlanService::new(repos.clone())
+            .create_travel_plan(
+                &NewTravelPlan {
+                    name: "Road trip".to_string(),
+                    description: None,
+                    start_location: "Amsterdam".to_string(),
+                    end_location: "Berlin".to_string(),
+                    start_date: Some(Utc.with_ymd_and_hms(2030, 6, 1, 9, 0, 0).unwrap()),
+                    end_date: Some(Utc.with_ymd_and_hms(2030, 6, 3, 18, 0, 0).unwrap()),
+                    home_currency: None,
+                },
+                "alice",
+            )
+            .unwrap()
+            .travel_plan
+            .id;
+        let route = RouteOptionService::new(repos.clone())
+            .generate_route_options(&plan_id, "alice", 1)
+            .unwrap()
+            .remove(0);
+        let itinerary = ItineraryService::new(repos);
+
+        let museum = itinerary.add_item(&plan_id, activity(2, "Museum"), "alice").unwrap();
+        let dinner = itinerary.add_item(&plan_id, activity(2, "Dinner"), "alice").unwrap();
+        let leg = itinerary
+            .add_item(
+                &plan_id,
+                NewItineraryItem {
+                    kind: ItineraryItemKind::RouteLeg,
+                    title: None,
+                    route_option_id: Some(route.route.id.clone()),
+                    ..activity(1, "")
+                },
+                "alice",
+            )
+            .unwrap();
+        assert_eq!(leg.title, route.route.name);
+        assert_eq!((museum.position, dinner.position), (0, 1));
+
+        assert!(matches!(
+            itinerary.add_item(&plan_id, activity(4, "Too late"), "alice"),
+            Err(ItineraryError::InvalidItem(_))
+        ));
+        let poi = &route.points_of_interest[0];
+        assert!(matches!(
+            itinerary.add_item(
+                &plan_id,
+                NewItineraryItem {
+                    kind: ItineraryItemKind::PoiVisit,
+                    poi_id: Some(poi.id.clone()),
+                    ..activity(2, "No window")
+                },
+                "alice",
+            ),
+            Err(ItineraryError::InvalidItem(_))
+        ));
+
+        let reordered = itinerary
+            .reorder_day(&plan_id, day(2), &[dinner.id.clone(), museum.id.clone()], "alice")
+            .unwrap();
+        assert_eq!(reordered.items[0].id, dinner.id);
+        assert!(matches!(
+            itinerary.reorder_day(&plan_id, day(2), std::slice::from_ref(&dinner.id), "alice"),
+            Err(ItineraryError::InvalidItem(_))
+        ));
+
+        let view = itinerary.itinerary(&plan_id, "alice").unwrap();
+        assert_eq!(view.days.len(), 3);
+        assert_eq!(view.days[0].items[0].id, leg.id);
+        let titles: Vec<&str> = view.days[1].items.iter().map(|i| i.title.as_str()).collect();
+        assert_eq!(titles, ["Dinner", "Museum"]);
+        assert!(view.days[2].items.is_empty());
+
+        assert!(matches!(
+            itinerary.add_item(&plan_id, activity(1, "Sneaky"), "mallory"),
+            Err(ItineraryError::TravelPlanError(TravelPlanError::Unauthorized))
+        ));
+    }
+
+    #[test]
+    fn lists_at_most_a_year_of_days() {
+        let repos = Arc::new(InMemoryRepository::new());
+        let plan_id = TravelPlanService::new(repos.clone())
+            .create_travel_plan(
+                &NewTravelPlan {
+                    name: "Two thousand years".to_string(),
+                    description: None,
+                    start_location: "Amsterdam".to_string(),
+                    end_location: "Berlin".to_string(),
+                    start_date: Some(Utc.with_ymd_and_hms(2030, 6, 1, 9, 0, 0).unwrap()),
+                    end_date: Some(Utc.with_ymd_and_hms(4030, 6, 1, 18, 0, 0).unwrap()),
+                    home_currency: None,
+                },
+                "alice",
+            )
+            .unwrap()
+            .travel_plan
+            .id;
+        let itinerary = ItineraryService::new(repos);
+        let much_later = NewItineraryItem {
+            day: NaiveDate::from_ymd_opt(2032, 1, 1).unwrap(),
+            ..activity(1, "Much later")
+        };
+        itinerary.add_item(&plan_id, much_later, "alice").unwrap();
+
+        let view = itinerary.itinerary(&plan_id, "alice").unwrap();
+        assert_eq!(view.days.len(), itinerary_scheduler::MAX_DAYS);
+        assert_eq!(view.out_of_range.len(), 1);
+    }
+}
@@ -1,3 +1,26 @@
+pub mod admin_service;
 pub mod auth_service;
+pub mod blocking;
 pub mod travel_plan_service;
-pub mod route_option_service;
\ No newline at end of file
+pub mod route_option_service;
+pub mod plan_sharing_service;
+pub mod share_link_service;
+pub mod route_vote_service;
+pub mod comment_service;
+pub mod activity_service;
+pub mod plan_revision_service;
+pub mod backup_service;
+pub mod precondition;
+pub mod itinerary_service;
+pub mod geo;
+pub mod itinerary_scheduler;
+pub mod plan_leg_service;
+pub mod route_optimizer;
+pub mod budget_service;
+pub mod currency;
+pub mod route_cost;
+pub mod route_comparison;
+pub mod emissions;
+pub mod emissions_service;
+#[cfg(test)]
+mod test_fixtures;
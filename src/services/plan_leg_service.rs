@@ -0,0 +1,490 @@
+use log::{error, info};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::sync::Arc;
+use utoipa::ToSchema;
+
+use crate::models::plan_leg::{NewPlanLeg, PatchPlanLeg, PlanLeg};
+use crate::models::plan_member::PlanRole;
+use crate::models::route_option::RouteOption;
+use crate::models::travel_mode::TravelMode;
+use crate::models::travel_plan::TravelPlan;
+use crate::repositories::{
+    BudgetRepository, CommentRepository, ItineraryRepository, PlanEventRepository,
+    PlanLegRepository, PlanMemberRepository, PlanRevisionRepository, PoiRepository, RepositoryError,
+    RouteOptionRepository, RouteVoteRepository, ShareLinkRepository, TravelPlanRepository,
+};
+use crate::services::blocking::BlockingError;
+use crate::services::travel_plan_service::{TravelPlanError, TravelPlanService};
+
+/// Splits a plan into ordered legs, each with its own ends, dates and mode.
+/// Members can read the legs; editors change them.
+pub struct PlanLegService<R: ?Sized> {
+    repos: Arc<R>,
+    travel_plans: TravelPlanService<R>,
+}
+
+#[derive(Debug)]
+pub enum PlanLegError {
+    TravelPlanError(TravelPlanError),
+    LegNotFound,
+    InvalidLeg(String),
+    /// One of the leg's routes is the plan's selected route.
+    RouteLocked,
+    DatabaseError(String),
+}
+
+impl From<TravelPlanError> for PlanLegError {
+    fn from(error: TravelPlanError) -> Self {
+        PlanLegError::TravelPlanError(error)
+    }
+}
+
+impl From<BlockingError> for PlanLegError {
+    fn from(error: BlockingError) -> Self {
+        PlanLegError::DatabaseError(error.to_string())
+    }
+}
+
+impl From<RepositoryError> for PlanLegError {
+    fn from(error: RepositoryError) -> Self {
+        error!("Plan leg repository error: {}", error);
+        PlanLegError::DatabaseError(error.to_string())
+    }
+}
+
+/// One leg of a plan with the route that counts for it.
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct LegSummary {
+    /// `None` for a plan without legs, which is summarised as one.
+    pub leg_id: Option<String>,
+    pub position: i64,
+    pub start_location: String,
+    pub end_location: String,
+    pub mode: TravelMode,
+    pub route_option_id: Option<String>,
+    /// False when nothing is selected yet and the fastest option stands in.
+    pub route_selected: bool,
+    pub distance: Option<f64>,
+    pub duration: Option<i64>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct PlanSummary {
+    pub travel_plan_id: String,
+    pub legs: Vec<LegSummary>,
+    /// Kilometres over every leg that has a route.
+    pub total_distance: f64,
+    /// Minutes over every leg that has a route.
+    pub total_duration: i64,
+    /// Whether every leg has a selected route.
+    pub complete: bool,
+}
+
+/// Checks a leg's ends and that its dates are in order and within the plan's.
+fn validate_leg(plan: &TravelPlan, leg: &PlanLeg) -> Result<(), PlanLegError> {
+    if leg.start_location.trim().is_empty() || leg.end_location.trim().is_empty() {
+        return Err(PlanLegError::InvalidLeg(
+            "startLocation and endLocation must not be empty".to_string(),
+        ));
+    }
+    if let (Some(start), Some(end)) = (leg.start_date, leg.end_date)
+        && end < start
+    {
+        return Err(PlanLegError::InvalidLeg("endDate is before startDate".to_string()));
+    }
+
+    let before_plan = |date| plan.start_date.is_some_and(|start| date < start);
+    let after_plan = |date| plan.end_date.is_some_and(|end| date > end);
+    for date in [leg.start_date, leg.end_date].into_iter().flatten() {
+        if before_plan(date) || after_plan(date) {
+            return Err(PlanLegError::InvalidLeg(format!(
+                "{} is outside the plan's dates",
+                date.to_rfc3339()
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// The route that counts for a leg: the selected one, or else the fastest.
+fn leg_route<'a>(
+    routes: &'a [RouteOption],
+    selected_route_id: Option<&str>,
+) -> Option<(&'a RouteOption, bool)> {
+    if let Some(selected) = routes.iter().find(|route| Some(route.id.as_str()) == selected_route_id)
+    {
+        return Some((selected, true));
+    }
+    routes
+        .iter()
+        .min_by_key(|route| route.duration.unwrap_or(i64::MAX))
+        .map(|route| (route, false))
+}
+
+fn leg_summary(
+    leg_id: Option<String>,
+    position: i64,
+    start_location: &str,
+    end_location: &str,
+    mode: TravelMode,
+    route: Option<(&RouteOption, bool)>,
+) -> LegSummary {
+    LegSummary {
+        leg_id,
+        position,
+        start_location: start_location.to_string(),
+        end_location: end_location.to_string(),
+        mode,
+        route_option_id: route.map(|(route, _)| route.id.clone()),
+        route_selected: route.is_some_and(|(_, selected)| selected),
+        distance: route.and_then(|(route, _)| route.distance),
+        duration: route.and_then(|(route, _)| route.duration),
+    }
+}
+
+impl<R> PlanLegService<R>
+where
+    R: TravelPlanRepository
+        + PlanMemberRepository
+        + ShareLinkRepository
+        + RouteOptionRepository
+        + RouteVoteRepository
+        + PoiRepository
+        + CommentRepository
+        + PlanEventRepository
+        + PlanRevisionRepository
+        + ItineraryRepository
+        + PlanLegRepository
+        + BudgetRepository
+        + ?Sized,
+{
+    pub fn new(repos: Arc<R>) -> Self {
+        PlanLegService {
+            travel_plans: TravelPlanService::new(repos.clone()),
+            repos,
+        }
+    }
+
+    fn leg_in_plan(&self, plan_id: &str, leg_id: &str) -> Result<PlanLeg, PlanLegError> {
+        match self.repos.find_plan_leg(leg_id)? {
+            Some(leg) if leg.travel_plan_id == plan_id => Ok(leg),
+            _ => Err(PlanLegError::LegNotFound),
+        }
+    }
+
+    pub fn get_legs(&self, plan_id: &str, user_id: &str) -> Result<Vec<PlanLeg>, PlanLegError> {
+        self.travel_plans.authorize(plan_id, user_id, PlanRole::Viewer)?;
+        Ok(self.repos.find_plan_legs(plan_id)?)
+    }
+
+    pub fn get_leg(
+        &self,
+        plan_id: &str,
+        leg_id: &str,
+        user_id: &str,
+    ) -> Result<PlanLeg, PlanLegError> {
+        self.travel_plans.authorize(plan_id, user_id, PlanRole::Viewer)?;
+        self.leg_in_plan(plan_id, leg_id)
+    }
+
+    /// Adds a leg after the plan's last one.
+    pub fn add_leg(
+        &self,
+        plan_id: &str,
+        new_leg: &NewPlanLeg,
+        user_id: &str,
+    ) -> Result<PlanLeg, PlanLegError> {
+        let plan = self
+            .travel_plans
+            .authorize(plan_id, user_id, PlanRole::Editor)?
+            .travel_plan;
+
+        let position = self
+            .repos
+            .find_plan_legs(plan_id)?
+            .iter()
+            .map(|leg| leg.position + 1)
+            .max()
+            .unwrap_or(0);
+        let leg = PlanLeg::new(plan_id, new_leg, position);
+        validate_leg(&plan, &leg)?;
+
+        self.repos.insert_plan_leg(&leg)?;
+        info!(
+            "User {} added leg {} from {} to {} to travel plan {}",
+            user_id, leg.id, leg.start_location, leg.end_location, plan_id
+        );
+        Ok(leg)
+    }
+
+    /// Applies a merge patch to a leg. A selected route must be one of the
+    /// leg's own.
+    pub fn patch_leg(
+        &self,
+        plan_id: &str,
+        leg_id: &str,
+        patch: &PatchPlanLeg,
+        user_id: &str,
+    ) -> Result<PlanLeg, PlanLegError> {
+        let plan = self
+            .travel_plans
+            .authorize(plan_id, user_id, PlanRole::Editor)?
+            .travel_plan;
+        let leg = self.leg_in_plan(plan_id, leg_id)?;
+
+        let patched_leg = leg.with_patch(patch);
+        validate_leg(&plan, &patched_leg)?;
+        if let Some(route_id) = &patched_leg.selected_route_id {
+            match self.repos.find_route_option(route_id)? {
+                Some(route) if route.leg_id.as_deref() == Some(leg_id) => {}
+                _ => {
+                    return Err(PlanLegError::InvalidLeg(
+                        "selectedRouteId must be one of the leg's route options".to_string(),
+                    ));
+                }
+            }
+        }
+
+        self.repos.update_plan_leg(&patched_leg)?;
+        Ok(patched_leg)
+    }
+
+    /// Removes a leg with its route options and everything on them.
+    pub fn delete_leg(
+        &self,
+        plan_id: &str,
+        leg_id: &str,
+        user_id: &str,
+    ) -> Result<(), PlanLegError> {
+        let plan = self
+            .travel_plans
+            .authorize(plan_id, user_id, PlanRole::Editor)?
+            .travel_plan;
+        self.leg_in_plan(plan_id, leg_id)?;
+
+        let routes: Vec<RouteOption> = self
+            .repos
+            .find_route_options_by_plan(plan_id)?
+            .into_iter()
+            .filter(|route| route.leg_id.as_deref() == Some(leg_id))
+            .collect();
+        if routes.iter().any(|route| plan.selected_route_id.as_ref() == Some(&route.id)) {
+            return Err(PlanLegError::RouteLocked);
+        }
+
+        for route in &routes {
+            self.repos.delete_route_option(&route.id, None)?;
+        }
+        if !self.repos.delete_plan_leg(leg_id)? {
+            return Err(PlanLegError::LegNotFound);
+        }
+
+        info!(
+            "User {} removed leg {} and its {} route options from travel plan {}",
+            user_id,
+            leg_id,
+            routes.len(),
+            plan_id
+        );
+        Ok(())
+    }
+
+    /// Puts the plan's legs in the given order. `leg_ids` must list every leg
+    /// exactly once.
+    pub fn reorder_legs(
+        &self,
+        plan_id: &str,
+        leg_ids: &[String],
+        user_id: &str,
+    ) -> Result<Vec<PlanLeg>, PlanLegError> {
+        self.travel_plans.authorize(plan_id, user_id, PlanRole::Editor)?;
+        let current_legs = self.repos.find_plan_legs(plan_id)?;
+
+        let current: HashSet<&str> = current_legs.iter().map(|leg| leg.id.as_str()).collect();
+        let requested: HashSet<&str> = leg_ids.iter().map(String::as_str).collect();
+        if requested.len() != leg_ids.len() || requested != current {
+            return Err(PlanLegError::InvalidLeg(format!(
+                "legIds must list each of the plan's {} legs exactly once",
+                current_legs.len()
+            )));
+        }
+
+        let mut legs = Vec::with_capacity(current_legs.len());
+        for (position, leg_id) in leg_ids.iter().enumerate() {
+            let mut leg = current_legs
+                .iter()
+                .find(|leg| leg.id == *leg_id)
+                .cloned()
+                .ok_or(PlanLegError::LegNotFound)?;
+            if leg.position != position as i64 {
+                leg.position = position as i64;
+                leg.updated_at = chrono::Utc::now();
+                self.repos.update_plan_leg(&leg)?;
+            }
+            legs.push(leg);
+        }
+
+        info!("User {} reordered the legs of travel plan {}", user_id, plan_id);
+        Ok(legs)
+    }
+
+    /// Rolls the routes of the plan's legs up into a total distance and
+    /// duration. A plan without legs counts as one leg over its own routes.
+    pub fn summary(&self, plan_id: &str, user_id: &str) -> Result<PlanSummary, PlanLegError> {
+        let plan = self
+            .travel_plans
+            .authorize(plan_id, user_id, PlanRole::Viewer)?
+            .travel_plan;
+        let legs = self.repos.find_plan_legs(plan_id)?;
+        let routes = self.repos.find_route_options_by_plan(plan_id)?;
+        let routes_of = |leg_id: Option<&str>| -> Vec<RouteOption> {
+            routes
+                .iter()
+                .filter(|route| route.leg_id.as_deref() == leg_id)
+                .cloned()
+                .collect()
+        };
+
+        let summaries: Vec<LegSummary> = if legs.is_empty() {
+            let plan_routes = routes_of(None);
+            vec![leg_summary(
+                None,
+                0,
+                &plan.start_location,
+                &plan.end_location,
+                TravelMode::default(),
+                leg_route(&plan_routes, plan.selected_route_id.as_deref()),
+            )]
+        } else {
+            legs.iter()
+                .map(|leg| {
+                    let leg_routes = routes_of(Some(&leg.id));
+                    leg_summary(
+                        Some(leg.id.clone()),
+                        leg.position,
+                        &leg.start_location,
+                        &leg.end_location,
+                        leg.mode,
+                        leg_route(&leg_routes, leg.selected_route_id.as_deref()),
+                    )
+                })
+                .collect()
+        };
+
+        Ok(PlanSummary {
+            travel_plan_id: plan.id,
+            total_distance: summaries.iter().filter_map(|leg| leg.distance).sum(),
+            total_duration: summaries.iter().filter_map(|leg| leg.duration).sum(),
+            complete: summaries.iter().all(|leg| leg.route_selected),
+            legs: summaries,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::travel_plan::NewTravelPlan;
+    use crate::repositories::memory::InMemoryRepository;
+    use crate::services::route_option_service::RouteOptionService;
+
+    fn leg(from: &str, to: &str, mode: TravelMode) -> NewPlanLeg {
+        NewPlanLeg {
+            start_location: from.to_string(),
+            end_location: to.to_string(),
+            start_date: None,
+            end_date: None,
+            mode,
+        }
+    }
+
+    #[test]
+    fn rolls_up_the_routes_of_each_leg() {
+        let repos = Arc::new(InMemoryRepository::new());
+        let plan_id = TravelPlanService::new(repos.clone())
+            .create_travel_plan(
+                &NewTravelPlan {
+                    name: "Grand tour".to_string(),
+                    description: None,
+                    start_location: "Lisbon".to_string(),
+                    end_location: "Rome".to_string(),
+                    start_date: None,
+                    end_date: None,
+                    home_currency: None,
+                },
+                "owner",
+            )
+            .unwrap()
+            .travel_plan
+            .id;
+        let service = PlanLegService::new(repos.clone());
+        let routes = RouteOptionService::new(repos.clone());
+
+        let first = service
+            .add_leg(&plan_id, &leg("Lisbon", "Madrid", TravelMode::Transit), "owner")
+            .unwrap();
+        let second = service
+            .add_leg(&plan_id, &leg("Madrid", "Rome", TravelMode::Flight), "owner")
+            .unwrap();
+        assert_eq!((first.position, second.position), (0, 1));
+        assert!(matches!(
+            service.add_leg(&plan_id, &leg(" ", "Rome", TravelMode::Driving), "owner"),
+            Err(PlanLegError::InvalidLeg(_))
+        ));
+
+        let first_routes = routes
+            .generate_leg_route_options(&plan_id, &first.id, "owner", 2)
+            .unwrap();
+        routes
+            .generate_leg_route_options(&plan_id, &second.id, "owner", 1)
+            .unwrap();
+        assert!(first_routes.iter().all(|r| r.route.leg_id.as_deref() == Some(&*first.id)));
+
+        // The first leg's slower option is selected, the second leg has none
+        let slower = first_routes
+            .iter()
+            .max_by_key(|r| r.route.duration)
+            .unwrap()
+            .route
+            .clone();
+        service
+            .patch_leg(
+                &plan_id,
+                &first.id,
+                &PatchPlanLeg {
+                    selected_route_id: Some(Some(slower.id.clone())),
+                    ..Default::default()
+                },
+                "owner",
+            )
+            .unwrap();
+
+        let summary = service.summary(&plan_id, "owner").unwrap();
+        assert_eq!(summary.legs.len(), 2);
+        assert_eq!(summary.legs[0].route_option_id.as_deref(), Some(slower.id.as_str()));
+        assert!(summary.legs[0].route_selected);
+        assert!(!summary.legs[1].route_selected);
+        assert!(!summary.complete);
+        let expected: i64 = summary.legs.iter().map(|leg| leg.duration.unwrap()).sum();
+        assert_eq!(summary.total_duration, expected);
+
+        // A route selected for a leg stays put
+        assert!(matches!(
+            routes.delete_route_option(&plan_id, &slower.id, "owner", None),
+            Err(crate::services::route_option_service::RouteOptionError::RouteLocked)
+        ));
+
+        let reordered = service
+            .reorder_legs(&plan_id, &[second.id.clone(), first.id.clone()], "owner")
+            .unwrap();
+        assert_eq!(reordered[0].id, second.id);
+
+        service.delete_leg(&plan_id, &second.id, "owner").unwrap();
+        let remaining = routes.get_route_options(&plan_id, "owner").unwrap();
+        assert_eq!(remaining.len(), 2);
+        assert_eq!(service.get_legs(&plan_id, "owner").unwrap().len(), 1);
+    }
+}
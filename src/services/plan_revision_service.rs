@@ -0,0 +1,273 @@
+use log::{error, info};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use utoipa::ToSchema;
+
+use crate::models::plan_event::{self, PlanEventAction};
+use crate::models::plan_member::PlanRole;
+use crate::models::plan_revision::PlanRevision;
+use crate::models::travel_plan::TravelPlan;
+use crate::repositories::{
+    BudgetRepository, CommentRepository, ItineraryRepository, PlanEventRepository,
+    PlanLegRepository, PlanMemberRepository, PlanRevisionRepository, RepositoryError,
+    RouteOptionRepository, RouteVoteRepository, ShareLinkRepository, TravelPlanRepository,
+};
+use crate::services::blocking::BlockingError;
+use crate::services::travel_plan_service::{TravelPlanDto, TravelPlanError, TravelPlanService};
+
+/// Browsing, comparing and restoring the earlier states of a plan. The
+/// revisions themselves are written by `TravelPlanService` on every update.
+pub struct PlanRevisionService<R: ?Sized> {
+    repos: Arc<R>,
+    travel_plans: TravelPlanService<R>,
+}
+
+#[derive(Debug)]
+pub enum PlanRevisionError {
+    TravelPlanError(TravelPlanError),
+    RevisionNotFound,
+    DatabaseError(String),
+}
+
+impl From<TravelPlanError> for PlanRevisionError {
+    fn from(error: TravelPlanError) -> Self {
+        PlanRevisionError::TravelPlanError(error)
+    }
+}
+
+impl From<BlockingError> for PlanRevisionError {
+    fn from(error: BlockingError) -> Self {
+        PlanRevisionError::DatabaseError(error.to_string())
+    }
+}
+
+impl From<RepositoryError> for PlanRevisionError {
+    fn from(error: RepositoryError) -> Self {
+        error!("Plan revision repository error: {}", error);
+        PlanRevisionError::DatabaseError(error.to_string())
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct RevisionHistory {
+    /// The number the plan's present state goes by; one past the newest
+    /// stored revision.
+    pub current_revision: i64,
+    /// Stored revisions, newest first.
+    pub revisions: Vec<PlanRevision>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct RevisionDiff {
+    pub from: i64,
+    pub to: i64,
+    /// Changed fields as `{"field": {"from": old, "to": new}}`.
+    #[schema(value_type = Object)]
+    pub changes: serde_json::Value,
+}
+
+impl<R> PlanRevisionService<R>
+where
+    R: TravelPlanRepository
+        + PlanMemberRepository
+        + ShareLinkRepository
+        + RouteOptionRepository
+        + RouteVoteRepository
+        + CommentRepository
+        + PlanEventRepository
+        + PlanRevisionRepository
+        + ItineraryRepository
+        + PlanLegRepository
+        + BudgetRepository
+        + ?Sized,
+{
+    pub fn new(repos: Arc<R>) -> Self {
+        PlanRevisionService {
+            travel_plans: TravelPlanService::new(repos.clone()),
+            repos,
+        }
+    }
+
+    pub fn list_revisions(
+        &self,
+        plan_id: &str,
+        user_id: &str,
+    ) -> Result<RevisionHistory, PlanRevisionError> {
+        self.travel_plans.authorize(plan_id, user_id, PlanRole::Viewer)?;
+
+        let revisions = self.repos.find_plan_revisions(plan_id)?;
+        Ok(RevisionHistory {
+            current_revision: revisions.first().map_or(1, |r| r.revision + 1),
+            revisions,
+        })
+    }
+
+    pub fn get_revision(
+        &self,
+        plan_id: &str,
+        revision: i64,
+        user_id: &str,
+    ) -> Result<PlanRevision, PlanRevisionError> {
+        self.travel_plans.authorize(plan_id, user_id, PlanRole::Viewer)?;
+
+        self.repos
+            .find_plan_revision(plan_id, revision)?
+            .ok_or(PlanRevisionError::RevisionNotFound)
+    }
+
+    /// The plan as it was at `revision`; the current revision number gives
+    /// the plan as it is now.
+    fn state_at(
+        &self,
+        plan: &TravelPlan,
+        current_revision: i64,
+        revision: i64,
+    ) -> Result<TravelPlan, PlanRevisionError> {
+        if revision == current_revision {
+            return Ok(plan.clone());
+        }
+
+        self.repos
+            .find_plan_revision(&plan.id, revision)?
+            .map(|stored| stored.apply_to(plan))
+            .ok_or(PlanRevisionError::RevisionNotFound)
+    }
+
+    /// Fields that differ between two revisions. `to` defaults to the
+    /// plan's current state.
+    pub fn diff_revisions(
+        &self,
+        plan_id: &str,
+        user_id: &str,
+        from: i64,
+        to: Option<i64>,
+    ) -> Result<RevisionDiff, PlanRevisionError> {
+        let plan = self
+            .travel_plans
+            .authorize(plan_id, user_id, PlanRole::Viewer)?
+            .travel_plan;
+        let current_revision = self
+            .repos
+            .find_plan_revisions(plan_id)?
+            .first()
+            .map_or(1, |r| r.revision + 1);
+        let to = to.unwrap_or(current_revision);
+
+        let before = self.state_at(&plan, current_revision, from)?;
+        let after = self.state_at(&plan, current_revision, to)?;
+
+        Ok(RevisionDiff {
+            from,
+            to,
+            changes: plan_event::diff(
+                &plan_event::plan_snapshot(&before),
+                &plan_event::plan_snapshot(&after),
+            ),
+        })
+    }
+
+    /// Puts an older revision's fields back. The state it replaces becomes a
+    /// revision of its own, so a restore can itself be undone.
+    pub fn restore_revision(
+        &self,
+        plan_id: &str,
+        revision: i64,
+        user_id: &str,
+    ) -> Result<TravelPlanDto, PlanRevisionError> {
+        let plan_dto = self.travel_plans.authorize(plan_id, user_id, PlanRole::Editor)?;
+        let stored = self
+            .repos
+            .find_plan_revision(plan_id, revision)?
+            .ok_or(PlanRevisionError::RevisionNotFound)?;
+
+        let restored = stored.apply_to(&plan_dto.travel_plan);
+        let plan_dto = self.travel_plans.save_travel_plan(
+            plan_dto,
+            restored,
+            user_id,
+            PlanEventAction::PlanRestored,
+        )?;
+
+        info!(
+            "User {} restored travel plan {} to revision {}",
+            user_id, plan_id, revision
+        );
+        Ok(plan_dto)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::travel_plan::{NewTravelPlan, UpdateTravelPlan};
+    use crate::repositories::memory::InMemoryRepository;
+    use serde_json::json;
+
+    fn rename(name: &str) -> UpdateTravelPlan {
+        UpdateTravelPlan {
+            name: Some(name.to_string()),
+            description: None,
+            start_location: None,
+            end_location: None,
+            start_date: None,
+            end_date: None,
+            home_currency: None,
+        }
+    }
+
+    #[test]
+    fn snapshots_updates_and_restores_as_new_revision() {
+        let repos = Arc::new(InMemoryRepository::new());
+        let plans = TravelPlanService::new(repos.clone());
+        let plan_id = plans
+            .create_travel_plan(
+                &NewTravelPlan {
+                    name: "Road trip".to_string(),
+                    description: Some("Two weeks".to_string()),
+                    start_location: "Amsterdam".to_string(),
+                    end_location: "Berlin".to_string(),
+                    start_date: None,
+                    end_date: None,
+                    home_currency: None,
+                },
+                "alice",
+            )
+            .unwrap()
+            .travel_plan
+            .id;
+        plans.update_travel_plan(&plan_id, &rename("Rhine trip"), "alice", None).unwrap();
+        plans.update_travel_plan(&plan_id, &rename("Danube trip"), "alice", None).unwrap();
+        // Saving the same values again is not a new revision
+        plans.update_travel_plan(&plan_id, &rename("Danube trip"), "alice", None).unwrap();
+
+        let revisions = PlanRevisionService::new(repos);
+        let history = revisions.list_revisions(&plan_id, "alice").unwrap();
+        assert_eq!(history.current_revision, 3);
+        let names: Vec<&str> = history.revisions.iter().map(|r| r.name.as_str()).collect();
+        assert_eq!(names, ["Rhine trip", "Road trip"]);
+
+        let diff = revisions.diff_revisions(&plan_id, "alice", 1, None).unwrap();
+        assert_eq!(diff.to, 3);
+        assert_eq!(
+            diff.changes,
+            json!({ "name": { "from": "Road trip", "to": "Danube trip" } })
+        );
+
+        let restored = revisions.restore_revision(&plan_id, 1, "alice").unwrap();
+        assert_eq!(restored.travel_plan.name, "Road trip");
+        let history = revisions.list_revisions(&plan_id, "alice").unwrap();
+        assert_eq!(history.current_revision, 4);
+        assert_eq!(history.revisions[0].name, "Danube trip");
+
+        assert!(matches!(
+            revisions.restore_revision(&plan_id, 4, "alice"),
+            Err(PlanRevisionError::RevisionNotFound)
+        ));
+        assert!(matches!(
+            revisions.get_revision(&plan_id, 1, "mallory"),
+            Err(PlanRevisionError::TravelPlanError(TravelPlanError::Unauthorized))
+        ));
+    }
+}
@@ -0,0 +1,430 @@
+use chrono::Utc;
+use log::{error, info};
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use std::sync::Arc;
+use utoipa::ToSchema;
+
+use crate::models::plan_event::{self, PlanEventAction};
+use crate::models::plan_member::{MemberStatus, PlanMember, PlanRole};
+use crate::models::user::User;
+use crate::repositories::{
+    BudgetRepository, CommentRepository, ItineraryRepository, PlanEventRepository,
+    PlanLegRepository, PlanMemberRepository, PlanRevisionRepository, RepositoryError,
+    RouteOptionRepository, RouteVoteRepository, ShareLinkRepository, TravelPlanRepository,
+    UserRepository,
+};
+use crate::services::blocking::BlockingError;
+use crate::services::travel_plan_service::{TravelPlanError, TravelPlanService};
+
+/// Invitations, acceptance and revocation of access to shared plans.
+pub struct PlanSharingService<R: ?Sized> {
+    repos: Arc<R>,
+    travel_plans: TravelPlanService<R>,
+}
+
+#[derive(Debug)]
+pub enum SharingError {
+    TravelPlanError(TravelPlanError),
+    UserNotFound,
+    AlreadyMember,
+    InvitationNotFound,
+    MemberNotFound,
+    InvalidInput(String),
+    DatabaseError(String),
+}
+
+impl From<TravelPlanError> for SharingError {
+    fn from(error: TravelPlanError) -> Self {
+        SharingError::TravelPlanError(error)
+    }
+}
+
+impl From<BlockingError> for SharingError {
+    fn from(error: BlockingError) -> Self {
+        SharingError::DatabaseError(error.to_string())
+    }
+}
+
+impl From<RepositoryError> for SharingError {
+    fn from(error: RepositoryError) -> Self {
+        error!("Plan sharing repository error: {}", error);
+        SharingError::DatabaseError(error.to_string())
+    }
+}
+
+/// Who to invite, by exactly one of username or email.
+#[derive(Debug, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct InviteMember {
+    pub username: Option<String>,
+    pub email: Option<String>,
+    pub role: PlanRole,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct PlanMemberDto {
+    #[serde(flatten)]
+    pub member: PlanMember,
+    pub username: String,
+}
+
+/// A pending invitation as shown to the invited user.
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct InvitationDto {
+    #[serde(flatten)]
+    pub member: PlanMember,
+    pub plan_name: String,
+}
+
+/// How a membership shows up in the plan's activity feed.
+fn member_snapshot(member: &PlanMember, username: &str) -> Value {
+    json!({ "member": { "username": username, "role": member.role } })
+}
+
+impl<R> PlanSharingService<R>
+where
+    R: UserRepository
+        + TravelPlanRepository
+        + PlanMemberRepository
+        + ShareLinkRepository
+        + RouteOptionRepository
+        + RouteVoteRepository
+        + CommentRepository
+        + PlanEventRepository
+        + PlanRevisionRepository
+        + ItineraryRepository
+        + PlanLegRepository
+        + BudgetRepository
+        + ?Sized,
+{
+    pub fn new(repos: Arc<R>) -> Self {
+        PlanSharingService {
+            travel_plans: TravelPlanService::new(repos.clone()),
+            repos,
+        }
+    }
+
+    fn with_username(&self, member: PlanMember) -> Result<PlanMemberDto, SharingError> {
+        let username = self
+            .repos
+            .find_user_by_id(&member.user_id)?
+            .map(|user| user.username)
+            .unwrap_or_default();
+        Ok(PlanMemberDto { member, username })
+    }
+
+    fn find_invitee(&self, invite: &InviteMember) -> Result<User, SharingError> {
+        let found = match (&invite.username, &invite.email) {
+            (Some(username), None) => self.repos.find_user_by_username(username)?,
+            (None, Some(email)) => self.repos.find_user_by_email(email)?,
+            _ => {
+                return Err(SharingError::InvalidInput(
+                    "give exactly one of username or email".to_string(),
+                ));
+            }
+        };
+        found.ok_or(SharingError::UserNotFound)
+    }
+
+    /// Everyone the plan is shared with, including pending invitations.
+    pub fn list_members(
+        &self,
+        plan_id: &str,
+        user_id: &str,
+    ) -> Result<Vec<PlanMemberDto>, SharingError> {
+        self.travel_plans.authorize(plan_id, user_id, PlanRole::Viewer)?;
+
+        self.repos
+            .find_plan_members(plan_id)?
+            .into_iter()
+            .map(|member| self.with_username(member))
+            .collect()
+    }
+
+    pub fn invite(
+        &self,
+        plan_id: &str,
+        user_id: &str,
+        invite: &InviteMember,
+    ) -> Result<PlanMemberDto, SharingError> {
+        let plan = self
+            .travel_plans
+            .authorize(plan_id, user_id, PlanRole::Owner)?
+            .travel_plan;
+        let invitee = self.find_invitee(invite)?;
+
+        if invitee.id == plan.user_id || invitee.id == user_id {
+            return Err(SharingError::InvalidInput(
+                "the plan's owner cannot be invited".to_string(),
+            ));
+        }
+        if self.repos.find_plan_member(plan_id, &invitee.id)?.is_some() {
+            return Err(SharingError::AlreadyMember);
+        }
+
+        let member = PlanMember::invite(plan_id, &invitee.id, invite.role, user_id);
+        self.repos.insert_plan_member(&member)?;
+
+        info!(
+            "User {} invited {} to travel plan {} as {}",
+            user_id, invitee.username, plan_id, invite.role
+        );
+        self.travel_plans.record_event(
+            plan_id,
+            user_id,
+            PlanEventAction::MemberInvited,
+            plan_event::diff(&Value::Null, &member_snapshot(&member, &invitee.username)),
+        )?;
+        Ok(PlanMemberDto {
+            member,
+            username: invitee.username,
+        })
+    }
+
+    /// Accepts the caller's invitation. Accepting twice is harmless.
+    pub fn accept(&self, plan_id: &str, user_id: &str) -> Result<PlanMemberDto, SharingError> {
+        let mut member = self
+            .repos
+            .find_plan_member(plan_id, user_id)?
+            .ok_or(SharingError::InvitationNotFound)?;
+
+        if member.status == MemberStatus::Pending {
+            member.status = MemberStatus::Accepted;
+            member.responded_at = Some(Utc::now());
+            self.repos.update_plan_member(&member)?;
+            info!("User {} accepted access to travel plan {}", user_id, plan_id);
+            self.travel_plans.record_event(
+                plan_id,
+                user_id,
+                PlanEventAction::MemberJoined,
+                json!({ "status": { "from": MemberStatus::Pending, "to": MemberStatus::Accepted } }),
+            )?;
+        }
+
+        self.with_username(member)
+    }
+
+    /// Owners can revoke anyone's access; members can remove themselves,
+    /// which is also how an invitation is declined.
+    pub fn remove_member(
+        &self,
+        plan_id: &str,
+        user_id: &str,
+        member_user_id: &str,
+    ) -> Result<(), SharingError> {
+        if member_user_id != user_id {
+            self.travel_plans.authorize(plan_id, user_id, PlanRole::Owner)?;
+        } else if self.repos.find_travel_plan(plan_id)?.is_none() {
+            return Err(TravelPlanError::NotFound.into());
+        }
+
+        let member = self
+            .repos
+            .find_plan_member(plan_id, member_user_id)?
+            .ok_or(SharingError::MemberNotFound)?;
+        if !self.repos.delete_plan_member(plan_id, member_user_id)? {
+            return Err(SharingError::MemberNotFound);
+        }
+
+        info!(
+            "User {} removed {} from travel plan {}",
+            user_id, member_user_id, plan_id
+        );
+        let removed = self.with_username(member)?;
+        self.travel_plans.record_event(
+            plan_id,
+            user_id,
+            PlanEventAction::MemberRemoved,
+            plan_event::diff(
+                &member_snapshot(&removed.member, &removed.username),
+                &Value::Null,
+            ),
+        )?;
+        Ok(())
+    }
+
+    /// Invitations waiting for the caller's answer, newest first.
+    pub fn pending_invitations(&self, user_id: &str) -> Result<Vec<InvitationDto>, SharingError> {
+        let mut invitations = Vec::new();
+
+        for member in self.repos.find_memberships_by_user(user_id)? {
+            if member.status != MemberStatus::Pending {
+                continue;
+            }
+            if let Some(plan) = self.repos.find_travel_plan(&member.travel_plan_id)? {
+                invitations.push(InvitationDto {
+                    member,
+                    plan_name: plan.name,
+                });
+            }
+        }
+
+        Ok(invitations)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::travel_plan::UpdateTravelPlan;
+    use crate::repositories::memory::InMemoryRepository;
+    use crate::services::test_fixtures::{create_plan, road_trip, user};
+
+    struct Fixture {
+        repos: Arc<InMemoryRepository>,
+        sharing: PlanSharingService<InMemoryRepository>,
+        plans: TravelPlanService<InMemoryRepository>,
+        owner: User,
+        friend: User,
+        plan_id: String,
+    }
+
+    fn setup() -> Fixture {
+        let repos = Arc::new(InMemoryRepository::new());
+        let owner = user(&repos, "owner");
+        let friend = user(&repos, "friend");
+        let plans = TravelPlanService::new(repos.clone());
+        let plan_id = create_plan(&repos, &road_trip(), &owner.id);
+
+        Fixture {
+            sharing: PlanSharingService::new(repos.clone()),
+            repos,
+            plans,
+            owner,
+            friend,
+            plan_id,
+        }
+    }
+
+    fn invite(username: &str, role: PlanRole) -> InviteMember {
+        InviteMember {
+            username: Some(username.to_string()),
+            email: None,
+            role,
+        }
+    }
+
+    fn rename() -> UpdateTravelPlan {
+        UpdateTravelPlan {
+            name: Some("Renamed".to_string()),
+            description: None,
+            start_location: None,
+            end_location: None,
+            start_date: None,
+            end_date: None,
+            home_currency: None,
+        }
+    }
+
+    #[test]
+    fn invitations_grant_access_once_accepted() {
+        let f = setup();
+
+        f.sharing
+            .invite(&f.plan_id, &f.owner.id, &invite("friend", PlanRole::Viewer))
+            .unwrap();
+
+        // Pending invitations grant nothing yet
+        assert!(matches!(
+            f.plans.get_travel_plan_by_id(&f.plan_id, &f.friend.id),
+            Err(TravelPlanError::Unauthorized)
+        ));
+        assert_eq!(f.sharing.pending_invitations(&f.friend.id).unwrap().len(), 1);
+
+        f.sharing.accept(&f.plan_id, &f.friend.id).unwrap();
+
+        let viewed = f.plans.get_travel_plan_by_id(&f.plan_id, &f.friend.id).unwrap();
+        assert_eq!(viewed.role, PlanRole::Viewer);
+        assert!(f.sharing.pending_invitations(&f.friend.id).unwrap().is_empty());
+
+        let listed = f.plans.get_travel_plans(&f.friend.id).unwrap();
+        assert_eq!(listed.len(), 1);
+        assert_eq!(listed[0].travel_plan.id, f.plan_id);
+
+        // Viewers cannot edit
+        assert!(matches!(
+            f.plans.update_travel_plan(&f.plan_id, &rename(), &f.friend.id, None),
+            Err(TravelPlanError::Unauthorized)
+        ));
+    }
+
+    #[test]
+    fn roles_limit_what_members_can_do() {
+        let f = setup();
+        let editor = user(&f.repos, "editor");
+        f.sharing
+            .invite(&f.plan_id, &f.owner.id, &invite("editor", PlanRole::Editor))
+            .unwrap();
+        f.sharing.accept(&f.plan_id, &editor.id).unwrap();
+
+        f.plans.update_travel_plan(&f.plan_id, &rename(), &editor.id, None).unwrap();
+
+        // Only owners may share or delete
+        assert!(matches!(
+            f.sharing.invite(&f.plan_id, &editor.id, &invite("friend", PlanRole::Viewer)),
+            Err(SharingError::TravelPlanError(TravelPlanError::Unauthorized))
+        ));
+        assert!(matches!(
+            f.plans.delete_travel_plan(&f.plan_id, &editor.id, None),
+            Err(TravelPlanError::Unauthorized)
+        ));
+    }
+
+    #[test]
+    fn invites_by_email_and_rejects_bad_invitations() {
+        let f = setup();
+
+        let by_email = InviteMember {
+            username: None,
+            email: Some("friend@example.com".to_string()),
+            role: PlanRole::Editor,
+        };
+        let member = f.sharing.invite(&f.plan_id, &f.owner.id, &by_email).unwrap();
+        assert_eq!(member.username, "friend");
+
+        assert!(matches!(
+            f.sharing.invite(&f.plan_id, &f.owner.id, &by_email),
+            Err(SharingError::AlreadyMember)
+        ));
+        assert!(matches!(
+            f.sharing.invite(&f.plan_id, &f.owner.id, &invite("nobody", PlanRole::Viewer)),
+            Err(SharingError::UserNotFound)
+        ));
+        assert!(matches!(
+            f.sharing.invite(&f.plan_id, &f.owner.id, &invite("owner", PlanRole::Viewer)),
+            Err(SharingError::InvalidInput(_))
+        ));
+    }
+
+    #[test]
+    fn owners_revoke_and_members_leave() {
+        let f = setup();
+        f.sharing
+            .invite(&f.plan_id, &f.owner.id, &invite("friend", PlanRole::Viewer))
+            .unwrap();
+        f.sharing.accept(&f.plan_id, &f.friend.id).unwrap();
+
+        f.sharing
+            .remove_member(&f.plan_id, &f.owner.id, &f.friend.id)
+            .unwrap();
+        assert!(matches!(
+            f.plans.get_travel_plan_by_id(&f.plan_id, &f.friend.id),
+            Err(TravelPlanError::Unauthorized)
+        ));
+
+        // Declining a new invitation removes it
+        f.sharing
+            .invite(&f.plan_id, &f.owner.id, &invite("friend", PlanRole::Viewer))
+            .unwrap();
+        f.sharing
+            .remove_member(&f.plan_id, &f.friend.id, &f.friend.id)
+            .unwrap();
+        assert!(matches!(
+            f.sharing.accept(&f.plan_id, &f.friend.id),
+            Err(SharingError::InvitationNotFound)
+        ));
+    }
+}
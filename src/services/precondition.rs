@@ -0,0 +1,60 @@
+//! Entity tags for optimistic concurrency, and evaluation of the
+//! `If-Match` / `If-None-Match` request headers against them.
+
+use chrono::{DateTime, Utc};
+
+/// A strong entity tag for a resource last changed at `changed_at`.
+/// `variant` adds whatever else shapes the representation a caller sees.
+/// Microseconds, because that is all PostgreSQL keeps.
+pub fn etag(changed_at: DateTime<Utc>, variant: &str) -> String {
+    if variant.is_empty() {
+        format!("\"{:x}\"", changed_at.timestamp_micros())
+    } else {
+        format!("\"{:x}-{}\"", changed_at.timestamp_micros(), variant)
+    }
+}
+
+fn tags(header: &str) -> impl Iterator<Item = &str> {
+    header.split(',').map(str::trim).filter(|tag| !tag.is_empty())
+}
+
+/// Whether an `If-Match` header lets a write go ahead. Uses the strong
+/// comparison, so weak tags never match; `*` matches any current state.
+pub fn if_match(header: &str, etag: &str) -> bool {
+    tags(header).any(|tag| tag == "*" || (!tag.starts_with("W/") && tag == etag))
+}
+
+/// Whether an `If-Match` header asks for the state that was read rather
+/// than any state (`*`), so a write must still find that state as it lands.
+pub fn pins_state(header: &str) -> bool {
+    tags(header).all(|tag| tag != "*")
+}
+
+/// Whether an `If-None-Match` header names the current state, in which case
+/// a read can answer 304. Uses the weak comparison.
+pub fn if_none_match(header: &str, etag: &str) -> bool {
+    let etag = etag.trim_start_matches("W/");
+    tags(header).any(|tag| tag == "*" || tag.trim_start_matches("W/") == etag)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn compares_entity_tags() {
+        let tag = etag(DateTime::from_timestamp(1_700_000_000, 0).unwrap(), "owner");
+        assert_eq!(tag, "\"60a24181e4000-owner\"");
+
+        assert!(if_match(&tag, &tag));
+        assert!(if_match(&format!("\"stale\", {}", tag), &tag));
+        assert!(if_match("*", &tag));
+        assert!(!if_match("\"stale\"", &tag));
+        assert!(!if_match(&format!("W/{}", tag), &tag));
+        assert!(pins_state(&tag));
+        assert!(!pins_state("*"));
+
+        assert!(if_none_match(&format!("W/{}", tag), &tag));
+        assert!(!if_none_match("\"stale\"", &tag));
+    }
+}
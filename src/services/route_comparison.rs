@@ -0,0 +1,261 @@
+//! Side-by-side comparison of route options: their figures, how much of
+//! them runs along the same roads, and which is best at what.
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use utoipa::ToSchema;
+
+use crate::models::travel_mode::TravelMode;
+use crate::services::geo::{self, Coordinates};
+use crate::services::route_cost::RouteCost;
+use crate::services::route_option_service::RouteOptionWithPois;
+
+/// Routes compared at once, at least and at most.
+pub const MIN_COMPARED_ROUTES: usize = 2;
+pub const MAX_COMPARED_ROUTES: usize = 10;
+
+/// How close two routes must run to count as sharing the road.
+pub const OVERLAP_TOLERANCE_KM: f64 = 1.0;
+
+/// The category points of interest without one are counted under.
+pub const UNCATEGORISED: &str = "uncategorised";
+
+/// What a route can be best at. Less is better, except for points of
+/// interest.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ComparisonMetric {
+    Distance,
+    Duration,
+    Cost,
+    Co2e,
+    PointsOfInterest,
+}
+
+/// One row of the comparison.
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ComparedRoute {
+    pub route_id: String,
+    pub name: String,
+    pub mode: TravelMode,
+    pub distance: Option<f64>,
+    pub duration: Option<i64>,
+    pub estimated_cost: Option<RouteCost>,
+    pub co2e_kg: Option<f64>,
+    pub points_of_interest: usize,
+    /// Points of interest by category, with every category of the
+    /// comparison present.
+    pub poi_categories: BTreeMap<String, usize>,
+    /// The metrics no other route beats it at. Ties mark every route that
+    /// shares the best value, unless all routes share it.
+    pub best_for: Vec<ComparisonMetric>,
+}
+
+/// How much two routes run along each other.
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct RouteOverlap {
+    pub route_ids: Vec<String>,
+    /// Kilometres within a kilometre of each other.
+    pub shared_km: f64,
+    /// The shared kilometres as a fraction of the shorter route, 0 to 1.
+    pub share: f64,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct RouteComparison {
+    /// Every category of point of interest on the compared routes.
+    pub categories: Vec<String>,
+    /// In the order they were asked for.
+    pub routes: Vec<ComparedRoute>,
+    /// One per pair of routes.
+    pub overlaps: Vec<RouteOverlap>,
+}
+
+fn metric_value(route: &RouteOptionWithPois, metric: ComparisonMetric) -> Option<f64> {
+    match metric {
+        ComparisonMetric::Distance => route.route.distance,
+        ComparisonMetric::Duration => route.route.duration.map(|minutes| minutes as f64),
+        ComparisonMetric::Cost => route.estimated_cost.as_ref().map(|cost| cost.total as f64),
+        ComparisonMetric::Co2e => route.co2e_kg,
+        // Negated so that more is better like everything else's less
+        ComparisonMetric::PointsOfInterest => Some(-(route.points_of_interest.len() as f64)),
+    }
+}
+
+/// Indexes of the routes with the best value of `metric`; none when they all
+/// tie or none has a value.
+fn best_routes(routes: &[RouteOptionWithPois], metric: ComparisonMetric) -> Vec<usize> {
+    let values: Vec<Option<f64>> =
+        routes.iter().map(|route| metric_value(route, metric)).collect();
+    let Some(best) = values.iter().flatten().copied().reduce(f64::min) else {
+        return Vec::new();
+    };
+    let winners: Vec<usize> = (0..routes.len()).filter(|&i| values[i] == Some(best)).collect();
+    if winners.len() == routes.len() {
+        return Vec::new();
+    }
+    winners
+}
+
+fn round_to(value: f64, places: i32) -> f64 {
+    let scale = 10f64.powi(places);
+    (value * scale).round() / scale
+}
+
+fn overlap(
+    route_a: &RouteOptionWithPois,
+    path_a: &[Coordinates],
+    route_b: &RouteOptionWithPois,
+    path_b: &[Coordinates],
+) -> RouteOverlap {
+    let shared_km = (geo::overlap_km(path_a, path_b, OVERLAP_TOLERANCE_KM)
+        + geo::overlap_km(path_b, path_a, OVERLAP_TOLERANCE_KM))
+        / 2.0;
+    let shorter = geo::path_length_km(path_a).min(geo::path_length_km(path_b));
+    let share = if shorter > 0.0 { (shared_km / shorter).min(1.0) } else { 0.0 };
+    RouteOverlap {
+        route_ids: vec![route_a.route.id.clone(), route_b.route.id.clone()],
+        shared_km: round_to(shared_km, 1),
+        share: round_to(share, 2),
+    }
+}
+
+/// Lays `routes` side by side.
+pub fn compare(routes: Vec<RouteOptionWithPois>) -> RouteComparison {
+    let mut categories: Vec<String> = routes
+        .iter()
+        .flat_map(|route| &route.points_of_interest)
+        .map(|poi| poi.category.clone().unwrap_or_else(|| UNCATEGORISED.to_string()))
+        .collect();
+    categories.sort();
+    categories.dedup();
+
+    let mut best_for = vec![Vec::new(); routes.len()];
+    let metrics = [
+        ComparisonMetric::Distance,
+        ComparisonMetric::Duration,
+        ComparisonMetric::Cost,
+        ComparisonMetric::Co2e,
+        ComparisonMetric::PointsOfInterest,
+    ];
+    for metric in metrics {
+        for index in best_routes(&routes, metric) {
+            best_for[index].push(metric);
+        }
+    }
+
+    let paths: Vec<Vec<Coordinates>> = routes.iter().map(|r| geo::route_path(&r.route)).collect();
+    let mut overlaps = Vec::new();
+    for i in 0..routes.len() {
+        for j in i + 1..routes.len() {
+            overlaps.push(overlap(&routes[i], &paths[i], &routes[j], &paths[j]));
+        }
+    }
+
+    let compared = routes
+        .into_iter()
+        .zip(best_for)
+        .map(|(route, best_for)| {
+            let mut poi_categories: BTreeMap<String, usize> =
+                categories.iter().map(|category| (category.clone(), 0)).collect();
+            for poi in &route.points_of_interest {
+                let category = poi.category.as_deref().unwrap_or(UNCATEGORISED);
+                *poi_categories.entry(category.to_string()).or_default() += 1;
+            }
+            ComparedRoute {
+                route_id: route.route.id,
+                name: route.route.name,
+                mode: route.mode,
+                distance: route.route.distance,
+                duration: route.route.duration,
+                estimated_cost: route.estimated_cost,
+                co2e_kg: route.co2e_kg,
+                points_of_interest: route.points_of_interest.len(),
+                poi_categories,
+                best_for,
+            }
+        })
+        .collect();
+
+    RouteComparison {
+        categories,
+        routes: compared,
+        overlaps,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::point_of_interest::PointOfInterest;
+    use crate::models::route_option::RouteOption;
+    use chrono::Utc;
+
+    fn route(id: &str, distance: f64, duration: i64, waypoints: &str) -> RouteOptionWithPois {
+        RouteOptionWithPois {
+            route: RouteOption {
+                id: id.to_string(),
+                travel_plan_id: "plan".to_string(),
+                leg_id: None,
+                name: format!("Route {}", id),
+                description: None,
+                distance: Some(distance),
+                duration: Some(duration),
+                start_coordinates: "0,0".to_string(),
+                end_coordinates: "0,2".to_string(),
+                waypoints: Some(waypoints.to_string()),
+                created_at: Utc::now(),
+                updated_at: Utc::now(),
+            },
+            mode: TravelMode::Driving,
+            estimated_cost: None,
+            co2e_kg: Some(distance / 10.0),
+            points_of_interest: Vec::new(),
+        }
+    }
+
+    fn poi(route_id: &str, category: Option<&str>) -> PointOfInterest {
+        PointOfInterest {
+            id: format!("{}-{:?}", route_id, category),
+            route_option_id: route_id.to_string(),
+            name: "Somewhere".to_string(),
+            description: None,
+            category: category.map(str::to_string),
+            coordinates: "0,1".to_string(),
+            created_at: Utc::now(),
+        }
+    }
+
+    #[test]
+    fn marks_the_best_route_per_metric_and_measures_overlap() {
+        let mut straight = route("a", 222.0, 180, "0,1");
+        let mut detour = route("b", 260.0, 170, "1,1");
+        let same_road = route("c", 222.0, 200, "0,1");
+        straight.points_of_interest = vec![poi("a", Some("museum"))];
+        detour.points_of_interest = vec![poi("b", Some("museum")), poi("b", None)];
+
+        let comparison = compare(vec![straight, detour, same_road]);
+
+        assert_eq!(comparison.categories, ["museum", UNCATEGORISED]);
+        let [a, b, c] = &comparison.routes[..] else {
+            panic!("expected three routes");
+        };
+        assert_eq!(a.best_for, [ComparisonMetric::Distance, ComparisonMetric::Co2e]);
+        assert_eq!(
+            b.best_for,
+            [ComparisonMetric::Duration, ComparisonMetric::PointsOfInterest]
+        );
+        assert_eq!(c.best_for, [ComparisonMetric::Distance, ComparisonMetric::Co2e]);
+        assert_eq!(a.poi_categories[UNCATEGORISED], 0);
+        assert_eq!(b.poi_categories[UNCATEGORISED], 1);
+
+        // a-b, a-c and b-c; the detour only shares the roads near each end
+        assert_eq!(comparison.overlaps.len(), 3);
+        assert_eq!(comparison.overlaps[1].route_ids, ["a", "c"]);
+        assert_eq!(comparison.overlaps[1].share, 1.0);
+        assert!(comparison.overlaps[0].share < 0.05, "{:?}", comparison.overlaps[0]);
+    }
+}
@@ -0,0 +1,285 @@
+//! What a route costs to travel: fuel and tolls by car, fares on public
+//! transport and flights. Amounts are in minor units of the model's currency.
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::path::Path;
+use utoipa::ToSchema;
+
+use crate::models::currency::CurrencyCode;
+use crate::models::route_option::RouteOption;
+use crate::models::travel_mode::TravelMode;
+use crate::services::geo::{self, Coordinates};
+
+/// How close a route must pass to both ends of a toll segment to pay it,
+/// unless the segment says otherwise.
+pub const DEFAULT_TOLL_RADIUS_KM: f64 = 5.0;
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct FuelModel {
+    pub litres_per_100_km: f64,
+    /// Minor units per litre.
+    pub price_per_litre: i64,
+}
+
+impl Default for FuelModel {
+    fn default() -> Self {
+        FuelModel {
+            litres_per_100_km: 6.5,
+            price_per_litre: 185,
+        }
+    }
+}
+
+/// A stretch of tolled road. Routes passing both ends, in order, pay it.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TollSegment {
+    pub name: String,
+    /// `"lat,lng"` of the entry.
+    pub from: String,
+    /// `"lat,lng"` of the exit.
+    pub to: String,
+    pub price: i64,
+    #[serde(default = "default_toll_radius")]
+    pub radius_km: f64,
+}
+
+fn default_toll_radius() -> f64 {
+    DEFAULT_TOLL_RADIUS_KM
+}
+
+impl TollSegment {
+    fn is_used_by(&self, path: &[Coordinates]) -> bool {
+        let (Some(from), Some(to)) = (Coordinates::parse(&self.from), Coordinates::parse(&self.to))
+        else {
+            return false;
+        };
+        geo::distance_from_path_km(path, &from) <= self.radius_km
+            && geo::distance_from_path_km(path, &to) <= self.radius_km
+            && geo::fraction_along(path, &from) < geo::fraction_along(path, &to)
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FareBand {
+    pub up_to_km: f64,
+    pub fare: i64,
+}
+
+/// Fares by distance: the first band that covers the trip, and past the
+/// last band its fare plus a rate per kilometre.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct FareTable {
+    pub bands: Vec<FareBand>,
+    pub per_km_beyond: i64,
+}
+
+impl FareTable {
+    pub fn fare(&self, distance_km: f64) -> i64 {
+        if let Some(band) = self.bands.iter().find(|band| distance_km <= band.up_to_km) {
+            return band.fare;
+        }
+        let (fare, covered_km) = self.bands.last().map_or((0, 0.0), |b| (b.fare, b.up_to_km));
+        fare + (self.per_km_beyond as f64 * (distance_km - covered_km)).round() as i64
+    }
+}
+
+/// Prices for every way of travelling. Fuel and tolls are per car; fares are
+/// per traveller. Cycling and walking are free.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct CostModel {
+    pub currency: CurrencyCode,
+    pub fuel: FuelModel,
+    pub tolls: Vec<TollSegment>,
+    pub transit_fares: FareTable,
+    pub flight_fares: FareTable,
+}
+
+impl Default for CostModel {
+    fn default() -> Self {
+        let band = |up_to_km, fare| FareBand { up_to_km, fare };
+        CostModel {
+            currency: CurrencyCode::parse("EUR").unwrap(),
+            fuel: FuelModel::default(),
+            tolls: Vec::new(),
+            transit_fares: FareTable {
+                bands: vec![band(10.0, 300), band(50.0, 900), band(150.0, 2500), band(400.0, 5500)],
+                per_km_beyond: 12,
+            },
+            flight_fares: FareTable {
+                bands: vec![band(800.0, 9000), band(2500.0, 18000)],
+                per_km_beyond: 8,
+            },
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct InvalidCostModel(pub String);
+
+impl fmt::Display for InvalidCostModel {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Invalid route cost model: {}", self.0)
+    }
+}
+
+impl std::error::Error for InvalidCostModel {}
+
+/// A route's estimated cost, by what it is spent on.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct RouteCost {
+    pub currency: String,
+    /// Everything below together, in minor units.
+    pub total: i64,
+    pub fuel: i64,
+    pub tolls: i64,
+    pub fares: i64,
+    /// The toll segments the route pays, by name.
+    pub toll_segments: Vec<String>,
+}
+
+impl CostModel {
+    /// Reads the JSON file named by `ROUTE_COST_FILE`, or the built-in prices
+    /// when it isn't set. Fields the file leaves out keep their defaults.
+    pub fn from_env() -> Result<Self, InvalidCostModel> {
+        match std::env::var("ROUTE_COST_FILE") {
+            Ok(path) if !path.is_empty() => CostModel::load(Path::new(&path)),
+            _ => Ok(CostModel::default()),
+        }
+    }
+
+    pub fn load(path: &Path) -> Result<Self, InvalidCostModel> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| InvalidCostModel(format!("{}: {}", path.display(), e)))?;
+        CostModel::parse(&text)
+    }
+
+    pub fn parse(text: &str) -> Result<Self, InvalidCostModel> {
+        let mut model: CostModel =
+            serde_json::from_str(text).map_err(|e| InvalidCostModel(e.to_string()))?;
+        for toll in &model.tolls {
+            if Coordinates::parse(&toll.from).is_none() || Coordinates::parse(&toll.to).is_none() {
+                return Err(InvalidCostModel(format!(
+                    "toll segment {} needs \"lat,lng\" ends",
+                    toll.name
+                )));
+            }
+        }
+        for table in [&mut model.transit_fares, &mut model.flight_fares] {
+            table.bands.sort_by(|a, b| a.up_to_km.total_cmp(&b.up_to_km));
+        }
+        Ok(model)
+    }
+
+    /// What travelling `route` by `mode` costs; `None` when the route's
+    /// distance isn't known.
+    pub fn estimate(&self, route: &RouteOption, mode: TravelMode) -> Option<RouteCost> {
+        let distance = route.distance?;
+        let mut cost = RouteCost {
+            currency: self.currency.to_string(),
+            total: 0,
+            fuel: 0,
+            tolls: 0,
+            fares: 0,
+            toll_segments: Vec::new(),
+        };
+
+        match mode {
+            TravelMode::Driving => {
+                let litres = distance * self.fuel.litres_per_100_km / 100.0;
+                cost.fuel = (litres * self.fuel.price_per_litre as f64).round() as i64;
+                let path = geo::route_path(route);
+                for toll in self.tolls.iter().filter(|toll| toll.is_used_by(&path)) {
+                    cost.tolls += toll.price;
+                    cost.toll_segments.push(toll.name.clone());
+                }
+            }
+            TravelMode::Transit => cost.fares = self.transit_fares.fare(distance),
+            TravelMode::Flight => cost.fares = self.flight_fares.fare(distance),
+            TravelMode::Cycling | TravelMode::Walking => {}
+        }
+
+        cost.total = cost.fuel + cost.tolls + cost.fares;
+        Some(cost)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+
+    fn route(distance: f64, waypoints: &str) -> RouteOption {
+        RouteOption {
+            id: "route".to_string(),
+            travel_plan_id: "plan".to_string(),
+            leg_id: None,
+            name: "Route".to_string(),
+            description: None,
+            distance: Some(distance),
+            duration: None,
+            start_coordinates: "0,0".to_string(),
+            end_coordinates: "0,2".to_string(),
+            waypoints: Some(waypoints.to_string()),
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+        }
+    }
+
+    #[test]
+    fn drivers_pay_fuel_and_the_tolls_they_pass() {
+        let model = CostModel::parse(
+            r#"{
+                "fuel": { "litresPer100Km": 5.0, "pricePerLitre": 200 },
+                "tolls": [
+                    { "name": "Bridge", "from": "0,0.5", "to": "0,0.6", "price": 700 },
+                    { "name": "Tunnel", "from": "1,1", "to": "1,1.1", "price": 900 },
+                    { "name": "Backwards", "from": "0,1.6", "to": "0,1.5", "price": 400 }
+                ]
+            }"#,
+        )
+        .unwrap();
+
+        let cost = model.estimate(&route(300.0, "0,1"), TravelMode::Driving).unwrap();
+        // 15 litres at 2.00 plus the bridge
+        assert_eq!(cost.fuel, 3000);
+        assert_eq!(cost.toll_segments, ["Bridge"]);
+        assert_eq!(cost.total, 3700);
+        assert_eq!(cost.currency, "EUR");
+
+        assert_eq!(model.estimate(&route(300.0, "0,1"), TravelMode::Cycling).unwrap().total, 0);
+        let mut unknown = route(300.0, "0,1");
+        unknown.distance = None;
+        assert!(model.estimate(&unknown, TravelMode::Driving).is_none());
+        let nowhere = r#"{ "tolls": [{ "name": "x", "from": "north", "to": "0,0", "price": 1 }] }"#;
+        assert!(CostModel::parse(nowhere).is_err());
+    }
+
+    #[test]
+    fn fares_follow_distance_bands() {
+        let model = CostModel::parse(
+            r#"{
+                "currency": "chf",
+                "transitFares": {
+                    "bands": [{ "upToKm": 100, "fare": 4000 }, { "upToKm": 20, "fare": 1000 }],
+                    "perKmBeyond": 10
+                }
+            }"#,
+        )
+        .unwrap();
+
+        let fare = |distance| model.estimate(&route(distance, "0,1"), TravelMode::Transit).unwrap();
+        assert_eq!(fare(15.0).fares, 1000);
+        assert_eq!(fare(60.0).fares, 4000);
+        assert_eq!(fare(150.0).total, 4500);
+        assert_eq!(fare(150.0).currency, "CHF");
+        // Flights keep the built-in table
+        assert_eq!(model.estimate(&route(500.0, "0,1"), TravelMode::Flight).unwrap().fares, 9000);
+    }
+}
@@ -0,0 +1,380 @@
+//! Orders the points of interest visited between a route's start and end so
+//! the whole trip is as short or as quick as possible. Small sets are solved
+//! exactly; larger ones start from a nearest-neighbour tour improved with
+//! 2-opt and or-opt moves until neither helps.
+
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::models::route_option::RouteOption;
+use crate::models::travel_mode::TravelMode;
+use crate::services::geo::Coordinates;
+
+/// Up to this many stops the order is found exactly (Held-Karp).
+pub const EXACT_LIMIT: usize = 10;
+pub const MAX_STOPS: usize = 50;
+/// Hops shorter than this go at town speeds.
+const LOCAL_KM: f64 = 25.0;
+/// Hops shorter than this aren't worth flying and are driven instead.
+const MIN_FLIGHT_KM: f64 = 300.0;
+/// Smallest change counted as an improvement, so rounding can't loop.
+const EPSILON: f64 = 1e-9;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum OptimizeObjective {
+    #[default]
+    Distance,
+    Time,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Solver {
+    Exact,
+    Heuristic,
+}
+
+#[derive(Debug, Default, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct OptimizeRequest {
+    /// The route's points of interest to visit; defaults to all of them.
+    pub poi_ids: Option<Vec<String>>,
+    #[serde(default)]
+    pub objective: OptimizeObjective,
+    #[serde(default)]
+    pub mode: TravelMode,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct OptimizedOrder {
+    pub objective: OptimizeObjective,
+    pub mode: TravelMode,
+    pub solver: Solver,
+    /// The points of interest in visiting order.
+    pub poi_ids: Vec<String>,
+    /// Kilometres from the start through every stop to the end.
+    pub total_distance: f64,
+    pub total_minutes: f64,
+    /// The same totals for the stops in the order they were given.
+    pub given_order_distance: f64,
+    pub given_order_minutes: f64,
+    /// The route with the new order saved as its waypoints.
+    pub route: RouteOption,
+}
+
+/// A visiting order as indexes into the stops, with what it costs.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Tour {
+    pub order: Vec<usize>,
+    pub solver: Solver,
+    pub distance: f64,
+    pub minutes: f64,
+}
+
+/// Minutes for one straight hop. Short hops are slower than the mode's
+/// cruising speed: town driving, stopping services, and flights too short
+/// to take.
+pub fn hop_minutes(mode: TravelMode, km: f64) -> f64 {
+    let speed = match mode {
+        TravelMode::Driving if km < LOCAL_KM => 40.0,
+        TravelMode::Transit if km < LOCAL_KM => 25.0,
+        TravelMode::Flight if km < MIN_FLIGHT_KM => TravelMode::Driving.average_speed_kmh(),
+        _ => mode.average_speed_kmh(),
+    };
+    km / speed * 60.0
+}
+
+/// Hop costs between every pair of points: the start, then the stops, then
+/// the end.
+struct Costs {
+    points: Vec<Coordinates>,
+    mode: TravelMode,
+    matrix: Vec<Vec<f64>>,
+}
+
+impl Costs {
+    fn new(
+        start: Coordinates,
+        end: Coordinates,
+        stops: &[Coordinates],
+        objective: OptimizeObjective,
+        mode: TravelMode,
+    ) -> Self {
+        let mut points = Vec::with_capacity(stops.len() + 2);
+        points.push(start);
+        points.extend_from_slice(stops);
+        points.push(end);
+
+        let matrix = points
+            .iter()
+            .map(|from| {
+                points
+                    .iter()
+                    .map(|to| {
+                        let km = from.distance_km(to);
+                        match objective {
+                            OptimizeObjective::Distance => km,
+                            OptimizeObjective::Time => hop_minutes(mode, km),
+                        }
+                    })
+                    .collect()
+            })
+            .collect();
+        Costs { points, mode, matrix }
+    }
+
+    fn stops(&self) -> usize {
+        self.points.len() - 2
+    }
+
+    /// Cost of a hop between points `a` and `b`, where stop `i` is point
+    /// `i + 1`, the start is 0 and the end is `stops() + 1`.
+    fn hop(&self, a: usize, b: usize) -> f64 {
+        self.matrix[a][b]
+    }
+
+    /// The points an order of stops passes through, ends included.
+    fn path(&self, order: &[usize]) -> Vec<usize> {
+        std::iter::once(0)
+            .chain(order.iter().map(|stop| stop + 1))
+            .chain(std::iter::once(self.stops() + 1))
+            .collect()
+    }
+
+    fn cost(&self, order: &[usize]) -> f64 {
+        self.path(order).windows(2).map(|pair| self.hop(pair[0], pair[1])).sum()
+    }
+
+    fn tour(&self, order: Vec<usize>, solver: Solver) -> Tour {
+        let hops: Vec<f64> = self
+            .path(&order)
+            .windows(2)
+            .map(|pair| self.points[pair[0]].distance_km(&self.points[pair[1]]))
+            .collect();
+        Tour {
+            order,
+            solver,
+            distance: hops.iter().sum(),
+            minutes: hops.iter().map(|km| hop_minutes(self.mode, *km)).sum(),
+        }
+    }
+}
+
+/// The best order in which to visit `stops` between `start` and `end`.
+pub fn optimize(
+    start: Coordinates,
+    end: Coordinates,
+    stops: &[Coordinates],
+    objective: OptimizeObjective,
+    mode: TravelMode,
+) -> Tour {
+    let costs = Costs::new(start, end, stops, objective, mode);
+    if stops.len() <= EXACT_LIMIT {
+        costs.tour(held_karp(&costs), Solver::Exact)
+    } else {
+        costs.tour(improve(&costs, nearest_neighbour(&costs)), Solver::Heuristic)
+    }
+}
+
+/// The stops in the order given, costed the same way as an optimised tour.
+pub fn given_order(
+    start: Coordinates,
+    end: Coordinates,
+    stops: &[Coordinates],
+    mode: TravelMode,
+) -> Tour {
+    let costs = Costs::new(start, end, stops, OptimizeObjective::Distance, mode);
+    costs.tour((0..stops.len()).collect(), Solver::Exact)
+}
+
+/// Dynamic programming over subsets: `best[visited][last]` is the cheapest
+/// way from the start through the stops in `visited`, ending at `last`.
+fn held_karp(costs: &Costs) -> Vec<usize> {
+    let n = costs.stops();
+    if n == 0 {
+        return Vec::new();
+    }
+
+    let full = (1usize << n) - 1;
+    let mut best = vec![vec![f64::INFINITY; n]; full + 1];
+    let mut previous = vec![vec![usize::MAX; n]; full + 1];
+    for stop in 0..n {
+        best[1 << stop][stop] = costs.hop(0, stop + 1);
+    }
+
+    for visited in 1..=full {
+        for last in (0..n).filter(|last| visited & (1 << last) != 0) {
+            let so_far = best[visited][last];
+            if so_far.is_infinite() {
+                continue;
+            }
+            for next in (0..n).filter(|next| visited & (1 << next) == 0) {
+                let extended = visited | (1 << next);
+                let cost = so_far + costs.hop(last + 1, next + 1);
+                if cost < best[extended][next] {
+                    best[extended][next] = cost;
+                    previous[extended][next] = last;
+                }
+            }
+        }
+    }
+
+    let end = n + 1;
+    let mut last = (0..n)
+        .min_by(|a, b| {
+            let cost = |stop: &usize| best[full][*stop] + costs.hop(stop + 1, end);
+            cost(a).total_cmp(&cost(b))
+        })
+        .unwrap_or(0);
+    let mut visited = full;
+    let mut order = Vec::with_capacity(n);
+    while last != usize::MAX {
+        order.push(last);
+        let before = previous[visited][last];
+        visited &= !(1 << last);
+        last = before;
+    }
+    order.reverse();
+    order
+}
+
+fn nearest_neighbour(costs: &Costs) -> Vec<usize> {
+    let mut remaining: Vec<usize> = (0..costs.stops()).collect();
+    let mut order = Vec::with_capacity(remaining.len());
+    let mut at = 0;
+    while !remaining.is_empty() {
+        let (index, _) = remaining
+            .iter()
+            .enumerate()
+            .min_by(|(_, a), (_, b)| costs.hop(at, **a + 1).total_cmp(&costs.hop(at, **b + 1)))
+            .unwrap();
+        let stop = remaining.swap_remove(index);
+        order.push(stop);
+        at = stop + 1;
+    }
+    order
+}
+
+/// Applies 2-opt and or-opt moves until neither shortens the tour.
+fn improve(costs: &Costs, mut order: Vec<usize>) -> Vec<usize> {
+    while two_opt(costs, &mut order) || or_opt(costs, &mut order) {}
+    order
+}
+
+/// Reverses the first stretch of stops whose reversal shortens the tour.
+fn two_opt(costs: &Costs, order: &mut [usize]) -> bool {
+    let path = costs.path(order);
+    for i in 1..path.len() - 2 {
+        for k in i + 1..path.len() - 1 {
+            let before = costs.hop(path[i - 1], path[i]) + costs.hop(path[k], path[k + 1]);
+            let after = costs.hop(path[i - 1], path[k]) + costs.hop(path[i], path[k + 1]);
+            if after < before - EPSILON {
+                order[i - 1..k].reverse();
+                return true;
+            }
+        }
+    }
+    false
+}
+
+/// Moves the first run of one to three stops whose move elsewhere, either
+/// way round, shortens the tour.
+fn or_opt(costs: &Costs, order: &mut Vec<usize>) -> bool {
+    let current = costs.cost(order);
+    for length in 1..=3.min(order.len()) {
+        for from in 0..=order.len() - length {
+            let mut rest = order.clone();
+            let run: Vec<usize> = rest.drain(from..from + length).collect();
+            for to in 0..=rest.len() {
+                if to == from {
+                    continue;
+                }
+                for reversed in [false, true] {
+                    let mut candidate = rest.clone();
+                    let mut moved = run.clone();
+                    if reversed {
+                        moved.reverse();
+                    }
+                    candidate.splice(to..to, moved);
+                    if costs.cost(&candidate) < current - EPSILON {
+                        *order = candidate;
+                        return true;
+                    }
+                }
+            }
+        }
+    }
+    false
+}
+
+/// Stops as the `"lat,lng;lat,lng"` waypoint list routes store.
+pub fn waypoints(stops: &[Coordinates]) -> Option<String> {
+    if stops.is_empty() {
+        return None;
+    }
+    let points: Vec<String> = stops
+        .iter()
+        .map(|stop| format!("{:.6},{:.6}", stop.lat, stop.lng))
+        .collect();
+    Some(points.join(";"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn point(lat: f64, lng: f64) -> Coordinates {
+        Coordinates { lat, lng }
+    }
+
+    #[test]
+    fn finds_the_shortest_order_exactly() {
+        // Stops along the equator, given out of order
+        let stops = [point(0.0, 3.0), point(0.0, 1.0), point(0.0, 4.0), point(0.0, 2.0)];
+        let tour = optimize(
+            point(0.0, 0.0),
+            point(0.0, 5.0),
+            &stops,
+            OptimizeObjective::Distance,
+            TravelMode::Driving,
+        );
+        assert_eq!(tour.solver, Solver::Exact);
+        assert_eq!(tour.order, [1, 3, 0, 2]);
+        let straight = point(0.0, 0.0).distance_km(&point(0.0, 5.0));
+        assert!((tour.distance - straight).abs() < 1e-6);
+        assert!(
+            given_order(point(0.0, 0.0), point(0.0, 5.0), &stops, TravelMode::Driving).distance
+                > tour.distance
+        );
+    }
+
+    #[test]
+    fn heuristic_matches_the_exact_solver_on_a_line() {
+        // Shuffled stops along a meridian; the best tour walks them in turn
+        let stops: Vec<Coordinates> = [7, 2, 11, 4, 0, 9, 5, 1, 10, 3, 8, 6]
+            .iter()
+            .map(|i| point(*i as f64 * 0.1, 0.0))
+            .collect();
+        let tour = optimize(
+            point(-0.1, 0.0),
+            point(1.2, 0.0),
+            &stops,
+            OptimizeObjective::Time,
+            TravelMode::Cycling,
+        );
+        assert_eq!(tour.solver, Solver::Heuristic);
+        let lats: Vec<f64> = tour.order.iter().map(|i| stops[*i].lat).collect();
+        assert!(lats.windows(2).all(|pair| pair[0] < pair[1]));
+        assert!((tour.minutes - hop_minutes(TravelMode::Cycling, tour.distance)).abs() < 1e-6);
+    }
+
+    #[test]
+    fn short_hops_are_slower() {
+        assert_eq!(hop_minutes(TravelMode::Driving, 20.0), 30.0);
+        assert_eq!(hop_minutes(TravelMode::Driving, 80.0), 60.0);
+        assert_eq!(hop_minutes(TravelMode::Flight, 160.0), 120.0);
+        assert_eq!(hop_minutes(TravelMode::Flight, 600.0), 60.0);
+    }
+}
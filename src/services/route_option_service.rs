@@ -1,17 +1,53 @@
-use crate::models::point_of_interest::{PointOfInterest, self};
-use crate::models::route_option::RouteOption;
-use crate::services::travel_plan_service::{TravelPlanError, TravelPlanService};
+use crate::models::plan_event::{self, PlanEventAction};
+use crate::models::point_of_interest::{PatchPointOfInterest, PointOfInterest};
+use crate::models::route_option::{PatchRouteOption, RouteOption};
+use crate::models::plan_leg::PlanLeg;
+use crate::models::plan_member::PlanRole;
+use crate::models::travel_mode::TravelMode;
+use crate::models::travel_plan::TravelPlan;
+use crate::repositories::{
+    BudgetRepository, CommentRepository, ItineraryRepository, PlanEventRepository,
+    PlanLegRepository, PlanMemberRepository, PlanRevisionRepository, PoiRepository,
+    RouteOptionRepository, RouteVoteRepository, ShareLinkRepository, TravelPlanRepository,
+};
+use crate::services::blocking::BlockingError;
+use crate::services::emissions::EmissionFactors;
+use crate::services::geo::Coordinates;
+use crate::services::precondition;
+use crate::services::route_comparison::{
+    self, MAX_COMPARED_ROUTES, MIN_COMPARED_ROUTES, RouteComparison,
+};
+use crate::services::route_cost::{CostModel, RouteCost};
+use crate::services::route_optimizer::{self, OptimizeRequest, OptimizedOrder};
+use crate::services::travel_plan_service::{TravelPlanError, TravelPlanService, check_if_match};
+use chrono::{DateTime, Utc};
 use log::{error, info};
-use rusqlite::{Connection, params};
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use std::sync::Arc;
+use utoipa::ToSchema;
 
-pub struct RouteOptionService;
+pub struct RouteOptionService<R: ?Sized> {
+    repos: Arc<R>,
+    travel_plans: TravelPlanService<R>,
+    costs: Arc<CostModel>,
+    emissions: Arc<EmissionFactors>,
+}
 
 #[derive(Debug)]
 pub enum RouteOptionError {
     TravelPlanError(TravelPlanError),
     RouteNotFound,
+    PoiNotFound,
+    LegNotFound,
     InvalidRouteOption,
+    /// The points of interest can't be put in order, e.g. none were chosen.
+    InvalidOptimization(String),
+    /// The routes to compare are too few or too many.
+    InvalidComparison(String),
+    /// The route is selected for the plan or one of its legs and must be
+    /// unselected first.
+    RouteLocked,
     DatabaseError(String),
 }
 
@@ -21,16 +57,169 @@ impl From<TravelPlanError> for RouteOptionError {
     }
 }
 
+impl From<BlockingError> for RouteOptionError {
+    fn from(error: BlockingError) -> Self {
+        RouteOptionError::DatabaseError(error.to_string())
+    }
+}
+
 #[derive(Debug, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct RouteOptionWithPois {
     pub route: RouteOption,
+    /// Its leg's mode, or driving for a route over the whole plan.
+    pub mode: TravelMode,
+    /// `None` when the route's distance isn't known.
+    pub estimated_cost: Option<RouteCost>,
+    /// Kilograms of CO2e per traveller; `None` when the distance isn't known.
+    pub co2e_kg: Option<f64>,
     pub points_of_interest: Vec<PointOfInterest>,
 }
 
-impl RouteOptionService {
+/// What to order route options by, least first. Options the value isn't
+/// known for come last.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum RouteSort {
+    Cost,
+    Distance,
+    Duration,
+}
+
+/// `routes` ordered by `sort`, or as they were without one. Ties keep their
+/// order.
+pub fn sort_routes(
+    mut routes: Vec<RouteOptionWithPois>,
+    sort: Option<RouteSort>,
+) -> Vec<RouteOptionWithPois> {
+    let Some(sort) = sort else {
+        return routes;
+    };
+    let key = |route: &RouteOptionWithPois| {
+        let value = match sort {
+            RouteSort::Cost => route.estimated_cost.as_ref().map(|cost| cost.total as f64),
+            RouteSort::Distance => route.route.distance,
+            RouteSort::Duration => route.route.duration.map(|minutes| minutes as f64),
+        };
+        value.unwrap_or(f64::INFINITY)
+    };
+    routes.sort_by(|a, b| key(a).total_cmp(&key(b)));
+    routes
+}
+
+/// Editing a point of interest also touches its route, so the route's
+/// `updated_at` versions both.
+fn route_etag(route: &RouteOption) -> String {
+    precondition::etag(route.updated_at, "")
+}
+
+/// The route fields the activity feed tracks, keyed by route id.
+fn route_snapshot(route: &RouteOption) -> Value {
+    json!({
+        route.id.as_str(): {
+            "name": route.name,
+            "description": route.description,
+            "distance": route.distance,
+            "duration": route.duration,
+            "waypoints": route.waypoints,
+        }
+    })
+}
+
+/// The point of interest fields the activity feed tracks, keyed by id.
+fn poi_snapshot(poi: &PointOfInterest) -> Value {
+    json!({
+        poi.id.as_str(): {
+            "name": poi.name,
+            "description": poi.description,
+            "category": poi.category,
+        }
+    })
+}
+
+impl RouteOptionWithPois {
+    pub fn etag(&self) -> String {
+        route_etag(&self.route)
+    }
+}
+
+impl OptimizedOrder {
+    pub fn etag(&self) -> String {
+        route_etag(&self.route)
+    }
+}
+
+impl<R> RouteOptionService<R>
+where
+    R: TravelPlanRepository
+        + PlanMemberRepository
+        + ShareLinkRepository
+        + RouteOptionRepository
+        + RouteVoteRepository
+        + CommentRepository
+        + PlanEventRepository
+        + PlanRevisionRepository
+        + ItineraryRepository
+        + PlanLegRepository
+        + BudgetRepository
+        + PoiRepository
+        + ?Sized,
+{
+    pub fn new(repos: Arc<R>) -> Self {
+        RouteOptionService {
+            travel_plans: TravelPlanService::new(repos.clone()),
+            repos,
+            costs: Arc::new(CostModel::default()),
+            emissions: Arc::new(EmissionFactors::default()),
+        }
+    }
+
+    /// Prices routes with `costs` instead of the built-in model.
+    pub fn with_costs(mut self, costs: Arc<CostModel>) -> Self {
+        self.costs = costs;
+        self
+    }
+
+    /// Estimates emissions with `factors` instead of the built-in table.
+    pub fn with_emission_factors(mut self, factors: Arc<EmissionFactors>) -> Self {
+        self.emissions = factors;
+        self
+    }
+
+    /// How `route` is travelled: by its leg's mode, or by car for a route
+    /// over the whole plan.
+    fn route_mode(&self, route: &RouteOption) -> Result<TravelMode, RouteOptionError> {
+        let Some(leg_id) = &route.leg_id else {
+            return Ok(TravelMode::Driving);
+        };
+        match self.repos.find_plan_leg(leg_id) {
+            Ok(leg) => Ok(leg.map_or(TravelMode::Driving, |leg| leg.mode)),
+            Err(e) => {
+                error!("Error fetching leg: {}", e);
+                Err(RouteOptionError::DatabaseError(e.to_string()))
+            }
+        }
+    }
+
+    /// `route` with its points of interest, and what it costs and emits to
+    /// travel.
+    fn with_pois(
+        &self,
+        route: RouteOption,
+        points_of_interest: Vec<PointOfInterest>,
+    ) -> Result<RouteOptionWithPois, RouteOptionError> {
+        let mode = self.route_mode(&route)?;
+        Ok(RouteOptionWithPois {
+            estimated_cost: self.costs.estimate(&route, mode),
+            co2e_kg: self.emissions.estimate(&route, mode),
+            mode,
+            route,
+            points_of_interest,
+        })
+    }
+
     pub fn get_route_options(
-        conn: &Connection,
+        &self,
         plan_id: &str,
         user_id: &str,
     ) -> Result<Vec<RouteOptionWithPois>, RouteOptionError> {
@@ -39,20 +228,15 @@ impl RouteOptionService {
             plan_id, user_id
         );
 
-        let _ = TravelPlanService::get_travel_plan_by_id(conn, plan_id, user_id)?;
+        let _ = self.travel_plans.get_travel_plan_by_id(plan_id, user_id)?;
 
-        match RouteOption::find_by_travel_plan_id(conn, plan_id) {
+        match self.repos.find_route_options_by_plan(plan_id) {
             Ok(routes) => {
                 let mut routes_with_pois = Vec::new();
 
                 for route in routes {
-                    match PointOfInterest::find_by_route_option_id(conn, &route.id) {
-                        Ok(pois) => {
-                            routes_with_pois.push(RouteOptionWithPois {
-                                route,
-                                points_of_interest: pois,
-                            });
-                        }
+                    match self.repos.find_pois_by_route(&route.id) {
+                        Ok(pois) => routes_with_pois.push(self.with_pois(route, pois)?),
                         Err(e) => {
                             error!("Error fetching points of interest: {}", e);
                             return Err(RouteOptionError::DatabaseError(e.to_string()));
@@ -74,8 +258,84 @@ impl RouteOptionService {
         }
     }
 
+    /// The plan's routes with the given ids, side by side in that order.
+    /// Repeated ids count once.
+    pub fn compare_route_options(
+        &self,
+        plan_id: &str,
+        user_id: &str,
+        route_ids: &[String],
+    ) -> Result<RouteComparison, RouteOptionError> {
+        let mut ids: Vec<&str> = Vec::new();
+        for id in route_ids {
+            if !ids.contains(&id.as_str()) {
+                ids.push(id);
+            }
+        }
+        if !(MIN_COMPARED_ROUTES..=MAX_COMPARED_ROUTES).contains(&ids.len()) {
+            return Err(RouteOptionError::InvalidComparison(format!(
+                "Compare between {} and {} route options",
+                MIN_COMPARED_ROUTES, MAX_COMPARED_ROUTES
+            )));
+        }
+
+        let mut routes = self.get_route_options(plan_id, user_id)?;
+        let mut compared = Vec::new();
+        for id in ids {
+            let index = routes
+                .iter()
+                .position(|route| route.route.id == id)
+                .ok_or(RouteOptionError::RouteNotFound)?;
+            compared.push(routes.swap_remove(index));
+        }
+
+        Ok(route_comparison::compare(compared))
+    }
+
+    fn route_ids(&self, plan_id: &str) -> Result<Vec<String>, RouteOptionError> {
+        match self.repos.find_route_options_by_plan(plan_id) {
+            Ok(routes) => Ok(routes.into_iter().map(|route| route.id).collect()),
+            Err(e) => {
+                error!("Error fetching route options: {}", e);
+                Err(RouteOptionError::DatabaseError(e.to_string()))
+            }
+        }
+    }
+
+    /// Routes that can't be deleted: the plan's selected route and those of
+    /// its legs.
+    fn locked_route_ids(&self, plan: &TravelPlan) -> Result<Vec<String>, RouteOptionError> {
+        let legs = match self.repos.find_plan_legs(&plan.id) {
+            Ok(legs) => legs,
+            Err(e) => {
+                error!("Error fetching legs: {}", e);
+                return Err(RouteOptionError::DatabaseError(e.to_string()));
+            }
+        };
+
+        Ok(plan
+            .selected_route_id
+            .iter()
+            .cloned()
+            .chain(legs.into_iter().filter_map(|leg| leg.selected_route_id))
+            .collect())
+    }
+
+    /// Records the plan's route ids before and after a change.
+    fn record_routes_event(
+        &self,
+        plan_id: &str,
+        user_id: &str,
+        action: PlanEventAction,
+        before: Vec<String>,
+    ) -> Result<(), RouteOptionError> {
+        let after = self.route_ids(plan_id)?;
+        let changes = plan_event::diff(&json!({ "routes": before }), &json!({ "routes": after }));
+        Ok(self.travel_plans.record_event(plan_id, user_id, action, changes)?)
+    }
+
     pub fn generate_route_options(
-        conn: &Connection,
+        &self,
         plan_id: &str,
         user_id: &str,
         count: usize,
@@ -85,47 +345,105 @@ impl RouteOptionService {
             count, plan_id, user_id
         );
 
-        let _ = TravelPlanService::get_travel_plan_by_id(conn, plan_id, user_id)?;
-
-        match RouteOption::generate_random_options(conn, plan_id, count) {
-            Ok(routes) => {
-                let mut routes_with_pois = Vec::new();
-
-                // For each route option, generate random points of interest
-                for route in routes {
-                    // Generate 2-5 random points of interest for each route
-                    let poi_count = 2 + (count % 4); // Between 2 and 5
-
-                    match PointOfInterest::generate_random_pois(conn, &route.id, poi_count) {
-                        Ok(pois) => {
-                            routes_with_pois.push(RouteOptionWithPois {
-                                route,
-                                points_of_interest: pois,
-                            });
-                        }
-                        Err(e) => {
-                            error!("Error generating points of interest: {}", e);
-                            return Err(RouteOptionError::DatabaseError(e.to_string()));
-                        }
-                    }
-                }
+        let plan_dto = self.travel_plans.authorize(plan_id, user_id, PlanRole::Editor)?;
+        let routes = RouteOption::generate_random_options(&plan_dto.travel_plan, count);
+        self.insert_generated_routes(plan_id, user_id, routes, count)
+    }
 
-                info!(
-                    "Generated {} route options with points of interest for travel plan ID: {}",
-                    routes_with_pois.len(),
-                    plan_id
-                );
-                Ok(routes_with_pois)
+    /// The leg `leg_id` of the plan.
+    fn leg_in_plan(&self, plan_id: &str, leg_id: &str) -> Result<PlanLeg, RouteOptionError> {
+        match self.repos.find_plan_leg(leg_id) {
+            Ok(Some(leg)) if leg.travel_plan_id == plan_id => Ok(leg),
+            Ok(_) => {
+                info!("Leg not found with ID: {}", leg_id);
+                Err(RouteOptionError::LegNotFound)
             }
             Err(e) => {
-                error!("Error generating route options: {}", e);
+                error!("Error fetching leg: {}", e);
                 Err(RouteOptionError::DatabaseError(e.to_string()))
             }
         }
     }
 
+    /// Generates route options between the ends of one leg of the plan.
+    pub fn generate_leg_route_options(
+        &self,
+        plan_id: &str,
+        leg_id: &str,
+        user_id: &str,
+        count: usize,
+    ) -> Result<Vec<RouteOptionWithPois>, RouteOptionError> {
+        info!(
+            "Generating {} random route options for leg {} of travel plan ID: {} for user: {}",
+            count, leg_id, plan_id, user_id
+        );
+
+        self.travel_plans.authorize(plan_id, user_id, PlanRole::Editor)?;
+        let leg = self.leg_in_plan(plan_id, leg_id)?;
+        let routes = RouteOption::generate_random_options_for_leg(&leg, count);
+        self.insert_generated_routes(plan_id, user_id, routes, count)
+    }
+
+    /// The route options of one leg of the plan.
+    pub fn get_leg_route_options(
+        &self,
+        plan_id: &str,
+        leg_id: &str,
+        user_id: &str,
+    ) -> Result<Vec<RouteOptionWithPois>, RouteOptionError> {
+        let routes_with_pois = self.get_route_options(plan_id, user_id)?;
+        self.leg_in_plan(plan_id, leg_id)?;
+
+        Ok(routes_with_pois
+            .into_iter()
+            .filter(|route| route.route.leg_id.as_deref() == Some(leg_id))
+            .collect())
+    }
+
+    /// Stores freshly generated routes with random points of interest.
+    fn insert_generated_routes(
+        &self,
+        plan_id: &str,
+        user_id: &str,
+        routes: Vec<RouteOption>,
+        count: usize,
+    ) -> Result<Vec<RouteOptionWithPois>, RouteOptionError> {
+        let route_ids = self.route_ids(plan_id)?;
+
+        let mut routes_with_pois = Vec::new();
+
+        // For each route option, generate random points of interest
+        for route in routes {
+            if let Err(e) = self.repos.insert_route_option(&route) {
+                error!("Error generating route options: {}", e);
+                return Err(RouteOptionError::DatabaseError(e.to_string()));
+            }
+
+            // Generate 2-5 random points of interest for each route
+            let poi_count = 2 + (count % 4); // Between 2 and 5
+            let pois = PointOfInterest::generate_random_pois(&route, poi_count);
+
+            for poi in &pois {
+                if let Err(e) = self.repos.insert_poi(poi) {
+                    error!("Error generating points of interest: {}", e);
+                    return Err(RouteOptionError::DatabaseError(e.to_string()));
+                }
+            }
+
+            routes_with_pois.push(self.with_pois(route, pois)?);
+        }
+
+        info!(
+            "Generated {} route options with points of interest for travel plan ID: {}",
+            routes_with_pois.len(),
+            plan_id
+        );
+        self.record_routes_event(plan_id, user_id, PlanEventAction::RoutesGenerated, route_ids)?;
+        Ok(routes_with_pois)
+    }
+
     pub fn get_route_option_by_id(
-        conn: &Connection,
+        &self,
         plan_id: &str,
         route_id: &str,
         user_id: &str,
@@ -135,15 +453,15 @@ impl RouteOptionService {
             route_id, plan_id, user_id
         );
 
-        let _ = TravelPlanService::get_travel_plan_by_id(conn, plan_id, user_id)?;
+        let _ = self.travel_plans.get_travel_plan_by_id(plan_id, user_id)?;
 
-        match RouteOption::find_by_id(conn, route_id) {
+        match self.repos.find_route_option(route_id) {
             Ok(Some(route)) => {
                 if route.travel_plan_id != plan_id {
                     return Err(RouteOptionError::InvalidRouteOption);
                 }
 
-                match PointOfInterest::find_by_route_option_id(conn, &route.id) {
+                match self.repos.find_pois_by_route(&route.id) {
                     Ok(pois) => {
                         info!(
                             "Found route option with ID: {} with {} points of interest",
@@ -151,10 +469,7 @@ impl RouteOptionService {
                             pois.len()
                         );
 
-                        Ok(RouteOptionWithPois {
-                            route,
-                            points_of_interest: pois,
-                        })
+                        self.with_pois(route, pois)
                     }
                     Err(e) => {
                         error!("Error fetching points of interest: {}", e);
@@ -173,44 +488,307 @@ impl RouteOptionService {
         }
     }
 
+    /// The route `route_id` of the plan, or why it can't be had.
+    fn route_in_plan(
+        &self,
+        plan_id: &str,
+        route_id: &str,
+    ) -> Result<RouteOption, RouteOptionError> {
+        match self.repos.find_route_option(route_id) {
+            Ok(Some(route)) if route.travel_plan_id == plan_id => Ok(route),
+            Ok(Some(_)) => Err(RouteOptionError::InvalidRouteOption),
+            Ok(None) => {
+                info!("Route option not found with ID: {}", route_id);
+                Err(RouteOptionError::RouteNotFound)
+            }
+            Err(e) => {
+                error!("Error fetching route option: {}", e);
+                Err(RouteOptionError::DatabaseError(e.to_string()))
+            }
+        }
+    }
+
+    /// Stores an edited route over the version last updated at
+    /// `expected_updated_at`; fails if the route has changed since.
+    fn update_route(
+        &self,
+        route: &RouteOption,
+        expected_updated_at: DateTime<Utc>,
+    ) -> Result<(), RouteOptionError> {
+        match self.repos.update_route_option(route, expected_updated_at) {
+            Ok(0) => {
+                info!("Route option {} changed while being updated", route.id);
+                Err(TravelPlanError::PreconditionFailed.into())
+            }
+            Ok(_) => Ok(()),
+            Err(e) => {
+                error!("Error updating route option: {}", e);
+                Err(RouteOptionError::DatabaseError(e.to_string()))
+            }
+        }
+    }
+
+    /// The route with its points of interest.
+    fn load_pois(&self, route: RouteOption) -> Result<RouteOptionWithPois, RouteOptionError> {
+        match self.repos.find_pois_by_route(&route.id) {
+            Ok(pois) => self.with_pois(route, pois),
+            Err(e) => {
+                error!("Error fetching points of interest: {}", e);
+                Err(RouteOptionError::DatabaseError(e.to_string()))
+            }
+        }
+    }
+
+    /// Applies a JSON Merge Patch to a route option.
+    pub fn patch_route_option(
+        &self,
+        plan_id: &str,
+        route_id: &str,
+        patch: &PatchRouteOption,
+        user_id: &str,
+        if_match: Option<&str>,
+    ) -> Result<RouteOptionWithPois, RouteOptionError> {
+        info!(
+            "Patching route option with ID: {} for travel plan ID: {} for user: {}",
+            route_id, plan_id, user_id
+        );
+
+        self.travel_plans.authorize(plan_id, user_id, PlanRole::Editor)?;
+        let route = self.route_in_plan(plan_id, route_id)?;
+        check_if_match(if_match, &route_etag(&route))?;
+
+        let patched_route = route.with_patch(patch);
+        self.update_route(&patched_route, route.updated_at)?;
+        let route_with_pois = self.load_pois(patched_route.clone())?;
+
+        let changes = plan_event::diff(&route_snapshot(&route), &route_snapshot(&patched_route));
+        if changes.as_object().is_some_and(|c| !c.is_empty()) {
+            self.travel_plans.record_event(
+                plan_id,
+                user_id,
+                PlanEventAction::RouteUpdated,
+                changes,
+            )?;
+        }
+        Ok(route_with_pois)
+    }
+
+    /// Applies a JSON Merge Patch to one of a route's points of interest.
+    /// `if_match` is checked against the route's ETag, which the edit bumps.
+    pub fn patch_point_of_interest(
+        &self,
+        plan_id: &str,
+        route_id: &str,
+        poi_id: &str,
+        patch: &PatchPointOfInterest,
+        user_id: &str,
+        if_match: Option<&str>,
+    ) -> Result<RouteOptionWithPois, RouteOptionError> {
+        info!(
+            "Patching point of interest with ID: {} on route option {} for user: {}",
+            poi_id, route_id, user_id
+        );
+
+        self.travel_plans.authorize(plan_id, user_id, PlanRole::Editor)?;
+        let mut route = self.route_in_plan(plan_id, route_id)?;
+        check_if_match(if_match, &route_etag(&route))?;
+
+        let poi = match self.repos.find_poi(poi_id) {
+            Ok(Some(poi)) if poi.route_option_id == route_id => poi,
+            Ok(_) => {
+                info!("Point of interest not found with ID: {}", poi_id);
+                return Err(RouteOptionError::PoiNotFound);
+            }
+            Err(e) => {
+                error!("Error fetching point of interest: {}", e);
+                return Err(RouteOptionError::DatabaseError(e.to_string()));
+            }
+        };
+
+        // Bump the route first, so that a concurrent edit fails before the
+        // point of interest is touched
+        let expected_updated_at = route.updated_at;
+        route.updated_at = Utc::now();
+        self.update_route(&route, expected_updated_at)?;
+        let patched_poi = poi.with_patch(patch);
+        if let Err(e) = self.repos.update_poi(&patched_poi) {
+            error!("Error updating point of interest: {}", e);
+            return Err(RouteOptionError::DatabaseError(e.to_string()));
+        }
+        let route_with_pois = self.load_pois(route)?;
+
+        let changes = plan_event::diff(&poi_snapshot(&poi), &poi_snapshot(&patched_poi));
+        if changes.as_object().is_some_and(|c| !c.is_empty()) {
+            self.travel_plans.record_event(
+                plan_id,
+                user_id,
+                PlanEventAction::PoiUpdated,
+                changes,
+            )?;
+        }
+        Ok(route_with_pois)
+    }
+
+    /// Orders the chosen points of interest so the route from its start
+    /// through each of them to its end is as short or as quick as possible,
+    /// and saves that order as the route's waypoints.
+    pub fn optimize_poi_order(
+        &self,
+        plan_id: &str,
+        route_id: &str,
+        request: &OptimizeRequest,
+        user_id: &str,
+        if_match: Option<&str>,
+    ) -> Result<OptimizedOrder, RouteOptionError> {
+        self.travel_plans.authorize(plan_id, user_id, PlanRole::Editor)?;
+        let route = self.route_in_plan(plan_id, route_id)?;
+        check_if_match(if_match, &route_etag(&route))?;
+
+        let invalid = |message: &str| RouteOptionError::InvalidOptimization(message.to_string());
+        let route_pois = match self.repos.find_pois_by_route(route_id) {
+            Ok(pois) => pois,
+            Err(e) => {
+                error!("Error fetching points of interest: {}", e);
+                return Err(RouteOptionError::DatabaseError(e.to_string()));
+            }
+        };
+        let chosen: Vec<PointOfInterest> = match &request.poi_ids {
+            None => route_pois,
+            Some(poi_ids) => {
+                let mut chosen = Vec::with_capacity(poi_ids.len());
+                for poi_id in poi_ids {
+                    if chosen.iter().any(|poi: &PointOfInterest| &poi.id == poi_id) {
+                        return Err(invalid("poiIds must not repeat a point of interest"));
+                    }
+                    let poi = route_pois
+                        .iter()
+                        .find(|poi| &poi.id == poi_id)
+                        .ok_or(RouteOptionError::PoiNotFound)?;
+                    chosen.push(poi.clone());
+                }
+                chosen
+            }
+        };
+        if chosen.is_empty() {
+            return Err(invalid("There are no points of interest to put in order"));
+        }
+        if chosen.len() > route_optimizer::MAX_STOPS {
+            return Err(RouteOptionError::InvalidOptimization(format!(
+                "At most {} points of interest can be put in order",
+                route_optimizer::MAX_STOPS
+            )));
+        }
+
+        let start = Coordinates::parse(&route.start_coordinates)
+            .ok_or_else(|| invalid("The route's start coordinates can't be read"))?;
+        let end = Coordinates::parse(&route.end_coordinates)
+            .ok_or_else(|| invalid("The route's end coordinates can't be read"))?;
+        let mut stops = Vec::with_capacity(chosen.len());
+        for poi in &chosen {
+            let coordinates = Coordinates::parse(&poi.coordinates).ok_or_else(|| {
+                RouteOptionError::InvalidOptimization(format!(
+                    "The coordinates of {} can't be read",
+                    poi.name
+                ))
+            })?;
+            stops.push(coordinates);
+        }
+
+        let given = route_optimizer::given_order(start, end, &stops, request.mode);
+        let tour = route_optimizer::optimize(start, end, &stops, request.objective, request.mode);
+        let ordered: Vec<_> = tour.order.iter().map(|stop| stops[*stop]).collect();
+
+        let mut optimized_route = route.clone();
+        optimized_route.waypoints = route_optimizer::waypoints(&ordered);
+        optimized_route.updated_at = Utc::now();
+        self.update_route(&optimized_route, route.updated_at)?;
+
+        let changes =
+            plan_event::diff(&route_snapshot(&route), &route_snapshot(&optimized_route));
+        if changes.as_object().is_some_and(|c| !c.is_empty()) {
+            self.travel_plans.record_event(
+                plan_id,
+                user_id,
+                PlanEventAction::RouteUpdated,
+                changes,
+            )?;
+        }
+
+        info!(
+            "User {} ordered {} points of interest on route option {} ({:?} solver)",
+            user_id,
+            chosen.len(),
+            route_id,
+            tour.solver
+        );
+        Ok(OptimizedOrder {
+            objective: request.objective,
+            mode: request.mode,
+            solver: tour.solver,
+            poi_ids: tour.order.iter().map(|stop| chosen[*stop].id.clone()).collect(),
+            total_distance: tour.distance,
+            total_minutes: tour.minutes,
+            given_order_distance: given.distance,
+            given_order_minutes: given.minutes,
+            route: optimized_route,
+        })
+    }
+
     pub fn delete_route_option(
-        conn: &Connection,
+        &self,
         plan_id: &str,
         route_id: &str,
         user_id: &str,
+        if_match: Option<&str>,
     ) -> Result<bool, RouteOptionError> {
         info!(
             "Deleting route option with ID: {} for travel plan ID: {} for user: {}",
             route_id, plan_id, user_id
         );
 
-        let _ = TravelPlanService::get_travel_plan_by_id(conn, plan_id, user_id)?;
+        let plan_dto = self.travel_plans.authorize(plan_id, user_id, PlanRole::Editor)?;
 
-        match RouteOption::find_by_id(conn, route_id) {
+        match self.repos.find_route_option(route_id) {
             Ok(Some(route)) => {
                 if route.travel_plan_id != plan_id {
                     return Err(RouteOptionError::InvalidRouteOption);
                 }
+                check_if_match(if_match, &route_etag(&route))?;
 
-                match point_of_interest::PointOfInterest::delete_by_route_option_id(conn, route_id) {
-                    Ok(_) => {
-                        match RouteOption::delete(conn, route_id) {
-                            Ok(deleted) => {
-                                info!(
-                                    "Route option with ID: {} {}",
-                                    route_id,
-                                    if deleted { "deleted successfully" } else { "not found" }
-                                );
-                                Ok(deleted)
-                            }
-                            Err(e) => {
-                                error!("Error deleting route option: {}", e);
-                                Err(RouteOptionError::DatabaseError(e.to_string()))
-                            }
-                        }
+                if self.locked_route_ids(&plan_dto.travel_plan)?.iter().any(|id| id == route_id) {
+                    info!("Refusing to delete selected route option {}", route_id);
+                    return Err(RouteOptionError::RouteLocked);
+                }
+
+                let route_ids = self.route_ids(plan_id)?;
+
+                // The repository removes the route's votes, comments, points
+                // of interest and itinerary items with it. An If-Match also
+                // has to hold at the moment of deletion.
+                let expected_updated_at = if_match
+                    .filter(|header| precondition::pins_state(header))
+                    .map(|_| route.updated_at);
+                match self.repos.delete_route_option(route_id, expected_updated_at) {
+                    Ok(true) => {
+                        info!("Route option with ID: {} deleted successfully", route_id);
+                        self.record_routes_event(
+                            plan_id,
+                            user_id,
+                            PlanEventAction::RouteDeleted,
+                            route_ids,
+                        )?;
+                        Ok(true)
+                    }
+                    Ok(false) if expected_updated_at.is_some() => {
+                        info!("Route option {} changed while being deleted", route_id);
+                        Err(TravelPlanError::PreconditionFailed.into())
+                    }
+                    Ok(false) => {
+                        info!("Route option with ID: {} not found", route_id);
+                        Ok(false)
                     }
                     Err(e) => {
-                        error!("Error deleting points of interest: {}", e);
+                        error!("Error deleting route option: {}", e);
                         Err(RouteOptionError::DatabaseError(e.to_string()))
                     }
                 }
@@ -226,55 +804,151 @@ impl RouteOptionService {
         }
     }
 
+    /// Deletes every route option of the plan except those selected for the
+    /// plan or one of its legs, which stay until they are unselected.
+    /// `if_match` is checked against the plan's ETag and has to hold at the
+    /// moment of deletion too.
     pub fn delete_all_route_options(
-        conn: &Connection,
+        &self,
         plan_id: &str,
         user_id: &str,
+        if_match: Option<&str>,
     ) -> Result<usize, RouteOptionError> {
         info!(
             "Deleting all route options for travel plan ID: {} for user: {}",
             plan_id, user_id
         );
 
-        let _ = TravelPlanService::get_travel_plan_by_id(conn, plan_id, user_id)?;
+        let plan_dto = self.travel_plans.authorize(plan_id, user_id, PlanRole::Editor)?;
+        check_if_match(if_match, &plan_dto.etag())?;
+        let expected_updated_at = if_match
+            .filter(|header| precondition::pins_state(header))
+            .map(|_| plan_dto.travel_plan.updated_at);
 
-        let route_options = match RouteOption::find_by_travel_plan_id(conn, plan_id) {
-            Ok(routes) => routes,
-            Err(e) => return Err(RouteOptionError::DatabaseError(e.to_string())),
+        // The repository reads the selection and deletes the rest, with
+        // everything on them, in one transaction
+        let deleted = match self
+            .repos
+            .delete_unselected_route_options(plan_id, expected_updated_at)
+        {
+            Ok(Some(deleted)) => deleted,
+            Ok(None) if expected_updated_at.is_some() => {
+                info!("Travel plan {} changed while its routes were being deleted", plan_id);
+                return Err(TravelPlanError::PreconditionFailed.into());
+            }
+            Ok(None) => return Err(TravelPlanError::NotFound.into()),
+            Err(e) => {
+                error!("Error deleting route options: {}", e);
+                return Err(RouteOptionError::DatabaseError(e.to_string()));
+            }
         };
-        
-        let count = route_options.len();
 
-        if count == 0 {
-            info!("No route options found for travel plan ID: {}", plan_id);
+        if deleted.is_empty() {
+            info!("No route options to delete for travel plan ID: {}", plan_id);
             return Ok(0);
         }
 
-        for route in &route_options {
-            match point_of_interest::PointOfInterest::delete_by_route_option_id(conn, &route.id) {
-                Ok(_) => {},
-                Err(e) => {
-                    error!("Error deleting points of interest for route option {}: {}", route.id, e);
-                    return Err(RouteOptionError::DatabaseError(e.to_string()));
-                }
-            }
-        }
+        info!(
+            "Deleted {} route options for travel plan ID: {}",
+            deleted.len(),
+            plan_id
+        );
+        // Only the deleted routes; selected ones that stay aren't part of it
+        let count = deleted.len();
+        let changes = plan_event::diff(&json!({ "routes": deleted }), &json!({ "routes": [] }));
+        self.travel_plans
+            .record_event(plan_id, user_id, PlanEventAction::RoutesCleared, changes)?;
+        Ok(count)
+    }
+}
 
-        match conn.execute(
-            "DELETE FROM route_options WHERE travel_plan_id = ?1",
-            params![plan_id],
-        ) {
-            Ok(deleted_count) => {
-                info!(
-                    "Deleted {} route options for travel plan ID: {}",
-                    deleted_count, plan_id
-                );
-                Ok(deleted_count)
-            }
-            Err(e) => {
-                error!("Error deleting route options: {}", e);
-                Err(RouteOptionError::DatabaseError(e.to_string()))
-            }
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::travel_plan::{NewTravelPlan, TravelPlan};
+    use crate::repositories::memory::InMemoryRepository;
+
+    fn setup() -> (RouteOptionService<InMemoryRepository>, TravelPlan) {
+        let repos = Arc::new(InMemoryRepository::new());
+        let plan = TravelPlan::new(
+            &NewTravelPlan {
+                name: "Road trip".to_string(),
+                description: None,
+                start_location: "Amsterdam".to_string(),
+                end_location: "Berlin".to_string(),
+                start_date: None,
+                end_date: None,
+                home_currency: None,
+            },
+            "alice",
+        );
+        repos.insert_travel_plan(&plan).unwrap();
+
+        (RouteOptionService::new(repos), plan)
+    }
+
+    #[test]
+    fn generates_routes_with_pois() {
+        let (service, plan) = setup();
+
+        let generated = service.generate_route_options(&plan.id, "alice", 3).unwrap();
+        let listed = service.get_route_options(&plan.id, "alice").unwrap();
+
+        assert_eq!(generated.len(), 3);
+        assert_eq!(listed.len(), 3);
+        for route in &listed {
+            assert_eq!(route.route.travel_plan_id, plan.id);
+            assert!(!route.points_of_interest.is_empty());
+            assert!(
+                route
+                    .points_of_interest
+                    .iter()
+                    .all(|poi| poi.route_option_id == route.route.id)
+            );
         }
     }
+
+    #[test]
+    fn rejects_other_users() {
+        let (service, plan) = setup();
+
+        let result = service.generate_route_options(&plan.id, "bob", 1);
+
+        assert!(matches!(
+            result,
+            Err(RouteOptionError::TravelPlanError(TravelPlanError::Unauthorized))
+        ));
+    }
+
+    #[test]
+    fn reports_missing_route() {
+        let (service, plan) = setup();
+
+        let result = service.get_route_option_by_id(&plan.id, "missing", "alice");
+
+        assert!(matches!(result, Err(RouteOptionError::RouteNotFound)));
+    }
+
+    #[test]
+    fn deletes_single_route_and_its_pois() {
+        let (service, plan) = setup();
+        let generated = service.generate_route_options(&plan.id, "alice", 2).unwrap();
+        let route_id = generated[0].route.id.clone();
+
+        assert!(service.delete_route_option(&plan.id, &route_id, "alice", None).unwrap());
+
+        assert_eq!(service.get_route_options(&plan.id, "alice").unwrap().len(), 1);
+        assert!(service.repos.find_pois_by_route(&route_id).unwrap().is_empty());
+    }
+
+    #[test]
+    fn deletes_all_routes() {
+        let (service, plan) = setup();
+        service.generate_route_options(&plan.id, "alice", 4).unwrap();
+
+        let deleted = service.delete_all_route_options(&plan.id, "alice", None).unwrap();
+
+        assert_eq!(deleted, 4);
+        assert!(service.get_route_options(&plan.id, "alice").unwrap().is_empty());
+    }
 }
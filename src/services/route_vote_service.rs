@@ -0,0 +1,342 @@
+use chrono::Utc;
+use log::{error, info};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::sync::Arc;
+use utoipa::ToSchema;
+
+use crate::models::plan_event::{self, PlanEventAction};
+use crate::models::plan_member::PlanRole;
+use crate::models::route_option::RouteOption;
+use crate::models::route_vote::{RouteVote, VoteDirection};
+use crate::repositories::{
+    BudgetRepository, CommentRepository, ItineraryRepository, PlanEventRepository,
+    PlanLegRepository, PlanMemberRepository, PlanRevisionRepository, RepositoryError,
+    RouteOptionRepository, RouteVoteRepository, ShareLinkRepository, TravelPlanRepository,
+};
+use crate::services::blocking::BlockingError;
+use crate::services::travel_plan_service::{TravelPlanDto, TravelPlanError, TravelPlanService};
+
+/// Members vote on a plan's route options; the owner locks one in.
+pub struct RouteVoteService<R: ?Sized> {
+    repos: Arc<R>,
+    travel_plans: TravelPlanService<R>,
+}
+
+#[derive(Debug)]
+pub enum RouteVoteError {
+    TravelPlanError(TravelPlanError),
+    /// The route does not exist or belongs to another plan.
+    RouteNotFound,
+    VoteNotFound,
+    DatabaseError(String),
+}
+
+impl From<TravelPlanError> for RouteVoteError {
+    fn from(error: TravelPlanError) -> Self {
+        RouteVoteError::TravelPlanError(error)
+    }
+}
+
+impl From<BlockingError> for RouteVoteError {
+    fn from(error: BlockingError) -> Self {
+        RouteVoteError::DatabaseError(error.to_string())
+    }
+}
+
+impl From<RepositoryError> for RouteVoteError {
+    fn from(error: RepositoryError) -> Self {
+        error!("Route vote repository error: {}", error);
+        RouteVoteError::DatabaseError(error.to_string())
+    }
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct CastVote {
+    pub direction: VoteDirection,
+}
+
+/// The owner's choice; `null` clears it.
+#[derive(Debug, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct SelectRoute {
+    pub route_id: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct RouteTally {
+    pub route_id: String,
+    pub name: String,
+    pub up: usize,
+    pub down: usize,
+    /// Up votes minus down votes.
+    pub score: i64,
+    /// The caller's own vote on this route.
+    pub your_vote: Option<VoteDirection>,
+    pub selected: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct VoteTally {
+    pub selected_route_id: Option<String>,
+    /// Highest score first; ties keep the order the routes were generated in.
+    pub routes: Vec<RouteTally>,
+}
+
+impl<R> RouteVoteService<R>
+where
+    R: TravelPlanRepository
+        + PlanMemberRepository
+        + ShareLinkRepository
+        + RouteOptionRepository
+        + RouteVoteRepository
+        + CommentRepository
+        + PlanEventRepository
+        + PlanRevisionRepository
+        + ItineraryRepository
+        + PlanLegRepository
+        + BudgetRepository
+        + ?Sized,
+{
+    pub fn new(repos: Arc<R>) -> Self {
+        RouteVoteService {
+            travel_plans: TravelPlanService::new(repos.clone()),
+            repos,
+        }
+    }
+
+    fn route_in_plan(&self, plan_id: &str, route_id: &str) -> Result<RouteOption, RouteVoteError> {
+        match self.repos.find_route_option(route_id)? {
+            Some(route) if route.travel_plan_id == plan_id => Ok(route),
+            _ => Err(RouteVoteError::RouteNotFound),
+        }
+    }
+
+    /// Any member who can see the plan can vote; voting again changes the vote.
+    pub fn cast_vote(
+        &self,
+        plan_id: &str,
+        route_id: &str,
+        user_id: &str,
+        direction: VoteDirection,
+    ) -> Result<RouteVote, RouteVoteError> {
+        self.travel_plans.authorize(plan_id, user_id, PlanRole::Viewer)?;
+        self.route_in_plan(plan_id, route_id)?;
+
+        let vote = RouteVote::new(plan_id, route_id, user_id, direction);
+        self.repos.upsert_route_vote(&vote)?;
+
+        info!(
+            "User {} voted {} on route option {}",
+            user_id,
+            direction.as_str(),
+            route_id
+        );
+        Ok(vote)
+    }
+
+    pub fn retract_vote(
+        &self,
+        plan_id: &str,
+        route_id: &str,
+        user_id: &str,
+    ) -> Result<(), RouteVoteError> {
+        self.travel_plans.authorize(plan_id, user_id, PlanRole::Viewer)?;
+        self.route_in_plan(plan_id, route_id)?;
+
+        if !self.repos.delete_route_vote(route_id, user_id)? {
+            return Err(RouteVoteError::VoteNotFound);
+        }
+        Ok(())
+    }
+
+    pub fn tally(&self, plan_id: &str, user_id: &str) -> Result<VoteTally, RouteVoteError> {
+        let plan = self
+            .travel_plans
+            .authorize(plan_id, user_id, PlanRole::Viewer)?
+            .travel_plan;
+        let votes = self.repos.find_route_votes_by_plan(plan_id)?;
+
+        let mut routes: Vec<RouteTally> = self
+            .repos
+            .find_route_options_by_plan(plan_id)?
+            .into_iter()
+            .map(|route| {
+                let route_votes = votes.iter().filter(|v| v.route_option_id == route.id);
+                let up = route_votes
+                    .clone()
+                    .filter(|v| v.direction == VoteDirection::Up)
+                    .count();
+                let down = route_votes
+                    .clone()
+                    .filter(|v| v.direction == VoteDirection::Down)
+                    .count();
+                let your_vote = route_votes
+                    .clone()
+                    .find(|v| v.user_id == user_id)
+                    .map(|v| v.direction);
+
+                RouteTally {
+                    selected: plan.selected_route_id.as_ref() == Some(&route.id),
+                    route_id: route.id,
+                    name: route.name,
+                    up,
+                    down,
+                    score: up as i64 - down as i64,
+                    your_vote,
+                }
+            })
+            .collect();
+        routes.sort_by_key(|tally| std::cmp::Reverse(tally.score));
+
+        Ok(VoteTally {
+            selected_route_id: plan.selected_route_id,
+            routes,
+        })
+    }
+
+    /// Locks in a route option, or clears the choice with `None`. Owner only.
+    pub fn select_route(
+        &self,
+        plan_id: &str,
+        user_id: &str,
+        route_id: Option<&str>,
+    ) -> Result<TravelPlanDto, RouteVoteError> {
+        let mut plan_dto = self.travel_plans.authorize(plan_id, user_id, PlanRole::Owner)?;
+
+        if let Some(route_id) = route_id {
+            self.route_in_plan(plan_id, route_id)?;
+        }
+
+        let now = Utc::now();
+        if !self.repos.set_selected_route(plan_id, route_id, now)? {
+            return Err(TravelPlanError::NotFound.into());
+        }
+
+        info!(
+            "User {} set the selected route of travel plan {} to {:?}",
+            user_id, plan_id, route_id
+        );
+        self.travel_plans.record_event(
+            plan_id,
+            user_id,
+            PlanEventAction::RouteSelected,
+            plan_event::diff(
+                &json!({ "selectedRouteId": plan_dto.travel_plan.selected_route_id }),
+                &json!({ "selectedRouteId": route_id }),
+            ),
+        )?;
+        plan_dto.travel_plan.selected_route_id = route_id.map(str::to_string);
+        plan_dto.travel_plan.updated_at = now;
+        Ok(plan_dto)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::repositories::memory::InMemoryRepository;
+    use crate::services::route_option_service::{RouteOptionError, RouteOptionService};
+    use crate::services::test_fixtures::{add_member, create_plan, road_trip};
+
+    struct Fixture {
+        repos: Arc<InMemoryRepository>,
+        votes: RouteVoteService<InMemoryRepository>,
+        routes: RouteOptionService<InMemoryRepository>,
+        plan_id: String,
+        route_ids: Vec<String>,
+    }
+
+    fn setup() -> Fixture {
+        let repos = Arc::new(InMemoryRepository::new());
+        let plan_id = create_plan(&repos, &road_trip(), "alice");
+        add_member(&repos, &plan_id, "bob", PlanRole::Editor, "alice");
+
+        let routes = RouteOptionService::new(repos.clone());
+        let route_ids = routes
+            .generate_route_options(&plan_id, "alice", 3)
+            .unwrap()
+            .into_iter()
+            .map(|r| r.route.id)
+            .collect();
+
+        Fixture {
+            votes: RouteVoteService::new(repos.clone()),
+            repos,
+            routes,
+            plan_id,
+            route_ids,
+        }
+    }
+
+    #[test]
+    fn tallies_one_vote_per_member() {
+        let f = setup();
+        let (first, second) = (&f.route_ids[0], &f.route_ids[1]);
+
+        f.votes.cast_vote(&f.plan_id, second, "alice", VoteDirection::Up).unwrap();
+        f.votes.cast_vote(&f.plan_id, second, "bob", VoteDirection::Down).unwrap();
+        // Changing a vote replaces it
+        f.votes.cast_vote(&f.plan_id, second, "bob", VoteDirection::Up).unwrap();
+        f.votes.cast_vote(&f.plan_id, first, "bob", VoteDirection::Down).unwrap();
+
+        let tally = f.votes.tally(&f.plan_id, "bob").unwrap();
+        assert_eq!(tally.routes[0].route_id, *second);
+        assert_eq!((tally.routes[0].up, tally.routes[0].down), (2, 0));
+        assert_eq!(tally.routes[0].your_vote, Some(VoteDirection::Up));
+        assert_eq!(tally.routes[1].score, 0);
+        assert_eq!(tally.routes[2].route_id, *first);
+        assert_eq!(tally.routes[2].score, -1);
+
+        f.votes.retract_vote(&f.plan_id, first, "bob").unwrap();
+        assert!(matches!(
+            f.votes.retract_vote(&f.plan_id, first, "bob"),
+            Err(RouteVoteError::VoteNotFound)
+        ));
+        assert!(matches!(
+            f.votes.cast_vote(&f.plan_id, first, "mallory", VoteDirection::Up),
+            Err(RouteVoteError::TravelPlanError(TravelPlanError::Unauthorized))
+        ));
+    }
+
+    #[test]
+    fn selected_route_survives_bulk_delete() {
+        let f = setup();
+        let chosen = &f.route_ids[1];
+
+        assert!(matches!(
+            f.votes.select_route(&f.plan_id, "bob", Some(chosen)),
+            Err(RouteVoteError::TravelPlanError(TravelPlanError::Unauthorized))
+        ));
+        assert!(matches!(
+            f.votes.select_route(&f.plan_id, "alice", Some("elsewhere")),
+            Err(RouteVoteError::RouteNotFound)
+        ));
+
+        let plan = f.votes.select_route(&f.plan_id, "alice", Some(chosen)).unwrap();
+        assert_eq!(plan.travel_plan.selected_route_id.as_deref(), Some(chosen.as_str()));
+
+        assert!(matches!(
+            f.routes.delete_route_option(&f.plan_id, chosen, "bob", None),
+            Err(RouteOptionError::RouteLocked)
+        ));
+        assert_eq!(f.routes.delete_all_route_options(&f.plan_id, "bob", None).unwrap(), 2);
+        // The activity feed lists only the routes that went
+        let cleared = &f.repos.find_plan_events(&f.plan_id, 1, 0).unwrap()[0];
+        assert_eq!(cleared.action, PlanEventAction::RoutesCleared);
+        assert_eq!(
+            cleared.changes,
+            json!({ "routes": { "from": [&f.route_ids[0], &f.route_ids[2]], "to": [] } })
+        );
+
+        let remaining = f.routes.get_route_options(&f.plan_id, "bob").unwrap();
+        assert_eq!(remaining.len(), 1);
+        assert_eq!(remaining[0].route.id, *chosen);
+
+        // Once unselected it can go like any other
+        f.votes.select_route(&f.plan_id, "alice", None).unwrap();
+        assert!(f.routes.delete_route_option(&f.plan_id, chosen, "bob", None).unwrap());
+    }
+}
@@ -0,0 +1,389 @@
+use chrono::{DateTime, Utc};
+use log::{error, info};
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use std::sync::Arc;
+use utoipa::ToSchema;
+
+use crate::models::plan_event::{self, PlanEventAction};
+use crate::models::plan_member::PlanRole;
+use crate::models::point_of_interest::PointOfInterest;
+use crate::models::route_option::RouteOption;
+use crate::models::share_link::{NewShareLink, ShareLink};
+use crate::models::travel_plan::TravelPlan;
+use crate::repositories::{
+    BudgetRepository, CommentRepository, ItineraryRepository, PlanEventRepository,
+    PlanLegRepository, PlanMemberRepository, PlanRevisionRepository, PoiRepository, RepositoryError,
+    RouteOptionRepository, RouteVoteRepository, ShareLinkRepository, TravelPlanRepository,
+};
+use crate::services::blocking::BlockingError;
+use crate::services::travel_plan_service::{TravelPlanError, TravelPlanService};
+
+/// Owner-managed public links, and the anonymous read-only view behind them.
+pub struct ShareLinkService<R: ?Sized> {
+    repos: Arc<R>,
+    travel_plans: TravelPlanService<R>,
+}
+
+#[derive(Debug)]
+pub enum ShareLinkError {
+    TravelPlanError(TravelPlanError),
+    LinkNotFound,
+    /// The link was revoked or is past its expiry.
+    LinkExpired,
+    InvalidInput(String),
+    DatabaseError(String),
+}
+
+impl From<TravelPlanError> for ShareLinkError {
+    fn from(error: TravelPlanError) -> Self {
+        ShareLinkError::TravelPlanError(error)
+    }
+}
+
+impl From<BlockingError> for ShareLinkError {
+    fn from(error: BlockingError) -> Self {
+        ShareLinkError::DatabaseError(error.to_string())
+    }
+}
+
+impl From<RepositoryError> for ShareLinkError {
+    fn from(error: RepositoryError) -> Self {
+        error!("Share link repository error: {}", error);
+        ShareLinkError::DatabaseError(error.to_string())
+    }
+}
+
+/// A plan as seen through a public link. Leaves out ids, the owner and
+/// anything else an anonymous reader has no use for.
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct SharedTravelPlanDto {
+    pub name: String,
+    pub description: Option<String>,
+    pub start_location: String,
+    pub end_location: String,
+    pub start_date: Option<DateTime<Utc>>,
+    pub end_date: Option<DateTime<Utc>>,
+    pub updated_at: DateTime<Utc>,
+    /// Present when the link was created with routes.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub routes: Option<Vec<SharedRouteOption>>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct SharedRouteOption {
+    pub name: String,
+    pub description: Option<String>,
+    pub distance: Option<f64>,
+    pub duration: Option<i64>,
+    pub start_coordinates: String,
+    pub end_coordinates: String,
+    pub waypoints: Option<String>,
+    /// Present when the link was created with points of interest.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub points_of_interest: Option<Vec<SharedPointOfInterest>>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct SharedPointOfInterest {
+    pub name: String,
+    pub description: Option<String>,
+    pub category: Option<String>,
+    pub coordinates: String,
+}
+
+impl From<TravelPlan> for SharedTravelPlanDto {
+    fn from(plan: TravelPlan) -> Self {
+        SharedTravelPlanDto {
+            name: plan.name,
+            description: plan.description,
+            start_location: plan.start_location,
+            end_location: plan.end_location,
+            start_date: plan.start_date,
+            end_date: plan.end_date,
+            updated_at: plan.updated_at,
+            routes: None,
+        }
+    }
+}
+
+impl From<RouteOption> for SharedRouteOption {
+    fn from(route: RouteOption) -> Self {
+        SharedRouteOption {
+            name: route.name,
+            description: route.description,
+            distance: route.distance,
+            duration: route.duration,
+            start_coordinates: route.start_coordinates,
+            end_coordinates: route.end_coordinates,
+            waypoints: route.waypoints,
+            points_of_interest: None,
+        }
+    }
+}
+
+impl From<PointOfInterest> for SharedPointOfInterest {
+    fn from(poi: PointOfInterest) -> Self {
+        SharedPointOfInterest {
+            name: poi.name,
+            description: poi.description,
+            category: poi.category,
+            coordinates: poi.coordinates,
+        }
+    }
+}
+
+/// How a share link shows up in the plan's activity feed; never the token.
+fn link_snapshot(link: &ShareLink) -> Value {
+    json!({
+        "shareLink": {
+            "id": link.id,
+            "expiresAt": link.expires_at,
+            "includeRoutes": link.include_routes,
+            "includePois": link.include_pois,
+        }
+    })
+}
+
+impl<R> ShareLinkService<R>
+where
+    R: TravelPlanRepository
+        + PlanMemberRepository
+        + ShareLinkRepository
+        + RouteOptionRepository
+        + RouteVoteRepository
+        + CommentRepository
+        + PlanEventRepository
+        + PlanRevisionRepository
+        + ItineraryRepository
+        + PlanLegRepository
+        + BudgetRepository
+        + PoiRepository
+        + ?Sized,
+{
+    pub fn new(repos: Arc<R>) -> Self {
+        ShareLinkService {
+            travel_plans: TravelPlanService::new(repos.clone()),
+            repos,
+        }
+    }
+
+    pub fn create_share_link(
+        &self,
+        plan_id: &str,
+        user_id: &str,
+        new_link: &NewShareLink,
+    ) -> Result<ShareLink, ShareLinkError> {
+        self.travel_plans.authorize(plan_id, user_id, PlanRole::Owner)?;
+
+        if new_link.expires_at.is_some_and(|expires_at| expires_at <= Utc::now()) {
+            return Err(ShareLinkError::InvalidInput(
+                "expiresAt must be in the future".to_string(),
+            ));
+        }
+
+        let link = ShareLink::new(new_link, plan_id, user_id);
+        self.repos.insert_share_link(&link)?;
+
+        info!("User {} created share link {} for travel plan {}", user_id, link.id, plan_id);
+        self.travel_plans.record_event(
+            plan_id,
+            user_id,
+            PlanEventAction::ShareLinkCreated,
+            plan_event::diff(&Value::Null, &link_snapshot(&link)),
+        )?;
+        Ok(link)
+    }
+
+    /// Every link of a plan, including revoked and expired ones.
+    pub fn list_share_links(
+        &self,
+        plan_id: &str,
+        user_id: &str,
+    ) -> Result<Vec<ShareLink>, ShareLinkError> {
+        self.travel_plans.authorize(plan_id, user_id, PlanRole::Owner)?;
+
+        Ok(self.repos.find_share_links_by_plan(plan_id)?)
+    }
+
+    /// Revoking an already revoked link is a no-op.
+    pub fn revoke_share_link(
+        &self,
+        plan_id: &str,
+        user_id: &str,
+        link_id: &str,
+    ) -> Result<(), ShareLinkError> {
+        self.travel_plans.authorize(plan_id, user_id, PlanRole::Owner)?;
+
+        match self.repos.find_share_link(link_id)? {
+            Some(link) if link.travel_plan_id == plan_id => {
+                if self.repos.revoke_share_link(link_id, Utc::now())? {
+                    info!("User {} revoked share link {}", user_id, link_id);
+                    self.travel_plans.record_event(
+                        plan_id,
+                        user_id,
+                        PlanEventAction::ShareLinkRevoked,
+                        plan_event::diff(&link_snapshot(&link), &Value::Null),
+                    )?;
+                }
+                Ok(())
+            }
+            _ => Err(ShareLinkError::LinkNotFound),
+        }
+    }
+
+    /// Resolves a public token and counts the visit.
+    pub fn view_shared_plan(&self, token: &str) -> Result<SharedTravelPlanDto, ShareLinkError> {
+        let link = self
+            .repos
+            .find_share_link_by_token(token)?
+            .ok_or(ShareLinkError::LinkNotFound)?;
+
+        let now = Utc::now();
+        if !link.is_active(now) {
+            info!("Rejected inactive share link {}", link.id);
+            return Err(ShareLinkError::LinkExpired);
+        }
+
+        let plan = self
+            .repos
+            .find_travel_plan(&link.travel_plan_id)?
+            .ok_or(ShareLinkError::LinkNotFound)?;
+
+        let routes = if link.include_routes {
+            let mut routes = Vec::new();
+            for route in self.repos.find_route_options_by_plan(&plan.id)? {
+                let points_of_interest = if link.include_pois {
+                    let pois = self.repos.find_pois_by_route(&route.id)?;
+                    Some(pois.into_iter().map(SharedPointOfInterest::from).collect())
+                } else {
+                    None
+                };
+                routes.push(SharedRouteOption {
+                    points_of_interest,
+                    ..route.into()
+                });
+            }
+            Some(routes)
+        } else {
+            None
+        };
+
+        self.repos.record_share_link_access(&link.id, now)?;
+
+        Ok(SharedTravelPlanDto {
+            routes,
+            ..plan.into()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::travel_plan::NewTravelPlan;
+    use crate::repositories::memory::InMemoryRepository;
+    use crate::services::route_option_service::RouteOptionService;
+    use chrono::Duration;
+
+    fn setup() -> (Arc<InMemoryRepository>, ShareLinkService<InMemoryRepository>, String) {
+        let repos = Arc::new(InMemoryRepository::new());
+        let plan_id = TravelPlanService::new(repos.clone())
+            .create_travel_plan(
+                &NewTravelPlan {
+                    name: "Road trip".to_string(),
+                    description: None,
+                    start_location: "Amsterdam".to_string(),
+                    end_location: "Berlin".to_string(),
+                    start_date: None,
+                    end_date: None,
+                    home_currency: None,
+                },
+                "alice",
+            )
+            .unwrap()
+            .travel_plan
+            .id;
+        RouteOptionService::new(repos.clone())
+            .generate_route_options(&plan_id, "alice", 2)
+            .unwrap();
+
+        (repos.clone(), ShareLinkService::new(repos), plan_id)
+    }
+
+    fn link(include_routes: bool, include_pois: bool) -> NewShareLink {
+        NewShareLink {
+            expires_at: None,
+            include_routes,
+            include_pois,
+        }
+    }
+
+    #[test]
+    fn shares_only_what_the_link_includes() {
+        let (repos, service, plan_id) = setup();
+
+        let plain = service.create_share_link(&plan_id, "alice", &link(false, false)).unwrap();
+        let full = service.create_share_link(&plan_id, "alice", &link(true, true)).unwrap();
+        assert_ne!(plain.token, full.token);
+
+        assert!(service.view_shared_plan(&plain.token).unwrap().routes.is_none());
+
+        let shared = service.view_shared_plan(&full.token).unwrap();
+        let routes = shared.routes.unwrap();
+        assert_eq!(routes.len(), 2);
+        assert!(routes[0].points_of_interest.is_some());
+
+        service.view_shared_plan(&full.token).unwrap();
+        let counted = repos.find_share_link(&full.id).unwrap().unwrap();
+        assert_eq!(counted.access_count, 2);
+        assert!(counted.last_accessed_at.is_some());
+    }
+
+    #[test]
+    fn revoked_and_expired_links_stop_working() {
+        let (repos, service, plan_id) = setup();
+
+        let revoked = service.create_share_link(&plan_id, "alice", &link(true, false)).unwrap();
+        service.revoke_share_link(&plan_id, "alice", &revoked.id).unwrap();
+        assert!(matches!(
+            service.view_shared_plan(&revoked.token),
+            Err(ShareLinkError::LinkExpired)
+        ));
+
+        let mut expired = ShareLink::new(&link(true, false), &plan_id, "alice");
+        expired.expires_at = Some(Utc::now() - Duration::minutes(1));
+        repos.insert_share_link(&expired).unwrap();
+        assert!(matches!(
+            service.view_shared_plan(&expired.token),
+            Err(ShareLinkError::LinkExpired)
+        ));
+
+        assert!(matches!(
+            service.view_shared_plan("not-a-token"),
+            Err(ShareLinkError::LinkNotFound)
+        ));
+    }
+
+    #[test]
+    fn only_owners_manage_links() {
+        let (_, service, plan_id) = setup();
+
+        assert!(matches!(
+            service.create_share_link(&plan_id, "mallory", &link(true, false)),
+            Err(ShareLinkError::TravelPlanError(TravelPlanError::Unauthorized))
+        ));
+
+        let past = NewShareLink {
+            expires_at: Some(Utc::now() - Duration::hours(1)),
+            ..link(true, false)
+        };
+        assert!(matches!(
+            service.create_share_link(&plan_id, "alice", &past),
+            Err(ShareLinkError::InvalidInput(_))
+        ));
+    }
+}
@@ -0,0 +1,63 @@
+//! Scaffolding shared by the service unit tests, which all run against the
+//! in-memory repository.
+
+use std::sync::Arc;
+
+use crate::models::plan_member::{MemberStatus, PlanMember, PlanRole};
+use crate::models::travel_plan::NewTravelPlan;
+use crate::models::user::{NewUser, User};
+use crate::repositories::memory::InMemoryRepository;
+use crate::repositories::{PlanMemberRepository, UserRepository};
+use crate::services::travel_plan_service::TravelPlanService;
+
+/// Stores an account called `username` with the password "password123".
+pub fn user(repos: &InMemoryRepository, username: &str) -> User {
+    let user = User::new(&NewUser {
+        username: username.to_string(),
+        password: "password123".to_string(),
+        email: format!("{}@example.com", username),
+    })
+    .unwrap();
+    repos.insert_user(&user).unwrap();
+    user
+}
+
+/// Amsterdam to Berlin, without dates.
+pub fn road_trip() -> NewTravelPlan {
+    NewTravelPlan {
+        name: "Road trip".to_string(),
+        description: None,
+        start_location: "Amsterdam".to_string(),
+        end_location: "Berlin".to_string(),
+        start_date: None,
+        end_date: None,
+        home_currency: None,
+    }
+}
+
+/// Creates `new_plan` owned by `owner_id` and returns its ID.
+pub fn create_plan(
+    repos: &Arc<InMemoryRepository>,
+    new_plan: &NewTravelPlan,
+    owner_id: &str,
+) -> String {
+    TravelPlanService::new(repos.clone())
+        .create_travel_plan(new_plan, owner_id)
+        .unwrap()
+        .travel_plan
+        .id
+}
+
+/// Adds `user_id` to the plan as if they had accepted `invited_by`'s
+/// invitation.
+pub fn add_member(
+    repos: &InMemoryRepository,
+    plan_id: &str,
+    user_id: &str,
+    role: PlanRole,
+    invited_by: &str,
+) {
+    let mut member = PlanMember::invite(plan_id, user_id, role, invited_by);
+    member.status = MemberStatus::Accepted;
+    repos.insert_plan_member(&member).unwrap();
+}
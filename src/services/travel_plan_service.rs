@@ -1,93 +1,198 @@
 use log::{error, info};
-use rusqlite::Connection;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
 use utoipa::ToSchema;
 
-use crate::models::route_option::RouteOption;
-use crate::models::travel_plan::{NewTravelPlan, TravelPlan, UpdateTravelPlan};
+use crate::models::audit::{Actor, AuditEntry};
+use crate::models::plan_event::{self, PlanEvent, PlanEventAction};
+use crate::models::plan_member::{MemberStatus, PlanRole};
+use crate::models::plan_revision::PlanRevision;
+use crate::models::travel_plan::{NewTravelPlan, PatchTravelPlan, TravelPlan, UpdateTravelPlan};
+use crate::repositories::{
+    AuditRepository, BudgetRepository, CommentRepository, ItineraryRepository, PlanEventRepository,
+    PlanLegRepository, PlanMemberRepository, PlanRevisionRepository, RouteOptionRepository,
+    RouteVoteRepository, ShareLinkRepository, TravelPlanRepository,
+};
+use crate::services::blocking::BlockingError;
+use crate::services::precondition;
 
-pub struct TravelPlanService;
+pub struct TravelPlanService<R: ?Sized> {
+    repos: Arc<R>,
+}
 
-#[derive(Debug, Serialize, ToSchema)]
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct TravelPlanDto {
     #[serde(flatten)]
     pub travel_plan: TravelPlan,
     pub has_routes_generated: bool,
+    /// The caller's access to the plan.
+    pub role: PlanRole,
 }
 
 #[derive(Debug)]
 pub enum TravelPlanError {
     NotFound,
     Unauthorized,
+    /// The caller's `If-Match` no longer names the current state.
+    PreconditionFailed,
     DatabaseError(String),
 }
 
-impl TravelPlanService {
-    pub fn get_travel_plans(
-        conn: &Connection,
+impl TravelPlanDto {
+    /// Changes with every update, and with anything else that shows in the
+    /// caller's view of the plan.
+    pub fn etag(&self) -> String {
+        let variant = format!("{}{}", self.role, if self.has_routes_generated { "-r" } else { "" });
+        precondition::etag(self.travel_plan.updated_at, &variant)
+    }
+}
+
+/// Fails unless an `If-Match` header, when given, names `etag`.
+pub fn check_if_match(if_match: Option<&str>, etag: &str) -> Result<(), TravelPlanError> {
+    match if_match {
+        Some(header) if !precondition::if_match(header, etag) => {
+            info!("If-Match {} does not match current ETag {}", header, etag);
+            Err(TravelPlanError::PreconditionFailed)
+        }
+        _ => Ok(()),
+    }
+}
+
+impl From<BlockingError> for TravelPlanError {
+    fn from(error: BlockingError) -> Self {
+        TravelPlanError::DatabaseError(error.to_string())
+    }
+}
+
+impl<R> TravelPlanService<R>
+where
+    R: TravelPlanRepository
+        + PlanMemberRepository
+        + ShareLinkRepository
+        + RouteOptionRepository
+        + RouteVoteRepository
+        + CommentRepository
+        + PlanEventRepository
+        + PlanRevisionRepository
+        + ItineraryRepository
+        + PlanLegRepository
+        + BudgetRepository
+        + ?Sized,
+{
+    pub fn new(repos: Arc<R>) -> Self {
+        TravelPlanService { repos }
+    }
+
+    /// Appends an entry to the plan's activity feed.
+    pub fn record_event(
+        &self,
+        plan_id: &str,
         user_id: &str,
-    ) -> Result<Vec<TravelPlanDto>, TravelPlanError> {
+        action: PlanEventAction,
+        changes: serde_json::Value,
+    ) -> Result<(), TravelPlanError> {
+        let event = PlanEvent::new(plan_id, user_id, action, changes);
+        self.repos.insert_plan_event(&event).map_err(|e| {
+            error!("Error recording plan event: {}", e);
+            TravelPlanError::DatabaseError(e.to_string())
+        })
+    }
+
+    fn has_routes(&self, plan_id: &str) -> bool {
+        match self.repos.find_route_options_by_plan(plan_id) {
+            Ok(routes) => !routes.is_empty(),
+            Err(e) => {
+                error!("Error checking for route options: {}", e);
+                false
+            }
+        }
+    }
+
+    /// The caller's own plans plus the plans shared with them, newest first.
+    pub fn get_travel_plans(&self, user_id: &str) -> Result<Vec<TravelPlanDto>, TravelPlanError> {
         info!("Fetching travel plans for user: {}", user_id);
 
-        match TravelPlan::find_by_user_id(conn, user_id) {
-            Ok(plans) => {
-                info!("Found {} travel plans for user {}", plans.len(), user_id);
+        let owned = self.repos.find_travel_plans_by_user(user_id);
+        let shared = self.repos.find_shared_travel_plans(user_id);
+        let memberships = self.repos.find_memberships_by_user(user_id);
 
-                let mut plan_dtos = Vec::new();
+        match (owned, shared, memberships) {
+            (Ok(owned), Ok(shared), Ok(memberships)) => {
+                info!(
+                    "Found {} own and {} shared travel plans for user {}",
+                    owned.len(),
+                    shared.len(),
+                    user_id
+                );
 
-                for plan in plans {
-                    let has_routes = match RouteOption::find_by_travel_plan_id(conn, &plan.id) {
-                        Ok(routes) => !routes.is_empty(),
-                        Err(e) => {
-                            error!("Error checking for route options: {}", e);
-                            false
-                        }
-                    };
+                let shared_with_roles = shared.into_iter().filter_map(|plan| {
+                    memberships
+                        .iter()
+                        .find(|m| m.travel_plan_id == plan.id)
+                        .map(|m| (plan, m.role))
+                });
 
-                    plan_dtos.push(TravelPlanDto {
+                let mut plan_dtos: Vec<TravelPlanDto> = owned
+                    .into_iter()
+                    .map(|plan| (plan, PlanRole::Owner))
+                    .chain(shared_with_roles)
+                    .map(|(plan, role)| TravelPlanDto {
+                        has_routes_generated: self.has_routes(&plan.id),
                         travel_plan: plan,
-                        has_routes_generated: has_routes,
-                    });
-                }
+                        role,
+                    })
+                    .collect();
+                plan_dtos.sort_by_key(|dto| std::cmp::Reverse(dto.travel_plan.created_at));
 
                 Ok(plan_dtos)
             }
-            Err(e) => {
+            (Err(e), _, _) | (_, Err(e), _) | (_, _, Err(e)) => {
                 error!("Error fetching travel plans: {}", e);
                 Err(TravelPlanError::DatabaseError(e.to_string()))
             }
         }
     }
 
-    pub fn get_travel_plan_by_id(
-        conn: &Connection,
+    /// The access `user_id` has to `plan`: owner for its creator, otherwise
+    /// the role of an accepted membership.
+    fn plan_role(&self, plan: &TravelPlan, user_id: &str) -> Result<Option<PlanRole>, TravelPlanError> {
+        if plan.user_id == user_id {
+            return Ok(Some(PlanRole::Owner));
+        }
+
+        match self.repos.find_plan_member(&plan.id, user_id) {
+            Ok(Some(member)) if member.status == MemberStatus::Accepted => Ok(Some(member.role)),
+            Ok(_) => Ok(None),
+            Err(e) => {
+                error!("Error fetching plan membership: {}", e);
+                Err(TravelPlanError::DatabaseError(e.to_string()))
+            }
+        }
+    }
+
+    /// Loads a plan if `user_id` holds at least the `required` role on it.
+    pub fn authorize(
+        &self,
         plan_id: &str,
         user_id: &str,
+        required: PlanRole,
     ) -> Result<TravelPlanDto, TravelPlanError> {
-        info!(
-            "Fetching travel plan with ID: {} for user: {}",
-            plan_id, user_id
-        );
-
-        match TravelPlan::find_by_id(conn, plan_id) {
+        match self.repos.find_travel_plan(plan_id) {
             Ok(Some(plan)) => {
-                if plan.user_id != user_id {
-                    info!(
-                        "User {} attempted to access travel plan {} belonging to user {}",
-                        user_id, plan_id, plan.user_id
-                    );
-                    return Err(TravelPlanError::Unauthorized);
-                }
-
-                let has_routes = match RouteOption::find_by_travel_plan_id(conn, plan_id) {
-                    Ok(routes) => !routes.is_empty(),
-                    Err(e) => {
-                        error!("Error checking for route options: {}", e);
-                        false
+                let role = match self.plan_role(&plan, user_id)? {
+                    Some(role) if role >= required => role,
+                    role => {
+                        info!(
+                            "User {} with role {:?} attempted {} access to travel plan {} belonging to user {}",
+                            user_id, role, required, plan_id, plan.user_id
+                        );
+                        return Err(TravelPlanError::Unauthorized);
                     }
                 };
 
+                let has_routes = self.has_routes(plan_id);
+
                 info!(
                     "Found travel plan: {} (has routes: {})",
                     plan.name, has_routes
@@ -96,6 +201,7 @@ impl TravelPlanService {
                 Ok(TravelPlanDto {
                     travel_plan: plan,
                     has_routes_generated: has_routes,
+                    role,
                 })
             }
             Ok(None) => {
@@ -109,19 +215,41 @@ impl TravelPlanService {
         }
     }
 
+    pub fn get_travel_plan_by_id(
+        &self,
+        plan_id: &str,
+        user_id: &str,
+    ) -> Result<TravelPlanDto, TravelPlanError> {
+        info!(
+            "Fetching travel plan with ID: {} for user: {}",
+            plan_id, user_id
+        );
+
+        self.authorize(plan_id, user_id, PlanRole::Viewer)
+    }
+
     pub fn create_travel_plan(
-        conn: &Connection,
+        &self,
         plan_data: &NewTravelPlan,
         user_id: &str,
     ) -> Result<TravelPlanDto, TravelPlanError> {
         info!("Creating new travel plan for user: {}", user_id);
 
-        match TravelPlan::create(conn, plan_data, user_id) {
-            Ok(plan) => {
+        let plan = TravelPlan::new(plan_data, user_id);
+
+        match self.repos.insert_travel_plan(&plan) {
+            Ok(()) => {
                 info!("Created new travel plan: {}", plan.name);
+                self.record_event(
+                    &plan.id,
+                    user_id,
+                    PlanEventAction::PlanCreated,
+                    plan_event::diff(&serde_json::Value::Null, &plan_event::plan_snapshot(&plan)),
+                )?;
                 Ok(TravelPlanDto {
                     travel_plan: plan,
                     has_routes_generated: false,
+                    role: PlanRole::Owner,
                 })
             }
             Err(e) => {
@@ -132,50 +260,125 @@ impl TravelPlanService {
     }
 
     pub fn update_travel_plan(
-        conn: &Connection,
+        &self,
         plan_id: &str,
         update_data: &UpdateTravelPlan,
         user_id: &str,
+        if_match: Option<&str>,
     ) -> Result<TravelPlanDto, TravelPlanError> {
         info!(
             "Updating travel plan with ID: {} for user: {}",
             plan_id, user_id
         );
 
-        let plan_dto = Self::get_travel_plan_by_id(conn, plan_id, user_id)?;
+        let plan_dto = self.authorize(plan_id, user_id, PlanRole::Editor)?;
+        check_if_match(if_match, &plan_dto.etag())?;
+        let updated_plan = plan_dto.travel_plan.with_update(update_data);
 
-        match plan_dto.travel_plan.update(conn, update_data) {
-            Ok(updated_plan) => {
-                info!("Updated travel plan: {}", updated_plan.name);
+        self.save_travel_plan(plan_dto, updated_plan, user_id, PlanEventAction::PlanUpdated)
+    }
 
-                Ok(TravelPlanDto {
-                    travel_plan: updated_plan,
-                    has_routes_generated: plan_dto.has_routes_generated,
-                })
+    /// Applies a JSON Merge Patch; unlike an update it can clear the
+    /// description and dates.
+    pub fn patch_travel_plan(
+        &self,
+        plan_id: &str,
+        patch: &PatchTravelPlan,
+        user_id: &str,
+        if_match: Option<&str>,
+    ) -> Result<TravelPlanDto, TravelPlanError> {
+        info!(
+            "Patching travel plan with ID: {} for user: {}",
+            plan_id, user_id
+        );
+
+        let plan_dto = self.authorize(plan_id, user_id, PlanRole::Editor)?;
+        check_if_match(if_match, &plan_dto.etag())?;
+        let patched_plan = plan_dto.travel_plan.with_patch(patch);
+
+        self.save_travel_plan(plan_dto, patched_plan, user_id, PlanEventAction::PlanUpdated)
+    }
+
+    /// Stores `updated_plan` over the plan `current` was read from, unless the
+    /// plan has changed since. When a revisioned field changes, the replaced
+    /// state is kept as a new revision and the change is logged as `action`.
+    pub fn save_travel_plan(
+        &self,
+        current: TravelPlanDto,
+        updated_plan: TravelPlan,
+        user_id: &str,
+        action: PlanEventAction,
+    ) -> Result<TravelPlanDto, TravelPlanError> {
+        let plan_id = current.travel_plan.id.as_str();
+        let changes = plan_event::diff(
+            &plan_event::plan_snapshot(&current.travel_plan),
+            &plan_event::plan_snapshot(&updated_plan),
+        );
+        let changed = changes.as_object().is_some_and(|c| !c.is_empty());
+
+        match self.repos.update_travel_plan(&updated_plan, current.travel_plan.updated_at) {
+            Ok(0) => {
+                info!("Travel plan {} changed while being updated", plan_id);
+                return Err(TravelPlanError::PreconditionFailed);
             }
+            Ok(_) => info!("Updated travel plan: {}", updated_plan.name),
             Err(e) => {
                 error!("Error updating travel plan: {}", e);
-                Err(TravelPlanError::DatabaseError(e.to_string()))
+                return Err(TravelPlanError::DatabaseError(e.to_string()));
+            }
+        }
+
+        if changed {
+            let revision = PlanRevision::new(&current.travel_plan, user_id);
+            if let Err(e) = self.repos.insert_plan_revision(&revision) {
+                error!("Error saving travel plan revision: {}", e);
+                return Err(TravelPlanError::DatabaseError(e.to_string()));
             }
+            self.record_event(plan_id, user_id, action, changes)?;
         }
+
+        Ok(TravelPlanDto {
+            travel_plan: updated_plan,
+            has_routes_generated: current.has_routes_generated,
+            role: current.role,
+        })
     }
 
     pub fn delete_travel_plan(
-        conn: &Connection,
+        &self,
         plan_id: &str,
         user_id: &str,
+        if_match: Option<&str>,
     ) -> Result<(), TravelPlanError> {
         info!(
             "Deleting travel plan with ID: {} for user: {}",
             plan_id, user_id
         );
 
-        let _plan = Self::get_travel_plan_by_id(conn, plan_id, user_id)?;
+        let plan = self.authorize(plan_id, user_id, PlanRole::Owner)?;
+        check_if_match(if_match, &plan.etag())?;
 
-        match TravelPlan::delete(conn, plan_id) {
+        // The repository removes everything the plan owns along with it. An
+        // If-Match also has to hold at the moment of deletion.
+        let expected_updated_at = if_match
+            .filter(|header| precondition::pins_state(header))
+            .map(|_| plan.travel_plan.updated_at);
+        match self.repos.delete_travel_plan(plan_id, expected_updated_at) {
             Ok(true) => {
                 info!("Deleted travel plan with ID: {}", plan_id);
-                Ok(())
+                self.record_event(
+                    plan_id,
+                    user_id,
+                    PlanEventAction::PlanDeleted,
+                    plan_event::diff(
+                        &plan_event::plan_snapshot(&plan.travel_plan),
+                        &serde_json::Value::Null,
+                    ),
+                )
+            }
+            Ok(false) if expected_updated_at.is_some() => {
+                info!("Travel plan {} changed while being deleted", plan_id);
+                Err(TravelPlanError::PreconditionFailed)
             }
             Ok(false) => {
                 info!("Travel plan not found with ID: {}", plan_id);
@@ -188,3 +391,204 @@ impl TravelPlanService {
         }
     }
 }
+
+impl<R> TravelPlanService<R>
+where
+    R: TravelPlanRepository
+        + PlanMemberRepository
+        + ShareLinkRepository
+        + RouteOptionRepository
+        + RouteVoteRepository
+        + CommentRepository
+        + PlanEventRepository
+        + PlanRevisionRepository
+        + ItineraryRepository
+        + PlanLegRepository
+        + BudgetRepository
+        + AuditRepository
+        + ?Sized,
+{
+    /// Read-only lookup: owners see their own plans, and support staff and
+    /// admins may see anyone's. Looks at other users' plans are audited.
+    pub fn view_travel_plan(
+        &self,
+        plan_id: &str,
+        viewer: &Actor,
+    ) -> Result<TravelPlanDto, TravelPlanError> {
+        let user_id = viewer.user_id.as_deref().unwrap_or_default();
+
+        match self.get_travel_plan_by_id(plan_id, user_id) {
+            Err(TravelPlanError::Unauthorized) if viewer.role.can_view_any_plan() => {}
+            result => return result,
+        }
+
+        let plan = self
+            .repos
+            .find_travel_plan(plan_id)
+            .map_err(|e| TravelPlanError::DatabaseError(e.to_string()))?
+            .ok_or(TravelPlanError::NotFound)?;
+
+        let entry = AuditEntry::new(
+            viewer,
+            "plan.view",
+            "travel_plan",
+            Some(plan_id),
+            Some(format!("owner {}", plan.user_id)),
+        );
+        if let Err(e) = self.repos.insert_audit_entry(&entry) {
+            error!("Error auditing travel plan view: {}", e);
+            return Err(TravelPlanError::DatabaseError(e.to_string()));
+        }
+
+        info!(
+            "{} {} viewed travel plan {} of user {}",
+            viewer.role, viewer.username, plan_id, plan.user_id
+        );
+
+        Ok(TravelPlanDto {
+            has_routes_generated: self.has_routes(plan_id),
+            travel_plan: plan,
+            role: PlanRole::Viewer,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::route_option::RouteOption;
+    use crate::repositories::memory::InMemoryRepository;
+
+    fn service() -> TravelPlanService<InMemoryRepository> {
+        TravelPlanService::new(Arc::new(InMemoryRepository::new()))
+    }
+
+    fn new_plan(name: &str) -> NewTravelPlan {
+        NewTravelPlan {
+            name: name.to_string(),
+            description: Some("A test travel plan".to_string()),
+            start_location: "New York".to_string(),
+            end_location: "Los Angeles".to_string(),
+            start_date: None,
+            end_date: None,
+            home_currency: None,
+        }
+    }
+
+    #[test]
+    fn creates_and_lists_plans_for_owner_only() {
+        let service = service();
+        service.create_travel_plan(&new_plan("First"), "alice").unwrap();
+        service.create_travel_plan(&new_plan("Second"), "alice").unwrap();
+        service.create_travel_plan(&new_plan("Other"), "bob").unwrap();
+
+        let plans = service.get_travel_plans("alice").unwrap();
+
+        assert_eq!(plans.len(), 2);
+        assert!(plans.iter().all(|p| p.travel_plan.user_id == "alice"));
+        assert!(plans.iter().all(|p| !p.has_routes_generated));
+    }
+
+    #[test]
+    fn rejects_access_by_other_user() {
+        let service = service();
+        let plan = service.create_travel_plan(&new_plan("Private"), "alice").unwrap();
+
+        let result = service.get_travel_plan_by_id(&plan.travel_plan.id, "bob");
+
+        assert!(matches!(result, Err(TravelPlanError::Unauthorized)));
+    }
+
+    #[test]
+    fn returns_not_found_for_unknown_plan() {
+        let service = service();
+
+        let result = service.get_travel_plan_by_id("missing", "alice");
+
+        assert!(matches!(result, Err(TravelPlanError::NotFound)));
+    }
+
+    #[test]
+    fn updates_only_provided_fields() {
+        let service = service();
+        let plan = service.create_travel_plan(&new_plan("Before"), "alice").unwrap();
+        let update = UpdateTravelPlan {
+            name: Some("After".to_string()),
+            description: None,
+            start_location: None,
+            end_location: Some("San Francisco".to_string()),
+            start_date: None,
+            end_date: None,
+            home_currency: None,
+        };
+
+        let updated = service
+            .update_travel_plan(&plan.travel_plan.id, &update, "alice", None)
+            .unwrap();
+        let stored = service
+            .get_travel_plan_by_id(&plan.travel_plan.id, "alice")
+            .unwrap();
+
+        assert_eq!(updated.travel_plan.name, "After");
+        assert_eq!(stored.travel_plan.end_location, "San Francisco");
+        assert_eq!(stored.travel_plan.start_location, "New York");
+        assert_eq!(stored.travel_plan.description, plan.travel_plan.description);
+    }
+
+    #[test]
+    fn refuses_to_save_over_a_newer_version() {
+        let service = service();
+        let plan = service.create_travel_plan(&new_plan("Before"), "alice").unwrap();
+        let plan_id = plan.travel_plan.id.clone();
+        let read_early = service.get_travel_plan_by_id(&plan_id, "alice").unwrap();
+        let rename = |name: &str| UpdateTravelPlan {
+            name: Some(name.to_string()),
+            description: None,
+            start_location: None,
+            end_location: None,
+            start_date: None,
+            end_date: None,
+            home_currency: None,
+        };
+        service.update_travel_plan(&plan_id, &rename("First"), "alice", None).unwrap();
+
+        // A writer that read before the first update lands second
+        let late_update = read_early.travel_plan.with_update(&rename("Second"));
+        let action = PlanEventAction::PlanUpdated;
+        let result = service.save_travel_plan(read_early, late_update, "alice", action);
+
+        assert!(matches!(result, Err(TravelPlanError::PreconditionFailed)));
+        let stored = service.get_travel_plan_by_id(&plan_id, "alice").unwrap();
+        assert_eq!(stored.travel_plan.name, "First");
+        assert_eq!(service.repos.find_plan_revisions(&plan_id).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn reports_generated_routes() {
+        let repos = Arc::new(InMemoryRepository::new());
+        let service = TravelPlanService::new(repos.clone());
+        let plan = service.create_travel_plan(&new_plan("Routes"), "alice").unwrap();
+
+        for route in RouteOption::generate_random_options(&plan.travel_plan, 2) {
+            repos.insert_route_option(&route).unwrap();
+        }
+
+        let fetched = service
+            .get_travel_plan_by_id(&plan.travel_plan.id, "alice")
+            .unwrap();
+        assert!(fetched.has_routes_generated);
+    }
+
+    #[test]
+    fn deletes_plan() {
+        let service = service();
+        let plan = service.create_travel_plan(&new_plan("Doomed"), "alice").unwrap();
+
+        service.delete_travel_plan(&plan.travel_plan.id, "alice", None).unwrap();
+
+        assert!(matches!(
+            service.get_travel_plan_by_id(&plan.travel_plan.id, "alice"),
+            Err(TravelPlanError::NotFound)
+        ));
+    }
+}
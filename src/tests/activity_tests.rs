@@ -0,0 +1,105 @@
+use actix_web::test;
+use serde_json::{Value, json};
+
+use crate::tests::common::{
+    add_member, bearer, create_plan, create_user, generate_routes, init_app, off_runtime,
+    test_repositories,
+};
+
+#[actix_web::test]
+async fn test_plan_activity_feed() {
+    let repos = test_repositories();
+    let app = init_app(repos.clone()).await;
+
+    let owner = create_user(&repos, "owner");
+    let editor = create_user(&repos, "editor");
+    let outsider = create_user(&repos, "outsider");
+    let plan = create_plan(&app, &owner, "Logged trip").await;
+    let plan_id = plan.travel_plan.id;
+    add_member(&app, &owner, &editor, &plan_id, "editor").await;
+
+    let req = test::TestRequest::put()
+        .uri(&format!("/api/travelplan/{}", plan_id))
+        .insert_header(bearer(&editor))
+        .set_json(json!({ "endDate": "2030-06-12T18:00:00Z" }))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status().as_u16(), 200);
+
+    generate_routes(&app, &editor, &plan_id, 2).await;
+    let req = test::TestRequest::delete()
+        .uri(&format!("/api/travelplan/{}/routes", plan_id))
+        .insert_header(bearer(&editor))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status().as_u16(), 200);
+
+    let activity_uri = format!("/api/travelplan/{}/activity", plan_id);
+    let req = test::TestRequest::get()
+        .uri(&activity_uri)
+        .insert_header(bearer(&owner))
+        .to_request();
+    let events: Value = test::call_and_read_body_json(&app, req).await;
+    let actions: Vec<&str> = events
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|e| e["action"].as_str().unwrap())
+        .collect();
+    assert_eq!(
+        actions,
+        [
+            "routes_cleared",
+            "routes_generated",
+            "plan_updated",
+            "member_joined",
+            "member_invited",
+            "plan_created"
+        ]
+    );
+
+    // Who wiped the routes, and which ones
+    assert_eq!(events[0]["actorUsername"], "editor");
+    assert_eq!(events[0]["changes"]["routes"]["from"].as_array().unwrap().len(), 2);
+    assert_eq!(events[0]["changes"]["routes"]["to"], json!([]));
+    assert_eq!(
+        events[2]["changes"],
+        json!({ "endDate": { "from": "2030-06-10T18:00:00Z", "to": "2030-06-12T18:00:00Z" } })
+    );
+    assert_eq!(events[4]["changes"]["member"]["to"]["username"], "editor");
+
+    let req = test::TestRequest::get()
+        .uri(&format!("{}?limit=2&offset=4", activity_uri))
+        .insert_header(bearer(&editor))
+        .to_request();
+    let page: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(page.as_array().unwrap().len(), 2);
+    assert_eq!(page[1]["action"], "plan_created");
+
+    let req = test::TestRequest::get()
+        .uri(&format!("{}?offset={}", activity_uri, u64::MAX))
+        .insert_header(bearer(&editor))
+        .to_request();
+    let page: Value = test::call_and_read_body_json(&app, req).await;
+    assert!(page.as_array().unwrap().is_empty());
+
+    let req = test::TestRequest::get()
+        .uri(&activity_uri)
+        .insert_header(bearer(&outsider))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status().as_u16(), 403);
+
+    // The log outlives the plan
+    let req = test::TestRequest::delete()
+        .uri(&format!("/api/travelplan/{}", plan_id))
+        .insert_header(bearer(&owner))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status().as_u16(), 204);
+
+    let req = test::TestRequest::get()
+        .uri(&activity_uri)
+        .insert_header(bearer(&owner))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status().as_u16(), 404);
+    let remaining = off_runtime(|| repos.find_plan_events(&plan_id, 10, 0)).unwrap();
+    assert_eq!(remaining[0].actor_id, owner.id);
+    assert_eq!(remaining[0].changes["name"]["from"], "Logged trip");
+}
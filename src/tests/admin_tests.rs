@@ -0,0 +1,211 @@
+use actix_web::test;
+use serde_json::{json, Value};
+use uuid::Uuid;
+
+use crate::app::AppState;
+use crate::db::backup::{self, RetentionPolicy};
+use crate::models::user::Role;
+use crate::services::backup_service::BackupConfig;
+use crate::tests::common::{
+    bearer, create_user, create_user_with_role, init_app, init_app_with_state,
+    sqlite_test_repositories, test_repositories,
+};
+
+#[actix_web::test]
+async fn test_admin_endpoints_require_admin_role() {
+    let repos = test_repositories();
+    let app = init_app(repos.clone()).await;
+
+    let user = create_user(&repos, "testuser");
+    let support = create_user_with_role(&repos, "helpdesk", Role::Support);
+
+    for caller in [&user, &support] {
+        for uri in ["/api/admin/users", "/api/admin/audit", "/api/admin/backups"] {
+            let req = test::TestRequest::get()
+                .uri(uri)
+                .insert_header(bearer(caller))
+                .to_request();
+            let resp = test::call_service(&app, req).await;
+            assert_eq!(resp.status().as_u16(), 403, "{} as {}", uri, caller.username);
+        }
+    }
+
+    // Without a token the request is unauthenticated, not forbidden
+    let req = test::TestRequest::get().uri("/api/admin/users").to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status().as_u16(), 401);
+}
+
+#[actix_web::test]
+async fn test_admin_manages_accounts_with_audit_trail() {
+    let repos = test_repositories();
+    let app = init_app(repos.clone()).await;
+
+    let admin = create_user_with_role(&repos, "root", Role::Admin);
+    create_user(&repos, "testuser");
+
+    // List users
+    let req = test::TestRequest::get()
+        .uri("/api/admin/users")
+        .insert_header(bearer(&admin))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status().as_u16(), 200);
+    let users: Vec<Value> = test::read_body_json(resp).await;
+    assert_eq!(users.len(), 2);
+    assert!(users.iter().all(|u| u.get("password_hash").is_none()));
+
+    // Lock an account, which blocks its logins
+    let req = test::TestRequest::post()
+        .uri("/api/admin/users/testuser/lock")
+        .insert_header(bearer(&admin))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status().as_u16(), 200);
+    let locked: Value = test::read_body_json(resp).await;
+    assert_eq!(locked["disabled"], true);
+
+    let req = test::TestRequest::post()
+        .uri("/api/login")
+        .set_json(json!({ "username": "testuser", "password": "password123" }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status().as_u16(), 403);
+
+    // Promote to support
+    let req = test::TestRequest::put()
+        .uri("/api/admin/users/testuser/role")
+        .insert_header(bearer(&admin))
+        .set_json(json!({ "role": "support" }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status().as_u16(), 200);
+    let promoted: Value = test::read_body_json(resp).await;
+    assert_eq!(promoted["role"], "support");
+
+    // Admins cannot lock themselves out, and unknown users are 404
+    let req = test::TestRequest::post()
+        .uri("/api/admin/users/root/lock")
+        .insert_header(bearer(&admin))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status().as_u16(), 400);
+
+    let req = test::TestRequest::post()
+        .uri("/api/admin/users/nobody/unlock")
+        .insert_header(bearer(&admin))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status().as_u16(), 404);
+
+    // Every action above is in the audit log, newest first
+    let req = test::TestRequest::get()
+        .uri("/api/admin/audit")
+        .insert_header(bearer(&admin))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status().as_u16(), 200);
+    let entries: Vec<Value> = test::read_body_json(resp).await;
+    let actions: Vec<&str> = entries.iter().map(|e| e["action"].as_str().unwrap()).collect();
+    assert_eq!(actions, ["user.set_role", "user.lock", "user.list"]);
+    assert!(entries.iter().all(|e| e["actorUsername"] == "root"));
+}
+
+#[actix_web::test]
+async fn test_tokens_follow_account_changes() {
+    let repos = test_repositories();
+    let app = init_app(repos.clone()).await;
+
+    let admin = create_user_with_role(&repos, "root", Role::Admin);
+    let deputy = create_user_with_role(&repos, "deputy", Role::Admin);
+    let member = create_user(&repos, "member");
+
+    // A locked account's token stops working at once
+    let req = test::TestRequest::post()
+        .uri("/api/admin/users/member/lock")
+        .insert_header(bearer(&admin))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status().as_u16(), 200);
+    let req = test::TestRequest::get()
+        .uri("/api/travelplan")
+        .insert_header(bearer(&member))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status().as_u16(), 403);
+
+    // So does a token that claims a role the account no longer has
+    let req = test::TestRequest::put()
+        .uri("/api/admin/users/deputy/role")
+        .insert_header(bearer(&admin))
+        .set_json(json!({ "role": "user" }))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status().as_u16(), 200);
+    for uri in ["/api/admin/users", "/api/travelplan"] {
+        let req = test::TestRequest::get()
+            .uri(uri)
+            .insert_header(bearer(&deputy))
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status().as_u16(), 401, "{}", uri);
+    }
+
+    let req = test::TestRequest::get()
+        .uri("/api/travelplan")
+        .insert_header(bearer(&admin))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status().as_u16(), 200);
+}
+
+#[actix_web::test]
+async fn test_backups_need_configuration() {
+    let repos = test_repositories();
+    let app = init_app(repos.clone()).await;
+    let admin = create_user_with_role(&repos, "root", Role::Admin);
+
+    let req = test::TestRequest::post()
+        .uri("/api/admin/backups")
+        .insert_header(bearer(&admin))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status().as_u16(), 503);
+}
+
+#[actix_web::test]
+async fn test_admin_can_trigger_and_list_backups() {
+    let dir = std::env::temp_dir().join(format!("travel-api-backup-{}", Uuid::new_v4()));
+    // Backups copy the SQLite file; PostgreSQL has tools of its own for that
+    let repos = sqlite_test_repositories();
+    let state = AppState::new(repos.clone()).with_backups(Some(BackupConfig {
+        dir: dir.clone(),
+        interval: None,
+        retention: RetentionPolicy {
+            keep_last: 3,
+            max_age: None,
+        },
+    }));
+    let app = init_app_with_state(state).await;
+    let admin = create_user_with_role(&repos, "root", Role::Admin);
+
+    let req = test::TestRequest::post()
+        .uri("/api/admin/backups")
+        .insert_header(bearer(&admin))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status().as_u16(), 201);
+
+    let created: Value = test::read_body_json(resp).await;
+    let file_name = created["backup"]["fileName"].as_str().unwrap();
+    assert!(created["backup"].get("path").is_none());
+    backup::verify_backup(&dir.join(file_name)).unwrap();
+
+    let req = test::TestRequest::get()
+        .uri("/api/admin/backups")
+        .insert_header(bearer(&admin))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status().as_u16(), 200);
+
+    let listed: Vec<Value> = test::read_body_json(resp).await;
+    assert_eq!(listed.len(), 1);
+    assert_eq!(listed[0]["fileName"], file_name);
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
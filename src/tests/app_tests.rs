@@ -0,0 +1,34 @@
+use std::sync::Arc;
+
+use actix_web::{test, web, App};
+use serde_json::json;
+
+use crate::app::{configure_app, AppState};
+use crate::repositories::memory::InMemoryRepository;
+
+#[actix_web::test]
+async fn test_endpoints_mount_inside_host_scope() {
+    let state = AppState::new(Arc::new(InMemoryRepository::new()));
+
+    // A host service mounting the travel endpoints under its own prefix
+    let app = test::init_service(
+        App::new().service(web::scope("/travel").configure(|cfg| configure_app(cfg, &state))),
+    )
+    .await;
+
+    let req = test::TestRequest::post()
+        .uri("/travel/api/register")
+        .set_json(json!({
+            "username": "embedded",
+            "password": "password123",
+            "email": "embedded@example.com"
+        }))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status().as_u16(), 201);
+
+    let req = test::TestRequest::post()
+        .uri("/travel/api/login")
+        .set_json(json!({ "username": "embedded", "password": "password123" }))
+        .to_request();
+    assert!(test::call_service(&app, req).await.status().is_success());
+}
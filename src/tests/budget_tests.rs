@@ -0,0 +1,330 @@
+use actix_web::http::header;
+use actix_web::test;
+use serde_json::{Value, json};
+
+use crate::app::AppState;
+use crate::services::currency::ExchangeRates;
+use crate::tests::common::{
+    add_member, bearer, create_plan, create_user, init_app, init_app_with_state,
+    test_repositories,
+};
+
+#[actix_web::test]
+async fn test_budget_summary_compares_spending_and_settles_up() {
+    let repos = test_repositories();
+    let app = init_app(repos.clone()).await;
+
+    let owner = create_user(&repos, "owner");
+    let friend = create_user(&repos, "friend");
+    let viewer = create_user(&repos, "viewer");
+    let outsider = create_user(&repos, "outsider");
+    let plan = create_plan(&app, &owner, "Shared costs").await;
+    let plan_id = plan.travel_plan.id.clone();
+    add_member(&app, &owner, &friend, &plan_id, "editor").await;
+    add_member(&app, &owner, &viewer, &plan_id, "viewer").await;
+    let expenses_uri = format!("/api/travelplan/{}/expenses", plan_id);
+
+    let req = test::TestRequest::put()
+        .uri(&format!("/api/travelplan/{}/budgets/food", plan_id))
+        .insert_header(bearer(&owner))
+        .set_json(json!({ "amount": 30000, "currency": "eur" }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status().as_u16(), 201);
+    let budget: Value = test::read_body_json(resp).await;
+    assert_eq!(budget["currency"], "EUR");
+
+    let req = test::TestRequest::put()
+        .uri(&format!("/api/travelplan/{}/budgets/food", plan_id))
+        .insert_header(bearer(&owner))
+        .set_json(json!({ "amount": 12000, "currency": "EUR" }))
+        .to_request();
+    // Replaces the earlier budget
+    assert_eq!(test::call_service(&app, req).await.status().as_u16(), 200);
+
+    let req = test::TestRequest::post()
+        .uri(&expenses_uri)
+        .insert_header(bearer(&owner))
+        .set_json(json!({
+            "category": "food",
+            "description": "Dinner",
+            "amount": 9000,
+            "currency": "EUR",
+            "splitBetween": [owner.id, outsider.id],
+            "spentOn": "2030-06-01"
+        }))
+        .to_request();
+    // Only members can share a cost
+    assert_eq!(test::call_service(&app, req).await.status().as_u16(), 400);
+
+    // The owner pays 90 for all three members, the friend 30 for the owner and themselves
+    let req = test::TestRequest::post()
+        .uri(&expenses_uri)
+        .insert_header(bearer(&owner))
+        .set_json(json!({
+            "category": "food",
+            "description": "Dinner",
+            "amount": 9000,
+            "currency": "EUR",
+            "spentOn": "2030-06-01"
+        }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status().as_u16(), 201);
+    let dinner: Value = test::read_body_json(resp).await;
+    assert_eq!(dinner["paidBy"], owner.id.as_str());
+    assert_eq!(dinner["splitBetween"].as_array().unwrap().len(), 3);
+
+    let req = test::TestRequest::post()
+        .uri(&expenses_uri)
+        .insert_header(bearer(&friend))
+        .set_json(json!({
+            "category": "food",
+            "description": "Lunch",
+            "amount": 3000,
+            "currency": "EUR",
+            "splitBetween": [owner.id, friend.id],
+            "spentOn": "2030-06-02"
+        }))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status().as_u16(), 201);
+
+    let req = test::TestRequest::post()
+        .uri(&expenses_uri)
+        .insert_header(bearer(&viewer))
+        .set_json(json!({
+            "category": "food",
+            "description": "Snacks",
+            "amount": 500,
+            "currency": "EUR",
+            "spentOn": "2030-06-02"
+        }))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status().as_u16(), 403);
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/travelplan/{}/budget-summary", plan_id))
+        .insert_header(bearer(&viewer))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status().as_u16(), 200);
+    let summary: Value = test::read_body_json(resp).await;
+
+    let food = &summary["categories"][0];
+    assert_eq!(food["category"], "food");
+    assert_eq!(food["budgeted"], 12000);
+    assert_eq!(food["spent"], 12000);
+    assert_eq!(food["remaining"], 0);
+
+    // Owner: paid 90, owes 30 + 15; friend: paid 30, owes 30 + 15; viewer owes 30
+    let balance = |user_id: &str| {
+        summary["balances"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|b| b["userId"] == user_id)
+            .unwrap()["balance"]
+            .as_i64()
+            .unwrap()
+    };
+    assert_eq!(balance(&owner.id), 4500);
+    assert_eq!(balance(&friend.id), -1500);
+    assert_eq!(balance(&viewer.id), -3000);
+
+    let settlements = summary["settlements"].as_array().unwrap();
+    assert_eq!(settlements.len(), 2);
+    assert!(settlements.iter().all(|s| s["toUserId"] == owner.id.as_str()));
+    assert_eq!(settlements[0]["fromUsername"], viewer.username.as_str());
+    assert_eq!(settlements[0]["amount"], 3000);
+    assert_eq!(settlements[1]["amount"], 1500);
+}
+
+#[actix_web::test]
+async fn test_expenses_can_be_patched_and_removed() {
+    let repos = test_repositories();
+    let app = init_app(repos.clone()).await;
+
+    let owner = create_user(&repos, "owner");
+    let plan = create_plan(&app, &owner, "Solo trip").await;
+    let plan_id = plan.travel_plan.id.clone();
+    let expenses_uri = format!("/api/travelplan/{}/expenses", plan_id);
+
+    let req = test::TestRequest::post()
+        .uri(&expenses_uri)
+        .insert_header(bearer(&owner))
+        .set_json(json!({
+            "category": "lodging",
+            "description": "Hostel",
+            "amount": 0,
+            "currency": "EUR",
+            "spentOn": "2030-06-01"
+        }))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status().as_u16(), 400);
+
+    let req = test::TestRequest::post()
+        .uri(&expenses_uri)
+        .insert_header(bearer(&owner))
+        .set_json(json!({
+            "category": "lodging",
+            "description": "Hostel",
+            "amount": 2500,
+            "currency": "EUR",
+            "spentOn": "2030-06-01"
+        }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status().as_u16(), 201);
+    let hostel: Value = test::read_body_json(resp).await;
+    let expense_uri = format!("{}/{}", expenses_uri, hostel["id"].as_str().unwrap());
+
+    let req = test::TestRequest::patch()
+        .uri(&expense_uri)
+        .insert_header(bearer(&owner))
+        .insert_header((header::CONTENT_TYPE, "application/merge-patch+json"))
+        .set_payload(json!({ "currency": "Euro" }).to_string())
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status().as_u16(), 400);
+
+    let req = test::TestRequest::patch()
+        .uri(&expense_uri)
+        .insert_header(bearer(&owner))
+        .insert_header((header::CONTENT_TYPE, "application/merge-patch+json"))
+        .set_payload(json!({ "amount": 3100, "currency": "chf" }).to_string())
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status().as_u16(), 200);
+    let patched: Value = test::read_body_json(resp).await;
+    assert_eq!(patched["amount"], 3100);
+    assert_eq!(patched["currency"], "CHF");
+    assert_eq!(patched["description"], "Hostel");
+
+    // No budget in francs, so nothing remains to compare against
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/travelplan/{}/budget-summary", plan_id))
+        .insert_header(bearer(&owner))
+        .to_request();
+    let summary: Value = test::read_body_json(test::call_service(&app, req).await).await;
+    assert_eq!(summary["categories"][0]["currency"], "CHF");
+    assert!(summary["categories"][0]["budgeted"].is_null());
+    assert!(summary["settlements"].as_array().unwrap().is_empty());
+
+    let req = test::TestRequest::delete()
+        .uri(&expense_uri)
+        .insert_header(bearer(&owner))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status().as_u16(), 204);
+    let req = test::TestRequest::get()
+        .uri(&expense_uri)
+        .insert_header(bearer(&owner))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status().as_u16(), 404);
+
+    let req = test::TestRequest::delete()
+        .uri(&format!("/api/travelplan/{}/budgets/transport", plan_id))
+        .insert_header(bearer(&owner))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status().as_u16(), 404);
+}
+
+#[actix_web::test]
+async fn test_budget_summary_converts_to_the_home_currency() {
+    let repos = test_repositories();
+    let rates = ExchangeRates::parse_csv(
+        "Date,USD,JPY,\n2030-06-04,1.2500,160.00,\n2030-06-03,1.1000,N/A,\n",
+    )
+    .unwrap();
+    let app = init_app_with_state(AppState::new(repos.clone()).with_exchange_rates(rates)).await;
+
+    let owner = create_user(&repos, "owner");
+    let friend = create_user(&repos, "friend");
+    let plan = create_plan(&app, &owner, "Abroad").await;
+    let plan_id = plan.travel_plan.id.clone();
+    add_member(&app, &owner, &friend, &plan_id, "editor").await;
+    let plan_uri = format!("/api/travelplan/{}", plan_id);
+    let expenses_uri = format!("{}/expenses", plan_uri);
+
+    let req = test::TestRequest::patch()
+        .uri(&plan_uri)
+        .insert_header(bearer(&owner))
+        .insert_header((header::CONTENT_TYPE, "application/merge-patch+json"))
+        .set_payload(json!({ "homeCurrency": "Euro" }).to_string())
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status().as_u16(), 400);
+
+    let req = test::TestRequest::patch()
+        .uri(&plan_uri)
+        .insert_header(bearer(&owner))
+        .insert_header((header::CONTENT_TYPE, "application/merge-patch+json"))
+        .set_payload(json!({ "homeCurrency": "eur" }).to_string())
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status().as_u16(), 200);
+    let patched: Value = test::read_body_json(resp).await;
+    assert_eq!(patched["homeCurrency"], "EUR");
+
+    let req = test::TestRequest::put()
+        .uri(&format!("{}/budgets/food", plan_uri))
+        .insert_header(bearer(&owner))
+        .set_json(json!({ "amount": 10000, "currency": "EUR" }))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status().as_u16(), 201);
+
+    // 55.00 USD at Monday's 1.10 and 8000 yen at Tuesday's 160 are 50.00 EUR each
+    for (amount, currency, spent_on) in [(5500, "USD", "2030-06-03"), (8000, "JPY", "2030-06-04")] {
+        let req = test::TestRequest::post()
+            .uri(&expenses_uri)
+            .insert_header(bearer(&owner))
+            .set_json(json!({
+                "category": "food",
+                "description": "Market",
+                "amount": amount,
+                "currency": currency,
+                "spentOn": spent_on
+            }))
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status().as_u16(), 201);
+    }
+
+    let summary_uri = format!("{}/budget-summary", plan_uri);
+    let req = test::TestRequest::get()
+        .uri(&summary_uri)
+        .insert_header(bearer(&friend))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status().as_u16(), 200);
+    let summary: Value = test::read_body_json(resp).await;
+    assert_eq!(summary["homeCurrency"], "EUR");
+    let categories = summary["categories"].as_array().unwrap();
+    assert_eq!(categories.len(), 1);
+    assert_eq!(categories[0]["currency"], "EUR");
+    assert_eq!(categories[0]["spent"], 10000);
+    assert_eq!(categories[0]["remaining"], 0);
+    let settlements = summary["settlements"].as_array().unwrap();
+    assert_eq!(settlements.len(), 1);
+    assert_eq!(settlements[0]["fromUserId"], friend.id.as_str());
+    assert_eq!(settlements[0]["amount"], 5000);
+    assert_eq!(settlements[0]["currency"], "EUR");
+
+    // There is no yen rate for Monday, so the summary can't be worked out
+    let req = test::TestRequest::post()
+        .uri(&expenses_uri)
+        .insert_header(bearer(&friend))
+        .set_json(json!({
+            "category": "food",
+            "description": "Sushi",
+            "amount": 3000,
+            "currency": "JPY",
+            "spentOn": "2030-06-03"
+        }))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status().as_u16(), 201);
+    let req = test::TestRequest::get()
+        .uri(&summary_uri)
+        .insert_header(bearer(&friend))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status().as_u16(), 422);
+    let body: Value = test::read_body_json(resp).await;
+    assert!(body["error"].as_str().unwrap().contains("JPY"));
+}
@@ -0,0 +1,198 @@
+use actix_web::test;
+use serde_json::{Value, json};
+
+use crate::tests::common::{
+    add_member, bearer, create_plan, create_user, generate_routes, init_app, off_runtime,
+    test_repositories,
+};
+
+#[actix_web::test]
+async fn test_plan_comment_threads() {
+    let repos = test_repositories();
+    let app = init_app(repos.clone()).await;
+
+    let owner = create_user(&repos, "owner");
+    let viewer = create_user(&repos, "viewer");
+    let outsider = create_user(&repos, "outsider");
+    let plan = create_plan(&app, &owner, "Discussed trip").await;
+    let plan_id = plan.travel_plan.id;
+    add_member(&app, &owner, &viewer, &plan_id, "viewer").await;
+    let comments_uri = format!("/api/travelplan/{}/comments", plan_id);
+
+    let req = test::TestRequest::post()
+        .uri(&comments_uri)
+        .insert_header(bearer(&owner))
+        .set_json(json!({ "body": "Which route, @viewer? cc @ghost" }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status().as_u16(), 201);
+    let thread: Value = test::read_body_json(resp).await;
+    assert_eq!(thread["username"], "owner");
+    assert_eq!(thread["mentions"], json!(["viewer"]));
+    let thread_id = thread["id"].as_str().unwrap().to_string();
+
+    // Viewers can join the discussion, outsiders can't
+    let req = test::TestRequest::post()
+        .uri(&comments_uri)
+        .insert_header(bearer(&viewer))
+        .set_json(json!({ "body": "The coastal one", "parentId": thread_id }))
+        .to_request();
+    let reply: Value = test::call_and_read_body_json(&app, req).await;
+    let reply_id = reply["id"].as_str().unwrap().to_string();
+
+    let req = test::TestRequest::post()
+        .uri(&comments_uri)
+        .insert_header(bearer(&outsider))
+        .set_json(json!({ "body": "Hello" }))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status().as_u16(), 403);
+
+    let req = test::TestRequest::post()
+        .uri(&comments_uri)
+        .insert_header(bearer(&viewer))
+        .set_json(json!({ "body": "Another topic" }))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status().as_u16(), 201);
+
+    let req = test::TestRequest::get()
+        .uri(&format!("{}?limit=1", comments_uri))
+        .insert_header(bearer(&viewer))
+        .to_request();
+    let page: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(page["total"], 2);
+    assert_eq!(page["threads"].as_array().unwrap().len(), 1);
+    assert_eq!(page["threads"][0]["replies"][0]["body"], "The coastal one");
+
+    let req = test::TestRequest::get()
+        .uri(&format!("{}?offset={}", comments_uri, u64::MAX))
+        .insert_header(bearer(&viewer))
+        .to_request();
+    let page: Value = test::call_and_read_body_json(&app, req).await;
+    assert!(page["threads"].as_array().unwrap().is_empty());
+
+    // Only the author may edit or delete
+    let reply_uri = format!("{}/{}", comments_uri, reply_id);
+    let req = test::TestRequest::put()
+        .uri(&reply_uri)
+        .insert_header(bearer(&owner))
+        .set_json(json!({ "body": "Hijacked" }))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status().as_u16(), 403);
+
+    let req = test::TestRequest::put()
+        .uri(&reply_uri)
+        .insert_header(bearer(&viewer))
+        .set_json(json!({ "body": "The coastal one, @owner" }))
+        .to_request();
+    let edited: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(edited["mentions"], json!(["owner"]));
+
+    let req = test::TestRequest::put()
+        .uri(&reply_uri)
+        .insert_header(bearer(&viewer))
+        .set_json(json!({ "body": "" }))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status().as_u16(), 400);
+
+    let req = test::TestRequest::delete()
+        .uri(&format!("{}/{}", comments_uri, thread_id))
+        .insert_header(bearer(&owner))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status().as_u16(), 204);
+
+    let req = test::TestRequest::get()
+        .uri(&comments_uri)
+        .insert_header(bearer(&viewer))
+        .to_request();
+    let page: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(page["total"], 2);
+    let replied = page["threads"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|t| t["id"] == thread_id.as_str())
+        .unwrap();
+    assert_eq!(replied["body"], "[deleted]");
+    assert_eq!(replied["replies"][0]["id"], reply_id.as_str());
+
+    let req = test::TestRequest::delete()
+        .uri(&reply_uri)
+        .insert_header(bearer(&viewer))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status().as_u16(), 204);
+    assert!(off_runtime(|| repos.find_comment(&reply_id)).unwrap().is_none());
+}
+
+#[actix_web::test]
+async fn test_comments_follow_their_parent() {
+    let repos = test_repositories();
+    let app = init_app(repos.clone()).await;
+
+    let owner = create_user(&repos, "owner");
+    let plan = create_plan(&app, &owner, "Commented trip").await;
+    let plan_id = plan.travel_plan.id;
+    let routes = generate_routes(&app, &owner, &plan_id, 2).await;
+    let route_id = routes[0]["route"]["id"].as_str().unwrap();
+    let poi_id = routes[0]["pointsOfInterest"][0]["id"].as_str().unwrap();
+    let route_uri = format!("/api/travelplan/{}/routes/{}", plan_id, route_id);
+    let poi_comments_uri = format!("{}/pois/{}/comments", route_uri, poi_id);
+
+    let mut comment_ids = Vec::new();
+    for uri in [format!("{}/comments", route_uri), poi_comments_uri.clone()] {
+        let req = test::TestRequest::post()
+            .uri(&uri)
+            .insert_header(bearer(&owner))
+            .set_json(json!({ "body": "Worth it" }))
+            .to_request();
+        let comment: Value = test::call_and_read_body_json(&app, req).await;
+        comment_ids.push(comment["id"].as_str().unwrap().to_string());
+    }
+
+    // A POI is only reachable through its own route
+    let other_route = routes[1]["route"]["id"].as_str().unwrap();
+    let req = test::TestRequest::get()
+        .uri(&format!(
+            "/api/travelplan/{}/routes/{}/pois/{}/comments",
+            plan_id, other_route, poi_id
+        ))
+        .insert_header(bearer(&owner))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status().as_u16(), 404);
+
+    let req = test::TestRequest::delete()
+        .uri(&route_uri)
+        .insert_header(bearer(&owner))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status().as_u16(), 200);
+    for id in &comment_ids {
+        assert!(off_runtime(|| repos.find_comment(id)).unwrap().is_none());
+    }
+
+    let req = test::TestRequest::get()
+        .uri(&poi_comments_uri)
+        .insert_header(bearer(&owner))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status().as_u16(), 404);
+
+    // Plan comments go with the plan
+    let req = test::TestRequest::delete()
+        .uri(&format!("/api/travelplan/{}/routes", plan_id))
+        .insert_header(bearer(&owner))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status().as_u16(), 200);
+
+    let req = test::TestRequest::post()
+        .uri(&format!("/api/travelplan/{}/comments", plan_id))
+        .insert_header(bearer(&owner))
+        .set_json(json!({ "body": "Bye" }))
+        .to_request();
+    let comment: Value = test::call_and_read_body_json(&app, req).await;
+
+    let req = test::TestRequest::delete()
+        .uri(&format!("/api/travelplan/{}", plan_id))
+        .insert_header(bearer(&owner))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status().as_u16(), 204);
+    let comment_id = comment["id"].as_str().unwrap();
+    assert!(off_runtime(|| repos.find_comment(comment_id)).unwrap().is_none());
+}
@@ -0,0 +1,166 @@
+use std::sync::{Arc, Mutex};
+
+use actix_http::Request;
+use actix_web::dev::{Service, ServiceResponse};
+use actix_web::http::header;
+use actix_web::{test, App, Error};
+use serde_json::{json, Value};
+
+use crate::app::{configure_app, AppState};
+use crate::db::connection::get_test_pool;
+use crate::db::postgres::{PgPool, get_test_pg_pool};
+use crate::middleware::auth::generate_token;
+use crate::models::user::{NewUser, Role, User};
+use crate::repositories::Repositories;
+use crate::repositories::postgres::PostgresRepository;
+use crate::repositories::sqlite::SqliteRepository;
+use crate::services::travel_plan_service::TravelPlanDto;
+
+pub struct TestUser {
+    pub id: String,
+    pub username: String,
+    pub token: String,
+}
+
+/// Closing a PostgreSQL connection blocks on a runtime of its own, which
+/// panics on the one a test runs on, so the pools stay open until the run ends.
+static PG_POOLS: Mutex<Vec<PgPool>> = Mutex::new(Vec::new());
+
+/// Fresh repositories backed by a private in-memory SQLite database, or by a
+/// private schema of the PostgreSQL database at `TEST_POSTGRES_URL` when that
+/// is set.
+pub fn test_repositories() -> Arc<dyn Repositories> {
+    match std::env::var("TEST_POSTGRES_URL") {
+        Ok(url) => {
+            let pool = off_runtime(|| get_test_pg_pool(&url)).unwrap();
+            PG_POOLS.lock().unwrap().push(pool.clone());
+            Arc::new(PostgresRepository::new(pool))
+        }
+        Err(_) => sqlite_test_repositories(),
+    }
+}
+
+/// Runs `f` on a thread of its own. The blocking PostgreSQL client can't be
+/// used from the async runtime a test runs on, so tests reach past the API
+/// to the repositories through this.
+pub fn off_runtime<T: Send>(f: impl FnOnce() -> T + Send) -> T {
+    std::thread::scope(|scope| scope.spawn(f).join().unwrap())
+}
+
+/// Always SQLite, for the tests of what only that backend does.
+pub fn sqlite_test_repositories() -> Arc<dyn Repositories> {
+    Arc::new(SqliteRepository::new(get_test_pool().unwrap()))
+}
+
+/// Builds the real application routes on top of the given repositories.
+pub async fn init_app(
+    repos: Arc<dyn Repositories>,
+) -> impl Service<Request, Response = ServiceResponse, Error = Error> {
+    init_app_with_state(AppState::new(repos)).await
+}
+
+/// Like [`init_app`], for tests that need admins or backups configured.
+pub async fn init_app_with_state(
+    state: AppState,
+) -> impl Service<Request, Response = ServiceResponse, Error = Error> {
+    test::init_service(App::new().configure(|cfg| configure_app(cfg, &state))).await
+}
+
+/// Stores a user directly and issues a token for it, skipping the register
+/// and login round trips.
+pub fn create_user(repos: &Arc<dyn Repositories>, username: &str) -> TestUser {
+    create_user_with_role(repos, username, Role::User)
+}
+
+pub fn create_user_with_role(repos: &Arc<dyn Repositories>, username: &str, role: Role) -> TestUser {
+    let mut user = User::new(&NewUser {
+        username: username.to_string(),
+        password: "password123".to_string(),
+        email: format!("{}@example.com", username),
+    })
+    .unwrap();
+    user.role = role;
+    off_runtime(|| repos.insert_user(&user)).unwrap();
+
+    let token = generate_token(&user).unwrap();
+
+    TestUser {
+        id: user.id,
+        username: user.username,
+        token: token.token,
+    }
+}
+
+pub fn bearer(user: &TestUser) -> (header::HeaderName, String) {
+    (header::AUTHORIZATION, format!("Bearer {}", user.token))
+}
+
+pub fn plan_body(name: &str) -> Value {
+    json!({
+        "name": name,
+        "description": "A test travel plan",
+        "startLocation": "New York",
+        "endLocation": "Los Angeles",
+        "startDate": "2030-06-01T09:00:00Z",
+        "endDate": "2030-06-10T18:00:00Z"
+    })
+}
+
+/// Creates a travel plan for `user` through the API.
+pub async fn create_plan(
+    app: &impl Service<Request, Response = ServiceResponse, Error = Error>,
+    user: &TestUser,
+    name: &str,
+) -> TravelPlanDto {
+    let req = test::TestRequest::post()
+        .uri("/api/travelplan")
+        .insert_header(bearer(user))
+        .set_json(plan_body(name))
+        .to_request();
+
+    let resp = test::call_service(app, req).await;
+    assert_eq!(resp.status().as_u16(), 201);
+
+    test::read_body_json(resp).await
+}
+
+/// Generates `count` route options for a plan and returns the response body.
+pub async fn generate_routes(
+    app: &impl Service<Request, Response = ServiceResponse, Error = Error>,
+    user: &TestUser,
+    plan_id: &str,
+    count: usize,
+) -> Vec<Value> {
+    let req = test::TestRequest::post()
+        .uri(&format!("/api/travelplan/{}/routes/generate?count={}", plan_id, count))
+        .insert_header(bearer(user))
+        .to_request();
+
+    let resp = test::call_service(app, req).await;
+    assert!(resp.status().is_success());
+
+    test::read_body_json(resp).await
+}
+
+/// Invites `member` with `role` and accepts on their behalf.
+pub async fn add_member(
+    app: &impl Service<Request, Response = ServiceResponse, Error = Error>,
+    owner: &TestUser,
+    member: &TestUser,
+    plan_id: &str,
+    role: &str,
+) {
+    let members_uri = format!("/api/travelplan/{}/members", plan_id);
+    let req = test::TestRequest::post()
+        .uri(&members_uri)
+        .insert_header(bearer(owner))
+        .set_json(json!({ "username": member.username, "role": role }))
+        .to_request();
+    assert_eq!(test::call_service(app, req).await.status().as_u16(), 201);
+
+    let req = test::TestRequest::post()
+        .uri(&format!("{}/accept", members_uri))
+        .insert_header(bearer(member))
+        .to_request();
+    assert_eq!(test::call_service(app, req).await.status().as_u16(), 200);
+}
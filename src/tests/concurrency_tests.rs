@@ -0,0 +1,143 @@
+use actix_web::http::header;
+use actix_web::test;
+use serde_json::json;
+
+use crate::tests::common::{
+    bearer, create_plan, create_user, generate_routes, init_app, test_repositories,
+};
+
+#[actix_web::test]
+async fn test_plan_etags_guard_concurrent_edits() {
+    let repos = test_repositories();
+    let app = init_app(repos.clone()).await;
+
+    let owner = create_user(&repos, "owner");
+    let plan = create_plan(&app, &owner, "Contended trip").await;
+    let plan_uri = format!("/api/travelplan/{}", plan.travel_plan.id);
+
+    let req = test::TestRequest::get()
+        .uri(&plan_uri)
+        .insert_header(bearer(&owner))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status().as_u16(), 200);
+    let etag = resp.headers().get(header::ETAG).unwrap().to_str().unwrap().to_string();
+
+    let req = test::TestRequest::get()
+        .uri(&plan_uri)
+        .insert_header(bearer(&owner))
+        .insert_header((header::IF_NONE_MATCH, etag.clone()))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status().as_u16(), 304);
+
+    // First writer wins and gets the new version back
+    let req = test::TestRequest::put()
+        .uri(&plan_uri)
+        .insert_header(bearer(&owner))
+        .insert_header((header::IF_MATCH, etag.clone()))
+        .set_json(json!({ "name": "First edit" }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status().as_u16(), 200);
+    let new_etag = resp.headers().get(header::ETAG).unwrap().to_str().unwrap().to_string();
+    assert_ne!(new_etag, etag);
+
+    // The second writer still holds the old version
+    let req = test::TestRequest::put()
+        .uri(&plan_uri)
+        .insert_header(bearer(&owner))
+        .insert_header((header::IF_MATCH, etag.clone()))
+        .set_json(json!({ "name": "Second edit" }))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status().as_u16(), 412);
+
+    let req = test::TestRequest::get()
+        .uri(&plan_uri)
+        .insert_header(bearer(&owner))
+        .insert_header((header::IF_NONE_MATCH, etag.clone()))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status().as_u16(), 200);
+    assert_eq!(resp.headers().get(header::ETAG).unwrap(), new_etag.as_str());
+
+    // Generating routes changes what the plan looks like, so its tag too
+    generate_routes(&app, &owner, &plan.travel_plan.id, 1).await;
+    let req = test::TestRequest::delete()
+        .uri(&plan_uri)
+        .insert_header(bearer(&owner))
+        .insert_header((header::IF_MATCH, new_etag.clone()))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status().as_u16(), 412);
+
+    // Clearing the routes is checked against the plan's tag as well
+    let routes_uri = format!("{}/routes", plan_uri);
+    let req = test::TestRequest::delete()
+        .uri(&routes_uri)
+        .insert_header(bearer(&owner))
+        .insert_header((header::IF_MATCH, new_etag))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status().as_u16(), 412);
+    let req = test::TestRequest::get()
+        .uri(&plan_uri)
+        .insert_header(bearer(&owner))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    let routed_etag = resp.headers().get(header::ETAG).unwrap().to_str().unwrap().to_string();
+    let req = test::TestRequest::delete()
+        .uri(&routes_uri)
+        .insert_header(bearer(&owner))
+        .insert_header((header::IF_MATCH, routed_etag))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status().as_u16(), 200);
+
+    // Without If-Match writes are last-write-wins, as before
+    let req = test::TestRequest::put()
+        .uri(&plan_uri)
+        .insert_header(bearer(&owner))
+        .set_json(json!({ "name": "Unconditional" }))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status().as_u16(), 200);
+}
+
+#[actix_web::test]
+async fn test_route_option_etags() {
+    let repos = test_repositories();
+    let app = init_app(repos.clone()).await;
+
+    let owner = create_user(&repos, "owner");
+    let plan = create_plan(&app, &owner, "Routed trip").await;
+    let routes = generate_routes(&app, &owner, &plan.travel_plan.id, 1).await;
+    let route_uri = format!(
+        "/api/travelplan/{}/routes/{}",
+        plan.travel_plan.id,
+        routes[0]["route"]["id"].as_str().unwrap()
+    );
+
+    let req = test::TestRequest::get()
+        .uri(&route_uri)
+        .insert_header(bearer(&owner))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    let etag = resp.headers().get(header::ETAG).unwrap().to_str().unwrap().to_string();
+
+    let req = test::TestRequest::get()
+        .uri(&route_uri)
+        .insert_header(bearer(&owner))
+        .insert_header((header::IF_NONE_MATCH, format!("W/{}", etag)))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status().as_u16(), 304);
+
+    let req = test::TestRequest::delete()
+        .uri(&route_uri)
+        .insert_header(bearer(&owner))
+        .insert_header((header::IF_MATCH, "\"something-else\""))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status().as_u16(), 412);
+
+    let req = test::TestRequest::delete()
+        .uri(&route_uri)
+        .insert_header(bearer(&owner))
+        .insert_header((header::IF_MATCH, etag))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status().as_u16(), 200);
+}
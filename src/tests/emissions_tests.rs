@@ -0,0 +1,72 @@
+use actix_web::test;
+use serde_json::Value;
+
+use crate::app::AppState;
+use crate::models::travel_mode::TravelMode;
+use crate::services::emissions::{EmissionFactors, round1};
+use crate::tests::common::{
+    bearer, create_plan, create_user, generate_routes, init_app_with_state, test_repositories,
+};
+
+#[actix_web::test]
+async fn test_routes_report_co2e_and_add_up_per_year() {
+    let repos = test_repositories();
+    let factors = EmissionFactors::parse(r#"{ "driving": 100, "transit": 10 }"#).unwrap();
+    let state = AppState::new(repos.clone()).with_emission_factors(factors.clone());
+    let app = init_app_with_state(state).await;
+
+    let user = create_user(&repos, "traveller");
+    let outsider = create_user(&repos, "outsider");
+    let plan_id = create_plan(&app, &user, "Road trip").await.travel_plan.id;
+
+    for generated in generate_routes(&app, &user, &plan_id, 3).await {
+        let distance = generated["route"]["distance"].as_f64().unwrap();
+        let expected = round1(factors.kg_for(distance, TravelMode::Driving));
+        assert_eq!(generated["co2eKg"].as_f64(), Some(expected));
+    }
+
+    // With nothing selected the plan counts its fastest route
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/travelplan/{}/summary", plan_id))
+        .insert_header(bearer(&user))
+        .to_request();
+    let summary: Value = test::read_body_json(test::call_service(&app, req).await).await;
+    let distance = summary["legs"][0]["distance"].as_f64().unwrap();
+    let expected = round1(factors.kg_for(distance, TravelMode::Driving));
+
+    let req = test::TestRequest::get()
+        .uri("/api/emissions?year=2030")
+        .insert_header(bearer(&user))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status().as_u16(), 200);
+    let report: Value = test::read_body_json(resp).await;
+    assert_eq!(report["year"], 2030);
+    assert_eq!(report["totalCo2eKg"].as_f64(), Some(expected));
+    assert_eq!(report["byMode"][0]["mode"], "driving");
+    assert_eq!(report["byMode"][0]["co2eKg"].as_f64(), Some(expected));
+    assert_eq!(report["plans"][0]["travelPlanId"], plan_id.as_str());
+    assert_eq!(report["plans"][0]["complete"], false);
+
+    // Other years and other users' plans aren't counted
+    let req = test::TestRequest::get()
+        .uri("/api/emissions?year=2031")
+        .insert_header(bearer(&user))
+        .to_request();
+    let report: Value = test::read_body_json(test::call_service(&app, req).await).await;
+    assert_eq!(report["totalCo2eKg"].as_f64(), Some(0.0));
+    assert_eq!(report["plans"].as_array().unwrap().len(), 0);
+
+    let req = test::TestRequest::get()
+        .uri("/api/emissions?year=2030")
+        .insert_header(bearer(&outsider))
+        .to_request();
+    let report: Value = test::read_body_json(test::call_service(&app, req).await).await;
+    assert_eq!(report["byMode"].as_array().unwrap().len(), 0);
+
+    let req = test::TestRequest::get()
+        .uri("/api/emissions?year=0")
+        .insert_header(bearer(&user))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status().as_u16(), 400);
+}
@@ -0,0 +1,324 @@
+use actix_web::http::header;
+use actix_web::test;
+use serde_json::{Value, json};
+
+use crate::tests::common::{
+    add_member, bearer, create_plan, create_user, generate_routes, init_app, test_repositories,
+};
+
+#[actix_web::test]
+async fn test_itinerary_items_follow_the_plan_dates() {
+    let repos = test_repositories();
+    let app = init_app(repos.clone()).await;
+
+    let owner = create_user(&repos, "owner");
+    let plan = create_plan(&app, &owner, "Scheduled trip").await;
+    let plan_id = plan.travel_plan.id.clone();
+    let items_uri = format!("/api/travelplan/{}/itinerary/items", plan_id);
+
+    // One day per date from the start to the end of the plan
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/travelplan/{}/itinerary", plan_id))
+        .insert_header(bearer(&owner))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status().as_u16(), 200);
+    let body: Value = test::read_body_json(resp).await;
+    let days = body["days"].as_array().unwrap();
+    assert_eq!(days.len(), 10);
+    assert_eq!(days[0]["date"], "2030-06-01");
+    assert_eq!(days[9]["date"], "2030-06-10");
+
+    let req = test::TestRequest::post()
+        .uri(&items_uri)
+        .insert_header(bearer(&owner))
+        .set_json(json!({ "day": "2030-06-11", "kind": "activity", "title": "Too late" }))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status().as_u16(), 400);
+
+    let req = test::TestRequest::post()
+        .uri(&items_uri)
+        .insert_header(bearer(&owner))
+        .set_json(json!({
+            "day": "2030-06-01",
+            "kind": "activity",
+            "title": "Dinner",
+            "startsAt": "2030-06-01T08:00:00Z"
+        }))
+        .to_request();
+    // Before the plan starts
+    assert_eq!(test::call_service(&app, req).await.status().as_u16(), 400);
+
+    let req = test::TestRequest::post()
+        .uri(&items_uri)
+        .insert_header(bearer(&owner))
+        .set_json(json!({ "day": "2030-06-01", "kind": "activity" }))
+        .to_request();
+    // Activities need a title
+    assert_eq!(test::call_service(&app, req).await.status().as_u16(), 400);
+
+    let req = test::TestRequest::post()
+        .uri(&items_uri)
+        .insert_header(bearer(&owner))
+        .set_json(json!({
+            "day": "2030-06-01",
+            "kind": "activity",
+            "title": "Dinner",
+            "startsAt": "2030-06-01T19:00:00Z"
+        }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status().as_u16(), 201);
+    let dinner: Value = test::read_body_json(resp).await;
+    assert_eq!(dinner["position"], 0);
+}
+
+#[actix_web::test]
+async fn test_itinerary_items_can_be_moved_and_reordered() {
+    let repos = test_repositories();
+    let app = init_app(repos.clone()).await;
+
+    let owner = create_user(&repos, "owner");
+    let plan = create_plan(&app, &owner, "Busy trip").await;
+    let plan_id = plan.travel_plan.id.clone();
+    let items_uri = format!("/api/travelplan/{}/itinerary/items", plan_id);
+    let routes = generate_routes(&app, &owner, &plan_id, 1).await;
+    let route = &routes[0]["route"];
+    let poi = &routes[0]["pointsOfInterest"][0];
+
+    let req = test::TestRequest::post()
+        .uri(&items_uri)
+        .insert_header(bearer(&owner))
+        .set_json(json!({ "day": "2030-06-02", "kind": "route_leg", "routeOptionId": route["id"] }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status().as_u16(), 201);
+    let leg: Value = test::read_body_json(resp).await;
+    // The title defaults to the route's name
+    assert_eq!(leg["title"], route["name"]);
+
+    let req = test::TestRequest::post()
+        .uri(&items_uri)
+        .insert_header(bearer(&owner))
+        .set_json(json!({ "day": "2030-06-02", "kind": "poi_visit", "poiId": poi["id"] }))
+        .to_request();
+    // Visits need a time window
+    assert_eq!(test::call_service(&app, req).await.status().as_u16(), 400);
+
+    let req = test::TestRequest::post()
+        .uri(&items_uri)
+        .insert_header(bearer(&owner))
+        .set_json(json!({
+            "day": "2030-06-02",
+            "kind": "poi_visit",
+            "poiId": poi["id"],
+            "startsAt": "2030-06-02T10:00:00Z",
+            "endsAt": "2030-06-02T12:00:00Z"
+        }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status().as_u16(), 201);
+    let visit: Value = test::read_body_json(resp).await;
+    assert_eq!(visit["position"], 1);
+    assert_eq!(visit["title"], poi["name"]);
+    assert_eq!(visit["routeOptionId"], route["id"]);
+
+    let order_uri = format!("/api/travelplan/{}/itinerary/days/2030-06-02/order", plan_id);
+    let req = test::TestRequest::put()
+        .uri(&order_uri)
+        .insert_header(bearer(&owner))
+        .set_json(json!({ "itemIds": [visit["id"]] }))
+        .to_request();
+    // Every item on the day must be named
+    assert_eq!(test::call_service(&app, req).await.status().as_u16(), 400);
+
+    let req = test::TestRequest::put()
+        .uri(&order_uri)
+        .insert_header(bearer(&owner))
+        .set_json(json!({ "itemIds": [visit["id"], leg["id"]] }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status().as_u16(), 200);
+    let day: Value = test::read_body_json(resp).await;
+    assert_eq!(day["items"][0]["id"], visit["id"]);
+    assert_eq!(day["items"][1]["id"], leg["id"]);
+
+    // Moving the leg to another day puts it last there
+    let leg_uri = format!("{}/{}", items_uri, leg["id"].as_str().unwrap());
+    let req = test::TestRequest::patch()
+        .uri(&leg_uri)
+        .insert_header(bearer(&owner))
+        .insert_header((header::CONTENT_TYPE, "application/merge-patch+json"))
+        .set_payload(json!({ "day": "2030-06-03", "notes": "Early start" }).to_string())
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status().as_u16(), 200);
+    let moved: Value = test::read_body_json(resp).await;
+    assert_eq!(moved["day"], "2030-06-03");
+    assert_eq!(moved["position"], 0);
+    assert_eq!(moved["notes"], "Early start");
+
+    // Deleting the route takes its legs and visits with it
+    let req = test::TestRequest::delete()
+        .uri(&format!("/api/travelplan/{}/routes/{}", plan_id, route["id"].as_str().unwrap()))
+        .insert_header(bearer(&owner))
+        .to_request();
+    assert!(test::call_service(&app, req).await.status().is_success());
+
+    let req = test::TestRequest::get()
+        .uri(&leg_uri)
+        .insert_header(bearer(&owner))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status().as_u16(), 404);
+}
+
+#[actix_web::test]
+async fn test_viewers_can_read_but_not_edit_the_itinerary() {
+    let repos = test_repositories();
+    let app = init_app(repos.clone()).await;
+
+    let owner = create_user(&repos, "owner");
+    let viewer = create_user(&repos, "viewer");
+    let plan = create_plan(&app, &owner, "Shared trip").await;
+    let plan_id = plan.travel_plan.id.clone();
+    add_member(&app, &owner, &viewer, &plan_id, "viewer").await;
+    let items_uri = format!("/api/travelplan/{}/itinerary/items", plan_id);
+
+    let req = test::TestRequest::post()
+        .uri(&items_uri)
+        .insert_header(bearer(&viewer))
+        .set_json(json!({ "day": "2030-06-01", "kind": "activity", "title": "Museum" }))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status().as_u16(), 403);
+
+    let req = test::TestRequest::post()
+        .uri(&items_uri)
+        .insert_header(bearer(&owner))
+        .set_json(json!({ "day": "2030-06-01", "kind": "activity", "title": "Museum" }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    let item: Value = test::read_body_json(resp).await;
+    let item_uri = format!("{}/{}", items_uri, item["id"].as_str().unwrap());
+
+    let req = test::TestRequest::get()
+        .uri(&item_uri)
+        .insert_header(bearer(&viewer))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status().as_u16(), 200);
+
+    let req = test::TestRequest::delete()
+        .uri(&item_uri)
+        .insert_header(bearer(&viewer))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status().as_u16(), 403);
+
+    let req = test::TestRequest::delete()
+        .uri(&item_uri)
+        .insert_header(bearer(&owner))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status().as_u16(), 204);
+
+    let req = test::TestRequest::get()
+        .uri(&item_uri)
+        .insert_header(bearer(&owner))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status().as_u16(), 404);
+}
+
+#[actix_web::test]
+async fn test_schedule_proposal_follows_the_selected_route() {
+    let repos = test_repositories();
+    let app = init_app(repos.clone()).await;
+
+    let owner = create_user(&repos, "owner");
+    let viewer = create_user(&repos, "viewer");
+    let plan = create_plan(&app, &owner, "Road trip").await;
+    let plan_id = plan.travel_plan.id.clone();
+    add_member(&app, &owner, &viewer, &plan_id, "viewer").await;
+    let schedule_uri = format!("/api/travelplan/{}/itinerary/schedule", plan_id);
+    let routes = generate_routes(&app, &owner, &plan_id, 1).await;
+    let route_id = routes[0]["route"]["id"].as_str().unwrap().to_string();
+
+    let req = test::TestRequest::post()
+        .uri(&schedule_uri)
+        .insert_header(bearer(&owner))
+        .set_json(json!({}))
+        .to_request();
+    // Nothing selected and no route named
+    assert_eq!(test::call_service(&app, req).await.status().as_u16(), 400);
+
+    // Ten hours of driving
+    let req = test::TestRequest::patch()
+        .uri(&format!("/api/travelplan/{}/routes/{}", plan_id, route_id))
+        .insert_header(bearer(&owner))
+        .insert_header((header::CONTENT_TYPE, "application/merge-patch+json"))
+        .set_payload(json!({ "distance": 800.0, "duration": 600 }).to_string())
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status().as_u16(), 200);
+    let req = test::TestRequest::put()
+        .uri(&format!("/api/travelplan/{}/selected-route", plan_id))
+        .insert_header(bearer(&owner))
+        .set_json(json!({ "routeId": route_id }))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status().as_u16(), 200);
+
+    let req = test::TestRequest::post()
+        .uri(&schedule_uri)
+        .insert_header(bearer(&viewer))
+        .set_json(json!({ "maxDailyTravelMinutes": 300 }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status().as_u16(), 200);
+    let proposal: Value = test::read_body_json(resp).await;
+    assert_eq!(proposal["routeOptionId"], route_id.as_str());
+    assert_eq!(proposal["mode"], "driving");
+    assert_eq!(proposal["applied"], false);
+    let days = proposal["days"].as_array().unwrap();
+    assert!(days.len() >= 2);
+    let mut travelled = 0;
+    for day in days {
+        let minutes = day["travelMinutes"].as_i64().unwrap();
+        assert!(minutes <= 300);
+        travelled += minutes;
+        let items = day["items"].as_array().unwrap();
+        let legs = items.iter().filter(|item| item["kind"] == "route_leg").count();
+        assert_eq!(legs, usize::from(minutes > 0));
+    }
+    assert!((travelled - 600).abs() <= 1);
+
+    // Only the proposal is open to viewers; nothing was saved
+    let req = test::TestRequest::post()
+        .uri(&schedule_uri)
+        .insert_header(bearer(&viewer))
+        .set_json(json!({ "apply": true }))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status().as_u16(), 403);
+
+    let req = test::TestRequest::post()
+        .uri(&schedule_uri)
+        .insert_header(bearer(&owner))
+        .set_json(json!({ "maxDailyTravelMinutes": 300, "apply": true }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status().as_u16(), 201);
+    let applied: Value = test::read_body_json(resp).await;
+    let scheduled: usize = applied["days"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|day| day["items"].as_array().unwrap().len())
+        .sum();
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/travelplan/{}/itinerary", plan_id))
+        .insert_header(bearer(&owner))
+        .to_request();
+    let itinerary: Value = test::call_and_read_body_json(&app, req).await;
+    let saved: usize = itinerary["days"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|day| day["items"].as_array().unwrap().len())
+        .sum();
+    assert_eq!(saved, scheduled);
+}
@@ -0,0 +1,135 @@
+use actix_web::http::header;
+use actix_web::test;
+use serde_json::{Value, json};
+
+use crate::tests::common::{
+    bearer, create_plan, create_user, generate_routes, init_app, test_repositories,
+};
+
+fn merge_patch(uri: &str, body: Value) -> test::TestRequest {
+    test::TestRequest::patch()
+        .uri(uri)
+        .insert_header((header::CONTENT_TYPE, "application/merge-patch+json"))
+        .set_payload(body.to_string())
+}
+
+#[actix_web::test]
+async fn test_patch_travel_plan_clears_nullable_fields() {
+    let repos = test_repositories();
+    let app = init_app(repos.clone()).await;
+
+    let owner = create_user(&repos, "owner");
+    let plan = create_plan(&app, &owner, "Patched trip").await;
+    let plan_uri = format!("/api/travelplan/{}", plan.travel_plan.id);
+
+    let req = merge_patch(&plan_uri, json!({ "description": null, "endDate": null }))
+        .insert_header(bearer(&owner))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status().as_u16(), 200);
+    let etag = resp.headers().get(header::ETAG).unwrap().to_str().unwrap().to_string();
+    let body: Value = test::read_body_json(resp).await;
+    assert_eq!(body["description"], Value::Null);
+    assert_eq!(body["endDate"], Value::Null);
+    // Fields the patch leaves out are untouched
+    assert_eq!(body["name"], "Patched trip");
+    assert_eq!(body["startDate"], "2030-06-01T09:00:00Z");
+
+    // The name can change but not be cleared
+    let req = merge_patch(&plan_uri, json!({ "name": null }))
+        .insert_header(bearer(&owner))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status().as_u16(), 400);
+
+    let req = merge_patch(&plan_uri, json!({ "name": "Renamed" }))
+        .insert_header(bearer(&owner))
+        .insert_header((header::IF_MATCH, "\"stale\""))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status().as_u16(), 412);
+
+    let req = merge_patch(&plan_uri, json!({ "name": "Renamed" }))
+        .insert_header(bearer(&owner))
+        .insert_header((header::IF_MATCH, etag))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status().as_u16(), 200);
+
+    // PUT keeps treating null as "leave unchanged"
+    let req = test::TestRequest::put()
+        .uri(&plan_uri)
+        .insert_header(bearer(&owner))
+        .set_json(json!({ "description": "Back again", "startDate": null }))
+        .to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["description"], "Back again");
+    assert_eq!(body["startDate"], "2030-06-01T09:00:00Z");
+    assert_eq!(body["name"], "Renamed");
+}
+
+#[actix_web::test]
+async fn test_patch_route_option_and_point_of_interest() {
+    let repos = test_repositories();
+    let app = init_app(repos.clone()).await;
+
+    let owner = create_user(&repos, "owner");
+    let viewer = create_user(&repos, "viewer");
+    let plan = create_plan(&app, &owner, "Routed trip").await;
+    let routes = generate_routes(&app, &owner, &plan.travel_plan.id, 1).await;
+    let route_id = routes[0]["route"]["id"].as_str().unwrap();
+    let poi_id = routes[0]["pointsOfInterest"][0]["id"].as_str().unwrap();
+    let route_uri = format!("/api/travelplan/{}/routes/{}", plan.travel_plan.id, route_id);
+
+    let req = merge_patch(&route_uri, json!({ "name": "Coastal", "waypoints": null }))
+        .insert_header(bearer(&owner))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status().as_u16(), 200);
+    let etag = resp.headers().get(header::ETAG).unwrap().to_str().unwrap().to_string();
+    let body: Value = test::read_body_json(resp).await;
+    assert_eq!(body["route"]["name"], "Coastal");
+    assert_eq!(body["route"]["waypoints"], Value::Null);
+    assert_eq!(body["route"]["startCoordinates"], routes[0]["route"]["startCoordinates"]);
+
+    let req = merge_patch(&route_uri, json!({ "name": "Mine" }))
+        .insert_header(bearer(&viewer))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status().as_u16(), 403);
+
+    // Editing a point of interest moves its route on to a new version
+    let poi_uri = format!("{}/pois/{}", route_uri, poi_id);
+    let req = merge_patch(&poi_uri, json!({ "category": null, "name": "Lighthouse" }))
+        .insert_header(bearer(&owner))
+        .insert_header((header::IF_MATCH, etag.clone()))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status().as_u16(), 200);
+    assert_ne!(resp.headers().get(header::ETAG).unwrap(), etag.as_str());
+    let body: Value = test::read_body_json(resp).await;
+    let poi = body["pointsOfInterest"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|poi| poi["id"] == poi_id)
+        .unwrap();
+    assert_eq!(poi["name"], "Lighthouse");
+    assert_eq!(poi["category"], Value::Null);
+
+    let req = merge_patch(&route_uri, json!({ "description": "Along the sea" }))
+        .insert_header(bearer(&owner))
+        .insert_header((header::IF_MATCH, etag))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status().as_u16(), 412);
+
+    let req = merge_patch(&format!("{}/pois/missing", route_uri), json!({ "name": "x" }))
+        .insert_header(bearer(&owner))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status().as_u16(), 404);
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/travelplan/{}/activity", plan.travel_plan.id))
+        .insert_header(bearer(&owner))
+        .to_request();
+    let events: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(events[0]["action"], "poi_updated");
+    assert_eq!(events[1]["action"], "route_updated");
+    assert_eq!(events[1]["changes"][route_id]["to"]["name"], "Coastal");
+}
@@ -1,3 +1,18 @@
+pub mod common;
+pub mod admin_tests;
+pub mod app_tests;
 pub mod auth_tests;
 pub mod travel_plan_tests;
-pub mod route_option_tests;
\ No newline at end of file
+pub mod route_option_tests;
+pub mod route_vote_tests;
+pub mod sharing_tests;
+pub mod share_link_tests;
+pub mod comment_tests;
+pub mod activity_tests;
+pub mod revision_tests;
+pub mod concurrency_tests;
+pub mod merge_patch_tests;
+pub mod itinerary_tests;
+pub mod plan_leg_tests;
+pub mod budget_tests;
+pub mod emissions_tests;
@@ -0,0 +1,227 @@
+use actix_web::http::header;
+use actix_web::test;
+use serde_json::{Value, json};
+
+use crate::tests::common::{
+    add_member, bearer, create_plan, create_user, generate_routes, init_app, off_runtime,
+    test_repositories,
+};
+
+#[actix_web::test]
+async fn test_plan_legs_have_their_own_routes_and_roll_up() {
+    let repos = test_repositories();
+    let app = init_app(repos.clone()).await;
+
+    let owner = create_user(&repos, "owner");
+    let plan = create_plan(&app, &owner, "Grand tour").await;
+    let plan_id = plan.travel_plan.id.clone();
+    let legs_uri = format!("/api/travelplan/{}/legs", plan_id);
+
+    let req = test::TestRequest::post()
+        .uri(&legs_uri)
+        .insert_header(bearer(&owner))
+        .set_json(json!({
+            "startLocation": "Lisbon",
+            "endLocation": "Madrid",
+            "startDate": "2030-05-31T09:00:00Z"
+        }))
+        .to_request();
+    // Before the plan starts
+    assert_eq!(test::call_service(&app, req).await.status().as_u16(), 400);
+
+    let mut legs = Vec::new();
+    for (from, to, mode) in [("Lisbon", "Madrid", "transit"), ("Madrid", "Rome", "flight")] {
+        let req = test::TestRequest::post()
+            .uri(&legs_uri)
+            .insert_header(bearer(&owner))
+            .set_json(json!({ "startLocation": from, "endLocation": to, "mode": mode }))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status().as_u16(), 201);
+        let leg: Value = test::read_body_json(resp).await;
+        legs.push(leg);
+    }
+    assert_eq!(legs[0]["position"], 0);
+    assert_eq!(legs[1]["position"], 1);
+    assert_eq!(legs[1]["mode"], "flight");
+
+    // Each leg gets its own routes, apart from the plan's
+    generate_routes(&app, &owner, &plan_id, 1).await;
+    let mut leg_routes = Vec::new();
+    for leg in &legs {
+        let leg_uri = format!("{}/{}", legs_uri, leg["id"].as_str().unwrap());
+        let req = test::TestRequest::post()
+            .uri(&format!("{}/routes/generate?count=2", leg_uri))
+            .insert_header(bearer(&owner))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status().as_u16(), 200);
+
+        let req = test::TestRequest::get()
+            .uri(&format!("{}/routes", leg_uri))
+            .insert_header(bearer(&owner))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status().as_u16(), 200);
+        let routes: Vec<Value> = test::read_body_json(resp).await;
+        assert_eq!(routes.len(), 2);
+        assert!(routes.iter().all(|r| r["route"]["legId"] == leg["id"]));
+        let description = routes[0]["route"]["description"].as_str().unwrap();
+        assert!(description.contains(leg["startLocation"].as_str().unwrap()));
+        leg_routes.push(routes);
+    }
+
+    // Pick the first leg's slower route
+    let slower = leg_routes[0]
+        .iter()
+        .max_by_key(|r| r["route"]["duration"].as_i64())
+        .unwrap()["route"]
+        .clone();
+    let first_leg_uri = format!("{}/{}", legs_uri, legs[0]["id"].as_str().unwrap());
+    let req = test::TestRequest::patch()
+        .uri(&first_leg_uri)
+        .insert_header(bearer(&owner))
+        .insert_header((header::CONTENT_TYPE, "application/merge-patch+json"))
+        .set_payload(json!({ "selectedRouteId": leg_routes[1][0]["route"]["id"] }).to_string())
+        .to_request();
+    // A route of another leg
+    assert_eq!(test::call_service(&app, req).await.status().as_u16(), 400);
+
+    let req = test::TestRequest::patch()
+        .uri(&first_leg_uri)
+        .insert_header(bearer(&owner))
+        .insert_header((header::CONTENT_TYPE, "application/merge-patch+json"))
+        .set_payload(json!({ "selectedRouteId": slower["id"] }).to_string())
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status().as_u16(), 200);
+    let patched: Value = test::read_body_json(resp).await;
+    assert_eq!(patched["selectedRouteId"], slower["id"]);
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/travelplan/{}/summary", plan_id))
+        .insert_header(bearer(&owner))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status().as_u16(), 200);
+    let summary: Value = test::read_body_json(resp).await;
+    let summary_legs = summary["legs"].as_array().unwrap();
+    assert_eq!(summary_legs.len(), 2);
+    assert_eq!(summary_legs[0]["routeOptionId"], slower["id"]);
+    assert_eq!(summary_legs[0]["routeSelected"], true);
+    assert_eq!(summary_legs[1]["routeSelected"], false);
+    assert_eq!(summary["complete"], false);
+    let fastest = leg_routes[1]
+        .iter()
+        .map(|r| r["route"]["duration"].as_i64().unwrap())
+        .min()
+        .unwrap();
+    assert_eq!(summary_legs[1]["duration"], fastest);
+    let total: i64 = summary_legs.iter().map(|l| l["duration"].as_i64().unwrap()).sum();
+    assert_eq!(summary["totalDuration"], total);
+
+    // A leg's selected route can't be deleted
+    let req = test::TestRequest::delete()
+        .uri(&format!("/api/travelplan/{}/routes/{}", plan_id, slower["id"].as_str().unwrap()))
+        .insert_header(bearer(&owner))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status().as_u16(), 409);
+}
+
+#[actix_web::test]
+async fn test_plan_legs_can_be_reordered_and_removed() {
+    let repos = test_repositories();
+    let app = init_app(repos.clone()).await;
+
+    let owner = create_user(&repos, "owner");
+    let viewer = create_user(&repos, "viewer");
+    let plan = create_plan(&app, &owner, "Island hopping").await;
+    let plan_id = plan.travel_plan.id.clone();
+    add_member(&app, &owner, &viewer, &plan_id, "viewer").await;
+    let legs_uri = format!("/api/travelplan/{}/legs", plan_id);
+
+    let mut leg_ids = Vec::new();
+    for (from, to) in [("Athens", "Naxos"), ("Naxos", "Santorini"), ("Santorini", "Crete")] {
+        let req = test::TestRequest::post()
+            .uri(&legs_uri)
+            .insert_header(bearer(&owner))
+            .set_json(json!({ "startLocation": from, "endLocation": to }))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status().as_u16(), 201);
+        let leg: Value = test::read_body_json(resp).await;
+        leg_ids.push(leg["id"].as_str().unwrap().to_string());
+    }
+
+    let req = test::TestRequest::post()
+        .uri(&legs_uri)
+        .insert_header(bearer(&viewer))
+        .set_json(json!({ "startLocation": "Crete", "endLocation": "Athens" }))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status().as_u16(), 403);
+
+    let req = test::TestRequest::put()
+        .uri(&format!("{}/order", legs_uri))
+        .insert_header(bearer(&owner))
+        .set_json(json!({ "legIds": [leg_ids[2], leg_ids[0]] }))
+        .to_request();
+    // Every leg must be listed
+    assert_eq!(test::call_service(&app, req).await.status().as_u16(), 400);
+
+    let req = test::TestRequest::put()
+        .uri(&format!("{}/order", legs_uri))
+        .insert_header(bearer(&owner))
+        .set_json(json!({ "legIds": [leg_ids[2], leg_ids[0], leg_ids[1]] }))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status().as_u16(), 200);
+
+    let leg_uri = format!("{}/{}", legs_uri, leg_ids[0]);
+    let req = test::TestRequest::post()
+        .uri(&format!("{}/routes/generate?count=2", leg_uri))
+        .insert_header(bearer(&owner))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status().as_u16(), 200);
+
+    // Removing a leg takes its routes with it
+    let req = test::TestRequest::delete()
+        .uri(&leg_uri)
+        .insert_header(bearer(&owner))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status().as_u16(), 204);
+    let req = test::TestRequest::get()
+        .uri(&leg_uri)
+        .insert_header(bearer(&owner))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status().as_u16(), 404);
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/travelplan/{}/routes", plan_id))
+        .insert_header(bearer(&owner))
+        .to_request();
+    let routes: Vec<Value> = test::read_body_json(test::call_service(&app, req).await).await;
+    assert!(routes.is_empty());
+
+    let req = test::TestRequest::get()
+        .uri(&legs_uri)
+        .insert_header(bearer(&viewer))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status().as_u16(), 200);
+    let legs: Vec<Value> = test::read_body_json(resp).await;
+    let order: Vec<&str> = legs.iter().map(|l| l["id"].as_str().unwrap()).collect();
+    assert_eq!(order, [leg_ids[2].as_str(), leg_ids[1].as_str()]);
+
+    // Deleting the plan takes the remaining legs and their routes with it
+    let req = test::TestRequest::post()
+        .uri(&format!("{}/{}/routes/generate?count=2", legs_uri, leg_ids[1]))
+        .insert_header(bearer(&owner))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status().as_u16(), 200);
+    let req = test::TestRequest::delete()
+        .uri(&format!("/api/travelplan/{}", plan_id))
+        .insert_header(bearer(&owner))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status().as_u16(), 204);
+    assert!(off_runtime(|| repos.find_plan_legs(&plan_id)).unwrap().is_empty());
+    assert!(off_runtime(|| repos.find_route_options_by_plan(&plan_id)).unwrap().is_empty());
+}
@@ -0,0 +1,113 @@
+use actix_web::test;
+use serde_json::{Value, json};
+
+use crate::tests::common::{
+    add_member, bearer, create_plan, create_user, init_app, off_runtime, test_repositories,
+};
+
+#[actix_web::test]
+async fn test_plan_revision_history() {
+    let repos = test_repositories();
+    let app = init_app(repos.clone()).await;
+
+    let owner = create_user(&repos, "owner");
+    let viewer = create_user(&repos, "viewer");
+    let plan = create_plan(&app, &owner, "First draft").await;
+    let plan_id = plan.travel_plan.id;
+    add_member(&app, &owner, &viewer, &plan_id, "viewer").await;
+    let plan_uri = format!("/api/travelplan/{}", plan_id);
+
+    for update in [
+        json!({ "name": "Second draft" }),
+        json!({ "endDate": "2030-06-20T18:00:00Z" }),
+        json!({ "homeCurrency": "CHF" }),
+    ] {
+        let req = test::TestRequest::put()
+            .uri(&plan_uri)
+            .insert_header(bearer(&owner))
+            .set_json(update)
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status().as_u16(), 200);
+    }
+
+    let req = test::TestRequest::get()
+        .uri(&format!("{}/revisions", plan_uri))
+        .insert_header(bearer(&viewer))
+        .to_request();
+    let history: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(history["currentRevision"], 4);
+    assert_eq!(history["revisions"][0]["revision"], 3);
+    assert_eq!(history["revisions"][0]["name"], "Second draft");
+    assert_eq!(history["revisions"][0]["homeCurrency"], Value::Null);
+    assert_eq!(history["revisions"][2]["name"], "First draft");
+
+    let req = test::TestRequest::get()
+        .uri(&format!("{}/revisions/1", plan_uri))
+        .insert_header(bearer(&viewer))
+        .to_request();
+    let first: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(first["endDate"], "2030-06-10T18:00:00Z");
+    assert_eq!(first["replacedBy"], owner.id.as_str());
+
+    let req = test::TestRequest::get()
+        .uri(&format!("{}/revisions/diff?from=1", plan_uri))
+        .insert_header(bearer(&viewer))
+        .to_request();
+    let diff: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(diff["to"], 4);
+    assert_eq!(
+        diff["changes"],
+        json!({
+            "name": { "from": "First draft", "to": "Second draft" },
+            "endDate": { "from": "2030-06-10T18:00:00Z", "to": "2030-06-20T18:00:00Z" },
+            "homeCurrency": { "from": null, "to": "CHF" },
+        })
+    );
+
+    let req = test::TestRequest::get()
+        .uri(&format!("{}/revisions/diff?from=1&to=9", plan_uri))
+        .insert_header(bearer(&viewer))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status().as_u16(), 404);
+
+    // Viewers can look back but not restore
+    let restore_uri = format!("{}/revisions/1/restore", plan_uri);
+    let req = test::TestRequest::post()
+        .uri(&restore_uri)
+        .insert_header(bearer(&viewer))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status().as_u16(), 403);
+
+    let req = test::TestRequest::post()
+        .uri(&restore_uri)
+        .insert_header(bearer(&owner))
+        .to_request();
+    let restored: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(restored["name"], "First draft");
+    assert_eq!(restored["endDate"], "2030-06-10T18:00:00Z");
+    assert_eq!(restored["homeCurrency"], Value::Null);
+
+    let req = test::TestRequest::get()
+        .uri(&format!("{}/revisions", plan_uri))
+        .insert_header(bearer(&owner))
+        .to_request();
+    let history: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(history["currentRevision"], 5);
+    assert_eq!(history["revisions"][0]["name"], "Second draft");
+    assert_eq!(history["revisions"][0]["endDate"], "2030-06-20T18:00:00Z");
+    assert_eq!(history["revisions"][0]["homeCurrency"], "CHF");
+
+    let req = test::TestRequest::get()
+        .uri(&format!("{}/activity?limit=1", plan_uri))
+        .insert_header(bearer(&owner))
+        .to_request();
+    let events: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(events[0]["action"], "plan_restored");
+
+    let req = test::TestRequest::delete()
+        .uri(&plan_uri)
+        .insert_header(bearer(&owner))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status().as_u16(), 204);
+    assert!(off_runtime(|| repos.find_plan_revisions(&plan_id)).unwrap().is_empty());
+}
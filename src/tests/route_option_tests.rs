@@ -1,257 +1,450 @@
-use actix_web::{test, web, App, http::header};
-use chrono::Utc;
-use rusqlite::Connection;
-use serde_json::json;
-
-use crate::db::schema;
-use crate::middleware::auth::{generate_token, AuthenticatedUser};
-use crate::models::travel_plan::{NewTravelPlan, TravelPlan};
-use crate::models::route_option::RouteOption;
-use crate::models::user::{NewUser, User};
-use crate::routes::auth::register;
-use crate::routes::travel_plan::create_travel_plan;
-use crate::routes::route_option::{
-    generate_route_options, get_route_options, get_route_option_by_id
-};
-
-// Helper function to create a test user and get a token
-async fn create_test_user_and_token(app: &impl actix_web::dev::Service<actix_web::dev::ServiceRequest, Response = actix_web::dev::ServiceResponse, Error = actix_web::Error>, conn: &Connection) -> (String, String) {
-    // Create a test user
-    let user_data = NewUser {
-        username: "testuser".to_string(),
-        password: "password123".to_string(),
-        email: "test@example.com".to_string(),
-    };
-    
-    let user = User::create(conn, &user_data).unwrap();
-    let token = generate_token(&user).unwrap();
-    
-    (user.id, token.token)
-}
+use actix_web::http::header;
+use actix_web::test;
+use serde_json::{Value, json};
 
-// Helper function to create a test travel plan
-async fn create_test_travel_plan(
-    app: &impl actix_web::dev::Service<actix_web::dev::ServiceRequest, Response = actix_web::dev::ServiceResponse, Error = actix_web::Error>,
-    user_id: &str,
-    token: &str
-) -> TravelPlan {
-    // Create test travel plan data
-    let plan_data = NewTravelPlan {
-        user_id: user_id.to_string(),
-        name: "Test Travel Plan".to_string(),
-        description: Some("A test travel plan".to_string()),
-        start_location: "New York".to_string(),
-        end_location: "Los Angeles".to_string(),
-        start_date: Some(Utc::now()),
-        end_date: Some(Utc::now()),
-    };
-    
-    // Send create travel plan request
-    let req = test::TestRequest::post()
-        .uri("/travelplan")
-        .insert_header((header::AUTHORIZATION, format!("Bearer {}", token)))
-        .set_json(&plan_data)
-        .to_request();
-    
-    let resp = test::call_service(app, req).await;
-    
-    // Parse response body
-    let body = test::read_body(resp).await;
-    serde_json::from_slice(&body).unwrap()
-}
+use crate::app::AppState;
+use crate::services::route_cost::CostModel;
+use crate::tests::common::{
+    bearer, create_plan, create_user, generate_routes, init_app, init_app_with_state,
+    test_repositories,
+};
 
 #[actix_web::test]
 async fn test_generate_route_options() {
-    // Set up in-memory database for testing
-    let conn = Connection::open_in_memory().unwrap();
-    schema::initialize_database(&conn).unwrap();
-    let app_data = web::Data::new(conn.clone());
-    
-    // Create test app
-    let app = test::init_service(
-        App::new()
-            .app_data(app_data.clone())
-            .route("/travelplan", web::post().to(create_travel_plan))
-            .route("/travelplan/{id}/routes/generate", web::post().to(generate_route_options))
-    ).await;
-    
-    // Create a test user and get a token
-    let (user_id, token) = create_test_user_and_token(&app, &conn).await;
-    
-    // Create a test travel plan
-    let travel_plan = create_test_travel_plan(&app, &user_id, &token).await;
-    
-    // Send generate route options request
-    let req = test::TestRequest::post()
-        .uri(&format!("/travelplan/{}/routes/generate?count=3", travel_plan.id))
-        .insert_header((header::AUTHORIZATION, format!("Bearer {}", token)))
-        .to_request();
-    
-    let resp = test::call_service(&app, req).await;
-    
-    // Assert response is successful
-    assert!(resp.status().is_success());
-    
-    // Parse response body
-    let body = test::read_body(resp).await;
-    let response: Vec<serde_json::Value> = serde_json::from_slice(&body).unwrap();
-    
+    let repos = test_repositories();
+    let app = init_app(repos.clone()).await;
+
+    // Create a test user and travel plan
+    let user = create_user(&repos, "testuser");
+    let travel_plan = create_plan(&app, &user, "Test Travel Plan").await.travel_plan;
+
+    // Generate route options
+    let response = generate_routes(&app, &user, &travel_plan.id, 3).await;
+
     // Assert response contains expected number of route options
     assert_eq!(response.len(), 3);
-    
+
     // Assert each route option has the expected structure
     for route_option in response {
         assert!(route_option.get("route").is_some());
-        assert!(route_option.get("points_of_interest").is_some());
-        
+        assert!(route_option.get("pointsOfInterest").is_some());
+
         let route = route_option.get("route").unwrap();
         assert!(route.get("id").is_some());
-        assert!(route.get("travel_plan_id").is_some());
+        assert!(route.get("travelPlanId").is_some());
         assert!(route.get("name").is_some());
-        assert!(route.get("start_coordinates").is_some());
-        assert!(route.get("end_coordinates").is_some());
-        
+        assert!(route.get("startCoordinates").is_some());
+        assert!(route.get("endCoordinates").is_some());
+
         // Assert the route belongs to the travel plan
-        assert_eq!(route.get("travel_plan_id").unwrap().as_str().unwrap(), travel_plan.id);
-        
+        assert_eq!(route["travelPlanId"], travel_plan.id);
+
         // Assert points of interest are present
-        let pois = route_option.get("points_of_interest").unwrap().as_array().unwrap();
+        let pois = route_option["pointsOfInterest"].as_array().unwrap();
         assert!(!pois.is_empty());
-        
+
         // Assert each point of interest has the expected structure
         for poi in pois {
             assert!(poi.get("id").is_some());
-            assert!(poi.get("route_option_id").is_some());
             assert!(poi.get("name").is_some());
             assert!(poi.get("coordinates").is_some());
-            
+
             // Assert the point of interest belongs to the route
-            assert_eq!(
-                poi.get("route_option_id").unwrap().as_str().unwrap(),
-                route.get("id").unwrap().as_str().unwrap()
-            );
+            assert_eq!(poi["routeOptionId"], route["id"]);
         }
     }
 }
 
 #[actix_web::test]
 async fn test_get_route_options() {
-    // Set up in-memory database for testing
-    let conn = Connection::open_in_memory().unwrap();
-    schema::initialize_database(&conn).unwrap();
-    let app_data = web::Data::new(conn.clone());
-    
-    // Create test app
-    let app = test::init_service(
-        App::new()
-            .app_data(app_data.clone())
-            .route("/travelplan", web::post().to(create_travel_plan))
-            .route("/travelplan/{id}/routes/generate", web::post().to(generate_route_options))
-            .route("/travelplan/{id}/routes", web::get().to(get_route_options))
-    ).await;
-    
-    // Create a test user and get a token
-    let (user_id, token) = create_test_user_and_token(&app, &conn).await;
-    
-    // Create a test travel plan
-    let travel_plan = create_test_travel_plan(&app, &user_id, &token).await;
-    
-    // Generate route options
-    let req = test::TestRequest::post()
-        .uri(&format!("/travelplan/{}/routes/generate?count=3", travel_plan.id))
-        .insert_header((header::AUTHORIZATION, format!("Bearer {}", token)))
-        .to_request();
-    
-    let _ = test::call_service(&app, req).await;
-    
+    let repos = test_repositories();
+    let app = init_app(repos.clone()).await;
+
+    let user = create_user(&repos, "testuser");
+    let travel_plan = create_plan(&app, &user, "Test Travel Plan").await.travel_plan;
+    generate_routes(&app, &user, &travel_plan.id, 3).await;
+
     // Send get route options request
     let req = test::TestRequest::get()
-        .uri(&format!("/travelplan/{}/routes", travel_plan.id))
-        .insert_header((header::AUTHORIZATION, format!("Bearer {}", token)))
+        .uri(&format!("/api/travelplan/{}/routes", travel_plan.id))
+        .insert_header(bearer(&user))
         .to_request();
-    
+
     let resp = test::call_service(&app, req).await;
-    
+
     // Assert response is successful
     assert!(resp.status().is_success());
-    
+
     // Parse response body
-    let body = test::read_body(resp).await;
-    let response: Vec<serde_json::Value> = serde_json::from_slice(&body).unwrap();
-    
+    let response: Vec<serde_json::Value> = test::read_body_json(resp).await;
+
     // Assert response contains expected number of route options
     assert_eq!(response.len(), 3);
+
+    // The plan now reports generated routes
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/travelplan/{}", travel_plan.id))
+        .insert_header(bearer(&user))
+        .to_request();
+    let plan: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(plan["hasRoutesGenerated"], true);
 }
 
 #[actix_web::test]
 async fn test_get_route_option_by_id() {
-    // Set up in-memory database for testing
-    let conn = Connection::open_in_memory().unwrap();
-    schema::initialize_database(&conn).unwrap();
-    let app_data = web::Data::new(conn.clone());
-    
-    // Create test app
-    let app = test::init_service(
-        App::new()
-            .app_data(app_data.clone())
-            .route("/travelplan", web::post().to(create_travel_plan))
-            .route("/travelplan/{id}/routes/generate", web::post().to(generate_route_options))
-            .route("/travelplan/{plan_id}/routes/{route_id}", web::get().to(get_route_option_by_id))
-    ).await;
-    
-    // Create a test user and get a token
-    let (user_id, token) = create_test_user_and_token(&app, &conn).await;
-    
-    // Create a test travel plan
-    let travel_plan = create_test_travel_plan(&app, &user_id, &token).await;
-    
-    // Generate route options
-    let req = test::TestRequest::post()
-        .uri(&format!("/travelplan/{}/routes/generate?count=1", travel_plan.id))
-        .insert_header((header::AUTHORIZATION, format!("Bearer {}", token)))
-        .to_request();
-    
-    let resp = test::call_service(&app, req).await;
-    
-    // Parse response body to get the generated route option
-    let body = test::read_body(resp).await;
-    let response: Vec<serde_json::Value> = serde_json::from_slice(&body).unwrap();
-    
+    let repos = test_repositories();
+    let app = init_app(repos.clone()).await;
+
+    let user = create_user(&repos, "testuser");
+    let travel_plan = create_plan(&app, &user, "Test Travel Plan").await.travel_plan;
+    let generated = generate_routes(&app, &user, &travel_plan.id, 1).await;
+
     // Get the first route option
-    let route_option = response[0].get("route").unwrap();
-    let route_id = route_option.get("id").unwrap().as_str().unwrap();
-    
+    let route_id = generated[0]["route"]["id"].as_str().unwrap().to_string();
+
     // Send get route option by ID request
     let req = test::TestRequest::get()
-        .uri(&format!("/travelplan/{}/routes/{}", travel_plan.id, route_id))
-        .insert_header((header::AUTHORIZATION, format!("Bearer {}", token)))
+        .uri(&format!("/api/travelplan/{}/routes/{}", travel_plan.id, route_id))
+        .insert_header(bearer(&user))
         .to_request();
-    
+
     let resp = test::call_service(&app, req).await;
-    
+
     // Assert response is successful
     assert!(resp.status().is_success());
-    
+
     // Parse response body
-    let body = test::read_body(resp).await;
-    let response: serde_json::Value = serde_json::from_slice(&body).unwrap();
-    
+    let response: serde_json::Value = test::read_body_json(resp).await;
+
     // Assert response contains expected fields
     assert!(response.get("route").is_some());
-    assert!(response.get("points_of_interest").is_some());
-    
-    let route = response.get("route").unwrap();
-    assert_eq!(route.get("id").unwrap().as_str().unwrap(), route_id);
-    assert_eq!(route.get("travel_plan_id").unwrap().as_str().unwrap(), travel_plan.id);
-    
+    assert!(response.get("pointsOfInterest").is_some());
+    assert_eq!(response["route"]["id"], route_id.as_str());
+    assert_eq!(response["route"]["travelPlanId"], travel_plan.id);
+
     // Try to get a non-existent route option
     let req = test::TestRequest::get()
-        .uri(&format!("/travelplan/{}/routes/nonexistent-id", travel_plan.id))
-        .insert_header((header::AUTHORIZATION, format!("Bearer {}", token)))
+        .uri(&format!("/api/travelplan/{}/routes/nonexistent-id", travel_plan.id))
+        .insert_header(bearer(&user))
         .to_request();
-    
+
     let resp = test::call_service(&app, req).await;
-    
+
     // Assert response is not found
     assert_eq!(resp.status().as_u16(), 404);
-}
\ No newline at end of file
+}
+
+#[actix_web::test]
+async fn test_delete_route_options() {
+    let repos = test_repositories();
+    let app = init_app(repos.clone()).await;
+
+    let user = create_user(&repos, "testuser");
+    let travel_plan = create_plan(&app, &user, "Test Travel Plan").await.travel_plan;
+    let generated = generate_routes(&app, &user, &travel_plan.id, 3).await;
+    let route_id = generated[0]["route"]["id"].as_str().unwrap();
+    let route_uri = format!("/api/travelplan/{}/routes/{}", travel_plan.id, route_id);
+
+    // Delete a single route, then it is gone
+    let req = test::TestRequest::delete()
+        .uri(&route_uri)
+        .insert_header(bearer(&user))
+        .to_request();
+    assert!(test::call_service(&app, req).await.status().is_success());
+
+    let req = test::TestRequest::delete()
+        .uri(&route_uri)
+        .insert_header(bearer(&user))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status().as_u16(), 404);
+
+    // Delete the remaining routes
+    let req = test::TestRequest::delete()
+        .uri(&format!("/api/travelplan/{}/routes", travel_plan.id))
+        .insert_header(bearer(&user))
+        .to_request();
+    assert!(test::call_service(&app, req).await.status().is_success());
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/travelplan/{}/routes", travel_plan.id))
+        .insert_header(bearer(&user))
+        .to_request();
+    let remaining: Vec<serde_json::Value> = test::call_and_read_body_json(&app, req).await;
+    assert!(remaining.is_empty());
+}
+
+#[actix_web::test]
+async fn test_other_users_routes_are_forbidden() {
+    let repos = test_repositories();
+    let app = init_app(repos.clone()).await;
+
+    let owner = create_user(&repos, "owner");
+    let intruder = create_user(&repos, "intruder");
+    let travel_plan = create_plan(&app, &owner, "Private plan").await.travel_plan;
+    let generated = generate_routes(&app, &owner, &travel_plan.id, 1).await;
+    let route_id = generated[0]["route"]["id"].as_str().unwrap();
+
+    let routes_uri = format!("/api/travelplan/{}/routes", travel_plan.id);
+    let route_uri = format!("{}/{}", routes_uri, route_id);
+
+    let requests = vec![
+        test::TestRequest::get().uri(&routes_uri),
+        test::TestRequest::post().uri(&format!("{}/generate", routes_uri)),
+        test::TestRequest::delete().uri(&routes_uri),
+        test::TestRequest::get().uri(&route_uri),
+        test::TestRequest::delete().uri(&route_uri),
+    ];
+
+    for req in requests {
+        let req = req.insert_header(bearer(&intruder)).to_request();
+        assert_eq!(test::call_service(&app, req).await.status().as_u16(), 403);
+    }
+
+    // Nothing was removed
+    let req = test::TestRequest::get()
+        .uri(&routes_uri)
+        .insert_header(bearer(&owner))
+        .to_request();
+    let remaining: Vec<serde_json::Value> = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(remaining.len(), 1);
+}
+
+#[actix_web::test]
+async fn test_routes_of_missing_plan_are_not_found() {
+    let repos = test_repositories();
+    let app = init_app(repos.clone()).await;
+
+    let user = create_user(&repos, "testuser");
+
+    let requests = vec![
+        test::TestRequest::get().uri("/api/travelplan/nonexistent-id/routes"),
+        test::TestRequest::post().uri("/api/travelplan/nonexistent-id/routes/generate"),
+        test::TestRequest::delete().uri("/api/travelplan/nonexistent-id/routes"),
+        test::TestRequest::get().uri("/api/travelplan/nonexistent-id/routes/some-route"),
+        test::TestRequest::delete().uri("/api/travelplan/nonexistent-id/routes/some-route"),
+    ];
+
+    for req in requests {
+        let req = req.insert_header(bearer(&user)).to_request();
+        assert_eq!(test::call_service(&app, req).await.status().as_u16(), 404);
+    }
+}
+
+#[actix_web::test]
+async fn test_optimize_poi_order_saves_waypoints() {
+    let repos = test_repositories();
+    let app = init_app(repos.clone()).await;
+
+    let user = create_user(&repos, "testuser");
+    let travel_plan = create_plan(&app, &user, "Test Travel Plan").await.travel_plan;
+    let generated = generate_routes(&app, &user, &travel_plan.id, 1).await;
+    let route_id = generated[0]["route"]["id"].as_str().unwrap();
+    let poi_ids: Vec<&str> = generated[0]["pointsOfInterest"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|poi| poi["id"].as_str().unwrap())
+        .collect();
+    let optimize_uri =
+        format!("/api/travelplan/{}/routes/{}/optimize", travel_plan.id, route_id);
+
+    let req = test::TestRequest::post()
+        .uri(&optimize_uri)
+        .insert_header(bearer(&user))
+        .set_json(json!({ "poiIds": [poi_ids[0], poi_ids[0]] }))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status().as_u16(), 400);
+
+    let req = test::TestRequest::post()
+        .uri(&optimize_uri)
+        .insert_header(bearer(&user))
+        .set_json(json!({ "poiIds": ["nonexistent-id"] }))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status().as_u16(), 404);
+
+    let req = test::TestRequest::post()
+        .uri(&optimize_uri)
+        .insert_header(bearer(&user))
+        .set_json(json!({ "objective": "time", "mode": "cycling" }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status().as_u16(), 200);
+    let etag = resp.headers().get(header::ETAG).unwrap().to_str().unwrap().to_string();
+    let order: Value = test::read_body_json(resp).await;
+    assert_eq!(order["solver"], "exact");
+    assert_eq!(order["poiIds"].as_array().unwrap().len(), poi_ids.len());
+    let total = order["totalDistance"].as_f64().unwrap();
+    assert!(total <= order["givenOrderDistance"].as_f64().unwrap() + 1e-9);
+
+    // The order is saved as the route's waypoints, one per stop
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/travelplan/{}/routes/{}", travel_plan.id, route_id))
+        .insert_header(bearer(&user))
+        .to_request();
+    let route: Value = test::read_body_json(test::call_service(&app, req).await).await;
+    let waypoints = route["route"]["waypoints"].as_str().unwrap();
+    assert_eq!(waypoints.split(';').count(), poi_ids.len());
+    assert_eq!(route["route"]["waypoints"], order["route"]["waypoints"]);
+
+    let req = test::TestRequest::post()
+        .uri(&optimize_uri)
+        .insert_header(bearer(&user))
+        .insert_header((header::IF_MATCH, "\"stale\""))
+        .set_json(json!({ "poiIds": [poi_ids[1]] }))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status().as_u16(), 412);
+
+    let req = test::TestRequest::post()
+        .uri(&optimize_uri)
+        .insert_header(bearer(&user))
+        .insert_header((header::IF_MATCH, etag))
+        .set_json(json!({ "poiIds": [poi_ids[1]] }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status().as_u16(), 200);
+    let order: Value = test::read_body_json(resp).await;
+    assert_eq!(order["poiIds"], json!([poi_ids[1]]));
+}
+
+#[actix_web::test]
+async fn test_routes_are_priced_and_sorted_by_cost() {
+    let repos = test_repositories();
+    let costs = CostModel::parse(
+        r#"{
+            "currency": "GBP",
+            "fuel": { "litresPer100Km": 10.0, "pricePerLitre": 150 },
+            "transitFares": { "bands": [{ "upToKm": 5000, "fare": 4200 }] }
+        }"#,
+    )
+    .unwrap();
+    let app = init_app_with_state(AppState::new(repos.clone()).with_route_costs(costs)).await;
+
+    let user = create_user(&repos, "traveller");
+    let plan_id = create_plan(&app, &user, "Priced").await.travel_plan.id;
+    let routes_uri = format!("/api/travelplan/{}/routes", plan_id);
+
+    // Whole-plan routes are driven: 10 litres per 100 km at 1.50
+    for generated in generate_routes(&app, &user, &plan_id, 4).await {
+        assert_eq!(generated["mode"], "driving");
+        let cost = &generated["estimatedCost"];
+        let distance = generated["route"]["distance"].as_f64().unwrap();
+        assert_eq!(cost["currency"], "GBP");
+        assert_eq!(cost["fuel"], (distance * 15.0).round() as i64);
+        assert_eq!(cost["total"], cost["fuel"]);
+    }
+
+    let req = test::TestRequest::get()
+        .uri(&format!("{}?sort=cost", routes_uri))
+        .insert_header(bearer(&user))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status().as_u16(), 200);
+    let sorted: Vec<Value> = test::read_body_json(resp).await;
+    let totals: Vec<i64> = sorted
+        .iter()
+        .map(|r| r["estimatedCost"]["total"].as_i64().unwrap())
+        .collect();
+    assert_eq!(totals.len(), 4);
+    assert!(totals.windows(2).all(|pair| pair[0] <= pair[1]));
+
+    let req = test::TestRequest::get()
+        .uri(&format!("{}?sort=price", routes_uri))
+        .insert_header(bearer(&user))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status().as_u16(), 400);
+
+    // A transit leg pays its fare instead
+    let req = test::TestRequest::post()
+        .uri(&format!("/api/travelplan/{}/legs", plan_id))
+        .insert_header(bearer(&user))
+        .set_json(json!({ "startLocation": "London", "endLocation": "Paris", "mode": "transit" }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status().as_u16(), 201);
+    let leg: Value = test::read_body_json(resp).await;
+    let req = test::TestRequest::post()
+        .uri(&format!(
+            "/api/travelplan/{}/legs/{}/routes/generate?count=2",
+            plan_id,
+            leg["id"].as_str().unwrap()
+        ))
+        .insert_header(bearer(&user))
+        .to_request();
+    let leg_routes: Vec<Value> = test::read_body_json(test::call_service(&app, req).await).await;
+    for generated in &leg_routes {
+        assert_eq!(generated["mode"], "transit");
+        assert_eq!(generated["estimatedCost"]["fares"], 4200);
+        assert_eq!(generated["estimatedCost"]["fuel"], 0);
+    }
+}
+
+#[actix_web::test]
+async fn test_compare_routes_side_by_side() {
+    let repos = test_repositories();
+    let app = init_app(repos.clone()).await;
+
+    let user = create_user(&repos, "traveller");
+    let stranger = create_user(&repos, "stranger");
+    let plan_id = create_plan(&app, &user, "Choices").await.travel_plan.id;
+    let routes = generate_routes(&app, &user, &plan_id, 3).await;
+    let ids: Vec<&str> = routes.iter().map(|r| r["route"]["id"].as_str().unwrap()).collect();
+    let compare_uri = |ids: &str| format!("/api/travelplan/{}/routes/compare?ids={}", plan_id, ids);
+
+    // Repeated ids count once
+    let req = test::TestRequest::get()
+        .uri(&compare_uri(&format!("{},{},%20{},{}", ids[2], ids[0], ids[1], ids[0])))
+        .insert_header(bearer(&user))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status().as_u16(), 200);
+    let comparison: Value = test::read_body_json(resp).await;
+    let compared = comparison["routes"].as_array().unwrap();
+    let compared_ids: Vec<&str> = compared.iter().map(|r| r["routeId"].as_str().unwrap()).collect();
+    assert_eq!(compared_ids, [ids[2], ids[0], ids[1]]);
+    assert_eq!(comparison["overlaps"].as_array().unwrap().len(), 3);
+    for overlap in comparison["overlaps"].as_array().unwrap() {
+        let share = overlap["share"].as_f64().unwrap();
+        assert!((0.0..=1.0).contains(&share));
+    }
+
+    let shortest = compared
+        .iter()
+        .map(|r| r["distance"].as_f64().unwrap())
+        .fold(f64::INFINITY, f64::min);
+    for route in compared {
+        let best_for = route["bestFor"].as_array().unwrap();
+        if route["distance"].as_f64() == Some(shortest) {
+            assert!(best_for.contains(&json!("distance")));
+        } else {
+            assert!(!best_for.contains(&json!("distance")));
+        }
+        assert!(route["estimatedCost"]["total"].is_i64());
+        assert!(route["co2eKg"].is_f64());
+        let counted: u64 = route["poiCategories"]
+            .as_object()
+            .unwrap()
+            .values()
+            .map(|count| count.as_u64().unwrap())
+            .sum();
+        assert_eq!(route["pointsOfInterest"].as_u64(), Some(counted));
+    }
+
+    let req = test::TestRequest::get()
+        .uri(&compare_uri(ids[0]))
+        .insert_header(bearer(&user))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status().as_u16(), 400);
+
+    let req = test::TestRequest::get()
+        .uri(&compare_uri(&format!("{},nonexistent", ids[0])))
+        .insert_header(bearer(&user))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status().as_u16(), 404);
+
+    let req = test::TestRequest::get()
+        .uri(&compare_uri(&format!("{},{}", ids[0], ids[1])))
+        .insert_header(bearer(&stranger))
+        .to_request();
+    let status = test::call_service(&app, req).await.status().as_u16();
+    assert!(status == 403 || status == 404, "{}", status);
+}
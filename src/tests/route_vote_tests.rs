@@ -0,0 +1,155 @@
+use actix_web::test;
+use serde_json::{Value, json};
+
+use crate::services::route_vote_service::VoteTally;
+use crate::services::travel_plan_service::TravelPlanDto;
+use crate::tests::common::{
+    add_member, bearer, create_plan, create_user, generate_routes, init_app, test_repositories,
+};
+
+fn route_ids(routes: &[Value]) -> Vec<String> {
+    routes
+        .iter()
+        .map(|r| r["route"]["id"].as_str().unwrap().to_string())
+        .collect()
+}
+
+#[actix_web::test]
+async fn test_members_vote_on_routes() {
+    let repos = test_repositories();
+    let app = init_app(repos.clone()).await;
+
+    let owner = create_user(&repos, "owner");
+    let viewer = create_user(&repos, "viewer");
+    let outsider = create_user(&repos, "outsider");
+    let plan = create_plan(&app, &owner, "Group trip").await;
+    let plan_id = plan.travel_plan.id;
+    add_member(&app, &owner, &viewer, &plan_id, "viewer").await;
+    let routes = route_ids(&generate_routes(&app, &owner, &plan_id, 2).await);
+    let vote_uri = |route_id: &str| format!("/api/travelplan/{}/routes/{}/vote", plan_id, route_id);
+
+    for (user, route_id, direction) in [
+        (&owner, &routes[1], "up"),
+        (&viewer, &routes[1], "down"),
+        (&viewer, &routes[1], "up"),
+        (&viewer, &routes[0], "down"),
+    ] {
+        let req = test::TestRequest::put()
+            .uri(&vote_uri(route_id))
+            .insert_header(bearer(user))
+            .set_json(json!({ "direction": direction }))
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status().as_u16(), 200);
+    }
+
+    let req = test::TestRequest::put()
+        .uri(&vote_uri(&routes[0]))
+        .insert_header(bearer(&outsider))
+        .set_json(json!({ "direction": "up" }))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status().as_u16(), 403);
+
+    let req = test::TestRequest::put()
+        .uri(&vote_uri("unknown-route"))
+        .insert_header(bearer(&owner))
+        .set_json(json!({ "direction": "up" }))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status().as_u16(), 404);
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/travelplan/{}/votes", plan_id))
+        .insert_header(bearer(&viewer))
+        .to_request();
+    let tally: Value = test::call_and_read_body_json(&app, req).await;
+    assert!(tally["selectedRouteId"].is_null());
+    assert_eq!(tally["routes"][0]["routeId"], routes[1].as_str());
+    assert_eq!(tally["routes"][0]["up"], 2);
+    assert_eq!(tally["routes"][0]["yourVote"], "up");
+    assert_eq!(tally["routes"][1]["score"], -1);
+
+    let req = test::TestRequest::delete()
+        .uri(&vote_uri(&routes[0]))
+        .insert_header(bearer(&viewer))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status().as_u16(), 204);
+
+    let req = test::TestRequest::delete()
+        .uri(&vote_uri(&routes[0]))
+        .insert_header(bearer(&viewer))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status().as_u16(), 404);
+}
+
+#[actix_web::test]
+async fn test_selected_route_is_locked_in() {
+    let repos = test_repositories();
+    let app = init_app(repos.clone()).await;
+
+    let owner = create_user(&repos, "owner");
+    let editor = create_user(&repos, "editor");
+    let plan = create_plan(&app, &owner, "Locked trip").await;
+    let plan_id = plan.travel_plan.id;
+    add_member(&app, &owner, &editor, &plan_id, "editor").await;
+    let routes = route_ids(&generate_routes(&app, &owner, &plan_id, 3).await);
+    let select_uri = format!("/api/travelplan/{}/selected-route", plan_id);
+    let routes_uri = format!("/api/travelplan/{}/routes", plan_id);
+
+    let req = test::TestRequest::put()
+        .uri(&select_uri)
+        .insert_header(bearer(&editor))
+        .set_json(json!({ "routeId": routes[2] }))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status().as_u16(), 403);
+
+    let req = test::TestRequest::put()
+        .uri(&select_uri)
+        .insert_header(bearer(&owner))
+        .set_json(json!({ "routeId": routes[2] }))
+        .to_request();
+    let selected: TravelPlanDto = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(selected.travel_plan.selected_route_id.as_deref(), Some(routes[2].as_str()));
+
+    // Editing the plan keeps the selection
+    let req = test::TestRequest::put()
+        .uri(&format!("/api/travelplan/{}", plan_id))
+        .insert_header(bearer(&owner))
+        .set_json(json!({ "name": "Renamed trip" }))
+        .to_request();
+    let updated: TravelPlanDto = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(updated.travel_plan.selected_route_id.as_deref(), Some(routes[2].as_str()));
+
+    let req = test::TestRequest::delete()
+        .uri(&format!("{}/{}", routes_uri, routes[2]))
+        .insert_header(bearer(&editor))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status().as_u16(), 409);
+
+    let req = test::TestRequest::delete()
+        .uri(&routes_uri)
+        .insert_header(bearer(&editor))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status().as_u16(), 200);
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/travelplan/{}/votes", plan_id))
+        .insert_header(bearer(&editor))
+        .to_request();
+    let tally: VoteTally = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(tally.routes.len(), 1);
+    assert_eq!(tally.routes[0].route_id, routes[2]);
+    assert!(tally.routes[0].selected);
+
+    // Clearing the selection unlocks the route again
+    let req = test::TestRequest::put()
+        .uri(&select_uri)
+        .insert_header(bearer(&owner))
+        .set_json(json!({ "routeId": null }))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status().as_u16(), 200);
+
+    let req = test::TestRequest::delete()
+        .uri(&format!("{}/{}", routes_uri, routes[2]))
+        .insert_header(bearer(&editor))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status().as_u16(), 200);
+}
@@ -0,0 +1,119 @@
+use actix_web::test;
+use serde_json::{Value, json};
+
+use crate::models::share_link::ShareLink;
+use crate::tests::common::{
+    bearer, create_plan, create_user, generate_routes, init_app, test_repositories,
+};
+
+#[actix_web::test]
+async fn test_public_share_link_lifecycle() {
+    let repos = test_repositories();
+    let app = init_app(repos.clone()).await;
+
+    let owner = create_user(&repos, "owner");
+    let plan = create_plan(&app, &owner, "Public trip").await;
+    generate_routes(&app, &owner, &plan.travel_plan.id, 2).await;
+    let links_uri = format!("/api/travelplan/{}/share-links", plan.travel_plan.id);
+
+    let req = test::TestRequest::post()
+        .uri(&links_uri)
+        .insert_header(bearer(&owner))
+        .set_json(json!({ "includePois": true }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status().as_u16(), 201);
+    let link: ShareLink = test::read_body_json(resp).await;
+    assert!(link.include_routes);
+
+    // Anyone with the token can read the plan, without owner details
+    let shared_uri = format!("/api/shared/{}", link.token);
+    let req = test::TestRequest::get().uri(&shared_uri).to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status().as_u16(), 200);
+    let shared: Value = test::read_body_json(resp).await;
+    assert_eq!(shared["name"], "Public trip");
+    assert!(shared.get("userId").is_none());
+    assert!(shared.get("id").is_none());
+    let routes = shared["routes"].as_array().unwrap();
+    assert_eq!(routes.len(), 2);
+    assert!(routes[0].get("travelPlanId").is_none());
+    assert!(routes[0]["pointsOfInterest"].is_array());
+
+    let req = test::TestRequest::get()
+        .uri(&links_uri)
+        .insert_header(bearer(&owner))
+        .to_request();
+    let links: Vec<ShareLink> = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(links.len(), 1);
+    assert_eq!(links[0].access_count, 1);
+
+    let req = test::TestRequest::delete()
+        .uri(&format!("{}/{}", links_uri, link.id))
+        .insert_header(bearer(&owner))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status().as_u16(), 204);
+
+    let req = test::TestRequest::get().uri(&shared_uri).to_request();
+    assert_eq!(test::call_service(&app, req).await.status().as_u16(), 410);
+
+    let req = test::TestRequest::get().uri("/api/shared/unknown").to_request();
+    assert_eq!(test::call_service(&app, req).await.status().as_u16(), 404);
+}
+
+#[actix_web::test]
+async fn test_share_links_are_owner_only() {
+    let repos = test_repositories();
+    let app = init_app(repos.clone()).await;
+
+    let owner = create_user(&repos, "owner");
+    let other = create_user(&repos, "other");
+    let plan = create_plan(&app, &owner, "Private trip").await;
+    let links_uri = format!("/api/travelplan/{}/share-links", plan.travel_plan.id);
+
+    let req = test::TestRequest::post()
+        .uri(&links_uri)
+        .insert_header(bearer(&other))
+        .set_json(json!({}))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status().as_u16(), 403);
+
+    let req = test::TestRequest::post()
+        .uri(&links_uri)
+        .set_json(json!({}))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status().as_u16(), 401);
+
+    let req = test::TestRequest::post()
+        .uri(&links_uri)
+        .insert_header(bearer(&owner))
+        .set_json(json!({ "expiresAt": "2001-01-01T00:00:00Z" }))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status().as_u16(), 400);
+
+    // Without routes, the shared view leaves them out entirely
+    let req = test::TestRequest::post()
+        .uri(&links_uri)
+        .insert_header(bearer(&owner))
+        .set_json(json!({ "includeRoutes": false, "expiresAt": "2100-01-01T00:00:00Z" }))
+        .to_request();
+    let link: ShareLink = test::call_and_read_body_json(&app, req).await;
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/shared/{}", link.token))
+        .to_request();
+    let shared: Value = test::call_and_read_body_json(&app, req).await;
+    assert!(shared.get("routes").is_none());
+
+    // Deleting the plan takes its links with it
+    let req = test::TestRequest::delete()
+        .uri(&format!("/api/travelplan/{}", plan.travel_plan.id))
+        .insert_header(bearer(&owner))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status().as_u16(), 204);
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/shared/{}", link.token))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status().as_u16(), 404);
+}
@@ -0,0 +1,179 @@
+use actix_web::test;
+use serde_json::{Value, json};
+
+use crate::models::plan_member::PlanRole;
+use crate::services::travel_plan_service::TravelPlanDto;
+use crate::tests::common::{
+    bearer, create_plan, create_user, generate_routes, init_app, plan_body, test_repositories,
+};
+
+#[actix_web::test]
+async fn test_shared_plan_access_follows_member_role() {
+    let repos = test_repositories();
+    let app = init_app(repos.clone()).await;
+
+    let owner = create_user(&repos, "owner");
+    let viewer = create_user(&repos, "viewer");
+    let editor = create_user(&repos, "editor");
+    let plan = create_plan(&app, &owner, "Shared trip").await;
+    let plan_uri = format!("/api/travelplan/{}", plan.travel_plan.id);
+    let members_uri = format!("{}/members", plan_uri);
+
+    // Invite one member by username and one by email
+    for body in [
+        json!({ "username": "viewer", "role": "viewer" }),
+        json!({ "email": "editor@example.com", "role": "editor" }),
+    ] {
+        let req = test::TestRequest::post()
+            .uri(&members_uri)
+            .insert_header(bearer(&owner))
+            .set_json(body)
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status().as_u16(), 201);
+    }
+
+    // Nothing is shared until the invitation is accepted
+    let req = test::TestRequest::get()
+        .uri(&plan_uri)
+        .insert_header(bearer(&viewer))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status().as_u16(), 403);
+
+    let req = test::TestRequest::get()
+        .uri("/api/invitations")
+        .insert_header(bearer(&viewer))
+        .to_request();
+    let invitations: Vec<Value> = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(invitations.len(), 1);
+    assert_eq!(invitations[0]["planName"], "Shared trip");
+    assert_eq!(invitations[0]["status"], "pending");
+
+    for member in [&viewer, &editor] {
+        let req = test::TestRequest::post()
+            .uri(&format!("{}/accept", members_uri))
+            .insert_header(bearer(member))
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status().as_u16(), 200);
+    }
+
+    // Viewers can read but not change the plan
+    let req = test::TestRequest::get()
+        .uri(&plan_uri)
+        .insert_header(bearer(&viewer))
+        .to_request();
+    let viewed: TravelPlanDto = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(viewed.role, PlanRole::Viewer);
+
+    let req = test::TestRequest::put()
+        .uri(&plan_uri)
+        .insert_header(bearer(&viewer))
+        .set_json(plan_body("Renamed by viewer"))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status().as_u16(), 403);
+
+    // Editors can change the plan and its routes, but not delete or share it
+    let req = test::TestRequest::put()
+        .uri(&plan_uri)
+        .insert_header(bearer(&editor))
+        .set_json(plan_body("Renamed by editor"))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status().as_u16(), 200);
+    assert_eq!(generate_routes(&app, &editor, &plan.travel_plan.id, 2).await.len(), 2);
+
+    let req = test::TestRequest::post()
+        .uri(&members_uri)
+        .insert_header(bearer(&editor))
+        .set_json(json!({ "username": "viewer", "role": "editor" }))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status().as_u16(), 403);
+
+    let req = test::TestRequest::delete()
+        .uri(&plan_uri)
+        .insert_header(bearer(&editor))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status().as_u16(), 403);
+
+    // Shared plans show up in the member's list with their role
+    let req = test::TestRequest::get()
+        .uri("/api/travelplan")
+        .insert_header(bearer(&editor))
+        .to_request();
+    let listed: Vec<TravelPlanDto> = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(listed.len(), 1);
+    assert_eq!(listed[0].travel_plan.name, "Renamed by editor");
+    assert_eq!(listed[0].role, PlanRole::Editor);
+
+    let req = test::TestRequest::get()
+        .uri(&members_uri)
+        .insert_header(bearer(&viewer))
+        .to_request();
+    let members: Vec<Value> = test::call_and_read_body_json(&app, req).await;
+    let usernames: Vec<&str> = members.iter().map(|m| m["username"].as_str().unwrap()).collect();
+    assert_eq!(usernames, ["viewer", "editor"]);
+}
+
+#[actix_web::test]
+async fn test_revoking_access() {
+    let repos = test_repositories();
+    let app = init_app(repos.clone()).await;
+
+    let owner = create_user(&repos, "owner");
+    let friend = create_user(&repos, "friend");
+    let plan = create_plan(&app, &owner, "Revocable").await;
+    let plan_uri = format!("/api/travelplan/{}", plan.travel_plan.id);
+    let members_uri = format!("{}/members", plan_uri);
+
+    let req = test::TestRequest::post()
+        .uri(&members_uri)
+        .insert_header(bearer(&owner))
+        .set_json(json!({ "username": "friend", "role": "viewer" }))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status().as_u16(), 201);
+
+    // Inviting twice conflicts; unknown users are not found
+    let req = test::TestRequest::post()
+        .uri(&members_uri)
+        .insert_header(bearer(&owner))
+        .set_json(json!({ "username": "friend", "role": "editor" }))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status().as_u16(), 409);
+
+    let req = test::TestRequest::post()
+        .uri(&members_uri)
+        .insert_header(bearer(&owner))
+        .set_json(json!({ "username": "nobody", "role": "viewer" }))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status().as_u16(), 404);
+
+    let req = test::TestRequest::post()
+        .uri(&format!("{}/accept", members_uri))
+        .insert_header(bearer(&friend))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status().as_u16(), 200);
+
+    // Only owners can remove someone else
+    let req = test::TestRequest::delete()
+        .uri(&format!("{}/{}", members_uri, owner.id))
+        .insert_header(bearer(&friend))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status().as_u16(), 403);
+
+    let req = test::TestRequest::delete()
+        .uri(&format!("{}/{}", members_uri, friend.id))
+        .insert_header(bearer(&owner))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status().as_u16(), 204);
+
+    let req = test::TestRequest::get()
+        .uri(&plan_uri)
+        .insert_header(bearer(&friend))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status().as_u16(), 403);
+
+    let req = test::TestRequest::delete()
+        .uri(&format!("{}/{}", members_uri, friend.id))
+        .insert_header(bearer(&owner))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status().as_u16(), 404);
+}
@@ -1,358 +1,296 @@
-use actix_web::{test, web, App, http::header};
-use chrono::Utc;
-use rusqlite::Connection;
+use actix_web::test;
 use serde_json::json;
 
-use crate::db::schema;
-use crate::middleware::auth::{generate_token, AuthenticatedUser};
-use crate::models::travel_plan::{NewTravelPlan, TravelPlan, UpdateTravelPlan};
+use crate::models::user::Role;
 use crate::services::travel_plan_service::TravelPlanDto;
-use crate::models::user::{NewUser, User};
-use crate::routes::auth::register;
-use crate::routes::travel_plan::{
-    create_travel_plan, delete_travel_plan, get_travel_plan_by_id, get_travel_plans, update_travel_plan
+use crate::tests::common::{
+    bearer, create_plan, create_user, create_user_with_role, init_app, off_runtime, plan_body,
+    test_repositories,
 };
 
-// Helper function to create a test user and get a token
-async fn create_test_user_and_token(app: &impl actix_web::dev::Service<actix_web::dev::ServiceRequest, Response = actix_web::dev::ServiceResponse, Error = actix_web::Error>, conn: &Connection) -> (String, String) {
-    // Create a test user
-    let user_data = NewUser {
-        username: "testuser".to_string(),
-        password: "password123".to_string(),
-        email: "test@example.com".to_string(),
-    };
-    
-    let user = User::create(conn, &user_data).unwrap();
-    let token = generate_token(&user).unwrap();
-    
-    (user.id, token.token)
-}
-
 #[actix_web::test]
 async fn test_create_travel_plan() {
-    // Set up in-memory database for testing
-    let conn = Connection::open_in_memory().unwrap();
-    schema::initialize_database(&conn).unwrap();
-    let app_data = web::Data::new(conn.clone());
-    
-    // Create test app
-    let app = test::init_service(
-        App::new()
-            .app_data(app_data.clone())
-            .route("/travelplan", web::post().to(create_travel_plan))
-    ).await;
-    
+    let repos = test_repositories();
+    let app = init_app(repos.clone()).await;
+
     // Create a test user and get a token
-    let (user_id, token) = create_test_user_and_token(&app, &conn).await;
-    
-    // Create test travel plan data
-    let plan_data = NewTravelPlan {
-        name: "Test Travel Plan".to_string(),
-        description: Some("A test travel plan".to_string()),
-        start_location: "New York".to_string(),
-        end_location: "Los Angeles".to_string(),
-        start_date: Some(Utc::now()),
-        end_date: Some(Utc::now()),
-    };
-    
+    let user = create_user(&repos, "testuser");
+
     // Send create travel plan request
     let req = test::TestRequest::post()
-        .uri("/travelplan")
-        .insert_header((header::AUTHORIZATION, format!("Bearer {}", token)))
-        .set_json(&plan_data)
+        .uri("/api/travelplan")
+        .insert_header(bearer(&user))
+        .set_json(plan_body("Test Travel Plan"))
         .to_request();
-    
+
     let resp = test::call_service(&app, req).await;
-    
-    // Assert response is successful
-    assert!(resp.status().is_success());
-    
+
+    // Assert response is created
+    assert_eq!(resp.status().as_u16(), 201);
+
     // Parse response body
-    let body = test::read_body(resp).await;
-    let response: TravelPlanDto = serde_json::from_slice(&body).unwrap();
-    
+    let response: TravelPlanDto = test::read_body_json(resp).await;
+
     // Assert response contains expected fields
-    assert_eq!(response.travel_plan.name, plan_data.name);
-    assert_eq!(response.travel_plan.description, plan_data.description);
-    assert_eq!(response.travel_plan.start_location, plan_data.start_location);
-    assert_eq!(response.travel_plan.end_location, plan_data.end_location);
-    assert_eq!(response.travel_plan.user_id, user_id);
-    assert_eq!(response.has_routes_generated, false); // New travel plan should have no routes
+    assert_eq!(response.travel_plan.name, "Test Travel Plan");
+    assert_eq!(response.travel_plan.description.as_deref(), Some("A test travel plan"));
+    assert_eq!(response.travel_plan.start_location, "New York");
+    assert_eq!(response.travel_plan.end_location, "Los Angeles");
+    assert_eq!(response.travel_plan.user_id, user.id);
+    assert!(!response.has_routes_generated); // New travel plan should have no routes
 }
 
 #[actix_web::test]
 async fn test_get_travel_plans() {
-    // Set up in-memory database for testing
-    let conn = Connection::open_in_memory().unwrap();
-    schema::initialize_database(&conn).unwrap();
-    let app_data = web::Data::new(conn.clone());
-    
-    // Create test app
-    let app = test::init_service(
-        App::new()
-            .app_data(app_data.clone())
-            .route("/travelplan", web::post().to(create_travel_plan))
-            .route("/travelplan", web::get().to(get_travel_plans))
-    ).await;
-    
-    // Create a test user and get a token
-    let (user_id, token) = create_test_user_and_token(&app, &conn).await;
-    
+    let repos = test_repositories();
+    let app = init_app(repos.clone()).await;
+
+    let user = create_user(&repos, "testuser");
+    let other = create_user(&repos, "otheruser");
+
     // Create multiple test travel plans
     for i in 1..=3 {
-        let plan_data = NewTravelPlan {
-            name: format!("Test Travel Plan {}", i),
-            description: Some(format!("A test travel plan {}", i)),
-            start_location: "New York".to_string(),
-            end_location: "Los Angeles".to_string(),
-            start_date: Some(Utc::now()),
-            end_date: Some(Utc::now()),
-        };
-        
-        let req = test::TestRequest::post()
-            .uri("/travelplan")
-            .insert_header((header::AUTHORIZATION, format!("Bearer {}", token)))
-            .set_json(&plan_data)
-            .to_request();
-        
-        let _ = test::call_service(&app, req).await;
+        create_plan(&app, &user, &format!("Test Travel Plan {}", i)).await;
     }
-    
+    create_plan(&app, &other, "Someone else's plan").await;
+
     // Send get travel plans request
     let req = test::TestRequest::get()
-        .uri("/travelplan")
-        .insert_header((header::AUTHORIZATION, format!("Bearer {}", token)))
+        .uri("/api/travelplan")
+        .insert_header(bearer(&user))
         .to_request();
-    
+
     let resp = test::call_service(&app, req).await;
-    
+
     // Assert response is successful
     assert!(resp.status().is_success());
-    
+
     // Parse response body
-    let body = test::read_body(resp).await;
-    let response: Vec<TravelPlanDto> = serde_json::from_slice(&body).unwrap();
-    
-    // Assert response contains expected number of travel plans
+    let response: Vec<TravelPlanDto> = test::read_body_json(resp).await;
+
+    // Assert response contains only this user's travel plans
     assert_eq!(response.len(), 3);
-    
-    // Assert all travel plans belong to the test user
     for plan_dto in response {
-        assert_eq!(plan_dto.travel_plan.user_id, user_id);
+        assert_eq!(plan_dto.travel_plan.user_id, user.id);
     }
 }
 
 #[actix_web::test]
 async fn test_get_travel_plan_by_id() {
-    // Set up in-memory database for testing
-    let conn = Connection::open_in_memory().unwrap();
-    schema::initialize_database(&conn).unwrap();
-    let app_data = web::Data::new(conn.clone());
-    
-    // Create test app
-    let app = test::init_service(
-        App::new()
-            .app_data(app_data.clone())
-            .route("/travelplan", web::post().to(create_travel_plan))
-            .route("/travelplan/{id}", web::get().to(get_travel_plan_by_id))
-    ).await;
-    
-    // Create a test user and get a token
-    let (user_id, token) = create_test_user_and_token(&app, &conn).await;
-    
-    // Create a test travel plan
-    let plan_data = NewTravelPlan {
-        name: "Test Travel Plan".to_string(),
-        description: Some("A test travel plan".to_string()),
-        start_location: "New York".to_string(),
-        end_location: "Los Angeles".to_string(),
-        start_date: Some(Utc::now()),
-        end_date: Some(Utc::now()),
-    };
-    
-    let req = test::TestRequest::post()
-        .uri("/travelplan")
-        .insert_header((header::AUTHORIZATION, format!("Bearer {}", token)))
-        .set_json(&plan_data)
-        .to_request();
-    
-    let resp = test::call_service(&app, req).await;
-    
-    // Parse response body to get the created travel plan
-    let body = test::read_body(resp).await;
-    let created_plan: TravelPlanDto = serde_json::from_slice(&body).unwrap();
-    
+    let repos = test_repositories();
+    let app = init_app(repos.clone()).await;
+
+    let user = create_user(&repos, "testuser");
+    let created_plan = create_plan(&app, &user, "Test Travel Plan").await;
+
     // Send get travel plan by ID request
     let req = test::TestRequest::get()
-        .uri(&format!("/travelplan/{}", created_plan.travel_plan.id))
-        .insert_header((header::AUTHORIZATION, format!("Bearer {}", token)))
+        .uri(&format!("/api/travelplan/{}", created_plan.travel_plan.id))
+        .insert_header(bearer(&user))
         .to_request();
-    
+
     let resp = test::call_service(&app, req).await;
-    
+
     // Assert response is successful
     assert!(resp.status().is_success());
-    
+
     // Parse response body
-    let body = test::read_body(resp).await;
-    let response: TravelPlanDto = serde_json::from_slice(&body).unwrap();
-    
+    let response: TravelPlanDto = test::read_body_json(resp).await;
+
     // Assert response contains expected fields
     assert_eq!(response.travel_plan.id, created_plan.travel_plan.id);
-    assert_eq!(response.travel_plan.name, plan_data.name);
-    assert_eq!(response.travel_plan.description, plan_data.description);
-    assert_eq!(response.travel_plan.start_location, plan_data.start_location);
-    assert_eq!(response.travel_plan.end_location, plan_data.end_location);
-    assert_eq!(response.travel_plan.user_id, user_id);
-    
+    assert_eq!(response.travel_plan.name, "Test Travel Plan");
+    assert_eq!(response.travel_plan.start_location, "New York");
+    assert_eq!(response.travel_plan.end_location, "Los Angeles");
+    assert_eq!(response.travel_plan.user_id, user.id);
+
     // Try to get a non-existent travel plan
     let req = test::TestRequest::get()
-        .uri("/travelplan/nonexistent-id")
-        .insert_header((header::AUTHORIZATION, format!("Bearer {}", token)))
+        .uri("/api/travelplan/nonexistent-id")
+        .insert_header(bearer(&user))
         .to_request();
-    
+
     let resp = test::call_service(&app, req).await;
-    
+
     // Assert response is not found
     assert_eq!(resp.status().as_u16(), 404);
 }
 
 #[actix_web::test]
 async fn test_update_travel_plan() {
-    // Set up in-memory database for testing
-    let conn = Connection::open_in_memory().unwrap();
-    schema::initialize_database(&conn).unwrap();
-    let app_data = web::Data::new(conn.clone());
-    
-    // Create test app
-    let app = test::init_service(
-        App::new()
-            .app_data(app_data.clone())
-            .route("/travelplan", web::post().to(create_travel_plan))
-            .route("/travelplan/{id}", web::put().to(update_travel_plan))
-    ).await;
-    
-    // Create a test user and get a token
-    let (user_id, token) = create_test_user_and_token(&app, &conn).await;
-    
-    // Create a test travel plan
-    let plan_data = NewTravelPlan {
-        user_id: user_id.clone(),
-        name: "Test Travel Plan".to_string(),
-        description: Some("A test travel plan".to_string()),
-        start_location: "New York".to_string(),
-        end_location: "Los Angeles".to_string(),
-        start_date: Some(Utc::now()),
-        end_date: Some(Utc::now()),
-    };
-    
-    let req = test::TestRequest::post()
-        .uri("/travelplan")
-        .insert_header((header::AUTHORIZATION, format!("Bearer {}", token)))
-        .set_json(&plan_data)
-        .to_request();
-    
-    let resp = test::call_service(&app, req).await;
-    
-    // Parse response body to get the created travel plan
-    let body = test::read_body(resp).await;
-    let created_plan: TravelPlanDto = serde_json::from_slice(&body).unwrap();
-    
-    // Create update data
-    let update_data = UpdateTravelPlan {
-        name: Some("Updated Travel Plan".to_string()),
-        description: Some("An updated test travel plan".to_string()),
-        start_location: Some("Boston".to_string()),
-        end_location: Some("San Francisco".to_string()),
-        start_date: None,
-        end_date: None,
-    };
-    
+    let repos = test_repositories();
+    let app = init_app(repos.clone()).await;
+
+    let user = create_user(&repos, "testuser");
+    let created_plan = create_plan(&app, &user, "Test Travel Plan").await;
+
     // Send update travel plan request
     let req = test::TestRequest::put()
-        .uri(&format!("/travelplan/{}", created_plan.travel_plan.id))
-        .insert_header((header::AUTHORIZATION, format!("Bearer {}", token)))
-        .set_json(&update_data)
+        .uri(&format!("/api/travelplan/{}", created_plan.travel_plan.id))
+        .insert_header(bearer(&user))
+        .set_json(json!({
+            "name": "Updated Travel Plan",
+            "description": "An updated test travel plan",
+            "startLocation": "Boston",
+            "endLocation": "San Francisco"
+        }))
         .to_request();
-    
+
     let resp = test::call_service(&app, req).await;
-    
+
     // Assert response is successful
     assert!(resp.status().is_success());
-    
+
     // Parse response body
-    let body = test::read_body(resp).await;
-    let response: TravelPlanDto = serde_json::from_slice(&body).unwrap();
-    
-    // Assert response contains updated fields
+    let response: TravelPlanDto = test::read_body_json(resp).await;
+
+    // Assert response contains updated fields and keeps the rest
     assert_eq!(response.travel_plan.id, created_plan.travel_plan.id);
-    assert_eq!(response.travel_plan.name, update_data.name.unwrap());
-    assert_eq!(response.travel_plan.description, update_data.description);
-    assert_eq!(response.travel_plan.start_location, update_data.start_location.unwrap());
-    assert_eq!(response.travel_plan.end_location, update_data.end_location.unwrap());
-    assert_eq!(response.travel_plan.user_id, user_id);
+    assert_eq!(response.travel_plan.name, "Updated Travel Plan");
+    assert_eq!(response.travel_plan.description.as_deref(), Some("An updated test travel plan"));
+    assert_eq!(response.travel_plan.start_location, "Boston");
+    assert_eq!(response.travel_plan.end_location, "San Francisco");
+    assert_eq!(response.travel_plan.start_date, created_plan.travel_plan.start_date);
+    assert_eq!(response.travel_plan.user_id, user.id);
 }
 
 #[actix_web::test]
 async fn test_delete_travel_plan() {
-    // Set up in-memory database for testing
-    let conn = Connection::open_in_memory().unwrap();
-    schema::initialize_database(&conn).unwrap();
-    let app_data = web::Data::new(conn.clone());
-    
-    // Create test app
-    let app = test::init_service(
-        App::new()
-            .app_data(app_data.clone())
-            .route("/travelplan", web::post().to(create_travel_plan))
-            .route("/travelplan/{id}", web::get().to(get_travel_plan_by_id))
-            .route("/travelplan/{id}", web::delete().to(delete_travel_plan))
-    ).await;
-    
-    // Create a test user and get a token
-    let (user_id, token) = create_test_user_and_token(&app, &conn).await;
-    
-    // Create a test travel plan
-    let plan_data = NewTravelPlan {
-        name: "Test Travel Plan".to_string(),
-        description: Some("A test travel plan".to_string()),
-        start_location: "New York".to_string(),
-        end_location: "Los Angeles".to_string(),
-        start_date: Some(Utc::now()),
-        end_date: Some(Utc::now()),
-    };
-    
-    let req = test::TestRequest::post()
-        .uri("/travelplan")
-        .insert_header((header::AUTHORIZATION, format!("Bearer {}", token)))
-        .set_json(&plan_data)
-        .to_request();
-    
-    let resp = test::call_service(&app, req).await;
-    
-    // Parse response body to get the created travel plan
-    let body = test::read_body(resp).await;
-    let created_plan: TravelPlanDto = serde_json::from_slice(&body).unwrap();
-    
+    let repos = test_repositories();
+    let app = init_app(repos.clone()).await;
+
+    let user = create_user(&repos, "testuser");
+    let created_plan = create_plan(&app, &user, "Test Travel Plan").await;
+
     // Send delete travel plan request
     let req = test::TestRequest::delete()
-        .uri(&format!("/travelplan/{}", created_plan.travel_plan.id))
-        .insert_header((header::AUTHORIZATION, format!("Bearer {}", token)))
+        .uri(&format!("/api/travelplan/{}", created_plan.travel_plan.id))
+        .insert_header(bearer(&user))
         .to_request();
-    
+
     let resp = test::call_service(&app, req).await;
-    
+
     // Assert response is successful (no content)
     assert_eq!(resp.status().as_u16(), 204);
-    
+
     // Try to get the deleted travel plan
     let req = test::TestRequest::get()
-        .uri(&format!("/travelplan/{}", created_plan.travel_plan.id))
-        .insert_header((header::AUTHORIZATION, format!("Bearer {}", token)))
+        .uri(&format!("/api/travelplan/{}", created_plan.travel_plan.id))
+        .insert_header(bearer(&user))
         .to_request();
-    
+
     let resp = test::call_service(&app, req).await;
-    
+
     // Assert response is not found
     assert_eq!(resp.status().as_u16(), 404);
-}
\ No newline at end of file
+}
+
+#[actix_web::test]
+async fn test_other_users_plan_is_forbidden() {
+    let repos = test_repositories();
+    let app = init_app(repos.clone()).await;
+
+    let owner = create_user(&repos, "owner");
+    let intruder = create_user(&repos, "intruder");
+    let plan = create_plan(&app, &owner, "Private plan").await;
+    let uri = format!("/api/travelplan/{}", plan.travel_plan.id);
+
+    let req = test::TestRequest::get()
+        .uri(&uri)
+        .insert_header(bearer(&intruder))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status().as_u16(), 403);
+
+    let req = test::TestRequest::put()
+        .uri(&uri)
+        .insert_header(bearer(&intruder))
+        .set_json(json!({ "name": "Hijacked" }))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status().as_u16(), 403);
+
+    let req = test::TestRequest::delete()
+        .uri(&uri)
+        .insert_header(bearer(&intruder))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status().as_u16(), 403);
+
+    // The owner still sees the plan unchanged
+    let req = test::TestRequest::get()
+        .uri(&uri)
+        .insert_header(bearer(&owner))
+        .to_request();
+    let response: TravelPlanDto = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(response.travel_plan.name, "Private plan");
+}
+
+#[actix_web::test]
+async fn test_missing_plan_is_not_found() {
+    let repos = test_repositories();
+    let app = init_app(repos.clone()).await;
+
+    let user = create_user(&repos, "testuser");
+
+    let req = test::TestRequest::put()
+        .uri("/api/travelplan/nonexistent-id")
+        .insert_header(bearer(&user))
+        .set_json(json!({ "name": "Nothing" }))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status().as_u16(), 404);
+
+    let req = test::TestRequest::delete()
+        .uri("/api/travelplan/nonexistent-id")
+        .insert_header(bearer(&user))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status().as_u16(), 404);
+}
+
+#[actix_web::test]
+async fn test_support_can_only_read_other_plans() {
+    let repos = test_repositories();
+    let app = init_app(repos.clone()).await;
+
+    let owner = create_user(&repos, "owner");
+    let stranger = create_user(&repos, "stranger");
+    let support = create_user_with_role(&repos, "helpdesk", Role::Support);
+    let plan = create_plan(&app, &owner, "Private trip").await;
+    let uri = format!("/api/travelplan/{}", plan.travel_plan.id);
+
+    // Regular users still cannot see someone else's plan
+    let req = test::TestRequest::get()
+        .uri(&uri)
+        .insert_header(bearer(&stranger))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status().as_u16(), 403);
+
+    // Support staff can read it, and the read is audited
+    let req = test::TestRequest::get()
+        .uri(&uri)
+        .insert_header(bearer(&support))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status().as_u16(), 200);
+    let viewed: TravelPlanDto = test::read_body_json(resp).await;
+    assert_eq!(viewed.travel_plan.name, "Private trip");
+
+    let entries = off_runtime(|| repos.find_audit_entries(10, 0)).unwrap();
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].action, "plan.view");
+    assert_eq!(entries[0].actor_username, "helpdesk");
+
+    // But cannot change or delete it
+    let req = test::TestRequest::put()
+        .uri(&uri)
+        .insert_header(bearer(&support))
+        .set_json(json!({ "name": "Hijacked" }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status().as_u16(), 403);
+
+    let req = test::TestRequest::delete()
+        .uri(&uri)
+        .insert_header(bearer(&support))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status().as_u16(), 403);
+}